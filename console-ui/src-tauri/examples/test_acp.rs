@@ -430,6 +430,8 @@ async fn spawn_loopback(
         approval_policy: None,
         sandbox_mode: None,
         app_server_args: Vec::new(),
+        log_dir: None,
+        log_verbose: false,
     };
 
     let server_handle = tokio::spawn(async move {
@@ -8,7 +8,10 @@ pub fn run() {
         )))
         .manage(crate::state::ConsoleRestartLock(std::sync::Mutex::new(())))
         .manage(crate::state::ConsoleStreamState(std::sync::Mutex::new(
-            false,
+            None,
+        )))
+        .manage(crate::state::LogStreamState(std::sync::Mutex::new(
+            std::collections::HashMap::new(),
         )))
         .manage(crate::state::TerminalSessions(std::sync::Mutex::new(
             std::collections::HashMap::new(),
@@ -34,24 +37,44 @@ pub fn run() {
             crate::commands::config::parse_proxy_config_toml,
             crate::commands::config::parse_broker_config_toml,
             crate::commands::console::restart_console,
+            crate::commands::console::proxy_reload_policy,
             crate::commands::console::validate_startup_config,
             crate::commands::console::log_ui_event,
             crate::commands::console::set_app_language,
+            crate::commands::console::proxy_fetch_summary,
             crate::commands::console::proxy_fetch_targets,
             crate::commands::console::proxy_fetch_snapshot,
             crate::commands::console::proxy_approve,
+            crate::commands::console::proxy_approve_edited,
+            crate::commands::console::proxy_list_forwards,
+            crate::commands::console::proxy_ensure_forward,
+            crate::commands::console::proxy_release_forward,
             crate::commands::console::proxy_deny,
             crate::commands::console::proxy_cancel,
             crate::commands::console::proxy_force_cancel,
+            crate::commands::console::proxy_kill_detached,
+            crate::commands::console::proxy_set_maintenance,
+            crate::commands::console::proxy_retry_target,
+            crate::commands::console::proxy_list_templates,
+            crate::commands::console::proxy_run_template,
             crate::commands::console::proxy_list_target_dirs,
             crate::commands::console::proxy_start_upload,
             crate::commands::console::proxy_upload_status,
+            crate::commands::console::proxy_export_history,
+            crate::commands::console::proxy_fetch_history,
             crate::commands::console::read_console_log,
             crate::commands::console::read_app_log,
+            crate::commands::console::start_log_stream,
+            crate::commands::console::stop_log_stream,
             crate::commands::ai::ai_risk_assess,
+            crate::commands::ai::ai_risk_cache_stats,
+            crate::commands::ai::ai_risk_cache_clear,
             crate::commands::console::start_console_stream,
+            crate::commands::terminal::terminal_list,
             crate::commands::terminal::terminal_open,
+            crate::commands::terminal::terminal_attach,
             crate::commands::terminal::terminal_input,
+            crate::commands::terminal::terminal_inject,
             crate::commands::terminal::terminal_resize,
             crate::commands::terminal::terminal_close,
             crate::commands::chat::read_text_file,
@@ -72,6 +95,7 @@ pub fn run() {
             crate::commands::openai::openai_send,
             crate::commands::mcp::mcp_set_config,
             crate::commands::mcp::mcp_list_tools,
+            crate::commands::mcp::mcp_list_resources,
             crate::commands::mcp::mcp_call_tool,
             crate::commands::opener::open_external
         ])
@@ -5,9 +5,14 @@ use std::sync::Mutex;
 use tauri::Manager;
 
 use crate::clients::McpClientState;
+use crate::services::ai_risk_cache::{AiRiskCache, CACHE_FILE_NAME as AI_RISK_CACHE_FILE_NAME};
 use crate::services::logging::append_log_line;
-use crate::services::profiles::{octovalve_dir, prepare_profiles};
-use crate::state::{AppLogState, ProfilesState, ProxyConfigState};
+use crate::services::profiles::{
+    current_profile_entry, octovalve_dir, prepare_profiles, profile_console_endpoint,
+};
+use crate::state::{
+    AiRiskCacheState, AppLogState, ConsoleEndpointState, ProfilesState, ProxyConfigState,
+};
 
 const RUNTIME_AGENTS_TEMPLATE: &str = include_str!("../../assets/runtime/AGENTS.md");
 
@@ -67,7 +72,12 @@ pub fn init(app: &mut tauri::App) -> Result<(), String> {
     app.manage(AppLogState {
         app_log: app_log.clone(),
     });
-    app.state::<McpClientState>().set_log_path(app_log.clone());
+    app.manage(AiRiskCacheState(AiRiskCache::new(
+        config_dir.join(AI_RISK_CACHE_FILE_NAME),
+    )));
+    let mcp_state = app.state::<McpClientState>();
+    mcp_state.set_log_path(app_log.clone());
+    mcp_state.set_app_handle(app_handle.clone());
     let (profiles, proxy_status) = prepare_profiles(&app_handle, &app_log)?;
     if let Err(err) = ensure_runtime_agents_file(&app_handle, &app_log) {
         let _ = append_log_line(
@@ -75,6 +85,10 @@ pub fn init(app: &mut tauri::App) -> Result<(), String> {
             &format!("[setup] failed to write runtime AGENTS.md: {}", err),
         );
     }
+    let current = current_profile_entry(&profiles)?;
+    app.manage(ConsoleEndpointState(Mutex::new(profile_console_endpoint(
+        &current,
+    ))));
     app.manage(ProfilesState(Mutex::new(profiles)));
     app.manage(ProxyConfigState(Mutex::new(proxy_status.clone())));
     if proxy_status.present {
@@ -9,8 +9,9 @@ use std::time::Duration;
 
 use acp_codex::{set_log_sink, CliConfig};
 use serde_json::{json, Value};
+use system_utils::lines::{LineReader, MAX_JSON_LINE_BYTES};
 use tauri::{AppHandle, Emitter};
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::io::{AsyncWriteExt, BufReader};
 use tokio::sync::{oneshot, Mutex};
 use tokio::task::AbortHandle;
 use tokio::time::timeout;
@@ -127,14 +128,17 @@ impl AcpClient {
         log_path: PathBuf,
     ) -> Result<(), AcpError> {
         log_line(&log_path, "[ACP reader] starting read loop");
-        let mut reader = BufReader::new(reader);
-        let mut buffer = String::new();
+        let mut reader = LineReader::new(BufReader::new(reader), MAX_JSON_LINE_BYTES);
         let mut exit_reason: Option<String> = None;
 
         loop {
-            buffer.clear();
-            let bytes = match reader.read_line(&mut buffer).await {
-                Ok(size) => size,
+            let line = match reader.next_line().await {
+                Ok(Some(Ok(line))) => line,
+                Ok(Some(Err(e))) => {
+                    log_line(&log_path, &format!("[ACP reader] dropped line: {}", e));
+                    continue;
+                }
+                Ok(None) => break,
                 Err(e) => {
                     let reason = format!("read error: {}", e);
                     log_line(&log_path, &format!("[ACP reader] {}", reason));
@@ -143,11 +147,6 @@ impl AcpClient {
                 }
             };
 
-            if bytes == 0 {
-                break;
-            }
-
-            let line = buffer.trim_end_matches(['\n', '\r']);
             if line.is_empty() {
                 continue;
             }
@@ -156,11 +155,11 @@ impl AcpClient {
                 &log_path,
                 &format!(
                     "[ACP reader] received line: {}",
-                    Self::truncate_log_line(line, 200)
+                    Self::truncate_log_line(&line, 200)
                 ),
             );
 
-            let message: AcpMessage = match serde_json::from_str(line) {
+            let message: AcpMessage = match serde_json::from_str(&line) {
                 Ok(m) => m,
                 Err(e) => {
                     log_line(&log_path, &format!("[ACP reader] parse error: {}", e));
@@ -1,16 +1,20 @@
 use rmcp::{
     model::{
-        CallToolRequestParam, CallToolResult, ClientCapabilities, ClientInfo, Implementation,
-        ListToolsResult, ProtocolVersion, Tool,
+        CallToolRequestParam, CallToolResult, ClientCapabilities, ClientInfo, ErrorCode,
+        Implementation, ProtocolVersion, Resource, Tool,
     },
-    service::{RoleClient, RunningService, ServiceExt},
+    service::{NotificationContext, RoleClient, RunningService, ServiceExt},
     transport::TokioChildProcess,
-    ServiceError,
+    ClientHandler, ServiceError,
 };
-use serde_json::Value;
+use serde::Serialize;
+use serde_json::{json, Value};
 use std::collections::HashMap;
+use std::future::Future;
 use std::path::PathBuf;
-use std::sync::{Arc, Mutex as StdMutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex as StdMutex, PoisonError};
+use tauri::{AppHandle, Emitter};
 use tokio::process::Command as TokioCommand;
 use tokio::sync::Mutex;
 
@@ -55,9 +59,19 @@ struct McpClientRegistry {
     clients: HashMap<String, Arc<McpClient>>,
 }
 
+pub const MCP_TOOLS_CHANGED_EVENT: &str = "mcp_tools_changed";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum McpListKind {
+    Tools,
+    Resources,
+}
+
 pub struct McpClientState {
     registry: Mutex<McpClientRegistry>,
     log_path: StdMutex<Option<PathBuf>>,
+    app: StdMutex<Option<AppHandle>>,
 }
 
 impl Default for McpClientState {
@@ -65,6 +79,7 @@ impl Default for McpClientState {
         Self {
             registry: Mutex::new(McpClientRegistry::default()),
             log_path: StdMutex::new(None),
+            app: StdMutex::new(None),
         }
     }
 }
@@ -75,6 +90,12 @@ impl McpClientState {
         *guard = Some(path);
     }
 
+    /// 收到 `list_changed` 通知时用它向前端发 [`MCP_TOOLS_CHANGED_EVENT`]。
+    pub fn set_app_handle(&self, app: AppHandle) {
+        let mut guard = self.app.lock().unwrap();
+        *guard = Some(app);
+    }
+
     fn log_line(&self, message: &str) {
         let log_path = self.log_path.lock().unwrap().clone();
         if let Some(path) = log_path.as_ref() {
@@ -119,32 +140,55 @@ impl McpClientState {
         Ok(())
     }
 
-    pub async fn list_tools(&self) -> Result<Vec<(String, Tool)>, String> {
-        let server_names = {
-            let guard = self.registry.lock().await;
-            guard.servers.keys().cloned().collect::<Vec<_>>()
-        };
+    /// 指定 server 时失败直接返回错误；未指定时遍历全部 server，失败的只记日志并跳过。
+    pub async fn list_tools(&self, server: Option<&str>) -> Result<Vec<(String, Tool)>, String> {
         let mut tools = Vec::new();
-        for server in server_names {
-            let client = match self.get_or_start_client(&server).await {
-                Ok(client) => client,
-                Err(err) => {
-                    self.log_line(&format!("[mcp] start client failed: {server} err={err}"));
-                    continue;
-                }
+        for name in self.server_names(server).await {
+            let result = match self.get_or_start_client(&name).await {
+                Ok(client) => client.list_tools().await,
+                Err(err) => Err(err),
             };
-            let result = match client.list_tools().await {
-                Ok(result) => result,
+            match result {
+                Ok(items) => tools.extend(items.into_iter().map(|tool| (name.clone(), tool))),
+                Err(err) if server.is_some() => return Err(err),
+                Err(err) => self.log_line(&format!("[mcp] list_tools failed: {name} err={err}")),
+            }
+        }
+        Ok(tools)
+    }
+
+    /// 与 [`Self::list_tools`] 相同；不支持 resources 的 server 返回空列表。
+    pub async fn list_resources(
+        &self,
+        server: Option<&str>,
+    ) -> Result<Vec<(String, Resource)>, String> {
+        let mut resources = Vec::new();
+        for name in self.server_names(server).await {
+            let result = match self.get_or_start_client(&name).await {
+                Ok(client) => client.list_resources().await,
+                Err(err) => Err(err),
+            };
+            match result {
+                Ok(items) => {
+                    resources.extend(items.into_iter().map(|resource| (name.clone(), resource)))
+                }
+                Err(err) if server.is_some() => return Err(err),
                 Err(err) => {
-                    self.log_line(&format!("[mcp] list_tools failed: {server} err={err}"));
-                    continue;
+                    self.log_line(&format!("[mcp] list_resources failed: {name} err={err}"))
                 }
-            };
-            for tool in result.tools {
-                tools.push((server.clone(), tool));
             }
         }
-        Ok(tools)
+        Ok(resources)
+    }
+
+    async fn server_names(&self, server: Option<&str>) -> Vec<String> {
+        match server {
+            Some(server) => vec![server.to_string()],
+            None => {
+                let guard = self.registry.lock().await;
+                guard.servers.keys().cloned().collect()
+            }
+        }
     }
 
     pub async fn call_tool(
@@ -182,20 +226,113 @@ impl McpClientState {
             old.shutdown().await;
         }
 
-        let client = Arc::new(McpClient::start_with_spec(&spec).await?);
+        let notify = self.list_change_notifier(server);
+        let client = Arc::new(McpClient::start_with_spec(&spec, notify).await?);
         let mut guard = self.registry.lock().await;
-        guard.clients.insert(server.to_string(), Arc::clone(&client));
+        guard
+            .clients
+            .insert(server.to_string(), Arc::clone(&client));
         Ok(client)
     }
+
+    fn list_change_notifier(&self, server: &str) -> ListChangeNotifier {
+        let app = self.app.lock().unwrap().clone();
+        let log_path = self.log_path.lock().unwrap().clone();
+        let server = server.to_string();
+        Arc::new(move |kind| {
+            if let Some(path) = log_path.as_ref() {
+                let _ = append_log_line(path, &format!("[mcp] {kind:?} list changed: {server}"));
+            }
+            if let Some(app) = app.as_ref() {
+                let _ = app.emit(
+                    MCP_TOOLS_CHANGED_EVENT,
+                    json!({ "server": server, "kind": kind }),
+                );
+            }
+        })
+    }
+}
+
+type ListChangeNotifier = Arc<dyn Fn(McpListKind) + Send + Sync>;
+
+// 列表缓存随连接存在，重连即是新的缓存；`generation` 防止通知前发出的请求把旧结果写回。
+#[derive(Default)]
+struct McpListCache {
+    generation: AtomicU64,
+    tools: StdMutex<Option<Vec<Tool>>>,
+    resources: StdMutex<Option<Vec<Resource>>>,
+}
+
+impl McpListCache {
+    fn invalidate(&self, kind: McpListKind) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        match kind {
+            McpListKind::Tools => *lock(&self.tools) = None,
+            McpListKind::Resources => *lock(&self.resources) = None,
+        }
+    }
+
+    fn store<T>(&self, slot: &StdMutex<Option<Vec<T>>>, generation: u64, items: &[T])
+    where
+        T: Clone,
+    {
+        let mut guard = lock(slot);
+        if self.generation.load(Ordering::SeqCst) == generation {
+            *guard = Some(items.to_vec());
+        }
+    }
+}
+
+fn lock<T>(mutex: &StdMutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+struct McpClientHandler {
+    info: ClientInfo,
+    cache: Arc<McpListCache>,
+    notify: ListChangeNotifier,
+}
+
+impl McpClientHandler {
+    fn list_changed(&self, kind: McpListKind) {
+        self.cache.invalidate(kind);
+        (self.notify)(kind);
+    }
+}
+
+impl ClientHandler for McpClientHandler {
+    fn on_tool_list_changed(
+        &self,
+        _context: NotificationContext<RoleClient>,
+    ) -> impl Future<Output = ()> + Send + '_ {
+        self.list_changed(McpListKind::Tools);
+        std::future::ready(())
+    }
+
+    fn on_resource_list_changed(
+        &self,
+        _context: NotificationContext<RoleClient>,
+    ) -> impl Future<Output = ()> + Send + '_ {
+        self.list_changed(McpListKind::Resources);
+        std::future::ready(())
+    }
+
+    fn get_info(&self) -> ClientInfo {
+        self.info.clone()
+    }
 }
 
 pub struct McpClient {
-    service: RunningService<RoleClient, ClientInfo>,
+    service: RunningService<RoleClient, McpClientHandler>,
     spec_signature: String,
+    cache: Arc<McpListCache>,
 }
 
 impl McpClient {
-    pub async fn start_with_spec(spec: &McpServerSpec) -> Result<Self, String> {
+    async fn start_with_spec(
+        spec: &McpServerSpec,
+        notify: ListChangeNotifier,
+    ) -> Result<Self, String> {
         let client_info = ClientInfo {
             protocol_version: ProtocolVersion::V_2025_06_18,
             capabilities: ClientCapabilities::default(),
@@ -216,13 +353,20 @@ impl McpClient {
             command.current_dir(cwd);
         }
         let transport = TokioChildProcess::new(command).map_err(|err| err.to_string())?;
-        let service = client_info
+        let cache = Arc::new(McpListCache::default());
+        let handler = McpClientHandler {
+            info: client_info,
+            cache: Arc::clone(&cache),
+            notify,
+        };
+        let service = handler
             .serve(transport)
             .await
             .map_err(|err| err.to_string())?;
         Ok(Self {
             service,
             spec_signature: spec.signature(),
+            cache,
         })
     }
 
@@ -249,11 +393,43 @@ impl McpClient {
             .map_err(format_service_error)
     }
 
-    pub async fn list_tools(&self) -> Result<ListToolsResult, String> {
-        self.service
-            .list_tools(None)
+    pub async fn list_tools(&self) -> Result<Vec<Tool>, String> {
+        if let Some(tools) = lock(&self.cache.tools).clone() {
+            return Ok(tools);
+        }
+        let generation = self.cache.generation.load(Ordering::SeqCst);
+        let tools = self
+            .service
+            .list_all_tools()
             .await
-            .map_err(format_service_error)
+            .map_err(format_service_error)?;
+        self.cache.store(&self.cache.tools, generation, &tools);
+        Ok(tools)
+    }
+
+    pub async fn list_resources(&self) -> Result<Vec<Resource>, String> {
+        if let Some(resources) = lock(&self.cache.resources).clone() {
+            return Ok(resources);
+        }
+        let advertised = self
+            .service
+            .peer_info()
+            .is_some_and(|info| info.capabilities.resources.is_some());
+        let generation = self.cache.generation.load(Ordering::SeqCst);
+        let resources = if advertised {
+            match self.service.list_all_resources().await {
+                Ok(resources) => resources,
+                Err(ServiceError::McpError(data)) if data.code == ErrorCode::METHOD_NOT_FOUND => {
+                    Vec::new()
+                }
+                Err(err) => return Err(format_service_error(err)),
+            }
+        } else {
+            Vec::new()
+        };
+        self.cache
+            .store(&self.cache.resources, generation, &resources);
+        Ok(resources)
     }
 
     pub async fn shutdown(&self) {
@@ -270,3 +446,21 @@ fn format_service_error(err: ServiceError) -> String {
         _ => err.to_string(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drops_list_results_fetched_before_invalidation() {
+        let cache = McpListCache::default();
+        let slot = StdMutex::new(None);
+        let generation = cache.generation.load(Ordering::SeqCst);
+        cache.invalidate(McpListKind::Tools);
+        cache.store(&slot, generation, &[1]);
+        assert!(lock(&slot).is_none());
+
+        cache.store(&slot, cache.generation.load(Ordering::SeqCst), &[2]);
+        assert_eq!(*lock(&slot), Some(vec![2]));
+    }
+}
@@ -315,9 +315,15 @@ impl OpenAiClient {
             || data.contains("\"finish_reason\":\"stop\"")
             || data.contains("\"finish_reason\": \"stop\"")
         {
-            emit_complete_event(app_handle, tool_calls);
             self.maybe_store_assistant_message(full_content, tool_calls)
                 .await;
+            // Some providers end a tool-call turn with "stop" or a bare [DONE]
+            // instead of finish_reason "tool_calls".
+            if tool_calls.is_empty() {
+                emit_complete_event(app_handle);
+            } else {
+                emit_tool_calls_event(app_handle, tool_calls, "tool_calls");
+            }
             return Ok(true);
         }
 
@@ -364,22 +370,7 @@ impl OpenAiClient {
                         // Tool calls delta
                         if let Some(tc) = delta.get("tool_calls").and_then(|t| t.as_array()) {
                             for tc_delta in tc {
-                                let index =
-                                    tc_delta.get("index").and_then(|i| i.as_u64()).unwrap_or(0)
-                                        as usize;
-
-                                // Ensure tool_calls has enough elements
-                                while tool_calls.len() <= index {
-                                    tool_calls.push(ToolCall {
-                                        id: String::new(),
-                                        call_type: "function".to_string(),
-                                        function: FunctionCall {
-                                            name: String::new(),
-                                            arguments: String::new(),
-                                        },
-                                    });
-                                }
-
+                                let index = tool_call_slot(tool_calls, tc_delta);
                                 if let Some(id) = tc_delta.get("id").and_then(|i| i.as_str()) {
                                     tool_calls[index].id = id.to_string();
                                 }
@@ -445,15 +436,11 @@ fn emit_cancelled_event(app_handle: &AppHandle) {
     let _ = app_handle.emit("openai-stream", &event);
 }
 
-fn emit_complete_event(app_handle: &AppHandle, tool_calls: &[ToolCall]) {
+fn emit_complete_event(app_handle: &AppHandle) {
     let event = ChatStreamEvent {
         event_type: "complete".to_string(),
         content: None,
-        tool_calls: if tool_calls.is_empty() {
-            None
-        } else {
-            Some(tool_calls.to_vec())
-        },
+        tool_calls: None,
         finish_reason: Some("stop".to_string()),
         error: None,
     };
@@ -475,6 +462,36 @@ fn emit_tool_calls_event(app_handle: &AppHandle, tool_calls: &[ToolCall], reason
     let _ = app_handle.emit("openai-stream", &event);
 }
 
+// Returns the tool call a streamed delta belongs to, growing the list as needed.
+// Deltas without an index (sent by some OpenAI-compatible providers, one
+// complete call per delta) start a new call when they carry a new id.
+fn tool_call_slot(tool_calls: &mut Vec<ToolCall>, delta: &Value) -> usize {
+    let index = match delta.get("index").and_then(|i| i.as_u64()) {
+        Some(index) => index as usize,
+        None => {
+            let id = delta.get("id").and_then(|i| i.as_str()).unwrap_or("");
+            match tool_calls.last() {
+                Some(last) if !id.is_empty() && !last.id.is_empty() && last.id != id => {
+                    tool_calls.len()
+                }
+                Some(_) => tool_calls.len() - 1,
+                None => 0,
+            }
+        }
+    };
+    while tool_calls.len() <= index {
+        tool_calls.push(ToolCall {
+            id: String::new(),
+            call_type: "function".to_string(),
+            function: FunctionCall {
+                name: String::new(),
+                arguments: String::new(),
+            },
+        });
+    }
+    index
+}
+
 fn strip_sse_data_prefix(line: &str) -> Option<&str> {
     if let Some(data) = line.strip_prefix("data: ") {
         return Some(data);
@@ -492,3 +509,26 @@ fn build_http_client() -> Result<Client, reqwest::Error> {
 
 // Global client state
 pub struct OpenAiClientState(pub Mutex<Option<Arc<OpenAiClient>>>);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tool_call_slot_separates_calls_without_index() {
+        let mut tool_calls = Vec::new();
+        assert_eq!(tool_call_slot(&mut tool_calls, &json!({ "index": 1 })), 1);
+        assert_eq!(tool_calls.len(), 2);
+
+        let mut tool_calls = Vec::new();
+        assert_eq!(tool_call_slot(&mut tool_calls, &json!({ "id": "a" })), 0);
+        tool_calls[0].id = "a".to_string();
+        assert_eq!(
+            tool_call_slot(&mut tool_calls, &json!({ "function": {} })),
+            0
+        );
+        assert_eq!(tool_call_slot(&mut tool_calls, &json!({ "id": "a" })), 0);
+        assert_eq!(tool_call_slot(&mut tool_calls, &json!({ "id": "b" })), 1);
+        assert_eq!(tool_calls.len(), 2);
+    }
+}
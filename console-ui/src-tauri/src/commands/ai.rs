@@ -1,9 +1,24 @@
-use crate::services::ai_risk;
-use crate::types::AiRiskRequest;
+use tauri::State;
+
+use crate::services::ai_risk_cache;
+use crate::state::{AiRiskCacheState, AppLogState};
+use crate::types::{AiRiskCacheStats, AiRiskRequest};
 
 #[tauri::command]
 pub async fn ai_risk_assess(
     request: AiRiskRequest,
+    cache: State<'_, AiRiskCacheState>,
+    log_state: State<'_, AppLogState>,
 ) -> Result<crate::types::AiRiskResponse, String> {
-    ai_risk::ai_risk_assess(request).await
+    ai_risk_cache::assess_cached(&cache.0, request, &log_state.app_log).await
+}
+
+#[tauri::command]
+pub fn ai_risk_cache_stats(cache: State<AiRiskCacheState>) -> AiRiskCacheStats {
+    cache.0.stats()
+}
+
+#[tauri::command]
+pub fn ai_risk_cache_clear(cache: State<AiRiskCacheState>) -> Result<(), String> {
+    cache.0.clear()
 }
@@ -1,5 +1,8 @@
 use std::path::PathBuf;
 
+use protocol::config::{
+    validate_proxy_config, validate_whitelist_entries, ConfigDiagnostic, ProxyConfig,
+};
 use tauri::State;
 
 use crate::services::config::{read_config_file, write_config_file, DEFAULT_PROXY_EXAMPLE};
@@ -41,10 +44,36 @@ pub async fn write_proxy_config(
 
 #[tauri::command]
 pub fn parse_proxy_config_toml(content: String) -> Result<ProxyConfigEditor, String> {
-    toml::from_str::<ProxyConfigEditor>(&content).map_err(|err| err.to_string())
+    let mut editor =
+        toml::from_str::<ProxyConfigEditor>(&content).map_err(|err| err.to_string())?;
+    editor.diagnostics = validate_proxy_config(&ProxyConfig {
+        default_target: editor.default_target.clone(),
+        aliases: editor.aliases.clone(),
+        defaults: editor.defaults.clone(),
+        targets: editor.targets.clone(),
+        terminal: None,
+        health: None,
+        server: None,
+        templates: editor.templates.clone(),
+        schedules: editor.schedules.clone(),
+    });
+    Ok(editor)
 }
 
 #[tauri::command]
 pub fn parse_broker_config_toml(content: String) -> Result<BrokerConfigEditor, String> {
-    toml::from_str::<BrokerConfigEditor>(&content).map_err(|err| err.to_string())
+    let mut editor =
+        toml::from_str::<BrokerConfigEditor>(&content).map_err(|err| err.to_string())?;
+    let whitelist = &editor.whitelist;
+    let mut diagnostics = validate_whitelist_entries(&whitelist.allowed, &whitelist.denied);
+    for (command, pattern) in &whitelist.arg_rules {
+        if let Err(err) = regex::Regex::new(pattern) {
+            diagnostics.push(ConfigDiagnostic::error(
+                format!("whitelist.arg_rules.{command}"),
+                format!("invalid regex: {err}"),
+            ));
+        }
+    }
+    editor.diagnostics = diagnostics;
+    Ok(editor)
 }
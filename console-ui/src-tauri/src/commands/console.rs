@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::fs::OpenOptions;
 use std::io::{Read, Seek, SeekFrom};
 use std::path::{Path, PathBuf};
@@ -5,12 +6,18 @@ use std::path::{Path, PathBuf};
 use serde_json::{json, Value};
 use tauri::{AppHandle, Manager, State};
 
-use crate::services::console_http::{console_get, console_post, console_post_json};
+use crate::services::console_http::{
+    console_delete, console_download, console_get, console_post, console_post_json,
+};
 use crate::services::console_sidecar::restart_console_sidecar;
 use crate::services::console_ws::start_console_stream as start_console_stream_service;
+use crate::services::log_stream::{spawn_log_stream, LogFilter};
 use crate::services::logging::append_log_line;
 use crate::services::startup_check;
-use crate::state::{AppLanguageState, AppLogState, ProfilesState, ProxyConfigState};
+use crate::state::{
+    AppLanguageState, AppLogState, ConsoleEndpointState, LogStreamState, ProfilesState,
+    ProxyConfigState,
+};
 use crate::types::{LogChunk, StartupCheckResult};
 use urlencoding::encode;
 
@@ -85,6 +92,62 @@ pub async fn read_app_log(
         .map_err(|err| err.to_string())?
 }
 
+/// 后台跟踪 `console` / `app` 日志并推送 `log_line` 事件；同一来源再次调用会替换旧任务（例如换了 filter）。
+/// offset 传分块回填读到的位置，保证回填与推送之间不漏行。
+#[tauri::command]
+pub async fn start_log_stream(
+    which: String,
+    filter: Option<String>,
+    offset: Option<u64>,
+    app: AppHandle,
+    log_state: State<'_, AppLogState>,
+    streams: State<'_, LogStreamState>,
+) -> Result<(), String> {
+    let path = match which.as_str() {
+        "console" => console_log_path(&app)?,
+        "app" => log_state.app_log.clone(),
+        other => return Err(format!("unknown log {other:?}")),
+    };
+    let filter = LogFilter::parse(filter.as_deref())?;
+    let task = spawn_log_stream(app, which.clone(), path, offset, filter);
+    if let Some(previous) = streams.0.lock().unwrap().insert(which, task) {
+        previous.abort();
+    }
+    Ok(())
+}
+
+/// 未指定 which 时停止全部日志跟踪。
+#[tauri::command]
+pub async fn stop_log_stream(
+    which: Option<String>,
+    streams: State<'_, LogStreamState>,
+) -> Result<(), String> {
+    let mut streams = streams.0.lock().unwrap();
+    match which {
+        Some(which) => {
+            if let Some(task) = streams.remove(&which) {
+                task.abort();
+            }
+        }
+        None => {
+            for (_, task) in streams.drain() {
+                task.abort();
+            }
+        }
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn proxy_reload_policy(
+    endpoint: State<'_, ConsoleEndpointState>,
+    log_state: State<'_, AppLogState>,
+) -> Result<Value, String> {
+    let endpoint = endpoint.current();
+    // 配置有误时 body 里有解析错误，用 console_post_json 带回前端。
+    console_post_json(&endpoint, "/policy/reload", json!({}), &log_state.app_log).await
+}
+
 #[tauri::command]
 pub async fn restart_console(
     app: AppHandle,
@@ -153,8 +216,21 @@ pub async fn set_app_language(
 }
 
 #[tauri::command]
-pub async fn proxy_fetch_targets(log_state: State<'_, AppLogState>) -> Result<Value, String> {
-    let targets = console_get("/targets", &log_state.app_log).await?;
+pub async fn proxy_fetch_summary(
+    endpoint: State<'_, ConsoleEndpointState>,
+    log_state: State<'_, AppLogState>,
+) -> Result<Value, String> {
+    let endpoint = endpoint.current();
+    console_get(&endpoint, "/summary", &log_state.app_log).await
+}
+
+#[tauri::command]
+pub async fn proxy_fetch_targets(
+    endpoint: State<'_, ConsoleEndpointState>,
+    log_state: State<'_, AppLogState>,
+) -> Result<Value, String> {
+    let endpoint = endpoint.current();
+    let targets = console_get(&endpoint, "/targets", &log_state.app_log).await?;
     let count = targets.as_array().map(|value| value.len()).unwrap_or(0);
     let _ = append_log_line(
         &log_state.app_log,
@@ -166,10 +242,12 @@ pub async fn proxy_fetch_targets(log_state: State<'_, AppLogState>) -> Result<Va
 #[tauri::command]
 pub async fn proxy_fetch_snapshot(
     name: String,
+    endpoint: State<'_, ConsoleEndpointState>,
     log_state: State<'_, AppLogState>,
 ) -> Result<Value, String> {
+    let endpoint = endpoint.current();
     let path = format!("/targets/{name}/snapshot");
-    let snapshot = console_get(&path, &log_state.app_log).await?;
+    let snapshot = console_get(&endpoint, &path, &log_state.app_log).await?;
     let queue_len = snapshot
         .get("queue")
         .and_then(|value| value.as_array())
@@ -187,55 +265,253 @@ pub async fn proxy_fetch_snapshot(
     Ok(snapshot)
 }
 
+#[tauri::command]
+pub async fn proxy_fetch_history(
+    name: String,
+    before_ms: Option<u64>,
+    before_id: Option<String>,
+    limit: Option<usize>,
+    endpoint: State<'_, ConsoleEndpointState>,
+    log_state: State<'_, AppLogState>,
+) -> Result<Value, String> {
+    let endpoint = endpoint.current();
+    let mut path = format!(
+        "/targets/{}/history?limit={}",
+        encode(&name),
+        limit.unwrap_or(50)
+    );
+    if let Some(before_ms) = before_ms {
+        path.push_str(&format!("&before_ms={before_ms}"));
+    }
+    if let Some(before_id) = before_id {
+        path.push_str(&format!("&before_id={}", encode(&before_id)));
+    }
+    console_get(&endpoint, &path, &log_state.app_log).await
+}
+
+// 批准/拒绝记录到审计里的操作员：当前系统用户名，取不到时由 console 记为 unknown。
+pub(crate) fn operator_name() -> Option<String> {
+    ["USER", "USERNAME"]
+        .iter()
+        .filter_map(|key| std::env::var(key).ok())
+        .map(|name| name.trim().to_string())
+        .find(|name| !name.is_empty())
+}
+
 #[tauri::command]
 pub async fn proxy_approve(
     name: String,
     id: String,
+    confirm: Option<String>,
+    endpoint: State<'_, ConsoleEndpointState>,
     log_state: State<'_, AppLogState>,
 ) -> Result<(), String> {
+    let endpoint = endpoint.current();
     let path = format!("/targets/{name}/approve");
-    console_post(&path, json!({ "id": id }), &log_state.app_log).await
+    console_post(
+        &endpoint,
+        &path,
+        json!({ "id": id, "confirm": confirm, "actor": operator_name() }),
+        &log_state.app_log,
+    )
+    .await
+}
+
+#[tauri::command]
+pub async fn proxy_approve_edited(
+    name: String,
+    id: String,
+    raw_command: String,
+    confirm: Option<String>,
+    endpoint: State<'_, ConsoleEndpointState>,
+    log_state: State<'_, AppLogState>,
+) -> Result<(), String> {
+    let endpoint = endpoint.current();
+    let path = format!("/targets/{}/approve-edited", encode(&name));
+    // 改写后的命令未通过白名单时 body 里有原因，用 console_post_json 带回前端。
+    console_post_json(
+        &endpoint,
+        &path,
+        json!({
+            "id": id,
+            "raw_command": raw_command,
+            "confirm": confirm,
+            "actor": operator_name(),
+        }),
+        &log_state.app_log,
+    )
+    .await
+    .map(|_| ())
+}
+
+#[tauri::command]
+pub async fn proxy_list_forwards(
+    name: String,
+    endpoint: State<'_, ConsoleEndpointState>,
+    log_state: State<'_, AppLogState>,
+) -> Result<Value, String> {
+    let endpoint = endpoint.current();
+    let path = format!("/targets/{}/forwards", encode(&name));
+    console_get(&endpoint, &path, &log_state.app_log).await
+}
+
+#[tauri::command]
+pub async fn proxy_ensure_forward(
+    name: String,
+    forward: String,
+    endpoint: State<'_, ConsoleEndpointState>,
+    log_state: State<'_, AppLogState>,
+) -> Result<Value, String> {
+    let endpoint = endpoint.current();
+    let path = format!(
+        "/targets/{}/forwards/{}/ensure",
+        encode(&name),
+        encode(&forward)
+    );
+    // ssh 建立失败时 body 里有 stderr，一并带回前端。
+    console_post_json(&endpoint, &path, json!({}), &log_state.app_log).await
+}
+
+#[tauri::command]
+pub async fn proxy_release_forward(
+    name: String,
+    forward: String,
+    endpoint: State<'_, ConsoleEndpointState>,
+    log_state: State<'_, AppLogState>,
+) -> Result<(), String> {
+    let endpoint = endpoint.current();
+    let path = format!("/targets/{}/forwards/{}", encode(&name), encode(&forward));
+    console_delete(&endpoint, &path, &log_state.app_log).await
 }
 
 #[tauri::command]
 pub async fn proxy_deny(
     name: String,
     id: String,
+    reason: Option<String>,
+    endpoint: State<'_, ConsoleEndpointState>,
     log_state: State<'_, AppLogState>,
 ) -> Result<(), String> {
+    let endpoint = endpoint.current();
     let path = format!("/targets/{name}/deny");
-    console_post(&path, json!({ "id": id }), &log_state.app_log).await
+    console_post(
+        &endpoint,
+        &path,
+        json!({ "id": id, "actor": operator_name(), "reason": reason }),
+        &log_state.app_log,
+    )
+    .await
 }
 
 #[tauri::command]
 pub async fn proxy_cancel(
     name: String,
     id: String,
+    endpoint: State<'_, ConsoleEndpointState>,
     log_state: State<'_, AppLogState>,
 ) -> Result<(), String> {
+    let endpoint = endpoint.current();
     let path = format!("/targets/{name}/cancel");
-    console_post(&path, json!({ "id": id }), &log_state.app_log).await
+    console_post(&endpoint, &path, json!({ "id": id }), &log_state.app_log).await
 }
 
 #[tauri::command]
 pub async fn proxy_force_cancel(
     name: String,
     id: String,
+    endpoint: State<'_, ConsoleEndpointState>,
     log_state: State<'_, AppLogState>,
 ) -> Result<(), String> {
+    let endpoint = endpoint.current();
     let path = format!("/targets/{name}/force-cancel");
-    console_post(&path, json!({ "id": id }), &log_state.app_log).await
+    console_post(&endpoint, &path, json!({ "id": id }), &log_state.app_log).await
+}
+
+#[tauri::command]
+pub async fn proxy_kill_detached(
+    name: String,
+    id: String,
+    endpoint: State<'_, ConsoleEndpointState>,
+    log_state: State<'_, AppLogState>,
+) -> Result<(), String> {
+    let endpoint = endpoint.current();
+    let path = format!("/targets/{name}/kill-detached");
+    console_post(&endpoint, &path, json!({ "id": id }), &log_state.app_log).await
+}
+
+#[tauri::command]
+pub async fn proxy_set_maintenance(
+    name: String,
+    enabled: bool,
+    message: Option<String>,
+    endpoint: State<'_, ConsoleEndpointState>,
+    log_state: State<'_, AppLogState>,
+) -> Result<Value, String> {
+    let endpoint = endpoint.current();
+    let path = format!("/targets/{name}/maintenance");
+    console_post_json(
+        &endpoint,
+        &path,
+        json!({ "enabled": enabled, "message": message }),
+        &log_state.app_log,
+    )
+    .await
+}
+
+#[tauri::command]
+pub async fn proxy_retry_target(
+    name: String,
+    endpoint: State<'_, ConsoleEndpointState>,
+    log_state: State<'_, AppLogState>,
+) -> Result<Value, String> {
+    let endpoint = endpoint.current();
+    let path = format!("/targets/{name}/retry");
+    console_post_json(&endpoint, &path, json!({}), &log_state.app_log).await
+}
+
+#[tauri::command]
+pub async fn proxy_list_templates(
+    endpoint: State<'_, ConsoleEndpointState>,
+    log_state: State<'_, AppLogState>,
+) -> Result<Value, String> {
+    let endpoint = endpoint.current();
+    console_get(&endpoint, "/templates", &log_state.app_log).await
+}
+
+#[tauri::command]
+pub async fn proxy_run_template(
+    name: String,
+    template: String,
+    params: HashMap<String, String>,
+    endpoint: State<'_, ConsoleEndpointState>,
+    log_state: State<'_, AppLogState>,
+) -> Result<Value, String> {
+    let endpoint = endpoint.current();
+    let path = format!(
+        "/targets/{}/templates/{}/run",
+        encode(&name),
+        encode(&template)
+    );
+    console_post_json(
+        &endpoint,
+        &path,
+        json!({ "params": params }),
+        &log_state.app_log,
+    )
+    .await
 }
 
 #[tauri::command]
 pub async fn proxy_list_target_dirs(
     name: String,
     path: String,
+    endpoint: State<'_, ConsoleEndpointState>,
     log_state: State<'_, AppLogState>,
 ) -> Result<Value, String> {
+    let endpoint = endpoint.current();
     let encoded = encode(&path);
     let path = format!("/targets/{name}/dirs?path={encoded}");
-    console_get(&path, &log_state.app_log).await
+    console_get(&endpoint, &path, &log_state.app_log).await
 }
 
 #[tauri::command]
@@ -243,10 +519,13 @@ pub async fn proxy_start_upload(
     name: String,
     local_path: String,
     remote_path: String,
+    endpoint: State<'_, ConsoleEndpointState>,
     log_state: State<'_, AppLogState>,
 ) -> Result<Value, String> {
+    let endpoint = endpoint.current();
     let path = format!("/targets/{name}/upload");
     console_post_json(
+        &endpoint,
         &path,
         json!({
             "local_path": local_path,
@@ -260,17 +539,45 @@ pub async fn proxy_start_upload(
 #[tauri::command]
 pub async fn proxy_upload_status(
     id: String,
+    endpoint: State<'_, ConsoleEndpointState>,
     log_state: State<'_, AppLogState>,
 ) -> Result<Value, String> {
+    let endpoint = endpoint.current();
     let path = format!("/uploads/{id}");
-    console_get(&path, &log_state.app_log).await
+    console_get(&endpoint, &path, &log_state.app_log).await
+}
+
+#[tauri::command]
+pub async fn proxy_export_history(
+    name: String,
+    from: Option<String>,
+    to: Option<String>,
+    format: Option<String>,
+    dest_path: String,
+    endpoint: State<'_, ConsoleEndpointState>,
+    log_state: State<'_, AppLogState>,
+) -> Result<u64, String> {
+    let endpoint = endpoint.current();
+    let format = match format.as_deref() {
+        Some("jsonl") => "jsonl",
+        _ => "csv",
+    };
+    let mut path = format!("/targets/{}/history/export?format={format}", encode(&name));
+    if let Some(from) = from.filter(|value| !value.trim().is_empty()) {
+        path.push_str(&format!("&from={}", encode(&from)));
+    }
+    if let Some(to) = to.filter(|value| !value.trim().is_empty()) {
+        path.push_str(&format!("&to={}", encode(&to)));
+    }
+    console_download(&endpoint, &path, Path::new(&dest_path), &log_state.app_log).await
 }
 
 #[tauri::command]
 pub async fn start_console_stream(
     app: AppHandle,
+    endpoint: State<'_, ConsoleEndpointState>,
     stream_state: State<'_, crate::state::ConsoleStreamState>,
     log_state: State<'_, AppLogState>,
-) -> Result<(), String> {
-    start_console_stream_service(app, stream_state, log_state).await
+) -> Result<String, String> {
+    start_console_stream_service(app, endpoint.current(), stream_state, log_state).await
 }
@@ -1,5 +1,5 @@
 use serde_json::Value;
-use tauri::State;
+use tauri::{AppHandle, State};
 
 use crate::clients::McpClientState;
 use crate::services::mcp;
@@ -7,18 +7,28 @@ use crate::state::ProxyConfigState;
 
 #[tauri::command]
 pub async fn mcp_set_config(
+    app: AppHandle,
     proxy_state: State<'_, ProxyConfigState>,
     mcp_state: State<'_, McpClientState>,
     config_json: Option<String>,
 ) -> Result<(), String> {
-    mcp::mcp_set_config(proxy_state, mcp_state, config_json).await
+    mcp::mcp_set_config(app, proxy_state, mcp_state, config_json).await
 }
 
 #[tauri::command]
 pub async fn mcp_list_tools(
     mcp_state: State<'_, McpClientState>,
+    server: Option<String>,
 ) -> Result<Vec<mcp::McpToolInfo>, String> {
-    mcp::mcp_list_tools(mcp_state).await
+    mcp::mcp_list_tools(mcp_state, server).await
+}
+
+#[tauri::command]
+pub async fn mcp_list_resources(
+    mcp_state: State<'_, McpClientState>,
+    server: Option<String>,
+) -> Result<Vec<mcp::McpResourceInfo>, String> {
+    mcp::mcp_list_resources(mcp_state, server).await
 }
 
 #[tauri::command]
@@ -1,19 +1,64 @@
+use serde_json::Value;
 use tauri::State;
 
 use crate::services::terminal;
 use crate::state::{AppLogState, TerminalSessions};
 
+#[tauri::command]
+pub async fn terminal_list(
+    target: String,
+    app: tauri::AppHandle,
+    log_state: State<'_, AppLogState>,
+) -> Result<Value, String> {
+    terminal::terminal_list(target, app, log_state).await
+}
+
 #[tauri::command]
 pub async fn terminal_open(
     name: String,
     cols: u16,
     rows: u16,
     term: Option<String>,
+    reattach_session_id: Option<String>,
+    app: tauri::AppHandle,
+    sessions: State<'_, TerminalSessions>,
+    log_state: State<'_, AppLogState>,
+) -> Result<String, String> {
+    terminal::terminal_open(
+        name,
+        cols,
+        rows,
+        term,
+        reattach_session_id,
+        app,
+        sessions,
+        log_state,
+    )
+    .await
+}
+
+#[tauri::command]
+pub async fn terminal_attach(
+    name: String,
+    attach_id: String,
+    mode: Option<String>,
     app: tauri::AppHandle,
     sessions: State<'_, TerminalSessions>,
     log_state: State<'_, AppLogState>,
 ) -> Result<String, String> {
-    terminal::terminal_open(name, cols, rows, term, app, sessions, log_state).await
+    terminal::terminal_attach(name, attach_id, mode, app, sessions, log_state).await
+}
+
+#[tauri::command]
+pub async fn terminal_inject(
+    name: String,
+    console_session_id: String,
+    command: String,
+    app: tauri::AppHandle,
+    log_state: State<'_, AppLogState>,
+) -> Result<(), String> {
+    let actor = super::console::operator_name();
+    terminal::terminal_inject(name, console_session_id, command, actor, app, log_state).await
 }
 
 #[tauri::command]
@@ -13,6 +13,7 @@ use crate::paths::resolve_octovalve_proxy_bin;
 use crate::services::app_error::app_error;
 use crate::services::console_sidecar::{build_console_path, DEFAULT_COMMAND_ADDR};
 use crate::services::logging::append_log_line;
+use crate::services::mcp::existing_control_token_file;
 use crate::services::mcp_config::{build_octovalve_server, parse_mcp_config_json};
 use crate::services::profiles::{expand_tilde_path, octovalve_dir};
 use crate::state::{AppLogState, ProxyConfigState};
@@ -37,7 +38,7 @@ fn resolve_codex_home(app: &AppHandle, raw: &str) -> Result<PathBuf, String> {
     if trimmed.is_empty() {
         return Err("codex_home is empty".to_string());
     }
-    let expanded = expand_tilde_path(app, trimmed).unwrap_or_else(|_| PathBuf::from(trimmed));
+    let expanded = expand_tilde_path(app, trimmed);
     if expanded.is_absolute() {
         return Ok(expanded);
     }
@@ -50,16 +51,24 @@ fn has_flag(args: &[String], flag: &str) -> bool {
         .any(|value| value == flag || value.starts_with(&format!("{flag}=")))
 }
 
-fn build_mcp_cli_override(proxy_bin: &Path, proxy_config: &Path) -> Result<String, String> {
+fn build_mcp_cli_override(
+    proxy_bin: &Path,
+    proxy_config: &Path,
+    control_token_file: Option<&Path>,
+) -> Result<String, String> {
     let command_value = proxy_bin.to_string_lossy();
     let config_value = proxy_config.to_string_lossy();
     let command = format_config_literal(command_value.as_ref())?;
-    let args = vec![
+    let mut args = vec![
         format_config_literal("--config")?,
         format_config_literal(config_value.as_ref())?,
         format_config_literal("--command-addr")?,
         format_config_literal(DEFAULT_COMMAND_ADDR)?,
     ];
+    if let Some(path) = control_token_file {
+        args.push(format_config_literal("--control-token-file")?);
+        args.push(format_config_literal(path.to_string_lossy().as_ref())?);
+    }
     let args_literal = format!("[{}]", args.join(", "));
     // Codex（app-server）对 MCP tool call 默认 60s 超时；给 octovalve MCP server 提高上限。
     // 注意：这是 Codex 的配置值（秒），不是我们的 MCP proxy 超时。
@@ -70,8 +79,17 @@ fn build_mcp_cli_override(proxy_bin: &Path, proxy_config: &Path) -> Result<Strin
     ))
 }
 
-fn build_mcp_servers(proxy_bin: &Path, proxy_config: &Path) -> Vec<serde_json::Value> {
-    let (_, value) = build_octovalve_server(proxy_bin, proxy_config, DEFAULT_COMMAND_ADDR);
+fn build_mcp_servers(
+    proxy_bin: &Path,
+    proxy_config: &Path,
+    control_token_file: Option<&Path>,
+) -> Vec<serde_json::Value> {
+    let (_, value) = build_octovalve_server(
+        proxy_bin,
+        proxy_config,
+        DEFAULT_COMMAND_ADDR,
+        control_token_file,
+    );
     vec![value]
 }
 
@@ -89,7 +107,7 @@ fn resolve_acp_cwd(app: &AppHandle, cwd: &str) -> Result<PathBuf, String> {
         return Ok(workspace_base);
     }
 
-    let expanded = expand_tilde_path(app, trimmed).unwrap_or_else(|_| PathBuf::from(trimmed));
+    let expanded = expand_tilde_path(app, trimmed);
     let absolute = if expanded.is_absolute() {
         expanded
     } else {
@@ -141,11 +159,16 @@ pub async fn acp_start(
             proxy_config_path.display()
         ),
     );
+    let control_token_file = existing_control_token_file(&app);
     let mut parsed = parse_mcp_config_json(mcp_config_json.as_deref().unwrap_or(""))?;
     let mut uses_builtin = false;
     if !parsed.has_octovalve {
-        let (_, value) =
-            build_octovalve_server(&proxy_bin, &proxy_config_path, DEFAULT_COMMAND_ADDR);
+        let (_, value) = build_octovalve_server(
+            &proxy_bin,
+            &proxy_config_path,
+            DEFAULT_COMMAND_ADDR,
+            control_token_file.as_deref(),
+        );
         parsed.servers.push(value);
         uses_builtin = true;
     }
@@ -176,7 +199,11 @@ pub async fn acp_start(
         }
     }
     if uses_builtin {
-        let mcp_override = build_mcp_cli_override(&proxy_bin, &proxy_config_path)?;
+        let mcp_override = build_mcp_cli_override(
+            &proxy_bin,
+            &proxy_config_path,
+            control_token_file.as_deref(),
+        )?;
         let _ = append_log_line(
             &log_path,
             &format!("[acp_start] mcp_override={}", mcp_override),
@@ -381,7 +408,7 @@ mod tests {
     fn mcp_override_does_not_include_exec_mode() {
         let proxy_bin = PathBuf::from("/tmp/octovalve-proxy");
         let proxy_config = PathBuf::from("/tmp/local-proxy-config.toml");
-        let value = build_mcp_cli_override(&proxy_bin, &proxy_config).unwrap();
+        let value = build_mcp_cli_override(&proxy_bin, &proxy_config, None).unwrap();
         assert!(value.contains("mcp_servers.octovalve="));
         assert!(value.contains("--command-addr"));
         assert!(!value.contains("exec-mode"));
@@ -391,7 +418,8 @@ mod tests {
     fn mcp_servers_args_exclude_exec_mode() {
         let proxy_bin = PathBuf::from("/tmp/octovalve-proxy");
         let proxy_config = PathBuf::from("/tmp/local-proxy-config.toml");
-        let servers = build_mcp_servers(&proxy_bin, &proxy_config);
+        let token_file = PathBuf::from("/tmp/control-token");
+        let servers = build_mcp_servers(&proxy_bin, &proxy_config, Some(&token_file));
         let args = servers[0]
             .get("args")
             .and_then(|value| value.as_array())
@@ -402,6 +430,7 @@ mod tests {
             .collect::<Vec<_>>()
             .join(" ");
         assert!(args_text.contains("--command-addr"));
+        assert!(args_text.contains("--control-token-file /tmp/control-token"));
         assert!(!args_text.contains("exec-mode"));
     }
 }
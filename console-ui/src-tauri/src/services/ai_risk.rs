@@ -1,58 +1,235 @@
 use std::time::Duration;
 
-use reqwest::Client;
+use futures_util::StreamExt;
+use reqwest::header::{
+    HeaderMap, HeaderName, HeaderValue, AUTHORIZATION, CONTENT_TYPE, RETRY_AFTER,
+};
+use reqwest::{Client, Response, StatusCode};
 use serde_json::{json, Value};
 
 use crate::services::http_utils::join_base_path;
 use crate::types::ai::{AiRiskModelResponse, AiRiskRequest, AiRiskResponse};
 
+const MAX_ERROR_BODY_CHARS: usize = 512;
+const RETRY_BACKOFF: Duration = Duration::from_millis(800);
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(5);
+
 pub async fn ai_risk_assess(request: AiRiskRequest) -> Result<AiRiskResponse, String> {
     if request.api_key.trim().is_empty() {
         return Err("missing api key".to_string());
     }
     let url = join_base_path(&request.base_url, &request.chat_path)?;
     let timeout_ms = request.timeout_ms.unwrap_or(10000);
+    let stream = request.stream.unwrap_or(false);
+    let headers = build_headers(&request)?;
     let client = Client::new();
-    let payload = json!({
+    let mut payload = json!({
         "model": request.model,
         "messages": [
             { "role": "user", "content": request.prompt }
         ],
         "temperature": 0.2,
     });
+    if stream {
+        payload["stream"] = Value::Bool(true);
+    }
 
-    let response = client
-        .post(url)
-        .bearer_auth(request.api_key)
-        .timeout(Duration::from_millis(timeout_ms))
-        .json(&payload)
-        .send()
-        .await
-        .map_err(|err| err.to_string())?;
-
-    let status = response.status();
-    let body = response.text().await.map_err(|err| err.to_string())?;
-    if !status.is_success() {
-        return Err(format!("ai request failed status={} body={}", status, body));
-    }
-
-    let value: Value = serde_json::from_str(&body).map_err(|err| err.to_string())?;
-    let content = value
-        .pointer("/choices/0/message/content")
-        .and_then(|val| val.as_str())
-        .or_else(|| {
-            value
-                .pointer("/choices/0/text")
-                .and_then(|val| val.as_str())
-        })
-        .unwrap_or("")
-        .trim();
+    // 429/5xx 重试一次；优先遵循 Retry-After（封顶 MAX_RETRY_AFTER）。
+    let mut retried = false;
+    let response = loop {
+        let response = client
+            .post(&url)
+            .headers(headers.clone())
+            .timeout(Duration::from_millis(timeout_ms))
+            .json(&payload)
+            .send()
+            .await
+            .map_err(|err| err.to_string())?;
+        let status = response.status();
+        if status.is_success() {
+            break response;
+        }
+        if !retried && is_retryable(status) {
+            retried = true;
+            let delay = retry_after(&response).unwrap_or(RETRY_BACKOFF);
+            tokio::time::sleep(delay).await;
+            continue;
+        }
+        let body = response.text().await.unwrap_or_default();
+        return Err(format!(
+            "ai request failed status={} error={}",
+            status,
+            provider_error_message(&body)
+        ));
+    };
+
+    let content = if stream && is_event_stream(&response) {
+        read_stream_content(response).await?
+    } else {
+        // 网关可能忽略 stream 直接返回完整 JSON。
+        let body = response.text().await.map_err(|err| err.to_string())?;
+        let value: Value = serde_json::from_str(&body).map_err(|err| err.to_string())?;
+        value
+            .pointer("/choices/0/message/content")
+            .and_then(|val| val.as_str())
+            .or_else(|| {
+                value
+                    .pointer("/choices/0/text")
+                    .and_then(|val| val.as_str())
+            })
+            .unwrap_or("")
+            .to_string()
+    };
+    let content = content.trim();
     if content.is_empty() {
         return Err("ai response missing content".to_string());
     }
     parse_ai_risk_content(content)
 }
 
+fn build_headers(request: &AiRiskRequest) -> Result<HeaderMap, String> {
+    let mut headers = HeaderMap::new();
+    let auth_name = request
+        .auth_header_name
+        .as_deref()
+        .map(str::trim)
+        .filter(|name| !name.is_empty() && !name.eq_ignore_ascii_case(AUTHORIZATION.as_str()));
+    match auth_name {
+        Some(name) => insert_header(&mut headers, name, request.api_key.trim())?,
+        None => insert_header(
+            &mut headers,
+            AUTHORIZATION.as_str(),
+            &format!("Bearer {}", request.api_key.trim()),
+        )?,
+    }
+    if let Some(extra) = &request.extra_headers {
+        for (name, value) in extra {
+            insert_header(&mut headers, name.trim(), value)?;
+        }
+    }
+    Ok(headers)
+}
+
+fn insert_header(headers: &mut HeaderMap, name: &str, value: &str) -> Result<(), String> {
+    let header_name = HeaderName::from_bytes(name.as_bytes())
+        .map_err(|_| format!("invalid header name: {name}"))?;
+    let header_value =
+        HeaderValue::from_str(value).map_err(|_| format!("invalid value for header {name}"))?;
+    headers.insert(header_name, header_value);
+    Ok(())
+}
+
+fn is_retryable(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+fn retry_after(response: &Response) -> Option<Duration> {
+    let secs: u64 = response
+        .headers()
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
+    Some(Duration::from_secs(secs).min(MAX_RETRY_AFTER))
+}
+
+fn is_event_stream(response: &Response) -> bool {
+    response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.contains("text/event-stream"))
+}
+
+// 优先取 OpenAI/Azure 风格的 error.message，否则用原始 body；都截断避免把整页 HTML 塞进提示。
+fn provider_error_message(body: &str) -> String {
+    let message = serde_json::from_str::<Value>(body)
+        .ok()
+        .and_then(|value| {
+            value
+                .pointer("/error/message")
+                .or_else(|| value.get("message"))
+                .and_then(|val| val.as_str())
+                .map(str::to_string)
+        })
+        .unwrap_or_else(|| body.trim().to_string());
+    if message.is_empty() {
+        return "<empty body>".to_string();
+    }
+    truncate_chars(&message, MAX_ERROR_BODY_CHARS)
+}
+
+fn truncate_chars(value: &str, max_chars: usize) -> String {
+    match value.char_indices().nth(max_chars) {
+        Some((index, _)) => format!("{}...", &value[..index]),
+        None => value.to_string(),
+    }
+}
+
+async fn read_stream_content(response: Response) -> Result<String, String> {
+    let mut stream = response.bytes_stream();
+    let mut reader = SseContent::default();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|err| format!("stream error: {err}"))?;
+        if reader.push(&chunk)? {
+            break;
+        }
+    }
+    Ok(reader.finish())
+}
+
+/// 累积 SSE 中的 `choices[0].delta.content`，直到 `[DONE]` 或连接结束。
+#[derive(Default)]
+struct SseContent {
+    buffer: Vec<u8>,
+    content: String,
+}
+
+impl SseContent {
+    /// 返回 true 表示已读到 `[DONE]`。
+    fn push(&mut self, chunk: &[u8]) -> Result<bool, String> {
+        self.buffer.extend_from_slice(chunk);
+        while let Some(newline) = self.buffer.iter().position(|byte| *byte == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=newline).collect();
+            if self.handle_line(&String::from_utf8_lossy(&line))? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn finish(mut self) -> String {
+        let rest = std::mem::take(&mut self.buffer);
+        let _ = self.handle_line(&String::from_utf8_lossy(&rest));
+        self.content
+    }
+
+    fn handle_line(&mut self, line: &str) -> Result<bool, String> {
+        let line = line.trim();
+        let Some(data) = line.strip_prefix("data:").map(str::trim_start) else {
+            return Ok(false);
+        };
+        if data == "[DONE]" {
+            return Ok(true);
+        }
+        let Ok(value) = serde_json::from_str::<Value>(data) else {
+            return Ok(false);
+        };
+        if value.get("error").is_some() {
+            return Err(format!("ai stream error: {}", provider_error_message(data)));
+        }
+        if let Some(delta) = value
+            .pointer("/choices/0/delta/content")
+            .and_then(|val| val.as_str())
+        {
+            self.content.push_str(delta);
+        }
+        Ok(false)
+    }
+}
+
 fn parse_ai_risk_content(content: &str) -> Result<AiRiskResponse, String> {
     let payload = extract_json_block(content).unwrap_or(content);
     let parsed: AiRiskModelResponse =
@@ -82,3 +259,81 @@ fn normalize_ai_risk(value: &str) -> Option<String> {
         _ => None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn request(auth_header_name: Option<&str>) -> AiRiskRequest {
+        AiRiskRequest {
+            base_url: "https://example.invalid".to_string(),
+            chat_path: "/chat/completions".to_string(),
+            model: "m".to_string(),
+            api_key: "secret".to_string(),
+            prompt: "p".to_string(),
+            timeout_ms: None,
+            auth_header_name: auth_header_name.map(str::to_string),
+            extra_headers: Some(HashMap::from([(
+                "X-Gateway".to_string(),
+                "risk".to_string(),
+            )])),
+            stream: None,
+            command: None,
+            target: None,
+            cache_ttl_secs: None,
+            force_refresh: false,
+        }
+    }
+
+    #[test]
+    fn builds_bearer_or_custom_auth_headers() {
+        let headers = build_headers(&request(None)).expect("headers");
+        assert_eq!(headers[AUTHORIZATION], "Bearer secret");
+        assert_eq!(headers["x-gateway"], "risk");
+
+        let headers = build_headers(&request(Some("api-key"))).expect("headers");
+        assert_eq!(headers["api-key"], "secret");
+        assert!(headers.get(AUTHORIZATION).is_none());
+
+        assert!(build_headers(&request(Some("bad header"))).is_err());
+    }
+
+    #[test]
+    fn accumulates_split_sse_chunks() {
+        let mut reader = SseContent::default();
+        let chunks: [&[u8]; 4] = [
+            b"data: {\"choices\":[{\"delta\":{\"content\":\"{\\\"risk\\\":\"}}]}\n\n: keep-alive\n",
+            b"data: {\"choices\":[{\"delta\":{\"content\":\"\\\"lo",
+            b"w\\\"}\"}}]}\n",
+            b"data: [DONE]\n",
+        ];
+        let mut done = false;
+        for chunk in chunks {
+            done = reader.push(chunk).expect("chunk");
+        }
+        assert!(done);
+        let content = reader.finish();
+        assert_eq!(content, "{\"risk\":\"low\"}");
+        assert_eq!(parse_ai_risk_content(&content).expect("parse").risk, "low");
+
+        let mut reader = SseContent::default();
+        assert!(reader
+            .push(b"data: {\"error\":{\"message\":\"quota exceeded\"}}\n")
+            .unwrap_err()
+            .contains("quota exceeded"));
+    }
+
+    #[test]
+    fn extracts_and_truncates_provider_errors() {
+        let body = r#"{"error":{"code":"429","message":"Rate limit reached"}}"#;
+        assert_eq!(provider_error_message(body), "Rate limit reached");
+        let long = "x".repeat(MAX_ERROR_BODY_CHARS + 10);
+        let message = provider_error_message(&long);
+        assert_eq!(message.chars().count(), MAX_ERROR_BODY_CHARS + 3);
+        assert_eq!(provider_error_message("  "), "<empty body>");
+        assert!(is_retryable(StatusCode::TOO_MANY_REQUESTS));
+        assert!(is_retryable(StatusCode::BAD_GATEWAY));
+        assert!(!is_retryable(StatusCode::UNAUTHORIZED));
+    }
+}
@@ -0,0 +1,238 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::services::ai_risk;
+use crate::services::logging::append_log_line;
+use crate::types::ai::{AiRiskCacheStats, AiRiskRequest, AiRiskResponse};
+
+pub const CACHE_FILE_NAME: &str = "ai-risk-cache.json";
+const DEFAULT_TTL_SECS: u64 = 7 * 24 * 60 * 60;
+const MAX_ENTRIES: usize = 2000;
+
+#[derive(Clone, Serialize, Deserialize)]
+struct CacheEntry {
+    response: AiRiskResponse,
+    stored_at_ms: u64,
+    expires_at_ms: u64,
+}
+
+#[derive(Deserialize)]
+struct CacheFile {
+    entries: HashMap<String, CacheEntry>,
+}
+
+/// 按 (规范化命令, 目标, 模型) 缓存 AI 风险评估结果，持久化在应用配置目录下的 JSON 文件。
+pub struct AiRiskCache {
+    path: PathBuf,
+    entries: Mutex<Option<HashMap<String, CacheEntry>>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl AiRiskCache {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            entries: Mutex::new(None),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    fn get(&self, key: &str, now_ms: u64) -> Option<AiRiskResponse> {
+        let mut guard = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        let entries = guard.get_or_insert_with(|| load_entries(&self.path));
+        let hit = entries
+            .get(key)
+            .filter(|entry| entry.expires_at_ms > now_ms)
+            .map(|entry| entry.response.clone());
+        let counter = if hit.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        hit
+    }
+
+    fn put(
+        &self,
+        key: String,
+        response: AiRiskResponse,
+        ttl_secs: u64,
+        now_ms: u64,
+    ) -> Result<(), String> {
+        let mut guard = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        let entries = guard.get_or_insert_with(|| load_entries(&self.path));
+        entries.retain(|_, entry| entry.expires_at_ms > now_ms);
+        entries.insert(
+            key,
+            CacheEntry {
+                response,
+                stored_at_ms: now_ms,
+                expires_at_ms: now_ms.saturating_add(ttl_secs.saturating_mul(1000)),
+            },
+        );
+        if entries.len() > MAX_ENTRIES {
+            let mut stored: Vec<(u64, String)> = entries
+                .iter()
+                .map(|(key, entry)| (entry.stored_at_ms, key.clone()))
+                .collect();
+            stored.sort();
+            for (_, key) in stored.into_iter().take(entries.len() - MAX_ENTRIES) {
+                entries.remove(&key);
+            }
+        }
+        write_entries(&self.path, entries)
+    }
+
+    pub fn stats(&self) -> AiRiskCacheStats {
+        let now_ms = now_ms();
+        let mut guard = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        let entries = guard.get_or_insert_with(|| load_entries(&self.path));
+        let expired = entries
+            .values()
+            .filter(|entry| entry.expires_at_ms <= now_ms)
+            .count();
+        AiRiskCacheStats {
+            entries: entries.len() - expired,
+            expired,
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            path: self.path.display().to_string(),
+        }
+    }
+
+    pub fn clear(&self) -> Result<(), String> {
+        let mut guard = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        *guard = Some(HashMap::new());
+        self.hits.store(0, Ordering::Relaxed);
+        self.misses.store(0, Ordering::Relaxed);
+        match fs::remove_file(&self.path) {
+            Ok(()) => Ok(()),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(err.to_string()),
+        }
+    }
+}
+
+/// 命中且未过期时直接返回缓存；`force_refresh` 跳过读取但仍写回新结果。
+/// 请求没有带 `command`，或 `cache_ttl_secs = 0` 时不缓存。
+pub async fn assess_cached(
+    cache: &AiRiskCache,
+    request: AiRiskRequest,
+    log_path: &Path,
+) -> Result<AiRiskResponse, String> {
+    let ttl_secs = request.cache_ttl_secs.unwrap_or(DEFAULT_TTL_SECS);
+    let key = request
+        .command
+        .as_deref()
+        .filter(|_| ttl_secs > 0)
+        .map(|command| cache_key(command, request.target.as_deref(), &request.model));
+    if let Some(key) = key.as_deref().filter(|_| !request.force_refresh) {
+        if let Some(response) = cache.get(key, now_ms()) {
+            return Ok(response);
+        }
+    }
+    let response = ai_risk::ai_risk_assess(request).await?;
+    if let Some(key) = key {
+        // 写缓存失败不影响本次评估结果。
+        if let Err(err) = cache.put(key, response.clone(), ttl_secs, now_ms()) {
+            let _ = append_log_line(log_path, &format!("[ai_risk] cache write failed: {err}"));
+        }
+    }
+    Ok(response)
+}
+
+fn cache_key(command: &str, target: Option<&str>, model: &str) -> String {
+    let command = command.split_whitespace().collect::<Vec<_>>().join(" ");
+    let mut hasher = Sha256::new();
+    for part in [command.as_str(), target.unwrap_or(""), model.trim()] {
+        hasher.update(part.as_bytes());
+        hasher.update([0]);
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+// 文件缺失或损坏时从空缓存开始，下一次写入会覆盖它。
+fn load_entries(path: &Path) -> HashMap<String, CacheEntry> {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|raw| serde_json::from_str::<CacheFile>(&raw).ok())
+        .map(|file| file.entries)
+        .unwrap_or_default()
+}
+
+// 先写同目录临时文件再 rename，崩溃时只会留下旧文件或新文件。
+fn write_entries(path: &Path, entries: &HashMap<String, CacheEntry>) -> Result<(), String> {
+    #[derive(Serialize)]
+    struct CacheFileRef<'a> {
+        entries: &'a HashMap<String, CacheEntry>,
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|err| err.to_string())?;
+    }
+    let payload = serde_json::to_vec(&CacheFileRef { entries }).map_err(|err| err.to_string())?;
+    let tmp_path = path.with_extension("json.tmp");
+    let mut file = fs::File::create(&tmp_path).map_err(|err| err.to_string())?;
+    std::io::Write::write_all(&mut file, &payload).map_err(|err| err.to_string())?;
+    file.sync_all().map_err(|err| err.to_string())?;
+    fs::rename(&tmp_path, path).map_err(|err| err.to_string())
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(risk: &str) -> AiRiskResponse {
+        AiRiskResponse {
+            risk: risk.to_string(),
+            reason: String::new(),
+            key_points: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn keys_ignore_whitespace_but_not_target_or_model() {
+        let key = cache_key("ls  -la\t/tmp", Some("dev"), "m1");
+        assert_eq!(key, cache_key(" ls -la /tmp ", Some("dev"), "m1"));
+        assert_ne!(key, cache_key("ls -la /tmp", Some("prod"), "m1"));
+        assert_ne!(key, cache_key("ls -la /tmp", Some("dev"), "m2"));
+    }
+
+    #[test]
+    fn persists_entries_and_expires_them() {
+        let dir = std::env::temp_dir().join(format!("octovalve-ai-cache-{}", uuid::Uuid::new_v4()));
+        let path = dir.join(CACHE_FILE_NAME);
+        let cache = AiRiskCache::new(path.clone());
+        cache
+            .put("k".to_string(), response("low"), 60, 1_000)
+            .expect("put");
+        assert!(!dir.join("ai-risk-cache.json.tmp").exists());
+
+        let reloaded = AiRiskCache::new(path.clone());
+        assert_eq!(reloaded.get("k", 2_000).expect("hit").risk, "low");
+        assert!(reloaded.get("k", 61_000).is_none());
+        assert_eq!(reloaded.hits.load(Ordering::Relaxed), 1);
+        assert_eq!(reloaded.misses.load(Ordering::Relaxed), 1);
+
+        reloaded.clear().expect("clear");
+        assert!(!path.exists());
+        assert!(AiRiskCache::new(path).get("k", 2_000).is_none());
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -1,6 +1,8 @@
 use std::fs;
 use std::path::Path;
 
+use uuid::Uuid;
+
 use crate::types::ConfigFilePayload;
 
 pub const DEFAULT_PROXY_EXAMPLE: &str =
@@ -37,3 +39,43 @@ pub fn write_config_file(path: &Path, content: &str) -> Result<(), String> {
     }
     fs::write(path, content).map_err(|err| err.to_string())
 }
+
+/// broker 配置缺少 control_token 时随机生成并写回，再把 token 同步到 `token_file`（0600）供
+/// octovalve-proxy 通过 `--control-token-file` 读取；不放进命令行参数，避免被其他用户 `ps` 看到。
+pub fn ensure_control_token(broker_config: &Path, token_file: &Path) -> Result<(), String> {
+    let content = fs::read_to_string(broker_config).map_err(|err| err.to_string())?;
+    let mut doc = content
+        .parse::<toml_edit::DocumentMut>()
+        .map_err(|err| format!("failed to parse {}: {err}", broker_config.display()))?;
+    let existing = doc
+        .get("control_token")
+        .and_then(|item| item.as_str())
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(str::to_string);
+    let token = match existing {
+        Some(token) => token,
+        None => {
+            let token = format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+            doc.insert("control_token", toml_edit::value(token.clone()));
+            fs::write(broker_config, doc.to_string()).map_err(|err| err.to_string())?;
+            token
+        }
+    };
+    write_private_file(token_file, &token)
+}
+
+fn write_private_file(path: &Path, content: &str) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|err| err.to_string())?;
+    }
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path).map_err(|err| err.to_string())?;
+    std::io::Write::write_all(&mut file, content.as_bytes()).map_err(|err| err.to_string())
+}
@@ -4,15 +4,19 @@ use std::sync::OnceLock;
 use std::time::Duration;
 
 use reqwest::header::CONTENT_TYPE;
-use reqwest::Client;
+use reqwest::{Client, RequestBuilder};
 use serde_json::Value;
+use tokio::io::AsyncWriteExt;
 
 use crate::services::http_utils::join_base_path;
 use crate::services::logging::{append_log_line, escape_log_body};
+use crate::state::ConsoleEndpoint;
 
-pub const CONSOLE_HTTP_HOST: &str = "127.0.0.1:19309";
+/// 本地 sidecar 的 HTTP/WS 地址；环境配置了 `console_addr` 时改用它。
+pub const DEFAULT_CONSOLE_ADDR: &str = "127.0.0.1:19309";
 const HTTP_CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
 const HTTP_IO_TIMEOUT: Duration = Duration::from_secs(5);
+const HTTP_DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(600);
 static HTTP_REQUEST_ID: AtomicU64 = AtomicU64::new(1);
 static HTTP_CLIENT: OnceLock<Client> = OnceLock::new();
 
@@ -21,9 +25,14 @@ struct HttpResponse {
     body: String,
 }
 
-pub async fn console_get(path: &str, log_path: &Path) -> Result<Value, String> {
+pub async fn console_get(
+    endpoint: &ConsoleEndpoint,
+    path: &str,
+    log_path: &Path,
+) -> Result<Value, String> {
     let response =
-        console_http_request_with_timeout("GET", path, None, log_path, HTTP_IO_TIMEOUT).await?;
+        console_http_request_with_timeout(endpoint, "GET", path, None, log_path, HTTP_IO_TIMEOUT)
+            .await?;
     if response.status / 100 != 2 {
         return Err(format!(
             "console http GET status {} for {}",
@@ -36,16 +45,40 @@ pub async fn console_get(path: &str, log_path: &Path) -> Result<Value, String> {
     })
 }
 
-pub async fn console_post(path: &str, payload: Value, log_path: &Path) -> Result<(), String> {
-    console_post_with_timeout(path, payload, log_path, HTTP_IO_TIMEOUT).await
+pub async fn console_post(
+    endpoint: &ConsoleEndpoint,
+    path: &str,
+    payload: Value,
+    log_path: &Path,
+) -> Result<(), String> {
+    console_post_with_timeout(endpoint, path, payload, log_path, HTTP_IO_TIMEOUT).await
 }
 
-pub async fn console_post_json(path: &str, payload: Value, log_path: &Path) -> Result<Value, String> {
+pub async fn console_post_json(
+    endpoint: &ConsoleEndpoint,
+    path: &str,
+    payload: Value,
+    log_path: &Path,
+) -> Result<Value, String> {
     let payload = payload.to_string();
-    let response =
-        console_http_request_with_timeout("POST", path, Some(payload), log_path, HTTP_IO_TIMEOUT)
-            .await?;
+    let response = console_http_request_with_timeout(
+        endpoint,
+        "POST",
+        path,
+        Some(payload),
+        log_path,
+        HTTP_IO_TIMEOUT,
+    )
+    .await?;
     if response.status / 100 != 2 {
+        // 模板参数校验等接口在 body 里给出具体原因，一并带回给前端。
+        let body = response.body.trim();
+        if !body.is_empty() {
+            return Err(format!(
+                "console http POST status {} for {}: {}",
+                response.status, path, body
+            ));
+        }
         return Err(format!(
             "console http POST status {} for {}",
             response.status, path
@@ -57,7 +90,31 @@ pub async fn console_post_json(path: &str, payload: Value, log_path: &Path) -> R
     })
 }
 
+pub async fn console_delete(
+    endpoint: &ConsoleEndpoint,
+    path: &str,
+    log_path: &Path,
+) -> Result<(), String> {
+    let response = console_http_request_with_timeout(
+        endpoint,
+        "DELETE",
+        path,
+        None,
+        log_path,
+        HTTP_IO_TIMEOUT,
+    )
+    .await?;
+    if response.status / 100 != 2 {
+        return Err(format!(
+            "console http DELETE status {} for {}",
+            response.status, path
+        ));
+    }
+    Ok(())
+}
+
 pub async fn console_post_with_timeout(
+    endpoint: &ConsoleEndpoint,
     path: &str,
     payload: Value,
     log_path: &Path,
@@ -65,9 +122,15 @@ pub async fn console_post_with_timeout(
 ) -> Result<(), String> {
     let payload = payload.to_string();
     let _ = append_log_line(log_path, &format!("console http POST payload: {}", payload));
-    let response =
-        console_http_request_with_timeout("POST", path, Some(payload), log_path, io_timeout)
-            .await?;
+    let response = console_http_request_with_timeout(
+        endpoint,
+        "POST",
+        path,
+        Some(payload),
+        log_path,
+        io_timeout,
+    )
+    .await?;
     if response.status / 100 != 2 {
         return Err(format!(
             "console http POST status {} for {}",
@@ -77,7 +140,47 @@ pub async fn console_post_with_timeout(
     Ok(())
 }
 
+pub async fn console_download(
+    endpoint: &ConsoleEndpoint,
+    path: &str,
+    dest: &Path,
+    log_path: &Path,
+) -> Result<u64, String> {
+    let url = join_base_path(&endpoint.http_base(), path)?;
+    let client = http_client().map_err(|err| err.to_string())?;
+    let mut response = with_api_token(client.get(&url), endpoint)
+        .header("Connection", "close")
+        .timeout(HTTP_DOWNLOAD_TIMEOUT)
+        .send()
+        .await
+        .map_err(|err| err.to_string())?;
+    let status = response.status().as_u16();
+    if status / 100 != 2 {
+        return Err(format!("console http GET status {status} for {path}"));
+    }
+    let mut file = tokio::fs::File::create(dest)
+        .await
+        .map_err(|err| format!("failed to create {}: {err}", dest.display()))?;
+    let mut written = 0u64;
+    while let Some(chunk) = response.chunk().await.map_err(|err| err.to_string())? {
+        file.write_all(&chunk)
+            .await
+            .map_err(|err| format!("failed to write {}: {err}", dest.display()))?;
+        written += chunk.len() as u64;
+    }
+    file.flush().await.map_err(|err| err.to_string())?;
+    let _ = append_log_line(
+        log_path,
+        &format!(
+            "console http download ok path={path} dest={} bytes={written}",
+            dest.display()
+        ),
+    );
+    Ok(written)
+}
+
 async fn console_http_request_with_timeout(
+    endpoint: &ConsoleEndpoint,
     method: &str,
     path: &str,
     body: Option<String>,
@@ -88,10 +191,12 @@ async fn console_http_request_with_timeout(
     let body_len = body.as_ref().map(|value| value.len()).unwrap_or(0);
     let _ = append_log_line(
         log_path,
-        &format!("console http {method}#{request_id} start path={path} body_len={body_len}"),
+        &format!(
+            "console http {method}#{request_id} start addr={} path={path} body_len={body_len}",
+            endpoint.addr
+        ),
     );
-    let base_url = format!("http://{}", CONSOLE_HTTP_HOST);
-    let url = join_base_path(&base_url, path).map_err(|err| {
+    let url = join_base_path(&endpoint.http_base(), path).map_err(|err| {
         let _ = append_log_line(
             log_path,
             &format!("console http {method}#{request_id} invalid url: {err}"),
@@ -102,9 +207,10 @@ async fn console_http_request_with_timeout(
     let mut request = match method {
         "GET" => client.get(&url),
         "POST" => client.post(&url),
+        "DELETE" => client.delete(&url),
         _ => return Err(format!("console http unsupported method {method}")),
     };
-    request = request
+    request = with_api_token(request, endpoint)
         .header("Accept", "application/json")
         .header("Connection", "close")
         .timeout(io_timeout);
@@ -167,6 +273,13 @@ async fn console_http_request_with_timeout(
     Ok(HttpResponse { status, body })
 }
 
+fn with_api_token(request: RequestBuilder, endpoint: &ConsoleEndpoint) -> RequestBuilder {
+    match endpoint.api_token.as_deref() {
+        Some(token) => request.bearer_auth(token),
+        None => request,
+    }
+}
+
 fn build_http_client() -> Result<Client, reqwest::Error> {
     Client::builder()
         .connect_timeout(HTTP_CONNECT_TIMEOUT)
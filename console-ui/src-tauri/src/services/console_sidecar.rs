@@ -9,9 +9,12 @@ use std::time::{Duration, Instant};
 use tauri::{AppHandle, Manager};
 use tauri_plugin_shell::{process::CommandEvent, ShellExt};
 
-use crate::services::config::{ensure_file, DEFAULT_BROKER_CONFIG};
+use crate::services::config::{ensure_control_token, ensure_file, DEFAULT_BROKER_CONFIG};
 use crate::services::logging::append_log_line;
-use crate::services::profiles::resolve_broker_config_path;
+use crate::services::profiles::{
+    control_token_path, current_profile_entry, profile_console_addr, profile_console_endpoint,
+    resolve_broker_config_path,
+};
 use crate::state::{
     AppLanguageState, ConsoleRestartLock, ConsoleSidecar, ConsoleSidecarState, ProfilesState,
 };
@@ -134,10 +137,24 @@ pub fn start_console(app: &AppHandle, proxy_config: &Path, app_log: &Path) -> Re
     fs::create_dir_all(&config_dir).map_err(|err| err.to_string())?;
 
     let profiles = app.state::<ProfilesState>().0.lock().unwrap().clone();
+    let profile = current_profile_entry(&profiles)?;
+    let console_addr = profile_console_addr(&profile);
+    if profile.external_console {
+        // 外部 console 由用户自己启动，这里只负责连接。
+        let _ = append_log_line(
+            app_log,
+            &format!(
+                "profile {} uses external console at {console_addr}; sidecar not started",
+                profile.name
+            ),
+        );
+        return Ok(());
+    }
     let resolved_broker =
         resolve_broker_config_path(app, proxy_config, &config_dir, Some(&profiles))?;
     let broker_config = resolved_broker.path;
     ensure_file(&broker_config, DEFAULT_BROKER_CONFIG)?;
+    ensure_control_token(&broker_config, &control_token_path(app)?)?;
     let logs_dir = config_dir.join("logs");
     fs::create_dir_all(&logs_dir).map_err(|err| err.to_string())?;
     let console_log = logs_dir.join("console.log");
@@ -162,10 +179,15 @@ pub fn start_console(app: &AppHandle, proxy_config: &Path, app_log: &Path) -> Re
         envs.insert("OCTOVALVE_TERMINAL_LOCALE".to_string(), locale);
     }
     envs.insert("OCTOVALVE_APP_LANGUAGE".to_string(), language);
+    if let Some(token) = profile_console_endpoint(&profile).api_token {
+        envs.insert("OCTOVALVE_CONSOLE_API_TOKEN".to_string(), token);
+    }
 
     let console_args = vec![
         "--config".to_string(),
         proxy_config.to_string_lossy().to_string(),
+        "--listen-addr".to_string(),
+        console_addr,
         "--command-listen-addr".to_string(),
         DEFAULT_COMMAND_ADDR.to_string(),
         "--broker-config".to_string(),
@@ -173,6 +195,8 @@ pub fn start_console(app: &AppHandle, proxy_config: &Path, app_log: &Path) -> Re
         "--log-to-stderr".to_string(),
     ];
 
+    check_console_config(app, proxy_config, &broker_config, &envs, app_log)?;
+
     // In dev, the previous console instance might still be winding down (e.g. after a hot-reload),
     // so we wait a bit for ports to become available before spawning a new sidecar.
     if let Err(err) = wait_for_tcp_port_free(DEFAULT_COMMAND_ADDR, Duration::from_secs(3)) {
@@ -247,6 +271,39 @@ pub fn start_console(app: &AppHandle, proxy_config: &Path, app_log: &Path) -> Re
     Ok(())
 }
 
+// Run `--check-config` first so a broken config shows up as the actual report instead of
+// a sidecar that exits right after spawning.
+fn check_console_config(
+    app: &AppHandle,
+    proxy_config: &Path,
+    broker_config: &Path,
+    envs: &HashMap<String, String>,
+    app_log: &Path,
+) -> Result<(), String> {
+    let command = app
+        .shell()
+        .sidecar("octovalve-console")
+        .map_err(|err| err.to_string())?
+        .args([
+            "--config".to_string(),
+            proxy_config.to_string_lossy().to_string(),
+            "--broker-config".to_string(),
+            broker_config.to_string_lossy().to_string(),
+            "--check-config".to_string(),
+        ])
+        .envs(envs.clone());
+    let output = tauri::async_runtime::block_on(command.output()).map_err(|err| err.to_string())?;
+    let report = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if output.status.success() {
+        let _ = append_log_line(app_log, &format!("console config check ok:\n{report}"));
+        return Ok(());
+    }
+    let _ = append_log_line(app_log, &format!("console config check failed:\n{report}"));
+    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    let detail = if report.is_empty() { stderr } else { report };
+    Err(format!("console config check failed:\n{detail}"))
+}
+
 pub fn stop_console(app: &AppHandle) {
     let state = app.state::<ConsoleSidecarState>();
     let mut guard = state.0.lock().unwrap();
@@ -1,19 +1,79 @@
+use std::collections::HashMap;
 use std::time::Duration;
 
 use futures_util::StreamExt;
-use serde_json::Value;
-use tauri::{AppHandle, Emitter, State};
-use tokio_tungstenite::tungstenite::Message;
+use serde_json::{json, Value};
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::handshake::client::Request;
+use tokio_tungstenite::tungstenite::http::header::{HeaderValue, AUTHORIZATION};
+use tokio_tungstenite::tungstenite::{self, Message};
+use urlencoding::encode;
 
+use crate::services::console_http::console_get;
 use crate::services::logging::append_log_line;
-use crate::state::{AppLogState, ConsoleStreamState};
+use crate::state::{AppLogState, ConsoleEndpoint, ConsoleStream, ConsoleStreamState};
 
-const CONSOLE_WS_URL: &str = "ws://127.0.0.1:19309/ws";
 const WS_RECONNECT_DELAY: Duration = Duration::from_secs(3);
+const WS_CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
 
-fn emit_ws_status(app: &AppHandle, log_path: &std::path::Path, status: &str) {
-    let _ = app.emit("console_ws_status", status.to_string());
-    let _ = append_log_line(log_path, &format!("ws {status}"));
+/// console 的 WS 握手请求；环境配置了 api token 时带上 Authorization 头。
+pub struct ConsoleWsRequest<'a> {
+    url: String,
+    api_token: Option<&'a str>,
+}
+
+pub fn console_ws_request(url: String, endpoint: &ConsoleEndpoint) -> ConsoleWsRequest<'_> {
+    ConsoleWsRequest {
+        url,
+        api_token: endpoint.api_token.as_deref(),
+    }
+}
+
+impl IntoClientRequest for ConsoleWsRequest<'_> {
+    fn into_client_request(self) -> tungstenite::Result<Request> {
+        let mut request = self.url.into_client_request()?;
+        if let Some(token) = self.api_token {
+            let value = HeaderValue::from_str(&format!("Bearer {token}"))
+                .map_err(|err| tungstenite::Error::HttpFormat(err.into()))?;
+            request.headers_mut().insert(AUTHORIZATION, value);
+        }
+        Ok(request)
+    }
+}
+
+fn emit_ws_status(app: &AppHandle, log_path: &std::path::Path, profile: &str, status: &str) {
+    let _ = app.emit(
+        "console_ws_status",
+        json!({ "profile": profile, "status": status }),
+    );
+    let _ = append_log_line(log_path, &format!("ws {status} profile={profile}"));
+}
+
+// 事件带上所属环境，前端切换环境时丢弃旧连接残留的事件。
+fn emit_console_event(app: &AppHandle, profile: &str, mut payload: Value) {
+    if let Some(object) = payload.as_object_mut() {
+        object.insert("profile".to_string(), Value::String(profile.to_string()));
+    }
+    let _ = app.emit("console_event", payload);
+}
+
+// 待审批数量单独以 summary_changed 推送，并同步 dock/任务栏徽标，不必拉取快照。
+fn emit_summary_changed(app: &AppHandle, profile: &str, payload: &Value) {
+    let Some(summary) = payload.get("summary") else {
+        return;
+    };
+    let total = summary
+        .get("total_pending")
+        .and_then(|value| value.as_i64())
+        .unwrap_or(0);
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.set_badge_count((total > 0).then_some(total));
+    }
+    let _ = app.emit(
+        "summary_changed",
+        json!({ "profile": profile, "summary": summary }),
+    );
 }
 
 fn log_ws_event(log_path: &std::path::Path, payload: &Value) {
@@ -61,35 +121,146 @@ fn log_ws_event(log_path: &std::path::Path, payload: &Value) {
                 &format!("ws event target_updated payload={}", payload.to_string()),
             );
         }
+        "request_queued" => {
+            let target = payload_str(payload, &["target"]);
+            let id = payload_str(payload, &["request", "id"]);
+            let priority = payload_str(payload, &["request", "priority"]);
+            let _ = append_log_line(
+                log_path,
+                &format!("ws event request_queued target={target} id={id} priority={priority}"),
+            );
+        }
+        "request_resolved" => {
+            let target = payload_str(payload, &["target"]);
+            let id = payload_str(payload, &["id"]);
+            let status = payload_str(payload, &["status"]);
+            let _ = append_log_line(
+                log_path,
+                &format!("ws event request_resolved target={target} id={id} status={status}"),
+            );
+        }
+        "result_recorded" => {
+            // 结果里可能带大段 stdout/stderr，只记录摘要。
+            let target = payload_str(payload, &["target"]);
+            let id = payload_str(payload, &["result", "id"]);
+            let status = payload_str(payload, &["result", "status"]);
+            let _ = append_log_line(
+                log_path,
+                &format!("ws event result_recorded target={target} id={id} status={status}"),
+            );
+        }
         _ => {}
     }
 }
 
+fn payload_str<'a>(payload: &'a Value, path: &[&str]) -> &'a str {
+    path.iter()
+        .try_fold(payload, |value, key| value.get(key))
+        .and_then(|value| value.as_str())
+        .unwrap_or("unknown")
+}
+
+// 记录最近收到的 epoch/seq，重连时带上 since 让 console 补发缺失事件，补不了时会回 targets_snapshot。
+struct StreamCursor {
+    base_url: String,
+    epoch: Option<String>,
+    seq: Option<u64>,
+}
+
+impl StreamCursor {
+    fn new(endpoint: &ConsoleEndpoint) -> Self {
+        Self {
+            base_url: format!("{}/ws", endpoint.ws_base()),
+            epoch: None,
+            seq: None,
+        }
+    }
+
+    fn url(&self) -> String {
+        match (self.epoch.as_deref(), self.seq) {
+            (Some(epoch), Some(seq)) => {
+                format!("{}?epoch={}&since={seq}", self.base_url, encode(epoch))
+            }
+            _ => self.base_url.clone(),
+        }
+    }
+
+    // 返回 false 表示是连接层消息（hello/heartbeat），不转发给前端。
+    fn observe(&mut self, payload: &Value) -> bool {
+        let kind = payload.get("type").and_then(|value| value.as_str());
+        if kind == Some("hello") {
+            self.epoch = payload
+                .get("epoch")
+                .and_then(|value| value.as_str())
+                .map(str::to_string);
+        }
+        if let Some(seq) = payload.get("seq").and_then(|value| value.as_u64()) {
+            self.seq = Some(seq);
+        }
+        !matches!(kind, Some("hello") | Some("heartbeat"))
+    }
+}
+
+/// 连接当前环境的 console；已在推送同一环境时直接返回，换了环境则先断开旧连接。
+/// 返回正在推送的环境名，前端据此过滤事件。
 pub async fn start_console_stream(
     app: AppHandle,
+    endpoint: ConsoleEndpoint,
     stream_state: State<'_, ConsoleStreamState>,
     log_state: State<'_, AppLogState>,
-) -> Result<(), String> {
-    let mut running = stream_state.0.lock().unwrap();
-    if *running {
-        return Ok(());
+) -> Result<String, String> {
+    let mut guard = stream_state.0.lock().unwrap();
+    if let Some(current) = guard.as_ref() {
+        if current.profile == endpoint.profile {
+            return Ok(endpoint.profile);
+        }
+    }
+    if let Some(previous) = guard.take() {
+        previous.task.abort();
     }
-    *running = true;
 
     let app_handle = app.clone();
     let log_path = log_state.app_log.clone();
-    tauri::async_runtime::spawn(async move {
+    let profile = endpoint.profile.clone();
+    let _ = append_log_line(
+        &log_path,
+        &format!("ws stream start profile={profile} addr={}", endpoint.addr),
+    );
+    let task = tauri::async_runtime::spawn(async move {
+        let profile = endpoint.profile.clone();
+        let mut cursor = StreamCursor::new(&endpoint);
+        let mut poller = ConsolePoller::new(endpoint);
         loop {
-            emit_ws_status(&app_handle, &log_path, "connecting");
-            match tokio_tungstenite::connect_async(CONSOLE_WS_URL).await {
-                Ok((mut stream, _)) => {
-                    emit_ws_status(&app_handle, &log_path, "connected");
+            if !poller.active {
+                emit_ws_status(&app_handle, &log_path, &profile, "connecting");
+            }
+            match tokio::time::timeout(
+                WS_CONNECT_TIMEOUT,
+                tokio_tungstenite::connect_async(console_ws_request(
+                    cursor.url(),
+                    &poller.endpoint,
+                )),
+            )
+            .await
+            {
+                Ok(Ok((mut stream, _))) => {
+                    poller.reset();
+                    emit_ws_status(&app_handle, &log_path, &profile, "connected");
                     while let Some(message) = stream.next().await {
                         match message {
                             Ok(Message::Text(text)) => match serde_json::from_str::<Value>(&text) {
                                 Ok(payload) => {
-                                    log_ws_event(&log_path, &payload);
-                                    let _ = app_handle.emit("console_event", payload);
+                                    if !cursor.observe(&payload) {
+                                        continue;
+                                    }
+                                    if payload.get("type").and_then(|value| value.as_str())
+                                        == Some("summary_changed")
+                                    {
+                                        emit_summary_changed(&app_handle, &profile, &payload);
+                                    } else {
+                                        log_ws_event(&log_path, &payload);
+                                        emit_console_event(&app_handle, &profile, payload);
+                                    }
                                 }
                                 Err(err) => {
                                     let _ = append_log_line(
@@ -110,14 +281,116 @@ pub async fn start_console_stream(
                             }
                         }
                     }
+                    emit_ws_status(&app_handle, &log_path, &profile, "disconnected");
                 }
-                Err(err) => {
+                Ok(Err(err)) => {
                     let _ = append_log_line(&log_path, &format!("ws connect failed: {err}"));
+                    if !poller.active {
+                        emit_ws_status(&app_handle, &log_path, &profile, "disconnected");
+                    }
+                }
+                Err(_) => {
+                    let _ = append_log_line(&log_path, "ws connect timed out");
+                    if !poller.active {
+                        emit_ws_status(&app_handle, &log_path, &profile, "disconnected");
+                    }
+                }
+            }
+            // WS 不可用时改为 HTTP 轮询，合成与 WS 相同的 console_event，前端无需区分。
+            match poller.poll(&app_handle, &log_path).await {
+                Ok(()) => {
+                    if !poller.active {
+                        poller.active = true;
+                        emit_ws_status(&app_handle, &log_path, &profile, "polling");
+                    }
+                }
+                Err(err) => {
+                    let _ = append_log_line(&log_path, &format!("poll fallback failed: {err}"));
+                    if poller.active {
+                        poller.reset();
+                        emit_ws_status(&app_handle, &log_path, &profile, "disconnected");
+                    }
                 }
             }
-            emit_ws_status(&app_handle, &log_path, "disconnected");
             tokio::time::sleep(WS_RECONNECT_DELAY).await;
         }
     });
-    Ok(())
+    *guard = Some(ConsoleStream {
+        profile: profile.clone(),
+        task,
+    });
+    Ok(profile)
+}
+
+/// 切换环境时断开旧 console 的推送；下一次 start_console_stream 连新地址。
+pub fn stop_console_stream(stream_state: &ConsoleStreamState) {
+    if let Some(previous) = stream_state.0.lock().unwrap().take() {
+        previous.task.abort();
+    }
+}
+
+struct ConsolePoller {
+    endpoint: ConsoleEndpoint,
+    active: bool,
+    sent_snapshot: bool,
+    targets: HashMap<String, Value>,
+    snapshots: HashMap<String, Value>,
+}
+
+impl ConsolePoller {
+    fn new(endpoint: ConsoleEndpoint) -> Self {
+        Self {
+            endpoint,
+            active: false,
+            sent_snapshot: false,
+            targets: HashMap::new(),
+            snapshots: HashMap::new(),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.active = false;
+        self.sent_snapshot = false;
+        self.targets.clear();
+        self.snapshots.clear();
+    }
+
+    async fn poll(&mut self, app: &AppHandle, log_path: &std::path::Path) -> Result<(), String> {
+        let targets = console_get(&self.endpoint, "/targets", log_path).await?;
+        let list = targets
+            .as_array()
+            .ok_or_else(|| "targets response is not an array".to_string())?;
+        if !self.sent_snapshot {
+            emit_console_event(
+                app,
+                &self.endpoint.profile,
+                json!({ "type": "targets_snapshot", "targets": targets }),
+            );
+            self.sent_snapshot = true;
+        }
+        for target in list {
+            let Some(name) = target.get("name").and_then(|value| value.as_str()) else {
+                continue;
+            };
+            let path = format!("/targets/{}/snapshot", urlencoding::encode(name));
+            let snapshot = console_get(&self.endpoint, &path, log_path).await.ok();
+            let target_changed = self.targets.get(name) != Some(target);
+            let snapshot_changed = match snapshot.as_ref() {
+                Some(snapshot) => self.snapshots.get(name) != Some(snapshot),
+                None => false,
+            };
+            if target_changed || snapshot_changed {
+                emit_console_event(
+                    app,
+                    &self.endpoint.profile,
+                    json!({ "type": "target_updated", "target": target }),
+                );
+            }
+            self.targets.insert(name.to_string(), target.clone());
+            if let Some(snapshot) = snapshot {
+                self.snapshots.insert(name.to_string(), snapshot);
+            }
+        }
+        Ok(())
+    }
 }
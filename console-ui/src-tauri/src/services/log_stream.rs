@@ -0,0 +1,254 @@
+use std::io::SeekFrom;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use regex::{Regex, RegexBuilder};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+// 每 100ms 读一次新增内容并合并成一个 log_line 事件，避免刷屏的 sidecar 压垮 IPC。
+const LOG_STREAM_INTERVAL: Duration = Duration::from_millis(100);
+const LOG_STREAM_MAX_READ: usize = 256 * 1024;
+const LOG_STREAM_MAX_LINES: usize = 500;
+
+/// `/pattern/` 按正则匹配，其余按子串匹配，均不区分大小写。
+pub enum LogFilter {
+    Substring(String),
+    Regex(Regex),
+}
+
+impl LogFilter {
+    pub fn parse(filter: Option<&str>) -> Result<Option<Self>, String> {
+        let Some(filter) = filter.map(str::trim).filter(|value| !value.is_empty()) else {
+            return Ok(None);
+        };
+        if let Some(pattern) = filter
+            .strip_prefix('/')
+            .and_then(|rest| rest.strip_suffix('/'))
+            .filter(|pattern| !pattern.is_empty())
+        {
+            let regex = RegexBuilder::new(pattern)
+                .case_insensitive(true)
+                .build()
+                .map_err(|err| format!("invalid log filter: {err}"))?;
+            return Ok(Some(Self::Regex(regex)));
+        }
+        Ok(Some(Self::Substring(filter.to_lowercase())))
+    }
+
+    fn matches(&self, line: &str) -> bool {
+        match self {
+            Self::Substring(needle) => line.to_lowercase().contains(needle),
+            Self::Regex(regex) => regex.is_match(line),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct LogLine<'a> {
+    which: &'a str,
+    line: String,
+    ts: u64,
+}
+
+// 按路径跟踪日志文件：inode 变化（轮转）或长度小于已读位置（截断）时重新打开并从头读。
+struct LogTail {
+    path: PathBuf,
+    file: Option<File>,
+    file_id: u64,
+    pos: u64,
+    partial: Vec<u8>,
+}
+
+impl LogTail {
+    async fn new(path: PathBuf, offset: Option<u64>) -> Self {
+        let len = tokio::fs::metadata(&path)
+            .await
+            .map(|meta| meta.len())
+            .unwrap_or(0);
+        let pos = match offset {
+            Some(offset) if offset <= len => offset,
+            Some(_) => 0,
+            None => len,
+        };
+        Self {
+            path,
+            file: None,
+            file_id: 0,
+            pos,
+            partial: Vec::new(),
+        }
+    }
+
+    async fn read_lines(&mut self) -> Vec<String> {
+        let mut lines = Vec::new();
+        let current = tokio::fs::metadata(&self.path).await.ok();
+        let replaced = match (&current, &self.file) {
+            (Some(meta), Some(_)) => file_id(meta) != self.file_id || meta.len() < self.pos,
+            (None, Some(_)) => true,
+            _ => false,
+        };
+        if replaced {
+            // 轮转前写入旧文件的最后几行先读完。
+            self.read_available(&mut lines).await;
+            if !self.partial.is_empty() {
+                lines.push(decode_line(&std::mem::take(&mut self.partial)));
+            }
+            self.file = None;
+            self.pos = 0;
+        }
+        if self.file.is_none() {
+            let Some(meta) = current else {
+                return lines;
+            };
+            let Ok(mut file) = File::open(&self.path).await else {
+                return lines;
+            };
+            if self.pos > meta.len() {
+                self.pos = 0;
+            }
+            if file.seek(SeekFrom::Start(self.pos)).await.is_err() {
+                return lines;
+            }
+            self.file_id = file_id(&meta);
+            self.file = Some(file);
+        }
+        self.read_available(&mut lines).await;
+        lines
+    }
+
+    async fn read_available(&mut self, lines: &mut Vec<String>) {
+        let Some(file) = self.file.as_mut() else {
+            return;
+        };
+        let mut buffer = vec![0u8; 64 * 1024];
+        let mut total = 0;
+        while total < LOG_STREAM_MAX_READ {
+            let read = match file.read(&mut buffer).await {
+                Ok(0) | Err(_) => break,
+                Ok(read) => read,
+            };
+            total += read;
+            self.pos += read as u64;
+            self.partial.extend_from_slice(&buffer[..read]);
+        }
+        while let Some(index) = self.partial.iter().position(|byte| *byte == b'\n') {
+            let rest = self.partial.split_off(index + 1);
+            let line = std::mem::replace(&mut self.partial, rest);
+            lines.push(decode_line(&line));
+        }
+        // 没有换行的超长内容也按一行推送，避免无限累积。
+        if self.partial.len() > LOG_STREAM_MAX_READ {
+            lines.push(decode_line(&std::mem::take(&mut self.partial)));
+        }
+    }
+}
+
+fn decode_line(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes)
+        .trim_end_matches(['\r', '\n'])
+        .to_string()
+}
+
+#[cfg(unix)]
+fn file_id(meta: &std::fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    meta.ino()
+}
+
+#[cfg(not(unix))]
+fn file_id(_meta: &std::fs::Metadata) -> u64 {
+    0
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// 从 offset（通常是分块回填读到的位置，None 表示文件末尾）开始跟踪日志，
+/// 按 filter 过滤后以 `log_line` 事件推送 `[{ which, line, ts }]`。
+pub fn spawn_log_stream(
+    app: AppHandle,
+    which: String,
+    path: PathBuf,
+    offset: Option<u64>,
+    filter: Option<LogFilter>,
+) -> tauri::async_runtime::JoinHandle<()> {
+    tauri::async_runtime::spawn(async move {
+        let mut tail = LogTail::new(path, offset).await;
+        let mut ticker = tokio::time::interval(LOG_STREAM_INTERVAL);
+        loop {
+            ticker.tick().await;
+            let mut lines: Vec<String> = tail
+                .read_lines()
+                .await
+                .into_iter()
+                .filter(|line| filter.as_ref().map_or(true, |filter| filter.matches(line)))
+                .collect();
+            if lines.is_empty() {
+                continue;
+            }
+            // 单次积压过多时只推送最新的部分。
+            let skip = lines.len().saturating_sub(LOG_STREAM_MAX_LINES);
+            let ts = now_ms();
+            let batch: Vec<LogLine> = lines
+                .drain(skip..)
+                .map(|line| LogLine {
+                    which: &which,
+                    line,
+                    ts,
+                })
+                .collect();
+            let _ = app.emit("log_line", batch);
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filter_matches_substring_or_regex_ignoring_case() {
+        let substring = LogFilter::parse(Some(" Error ")).unwrap().unwrap();
+        assert!(substring.matches("ws connect ERROR: refused"));
+        assert!(!substring.matches("ws connected"));
+        let regex = LogFilter::parse(Some("/^ws (connect|stream) /"))
+            .unwrap()
+            .unwrap();
+        assert!(regex.matches("WS stream error"));
+        assert!(!regex.matches("poll ws stream"));
+        assert!(LogFilter::parse(Some("/[/")).is_err());
+        assert!(LogFilter::parse(Some("  ")).unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn tail_follows_appends_and_truncation() {
+        let path = std::env::temp_dir().join(format!("octovalve-log-tail-{}.log", now_ms()));
+        std::fs::write(&path, "old\n").unwrap();
+        let mut tail = LogTail::new(path.clone(), None).await;
+        assert!(tail.read_lines().await.is_empty());
+
+        let append = |text: &str| {
+            use std::io::Write;
+            let mut file = std::fs::OpenOptions::new()
+                .append(true)
+                .open(&path)
+                .unwrap();
+            file.write_all(text.as_bytes()).unwrap();
+        };
+        append("first\nsec");
+        assert_eq!(tail.read_lines().await, ["first"]);
+        append("ond\r\n");
+        assert_eq!(tail.read_lines().await, ["second"]);
+
+        std::fs::write(&path, "after truncate\n").unwrap();
+        assert_eq!(tail.read_lines().await, ["after truncate"]);
+        let _ = std::fs::remove_file(&path);
+    }
+}
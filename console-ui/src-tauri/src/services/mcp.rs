@@ -1,11 +1,12 @@
 use serde::Serialize;
 use serde_json::Value;
-use tauri::State;
+use tauri::{AppHandle, State};
 
 use crate::clients::McpClientState;
 use crate::paths::resolve_octovalve_proxy_bin;
 use crate::services::console_sidecar::DEFAULT_COMMAND_ADDR;
 use crate::services::mcp_config::{build_octovalve_server, parse_mcp_config_json};
+use crate::services::profiles::control_token_path;
 use crate::state::ProxyConfigState;
 
 #[derive(Debug, Serialize)]
@@ -17,7 +18,23 @@ pub struct McpToolInfo {
     pub input_schema: Value,
 }
 
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct McpResourceInfo {
+    pub server: String,
+    pub uri: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub mime_type: Option<String>,
+}
+
+/// console 尚未生成 token 文件时不传 `--control-token-file`，proxy 启动不会因读文件失败。
+pub(crate) fn existing_control_token_file(app: &AppHandle) -> Option<std::path::PathBuf> {
+    control_token_path(app).ok().filter(|path| path.exists())
+}
+
 pub async fn mcp_set_config(
+    app: AppHandle,
     proxy_state: State<'_, ProxyConfigState>,
     mcp_state: State<'_, McpClientState>,
     config_json: Option<String>,
@@ -30,8 +47,13 @@ pub async fn mcp_set_config(
         if status.present {
             let proxy_config = std::path::PathBuf::from(status.path);
             let proxy_bin = resolve_octovalve_proxy_bin()?;
-            let (spec, value) =
-                build_octovalve_server(&proxy_bin, &proxy_config, DEFAULT_COMMAND_ADDR);
+            let token_file = existing_control_token_file(&app);
+            let (spec, value) = build_octovalve_server(
+                &proxy_bin,
+                &proxy_config,
+                DEFAULT_COMMAND_ADDR,
+                token_file.as_deref(),
+            );
             parsed.servers.push(value);
             parsed.stdio_servers.push(spec);
         }
@@ -42,8 +64,9 @@ pub async fn mcp_set_config(
 
 pub async fn mcp_list_tools(
     mcp_state: State<'_, McpClientState>,
+    server: Option<String>,
 ) -> Result<Vec<McpToolInfo>, String> {
-    let tools = mcp_state.list_tools().await?;
+    let tools = mcp_state.list_tools(server.as_deref()).await?;
     let mut result = Vec::new();
     for (server, tool) in tools {
         let input_schema = Value::Object(tool.input_schema.as_ref().clone());
@@ -57,6 +80,23 @@ pub async fn mcp_list_tools(
     Ok(result)
 }
 
+pub async fn mcp_list_resources(
+    mcp_state: State<'_, McpClientState>,
+    server: Option<String>,
+) -> Result<Vec<McpResourceInfo>, String> {
+    let resources = mcp_state.list_resources(server.as_deref()).await?;
+    Ok(resources
+        .into_iter()
+        .map(|(server, resource)| McpResourceInfo {
+            server,
+            uri: resource.raw.uri,
+            name: resource.raw.name,
+            description: resource.raw.description,
+            mime_type: resource.raw.mime_type,
+        })
+        .collect())
+}
+
 pub async fn mcp_call_tool(
     mcp_state: State<'_, McpClientState>,
     server: Option<String>,
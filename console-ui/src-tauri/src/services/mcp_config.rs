@@ -89,13 +89,18 @@ pub fn build_octovalve_server(
     proxy_bin: &Path,
     proxy_config: &Path,
     command_addr: &str,
+    control_token_file: Option<&Path>,
 ) -> (McpServerSpec, Value) {
-    let args = vec![
+    let mut args = vec![
         "--config".to_string(),
         proxy_config.to_string_lossy().to_string(),
         "--command-addr".to_string(),
         command_addr.to_string(),
     ];
+    if let Some(path) = control_token_file {
+        args.push("--control-token-file".to_string());
+        args.push(path.to_string_lossy().to_string());
+    }
     let spec = McpServerSpec {
         name: "octovalve".to_string(),
         command: proxy_bin.to_path_buf(),
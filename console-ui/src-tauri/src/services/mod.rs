@@ -1,5 +1,6 @@
 pub mod acp;
 pub mod ai_risk;
+pub mod ai_risk_cache;
 pub mod app_error;
 pub mod config;
 pub mod console_http;
@@ -7,6 +8,7 @@ pub mod console_sidecar;
 pub mod console_ws;
 pub mod dev_sidecars;
 pub mod http_utils;
+pub mod log_stream;
 pub mod logging;
 pub mod mcp;
 pub mod mcp_config;
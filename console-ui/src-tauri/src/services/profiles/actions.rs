@@ -1,16 +1,21 @@
 use std::fs;
 use std::path::{Path, PathBuf};
 
-use tauri::{AppHandle, State};
+use tauri::{AppHandle, Manager, State};
 
 use crate::services::config::{
     ensure_file, read_config_file, write_config_file, DEFAULT_BROKER_CONFIG, DEFAULT_PROXY_EXAMPLE,
 };
-use crate::state::{ProfilesState, ProxyConfigState};
+use crate::services::console_ws::stop_console_stream;
+use crate::services::terminal::close_all_terminals;
+use crate::state::{
+    ConsoleEndpointState, ConsoleStreamState, ProfilesState, ProxyConfigState, TerminalSessions,
+};
 use crate::types::ProfileRecord;
 
 use super::index::{
-    current_profile_entry, profile_entry_by_name, validate_profile_name, write_profiles_file,
+    current_profile_entry, profile_console_endpoint, profile_entry_by_name, validate_profile_name,
+    write_profiles_file,
 };
 use super::paths::{
     profile_broker_path, profile_dir_for, profile_proxy_path, profiles_dir, profiles_index_path,
@@ -76,6 +81,10 @@ pub fn create_profile(
         name: name.clone(),
         proxy_path: new_proxy_path.to_string_lossy().to_string(),
         broker_path: new_broker_path.to_string_lossy().to_string(),
+        console_addr: None,
+        external_console: false,
+        console_api_token: None,
+        console_tls: false,
     };
     profiles.profiles.push(record);
     write_profiles_file(&index_path, &profiles)?;
@@ -134,6 +143,11 @@ pub fn select_profile(
     status.path = entry.proxy_path.clone();
     status.present = Path::new(&entry.proxy_path).exists();
     drop(status);
+
+    // 旧环境的推送和终端都挂在旧 console 上，先断开再切地址，前端随后重新 start_console_stream。
+    stop_console_stream(&app.state::<ConsoleStreamState>());
+    close_all_terminals(&app.state::<TerminalSessions>());
+    *app.state::<ConsoleEndpointState>().0.lock().unwrap() = profile_console_endpoint(&entry);
     Ok(())
 }
 
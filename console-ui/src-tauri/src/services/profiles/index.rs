@@ -2,6 +2,8 @@ use std::fs;
 use std::path::Path;
 
 use crate::services::config::write_config_file;
+use crate::services::console_http::DEFAULT_CONSOLE_ADDR;
+use crate::state::ConsoleEndpoint;
 use crate::types::{ProfileRecord, ProfileSummary, ProfilesFile, ProfilesStatus};
 
 pub fn validate_profile_name(name: &str) -> Result<(), String> {
@@ -29,11 +31,54 @@ pub fn profiles_status(data: &ProfilesFile) -> ProfilesStatus {
             .iter()
             .map(|profile| ProfileSummary {
                 name: profile.name.clone(),
+                console_addr: profile_console_addr(profile),
+                external_console: profile.external_console,
             })
             .collect(),
     }
 }
 
+pub fn profile_console_addr(profile: &ProfileRecord) -> String {
+    profile
+        .console_addr
+        .as_deref()
+        .map(str::trim)
+        .filter(|addr| !addr.is_empty())
+        .unwrap_or(DEFAULT_CONSOLE_ADDR)
+        .to_string()
+}
+
+pub fn profile_console_endpoint(profile: &ProfileRecord) -> ConsoleEndpoint {
+    ConsoleEndpoint {
+        profile: profile.name.clone(),
+        addr: profile_console_addr(profile),
+        api_token: profile
+            .console_api_token
+            .as_deref()
+            .map(str::trim)
+            .filter(|token| !token.is_empty())
+            .map(str::to_string),
+        tls: profile.console_tls,
+    }
+}
+
+// 只接受 host:port，scheme 和路径由 http/ws 客户端自己拼。
+fn validate_console_addr(name: &str, addr: &str) -> Result<(), String> {
+    let valid = match addr.trim().rsplit_once(':') {
+        Some((host, port)) => {
+            !host.is_empty() && !host.contains('/') && port.parse::<u16>().is_ok_and(|p| p != 0)
+        }
+        None => false,
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(format!(
+            "环境 {name} 的 console_addr 无效：{addr}（格式为 host:port）"
+        ))
+    }
+}
+
 pub fn current_profile_entry(data: &ProfilesFile) -> Result<ProfileRecord, String> {
     data.profiles
         .iter()
@@ -64,6 +109,30 @@ pub(crate) fn load_profiles_file(path: &Path) -> Result<ProfilesFile, String> {
         if !seen.insert(profile.name.clone()) {
             return Err(format!("重复的环境名称：{}", profile.name));
         }
+        if let Some(addr) = profile.console_addr.as_deref() {
+            validate_console_addr(&profile.name, addr)?;
+        }
+        if profile.external_console && profile.console_addr.is_none() {
+            return Err(format!(
+                "环境 {} 使用外部 console 时必须配置 console_addr",
+                profile.name
+            ));
+        }
+        // 本地 sidecar 没有证书，只有外部 console 才能走 TLS。
+        if profile.console_tls && !profile.external_console {
+            return Err(format!(
+                "环境 {} 的 console_tls 只能用于外部 console（external_console = true）",
+                profile.name
+            ));
+        }
+        if let Some(token) = profile.console_api_token.as_deref() {
+            if !token.trim().chars().all(|ch| ch.is_ascii_graphic()) {
+                return Err(format!(
+                    "环境 {} 的 console_api_token 只能包含可见 ASCII 字符",
+                    profile.name
+                ));
+            }
+        }
     }
     Ok(parsed)
 }
@@ -72,3 +141,18 @@ pub(crate) fn write_profiles_file(path: &Path, data: &ProfilesFile) -> Result<()
     let content = toml::to_string_pretty(data).map_err(|err| err.to_string())?;
     write_config_file(path, &content)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validates_console_addr() {
+        assert!(validate_console_addr("dev", "127.0.0.1:19309").is_ok());
+        assert!(validate_console_addr("dev", "console.internal:8080").is_ok());
+        assert!(validate_console_addr("dev", "[::1]:19309").is_ok());
+        assert!(validate_console_addr("dev", "http://127.0.0.1:19309").is_err());
+        assert!(validate_console_addr("dev", "127.0.0.1").is_err());
+        assert!(validate_console_addr("dev", "127.0.0.1:0").is_err());
+    }
+}
@@ -61,6 +61,10 @@ fn create_default_profile(
         name: name.to_string(),
         proxy_path: proxy_path.to_string_lossy().to_string(),
         broker_path: broker_path.to_string_lossy().to_string(),
+        console_addr: None,
+        external_console: false,
+        console_api_token: None,
+        console_tls: false,
     })
 }
 
@@ -166,7 +170,7 @@ pub fn resolve_broker_config_path(
             if raw.trim().is_empty() {
                 // fall back to proxy config resolution
             } else {
-                let mut path = expand_tilde_path(app, &raw)?;
+                let mut path = expand_tilde_path(app, &raw);
                 if !path.is_absolute() {
                     let base = profiles_index_path(app)?;
                     path = resolve_config_path(app, &base, &raw)?;
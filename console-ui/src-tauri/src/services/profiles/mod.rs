@@ -8,11 +8,12 @@ pub use actions::{
     select_profile, write_profile_broker_config, write_profile_proxy_config,
 };
 pub use index::{
-    current_profile_entry, profile_entry_by_name, profiles_status, validate_profile_name,
+    current_profile_entry, profile_console_addr, profile_console_endpoint, profile_entry_by_name,
+    profiles_status, validate_profile_name,
 };
 pub use lifecycle::{prepare_profiles, resolve_broker_config_path};
 pub use paths::{
-    expand_tilde_path, legacy_proxy_config_path, octovalve_dir, profile_broker_path,
-    profile_proxy_path, profiles_dir, profiles_index_path, resolve_config_path,
-    resolve_profile_path,
+    control_token_path, expand_tilde_path, legacy_proxy_config_path, octovalve_dir,
+    profile_broker_path, profile_proxy_path, profiles_dir, profiles_index_path,
+    resolve_config_path, resolve_profile_path,
 };
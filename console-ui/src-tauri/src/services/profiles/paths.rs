@@ -1,5 +1,6 @@
 use std::path::{Path, PathBuf};
 
+use system_utils::path::expand_tilde_with;
 use tauri::{AppHandle, Manager};
 
 use crate::types::ProfileRecord;
@@ -9,6 +10,12 @@ pub fn octovalve_dir(app: &AppHandle) -> Result<PathBuf, String> {
     Ok(home.join(".octovalve"))
 }
 
+/// console 启动时写入的命令通道 token，proxy 通过 `--control-token-file` 读取。
+pub fn control_token_path(app: &AppHandle) -> Result<PathBuf, String> {
+    let config_dir = app.path().app_config_dir().map_err(|err| err.to_string())?;
+    Ok(config_dir.join("control-token"))
+}
+
 pub fn profiles_dir(app: &AppHandle) -> Result<PathBuf, String> {
     Ok(octovalve_dir(app)?.join("profiles"))
 }
@@ -26,7 +33,7 @@ pub(crate) fn profile_dir_for(base: &Path, name: &str) -> PathBuf {
 }
 
 pub fn resolve_config_path(app: &AppHandle, base: &Path, value: &str) -> Result<PathBuf, String> {
-    let expanded = expand_tilde_path(app, value)?;
+    let expanded = expand_tilde_path(app, value);
     if expanded.is_absolute() {
         return Ok(expanded);
     }
@@ -37,7 +44,7 @@ pub fn resolve_config_path(app: &AppHandle, base: &Path, value: &str) -> Result<
 }
 
 pub fn resolve_profile_path(app: &AppHandle, value: &str) -> Result<PathBuf, String> {
-    let expanded = expand_tilde_path(app, value)?;
+    let expanded = expand_tilde_path(app, value);
     if expanded.is_absolute() {
         return Ok(expanded);
     }
@@ -53,13 +60,6 @@ pub fn profile_broker_path(app: &AppHandle, profile: &ProfileRecord) -> Result<P
     resolve_profile_path(app, &profile.broker_path)
 }
 
-pub fn expand_tilde_path(app: &AppHandle, value: &str) -> Result<PathBuf, String> {
-    if value == "~" {
-        return app.path().home_dir().map_err(|err| err.to_string());
-    }
-    if let Some(rest) = value.strip_prefix("~/") {
-        let home = app.path().home_dir().map_err(|err| err.to_string())?;
-        return Ok(home.join(rest));
-    }
-    Ok(PathBuf::from(value))
+pub fn expand_tilde_path(app: &AppHandle, value: &str) -> PathBuf {
+    expand_tilde_with(value, || app.path().home_dir().ok())
 }
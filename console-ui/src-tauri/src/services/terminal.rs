@@ -1,23 +1,42 @@
 use futures_util::{SinkExt, StreamExt};
-use serde_json::json;
+use serde_json::{json, Value};
 use tauri::{AppHandle, Emitter, Manager, State};
 use tokio::sync::mpsc;
 use tokio_tungstenite::tungstenite::Message;
 use uuid::Uuid;
 
-use crate::services::console_http::CONSOLE_HTTP_HOST;
+use crate::services::console_http::{console_get, console_post_json};
+use crate::services::console_ws::console_ws_request;
 use crate::services::logging::append_log_line;
-use crate::state::TerminalSessions;
+use crate::state::{ConsoleEndpoint, ConsoleEndpointState, TerminalSessions};
 use crate::types::terminal::TerminalMessage;
 
 pub const DEFAULT_TERM: &str = "xterm-256color";
 
-fn console_terminal_url(name: &str, cols: u16, rows: u16, term: &str) -> String {
+fn console_terminal_url(
+    endpoint: &ConsoleEndpoint,
+    name: &str,
+    cols: u16,
+    rows: u16,
+    term: &str,
+) -> String {
+    let base = endpoint.ws_base();
     let encoded_name = urlencoding::encode(name);
     let encoded_term = urlencoding::encode(term);
-    format!(
-        "ws://{CONSOLE_HTTP_HOST}/targets/{encoded_name}/terminal?cols={cols}&rows={rows}&term={encoded_term}"
-    )
+    format!("{base}/targets/{encoded_name}/terminal?cols={cols}&rows={rows}&term={encoded_term}")
+}
+
+fn console_terminal_attach_url(
+    endpoint: &ConsoleEndpoint,
+    name: &str,
+    attach_id: &str,
+    mode: &str,
+) -> String {
+    let base = endpoint.ws_base();
+    let encoded_name = urlencoding::encode(name);
+    let encoded_id = urlencoding::encode(attach_id);
+    let encoded_mode = urlencoding::encode(mode);
+    format!("{base}/targets/{encoded_name}/terminal?attach={encoded_id}&mode={encoded_mode}")
 }
 
 fn send_terminal_message(
@@ -35,11 +54,23 @@ fn send_terminal_message(
         .map_err(|_| "terminal session unavailable".to_string())
 }
 
+/// 列出 console 上该目标仍在运行的终端会话；`detached` 的会话可通过 `terminal_open` 重新接管。
+pub async fn terminal_list(
+    target: String,
+    app: AppHandle,
+    log_state: State<'_, crate::state::AppLogState>,
+) -> Result<Value, String> {
+    let endpoint = app.state::<ConsoleEndpointState>().current();
+    let path = format!("/targets/{}/terminals", urlencoding::encode(&target));
+    console_get(&endpoint, &path, &log_state.app_log).await
+}
+
 pub async fn terminal_open(
     name: String,
     cols: u16,
     rows: u16,
     term: Option<String>,
+    reattach_session_id: Option<String>,
     app: AppHandle,
     sessions: State<'_, TerminalSessions>,
     log_state: State<'_, crate::state::AppLogState>,
@@ -55,8 +86,85 @@ pub async fn terminal_open(
             }
         })
         .unwrap_or_else(|| DEFAULT_TERM.to_string());
-    let url = console_terminal_url(&name, cols, rows, &term);
-    let (stream, _) = tokio_tungstenite::connect_async(url)
+    let endpoint = app.state::<ConsoleEndpointState>().current();
+    let Some(console_session_id) = reattach_session_id else {
+        let url = console_terminal_url(&endpoint, &name, cols, rows, &term);
+        return connect_terminal(url, &endpoint, app, sessions, log_state).await;
+    };
+    // 接管应用重启前留下的会话：console 先回放 scrollback，再按当前窗口大小调整 PTY。
+    let _ = append_log_line(
+        &log_state.app_log,
+        &format!("terminal reattach target={name} session={console_session_id}"),
+    );
+    let url = console_terminal_attach_url(&endpoint, &name, &console_session_id, "primary");
+    let session_id = connect_terminal(url, &endpoint, app.clone(), sessions, log_state).await?;
+    terminal_resize(
+        session_id.clone(),
+        cols,
+        rows,
+        app.state::<TerminalSessions>(),
+    )?;
+    Ok(session_id)
+}
+
+pub async fn terminal_attach(
+    name: String,
+    attach_id: String,
+    mode: Option<String>,
+    app: AppHandle,
+    sessions: State<'_, TerminalSessions>,
+    log_state: State<'_, crate::state::AppLogState>,
+) -> Result<String, String> {
+    let mode = match mode.as_deref() {
+        Some("rw") => "rw",
+        _ => "readonly",
+    };
+    let _ = append_log_line(
+        &log_state.app_log,
+        &format!("terminal attach target={name} session={attach_id} mode={mode}"),
+    );
+    let endpoint = app.state::<ConsoleEndpointState>().current();
+    let url = console_terminal_attach_url(&endpoint, &name, &attach_id, mode);
+    connect_terminal(url, &endpoint, app, sessions, log_state).await
+}
+
+/// 经 console 校验 readonly_whitelist 后写入终端会话；拒绝时错误里带着 console 给出的原因。
+pub async fn terminal_inject(
+    name: String,
+    console_session_id: String,
+    command: String,
+    actor: Option<String>,
+    app: AppHandle,
+    log_state: State<'_, crate::state::AppLogState>,
+) -> Result<(), String> {
+    let _ = append_log_line(
+        &log_state.app_log,
+        &format!("terminal inject target={name} session={console_session_id} command={command}"),
+    );
+    let endpoint = app.state::<ConsoleEndpointState>().current();
+    let path = format!(
+        "/targets/{}/terminal/{}/inject",
+        urlencoding::encode(&name),
+        urlencoding::encode(&console_session_id)
+    );
+    console_post_json(
+        &endpoint,
+        &path,
+        json!({ "command": command, "actor": actor }),
+        &log_state.app_log,
+    )
+    .await
+    .map(|_| ())
+}
+
+async fn connect_terminal(
+    url: String,
+    endpoint: &ConsoleEndpoint,
+    app: AppHandle,
+    sessions: State<'_, TerminalSessions>,
+    log_state: State<'_, crate::state::AppLogState>,
+) -> Result<String, String> {
+    let (stream, _) = tokio_tungstenite::connect_async(console_ws_request(url, endpoint))
         .await
         .map_err(|err| err.to_string())?;
     let (mut ws_tx, mut ws_rx) = stream.split();
@@ -97,10 +205,10 @@ pub async fn terminal_open(
                             json!({ "session_id": &session_id_for_read, "data": data }),
                         );
                     }
-                    Ok(TerminalMessage::Exit { code }) => {
+                    Ok(TerminalMessage::Exit { code, reason }) => {
                         let _ = app_handle_for_read.emit(
                             "terminal_exit",
-                            json!({ "session_id": &session_id_for_read, "code": code }),
+                            json!({ "session_id": &session_id_for_read, "code": code, "reason": reason }),
                         );
                         closed = true;
                         break;
@@ -113,10 +221,30 @@ pub async fn terminal_open(
                         closed = true;
                         break;
                     }
-                    Ok(TerminalMessage::Ready { cols, rows, term }) => {
+                    Ok(TerminalMessage::Ready {
+                        cols,
+                        rows,
+                        term,
+                        session_id,
+                        mode,
+                    }) => {
+                        let console_session = session_id.unwrap_or_default();
+                        let mode = mode.unwrap_or_default();
                         let _ = append_log_line(
                             &log_path,
-                            &format!("terminal ready cols={cols} rows={rows} term={term}"),
+                            &format!(
+                                "terminal ready cols={cols} rows={rows} term={term} console_session={console_session} mode={mode}"
+                            ),
+                        );
+                        let _ = app_handle_for_read.emit(
+                            "terminal_ready",
+                            json!({
+                                "session_id": &session_id_for_read,
+                                "console_session_id": console_session,
+                                "mode": mode,
+                                "cols": cols,
+                                "rows": rows,
+                            }),
                         );
                     }
                     Err(err) => {
@@ -127,6 +255,7 @@ pub async fn terminal_open(
                     closed = true;
                     break;
                 }
+                // tungstenite 读取时自动回复 Ping，console 以此判断客户端是否还在。
                 Ok(Message::Binary(_)) | Ok(Message::Ping(_)) | Ok(Message::Pong(_)) => {}
                 Ok(Message::Frame(_)) => {}
                 Err(err) => {
@@ -181,6 +310,14 @@ pub fn terminal_resize(
     )
 }
 
+/// 切换环境时关闭所有终端会话，旧 console 上的 shell 随之退出。
+pub fn close_all_terminals(sessions: &TerminalSessions) {
+    let message = json!({ "type": "close" }).to_string();
+    for (_, session) in sessions.0.lock().unwrap().drain() {
+        let _ = session.tx.send(message.clone());
+    }
+}
+
 pub fn terminal_close(
     session_id: String,
     sessions: State<'_, TerminalSessions>,
@@ -15,7 +15,44 @@ pub struct ConsoleSidecar {
 
 pub struct ConsoleSidecarState(pub Mutex<Option<ConsoleSidecar>>);
 pub struct ConsoleRestartLock(pub Mutex<()>);
-pub struct ConsoleStreamState(pub Mutex<bool>);
+pub struct ConsoleStream {
+    pub profile: String,
+    pub task: tauri::async_runtime::JoinHandle<()>,
+}
+
+pub struct ConsoleStreamState(pub Mutex<Option<ConsoleStream>>);
+
+/// 按日志来源（`console` / `app`）保存正在运行的 tail 任务。
+pub struct LogStreamState(pub Mutex<HashMap<String, tauri::async_runtime::JoinHandle<()>>>);
+
+/// 当前环境连接的 console；http/ws/terminal 都从这里取地址。
+#[derive(Clone)]
+pub struct ConsoleEndpoint {
+    pub profile: String,
+    pub addr: String,
+    pub api_token: Option<String>,
+    pub tls: bool,
+}
+
+impl ConsoleEndpoint {
+    pub fn http_base(&self) -> String {
+        let scheme = if self.tls { "https" } else { "http" };
+        format!("{scheme}://{}", self.addr)
+    }
+
+    pub fn ws_base(&self) -> String {
+        let scheme = if self.tls { "wss" } else { "ws" };
+        format!("{scheme}://{}", self.addr)
+    }
+}
+
+pub struct ConsoleEndpointState(pub Mutex<ConsoleEndpoint>);
+
+impl ConsoleEndpointState {
+    pub fn current(&self) -> ConsoleEndpoint {
+        self.0.lock().unwrap().clone()
+    }
+}
 pub struct ProxyConfigState(pub Mutex<ProxyConfigStatus>);
 pub struct ProfilesState(pub Mutex<ProfilesFile>);
 
@@ -30,3 +67,5 @@ pub struct AppLogState {
 }
 
 pub struct AppLanguageState(pub Mutex<Option<String>>);
+
+pub struct AiRiskCacheState(pub crate::services::ai_risk_cache::AiRiskCache);
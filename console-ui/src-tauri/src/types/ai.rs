@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Deserialize)]
@@ -8,6 +10,23 @@ pub struct AiRiskRequest {
     pub api_key: String,
     pub prompt: String,
     pub timeout_ms: Option<u64>,
+    /// 缺省或为 `Authorization` 时按 Bearer 发送；其他名称（如 Azure 的 `api-key`）直接放 api_key。
+    #[serde(default)]
+    pub auth_header_name: Option<String>,
+    #[serde(default)]
+    pub extra_headers: Option<HashMap<String, String>>,
+    #[serde(default)]
+    pub stream: Option<bool>,
+    /// 用于缓存键的原始命令与目标；不传 command 时不走缓存。
+    #[serde(default)]
+    pub command: Option<String>,
+    #[serde(default)]
+    pub target: Option<String>,
+    /// 缓存有效期，缺省 7 天；0 表示不缓存。
+    #[serde(default)]
+    pub cache_ttl_secs: Option<u64>,
+    #[serde(default)]
+    pub force_refresh: bool,
 }
 
 #[derive(Debug, Deserialize)]
@@ -18,9 +37,19 @@ pub struct AiRiskModelResponse {
     pub key_points: Option<Vec<String>>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AiRiskResponse {
     pub risk: String,
     pub reason: String,
     pub key_points: Vec<String>,
 }
+
+#[derive(Debug, Serialize)]
+pub struct AiRiskCacheStats {
+    pub entries: usize,
+    pub expired: usize,
+    /// 本次启动以来的命中/未命中次数。
+    pub hits: u64,
+    pub misses: u64,
+    pub path: String,
+}
@@ -2,7 +2,9 @@ use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 use std::path::PathBuf;
 
-use protocol::config::{ProxyDefaults, TargetConfig};
+use protocol::config::{
+    CommandSchedule, CommandTemplate, ConfigDiagnostic, ProxyDefaults, TargetConfig,
+};
 
 #[derive(Clone, Serialize)]
 pub struct ProxyConfigStatus {
@@ -29,10 +31,19 @@ pub struct ProxyConfigEditor {
     pub broker_config_path: Option<String>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub default_target: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub aliases: BTreeMap<String, String>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub defaults: Option<ProxyDefaults>,
     #[serde(default)]
     pub targets: Vec<TargetConfig>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub templates: Vec<CommandTemplate>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub schedules: Vec<CommandSchedule>,
+    /// 解析后的结构检查结果，只返回给前端，不来自 TOML。
+    #[serde(default, skip_deserializing, skip_serializing_if = "Vec::is_empty")]
+    pub diagnostics: Vec<ConfigDiagnostic>,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -43,6 +54,70 @@ pub struct BrokerConfigEditor {
     pub limits: BrokerLimitsConfig,
     #[serde(default = "default_auto_approve_allowed")]
     pub auto_approve_allowed: bool,
+    #[serde(default)]
+    pub require_intent: bool,
+    #[serde(default)]
+    pub min_intent_length: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub banned_intents: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub control_token: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retention: Option<BrokerRetentionConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub redaction: Option<BrokerRedactionConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quota: Option<BrokerQuotaConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub readonly_whitelist: Option<BrokerReadonlyWhitelistConfig>,
+    #[serde(default, skip_deserializing, skip_serializing_if = "Vec::is_empty")]
+    pub diagnostics: Vec<ConfigDiagnostic>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, Default)]
+pub struct BrokerRetentionConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_total_bytes: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_age_days: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_files: Option<u64>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, Default)]
+pub struct BrokerRedactionConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub env_patterns: Option<Vec<String>>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, Default)]
+pub struct BrokerReadonlyWhitelistConfig {
+    #[serde(default)]
+    pub allowed: Vec<String>,
+    #[serde(default)]
+    pub denied: Vec<String>,
+    #[serde(default)]
+    pub arg_rules: BTreeMap<String, String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, Default)]
+pub struct BrokerQuotaConfig {
+    #[serde(flatten)]
+    pub limits: BrokerQuotaLimits,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub per_client: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exempt_templates: Option<bool>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub targets: BTreeMap<String, BrokerQuotaLimits>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, Default)]
+pub struct BrokerQuotaLimits {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_daily_commands: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_daily_execution_secs: Option<u64>,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize, Default)]
@@ -53,12 +128,54 @@ pub struct BrokerWhitelistConfig {
     pub denied: Vec<String>,
     #[serde(default)]
     pub arg_rules: BTreeMap<String, String>,
+    #[serde(default)]
+    pub auto_approve_tags: Vec<String>,
+    #[serde(default)]
+    pub dangerous_patterns: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_unparseable: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hard_deny: Option<BrokerHardDenyConfig>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub time_windows: Vec<BrokerTimeWindowConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, Default)]
+pub struct BrokerTimeWindowConfig {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub commands: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub intent_tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allowed_hours: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_days: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deny_outside_window: Option<bool>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, Default)]
+pub struct BrokerHardDenyConfig {
+    #[serde(default)]
+    pub commands: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub immutable: Option<bool>,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct BrokerLimitsConfig {
     pub timeout_secs: u64,
     pub max_output_bytes: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nice: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_cpu_secs: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_memory_bytes: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_file_size_bytes: Option<u64>,
 }
 
 impl Default for BrokerLimitsConfig {
@@ -66,6 +183,10 @@ impl Default for BrokerLimitsConfig {
         Self {
             timeout_secs: 30,
             max_output_bytes: 1024 * 1024,
+            nice: None,
+            max_cpu_secs: None,
+            max_memory_bytes: None,
+            max_file_size_bytes: None,
         }
     }
 }
@@ -5,6 +5,22 @@ pub struct ProfileRecord {
     pub name: String,
     pub proxy_path: String,
     pub broker_path: String,
+    /// console 的 HTTP/WS 地址（host:port），缺省为本地 sidecar 的 127.0.0.1:19309。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub console_addr: Option<String>,
+    /// 为 true 时 console 由外部管理，切换到该环境不启动本地 sidecar。
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub external_console: bool,
+    /// console 以 --api-token 启动时需要的 bearer token；本地 sidecar 也会用它启动。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub console_api_token: Option<String>,
+    /// console 以 --tls-cert/--tls-key 提供 HTTPS 时设为 true，改用 https/wss 连接。
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub console_tls: bool,
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
 }
 
 #[derive(Clone, Deserialize, Serialize)]
@@ -16,6 +32,8 @@ pub struct ProfilesFile {
 #[derive(Clone, Serialize)]
 pub struct ProfileSummary {
     pub name: String,
+    pub console_addr: String,
+    pub external_console: bool,
 }
 
 #[derive(Clone, Serialize)]
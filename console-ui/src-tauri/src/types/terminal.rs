@@ -3,8 +3,24 @@ use serde::Deserialize;
 #[derive(Debug, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum TerminalMessage {
-    Ready { cols: u16, rows: u16, term: String },
-    Output { data: String },
-    Exit { code: Option<i32> },
-    Error { message: String },
+    Ready {
+        cols: u16,
+        rows: u16,
+        term: String,
+        #[serde(default)]
+        session_id: Option<String>,
+        #[serde(default)]
+        mode: Option<String>,
+    },
+    Output {
+        data: String,
+    },
+    Exit {
+        code: Option<i32>,
+        #[serde(default)]
+        reason: Option<String>,
+    },
+    Error {
+        message: String,
+    },
 }
@@ -10,6 +10,7 @@ const AI_RISK_CACHE_LIMIT = 2000;
 type AiTask = {
   target: string;
   request: RequestSnapshot;
+  forceRefresh?: boolean;
 };
 
 type AiRiskQueueOptions = {
@@ -153,7 +154,7 @@ export function useAiRiskQueue({ settings, onError }: AiRiskQueueOptions) {
     });
   }
 
-  function enqueueAiTask(targetName: string, request: RequestSnapshot) {
+  function enqueueAiTask(targetName: string, request: RequestSnapshot, forceRefresh = false) {
     if (!settings.value.ai.enabled) {
       return;
     }
@@ -170,7 +171,7 @@ export function useAiRiskQueue({ settings, onError }: AiRiskQueueOptions) {
       return;
     }
     aiQueuedKeys.add(key);
-    aiQueue.value = [...aiQueue.value, { target: targetName, request }];
+    aiQueue.value = [...aiQueue.value, { target: targetName, request, forceRefresh }];
     setAiRisk(key, { status: 'pending', updatedAt: Date.now() });
     scheduleAiQueue();
   }
@@ -236,6 +237,10 @@ export function useAiRiskQueue({ settings, onError }: AiRiskQueueOptions) {
         api_key: apiKey,
         prompt,
         timeout_ms: settings.value.ai.timeoutMs,
+        command: task.request.raw_command,
+        target: task.target,
+        cache_ttl_secs: settings.value.ai.cacheTtlDays * 24 * 60 * 60,
+        force_refresh: task.forceRefresh,
       });
       applyAiResult(key, response);
     } catch (err) {
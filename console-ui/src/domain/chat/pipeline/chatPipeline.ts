@@ -111,7 +111,19 @@ export function parseAcpHistory(
       content = stripOctovalveToolContext(content);
     }
 
-    if (!content.trim()) {
+    // acp-codex replays image inputs as their own entries: inline base64, a file:// uri,
+    // or only a placeholder content when the file is gone.
+    const images: string[] = [];
+    if ((obj?.content_type ?? obj?.contentType) === 'image') {
+      const mimeType = String(obj?.mime_type ?? obj?.mimeType ?? 'image/png');
+      if (typeof obj?.data === 'string' && obj.data) {
+        images.push(`data:${mimeType};base64,${obj.data}`);
+      } else if (typeof obj?.uri === 'string' && obj.uri) {
+        images.push(obj.uri);
+      }
+    }
+
+    if (!content.trim() && images.length === 0) {
       continue;
     }
 
@@ -129,6 +141,7 @@ export function parseAcpHistory(
       say: 'text',
       role,
       content,
+      ...(images.length > 0 ? { images } : {}),
       status: 'complete',
       partial: false,
     });
@@ -36,7 +36,7 @@ type AcpService = {
   authenticate: (methodId: string) => Promise<void>;
   newSession: (cwd: string) => Promise<{ sessionId: string }>;
   loadSession: (sessionId: string) => Promise<{ history: unknown }>;
-  listSessions: () => Promise<{ sessions?: AcpSessionSummary[] }>;
+  listSessions: () => Promise<{ sessions?: AcpSessionSummary[]; warnings?: { path: string; error: string }[] }>;
   deleteSession: (sessionId: string) => Promise<void>;
   prompt: (prompt: AcpContentBlock[], context?: ContextItem[]) => Promise<void>;
   cancel: () => Promise<void>;
@@ -190,6 +190,9 @@ export function createAcpProvider(context: AcpProviderContext, deps: AcpProvider
     try {
       const result = await deps.acpService.listSessions();
       context.acpHistorySummaries.value = result.sessions ?? [];
+      if (result.warnings?.length) {
+        console.warn('[acpProvider] skipped unreadable ACP sessions:', result.warnings);
+      }
     } catch (e) {
       console.warn('[acpProvider] load ACP history failed:', e);
       context.acpHistorySummaries.value = [];
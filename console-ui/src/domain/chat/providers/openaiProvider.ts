@@ -7,7 +7,11 @@ import type {
   ToolCall,
 } from '../types';
 import type { TargetInfo } from '../../../shared/types';
-import { buildOpenAiToolsFromMcp, type McpToolInfo } from '../../../shared/mcpTools';
+import {
+  buildOpenAiToolsFromMcp,
+  type McpListChangedEvent,
+  type McpToolInfo,
+} from '../../../shared/mcpTools';
 import {
   buildPromptBlocks,
   toDisplayFiles,
@@ -61,6 +65,7 @@ type OpenaiProviderDeps = {
     setConfig: (configJson: string) => Promise<void>;
     listTools: () => Promise<McpToolInfo[]>;
     callTool: (server: string, name: string, args: Record<string, unknown>) => Promise<unknown>;
+    onToolsChanged: (handler: (event: McpListChangedEvent) => void) => Promise<() => void>;
   };
   t: (key: string, params?: Record<string, unknown>) => string;
 };
@@ -78,6 +83,7 @@ export function createOpenaiProvider(context: OpenaiProviderContext, deps: Opena
   const lastTargetSignature = ref('');
   const mcpToolMapping = new Map<string, { server: string; name: string }>();
   let mcpConfigJson = '';
+  let mcpToolsChangedUnlisten: (() => void) | null = null;
 
   function enqueueOpenaiContextOp(op: () => Promise<void>) {
     openaiContextQueue = openaiContextQueue
@@ -252,6 +258,40 @@ export function createOpenaiProvider(context: OpenaiProviderContext, deps: Opena
     });
   }
 
+  // server 增删或改名工具时按最新列表重建 schema，不必等下一次 targets 变化。
+  function setupMcpToolsChangedListener() {
+    const token = openaiListenerToken;
+    if (mcpToolsChangedUnlisten) {
+      mcpToolsChangedUnlisten();
+      mcpToolsChangedUnlisten = null;
+    }
+    deps.mcpService
+      .onToolsChanged((event) => {
+        if (event.kind !== 'tools' || !context.openaiInitialized.value) {
+          return;
+        }
+        void reloadOpenaiTools();
+      })
+      .then((unlisten) => {
+        if (openaiListenerToken !== token) {
+          unlisten();
+          return;
+        }
+        mcpToolsChangedUnlisten = unlisten;
+      });
+  }
+
+  async function reloadOpenaiTools() {
+    try {
+      // 同名工具的 schema 也可能变了，这里不按签名跳过。
+      const { tools, signature } = await loadMcpTools();
+      await deps.openaiService.setTools(tools);
+      openaiToolsSignature.value = signature;
+    } catch (e) {
+      console.warn('[openaiProvider] reload tools after list_changed failed:', e);
+    }
+  }
+
   function handleOpenaiStreamEvent(event: ChatStreamEvent) {
     if (event.eventType === 'content' && event.content) {
       if (context.currentAssistantMessageId.value) {
@@ -428,14 +468,11 @@ export function createOpenaiProvider(context: OpenaiProviderContext, deps: Opena
       return;
     }
 
+    // 每个 tool_call_id 都要有对应的 tool 消息，缺一条下一轮请求就会被接口拒绝。
     for (const tc of toolCalls) {
-      if (context.isToolCallCancelled(messageId, tc.id)) {
-        continue;
-      }
-      const text = results.get(tc.id);
-      if (!text) {
-        continue;
-      }
+      const text = context.isToolCallCancelled(messageId, tc.id)
+        ? deps.t('chat.tool.result.missing', { status: 'cancelled' })
+        : results.get(tc.id) || deps.t('chat.tool.result.missing', { status: 'completed' });
       await enqueueOpenaiContextOp(async () => {
         await deps.openaiService.addMessage({
           role: 'tool',
@@ -495,6 +532,7 @@ export function createOpenaiProvider(context: OpenaiProviderContext, deps: Opena
       context.providerInitialized.value = true;
       context.provider.value = 'openai';
       setupOpenaiEventListener();
+      setupMcpToolsChangedListener();
       context.setConnected(true);
       await syncOpenaiContextForSession(context.activeSession.value);
       console.log('[chatStore] initializeOpenai done');
@@ -661,6 +699,10 @@ export function createOpenaiProvider(context: OpenaiProviderContext, deps: Opena
       openaiEventUnlisten();
       openaiEventUnlisten = null;
     }
+    if (mcpToolsChangedUnlisten) {
+      mcpToolsChangedUnlisten();
+      mcpToolsChangedUnlisten = null;
+    }
     context.openaiInitialized.value = false;
     context.providerInitialized.value = false;
     context.setConnected(false);
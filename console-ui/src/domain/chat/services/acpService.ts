@@ -64,10 +64,13 @@ export interface AcpSessionSummary {
   createdAt: number;
   updatedAt: number;
   messageCount: number;
+  model?: string | null;
+  firstPrompt?: string | null;
 }
 
 export interface AcpListSessionsResult {
   sessions: AcpSessionSummary[];
+  warnings?: { path: string; error: string }[];
 }
 
 export interface ContextItem {
@@ -1,12 +1,32 @@
 import { invoke } from '@tauri-apps/api/core';
-import type { McpToolInfo } from '../../../shared/mcpTools';
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
+import type {
+  McpListChangedEvent,
+  McpResourceInfo,
+  McpToolInfo,
+} from '../../../shared/mcpTools';
 
 export async function mcpSetConfig(configJson: string): Promise<void> {
   return invoke('mcp_set_config', { configJson });
 }
 
-export async function mcpListTools(): Promise<McpToolInfo[]> {
-  return invoke<McpToolInfo[]>('mcp_list_tools');
+export async function mcpListTools(server?: string): Promise<McpToolInfo[]> {
+  return invoke<McpToolInfo[]>('mcp_list_tools', { server: server ?? null });
+}
+
+export async function mcpListResources(server?: string): Promise<McpResourceInfo[]> {
+  return invoke<McpResourceInfo[]>('mcp_list_resources', { server: server ?? null });
+}
+
+/**
+ * Fired when a server sends a tools/resources `list_changed` notification
+ */
+export function onMcpToolsChanged(
+  callback: (event: McpListChangedEvent) => void
+): Promise<UnlistenFn> {
+  return listen<McpListChangedEvent>('mcp_tools_changed', (e) => {
+    callback(e.payload);
+  });
 }
 
 export async function mcpCallTool(
@@ -20,5 +40,7 @@ export async function mcpCallTool(
 export const mcpService = {
   setConfig: mcpSetConfig,
   listTools: mcpListTools,
+  listResources: mcpListResources,
   callTool: mcpCallTool,
+  onToolsChanged: onMcpToolsChanged,
 };
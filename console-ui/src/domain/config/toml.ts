@@ -1,4 +1,10 @@
-import type { BrokerConfigEditor, ProxyConfigEditor, ProxyTargetConfig } from '../../shared/types';
+import type {
+  BrokerConfigEditor,
+  CommandScheduleConfig,
+  CommandTemplateConfig,
+  ProxyConfigEditor,
+  ProxyTargetConfig,
+} from '../../shared/types';
 
 function isBlank(value: string | null | undefined) {
   return value === null || value === undefined || value.trim() === '';
@@ -13,6 +19,10 @@ function tomlString(value: string) {
   return JSON.stringify(value);
 }
 
+function tomlKey(key: string) {
+  return /^[A-Za-z0-9_-]+$/.test(key) ? key : tomlString(key);
+}
+
 function writeStringArray(key: string, values: Array<string | null | undefined>): string[] {
   const filtered = values
     .map((value) => value?.trim())
@@ -41,7 +51,7 @@ function writeInlineStringMap(key: string, map: Record<string, string> | null |
     return [];
   }
   entries.sort((a, b) => a[0].localeCompare(b[0]));
-  const rendered = entries.map(([k, v]) => `${k} = ${tomlString(v)}`).join(', ');
+  const rendered = entries.map(([k, v]) => `${tomlKey(k)} = ${tomlString(v)}`).join(', ');
   return [`${key} = { ${rendered} }`];
 }
 
@@ -85,9 +95,39 @@ function serializeTarget(target: ProxyTargetConfig): string[] {
     lines.push(...writeStringArray('ssh_args', sshArgs));
   }
   pushIf(lines, 'ssh_password', target.ssh_password);
+  pushIf(lines, 'ssh_password_encrypted', target.ssh_password_encrypted);
+  pushIf(lines, 'ssh_host_key_policy', target.ssh_host_key_policy);
+  pushIf(lines, 'ssh_host_key', target.ssh_host_key);
   pushIf(lines, 'terminal_locale', target.terminal_locale);
   pushIf(lines, 'tty', target.tty);
+  pushIf(lines, 'default_cwd', target.default_cwd);
+  pushIf(lines, 'queue_when_unreachable', target.queue_when_unreachable);
+  pushIf(lines, 'banner', target.banner);
+
+  return lines;
+}
+
+function serializeTemplate(template: CommandTemplateConfig): string[] {
+  const lines: string[] = [];
+  lines.push('[[templates]]');
+  lines.push(`name = ${tomlString(template.name ?? '')}`);
+  pushIf(lines, 'description', template.description);
+  pushIf(lines, 'target', template.target);
+  lines.push(`raw_command = ${tomlString(template.raw_command ?? '')}`);
+  lines.push(...writeInlineStringMap('params', template.params));
+  return lines;
+}
 
+function serializeSchedule(schedule: CommandScheduleConfig): string[] {
+  const lines: string[] = [];
+  lines.push('[[schedules]]');
+  lines.push(`name = ${tomlString(schedule.name ?? '')}`);
+  lines.push(`cron = ${tomlString(schedule.cron ?? '')}`);
+  lines.push(`target = ${tomlString(schedule.target ?? '')}`);
+  pushIf(lines, 'command', schedule.command);
+  pushIf(lines, 'template', schedule.template);
+  lines.push(...writeInlineStringMap('params', schedule.params));
+  pushIf(lines, 'auto_approve', schedule.auto_approve);
   return lines;
 }
 
@@ -102,6 +142,7 @@ export function serializeProxyConfigToml(config: ProxyConfigEditor): string {
   if (defaultTarget) {
     lines.push(`default_target = ${tomlString(defaultTarget)}`);
   }
+  lines.push(...writeInlineStringMap('aliases', config.aliases));
 
   const defaults = config.defaults ?? null;
   if (defaults) {
@@ -113,7 +154,9 @@ export function serializeProxyConfigToml(config: ProxyConfigEditor): string {
       defaultsLines.push(...writeStringArray('ssh_args', sshArgs));
     }
     pushIf(defaultsLines, 'ssh_password', defaults.ssh_password);
+    pushIf(defaultsLines, 'ssh_password_encrypted', defaults.ssh_password_encrypted);
     pushIf(defaultsLines, 'terminal_locale', defaults.terminal_locale);
+    defaultsLines.push(...writeInlineStringMap('metadata', defaults.metadata));
     if (defaultsLines.length > 0) {
       if (lines.length > 0) {
         lines.push('');
@@ -130,23 +173,135 @@ export function serializeProxyConfigToml(config: ProxyConfigEditor): string {
     lines.push(...serializeTarget(target));
   }
 
+  for (const template of config.templates ?? []) {
+    if (lines.length > 0) {
+      lines.push('');
+    }
+    lines.push(...serializeTemplate(template));
+  }
+
+  for (const schedule of config.schedules ?? []) {
+    if (lines.length > 0) {
+      lines.push('');
+    }
+    lines.push(...serializeSchedule(schedule));
+  }
+
   return lines.length > 0 ? `${lines.join('\n')}\n` : '';
 }
 
 export function serializeBrokerConfigToml(config: BrokerConfigEditor): string {
   const lines: string[] = [];
   lines.push(`auto_approve_allowed = ${config.auto_approve_allowed ? 'true' : 'false'}`);
+  pushIf(lines, 'require_intent', config.require_intent);
+  pushIf(lines, 'min_intent_length', config.min_intent_length || null);
+  if (Array.isArray(config.banned_intents)) {
+    lines.push(...writeStringArray('banned_intents', config.banned_intents));
+  }
+  pushIf(lines, 'control_token', config.control_token);
 
   lines.push('');
   lines.push('[whitelist]');
   lines.push(...writeStringArray('allowed', config.whitelist.allowed ?? []));
   lines.push(...writeStringArray('denied', config.whitelist.denied ?? []));
   lines.push(...writeInlineStringMap('arg_rules', config.whitelist.arg_rules ?? {}));
+  const autoApproveTags = config.whitelist.auto_approve_tags ?? [];
+  if (autoApproveTags.length > 0) {
+    lines.push(...writeStringArray('auto_approve_tags', autoApproveTags));
+  }
+  const dangerousPatterns = config.whitelist.dangerous_patterns ?? [];
+  if (dangerousPatterns.length > 0) {
+    lines.push(...writeStringArray('dangerous_patterns', dangerousPatterns));
+  }
+  pushIf(lines, 'on_unparseable', config.whitelist.on_unparseable);
+  pushIf(lines, 'timezone', config.whitelist.timezone);
+  const hardDeny = config.whitelist.hard_deny;
+  if (hardDeny && ((hardDeny.commands ?? []).length > 0 || hardDeny.immutable)) {
+    lines.push('');
+    lines.push('[whitelist.hard_deny]');
+    lines.push(...writeStringArray('commands', hardDeny.commands ?? []));
+    pushIf(lines, 'immutable', hardDeny.immutable);
+  }
+  for (const window of config.whitelist.time_windows ?? []) {
+    lines.push('');
+    lines.push('[[whitelist.time_windows]]');
+    if ((window.commands ?? []).length > 0) {
+      lines.push(...writeStringArray('commands', window.commands ?? []));
+    }
+    if ((window.intent_tags ?? []).length > 0) {
+      lines.push(...writeStringArray('intent_tags', window.intent_tags ?? []));
+    }
+    pushIf(lines, 'allowed_hours', window.allowed_hours);
+    if ((window.allowed_days ?? []).length > 0) {
+      lines.push(...writeStringArray('allowed_days', window.allowed_days ?? []));
+    }
+    pushIf(lines, 'deny_outside_window', window.deny_outside_window);
+  }
 
   lines.push('');
   lines.push('[limits]');
   lines.push(`timeout_secs = ${config.limits.timeout_secs}`);
   lines.push(`max_output_bytes = ${config.limits.max_output_bytes}`);
 
+  const retention = config.retention;
+  if (retention) {
+    const retentionLines: string[] = [];
+    pushIf(retentionLines, 'max_total_bytes', retention.max_total_bytes);
+    pushIf(retentionLines, 'max_age_days', retention.max_age_days);
+    pushIf(retentionLines, 'max_files', retention.max_files);
+    if (retentionLines.length > 0) {
+      lines.push('');
+      lines.push('[retention]');
+      lines.push(...retentionLines);
+    }
+  }
+
+  const envPatterns = config.redaction?.env_patterns;
+  if (Array.isArray(envPatterns)) {
+    lines.push('');
+    lines.push('[redaction]');
+    lines.push(...writeStringArray('env_patterns', envPatterns));
+  }
+
+  const readonly = config.readonly_whitelist;
+  if (readonly) {
+    lines.push('');
+    lines.push('[readonly_whitelist]');
+    lines.push(...writeStringArray('allowed', readonly.allowed ?? []));
+    if ((readonly.denied ?? []).length > 0) {
+      lines.push(...writeStringArray('denied', readonly.denied ?? []));
+    }
+    if (Object.keys(readonly.arg_rules ?? {}).length > 0) {
+      lines.push(...writeInlineStringMap('arg_rules', readonly.arg_rules ?? {}));
+    }
+  }
+
+  const quota = config.quota;
+  if (quota) {
+    const quotaLines: string[] = [];
+    pushIf(quotaLines, 'max_daily_commands', quota.max_daily_commands);
+    pushIf(quotaLines, 'max_daily_execution_secs', quota.max_daily_execution_secs);
+    pushIf(quotaLines, 'per_client', quota.per_client);
+    // exempt_templates defaults to true, so only an explicit false needs writing.
+    if (quota.exempt_templates === false) {
+      quotaLines.push('exempt_templates = false');
+    }
+    if (quotaLines.length > 0) {
+      lines.push('');
+      lines.push('[quota]');
+      lines.push(...quotaLines);
+    }
+    for (const [name, limits] of Object.entries(quota.targets ?? {})) {
+      const targetLines: string[] = [];
+      pushIf(targetLines, 'max_daily_commands', limits.max_daily_commands);
+      pushIf(targetLines, 'max_daily_execution_secs', limits.max_daily_execution_secs);
+      if (targetLines.length > 0) {
+        lines.push('');
+        lines.push(`[quota.targets.${tomlKey(name)}]`);
+        lines.push(...targetLines);
+      }
+    }
+  }
+
   return `${lines.join('\n')}\n`;
 }
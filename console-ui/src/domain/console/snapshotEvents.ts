@@ -0,0 +1,58 @@
+import type {
+  CommandStatus,
+  RequestPriority,
+  RequestSnapshot,
+  ResultSnapshot,
+  ServiceSnapshot,
+} from '../../shared/types';
+
+// 与 console 端 HISTORY_LIMIT 保持一致。
+const HISTORY_LIMIT = 50;
+
+const PRIORITY_RANK: Record<RequestPriority, number> = { low: 0, normal: 1, high: 2 };
+
+function priorityRank(request: RequestSnapshot) {
+  return PRIORITY_RANK[request.priority ?? 'normal'];
+}
+
+// 与后端 insert_pending 相同：插到所有同级或更高优先级请求之后。
+export function applyRequestQueued(snapshot: ServiceSnapshot, request: RequestSnapshot): ServiceSnapshot {
+  const queue = snapshot.queue.filter((item) => item.id !== request.id);
+  const rank = priorityRank(request);
+  const index = queue.findIndex((item) => priorityRank(item) < rank);
+  queue.splice(index === -1 ? queue.length : index, 0, request);
+  return { ...snapshot, queue };
+}
+
+export function applyRequestResolved(
+  snapshot: ServiceSnapshot,
+  id: string,
+  status: CommandStatus,
+  nowMs = Date.now()
+): ServiceSnapshot {
+  const request = snapshot.queue.find((item) => item.id === id);
+  const queue = snapshot.queue.filter((item) => item.id !== id);
+  if (!request || status !== 'approved') {
+    return { ...snapshot, queue };
+  }
+  const running = [
+    {
+      ...request,
+      queued_for_secs: Math.max(0, Math.floor((nowMs - request.received_at_ms) / 1000)),
+      started_at_ms: nowMs,
+    },
+    ...snapshot.running.filter((item) => item.id !== id),
+  ];
+  return { ...snapshot, queue, running };
+}
+
+export function applyResultRecorded(snapshot: ServiceSnapshot, result: ResultSnapshot): ServiceSnapshot {
+  const history = [result, ...snapshot.history.filter((item) => item.id !== result.id)].slice(0, HISTORY_LIMIT);
+  return {
+    ...snapshot,
+    queue: snapshot.queue.filter((item) => item.id !== result.id),
+    running: snapshot.running.filter((item) => item.id !== result.id),
+    history,
+    last_result: result,
+  };
+}
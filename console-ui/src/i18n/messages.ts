@@ -46,6 +46,7 @@ export const messages = {
         booting: '正在启动...',
         connecting: '正在连接...',
         disconnected: '连接已断开',
+        polling: '实时连接不可用，已切换为轮询',
       },
       default: '默认',
       emptyTarget: '暂无可用目标',
@@ -54,9 +55,15 @@ export const messages = {
         pendingCount: '查看 {count} 条待审批',
         noPending: '暂无待审批',
         approveFailed: '批准失败',
+        approveEditedFailed: '修改后的命令未能批准',
         denyFailed: '拒绝失败',
         cancelFailed: '取消失败',
         forceCancelFailed: '强制终止失败',
+        killDetachedFailed: '终止后台任务失败',
+        maintenanceFailed: '切换维护模式失败',
+        retryFailed: '重新探测目标失败',
+        injectNoTerminal: '请先打开该目标的终端',
+        injectFailed: '命令未写入终端',
       },
       startup: {
         title: '启动环境',
@@ -203,6 +210,40 @@ export const messages = {
         approve: '批准',
         deny: '拒绝',
         cancel: '取消',
+        edit: '修改命令',
+      },
+      edit: {
+        approve: '按修改后的命令批准',
+        discard: '放弃修改',
+        hint: '修改后的命令会重新做白名单校验，客户端会收到实际执行的命令',
+      },
+      maintenance: {
+        enable: '进入维护模式',
+        disable: '退出维护模式',
+        active: '维护中：新请求将被直接拒绝，排队请求暂不可批准',
+        promptMessage: '维护说明（可留空）',
+      },
+      temporaryRule: {
+        chip: '临时放行 {rule} · 剩余 {remaining}',
+        title: '由 {actor} 添加；命中的请求仍需人工审批',
+      },
+      retry: {
+        failures: '连续探测失败 {count} 次',
+        next: '{time} 自动重试',
+        now: '立即重试',
+      },
+      forwardDiagnostics: {
+        established: '转发 {name} 已建立，耗时 {latency} ms',
+        reused: '转发 {name} 复用已有连接',
+        phase: {
+          connect: '连接失败',
+          auth: '认证失败',
+          remote_bind: '远端端口拒绝',
+          local_bind: '本地端口不可用',
+        },
+      },
+      audit: {
+        usage: '审计记录占用 {size}',
       },
       forceCancel: {
         title: '强制终止命令',
@@ -237,12 +278,37 @@ export const messages = {
         intent: '意图',
         mode: '模式',
         cwd: '工作目录',
+        listCwdInTerminal: '在终端中 ls -la',
         peer: '客户端',
         timeout: '超时',
         status: '状态',
         summary: '摘要',
         queuedFor: '排队时长',
-      },
+        deniedByRule: '拒绝规则',
+        approvedBy: '批准人',
+        deniedBy: '拒绝人',
+        denyReason: '拒绝原因',
+        decisionLatency: '决策耗时',
+        originalCommand: '原始命令（审批时已修改）',
+        batch: '批次',
+        metadata: '调用方信息',
+        env: '环境变量',
+        fileChanges: '文件变更',
+      },
+      fileChange: {
+        write: '写入 {path}',
+        append: '追加到 {path}',
+        edit: '原地修改 {path}',
+      },
+      confirm: {
+        warning: '该命令命中高危规则，请输入请求 ID 前 8 位（{prefix}）后再批准',
+        placeholder: '输入请求 ID 前 8 位',
+      },
+      denyReason: {
+        placeholder: '拒绝原因（可选，会返回给 agent）',
+      },
+      unparseable: 'console 无法解析该命令，白名单未按实际命令校验，请仔细核对原始命令后再批准',
+      outsideWindow: '不在允许的时间窗内（OUTSIDE ALLOWED WINDOW），只能人工确认后批准',
       emptySelection: '请选择一条命令',
       fullscreen: {
         enter: '进入全屏',
@@ -263,6 +329,17 @@ export const messages = {
       preview: {
         pending: '待审批预览',
         output: '输出预览',
+        binaryOutput: '输出不是合法 UTF-8，以下为有损文本；完整字节可通过 output 接口下载。',
+      },
+      execution: {
+        title: '执行详情',
+        path: '执行方式',
+        sshArgv: 'ssh 参数',
+        remoteCommand: '远端命令',
+        localEnv: '本地环境变量',
+        runAs: '执行账号',
+        sandbox: '沙箱',
+        cwdCreated: '（执行前已创建）',
       },
       status: {
         pending: '待审批',
@@ -271,6 +348,12 @@ export const messages = {
         denied: '已拒绝',
         cancelled: '已取消',
         error: '出错',
+        detached: '后台运行',
+      },
+      detached: {
+        title: '后台任务',
+        kill: '终止',
+        finished: '已结束（退出码 {exit}）',
       },
       summary: {
         completed: '已完成（退出码 {exit}）',
@@ -286,6 +369,7 @@ export const messages = {
       containerNotReady: '终端容器未就绪',
       error: '终端错误',
       exited: '终端已退出',
+      exitedWithReason: '终端已退出：{reason}',
       noSsh: '该目标未开启终端',
       upload: {
         title: '上传文件',
@@ -315,6 +399,7 @@ export const messages = {
         terminal: '仅 Tauri 环境支持终端',
         upload: '仅 Tauri 环境支持文件上传',
         aiRisk: 'AI 检查仅支持 Tauri',
+        history: '仅 Tauri 环境支持导出历史',
       },
     },
     aiRisk: {
@@ -368,6 +453,10 @@ export const messages = {
         timeoutHelp: '超时后视为失败',
         maxConcurrency: '最大并发',
         maxConcurrencyHelp: '同时评估的请求数',
+        cacheTtl: '结果缓存 (天)',
+        cacheTtlHelp: '相同目标、命令与模型复用评估结果，0 表示不缓存',
+        cacheStats: '已缓存 {entries} 条，命中 {hits} / 未命中 {misses}',
+        cacheClear: '清空缓存',
         prompt: 'Prompt',
         promptHelp: '支持 {\'{{field}}\'} 占位',
         promptReset: '恢复默认',
@@ -478,6 +567,7 @@ export const messages = {
           toml: '编辑 TOML',
           parsing: '正在解析配置...',
           parseFailed: '配置解析失败',
+          diagnostics: '配置检查',
         },
         fields: {
           brokerConfigPath: 'broker_config_path（可选）',
@@ -491,6 +581,8 @@ export const messages = {
           namePlaceholder: '例如 dev',
           desc: '描述',
           descPlaceholder: '用于备注',
+          banner: 'banner',
+          bannerPlaceholder: '运维提示，展示给 agent 与审批人，例如：生产库，需在变更窗口内操作',
           ssh: 'ssh',
           sshPlaceholder: "例如 devops{'@'}host",
           sshHost: 'Host（机器地址）',
@@ -502,7 +594,10 @@ export const messages = {
           passwordPlaceholder: '建议使用 SSH Key',
           terminalLocale: 'terminal_locale（可选）',
           terminalLocalePlaceholder: '例如 en_US.UTF-8',
+          defaultCwd: 'default_cwd（可选）',
+          defaultCwdPlaceholder: "例如 /home/devops/work/{'{'}client{'}'}",
           tty: 'tty（可选）',
+          queueWhenUnreachable: 'queue_when_unreachable（console 不可达时离线排队）',
           autoApproveAllowed: '允许自动批准',
           allowed: 'allowed（白名单）',
           denied: 'denied（黑名单）',
@@ -542,6 +637,8 @@ export const messages = {
         failed: '应用失败：{error}',
         switchProfile: '已切换到环境 {name}。',
         localApplied: '连接配置已应用，console 已重启。',
+        policyReloaded: '审批策略已重新加载，对之后的请求生效。',
+        policyReloadFailed: '审批策略有误，仍使用原策略：{error}',
       },
       log: {
         title: {
@@ -622,6 +719,7 @@ export const messages = {
         booting: 'Starting...',
         connecting: 'Connecting...',
         disconnected: 'Disconnected',
+        polling: 'Live connection unavailable, polling instead',
       },
       default: 'Default',
       emptyTarget: 'No target available',
@@ -630,9 +728,15 @@ export const messages = {
         pendingCount: 'View {count} pending',
         noPending: 'No pending approvals',
         approveFailed: 'Approve failed',
+        approveEditedFailed: 'Edited command was not approved',
         denyFailed: 'Deny failed',
         cancelFailed: 'Cancel failed',
         forceCancelFailed: 'Force terminate failed',
+        killDetachedFailed: 'Failed to kill detached job',
+        maintenanceFailed: 'Failed to toggle maintenance mode',
+        retryFailed: 'Failed to retry target',
+        injectNoTerminal: 'Open a terminal for this target first',
+        injectFailed: 'Command was not sent to the terminal',
       },
       startup: {
         title: 'Startup Setup',
@@ -779,6 +883,40 @@ export const messages = {
         approve: 'Approve',
         deny: 'Deny',
         cancel: 'Cancel',
+        edit: 'Edit command',
+      },
+      edit: {
+        approve: 'Approve edited command',
+        discard: 'Discard edit',
+        hint: 'The edited command is checked against the whitelist again; the client is told which command actually ran.',
+      },
+      maintenance: {
+        enable: 'Enter maintenance mode',
+        disable: 'Leave maintenance mode',
+        active: 'In maintenance: new requests are denied and queued requests cannot be approved',
+        promptMessage: 'Maintenance note (optional)',
+      },
+      temporaryRule: {
+        chip: 'Temporarily allowed: {rule} · {remaining} left',
+        title: 'Added by {actor}; matching requests still need manual approval',
+      },
+      retry: {
+        failures: '{count} consecutive probe failures',
+        next: 'next retry at {time}',
+        now: 'Retry now',
+      },
+      forwardDiagnostics: {
+        established: 'Forward {name} established in {latency} ms',
+        reused: 'Forward {name} reused an existing connection',
+        phase: {
+          connect: 'connect failed',
+          auth: 'auth failed',
+          remote_bind: 'remote port refused',
+          local_bind: 'local port unavailable',
+        },
+      },
+      audit: {
+        usage: 'Audit records use {size}',
       },
       forceCancel: {
         title: 'Force terminate command',
@@ -813,12 +951,37 @@ export const messages = {
         intent: 'Intent',
         mode: 'Mode',
         cwd: 'CWD',
+        listCwdInTerminal: 'ls -la in terminal',
         peer: 'Client',
         timeout: 'Timeout',
         status: 'Status',
         summary: 'Summary',
         queuedFor: 'Queued for',
-      },
+        deniedByRule: 'Denied by rule',
+        approvedBy: 'Approved by',
+        deniedBy: 'Denied by',
+        denyReason: 'Deny reason',
+        decisionLatency: 'Decision latency',
+        originalCommand: 'Original command (edited at approval)',
+        batch: 'Batch',
+        metadata: 'Caller metadata',
+        env: 'Environment',
+        fileChanges: 'File changes',
+      },
+      fileChange: {
+        write: 'writes {path}',
+        append: 'appends to {path}',
+        edit: 'edits {path} in place',
+      },
+      confirm: {
+        warning: 'This command matches a dangerous pattern. Type the first 8 characters of the request ID ({prefix}) to approve.',
+        placeholder: 'First 8 characters of the request ID',
+      },
+      denyReason: {
+        placeholder: 'Deny reason (optional, returned to the agent)',
+      },
+      unparseable: 'The console could not parse this command, so the whitelist was not checked against what will actually run. Review the raw command carefully before approving.',
+      outsideWindow: 'OUTSIDE ALLOWED WINDOW: this command is restricted to other hours. Approve only if you really mean to run it now.',
       emptySelection: 'Select a command',
       fullscreen: {
         enter: 'Enter fullscreen',
@@ -839,6 +1002,17 @@ export const messages = {
       preview: {
         pending: 'Pending preview',
         output: 'Output preview',
+        binaryOutput: 'Output is not valid UTF-8; the text below is lossy. Download the raw bytes via the output API.',
+      },
+      execution: {
+        title: 'Execution details',
+        path: 'Path',
+        sshArgv: 'ssh argv',
+        remoteCommand: 'Remote command',
+        localEnv: 'Local env',
+        runAs: 'Run as',
+        sandbox: 'Sandbox',
+        cwdCreated: ' (created before run)',
       },
       status: {
         pending: 'Pending',
@@ -847,6 +1021,12 @@ export const messages = {
         denied: 'Denied',
         cancelled: 'Cancelled',
         error: 'Error',
+        detached: 'Detached',
+      },
+      detached: {
+        title: 'Detached jobs',
+        kill: 'Kill',
+        finished: 'Finished (exit {exit})',
       },
       summary: {
         completed: 'Completed (exit {exit})',
@@ -862,6 +1042,7 @@ export const messages = {
       containerNotReady: 'Terminal container not ready',
       error: 'Terminal error',
       exited: 'Terminal exited',
+      exitedWithReason: 'Terminal exited: {reason}',
       noSsh: 'Terminal is not available for this target',
       upload: {
         title: 'Upload File',
@@ -891,6 +1072,7 @@ export const messages = {
         terminal: 'Terminal is only available in Tauri',
         upload: 'File upload is only available in Tauri',
         aiRisk: 'AI inspection is only available in Tauri',
+        history: 'History export is only available in Tauri',
       },
     },
     aiRisk: {
@@ -944,6 +1126,10 @@ export const messages = {
         timeoutHelp: 'Treat as failure on timeout',
         maxConcurrency: 'Max Concurrency',
         maxConcurrencyHelp: 'Concurrent assessments',
+        cacheTtl: 'Result Cache (days)',
+        cacheTtlHelp: 'Reuse results for the same target, command and model; 0 disables caching',
+        cacheStats: '{entries} cached, {hits} hits / {misses} misses',
+        cacheClear: 'Clear Cache',
         prompt: 'Prompt',
         promptHelp: 'Supports {\'{{field}}\'} placeholders',
         promptReset: 'Reset',
@@ -1054,6 +1240,7 @@ export const messages = {
           toml: 'Edit TOML',
           parsing: 'Parsing config...',
           parseFailed: 'Failed to parse config',
+          diagnostics: 'Config checks',
         },
         fields: {
           brokerConfigPath: 'broker_config_path (optional)',
@@ -1067,6 +1254,8 @@ export const messages = {
           namePlaceholder: 'e.g. dev',
           desc: 'Description',
           descPlaceholder: 'Shown in the UI',
+          banner: 'banner',
+          bannerPlaceholder: 'Operational notice shown to agents and approvers, e.g. PROD database, change window required',
           ssh: 'ssh',
           sshPlaceholder: "e.g. user{'@'}host",
           sshHost: 'Host',
@@ -1078,7 +1267,10 @@ export const messages = {
           passwordPlaceholder: 'Prefer SSH key auth',
           terminalLocale: 'terminal_locale (optional)',
           terminalLocalePlaceholder: 'e.g. en_US.UTF-8',
+          defaultCwd: 'default_cwd (optional)',
+          defaultCwdPlaceholder: "e.g. /home/devops/work/{'{'}client{'}'}",
           tty: 'tty (optional)',
+          queueWhenUnreachable: 'queue_when_unreachable (queue offline when the console is unreachable)',
           autoApproveAllowed: 'Auto approve allowed',
           allowed: 'allowed',
           denied: 'denied',
@@ -1118,6 +1310,8 @@ export const messages = {
         failed: 'Apply failed: {error}',
         switchProfile: 'Switched to profile {name}.',
         localApplied: 'Connection settings applied. Console restarted.',
+        policyReloaded: 'Approval policy reloaded; it applies to new requests.',
+        policyReloadFailed: 'Approval policy is invalid; the previous policy stays active: {error}',
       },
       log: {
         title: {
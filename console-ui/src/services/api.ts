@@ -3,15 +3,21 @@ import { invoke, isTauri } from '@tauri-apps/api/core';
 import { i18n } from '../i18n';
 import type {
   AiRiskApiResponse,
+  AiRiskCacheStats,
   BrokerConfigEditor,
   ConfigFilePayload,
   ConsoleEvent,
+  ConsoleSummary,
   AppLanguage,
+  CommandTemplateInfo,
+  HistoryPage,
   ProfilesStatus,
   ProxyConfigEditor,
   ServiceSnapshot,
   TargetInfo,
+  TemplateRunResponse,
   DirectoryListing,
+  ForwardInfo,
   UploadStartResponse,
   UploadStatus,
 } from '../shared/types';
@@ -26,7 +32,7 @@ const RAW_WS = (import.meta.env.VITE_CONSOLE_WS as string | undefined) || DEFAUL
 const HTTP_BASE = TAURI_AVAILABLE && RAW_HTTP.startsWith('/') ? DEFAULT_HTTP : RAW_HTTP;
 const WS_BASE = TAURI_AVAILABLE && RAW_WS.startsWith('/') ? DEFAULT_WS : RAW_WS;
 
-export type ConsoleConnectionStatus = 'connected' | 'connecting' | 'disconnected';
+export type ConsoleConnectionStatus = 'connected' | 'connecting' | 'polling' | 'disconnected';
 export type ConsoleStreamHandle = { close: () => void };
 export type ProxyConfigStatus = {
   present: boolean;
@@ -69,6 +75,14 @@ export type ConsoleLogChunk = {
   nextOffset: number;
 };
 
+export type LogSource = 'console' | 'app';
+
+export type LogLine = {
+  which: LogSource;
+  line: string;
+  ts: number;
+};
+
 function joinUrl(base: string, path: string) {
   const normalizedBase = base.endsWith('/') ? base.slice(0, -1) : base;
   const normalizedPath = path.startsWith('/') ? path : `/${path}`;
@@ -103,6 +117,47 @@ export async function fetchTargets(): Promise<TargetInfo[]> {
   return response.json() as Promise<TargetInfo[]>;
 }
 
+export async function fetchSummary(): Promise<ConsoleSummary> {
+  if (TAURI_AVAILABLE) {
+    return invoke<ConsoleSummary>('proxy_fetch_summary');
+  }
+  const response = await fetch(joinUrl(HTTP_BASE, '/summary'));
+  if (!response.ok) {
+    throw new Error(`failed to fetch summary: ${response.status}`);
+  }
+  return response.json() as Promise<ConsoleSummary>;
+}
+
+export async function fetchHistoryPage(
+  name: string,
+  beforeMs?: number | null,
+  limit = 50,
+  beforeId?: string | null
+): Promise<HistoryPage> {
+  if (TAURI_AVAILABLE) {
+    return invoke<HistoryPage>('proxy_fetch_history', {
+      name,
+      beforeMs: beforeMs ?? null,
+      beforeId: beforeId ?? null,
+      limit,
+    });
+  }
+  const params = new URLSearchParams({ limit: String(limit) });
+  if (beforeMs != null) {
+    params.set('before_ms', String(beforeMs));
+  }
+  if (beforeId != null) {
+    params.set('before_id', beforeId);
+  }
+  const response = await fetch(
+    joinUrl(HTTP_BASE, `/targets/${encodeURIComponent(name)}/history?${params.toString()}`)
+  );
+  if (!response.ok) {
+    throw new Error(`failed to fetch history: ${response.status}`);
+  }
+  return response.json() as Promise<HistoryPage>;
+}
+
 export async function fetchSnapshot(name: string): Promise<ServiceSnapshot> {
   if (TAURI_AVAILABLE) {
     return invoke<ServiceSnapshot>('proxy_fetch_snapshot', { name });
@@ -143,30 +198,107 @@ export async function fetchUploadStatus(id: string): Promise<UploadStatus> {
   return invoke<UploadStatus>('proxy_upload_status', { id });
 }
 
-export async function approveCommand(name: string, id: string) {
+export async function approveCommand(name: string, id: string, confirm?: string) {
   if (TAURI_AVAILABLE) {
-    await invoke('proxy_approve', { name, id });
+    await invoke('proxy_approve', { name, id, confirm: confirm ?? null });
     return;
   }
   const response = await fetch(joinUrl(HTTP_BASE, `/targets/${encodeURIComponent(name)}/approve`), {
     method: 'POST',
     headers: { 'Content-Type': 'application/json' },
-    body: JSON.stringify({ id }),
+    body: JSON.stringify({ id, confirm }),
   });
   if (!response.ok) {
     throw new Error(`approve failed: ${response.status}`);
   }
 }
 
-export async function denyCommand(name: string, id: string) {
+export async function approveEditedCommand(
+  name: string,
+  id: string,
+  rawCommand: string,
+  confirm?: string,
+) {
   if (TAURI_AVAILABLE) {
-    await invoke('proxy_deny', { name, id });
+    await invoke('proxy_approve_edited', {
+      name,
+      id,
+      rawCommand,
+      confirm: confirm ?? null,
+    });
+    return;
+  }
+  const response = await fetch(
+    joinUrl(HTTP_BASE, `/targets/${encodeURIComponent(name)}/approve-edited`),
+    {
+      method: 'POST',
+      headers: { 'Content-Type': 'application/json' },
+      body: JSON.stringify({ id, raw_command: rawCommand, confirm }),
+    },
+  );
+  if (!response.ok) {
+    const detail = await response.text();
+    throw new Error(`approve edited failed: ${response.status}${detail ? ` ${detail}` : ''}`);
+  }
+}
+
+export async function listForwards(name: string): Promise<ForwardInfo[]> {
+  if (TAURI_AVAILABLE) {
+    return invoke<ForwardInfo[]>('proxy_list_forwards', { name });
+  }
+  const response = await fetch(
+    joinUrl(HTTP_BASE, `/targets/${encodeURIComponent(name)}/forwards`),
+  );
+  if (!response.ok) {
+    throw new Error(`list forwards failed: ${response.status}`);
+  }
+  return response.json();
+}
+
+export async function ensureForward(name: string, forward: string): Promise<ForwardInfo> {
+  if (TAURI_AVAILABLE) {
+    return invoke<ForwardInfo>('proxy_ensure_forward', { name, forward });
+  }
+  const response = await fetch(
+    joinUrl(
+      HTTP_BASE,
+      `/targets/${encodeURIComponent(name)}/forwards/${encodeURIComponent(forward)}/ensure`,
+    ),
+    { method: 'POST' },
+  );
+  if (!response.ok) {
+    const detail = await response.text();
+    throw new Error(`ensure forward failed: ${response.status}${detail ? ` ${detail}` : ''}`);
+  }
+  return response.json();
+}
+
+export async function releaseForward(name: string, forward: string) {
+  if (TAURI_AVAILABLE) {
+    await invoke('proxy_release_forward', { name, forward });
+    return;
+  }
+  const response = await fetch(
+    joinUrl(
+      HTTP_BASE,
+      `/targets/${encodeURIComponent(name)}/forwards/${encodeURIComponent(forward)}`,
+    ),
+    { method: 'DELETE' },
+  );
+  if (!response.ok) {
+    throw new Error(`release forward failed: ${response.status}`);
+  }
+}
+
+export async function denyCommand(name: string, id: string, reason?: string) {
+  if (TAURI_AVAILABLE) {
+    await invoke('proxy_deny', { name, id, reason: reason ?? null });
     return;
   }
   const response = await fetch(joinUrl(HTTP_BASE, `/targets/${encodeURIComponent(name)}/deny`), {
     method: 'POST',
     headers: { 'Content-Type': 'application/json' },
-    body: JSON.stringify({ id }),
+    body: JSON.stringify({ id, reason }),
   });
   if (!response.ok) {
     throw new Error(`deny failed: ${response.status}`);
@@ -188,6 +320,78 @@ export async function cancelCommand(name: string, id: string) {
   }
 }
 
+export async function setTargetMaintenance(
+  name: string,
+  enabled: boolean,
+  message?: string,
+): Promise<TargetInfo> {
+  if (TAURI_AVAILABLE) {
+    return invoke<TargetInfo>('proxy_set_maintenance', { name, enabled, message });
+  }
+  const response = await fetch(
+    joinUrl(HTTP_BASE, `/targets/${encodeURIComponent(name)}/maintenance`),
+    {
+      method: 'POST',
+      headers: { 'Content-Type': 'application/json' },
+      body: JSON.stringify({ enabled, message }),
+    },
+  );
+  if (!response.ok) {
+    throw new Error(`maintenance failed: ${response.status}`);
+  }
+  return response.json();
+}
+
+export async function retryTarget(name: string): Promise<TargetInfo> {
+  if (TAURI_AVAILABLE) {
+    return invoke<TargetInfo>('proxy_retry_target', { name });
+  }
+  const response = await fetch(joinUrl(HTTP_BASE, `/targets/${encodeURIComponent(name)}/retry`), {
+    method: 'POST',
+  });
+  if (!response.ok) {
+    throw new Error(`retry failed: ${response.status}`);
+  }
+  return response.json();
+}
+
+export async function listCommandTemplates(): Promise<CommandTemplateInfo[]> {
+  if (TAURI_AVAILABLE) {
+    return invoke<CommandTemplateInfo[]>('proxy_list_templates');
+  }
+  const response = await fetch(joinUrl(HTTP_BASE, '/templates'));
+  if (!response.ok) {
+    throw new Error(`list templates failed: ${response.status}`);
+  }
+  return response.json();
+}
+
+export async function runCommandTemplate(
+  name: string,
+  template: string,
+  params: Record<string, string>,
+): Promise<TemplateRunResponse> {
+  if (TAURI_AVAILABLE) {
+    return invoke<TemplateRunResponse>('proxy_run_template', { name, template, params });
+  }
+  const response = await fetch(
+    joinUrl(
+      HTTP_BASE,
+      `/targets/${encodeURIComponent(name)}/templates/${encodeURIComponent(template)}/run`,
+    ),
+    {
+      method: 'POST',
+      headers: { 'Content-Type': 'application/json' },
+      body: JSON.stringify({ params }),
+    },
+  );
+  if (!response.ok) {
+    const detail = await response.text();
+    throw new Error(`run template failed: ${response.status}${detail ? ` ${detail}` : ''}`);
+  }
+  return response.json();
+}
+
 export async function forceCancelCommand(name: string, id: string) {
   if (TAURI_AVAILABLE) {
     await invoke('proxy_force_cancel', { name, id });
@@ -203,6 +407,21 @@ export async function forceCancelCommand(name: string, id: string) {
   }
 }
 
+export async function killDetachedJob(name: string, id: string) {
+  if (TAURI_AVAILABLE) {
+    await invoke('proxy_kill_detached', { name, id });
+    return;
+  }
+  const response = await fetch(joinUrl(HTTP_BASE, `/targets/${encodeURIComponent(name)}/kill-detached`), {
+    method: 'POST',
+    headers: { 'Content-Type': 'application/json' },
+    body: JSON.stringify({ id }),
+  });
+  if (!response.ok) {
+    throw new Error(`kill detached failed: ${response.status}`);
+  }
+}
+
 export async function getProxyConfigStatus(): Promise<ProxyConfigStatus> {
   if (TAURI_AVAILABLE) {
     return invoke<ProxyConfigStatus>('get_proxy_config_status');
@@ -286,13 +505,24 @@ export async function openConsoleStream(
   onStatus?: (status: ConsoleConnectionStatus) => void
 ): Promise<ConsoleStreamHandle> {
   if (TAURI_AVAILABLE) {
-    const unlistenEvent = await listen<ConsoleEvent>('console_event', (event) => {
+    // 事件带 profile 字段；切换环境后旧连接残留的事件直接丢弃。
+    let profile: string | null = null;
+    const unlistenEvent = await listen<ConsoleEvent & { profile?: string }>('console_event', (event) => {
+      if (profile !== null && event.payload.profile !== profile) {
+        return;
+      }
       onEvent(event.payload);
     });
-    const unlistenStatus = await listen<ConsoleConnectionStatus>('console_ws_status', (event) => {
-      onStatus?.(event.payload);
-    });
-    await invoke('start_console_stream');
+    const unlistenStatus = await listen<{ profile: string; status: ConsoleConnectionStatus }>(
+      'console_ws_status',
+      (event) => {
+        if (profile !== null && event.payload.profile !== profile) {
+          return;
+        }
+        onStatus?.(event.payload.status);
+      }
+    );
+    profile = await invoke<string>('start_console_stream');
     return {
       close: () => {
         unlistenEvent();
@@ -303,16 +533,41 @@ export async function openConsoleStream(
 
   let ws: WebSocket | null = null;
   let reconnectTimer: number | null = null;
+  // 重连时带上 epoch/since，console 会补发断线期间的事件，补不了时回 targets_snapshot。
+  let epoch: string | null = null;
+  let lastSeq: number | null = null;
+
+  const streamUrl = () => {
+    const base = resolveWsUrl(WS_BASE);
+    if (epoch === null || lastSeq === null) {
+      return base;
+    }
+    const separator = base.includes('?') ? '&' : '?';
+    return `${base}${separator}epoch=${encodeURIComponent(epoch)}&since=${lastSeq}`;
+  };
 
   const connect = () => {
     onStatus?.('connecting');
-    ws = new WebSocket(resolveWsUrl(WS_BASE));
+    ws = new WebSocket(streamUrl());
     ws.onmessage = (message) => {
       try {
-        const parsed = JSON.parse(message.data) as ConsoleEvent;
-        if (parsed && typeof parsed.type === 'string') {
-          onEvent(parsed);
+        const parsed = JSON.parse(message.data) as (ConsoleEvent | { type: 'hello' | 'heartbeat'; epoch?: string }) & {
+          seq?: number;
+        };
+        if (!parsed || typeof parsed.type !== 'string') {
+          return;
         }
+        if (typeof parsed.seq === 'number') {
+          lastSeq = parsed.seq;
+        }
+        if (parsed.type === 'hello') {
+          epoch = parsed.epoch ?? null;
+          return;
+        }
+        if (parsed.type === 'heartbeat') {
+          return;
+        }
+        onEvent(parsed);
       } catch (err) {
         console.warn('failed to parse websocket event', err);
       }
@@ -379,6 +634,19 @@ export async function writeProxyConfig(content: string) {
   await invoke('write_proxy_config', { content });
 }
 
+// 热重载 broker 配置；配置有误时抛出的错误带 `422`，console 继续使用旧策略。
+export async function reloadPolicy(): Promise<{ message: string; warnings: string[] }> {
+  if (TAURI_AVAILABLE) {
+    return invoke('proxy_reload_policy');
+  }
+  const response = await fetch(joinUrl(HTTP_BASE, '/policy/reload'), { method: 'POST' });
+  if (!response.ok) {
+    const detail = await response.text();
+    throw new Error(`policy reload failed: ${response.status}${detail ? ` ${detail}` : ''}`);
+  }
+  return response.json();
+}
+
 export async function restartConsole() {
   if (!TAURI_AVAILABLE) {
     throw new Error(t('api.tauriOnly.configEditor'));
@@ -407,11 +675,94 @@ export async function readAppLog(offset: number, maxBytes: number): Promise<Cons
   return invoke<ConsoleLogChunk>('read_app_log', { offset, maxBytes });
 }
 
-export async function terminalOpen(name: string, cols: number, rows: number, term?: string) {
+// 后端 tail 任务每 100ms 合并推送一批 log_line；offset 传回填读到的位置，filter 为 `/regex/` 或子串。
+export async function watchLog(
+  which: LogSource,
+  onLines: (lines: LogLine[]) => void,
+  options: { offset?: number; filter?: string } = {}
+): Promise<() => void> {
+  if (!TAURI_AVAILABLE) {
+    return () => {};
+  }
+  const unlisten = await listen<LogLine[]>('log_line', (event) => {
+    const lines = event.payload.filter((line) => line.which === which);
+    if (lines.length) {
+      onLines(lines);
+    }
+  });
+  try {
+    await invoke('start_log_stream', {
+      which,
+      filter: options.filter ?? null,
+      offset: options.offset ?? null,
+    });
+  } catch (err) {
+    unlisten();
+    throw err;
+  }
+  return () => {
+    unlisten();
+    void invoke('stop_log_stream', { which }).catch(() => {});
+  };
+}
+
+export async function terminalOpen(
+  name: string,
+  cols: number,
+  rows: number,
+  term?: string,
+  reattachSessionId?: string
+) {
+  if (!TAURI_AVAILABLE) {
+    throw new Error(t('api.tauriOnly.terminal'));
+  }
+  return invoke<string>('terminal_open', { name, cols, rows, term, reattachSessionId });
+}
+
+export type TerminalAttachMode = 'readonly' | 'rw';
+
+export type TerminalSessionInfo = {
+  session_id: string;
+  target: string;
+  cols: number;
+  rows: number;
+  term: string;
+  started_at_ms: number;
+  attachments: number;
+  detached?: boolean;
+};
+
+export async function listTargetTerminals(name: string): Promise<TerminalSessionInfo[]> {
   if (!TAURI_AVAILABLE) {
     throw new Error(t('api.tauriOnly.terminal'));
   }
-  return invoke<string>('terminal_open', { name, cols, rows, term });
+  return invoke<TerminalSessionInfo[]>('terminal_list', { target: name });
+}
+
+export type HistoryExportFormat = 'csv' | 'jsonl';
+
+export async function exportHistory(
+  name: string,
+  destPath: string,
+  options: { from?: string; to?: string; format?: HistoryExportFormat } = {}
+): Promise<number> {
+  if (!TAURI_AVAILABLE) {
+    throw new Error(t('api.tauriOnly.history'));
+  }
+  return invoke<number>('proxy_export_history', {
+    name,
+    from: options.from ?? null,
+    to: options.to ?? null,
+    format: options.format ?? 'csv',
+    destPath,
+  });
+}
+
+export async function terminalAttach(name: string, attachId: string, mode: TerminalAttachMode = 'readonly') {
+  if (!TAURI_AVAILABLE) {
+    throw new Error(t('api.tauriOnly.terminal'));
+  }
+  return invoke<string>('terminal_attach', { name, attachId, mode });
 }
 
 export async function terminalInput(sessionId: string, dataBase64: string) {
@@ -421,6 +772,14 @@ export async function terminalInput(sessionId: string, dataBase64: string) {
   await invoke('terminal_input', { sessionId, dataBase64 });
 }
 
+// 只有通过 console readonly_whitelist 的命令才会写入会话，拒绝原因在错误里。
+export async function terminalInject(name: string, consoleSessionId: string, command: string) {
+  if (!TAURI_AVAILABLE) {
+    throw new Error(t('api.tauriOnly.terminal'));
+  }
+  await invoke('terminal_inject', { name, consoleSessionId, command });
+}
+
 export async function terminalResize(sessionId: string, cols: number, rows: number) {
   if (!TAURI_AVAILABLE) {
     throw new Error(t('api.tauriOnly.terminal'));
@@ -442,6 +801,13 @@ export type AiRiskRequestPayload = {
   api_key: string;
   prompt: string;
   timeout_ms?: number;
+  auth_header_name?: string;
+  extra_headers?: Record<string, string>;
+  stream?: boolean;
+  command?: string;
+  target?: string;
+  cache_ttl_secs?: number;
+  force_refresh?: boolean;
 };
 
 export async function aiRiskAssess(request: AiRiskRequestPayload): Promise<AiRiskApiResponse> {
@@ -450,3 +816,17 @@ export async function aiRiskAssess(request: AiRiskRequestPayload): Promise<AiRis
   }
   return invoke<AiRiskApiResponse>('ai_risk_assess', { request });
 }
+
+export async function aiRiskCacheStats(): Promise<AiRiskCacheStats | null> {
+  if (!TAURI_AVAILABLE) {
+    return null;
+  }
+  return invoke<AiRiskCacheStats>('ai_risk_cache_stats');
+}
+
+export async function aiRiskCacheClear() {
+  if (!TAURI_AVAILABLE) {
+    return;
+  }
+  await invoke('ai_risk_cache_clear');
+}
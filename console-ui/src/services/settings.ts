@@ -54,6 +54,7 @@ const BASE_AI_SETTINGS: Omit<AiSettings, 'prompt'> = {
   apiKey: '',
   timeoutMs: 10000,
   maxConcurrency: 2,
+  cacheTtlDays: 7,
 };
 
 function buildDefaultAiSettings(language: AppLanguage): AiSettings {
@@ -196,6 +197,7 @@ export function loadSettings(): AppSettings {
       prompt: normalizeText(parsedAi.prompt, defaultAiSettings.prompt),
       timeoutMs: normalizeNumber(parsedAi.timeoutMs, defaultAiSettings.timeoutMs, 1000, 60000),
       maxConcurrency: normalizeNumber(parsedAi.maxConcurrency, defaultAiSettings.maxConcurrency, 1, 10),
+      cacheTtlDays: normalizeNumber(parsedAi.cacheTtlDays, defaultAiSettings.cacheTtlDays, 0, 90),
     };
     const parsedChat = (parsed.chat ?? {}) as Partial<ChatProviderConfig>;
     const normalizeChatProvider = (value: unknown): 'openai' | 'acp' =>
@@ -7,6 +7,19 @@ export type McpToolInfo = {
   inputSchema: Record<string, unknown> | null;
 };
 
+export type McpResourceInfo = {
+  server: string;
+  uri: string;
+  name: string;
+  description?: string | null;
+  mimeType?: string | null;
+};
+
+export type McpListChangedEvent = {
+  server: string;
+  kind: 'tools' | 'resources';
+};
+
 type McpToolMapping = Map<string, { server: string; name: string }>;
 
 function normalizeIdentifier(value: string): string {
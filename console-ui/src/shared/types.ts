@@ -10,6 +10,9 @@ export interface ConfigFilePayload {
 
 export interface ProfileSummary {
   name: string;
+  /** console 的 host:port；未配置时为本地 sidecar 地址。 */
+  console_addr: string;
+  external_console: boolean;
 }
 
 export interface ProfilesStatus {
@@ -22,7 +25,9 @@ export interface ProxyDefaultsConfig {
   max_output_bytes?: number | null;
   ssh_args?: string[] | null;
   ssh_password?: string | null;
+  ssh_password_encrypted?: string | null;
   terminal_locale?: string | null;
+  metadata?: Record<string, string> | null;
 }
 
 export interface ProxyTargetConfig {
@@ -31,21 +36,95 @@ export interface ProxyTargetConfig {
   ssh?: string | null;
   ssh_args?: string[] | null;
   ssh_password?: string | null;
+  ssh_password_encrypted?: string | null;
   terminal_locale?: string | null;
   tty?: boolean | null;
+  default_cwd?: string | null;
+  queue_when_unreachable?: boolean | null;
+  ssh_host_key_policy?: 'accept_new' | 'strict' | 'pinned' | 'insecure' | null;
+  ssh_host_key?: string | null;
+  banner?: string | null;
+  extra_forwards?: ExtraForwardConfig[];
+}
+
+export interface ExtraForwardConfig {
+  name: string;
+  remote_addr: string;
+  local_port?: number | null;
 }
 
 export interface ProxyConfigEditor {
   broker_config_path?: string | null;
   default_target?: string | null;
+  aliases?: Record<string, string>;
   defaults?: ProxyDefaultsConfig | null;
   targets: ProxyTargetConfig[];
+  templates?: CommandTemplateConfig[];
+  schedules?: CommandScheduleConfig[];
+  diagnostics?: ConfigDiagnostic[];
+}
+
+export interface ConfigDiagnostic {
+  path: string;
+  message: string;
+  severity: 'error' | 'warning';
+}
+
+export interface CommandTemplateInfo {
+  name: string;
+  description: string;
+  target?: string;
+  raw_command: string;
+  params: Record<string, string>;
+}
+
+export interface TemplateRunResponse {
+  id: string;
+  raw_command: string;
+  status: 'queued';
+}
+
+export interface CommandTemplateConfig {
+  name: string;
+  description?: string;
+  target?: string | null;
+  raw_command: string;
+  params?: Record<string, string>;
+}
+
+export interface CommandScheduleConfig {
+  name: string;
+  cron: string;
+  target: string;
+  command?: string | null;
+  template?: string | null;
+  params?: Record<string, string>;
+  auto_approve?: boolean;
 }
 
 export interface BrokerWhitelistConfig {
   allowed: string[];
   denied: string[];
   arg_rules: Record<string, string>;
+  auto_approve_tags?: string[];
+  dangerous_patterns?: string[];
+  on_unparseable?: 'deny' | 'manual' | null;
+  hard_deny?: BrokerHardDenyConfig | null;
+  time_windows?: BrokerTimeWindowConfig[];
+  timezone?: string | null;
+}
+
+export interface BrokerTimeWindowConfig {
+  commands?: string[];
+  intent_tags?: string[];
+  allowed_hours?: string | null;
+  allowed_days?: string[];
+  deny_outside_window?: boolean | null;
+}
+
+export interface BrokerHardDenyConfig {
+  commands?: string[];
+  immutable?: boolean | null;
 }
 
 export interface BrokerLimitsConfig {
@@ -53,15 +132,52 @@ export interface BrokerLimitsConfig {
   max_output_bytes: number;
 }
 
+export interface BrokerRetentionConfig {
+  max_total_bytes?: number | null;
+  max_age_days?: number | null;
+  max_files?: number | null;
+}
+
+export interface BrokerRedactionConfig {
+  env_patterns?: string[] | null;
+}
+
+export interface BrokerReadonlyWhitelistConfig {
+  allowed?: string[];
+  denied?: string[];
+  arg_rules?: Record<string, string>;
+}
+
+export interface BrokerQuotaLimits {
+  max_daily_commands?: number | null;
+  max_daily_execution_secs?: number | null;
+}
+
+export interface BrokerQuotaConfig extends BrokerQuotaLimits {
+  per_client?: boolean | null;
+  exempt_templates?: boolean | null;
+  targets?: Record<string, BrokerQuotaLimits>;
+}
+
 export interface BrokerConfigEditor {
   auto_approve_allowed: boolean;
+  require_intent?: boolean;
+  min_intent_length?: number;
+  banned_intents?: string[] | null;
+  control_token?: string | null;
   whitelist: BrokerWhitelistConfig;
   limits: BrokerLimitsConfig;
+  retention?: BrokerRetentionConfig | null;
+  redaction?: BrokerRedactionConfig | null;
+  quota?: BrokerQuotaConfig | null;
+  readonly_whitelist?: BrokerReadonlyWhitelistConfig | null;
+  diagnostics?: ConfigDiagnostic[];
 }
 
 export interface TargetInfo {
   name: string;
   desc: string;
+  banner?: string | null;
   ssh?: string | null;
   status: TargetStatus;
   pending_count: number;
@@ -69,8 +185,57 @@ export interface TargetInfo {
   last_error?: string | null;
   terminal_available?: boolean;
   is_default?: boolean;
+  maintenance?: boolean;
+  maintenance_message?: string | null;
+  consecutive_failures?: number;
+  next_retry_at?: string | null;
+  audit_bytes?: number | null;
+  warning?: string | null;
+  forwards?: ForwardInfo[];
+  forward_diagnostics?: ForwardDiagnostic[];
+  quota?: QuotaUsage | null;
+  temporary_rules?: TemporaryRule[];
+}
+
+export interface TemporaryRule {
+  id: string;
+  rule: string;
+  actor: string;
+  created_at_ms: number;
+  expires_at_ms: number;
+}
+
+export interface QuotaCounter {
+  commands: number;
+  execution_secs: number;
+}
+
+export interface QuotaUsage {
+  used: QuotaCounter;
+  max_daily_commands?: number | null;
+  max_daily_execution_secs?: number | null;
+  per_client: boolean;
+  clients?: Record<string, QuotaCounter>;
+  resets_at_ms: number;
 }
 
+export interface ForwardInfo {
+  name: string;
+  remote_addr: string;
+  local_addr?: string | null;
+}
+
+export type SshFailurePhase = 'connect' | 'auth' | 'remote_bind' | 'local_bind';
+
+export type ForwardDiagnostic = {
+  name: string;
+  remote_addr: string;
+  at_ms: number;
+} & (
+  | { outcome: 'established'; latency_ms: number; reused: boolean }
+  | { outcome: 'failed'; phase: SshFailurePhase; detail: string }
+);
+
 export interface DirectoryEntry {
   name: string;
   path: string;
@@ -99,7 +264,7 @@ export interface UploadStatus {
 }
 
 export type CommandMode = 'shell';
-export type CommandStatus = 'approved' | 'denied' | 'error' | 'cancelled' | 'completed';
+export type CommandStatus = 'approved' | 'denied' | 'error' | 'cancelled' | 'completed' | 'detached';
 
 export interface CommandStage {
   argv: string[];
@@ -113,11 +278,33 @@ export interface RequestSnapshot {
   intent: string;
   mode: CommandMode;
   raw_command: string;
+  original_command?: string | null;
   pipeline: CommandStage[];
   cwd?: string | null;
   timeout_ms?: number | null;
   max_output_bytes?: number | null;
   received_at_ms: number;
+  priority?: RequestPriority;
+  batch_id?: string | null;
+  intent_tags?: string[];
+  metadata?: Record<string, string> | null;
+  env_preview?: Record<string, string> | null;
+  file_changes?: FileChange[];
+  requires_confirmation?: boolean;
+  unparseable?: boolean;
+  outside_window?: boolean;
+  run_as?: string | null;
+}
+
+export type RequestPriority = 'low' | 'normal' | 'high';
+
+export type FileChangeAction = 'write' | 'append' | 'edit';
+
+export interface FileChange {
+  path: string;
+  action: FileChangeAction;
+  preview?: string | null;
+  bytes?: number | null;
 }
 
 export interface RunningSnapshot {
@@ -128,11 +315,21 @@ export interface RunningSnapshot {
   intent: string;
   mode: CommandMode;
   raw_command: string;
+  original_command?: string | null;
   pipeline: CommandStage[];
   cwd?: string | null;
   timeout_ms?: number | null;
   max_output_bytes?: number | null;
   received_at_ms: number;
+  priority?: RequestPriority;
+  batch_id?: string | null;
+  intent_tags?: string[];
+  metadata?: Record<string, string> | null;
+  env_preview?: Record<string, string> | null;
+  file_changes?: FileChange[];
+  requires_confirmation?: boolean;
+  unparseable?: boolean;
+  outside_window?: boolean;
   queued_for_secs: number;
   started_at_ms: number;
 }
@@ -145,6 +342,7 @@ export interface ResultSnapshot {
   intent: string;
   mode: CommandMode;
   raw_command: string;
+  original_command?: string | null;
   pipeline: CommandStage[];
   cwd?: string | null;
   peer: string;
@@ -152,6 +350,41 @@ export interface ResultSnapshot {
   finished_at_ms: number;
   stdout?: string | null;
   stderr?: string | null;
+  denied_by_rule?: string | null;
+  approved_by?: string | null;
+  denied_by?: string | null;
+  deny_reason?: string | null;
+  decision_latency_ms?: number | null;
+  batch_id?: string | null;
+  intent_tags?: string[];
+  metadata?: Record<string, string> | null;
+  binary_output?: boolean;
+  execution?: ExecutionContext | null;
+  source?: string | null;
+}
+
+export interface ExecutionContext {
+  path: 'ssh' | 'pty';
+  ssh_argv: string[];
+  remote_command: string;
+  cwd?: string | null;
+  env?: Record<string, string>;
+  local_env?: Record<string, string>;
+  locale?: string | null;
+  control_path?: string | null;
+  tty: boolean;
+  run_as?: string | null;
+  sandbox?: string[] | null;
+  cwd_created?: boolean;
+}
+
+export interface DetachedSnapshot extends RequestSnapshot {
+  started_at_ms: number;
+  state: 'running' | 'finished';
+  status?: CommandStatus | null;
+  exit_code?: number | null;
+  error?: string | null;
+  finished_at_ms?: number | null;
 }
 
 export interface ServiceSnapshot {
@@ -159,11 +392,34 @@ export interface ServiceSnapshot {
   running: RunningSnapshot[];
   history: ResultSnapshot[];
   last_result?: ResultSnapshot | null;
+  detached?: DetachedSnapshot[];
+}
+
+export interface HistoryPage {
+  items: ResultSnapshot[];
+  next_before_ms?: number | null;
+  next_before_id?: string | null;
 }
 
 export type ConsoleEvent =
   | { type: 'targets_snapshot'; targets: TargetInfo[] }
-  | { type: 'target_updated'; target: TargetInfo };
+  | { type: 'target_updated'; target: TargetInfo }
+  | { type: 'request_queued'; target: string; request: RequestSnapshot }
+  | { type: 'request_resolved'; target: string; id: string; status: CommandStatus }
+  | { type: 'result_recorded'; target: string; result: ResultSnapshot }
+  | { type: 'summary_changed'; summary: ConsoleSummary };
+
+export interface TargetSummary {
+  name: string;
+  status: TargetStatus;
+  pending_count: number;
+  oldest_pending_secs?: number | null;
+}
+
+export interface ConsoleSummary {
+  total_pending: number;
+  targets: TargetSummary[];
+}
 
 export type ListTab = 'pending' | 'history';
 
@@ -187,6 +443,14 @@ export interface AiRiskApiResponse {
   key_points: string[];
 }
 
+export interface AiRiskCacheStats {
+  entries: number;
+  expired: number;
+  hits: number;
+  misses: number;
+  path: string;
+}
+
 export interface AiSettings {
   enabled: boolean;
   autoApproveLowRisk: boolean;
@@ -199,6 +463,7 @@ export interface AiSettings {
   prompt: string;
   timeoutMs: number;
   maxConcurrency: number;
+  cacheTtlDays: number;
 }
 
 export interface ChatProviderConfig {
@@ -66,6 +66,22 @@ describe('chatPipeline', () => {
     expect(parsed[2].role).toBe('system');
   });
 
+  it('keeps replayed image entries from ACP history', () => {
+    const history = [
+      { role: 'user', content: 'look' },
+      { role: 'user', content_type: 'image', mime_type: 'image/png', data: 'abc' },
+      { role: 'user', content_type: 'image', mime_type: 'image/jpeg', uri: 'file:///tmp/big.jpg' },
+      { role: 'user', content_type: 'image', mime_type: 'image/png', content: '[image unavailable]' },
+      { role: 'assistant', content: 'ok' },
+    ];
+    const parsed = parseAcpHistory(history, () => 'id', 1000);
+    expect(parsed).toHaveLength(5);
+    expect(parsed[1].images).toEqual(['data:image/png;base64,abc']);
+    expect(parsed[2].images).toEqual(['file:///tmp/big.jpg']);
+    expect(parsed[3].images).toBeUndefined();
+    expect(parsed[3].content).toBe('[image unavailable]');
+  });
+
   it('extracts plain text from nested content', () => {
     expect(toPlainText({ content: [{ text: 'hi' }, { text: ' there' }] })).toBe('hi there');
   });
@@ -4,8 +4,8 @@ use std::{
     path::{Path, PathBuf},
     process::Stdio,
     sync::{
-        atomic::{AtomicI64, Ordering},
-        Arc,
+        atomic::{AtomicI64, AtomicU64, Ordering},
+        Arc, RwLock,
     },
 };
 
@@ -15,14 +15,16 @@ use codex_app_server_protocol::{
     AddConversationListenerParams, AddConversationSubscriptionResponse, ApplyPatchApprovalResponse,
     ClientInfo, ClientNotification, ClientRequest, ExecCommandApprovalResponse, InitializeParams,
     InitializeResponse, InputItem, InterruptConversationParams, InterruptConversationResponse,
-    JSONRPCError, JSONRPCMessage, JSONRPCNotification, JSONRPCRequest, JSONRPCResponse,
-    NewConversationParams, NewConversationResponse, RemoveConversationListenerParams,
-    RemoveConversationSubscriptionResponse, RequestId, ResumeConversationParams,
-    ResumeConversationResponse, SendUserMessageParams, SendUserMessageResponse, ServerRequest,
+    JSONRPCError, JSONRPCErrorError, JSONRPCMessage, JSONRPCNotification, JSONRPCRequest,
+    JSONRPCResponse, NewConversationParams, NewConversationResponse,
+    RemoveConversationListenerParams, RemoveConversationSubscriptionResponse, RequestId,
+    ResumeConversationParams, ResumeConversationResponse, SendUserMessageParams,
+    SendUserMessageResponse, ServerRequest,
 };
 use codex_protocol::{protocol::EventMsg, protocol::ReviewDecision, ConversationId};
 use serde::Deserialize;
-use serde_json::Value;
+use serde_json::{json, Value};
+use system_utils::lines::{LineReader, MAX_JSON_LINE_BYTES};
 use tokio::{
     io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
     process::Command,
@@ -31,7 +33,8 @@ use tokio::{
 };
 
 use crate::cli::CliConfig;
-use crate::logging::{log_fmt, LogLevel};
+use crate::logging::{log_fmt, log_rpc, LogLevel};
+use crate::state::{ClientFsMethod, ModelInfo};
 
 // Prefer the user's local Codex CLI (`codex app-server`). We support overriding the executable
 // path via `CliConfig.codex_path` to avoid PATH issues when launching from a bundled app (DMG).
@@ -46,11 +49,25 @@ pub(crate) enum AppServerEvent {
         msg: EventMsg,
     },
     StderrLine(String),
+    /// app-server 请求读写文件；`respond_to` 收到 None 表示客户端不支持，按未知请求回空结果。
+    ClientFsRequest {
+        method: ClientFsMethod,
+        params: Value,
+        respond_to: oneshot::Sender<Option<std::result::Result<Value, String>>>,
+    },
+    /// app-server 进程退出；`generation` 与 [`AppServerClient::generation`] 不一致说明是已被替换的旧进程。
+    Exited {
+        generation: u64,
+        status: String,
+    },
 }
 
 pub(crate) struct AppServerClient {
-    rpc: JsonRpcPeer,
-    _child: Mutex<tokio::process::Child>,
+    rpc: RwLock<JsonRpcPeer>,
+    events_tx: mpsc::UnboundedSender<AppServerEvent>,
+    generation: AtomicU64,
+    // drop（或重启时被替换）后 watcher 会杀掉对应的子进程，等价于原来的 kill_on_drop。
+    child_guard: Mutex<oneshot::Sender<()>>,
 }
 
 fn ensure_codex_home_links(codex_home: &str) {
@@ -122,7 +139,44 @@ impl AppServerClient {
         config: &CliConfig,
     ) -> Result<(Self, mpsc::UnboundedReceiver<AppServerEvent>)> {
         let (events_tx, events_rx) = mpsc::unbounded_channel();
+        let (rpc, child_guard) = Self::launch(config, &events_tx, 0).await?;
+        Ok((
+            Self {
+                rpc: RwLock::new(rpc),
+                events_tx,
+                generation: AtomicU64::new(0),
+                child_guard: Mutex::new(child_guard),
+            },
+            events_rx,
+        ))
+    }
+
+    /// 进程退出后重新拉起 app-server，事件继续发往原来的 receiver；调用方需要重新 initialize。
+    pub(crate) async fn respawn(&self, config: &CliConfig) -> Result<()> {
+        let generation = self.generation.load(Ordering::SeqCst) + 1;
+        let (rpc, child_guard) = Self::launch(config, &self.events_tx, generation).await?;
+        *self.rpc.write().unwrap_or_else(|err| err.into_inner()) = rpc;
+        *self.child_guard.lock().await = child_guard;
+        self.generation.store(generation, Ordering::SeqCst);
+        Ok(())
+    }
+
+    pub(crate) fn generation(&self) -> u64 {
+        self.generation.load(Ordering::SeqCst)
+    }
+
+    fn rpc(&self) -> JsonRpcPeer {
+        self.rpc
+            .read()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone()
+    }
 
+    async fn launch(
+        config: &CliConfig,
+        events_tx: &mpsc::UnboundedSender<AppServerEvent>,
+        generation: u64,
+    ) -> Result<(JsonRpcPeer, oneshot::Sender<()>)> {
         let program = config
             .codex_path
             .as_deref()
@@ -246,20 +300,18 @@ impl AppServerClient {
             });
         }
 
-        let callbacks = Arc::new(AppServerCallbacks { events_tx });
+        let callbacks = Arc::new(AppServerCallbacks {
+            events_tx: events_tx.clone(),
+        });
         let rpc = JsonRpcPeer::spawn(stdin, stdout, callbacks);
-        Ok((
-            Self {
-                rpc,
-                _child: Mutex::new(child),
-            },
-            events_rx,
-        ))
+        let child_guard = watch_child(child, generation, events_tx.clone());
+        Ok((rpc, child_guard))
     }
 
     pub(crate) async fn initialize(&self) -> Result<()> {
+        let rpc = self.rpc();
         let request = ClientRequest::Initialize {
-            request_id: self.rpc.next_request_id(),
+            request_id: rpc.next_request_id(),
             params: InitializeParams {
                 client_info: ClientInfo {
                     name: "acp-codex".to_string(),
@@ -268,11 +320,10 @@ impl AppServerClient {
                 },
             },
         };
-        self.rpc
-            .request::<InitializeResponse, _>(request_id(&request), &request, "initialize")
+        rpc.request::<InitializeResponse, _>(request_id(&request), &request, "initialize")
             .await?;
         let notification = ClientNotification::Initialized;
-        self.rpc.send(&notification).await?;
+        rpc.send(&notification).await?;
         Ok(())
     }
 
@@ -280,12 +331,12 @@ impl AppServerClient {
         &self,
         params: NewConversationParams,
     ) -> Result<NewConversationResponse> {
+        let rpc = self.rpc();
         let request = ClientRequest::NewConversation {
-            request_id: self.rpc.next_request_id(),
+            request_id: rpc.next_request_id(),
             params,
         };
-        self.rpc
-            .request(request_id(&request), &request, "newConversation")
+        rpc.request(request_id(&request), &request, "newConversation")
             .await
     }
 
@@ -294,8 +345,9 @@ impl AppServerClient {
         rollout_path: PathBuf,
         overrides: NewConversationParams,
     ) -> Result<ResumeConversationResponse> {
+        let rpc = self.rpc();
         let request = ClientRequest::ResumeConversation {
-            request_id: self.rpc.next_request_id(),
+            request_id: rpc.next_request_id(),
             params: ResumeConversationParams {
                 path: Some(rollout_path),
                 overrides: Some(overrides),
@@ -303,8 +355,7 @@ impl AppServerClient {
                 history: None,
             },
         };
-        self.rpc
-            .request(request_id(&request), &request, "resumeConversation")
+        rpc.request(request_id(&request), &request, "resumeConversation")
             .await
     }
 
@@ -312,15 +363,15 @@ impl AppServerClient {
         &self,
         conversation_id: ConversationId,
     ) -> Result<AddConversationSubscriptionResponse> {
+        let rpc = self.rpc();
         let request = ClientRequest::AddConversationListener {
-            request_id: self.rpc.next_request_id(),
+            request_id: rpc.next_request_id(),
             params: AddConversationListenerParams {
                 conversation_id,
                 experimental_raw_events: false,
             },
         };
-        self.rpc
-            .request(request_id(&request), &request, "addConversationListener")
+        rpc.request(request_id(&request), &request, "addConversationListener")
             .await
     }
 
@@ -329,15 +380,15 @@ impl AppServerClient {
         conversation_id: ConversationId,
         items: Vec<InputItem>,
     ) -> Result<SendUserMessageResponse> {
+        let rpc = self.rpc();
         let request = ClientRequest::SendUserMessage {
-            request_id: self.rpc.next_request_id(),
+            request_id: rpc.next_request_id(),
             params: SendUserMessageParams {
                 conversation_id,
                 items,
             },
         };
-        self.rpc
-            .request(request_id(&request), &request, "sendUserMessage")
+        rpc.request(request_id(&request), &request, "sendUserMessage")
             .await
     }
 
@@ -345,12 +396,12 @@ impl AppServerClient {
         &self,
         conversation_id: ConversationId,
     ) -> Result<InterruptConversationResponse> {
+        let rpc = self.rpc();
         let request = ClientRequest::InterruptConversation {
-            request_id: self.rpc.next_request_id(),
+            request_id: rpc.next_request_id(),
             params: InterruptConversationParams { conversation_id },
         };
-        self.rpc
-            .request(request_id(&request), &request, "interruptConversation")
+        rpc.request(request_id(&request), &request, "interruptConversation")
             .await
     }
 
@@ -358,28 +409,117 @@ impl AppServerClient {
         &self,
         conversation_id: ConversationId,
     ) -> Result<()> {
+        let rpc = self.rpc();
         // `interruptConversation` returns only after `TurnAborted`; if the conversation
         // has no active turn, the server may never respond. For our purposes we only
         // need best-effort delivery.
         let request = ClientRequest::InterruptConversation {
-            request_id: self.rpc.next_request_id(),
+            request_id: rpc.next_request_id(),
             params: InterruptConversationParams { conversation_id },
         };
-        self.rpc.send(&request).await
+        rpc.send(&request).await
     }
 
     pub(crate) async fn remove_conversation_listener(
         &self,
         subscription_id: uuid::Uuid,
     ) -> Result<RemoveConversationSubscriptionResponse> {
+        let rpc = self.rpc();
         let request = ClientRequest::RemoveConversationListener {
-            request_id: self.rpc.next_request_id(),
+            request_id: rpc.next_request_id(),
             params: RemoveConversationListenerParams { subscription_id },
         };
-        self.rpc
-            .request(request_id(&request), &request, "removeConversationListener")
+        rpc.request(request_id(&request), &request, "removeConversationListener")
             .await
     }
+
+    /// 查询 app-server 支持的模型列表（`model/list`），按游标取完所有分页。
+    pub(crate) async fn list_models(&self) -> Result<Vec<ModelInfo>> {
+        let rpc = self.rpc();
+        let mut models = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let id = rpc.next_request_id();
+            let request = json!({
+                "id": id,
+                "method": "model/list",
+                "params": { "cursor": cursor },
+            });
+            let response: Value = rpc.request(id, &request, "model/list").await?;
+            let (page, next_cursor) = parse_model_list(&response);
+            models.extend(page);
+            match next_cursor {
+                Some(next) if Some(&next) != cursor.as_ref() => cursor = Some(next),
+                _ => break,
+            }
+        }
+        Ok(models)
+    }
+}
+
+fn parse_model_list(response: &Value) -> (Vec<ModelInfo>, Option<String>) {
+    let field = |value: &Value, primary: &str, fallback: &str| {
+        value
+            .get(primary)
+            .or_else(|| value.get(fallback))
+            .and_then(Value::as_str)
+            .map(str::to_string)
+    };
+    let items = response
+        .get("items")
+        .or_else(|| response.get("data"))
+        .and_then(Value::as_array)
+        .cloned()
+        .unwrap_or_default();
+    let models = items
+        .iter()
+        .filter_map(|item| {
+            let id = field(item, "id", "model")?;
+            Some(ModelInfo {
+                name: field(item, "displayName", "display_name").unwrap_or_else(|| id.clone()),
+                description: item
+                    .get("description")
+                    .and_then(Value::as_str)
+                    .map(str::to_string)
+                    .filter(|text| !text.is_empty()),
+                is_default: item
+                    .get("isDefault")
+                    .or_else(|| item.get("is_default"))
+                    .and_then(Value::as_bool)
+                    .unwrap_or(false),
+                id,
+            })
+        })
+        .collect();
+    (models, field(response, "nextCursor", "next_cursor"))
+}
+
+// 等待子进程退出并上报；guard 被 drop 时主动杀掉进程。
+fn watch_child(
+    mut child: tokio::process::Child,
+    generation: u64,
+    events_tx: mpsc::UnboundedSender<AppServerEvent>,
+) -> oneshot::Sender<()> {
+    let (guard, stop) = oneshot::channel::<()>();
+    tokio::spawn(async move {
+        tokio::select! {
+            status = child.wait() => {
+                let status = match status {
+                    Ok(status) => status.to_string(),
+                    Err(err) => err.to_string(),
+                };
+                log_fmt(
+                    LogLevel::Warn,
+                    format_args!("codex app-server 已退出: {status}"),
+                );
+                let _ = events_tx.send(AppServerEvent::Exited { generation, status });
+            }
+            _ = stop => {
+                let _ = child.kill().await;
+            }
+        }
+    });
+    guard
 }
 
 #[async_trait]
@@ -421,6 +561,8 @@ impl JsonRpcPeer {
                     format_args!("app-server read loop failed: {err}"),
                 );
             }
+            // stdout 关闭后不会再有响应，让等待中的请求立即失败而不是一直挂起。
+            peer_clone.pending.lock().await.clear();
         });
         peer
     }
@@ -431,6 +573,7 @@ impl JsonRpcPeer {
 
     async fn send<T: serde::Serialize>(&self, message: &T) -> Result<()> {
         let raw = serde_json::to_string(message)?;
+        log_rpc("->", "app-server", &raw);
         let mut guard = self.stdin.lock().await;
         guard.write_all(raw.as_bytes()).await?;
         guard.write_all(b"\n").await?;
@@ -444,6 +587,7 @@ impl JsonRpcPeer {
         M: serde::Serialize,
     {
         let raw = serde_json::to_string(message)?;
+        log_rpc("->", "app-server", &raw);
         let (tx, rx) = oneshot::channel();
         {
             let mut pending = self.pending.lock().await;
@@ -469,22 +613,26 @@ impl JsonRpcPeer {
         stdout: tokio::process::ChildStdout,
         callbacks: Arc<dyn JsonRpcCallbacks>,
     ) -> Result<()> {
-        let mut reader = BufReader::new(stdout);
-        let mut buffer = String::new();
-        loop {
-            buffer.clear();
-            let bytes = reader.read_line(&mut buffer).await?;
-            if bytes == 0 {
-                break;
-            }
-            let line = buffer.trim_end_matches(['\n', '\r']);
+        let mut reader = LineReader::new(BufReader::new(stdout), MAX_JSON_LINE_BYTES);
+        while let Some(line) = reader.next_line().await? {
+            let line = match line {
+                Ok(line) => line,
+                Err(err) => {
+                    log_fmt(
+                        LogLevel::Warn,
+                        format_args!("丢弃无效的 app-server 输出: {err}"),
+                    );
+                    continue;
+                }
+            };
             if line.is_empty() {
                 continue;
             }
-            let message: JSONRPCMessage = match serde_json::from_str(line) {
+            log_rpc("<-", "app-server", &line);
+            let message: JSONRPCMessage = match serde_json::from_str(&line) {
                 Ok(message) => message,
                 Err(_) => {
-                    callbacks.on_non_json(line).await?;
+                    callbacks.on_non_json(&line).await?;
                     continue;
                 }
             };
@@ -545,6 +693,52 @@ struct AppServerCallbacks {
 #[async_trait]
 impl JsonRpcCallbacks for AppServerCallbacks {
     async fn on_request(&self, peer: &JsonRpcPeer, request: JSONRPCRequest) -> Result<()> {
+        if let Some(method) = ClientFsMethod::from_method(&request.method) {
+            // 需要等编辑器应答，放到单独任务里，避免阻塞 app-server 的读循环。
+            let (respond_to, response_rx) = oneshot::channel();
+            let _ = self.events_tx.send(AppServerEvent::ClientFsRequest {
+                method,
+                params: request.params.clone().unwrap_or(Value::Null),
+                respond_to,
+            });
+            let peer = peer.clone();
+            tokio::spawn(async move {
+                let result = match response_rx.await.ok().flatten() {
+                    Some(Err(message)) => {
+                        let payload = JSONRPCError {
+                            id: request.id,
+                            error: JSONRPCErrorError {
+                                code: -32603,
+                                message,
+                                data: None,
+                            },
+                        };
+                        peer.send(&payload).await
+                    }
+                    Some(Ok(result)) => {
+                        let payload = JSONRPCResponse {
+                            id: request.id,
+                            result,
+                        };
+                        peer.send(&payload).await
+                    }
+                    None => {
+                        let payload = JSONRPCResponse {
+                            id: request.id,
+                            result: Value::Null,
+                        };
+                        peer.send(&payload).await
+                    }
+                };
+                if let Err(err) = result {
+                    log_fmt(
+                        LogLevel::Warn,
+                        format_args!("回复 app-server 文件请求失败: {err}"),
+                    );
+                }
+            });
+            return Ok(());
+        }
         match ServerRequest::try_from(request.clone()) {
             Ok(ServerRequest::ExecCommandApproval { request_id, .. }) => {
                 let response = ExecCommandApprovalResponse {
@@ -1,4 +1,5 @@
 use std::env;
+use std::path::PathBuf;
 
 use anyhow::{anyhow, Result};
 
@@ -9,6 +10,10 @@ pub struct CliConfig {
     pub approval_policy: Option<String>,
     pub sandbox_mode: Option<String>,
     pub app_server_args: Vec<String>,
+    pub log_dir: Option<PathBuf>,
+    pub log_verbose: bool,
+    /// 允许 session/new 使用的 MCP server 命令（按 basename 比较）；None 表示不限制。
+    pub allowed_mcp_commands: Option<Vec<String>>,
 }
 
 impl CliConfig {
@@ -23,6 +28,9 @@ impl CliConfig {
         let mut approval_policy = None;
         let mut sandbox_mode = None;
         let mut app_server_args = Vec::new();
+        let mut log_dir = None;
+        let mut log_verbose = false;
+        let mut allowed_mcp_commands = None;
         let mut args = args.into_iter().peekable();
 
         while let Some(arg) = args.next() {
@@ -51,6 +59,28 @@ impl CliConfig {
                         .ok_or_else(|| anyhow!("--sandbox-mode missing value"))?;
                     sandbox_mode = Some(value.replace('_', "-"));
                 }
+                "--log-dir" | "--log_dir" => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| anyhow!("--log-dir missing value"))?;
+                    log_dir = Some(PathBuf::from(value));
+                }
+                "--log-verbose" | "--log_verbose" => {
+                    log_verbose = true;
+                }
+                "--allowed-mcp-commands" | "--allowed_mcp_commands" => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| anyhow!("--allowed-mcp-commands missing value"))?;
+                    allowed_mcp_commands = Some(
+                        value
+                            .split(',')
+                            .map(str::trim)
+                            .filter(|item| !item.is_empty())
+                            .map(str::to_string)
+                            .collect(),
+                    );
+                }
                 "-c" | "--config" => {
                     let value = args
                         .next()
@@ -71,6 +101,9 @@ impl CliConfig {
             approval_policy,
             sandbox_mode,
             app_server_args,
+            log_dir,
+            log_verbose,
+            allowed_mcp_commands,
         })
     }
 
@@ -1,4 +1,6 @@
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Instant;
 
 use anyhow::{anyhow, Result};
 use codex_app_server_protocol::InputItem;
@@ -22,18 +24,26 @@ use crate::protocol::{
     AuthenticateParamsInput, CancelParamsInput, ContentBlock, DeleteSessionParamsInput,
     InitializeParamsInput, JsonRpcErrorOut, JsonRpcErrorOutPayload, JsonRpcIncomingRequest,
     JsonRpcResponseOut, ListSessionsParamsInput, LoadSessionParamsInput, NewSessionParamsInput,
-    PromptParamsInput,
+    PromptParamsInput, SelectModelParamsInput,
 };
 use crate::sessions::{delete_workspace_session, list_workspace_sessions};
-use crate::state::AcpState;
+use crate::state::{AcpState, ClientFsCapabilities, ClientFsMethod, ModelInfo, QueuedPrompt};
 use crate::utils::{
-    build_mcp_overrides, build_new_conversation_params, insert_dual, load_mcp_servers,
-    load_rollout_history, normalize_cwd, normalize_mcp_servers, save_mcp_servers, update_with_type,
-    write_temp_image, SessionHandler,
+    build_mcp_overrides, build_new_conversation_params, disallowed_mcp_servers, insert_dual,
+    load_mcp_servers, load_rollout_history, normalize_cwd, normalize_mcp_servers, save_mcp_servers,
+    update_with_type, write_temp_image, SessionHandler,
 };
 use crate::writer::AcpWriter;
 
 const APP_SERVER_MAX_RETRIES: u32 = 5;
+// turn 进行中最多排队的 prompt 数，超出时返回 turn_in_progress。
+const MAX_QUEUED_PROMPTS: usize = 4;
+const TURN_IN_PROGRESS_CODE: i32 = -32001;
+const INVALID_PARAMS_CODE: i32 = -32602;
+const CLIENT_FS_TIMEOUT: Duration = Duration::from_secs(30);
+// app-server 进程意外退出后，每个窗口内最多自动重启的次数。
+const APP_SERVER_MAX_RESTARTS: usize = 3;
+const APP_SERVER_RESTART_WINDOW: Duration = Duration::from_secs(300);
 
 fn extract_tool_result_text(value: &Value) -> Option<String> {
     match value {
@@ -89,9 +99,13 @@ async fn reset_session_state(
         let mut guard = state.lock().await;
         let previous = (guard.conversation_id, guard.conversation_subscription_id);
         guard.session_id = None;
-        guard.pending_prompt_ids.clear();
+        guard.active_prompt_id = None;
+        guard.queued_prompts.clear();
         guard.conversation_id = None;
         guard.conversation_subscription_id = None;
+        guard.rollout_path = None;
+        guard.session_cwd = None;
+        guard.current_model = None;
         guard.saw_message_delta = false;
         guard.saw_reasoning_delta = false;
         guard.retry_count = 0;
@@ -158,20 +172,15 @@ async fn handle_error_message(
     message: String,
     writer: &AcpWriter,
     state: &Arc<Mutex<AcpState>>,
+    app_server: &Arc<AppServerClient>,
 ) -> Result<()> {
     if is_retry_related_message(&message) {
-        handle_retry_signal(session_id, message, false, writer, state).await?;
+        handle_retry_signal(session_id, message, false, writer, state, app_server).await?;
         return Ok(());
     }
     let mut update = update_with_type("error");
     update.insert("error".to_string(), json!({ "message": message }));
     send_session_update(writer, session_id, Value::Object(update)).await?;
-    if let Some(prompt_id) = {
-        let mut guard = state.lock().await;
-        guard.pending_prompt_ids.pop_front()
-    } {
-        send_prompt_complete(writer, prompt_id, "error").await?;
-    }
     {
         let mut guard = state.lock().await;
         guard.saw_message_delta = false;
@@ -179,7 +188,66 @@ async fn handle_error_message(
         guard.retry_count = 0;
         guard.retry_exhausted = false;
     }
-    Ok(())
+    finish_active_prompt(writer, state, app_server, "error").await
+}
+
+enum PromptAdmission {
+    Start(Vec<InputItem>),
+    Queued(usize),
+    Rejected,
+}
+
+/// 结束当前 turn：回复进行中的 session/prompt，然后提交队列中的下一个 prompt。
+async fn finish_active_prompt(
+    writer: &AcpWriter,
+    state: &Arc<Mutex<AcpState>>,
+    app_server: &Arc<AppServerClient>,
+    stop_reason: &str,
+) -> Result<()> {
+    let finished = {
+        let mut guard = state.lock().await;
+        guard.active_prompt_id.take()
+    };
+    if let Some(prompt_id) = finished {
+        send_prompt_complete(writer, prompt_id, stop_reason).await?;
+    }
+    loop {
+        let (next, conversation_id) = {
+            let mut guard = state.lock().await;
+            if guard.active_prompt_id.is_some() {
+                return Ok(());
+            }
+            let Some(next) = guard.queued_prompts.pop_front() else {
+                return Ok(());
+            };
+            guard.active_prompt_id = Some(next.id);
+            guard.saw_message_delta = false;
+            guard.saw_reasoning_delta = false;
+            guard.retry_count = 0;
+            guard.retry_exhausted = false;
+            (next, guard.conversation_id)
+        };
+        let result = match conversation_id {
+            Some(conversation_id) => app_server
+                .send_user_message(conversation_id, next.items)
+                .await
+                .map(|_| ()),
+            None => Err(anyhow!("尚未初始化会话")),
+        };
+        let Err(err) = result else {
+            return Ok(());
+        };
+        // 提交失败时该 prompt 以错误结束，继续尝试队列中的下一个。
+        log_fmt(
+            LogLevel::Warn,
+            format_args!("提交排队的 prompt 失败: {err}"),
+        );
+        {
+            let mut guard = state.lock().await;
+            guard.active_prompt_id = None;
+        }
+        send_prompt_error(writer, next.id, -32000, err.to_string()).await?;
+    }
 }
 
 pub(crate) async fn handle_codex_event(
@@ -187,6 +255,7 @@ pub(crate) async fn handle_codex_event(
     event: EventMsg,
     writer: &AcpWriter,
     state: &Arc<Mutex<AcpState>>,
+    app_server: &Arc<AppServerClient>,
 ) -> Result<()> {
     // `addConversationListener` is sticky unless explicitly removed; without
     // filtering, events from an old conversation can be mislabeled as the new
@@ -498,10 +567,10 @@ pub(crate) async fn handle_codex_event(
             .await?;
         }
         EventMsg::StreamError(StreamErrorEvent { message, .. }) => {
-            handle_error_message(&session_id, message, writer, state).await?;
+            handle_error_message(&session_id, message, writer, state, app_server).await?;
         }
         EventMsg::Error(ErrorEvent { message, .. }) => {
-            handle_error_message(&session_id, message, writer, state).await?;
+            handle_error_message(&session_id, message, writer, state, app_server).await?;
         }
         EventMsg::TaskComplete(_) => {
             let retry_active = {
@@ -533,12 +602,6 @@ pub(crate) async fn handle_codex_event(
                 Value::String("end_turn".to_string()),
             );
             send_session_update(writer, &session_id, Value::Object(update)).await?;
-            if let Some(prompt_id) = {
-                let mut guard = state.lock().await;
-                guard.pending_prompt_ids.pop_front()
-            } {
-                send_prompt_complete(writer, prompt_id, "end_turn").await?;
-            }
             {
                 let mut guard = state.lock().await;
                 guard.saw_message_delta = false;
@@ -546,6 +609,7 @@ pub(crate) async fn handle_codex_event(
                 guard.retry_count = 0;
                 guard.retry_exhausted = false;
             }
+            finish_active_prompt(writer, state, app_server, "end_turn").await?;
         }
         _ => {}
     }
@@ -553,6 +617,49 @@ pub(crate) async fn handle_codex_event(
     Ok(())
 }
 
+/// 把 app-server 的文件请求转成 ACP `fs/*` 请求发给编辑器，可读到未保存的缓冲区内容。
+/// 客户端没有声明对应能力时返回 None，保持原来的行为。
+pub(crate) async fn forward_client_fs_request(
+    method: ClientFsMethod,
+    params: Value,
+    writer: &AcpWriter,
+    state: &Arc<Mutex<AcpState>>,
+) -> Option<Result<Value, String>> {
+    let (allowed, session_id) = {
+        let guard = state.lock().await;
+        (guard.client_fs.allows(method), guard.session_id.clone())
+    };
+    if !allowed {
+        return None;
+    }
+    let Value::Object(mut params) = params else {
+        return Some(Err(format!(
+            "{} params must be an object",
+            method.acp_method()
+        )));
+    };
+    // app-server 以 conversationId 标识会话，编辑器只认 ACP 的 sessionId。
+    params.remove("conversationId");
+    params.remove("conversation_id");
+    if !params.contains_key("sessionId") {
+        if let Some(session_id) = session_id {
+            params.insert("sessionId".to_string(), Value::String(session_id));
+        }
+    }
+    let result = writer
+        .request(
+            method.acp_method(),
+            Value::Object(params),
+            CLIENT_FS_TIMEOUT,
+        )
+        .await
+        .map_err(|err| err.to_string());
+    if let Err(err) = &result {
+        log_fmt(LogLevel::Warn, format_args!("转发文件请求失败: {err}"));
+    }
+    Some(result)
+}
+
 pub(crate) async fn handle_acp_request(
     request: JsonRpcIncomingRequest,
     writer: &AcpWriter,
@@ -585,7 +692,7 @@ async fn handle_acp_request_inner(
 ) -> Result<()> {
     match request.method.as_str() {
         "initialize" => {
-            let _params: InitializeParamsInput = request
+            let params: InitializeParamsInput = request
                 .params
                 .as_ref()
                 .map(|value| serde_json::from_value(value.clone()))
@@ -597,6 +704,8 @@ async fn handle_acp_request_inner(
                 });
 
             let mut guard = state.lock().await;
+            guard.client_fs =
+                ClientFsCapabilities::from_client_capabilities(&params.client_capabilities);
             if !guard.app_server_initialized {
                 guard.app_server_initialized = true;
                 drop(guard);
@@ -665,6 +774,11 @@ async fn handle_acp_request_inner(
                 .map(|value| serde_json::from_value(value.clone()))
                 .transpose()?
                 .ok_or_else(|| anyhow!("session/new missing params"))?;
+            if reject_disallowed_mcp_servers(writer, config, request.id, &params.mcp_servers)
+                .await?
+            {
+                return Ok(());
+            }
             let cwd = normalize_cwd(&params.cwd);
             reset_session_state(state, app_server).await?;
             let mut conversation_params = build_new_conversation_params(config, &cwd)?;
@@ -683,6 +797,8 @@ async fn handle_acp_request_inner(
                 let mut guard = state.lock().await;
                 guard.conversation_id = Some(conversation_id);
                 guard.session_id = Some(session_id.clone());
+                guard.rollout_path = Some(response.rollout_path.clone());
+                guard.session_cwd = Some(cwd.clone());
             }
             let subscription = app_server
                 .add_conversation_listener(conversation_id)
@@ -692,6 +808,7 @@ async fn handle_acp_request_inner(
                 guard.conversation_subscription_id = Some(subscription.subscription_id);
             }
 
+            let models = available_models(state, app_server).await;
             let mut result = serde_json::Map::new();
             insert_dual(
                 &mut result,
@@ -700,7 +817,13 @@ async fn handle_acp_request_inner(
                 Value::String(session_id),
             );
             result.insert("modes".to_string(), Value::Array(Vec::new()));
-            result.insert("models".to_string(), Value::Array(Vec::new()));
+            result.insert("models".to_string(), models_value(&models));
+            insert_dual(
+                &mut result,
+                "current_model_id",
+                "currentModelId",
+                default_model_id(&models),
+            );
             insert_dual(
                 &mut result,
                 "config_options",
@@ -721,8 +844,7 @@ async fn handle_acp_request_inner(
                 .map(|value| serde_json::from_value(value.clone()))
                 .transpose()?
                 .unwrap_or(ListSessionsParamsInput { cwd: None });
-            let sessions = list_workspace_sessions()?;
-            let result = json!({ "sessions": sessions });
+            let result = list_workspace_sessions()?.to_value();
             let response = JsonRpcResponseOut {
                 jsonrpc: "2.0",
                 id: request.id,
@@ -737,6 +859,11 @@ async fn handle_acp_request_inner(
                 .map(|value| serde_json::from_value(value.clone()))
                 .transpose()?
                 .ok_or_else(|| anyhow!("session/load missing params"))?;
+            if reject_disallowed_mcp_servers(writer, config, request.id, &params.mcp_servers)
+                .await?
+            {
+                return Ok(());
+            }
 
             reset_session_state(state, app_server).await?;
 
@@ -778,6 +905,8 @@ async fn handle_acp_request_inner(
                 let mut guard = state.lock().await;
                 guard.session_id = Some(params.session_id.clone());
                 guard.conversation_id = Some(conversation_id);
+                guard.rollout_path = Some(rollout_path.clone());
+                guard.session_cwd = Some(cwd.clone());
             }
             let subscription = app_server
                 .add_conversation_listener(conversation_id)
@@ -791,9 +920,13 @@ async fn handle_acp_request_inner(
                 .await
                 .unwrap_or_default();
 
+            let models = available_models(state, app_server).await;
+            let current = default_model_id(&models);
             let result = json!({
                 "modes": [],
-                "models": [],
+                "models": models_value(&models),
+                "current_model_id": current,
+                "currentModelId": current,
                 "history": history,
             });
             let response = JsonRpcResponseOut {
@@ -837,14 +970,6 @@ async fn handle_acp_request_inner(
                 return Err(anyhow!("session_id 不匹配"));
             }
 
-            {
-                let mut guard = state.lock().await;
-                guard.saw_message_delta = false;
-                guard.saw_reasoning_delta = false;
-                guard.retry_count = 0;
-                guard.retry_exhausted = false;
-            }
-
             let mut items = Vec::new();
             for block in params.prompt {
                 match block {
@@ -878,13 +1003,141 @@ async fn handle_acp_request_inner(
                 return Ok(());
             }
 
-            app_server.send_user_message(conversation_id, items).await?;
-
-            {
+            // 上一个 turn 尚未结束时排队，避免多个 prompt 同时提交导致响应错位。
+            let admission = {
                 let mut guard = state.lock().await;
-                guard.pending_prompt_ids.push_back(request.id);
+                if guard.active_prompt_id.is_none() {
+                    guard.active_prompt_id = Some(request.id);
+                    guard.saw_message_delta = false;
+                    guard.saw_reasoning_delta = false;
+                    guard.retry_count = 0;
+                    guard.retry_exhausted = false;
+                    PromptAdmission::Start(items)
+                } else if guard.queued_prompts.len() >= MAX_QUEUED_PROMPTS {
+                    PromptAdmission::Rejected
+                } else {
+                    guard.queued_prompts.push_back(QueuedPrompt {
+                        id: request.id,
+                        items,
+                    });
+                    PromptAdmission::Queued(guard.queued_prompts.len())
+                }
+            };
+            match admission {
+                PromptAdmission::Start(items) => {
+                    if let Err(err) = app_server.send_user_message(conversation_id, items).await {
+                        // 提交失败时释放 turn，由外层把错误回给本次请求，并继续处理排队的 prompt。
+                        state.lock().await.active_prompt_id = None;
+                        finish_active_prompt(writer, state, app_server, "error").await?;
+                        return Err(err);
+                    }
+                }
+                PromptAdmission::Queued(position) => {
+                    let mut update = update_with_type("prompt_queued");
+                    insert_dual(
+                        &mut update,
+                        "prompt_id",
+                        "promptId",
+                        Value::Number(serde_json::Number::from(request.id)),
+                    );
+                    insert_dual(
+                        &mut update,
+                        "position",
+                        "position",
+                        Value::Number(serde_json::Number::from(position)),
+                    );
+                    send_session_update(writer, &session_id, Value::Object(update)).await?;
+                }
+                PromptAdmission::Rejected => {
+                    send_prompt_error(
+                        writer,
+                        request.id,
+                        TURN_IN_PROGRESS_CODE,
+                        "turn_in_progress".to_string(),
+                    )
+                    .await?;
+                }
             }
         }
+        "session/select_model" | "session/set_model" => {
+            let params: SelectModelParamsInput = request
+                .params
+                .as_ref()
+                .map(|value| serde_json::from_value(value.clone()))
+                .transpose()?
+                .ok_or_else(|| anyhow!("session/select_model missing params"))?;
+            let models = available_models(state, app_server).await;
+            if !models.iter().any(|model| model.id == params.model_id) {
+                let valid: Vec<&str> = models.iter().map(|model| model.id.as_str()).collect();
+                let response = JsonRpcErrorOut {
+                    jsonrpc: "2.0",
+                    id: request.id,
+                    error: JsonRpcErrorOutPayload {
+                        code: INVALID_PARAMS_CODE,
+                        message: format!(
+                            "unknown model: {}; valid models: {}",
+                            params.model_id,
+                            valid.join(", ")
+                        ),
+                        data: Some(json!({ "validModelIds": valid })),
+                    },
+                };
+                writer.send_json(&response).await?;
+                return Ok(());
+            }
+
+            let (session_id, rollout_path, cwd, busy, previous_model) = {
+                let guard = state.lock().await;
+                (
+                    guard.session_id.clone(),
+                    guard.rollout_path.clone(),
+                    guard.session_cwd.clone(),
+                    guard.active_prompt_id.is_some() || !guard.queued_prompts.is_empty(),
+                    guard.current_model.clone(),
+                )
+            };
+            let session_id = session_id.ok_or_else(|| anyhow!("尚未初始化会话"))?;
+            if params.session_id != session_id {
+                return Err(anyhow!("session_id 不匹配"));
+            }
+            if previous_model.as_deref() != Some(params.model_id.as_str()) {
+                // 换模型要从 rollout 重新恢复会话，turn 进行中时不能切换。
+                if busy {
+                    send_prompt_error(
+                        writer,
+                        request.id,
+                        TURN_IN_PROGRESS_CODE,
+                        "turn_in_progress".to_string(),
+                    )
+                    .await?;
+                    return Ok(());
+                }
+                state.lock().await.current_model = Some(params.model_id.clone());
+                if let Some(rollout_path) = rollout_path {
+                    let cwd = cwd.unwrap_or_else(|| normalize_cwd("."));
+                    if let Err(err) =
+                        switch_conversation(state, app_server, config, rollout_path, &cwd).await
+                    {
+                        state.lock().await.current_model = previous_model;
+                        return Err(err);
+                    }
+                }
+                let mut update = update_with_type("current_model_update");
+                insert_dual(
+                    &mut update,
+                    "current_model_id",
+                    "currentModelId",
+                    Value::String(params.model_id.clone()),
+                );
+                send_session_update(writer, &session_id, Value::Object(update)).await?;
+            }
+            let response = JsonRpcResponseOut {
+                jsonrpc: "2.0",
+                id: request.id,
+                result: Value::Null,
+            };
+            writer.send_json(&response).await?;
+        }
         "session/cancel" => {
             let _params: CancelParamsInput = request
                 .params
@@ -909,18 +1162,19 @@ async fn handle_acp_request_inner(
                     );
                 }
             }
-            if let Some(prompt_id) = {
-                let mut guard = state.lock().await;
-                guard.pending_prompt_ids.pop_front()
-            } {
-                send_prompt_complete(writer, prompt_id, "cancelled").await?;
-            }
-            {
+            // 取消同时清空排队中的 prompt，全部以 cancelled 结束。
+            let cancelled_ids = {
                 let mut guard = state.lock().await;
                 guard.saw_message_delta = false;
                 guard.saw_reasoning_delta = false;
                 guard.retry_count = 0;
                 guard.retry_exhausted = false;
+                let mut ids: Vec<u64> = guard.active_prompt_id.take().into_iter().collect();
+                ids.extend(guard.queued_prompts.drain(..).map(|prompt| prompt.id));
+                ids
+            };
+            for prompt_id in cancelled_ids {
+                send_prompt_complete(writer, prompt_id, "cancelled").await?;
             }
             let response = JsonRpcResponseOut {
                 jsonrpc: "2.0",
@@ -969,10 +1223,282 @@ async fn send_prompt_complete(writer: &AcpWriter, id: u64, stop_reason: &str) ->
     writer.send_json(&response).await
 }
 
+/// 返回缓存的模型列表，首次调用时向 app-server 查询；不支持 `model/list` 的旧版本按空列表处理。
+async fn available_models(
+    state: &Arc<Mutex<AcpState>>,
+    app_server: &Arc<AppServerClient>,
+) -> Vec<ModelInfo> {
+    if let Some(models) = state.lock().await.available_models.clone() {
+        return models;
+    }
+    let models = match app_server.list_models().await {
+        Ok(models) => models,
+        Err(err) => {
+            log_fmt(LogLevel::Warn, format_args!("查询模型列表失败: {err}"));
+            Vec::new()
+        }
+    };
+    state.lock().await.available_models = Some(models.clone());
+    models
+}
+
+fn models_value(models: &[ModelInfo]) -> Value {
+    Value::Array(
+        models
+            .iter()
+            .map(|model| {
+                let mut entry = serde_json::Map::new();
+                entry.insert("id".to_string(), Value::String(model.id.clone()));
+                entry.insert("name".to_string(), Value::String(model.name.clone()));
+                if let Some(description) = &model.description {
+                    entry.insert(
+                        "description".to_string(),
+                        Value::String(description.clone()),
+                    );
+                }
+                insert_dual(
+                    &mut entry,
+                    "is_default",
+                    "isDefault",
+                    Value::Bool(model.is_default),
+                );
+                Value::Object(entry)
+            })
+            .collect(),
+    )
+}
+
+// 新建/加载的会话不带模型覆盖，当前模型就是 app-server 标记的默认模型。
+fn default_model_id(models: &[ModelInfo]) -> Value {
+    models
+        .iter()
+        .find(|model| model.is_default)
+        .map(|model| Value::String(model.id.clone()))
+        .unwrap_or(Value::Null)
+}
+
+/// 配置了 `--allowed-mcp-commands` 时校验客户端传入的 MCP server，不通过则直接回 JSON-RPC 错误。
+async fn reject_disallowed_mcp_servers(
+    writer: &AcpWriter,
+    config: &CliConfig,
+    id: u64,
+    servers: &[Value],
+) -> Result<bool> {
+    let Some(allowed) = config.allowed_mcp_commands.as_deref() else {
+        return Ok(false);
+    };
+    let rejected = disallowed_mcp_servers(servers, allowed);
+    if rejected.is_empty() {
+        return Ok(false);
+    }
+    log_fmt(
+        LogLevel::Warn,
+        format_args!("拒绝未授权的 MCP server: {}", rejected.join(", ")),
+    );
+    let response = JsonRpcErrorOut {
+        jsonrpc: "2.0",
+        id,
+        error: JsonRpcErrorOutPayload {
+            code: -32602,
+            message: format!("MCP server command not allowed: {}", rejected.join(", ")),
+            data: Some(json!({ "rejected": rejected })),
+        },
+    };
+    writer.send_json(&response).await?;
+    Ok(true)
+}
+
+async fn send_prompt_error(writer: &AcpWriter, id: u64, code: i32, message: String) -> Result<()> {
+    let response = JsonRpcErrorOut {
+        jsonrpc: "2.0",
+        id,
+        error: JsonRpcErrorOutPayload {
+            code,
+            message,
+            data: None,
+        },
+    };
+    writer.send_json(&response).await
+}
+
+/// app-server 进程退出：结束进行中的 prompt，在限额内重启进程并恢复当前会话。
+pub(crate) async fn handle_app_server_exit(
+    generation: u64,
+    status: String,
+    writer: &AcpWriter,
+    state: &Arc<Mutex<AcpState>>,
+    app_server: &Arc<AppServerClient>,
+    config: &CliConfig,
+) -> Result<()> {
+    if generation != app_server.generation() {
+        return Ok(());
+    }
+    let (session_id, active_prompt, queued) = {
+        let mut guard = state.lock().await;
+        guard.saw_message_delta = false;
+        guard.saw_reasoning_delta = false;
+        guard.retry_count = 0;
+        guard.retry_exhausted = false;
+        (
+            guard.session_id.clone(),
+            guard.active_prompt_id.take(),
+            guard.queued_prompts.drain(..).collect::<Vec<_>>(),
+        )
+    };
+    if let Some(session_id) = session_id.as_deref() {
+        send_error_update(
+            writer,
+            session_id,
+            format!("codex app-server 意外退出（{status}），正在尝试恢复"),
+        )
+        .await?;
+    }
+    if let Some(prompt_id) = active_prompt {
+        send_prompt_complete(writer, prompt_id, "error").await?;
+    }
+    for prompt in queued {
+        send_prompt_error(
+            writer,
+            prompt.id,
+            -32000,
+            "codex app-server 已退出".to_string(),
+        )
+        .await?;
+    }
+
+    loop {
+        let allowed = {
+            let mut guard = state.lock().await;
+            let now = Instant::now();
+            while guard
+                .app_server_restarts
+                .front()
+                .is_some_and(|at| now.duration_since(*at) > APP_SERVER_RESTART_WINDOW)
+            {
+                guard.app_server_restarts.pop_front();
+            }
+            let allowed = guard.app_server_restarts.len() < APP_SERVER_MAX_RESTARTS;
+            if allowed {
+                guard.app_server_restarts.push_back(now);
+            }
+            allowed
+        };
+        if !allowed {
+            let message = format!(
+                "codex app-server 在 {} 分钟内已重启 {APP_SERVER_MAX_RESTARTS} 次，停止自动恢复，请重启 agent",
+                APP_SERVER_RESTART_WINDOW.as_secs() / 60
+            );
+            log_fmt(LogLevel::Error, format_args!("{message}"));
+            if let Some(session_id) = session_id.as_deref() {
+                send_error_update(writer, session_id, message).await?;
+            }
+            return Ok(());
+        }
+        match restart_app_server(state, app_server, config).await {
+            Ok(()) => {
+                log_fmt(LogLevel::Info, format_args!("codex app-server 已恢复"));
+                return Ok(());
+            }
+            Err(err) => {
+                log_fmt(
+                    LogLevel::Warn,
+                    format_args!("重启 codex app-server 失败: {err}"),
+                );
+                sleep(Duration::from_secs(1)).await;
+            }
+        }
+    }
+}
+
+async fn restart_app_server(
+    state: &Arc<Mutex<AcpState>>,
+    app_server: &Arc<AppServerClient>,
+    config: &CliConfig,
+) -> Result<()> {
+    app_server.respawn(config).await?;
+    let (initialized, rollout_path, cwd) = {
+        let mut guard = state.lock().await;
+        // 旧进程上的订阅随进程一起失效。
+        guard.conversation_subscription_id = None;
+        (
+            guard.app_server_initialized,
+            guard.rollout_path.clone(),
+            guard.session_cwd.clone(),
+        )
+    };
+    if initialized {
+        app_server.initialize().await?;
+    }
+    let Some(rollout_path) = rollout_path else {
+        let mut guard = state.lock().await;
+        guard.conversation_id = None;
+        return Ok(());
+    };
+    let cwd = cwd.unwrap_or_else(|| normalize_cwd("."));
+    resume_rollout(state, app_server, config, rollout_path, &cwd).await
+}
+
+/// 用当前选中的模型从 rollout 恢复会话，历史保留。
+async fn resume_rollout(
+    state: &Arc<Mutex<AcpState>>,
+    app_server: &Arc<AppServerClient>,
+    config: &CliConfig,
+    rollout_path: PathBuf,
+    cwd: &Path,
+) -> Result<()> {
+    let mut conversation_params = build_new_conversation_params(config, cwd)?;
+    conversation_params.model = state.lock().await.current_model.clone();
+    let mcp_servers = load_mcp_servers(&rollout_path).ok().flatten();
+    if let Some(overrides) = mcp_servers.as_deref().and_then(build_mcp_overrides) {
+        conversation_params.config = Some(overrides);
+    }
+    let response = app_server
+        .resume_conversation(rollout_path, conversation_params)
+        .await?;
+    let conversation_id = response.conversation_id;
+    // session_id 保持不变，编辑器继续用原来的会话。
+    state.lock().await.conversation_id = Some(conversation_id);
+    let subscription = app_server
+        .add_conversation_listener(conversation_id)
+        .await?;
+    state.lock().await.conversation_subscription_id = Some(subscription.subscription_id);
+    Ok(())
+}
+
+/// 切换模型：退订当前会话，再按新模型从 rollout 恢复。
+async fn switch_conversation(
+    state: &Arc<Mutex<AcpState>>,
+    app_server: &Arc<AppServerClient>,
+    config: &CliConfig,
+    rollout_path: PathBuf,
+    cwd: &Path,
+) -> Result<()> {
+    let previous_subscription_id = state.lock().await.conversation_subscription_id.take();
+    if let Some(previous_subscription_id) = previous_subscription_id {
+        if let Err(err) = app_server
+            .remove_conversation_listener(previous_subscription_id)
+            .await
+        {
+            log_fmt(
+                LogLevel::Warn,
+                format_args!("removeConversationListener 失败: {err}"),
+            );
+        }
+    }
+    resume_rollout(state, app_server, config, rollout_path, cwd).await
+}
+
+async fn send_error_update(writer: &AcpWriter, session_id: &str, message: String) -> Result<()> {
+    let mut update = update_with_type("error");
+    update.insert("error".to_string(), json!({ "message": message }));
+    send_session_update(writer, session_id, Value::Object(update)).await
+}
+
 pub(crate) async fn handle_app_server_stderr_line(
     line: String,
     writer: &AcpWriter,
     state: &Arc<Mutex<AcpState>>,
+    app_server: &Arc<AppServerClient>,
 ) -> Result<()> {
     let session_id = {
         let guard = state.lock().await;
@@ -990,7 +1516,7 @@ pub(crate) async fn handle_app_server_stderr_line(
     }
 
     let message = extract_canonical_error_message(&line);
-    handle_retry_signal(&session_id, message, true, writer, state).await
+    handle_retry_signal(&session_id, message, true, writer, state, app_server).await
 }
 
 fn extract_canonical_error_message(line: &str) -> String {
@@ -1049,6 +1575,7 @@ async fn handle_retry_signal(
     increment_if_missing: bool,
     writer: &AcpWriter,
     state: &Arc<Mutex<AcpState>>,
+    app_server: &Arc<AppServerClient>,
 ) -> Result<()> {
     let parsed_attempt = parse_retry_progress(&message);
     let (attempt, exhausted) = {
@@ -1116,17 +1643,10 @@ async fn handle_retry_signal(
     let mut update = update_with_type("error");
     update.insert("error".to_string(), json!({ "message": error_message }));
     send_session_update(writer, session_id, Value::Object(update)).await?;
-    if let Some(prompt_id) = {
-        let mut guard = state.lock().await;
-        guard.pending_prompt_ids.pop_front()
-    } {
-        send_prompt_complete(writer, prompt_id, "error").await?;
-    }
     {
         let mut guard = state.lock().await;
         guard.saw_message_delta = false;
         guard.saw_reasoning_delta = false;
     }
-
-    Ok(())
+    finish_active_prompt(writer, state, app_server, "error").await
 }
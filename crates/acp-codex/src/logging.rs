@@ -1,5 +1,9 @@
 use std::fmt;
-use std::sync::{Arc, OnceLock};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::SystemTime;
 
 #[derive(Clone, Copy, Debug)]
 pub enum LogLevel {
@@ -11,6 +15,12 @@ pub enum LogLevel {
 type LogSink = Arc<dyn Fn(LogLevel, &str) + Send + Sync + 'static>;
 
 static LOG_SINK: OnceLock<LogSink> = OnceLock::new();
+static FILE_LOG: OnceLock<FileLog> = OnceLock::new();
+
+const LOG_FILE_NAME: &str = "acp-codex.log";
+const LOG_ROTATE_BYTES: u64 = 10 * 1024 * 1024;
+// 包含当前文件在内共保留 3 个：acp-codex.log、.1、.2。
+const LOG_KEEP_FILES: usize = 3;
 
 pub fn set_log_sink(sink: LogSink) {
     let _ = LOG_SINK.set(sink);
@@ -23,9 +33,159 @@ pub fn log_fmt(level: LogLevel, args: fmt::Arguments) {
         LogLevel::Info => "info",
     };
     let line = format!("[acp-codex][{tag}] {args}");
+    if let Some(file) = FILE_LOG.get() {
+        file.append(&line);
+    }
     if let Some(sink) = LOG_SINK.get() {
         (sink)(level, &line);
     } else {
         eprintln!("{line}");
     }
 }
+
+/// 启用 `--log-dir`：所有经由 [`log_fmt`] 的日志（含 app-server stderr）额外追加到轮转日志文件。
+pub(crate) fn init_file_log(dir: &Path, verbose: bool) -> anyhow::Result<()> {
+    fs::create_dir_all(dir)?;
+    let log = FileLog::open(dir.join(LOG_FILE_NAME), verbose)?;
+    let _ = FILE_LOG.set(log);
+    Ok(())
+}
+
+/// 记录 JSON-RPC 方法名；`--log-verbose` 时附带完整消息体。只写文件，不写 stderr。
+pub(crate) fn log_rpc(direction: &str, peer: &str, raw: &str) {
+    let Some(file) = FILE_LOG.get() else {
+        return;
+    };
+    let label = rpc_label(raw);
+    if file.verbose {
+        file.append(&format!(
+            "[acp-codex][rpc] {peer} {direction} {label} {raw}"
+        ));
+    } else {
+        file.append(&format!("[acp-codex][rpc] {peer} {direction} {label}"));
+    }
+}
+
+fn rpc_label(raw: &str) -> String {
+    let Ok(value) = serde_json::from_str::<serde_json::Value>(raw) else {
+        return "non-json".to_string();
+    };
+    if let Some(method) = value.get("method").and_then(|method| method.as_str()) {
+        return method.to_string();
+    }
+    let id = value
+        .get("id")
+        .map(|id| id.to_string())
+        .unwrap_or_else(|| "?".to_string());
+    if value.get("error").is_some() {
+        format!("error#{id}")
+    } else {
+        format!("response#{id}")
+    }
+}
+
+struct FileLog {
+    path: PathBuf,
+    verbose: bool,
+    rotate_bytes: u64,
+    inner: Mutex<FileLogInner>,
+}
+
+struct FileLogInner {
+    file: File,
+    size: u64,
+}
+
+impl FileLog {
+    fn open(path: PathBuf, verbose: bool) -> std::io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata().map(|meta| meta.len()).unwrap_or(0);
+        Ok(Self {
+            path,
+            verbose,
+            rotate_bytes: LOG_ROTATE_BYTES,
+            inner: Mutex::new(FileLogInner { file, size }),
+        })
+    }
+
+    fn append(&self, line: &str) {
+        let Ok(mut inner) = self.inner.lock() else {
+            return;
+        };
+        let entry = format!(
+            "{} {line}\n",
+            humantime::format_rfc3339_millis(SystemTime::now())
+        );
+        if inner.size > 0 && inner.size + entry.len() as u64 > self.rotate_bytes {
+            if let Ok(file) = self.rotate() {
+                inner.file = file;
+                inner.size = 0;
+            }
+        }
+        if inner.file.write_all(entry.as_bytes()).is_ok() {
+            inner.size += entry.len() as u64;
+        }
+    }
+
+    fn rotate(&self) -> std::io::Result<File> {
+        for index in (1..LOG_KEEP_FILES).rev() {
+            let from = if index == 1 {
+                self.path.clone()
+            } else {
+                rotated_path(&self.path, index - 1)
+            };
+            let to = rotated_path(&self.path, index);
+            if from.exists() {
+                let _ = fs::rename(&from, &to);
+            }
+        }
+        OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&self.path)
+    }
+}
+
+fn rotated_path(path: &Path, index: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{index}"));
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::UNIX_EPOCH;
+
+    fn temp_dir(prefix: &str) -> PathBuf {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("{prefix}-{nanos}"));
+        fs::create_dir_all(&dir).expect("create temp dir");
+        dir
+    }
+
+    #[test]
+    fn rotates_past_the_cap_and_keeps_two_backups() {
+        let dir = temp_dir("acp-codex-log-rotate");
+        let path = dir.join(LOG_FILE_NAME);
+        let mut log = FileLog::open(path.clone(), false).expect("open");
+        log.rotate_bytes = 40;
+        // 每行 32 字节（时间戳 + 内容），第二行起每写一行都会先轮转；line-0 最终被淘汰。
+        for index in 0..4 {
+            log.append(&format!("line-{index}"));
+        }
+
+        let read = |path: PathBuf| fs::read_to_string(path).expect("read log");
+        assert!(read(path.clone()).contains("line-3"));
+        assert!(read(rotated_path(&path, 1)).contains("line-2"));
+        assert!(read(rotated_path(&path, 2)).contains("line-1"));
+        assert!(!rotated_path(&path, LOG_KEEP_FILES).exists());
+        let files = fs::read_dir(&dir).expect("read dir").count();
+        assert_eq!(files, LOG_KEEP_FILES);
+        fs::remove_dir_all(&dir).ok();
+    }
+}
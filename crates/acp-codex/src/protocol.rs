@@ -1,6 +1,6 @@
 pub(crate) use acp_types::{
     AcpMessage, AuthenticateParamsInput, CancelParamsInput, ContentBlock, DeleteSessionParamsInput,
     InitializeParamsInput, JsonRpcErrorOut, JsonRpcErrorOutPayload, JsonRpcIncomingRequest,
-    JsonRpcResponseOut, ListSessionsParamsInput, LoadSessionParamsInput, NewSessionParamsInput,
-    PromptParamsInput,
+    JsonRpcRequest, JsonRpcResponse, JsonRpcResponseOut, ListSessionsParamsInput,
+    LoadSessionParamsInput, NewSessionParamsInput, PromptParamsInput, SelectModelParamsInput,
 };
@@ -2,13 +2,17 @@ use std::sync::Arc;
 
 use anyhow::Result;
 use codex_protocol::ConversationId;
-use tokio::io::{AsyncBufReadExt, BufReader};
+use system_utils::lines::{LineReader, MAX_JSON_LINE_BYTES};
+use tokio::io::BufReader;
 use tokio::sync::Mutex;
 
 use crate::app_server::{AppServerClient, AppServerEvent};
 use crate::cli::CliConfig;
-use crate::handlers::{handle_acp_request, handle_app_server_stderr_line, handle_codex_event};
-use crate::logging::{log_fmt, LogLevel};
+use crate::handlers::{
+    forward_client_fs_request, handle_acp_request, handle_app_server_exit,
+    handle_app_server_stderr_line, handle_codex_event,
+};
+use crate::logging::{init_file_log, log_fmt, log_rpc, LogLevel};
 use crate::protocol::AcpMessage;
 use crate::state::AcpState;
 use crate::writer::AcpWriter;
@@ -40,6 +44,14 @@ where
     R: tokio::io::AsyncRead + Unpin,
     W: tokio::io::AsyncWrite + Send + Unpin + 'static,
 {
+    if let Some(dir) = config.log_dir.as_deref() {
+        if let Err(err) = init_file_log(dir, config.log_verbose) {
+            log_fmt(
+                LogLevel::Warn,
+                format_args!("无法初始化日志目录 {}: {err}", dir.display()),
+            );
+        }
+    }
     let writer = Arc::new(AcpWriter::new(Box::new(writer)));
     let state = Arc::new(Mutex::new(AcpState::default()));
     let (app_server, mut app_events) = match AppServerClient::spawn(&config).await {
@@ -60,6 +72,8 @@ where
 
     let writer_clone = writer.clone();
     let state_clone = state.clone();
+    let app_server_clone = app_server.clone();
+    let config_clone = config.clone();
     tokio::spawn(async move {
         while let Some(event) = app_events.recv().await {
             match event {
@@ -88,15 +102,53 @@ where
                     conversation_id,
                     msg,
                 } => {
-                    if let Err(err) =
-                        handle_codex_event(conversation_id, msg, &writer_clone, &state_clone).await
+                    if let Err(err) = handle_codex_event(
+                        conversation_id,
+                        msg,
+                        &writer_clone,
+                        &state_clone,
+                        &app_server_clone,
+                    )
+                    .await
                     {
                         log_fmt(LogLevel::Error, format_args!("处理 codex 事件失败: {err}"));
                     }
                 }
+                AppServerEvent::ClientFsRequest {
+                    method,
+                    params,
+                    respond_to,
+                } => {
+                    let writer = writer_clone.clone();
+                    let state = state_clone.clone();
+                    tokio::spawn(async move {
+                        let result =
+                            forward_client_fs_request(method, params, &writer, &state).await;
+                        let _ = respond_to.send(result);
+                    });
+                }
+                AppServerEvent::Exited { generation, status } => {
+                    if let Err(err) = handle_app_server_exit(
+                        generation,
+                        status,
+                        &writer_clone,
+                        &state_clone,
+                        &app_server_clone,
+                        &config_clone,
+                    )
+                    .await
+                    {
+                        log_fmt(LogLevel::Error, format_args!("恢复 app-server 失败: {err}"));
+                    }
+                }
                 AppServerEvent::StderrLine(line) => {
-                    if let Err(err) =
-                        handle_app_server_stderr_line(line, &writer_clone, &state_clone).await
+                    if let Err(err) = handle_app_server_stderr_line(
+                        line,
+                        &writer_clone,
+                        &state_clone,
+                        &app_server_clone,
+                    )
+                    .await
                     {
                         log_fmt(
                             LogLevel::Error,
@@ -108,21 +160,22 @@ where
         }
     });
 
-    let mut reader = BufReader::new(reader);
-    let mut buffer = String::new();
+    let mut reader = LineReader::new(BufReader::new(reader), MAX_JSON_LINE_BYTES);
 
-    loop {
-        buffer.clear();
-        let bytes = reader.read_line(&mut buffer).await?;
-        if bytes == 0 {
-            break;
-        }
-        let line = buffer.trim_end_matches(['\n', '\r']);
+    while let Some(line) = reader.next_line().await? {
+        let line = match line {
+            Ok(line) => line,
+            Err(err) => {
+                log_fmt(LogLevel::Warn, format_args!("丢弃无效的 ACP 消息: {err}"));
+                continue;
+            }
+        };
         if line.is_empty() {
             continue;
         }
 
-        let message: AcpMessage = match serde_json::from_str(line) {
+        log_rpc("<-", "acp", &line);
+        let message: AcpMessage = match serde_json::from_str(&line) {
             Ok(value) => value,
             Err(err) => {
                 log_fmt(LogLevel::Warn, format_args!("无法解析 ACP 消息: {err}"));
@@ -130,12 +183,16 @@ where
             }
         };
 
-        if let AcpMessage::Request(request) = message {
-            if let Err(err) =
-                handle_acp_request(request, &writer, &state, &app_server, &config).await
-            {
-                log_fmt(LogLevel::Error, format_args!("处理 ACP 请求失败: {err}"));
+        match message {
+            AcpMessage::Request(request) => {
+                if let Err(err) =
+                    handle_acp_request(request, &writer, &state, &app_server, &config).await
+                {
+                    log_fmt(LogLevel::Error, format_args!("处理 ACP 请求失败: {err}"));
+                }
             }
+            AcpMessage::Response(response) => writer.resolve_response(response).await,
+            AcpMessage::Notification(_) => {}
         }
     }
 
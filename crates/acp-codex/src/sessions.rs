@@ -4,14 +4,17 @@ use std::path::{Path, PathBuf};
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use anyhow::{anyhow, Result};
-use serde::Serialize;
-use serde_json::Value;
+use serde_json::{json, Value};
 
 use crate::logging::{log_fmt, LogLevel};
-use crate::utils::SessionHandler;
+use crate::utils::{insert_dual, SessionHandler};
 
-#[derive(Debug, Serialize)]
-#[serde(rename_all = "camelCase")]
+// 会话目录可能积累大量 rollout 文件，只解析按 mtime 最新的这些。
+const MAX_LISTED_ROLLOUTS: usize = 200;
+const TITLE_MAX_CHARS: usize = 200;
+const FIRST_PROMPT_MAX_CHARS: usize = 120;
+
+#[derive(Debug)]
 pub(crate) struct SessionSummary {
     pub(crate) session_id: String,
     pub(crate) title: String,
@@ -19,6 +22,63 @@ pub(crate) struct SessionSummary {
     pub(crate) created_at: u64,
     pub(crate) updated_at: u64,
     pub(crate) message_count: u64,
+    pub(crate) model: Option<String>,
+    pub(crate) first_prompt: Option<String>,
+}
+
+impl SessionSummary {
+    fn to_value(&self) -> Value {
+        let mut map = serde_json::Map::new();
+        insert_dual(
+            &mut map,
+            "session_id",
+            "sessionId",
+            Value::String(self.session_id.clone()),
+        );
+        map.insert("title".to_string(), Value::String(self.title.clone()));
+        map.insert("cwd".to_string(), Value::String(self.cwd.clone()));
+        insert_dual(&mut map, "created_at", "createdAt", self.created_at.into());
+        insert_dual(&mut map, "updated_at", "updatedAt", self.updated_at.into());
+        insert_dual(
+            &mut map,
+            "message_count",
+            "messageCount",
+            self.message_count.into(),
+        );
+        map.insert("model".to_string(), self.model.clone().into());
+        insert_dual(
+            &mut map,
+            "first_prompt",
+            "firstPrompt",
+            self.first_prompt.clone().into(),
+        );
+        Value::Object(map)
+    }
+}
+
+/// 无法解析的 rollout 文件不影响整个列表，只作为 warning 返回。
+#[derive(Debug)]
+pub(crate) struct SkippedRollout {
+    pub(crate) path: String,
+    pub(crate) error: String,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct SessionListing {
+    pub(crate) sessions: Vec<SessionSummary>,
+    pub(crate) warnings: Vec<SkippedRollout>,
+}
+
+impl SessionListing {
+    pub(crate) fn to_value(&self) -> Value {
+        let sessions: Vec<Value> = self.sessions.iter().map(SessionSummary::to_value).collect();
+        let warnings: Vec<Value> = self
+            .warnings
+            .iter()
+            .map(|warning| json!({ "path": warning.path, "error": warning.error }))
+            .collect();
+        json!({ "sessions": sessions, "warnings": warnings })
+    }
 }
 
 #[derive(Debug)]
@@ -27,17 +87,39 @@ struct SessionMetadata {
     cwd: String,
 }
 
-pub(crate) fn list_workspace_sessions() -> Result<Vec<SessionSummary>> {
+pub(crate) fn list_workspace_sessions() -> Result<SessionListing> {
     log_fmt(
         LogLevel::Info,
         format_args!("list_workspace_sessions called"),
     );
     let sessions_root = SessionHandler::sessions_root()?;
     let workspace_root = workspace_root()?;
-    let mut sessions = Vec::new();
-    scan_session_dir(&sessions_root, &workspace_root, &mut sessions)?;
-    sessions.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
-    Ok(sessions)
+    let mut rollouts = Vec::new();
+    collect_rollout_files(&sessions_root, &mut rollouts)?;
+    rollouts.sort_by(|a, b| b.1.cmp(&a.1));
+    rollouts.truncate(MAX_LISTED_ROLLOUTS);
+
+    let mut listing = SessionListing::default();
+    for (path, modified) in rollouts {
+        match parse_rollout_summary(&path, &workspace_root, modified) {
+            Ok(Some(summary)) => listing.sessions.push(summary),
+            Ok(None) => {}
+            Err(err) => {
+                log_fmt(
+                    LogLevel::Warn,
+                    format_args!("跳过无法解析的 rollout 文件 {}: {err}", path.display()),
+                );
+                listing.warnings.push(SkippedRollout {
+                    path: path.display().to_string(),
+                    error: err.to_string(),
+                });
+            }
+        }
+    }
+    listing
+        .sessions
+        .sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
+    Ok(listing)
 }
 
 pub(crate) fn delete_workspace_session(session_id: &str) -> Result<()> {
@@ -62,11 +144,7 @@ pub(crate) fn delete_workspace_session(session_id: &str) -> Result<()> {
     Ok(())
 }
 
-fn scan_session_dir(
-    dir: &Path,
-    workspace_root: &Path,
-    output: &mut Vec<SessionSummary>,
-) -> Result<()> {
+fn collect_rollout_files(dir: &Path, output: &mut Vec<(PathBuf, u64)>) -> Result<()> {
     if !dir.exists() {
         return Ok(());
     }
@@ -74,15 +152,14 @@ fn scan_session_dir(
         let entry = entry?;
         let path = entry.path();
         if path.is_dir() {
-            scan_session_dir(&path, workspace_root, output)?;
+            collect_rollout_files(&path, output)?;
             continue;
         }
         if !is_rollout_file(&path) {
             continue;
         }
-        if let Ok(Some(summary)) = parse_rollout_summary(&path, workspace_root) {
-            output.push(summary);
-        }
+        let modified = file_time_ms(&path).ok().flatten().unwrap_or(0);
+        output.push((path, modified));
     }
     Ok(())
 }
@@ -94,16 +171,23 @@ fn is_rollout_file(path: &Path) -> bool {
     filename.starts_with("rollout-") && filename.ends_with(".jsonl")
 }
 
-fn parse_rollout_summary(path: &Path, workspace_root: &Path) -> Result<Option<SessionSummary>> {
+fn parse_rollout_summary(
+    path: &Path,
+    workspace_root: &Path,
+    modified: u64,
+) -> Result<Option<SessionSummary>> {
     let file = fs::File::open(path)?;
     let reader = std::io::BufReader::new(file);
     let mut session_id: Option<String> = None;
     let mut cwd: Option<String> = None;
-    let mut title: Option<String> = None;
+    let mut started_at: Option<u64> = None;
+    let mut model: Option<String> = None;
+    let mut first_prompt: Option<String> = None;
     let mut message_count = 0u64;
 
     for line in reader.lines() {
-        let line = line?;
+        let line = line.map_err(|err| anyhow!("read failed: {err}"))?;
+        // 写到一半的最后一行或损坏的行直接跳过。
         let value: Value = match serde_json::from_str(&line) {
             Ok(value) => value,
             Err(_) => continue,
@@ -111,61 +195,78 @@ fn parse_rollout_summary(path: &Path, workspace_root: &Path) -> Result<Option<Se
         let Some(entry_type) = value.get("type").and_then(|v| v.as_str()) else {
             continue;
         };
-        if entry_type == "session_meta" {
-            if let Some(payload) = value.get("payload") {
+        let Some(payload) = value.get("payload") else {
+            continue;
+        };
+        let text = |key: &str| {
+            payload
+                .get(key)
+                .and_then(|v| v.as_str())
+                .map(str::to_string)
+        };
+        match entry_type {
+            "session_meta" => {
                 if session_id.is_none() {
-                    session_id = payload
-                        .get("id")
-                        .and_then(|v| v.as_str())
-                        .map(|v| v.to_string());
+                    session_id = text("id");
                 }
                 if cwd.is_none() {
-                    cwd = payload
-                        .get("cwd")
-                        .and_then(|v| v.as_str())
-                        .map(|v| v.to_string());
+                    cwd = text("cwd");
+                }
+                if started_at.is_none() {
+                    started_at = text("timestamp")
+                        .or_else(|| {
+                            value
+                                .get("timestamp")
+                                .and_then(|v| v.as_str())
+                                .map(str::to_string)
+                        })
+                        .and_then(|timestamp| humantime::parse_rfc3339_weak(&timestamp).ok())
+                        .map(system_time_ms);
+                }
+                if model.is_none() {
+                    model = text("model");
                 }
             }
-            continue;
-        }
-        if entry_type == "event_msg" {
-            let Some(payload) = value.get("payload") else {
-                continue;
-            };
-            let kind = payload.get("type").and_then(|v| v.as_str());
-            match kind {
+            "turn_context" => {
+                if model.is_none() {
+                    model = text("model");
+                }
+            }
+            "event_msg" => match payload.get("type").and_then(|v| v.as_str()) {
                 Some("user_message") => {
                     message_count = message_count.saturating_add(1);
-                    if title.is_none() {
-                        title = payload
-                            .get("message")
-                            .and_then(|v| v.as_str())
-                            .map(|v| normalize_title(&strip_tool_context(v)));
+                    if first_prompt.is_none() {
+                        first_prompt = text("message")
+                            .map(|message| strip_tool_context(&message))
+                            .filter(|message| !message.trim().is_empty());
                     }
                 }
                 Some("agent_message") => {
                     message_count = message_count.saturating_add(1);
                 }
                 _ => {}
-            }
+            },
+            _ => {}
         }
     }
 
-    let session_id = match session_id {
-        Some(value) => value,
-        None => return Ok(None),
-    };
-    let cwd = match cwd {
-        Some(value) => value,
-        None => return Ok(None),
+    let (Some(session_id), Some(cwd)) = (session_id, cwd) else {
+        return Err(anyhow!("missing session_meta with id and cwd"));
     };
     if !is_within_workspace(&cwd, workspace_root) {
         return Ok(None);
     }
 
-    let updated_at = file_time_ms(path)?.unwrap_or_else(now_ms);
-    let created_at = file_created_ms(path).unwrap_or(updated_at);
-    let title = title.unwrap_or_else(|| format!("Session {}", session_id));
+    let updated_at = if modified > 0 { modified } else { now_ms() };
+    // 文件创建时间并非所有平台/文件系统都有，优先用 rollout 头部记录的时间。
+    let created_at = started_at
+        .or_else(|| file_created_ms(path))
+        .unwrap_or(updated_at);
+    let title = first_prompt
+        .as_deref()
+        .map(|prompt| normalize_text(prompt, TITLE_MAX_CHARS))
+        .unwrap_or_else(|| format!("Session {}", session_id));
+    let first_prompt = first_prompt.map(|prompt| normalize_text(&prompt, FIRST_PROMPT_MAX_CHARS));
 
     Ok(Some(SessionSummary {
         session_id,
@@ -174,6 +275,8 @@ fn parse_rollout_summary(path: &Path, workspace_root: &Path) -> Result<Option<Se
         created_at,
         updated_at,
         message_count,
+        model,
+        first_prompt,
     }))
 }
 
@@ -307,16 +410,14 @@ fn now_ms() -> u64 {
     system_time_ms(SystemTime::now())
 }
 
-fn normalize_title(raw: &str) -> String {
+fn normalize_text(raw: &str, max_chars: usize) -> String {
     let single = raw.split_whitespace().collect::<Vec<_>>().join(" ");
-    let trimmed = single.trim();
-    let max_len = 200;
-    if trimmed.len() <= max_len {
-        trimmed.to_string()
+    if single.chars().count() <= max_chars {
+        single
     } else {
         format!(
             "{}...",
-            trimmed.chars().take(max_len - 3).collect::<String>()
+            single.chars().take(max_chars - 3).collect::<String>()
         )
     }
 }
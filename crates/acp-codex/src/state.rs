@@ -1,6 +1,10 @@
 use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::time::Instant;
 
+use codex_app_server_protocol::InputItem;
 use codex_protocol::ConversationId;
+use serde_json::Value;
 use tokio::sync::oneshot;
 use uuid::Uuid;
 
@@ -9,11 +13,90 @@ pub(crate) struct AcpState {
     pub(crate) session_id: Option<String>,
     pub(crate) conversation_id: Option<ConversationId>,
     pub(crate) conversation_subscription_id: Option<Uuid>,
-    pub(crate) pending_prompt_ids: VecDeque<u64>,
+    /// 当前会话的 rollout 文件与 cwd，app-server 崩溃重启后用来恢复会话。
+    pub(crate) rollout_path: Option<PathBuf>,
+    pub(crate) session_cwd: Option<PathBuf>,
+    /// 最近几次自动重启 app-server 的时间，用于限制重启频率。
+    pub(crate) app_server_restarts: VecDeque<Instant>,
+    /// 正在 app-server 上执行的 session/prompt 请求 id；同一时间只有一个 turn。
+    pub(crate) active_prompt_id: Option<u64>,
+    /// turn 进行中到达的 prompt，按顺序在当前 turn 结束后提交。
+    pub(crate) queued_prompts: VecDeque<QueuedPrompt>,
     pub(crate) session_id_waiters: Vec<oneshot::Sender<String>>,
     pub(crate) app_server_initialized: bool,
     pub(crate) saw_message_delta: bool,
     pub(crate) saw_reasoning_delta: bool,
     pub(crate) retry_count: u32,
     pub(crate) retry_exhausted: bool,
+    /// initialize 时客户端声明的 `fs` 能力；未声明时 app-server 的文件请求保持原来的空响应。
+    pub(crate) client_fs: ClientFsCapabilities,
+    /// app-server 提供的可选模型，首次建会话时查询；None 表示还没查过。
+    pub(crate) available_models: Option<Vec<ModelInfo>>,
+    /// session/select_model 选中的模型；None 表示使用 app-server 的默认模型。
+    pub(crate) current_model: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct ModelInfo {
+    pub(crate) id: String,
+    pub(crate) name: String,
+    pub(crate) description: Option<String>,
+    pub(crate) is_default: bool,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct ClientFsCapabilities {
+    pub(crate) read_text_file: bool,
+    pub(crate) write_text_file: bool,
+}
+
+impl ClientFsCapabilities {
+    pub(crate) fn from_client_capabilities(value: &Value) -> Self {
+        let fs = value.get("fs");
+        let flag = |camel: &str, snake: &str| {
+            fs.and_then(|fs| fs.get(camel).or_else(|| fs.get(snake)))
+                .and_then(Value::as_bool)
+                .unwrap_or(false)
+        };
+        Self {
+            read_text_file: flag("readTextFile", "read_text_file"),
+            write_text_file: flag("writeTextFile", "write_text_file"),
+        }
+    }
+
+    pub(crate) fn allows(self, method: ClientFsMethod) -> bool {
+        match method {
+            ClientFsMethod::ReadTextFile => self.read_text_file,
+            ClientFsMethod::WriteTextFile => self.write_text_file,
+        }
+    }
+}
+
+/// app-server 发来、可以转给 ACP 客户端处理的文件请求。
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ClientFsMethod {
+    ReadTextFile,
+    WriteTextFile,
+}
+
+impl ClientFsMethod {
+    pub(crate) fn from_method(method: &str) -> Option<Self> {
+        match method {
+            "fs/read_text_file" | "fs/readTextFile" => Some(Self::ReadTextFile),
+            "fs/write_text_file" | "fs/writeTextFile" => Some(Self::WriteTextFile),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn acp_method(self) -> &'static str {
+        match self {
+            Self::ReadTextFile => "fs/read_text_file",
+            Self::WriteTextFile => "fs/write_text_file",
+        }
+    }
+}
+
+pub(crate) struct QueuedPrompt {
+    pub(crate) id: u64,
+    pub(crate) items: Vec<InputItem>,
 }
@@ -8,6 +8,7 @@ use codex_protocol::{
     config_types::SandboxMode as CodexSandboxMode, protocol::AskForApproval as CodexAskForApproval,
 };
 use serde_json::{json, Value};
+use system_utils::path::expand_tilde_with;
 use uuid::Uuid;
 
 use crate::cli::CliConfig;
@@ -20,13 +21,7 @@ impl SessionHandler {
         if let Ok(value) = std::env::var("CODEX_HOME") {
             let trimmed = value.trim();
             if !trimmed.is_empty() {
-                let mut root = if trimmed == "~" {
-                    home_dir.clone()
-                } else if let Some(rest) = trimmed.strip_prefix("~/") {
-                    home_dir.join(rest)
-                } else {
-                    PathBuf::from(trimmed)
-                };
+                let mut root = expand_tilde_with(trimmed, || Some(home_dir.clone()));
                 if root.is_relative() {
                     root = home_dir.join(root);
                 }
@@ -180,6 +175,8 @@ fn normalize_mcp_server_config(config: &mut serde_json::Map<String, Value>) {
                     config
                         .entry("env_vars".to_string())
                         .or_insert(Value::Array(values));
+                } else if let Some(env) = env_pairs_to_map(&values) {
+                    config.insert("env".to_string(), Value::Object(env));
                 }
             }
             other => {
@@ -189,6 +186,50 @@ fn normalize_mcp_server_config(config: &mut serde_json::Map<String, Value>) {
     }
 }
 
+// ACP 客户端按 `[{ name, value }]` 传 env，Codex 配置需要 `{ NAME = "value" }`。
+fn env_pairs_to_map(values: &[Value]) -> Option<serde_json::Map<String, Value>> {
+    let mut env = serde_json::Map::new();
+    for value in values {
+        let name = value.get("name").and_then(Value::as_str)?.trim();
+        if name.is_empty() {
+            continue;
+        }
+        let value = match value.get("value") {
+            Some(Value::String(value)) => value.clone(),
+            Some(Value::Null) | None => String::new(),
+            Some(other) => other.to_string(),
+        };
+        env.insert(name.to_string(), Value::String(value));
+    }
+    if env.is_empty() {
+        None
+    } else {
+        Some(env)
+    }
+}
+
+/// 返回 command 不在白名单内的 MCP server（`name (command)`），按 basename 比较；没有 command 的（如 HTTP）不受限制。
+pub(crate) fn disallowed_mcp_servers(servers: &[Value], allowed: &[String]) -> Vec<String> {
+    let mut rejected = Vec::new();
+    for server in servers {
+        let Value::Object(map) = server else {
+            continue;
+        };
+        let name = map.get("name").and_then(Value::as_str).unwrap_or("-");
+        let Some(command) = map.get("command").and_then(Value::as_str).map(str::trim) else {
+            continue;
+        };
+        let basename = Path::new(command)
+            .file_name()
+            .and_then(|value| value.to_str())
+            .unwrap_or(command);
+        if command.is_empty() || !allowed.iter().any(|entry| entry == basename) {
+            rejected.push(format!("{name} ({command})"));
+        }
+    }
+    rejected
+}
+
 pub(crate) fn save_mcp_servers(rollout_path: &Path, servers: &[Value]) -> Result<()> {
     if servers.is_empty() {
         return Ok(());
@@ -294,6 +335,21 @@ pub(crate) fn image_extension_for_mime(mime_type: &str) -> &'static str {
     }
 }
 
+fn image_mime_for_path(path: &Path) -> &'static str {
+    let ext = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    match ext.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "webp" => "image/webp",
+        "gif" => "image/gif",
+        _ => "application/octet-stream",
+    }
+}
+
 pub(crate) fn write_temp_image(data: &str, mime_type: &str) -> Result<PathBuf> {
     let normalized = normalize_base64_payload(data);
     let bytes = base64::engine::general_purpose::STANDARD
@@ -335,7 +391,90 @@ pub(crate) async fn load_rollout_history(path: &Path) -> Result<Vec<Value>> {
             "role": role,
             "content": message.unwrap_or_default(),
         }));
+        if kind == "user_message" {
+            for key in ["images", "local_images"] {
+                let Some(images) = payload.get(key).and_then(|v| v.as_array()) else {
+                    continue;
+                };
+                entries.extend(images.iter().filter_map(history_image_entry));
+            }
+        }
     }
 
     Ok(entries)
 }
+
+/// 回放时附带的本地图片上限；超过时只给 `file://` 路径，避免 session/load 响应过大。
+const MAX_HISTORY_IMAGE_BYTES: u64 = 5 * 1024 * 1024;
+const IMAGE_UNAVAILABLE: &str = "[image unavailable]";
+
+// Codex 记录的图片可能是 data URL、file:// URL 或本地路径（LocalImage 输入）。
+fn history_image_entry(image: &Value) -> Option<Value> {
+    let image = match image {
+        Value::String(value) => value.as_str(),
+        Value::Object(map) => map
+            .get("path")
+            .or_else(|| map.get("image_url"))
+            .and_then(|v| v.as_str())?,
+        _ => return None,
+    };
+    let mut entry = serde_json::Map::new();
+    entry.insert("role".to_string(), Value::String("user".to_string()));
+    insert_dual(
+        &mut entry,
+        "content_type",
+        "contentType",
+        Value::String("image".to_string()),
+    );
+    if let Some(rest) = image.strip_prefix("data:") {
+        let mime_type = rest.split([';', ',']).next().unwrap_or_default();
+        insert_dual(
+            &mut entry,
+            "mime_type",
+            "mimeType",
+            Value::String(mime_type.to_string()),
+        );
+        entry.insert(
+            "data".to_string(),
+            Value::String(normalize_base64_payload(image)),
+        );
+        return Some(Value::Object(entry));
+    }
+
+    let path = PathBuf::from(image.strip_prefix("file://").unwrap_or(image));
+    insert_dual(
+        &mut entry,
+        "mime_type",
+        "mimeType",
+        Value::String(image_mime_for_path(&path).to_string()),
+    );
+    match std::fs::metadata(&path) {
+        Ok(meta) if meta.is_file() && meta.len() <= MAX_HISTORY_IMAGE_BYTES => {
+            match std::fs::read(&path) {
+                Ok(bytes) => {
+                    let data = base64::engine::general_purpose::STANDARD.encode(bytes);
+                    entry.insert("data".to_string(), Value::String(data));
+                }
+                Err(_) => {
+                    entry.insert(
+                        "content".to_string(),
+                        Value::String(IMAGE_UNAVAILABLE.to_string()),
+                    );
+                }
+            }
+        }
+        Ok(meta) if meta.is_file() => {
+            entry.insert(
+                "uri".to_string(),
+                Value::String(format!("file://{}", path.display())),
+            );
+        }
+        _ => {
+            entry.insert(
+                "content".to_string(),
+                Value::String(IMAGE_UNAVAILABLE.to_string()),
+            );
+        }
+    }
+    Some(Value::Object(entry))
+}
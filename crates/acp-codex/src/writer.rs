@@ -1,16 +1,29 @@
-use anyhow::Result;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
 use serde::Serialize;
+use serde_json::Value;
 use tokio::io::AsyncWriteExt;
-use tokio::sync::Mutex;
+use tokio::sync::{oneshot, Mutex};
+
+use crate::logging::log_rpc;
+use crate::protocol::{JsonRpcRequest, JsonRpcResponse};
 
 pub(crate) struct AcpWriter {
     stdout: Mutex<Box<dyn tokio::io::AsyncWrite + Send + Unpin>>,
+    /// 发给 ACP 客户端（编辑器）的请求 id，与客户端发来的请求 id 互不相关。
+    next_request_id: AtomicU64,
+    pending: Mutex<HashMap<u64, oneshot::Sender<Result<Value, String>>>>,
 }
 
 impl AcpWriter {
     pub(crate) fn new(writer: Box<dyn tokio::io::AsyncWrite + Send + Unpin>) -> Self {
         Self {
             stdout: Mutex::new(writer),
+            next_request_id: AtomicU64::new(1),
+            pending: Mutex::new(HashMap::new()),
         }
     }
 
@@ -20,10 +33,52 @@ impl AcpWriter {
 
     pub(crate) async fn send_json<T: Serialize + Sync>(&self, value: &T) -> Result<()> {
         let raw = serde_json::to_string(value)?;
+        log_rpc("->", "acp", &raw);
         let mut guard = self.stdout.lock().await;
         guard.write_all(raw.as_bytes()).await?;
         guard.write_all(b"\n").await?;
         guard.flush().await?;
         Ok(())
     }
+
+    /// 向 ACP 客户端发起请求并等待响应；超时或客户端返回 error 时报错。
+    pub(crate) async fn request(
+        &self,
+        method: &str,
+        params: Value,
+        wait: Duration,
+    ) -> Result<Value> {
+        let id = self.next_request_id.fetch_add(1, Ordering::SeqCst);
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().await.insert(id, tx);
+        let request = JsonRpcRequest::new(id, method, Some(params));
+        if let Err(err) = self.send_json(&request).await {
+            self.pending.lock().await.remove(&id);
+            return Err(err);
+        }
+        match tokio::time::timeout(wait, rx).await {
+            Ok(Ok(Ok(value))) => Ok(value),
+            Ok(Ok(Err(message))) => Err(anyhow!("{method} failed: {message}")),
+            Ok(Err(_)) => Err(anyhow!("{method} cancelled")),
+            Err(_) => {
+                self.pending.lock().await.remove(&id);
+                Err(anyhow!("{method} timed out after {}s", wait.as_secs()))
+            }
+        }
+    }
+
+    /// 把客户端发回的响应交给对应的 [`AcpWriter::request`]；未知 id 直接忽略。
+    pub(crate) async fn resolve_response(&self, response: JsonRpcResponse) {
+        let Some(id) = response.id else {
+            return;
+        };
+        let Some(sender) = self.pending.lock().await.remove(&id) else {
+            return;
+        };
+        let result = match response.error {
+            Some(error) => Err(error.message),
+            None => Ok(response.result.unwrap_or(Value::Null)),
+        };
+        let _ = sender.send(result);
+    }
 }
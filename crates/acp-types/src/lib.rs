@@ -293,6 +293,20 @@ pub struct DeleteSessionParams {
     pub session_id: String,
 }
 
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SelectModelParamsInput {
+    pub session_id: String,
+    pub model_id: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SelectModelParams {
+    pub session_id: String,
+    pub model_id: String,
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct LoadSessionResult {
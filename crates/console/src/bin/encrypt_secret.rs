@@ -0,0 +1,42 @@
+use anyhow::Context;
+use clap::Parser;
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::PathBuf;
+use system_utils::secret::{ConfigKey, CONFIG_KEY_ENV};
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "octovalve-encrypt-secret",
+    about = "Encrypt a secret (e.g. ssh_password) for ssh_password_encrypted; reads the plaintext from stdin"
+)]
+struct Args {
+    /// 口令文件；未设置时读取 OCTOVALVE_CONFIG_KEY。
+    #[arg(long)]
+    config_key_file: Option<PathBuf>,
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+    let key = ConfigKey::resolve(args.config_key_file.as_deref())?.ok_or_else(|| {
+        anyhow::anyhow!("no config key available; set {CONFIG_KEY_ENV} or pass --config-key-file")
+    })?;
+
+    let stdin = std::io::stdin();
+    if stdin.is_terminal() {
+        eprint!("secret: ");
+        std::io::stderr().flush().ok();
+    }
+    let mut line = String::new();
+    stdin
+        .lock()
+        .read_line(&mut line)
+        .context("failed to read secret from stdin")?;
+    let secret = line.trim_end_matches(['\r', '\n']);
+    if secret.is_empty() {
+        anyhow::bail!("secret cannot be empty");
+    }
+
+    let encrypted = key.encrypt(secret)?;
+    println!("{encrypted}");
+    Ok(())
+}
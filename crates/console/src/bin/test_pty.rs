@@ -4,10 +4,12 @@ use std::collections::BTreeMap;
 use std::process::{Command, Stdio};
 
 #[path = "../shell_utils.rs"]
+#[allow(dead_code)]
 mod shell_utils;
 
 use shell_utils::{
     apply_ssh_options, build_env_prefix, env_language_locale, env_locale, shell_escape,
+    HostKeyCheck,
 };
 
 #[derive(Parser, Debug)]
@@ -49,7 +51,7 @@ fn main() -> anyhow::Result<()> {
     } else {
         cmd.arg("-T");
     }
-    apply_ssh_options(&mut cmd, false);
+    apply_ssh_options(&mut cmd, false, &HostKeyCheck::default());
     apply_locale_env(&mut cmd, locale.as_deref());
     cmd.arg(args.ssh);
     cmd.arg(remote_cmd);
@@ -0,0 +1,74 @@
+use anyhow::Context;
+
+use crate::cli::Args;
+use crate::config::load_console_config;
+use crate::local_exec::{PolicyConfig, ScheduleRegistry, TemplateRegistry};
+use crate::state::build_console_state;
+use system_utils::secret::ConfigKey;
+
+/// `--check-config`：只加载并校验配置，把报告打印到 stdout，不启动任何服务。返回是否通过。
+pub(crate) fn run_check_config(args: &Args) -> bool {
+    let mut ok = true;
+
+    match check_targets(args) {
+        Ok(count) => println!("targets: {} ok ({count} targets)", args.config.display()),
+        Err(err) => {
+            ok = false;
+            println!("targets: {} FAILED", args.config.display());
+            println!("  error: {err:#}");
+        }
+    }
+
+    match PolicyConfig::load(&args.broker_config) {
+        Ok(policy) => {
+            let check = policy.check();
+            let status = if check.errors.is_empty() {
+                "ok"
+            } else {
+                "FAILED"
+            };
+            println!("policy: {} {status}", args.broker_config.display());
+            println!(
+                "  whitelist: {} allowed, {} denied, {} arg rules",
+                check.allowed, check.denied, check.arg_rules
+            );
+            println!(
+                "  limits: timeout {}s, max output {} bytes",
+                policy.limits.timeout_secs, policy.limits.max_output_bytes
+            );
+            if policy.intent.require_intent {
+                println!(
+                    "  intent: required, min {} characters",
+                    policy.intent.min_intent_length
+                );
+            }
+            for error in &check.errors {
+                println!("  error: {error}");
+            }
+            for warning in &check.warnings {
+                println!("  warning: {warning}");
+            }
+            ok &= check.errors.is_empty();
+        }
+        Err(err) => {
+            ok = false;
+            println!("policy: {} FAILED", args.broker_config.display());
+            println!("  error: {err:#}");
+        }
+    }
+
+    println!("result: {}", if ok { "ok" } else { "failed" });
+    ok
+}
+
+fn check_targets(args: &Args) -> anyhow::Result<usize> {
+    let config = load_console_config(&args.config)?;
+    let templates = TemplateRegistry::from_config(&config.templates)
+        .with_context(|| format!("invalid templates in {}", args.config.display()))?;
+    ScheduleRegistry::from_config(&config.schedules, &templates)
+        .with_context(|| format!("invalid schedules in {}", args.config.display()))?;
+    let config_key = ConfigKey::resolve(args.config_key_file.as_deref())?;
+    let state = build_console_state(config, config_key.as_ref())
+        .with_context(|| format!("invalid targets in {}", args.config.display()))?;
+    Ok(state.target_specs().len())
+}
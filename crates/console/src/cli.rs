@@ -1,4 +1,4 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use std::path::PathBuf;
 
 #[derive(Parser, Debug)]
@@ -16,4 +16,91 @@ pub(crate) struct Args {
     pub(crate) local_audit_dir: String,
     #[arg(long, default_value = "127.0.0.1:19310")]
     pub(crate) command_listen_addr: String,
+    #[arg(long, default_value_t = false)]
+    pub(crate) terminal_allow_rw_attach: bool,
+    /// 允许 command_listen_addr 绑定到非 loopback 地址（需配合 --allowed-client-cidr）。
+    #[arg(long, default_value_t = false)]
+    pub(crate) allow_nonlocal_bind: bool,
+    /// 非 loopback 监听时允许连接的客户端网段，可重复或逗号分隔，例如 10.0.0.0/8。
+    #[arg(long = "allowed-client-cidr", value_delimiter = ',')]
+    pub(crate) allowed_client_cidrs: Vec<String>,
+    /// 在 `/` 提供只读状态页（`--serve-status-page=false` 关闭）。
+    #[arg(long, default_value_t = true, action = clap::ArgAction::Set)]
+    pub(crate) serve_status_page: bool,
+    /// 关闭 `/metrics`（Prometheus 文本格式）。
+    #[arg(long, default_value_t = false)]
+    pub(crate) metrics_disabled: bool,
+    /// 解密 ssh_password_encrypted 的口令文件；未设置时读取 OCTOVALVE_CONFIG_KEY。
+    #[arg(long)]
+    pub(crate) config_key_file: Option<PathBuf>,
+    /// HTTP/WS API 要求的 bearer token（/health 除外）；也可用 --api-token-file 或 OCTOVALVE_CONSOLE_API_TOKEN。
+    #[arg(long, conflicts_with = "api_token_file")]
+    pub(crate) api_token: Option<String>,
+    #[arg(long)]
+    pub(crate) api_token_file: Option<PathBuf>,
+    /// PEM 证书链；与 --tls-key 一起设置时 --listen-addr 改为提供 HTTPS/WSS。
+    #[arg(long, requires = "tls_key")]
+    pub(crate) tls_cert: Option<PathBuf>,
+    #[arg(long, requires = "tls_cert")]
+    pub(crate) tls_key: Option<PathBuf>,
+    /// 每个请求结束后把结果 POST 到该地址，覆盖 --broker-config 中的 `result_webhook`。
+    #[arg(long)]
+    pub(crate) result_webhook: Option<String>,
+    /// 只校验 --config 与 --broker-config 并打印报告，通过退出 0，否则退出 1。
+    #[arg(long, default_value_t = false)]
+    pub(crate) check_config: bool,
+    #[command(subcommand)]
+    pub(crate) command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub(crate) enum Command {
+    /// 通过正在运行的 console 的 HTTP/WS API 查看并审批排队请求。
+    Ctl(CtlArgs),
+}
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct CtlArgs {
+    /// console 的 HTTP 地址（对应其 --listen-addr）。
+    #[arg(long, default_value = "http://127.0.0.1:19309")]
+    pub(crate) url: String,
+    /// 与 console 相同的 bearer token；也可用 --api-token-file 或 OCTOVALVE_CONSOLE_API_TOKEN。
+    #[arg(long, conflicts_with = "api_token_file")]
+    pub(crate) api_token: Option<String>,
+    #[arg(long)]
+    pub(crate) api_token_file: Option<PathBuf>,
+    #[command(subcommand)]
+    pub(crate) action: CtlAction,
+}
+
+#[derive(Subcommand, Debug)]
+pub(crate) enum CtlAction {
+    /// 列出各目标状态与待审批请求。
+    List {
+        #[arg(long)]
+        target: Option<String>,
+    },
+    /// 批准待审批请求；命中高危规则的请求需加 --confirm。
+    Approve {
+        target: String,
+        id: String,
+        #[arg(long, default_value_t = false)]
+        confirm: bool,
+        #[arg(long, default_value = "ctl")]
+        actor: String,
+    },
+    /// 拒绝待审批请求。
+    Deny {
+        target: String,
+        id: String,
+        #[arg(long)]
+        reason: Option<String>,
+        #[arg(long, default_value = "ctl")]
+        actor: String,
+    },
+    /// 持续打印请求与目标事件，连接断开时以非零状态退出。
+    Tail {
+        #[arg(long)]
+        target: Option<String>,
+    },
 }
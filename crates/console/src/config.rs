@@ -1,4 +1,5 @@
 use anyhow::Context;
+use protocol::config::validate_proxy_config;
 pub(crate) use protocol::config::{
     ProxyConfig as ConsoleConfig, ProxyDefaults as ConsoleDefaults, TargetConfig,
 };
@@ -13,5 +14,15 @@ pub(crate) fn load_console_config(path: &PathBuf) -> anyhow::Result<ConsoleConfi
     if config.targets.is_empty() {
         warn!("config has no targets; console will start without workers");
     }
+    let (errors, warnings): (Vec<_>, Vec<_>) = validate_proxy_config(&config)
+        .into_iter()
+        .partition(|diagnostic| diagnostic.is_error());
+    for warning in &warnings {
+        warn!(path = %warning.path, "{}", warning.message);
+    }
+    if !errors.is_empty() {
+        let errors: Vec<String> = errors.iter().map(ToString::to_string).collect();
+        anyhow::bail!("invalid config {}: {}", path.display(), errors.join("; "));
+    }
     Ok(config)
 }
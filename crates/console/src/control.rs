@@ -1,3 +1,4 @@
 pub(crate) use protocol::control::{
-    ControlRequest, ControlResponse, ServiceEvent, ServiceSnapshot,
+    ControlRequest, ControlResponse, DetachedSnapshot, ForwardDiagnostic, HistoryPage, OutputChunk,
+    ResultSnapshot, ServiceEvent, ServiceSnapshot,
 };
@@ -0,0 +1,550 @@
+//! `octovalve-console ctl`：在没有桌面端的主机上通过 console 的 HTTP/WS API 审批排队请求。
+
+use std::io::Write;
+use std::sync::Arc;
+
+use anyhow::Context;
+use futures_util::StreamExt;
+use protocol::control::{RequestSnapshot, ServiceSnapshot};
+use reqwest::StatusCode;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::header::{HeaderValue, AUTHORIZATION};
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::cli::{CtlAction, CtlArgs};
+use crate::http_security::resolve_api_token;
+use crate::time_utils::now_ms;
+
+const COMMAND_PREVIEW_CHARS: usize = 60;
+
+#[derive(Deserialize)]
+struct TargetEntry {
+    name: String,
+    status: String,
+    pending_count: usize,
+    #[serde(default)]
+    maintenance: bool,
+}
+
+pub(crate) async fn run(args: &CtlArgs) -> anyhow::Result<()> {
+    let token = resolve_api_token(args.api_token.as_deref(), args.api_token_file.as_deref())?;
+    let client = CtlClient::new(&args.url, token)?;
+    let mut out = std::io::stdout().lock();
+    match &args.action {
+        CtlAction::List { target } => client.list(target.as_deref(), &mut out).await,
+        CtlAction::Approve {
+            target,
+            id,
+            confirm,
+            actor,
+        } => {
+            let message = client.approve(target, id, *confirm, actor).await?;
+            writeln!(out, "{target} {id}: {message}")?;
+            Ok(())
+        }
+        CtlAction::Deny {
+            target,
+            id,
+            reason,
+            actor,
+        } => {
+            let message = client.deny(target, id, reason.as_deref(), actor).await?;
+            writeln!(out, "{target} {id}: {message}")?;
+            Ok(())
+        }
+        CtlAction::Tail { target } => client.tail(target.as_deref(), &mut out).await,
+    }
+}
+
+struct CtlClient {
+    http: reqwest::Client,
+    base: reqwest::Url,
+    token: Option<Arc<str>>,
+}
+
+impl CtlClient {
+    fn new(url: &str, token: Option<Arc<str>>) -> anyhow::Result<Self> {
+        let base =
+            reqwest::Url::parse(url).with_context(|| format!("invalid console url {url}"))?;
+        if !matches!(base.scheme(), "http" | "https") {
+            anyhow::bail!("invalid console url {url}: scheme must be http or https");
+        }
+        Ok(Self {
+            http: reqwest::Client::new(),
+            base,
+            token,
+        })
+    }
+
+    fn endpoint(&self, segments: &[&str]) -> reqwest::Url {
+        let mut url = self.base.clone();
+        url.path_segments_mut()
+            .expect("http url has path segments")
+            .pop_if_empty()
+            .extend(segments);
+        url
+    }
+
+    fn request(&self, method: reqwest::Method, segments: &[&str]) -> reqwest::RequestBuilder {
+        let request = self.http.request(method, self.endpoint(segments));
+        match self.token.as_deref() {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    async fn list(&self, target: Option<&str>, out: &mut impl Write) -> anyhow::Result<()> {
+        let response = self
+            .request(reqwest::Method::GET, &["targets"])
+            .send()
+            .await
+            .context("console unreachable")?;
+        let targets: Vec<TargetEntry> = checked(response, "list targets").await?.json().await?;
+        if let Some(name) = target {
+            if !targets.iter().any(|entry| entry.name == name) {
+                anyhow::bail!("unknown target {name}");
+            }
+        }
+        let now = now_ms();
+        let mut rows = Vec::new();
+        for entry in targets
+            .iter()
+            .filter(|entry| target.is_none_or(|name| entry.name == name))
+        {
+            // 未配置 ssh 或尚未就绪的目标没有快照，只在目标行里体现。
+            let response = self
+                .request(reqwest::Method::GET, &["targets", &entry.name, "snapshot"])
+                .send()
+                .await
+                .context("console unreachable")?;
+            if response.status() == StatusCode::NOT_FOUND {
+                continue;
+            }
+            let snapshot: ServiceSnapshot = checked(response, "snapshot").await?.json().await?;
+            rows.extend(
+                snapshot
+                    .queue
+                    .iter()
+                    .map(|request| queue_row(&entry.name, request, now)),
+            );
+        }
+        for entry in targets
+            .iter()
+            .filter(|entry| target.is_none_or(|name| entry.name == name))
+        {
+            let maintenance = if entry.maintenance {
+                " (maintenance)"
+            } else {
+                ""
+            };
+            writeln!(
+                out,
+                "target {}: {}, {} pending{maintenance}",
+                entry.name, entry.status, entry.pending_count
+            )?;
+        }
+        writeln!(out)?;
+        write_table(
+            out,
+            &["TARGET", "ID", "CLIENT", "AGE", "FLAGS", "COMMAND"],
+            &rows,
+        )?;
+        Ok(())
+    }
+
+    async fn approve(
+        &self,
+        target: &str,
+        id: &str,
+        confirm: bool,
+        actor: &str,
+    ) -> anyhow::Result<String> {
+        let body = json!({
+            "id": id,
+            "actor": actor,
+            "confirm": confirm.then_some(id),
+        });
+        let response = self
+            .request(reqwest::Method::POST, &["targets", target, "approve"])
+            .json(&body)
+            .send()
+            .await
+            .context("console unreachable")?;
+        match response.status() {
+            StatusCode::PRECONDITION_REQUIRED => anyhow::bail!(
+                "request {id} matches a dangerous pattern; re-run with --confirm to approve it"
+            ),
+            StatusCode::NOT_FOUND => anyhow::bail!("no pending request {id} on target {target}"),
+            StatusCode::CONFLICT => anyhow::bail!("target {target} is in maintenance"),
+            _ => action_message(checked(response, "approve").await?).await,
+        }
+    }
+
+    async fn deny(
+        &self,
+        target: &str,
+        id: &str,
+        reason: Option<&str>,
+        actor: &str,
+    ) -> anyhow::Result<String> {
+        let body = json!({ "id": id, "actor": actor, "reason": reason });
+        let response = self
+            .request(reqwest::Method::POST, &["targets", target, "deny"])
+            .json(&body)
+            .send()
+            .await
+            .context("console unreachable")?;
+        if response.status() == StatusCode::NOT_FOUND {
+            anyhow::bail!("unknown target {target}");
+        }
+        action_message(checked(response, "deny").await?).await
+    }
+
+    /// 订阅 `/ws`，逐行打印请求与目标事件；连接断开时返回错误。
+    async fn tail(&self, target: Option<&str>, out: &mut impl Write) -> anyhow::Result<()> {
+        let mut url = self.endpoint(&["ws"]);
+        let scheme = if url.scheme() == "https" { "wss" } else { "ws" };
+        url.set_scheme(scheme)
+            .map_err(|_| anyhow::anyhow!("cannot derive websocket url from {}", self.base))?;
+        let mut request = url.as_str().into_client_request()?;
+        if let Some(token) = self.token.as_deref() {
+            request.headers_mut().insert(
+                AUTHORIZATION,
+                HeaderValue::from_str(&format!("Bearer {token}"))?,
+            );
+        }
+        let (mut stream, _) = tokio_tungstenite::connect_async(request)
+            .await
+            .context("failed to connect to the console event stream")?;
+        while let Some(message) = stream.next().await {
+            let Message::Text(text) = message? else {
+                continue;
+            };
+            let Ok(event) = serde_json::from_str::<Value>(&text) else {
+                continue;
+            };
+            if let Some(line) = format_event(&event, target) {
+                writeln!(out, "{line}")?;
+                out.flush()?;
+            }
+        }
+        anyhow::bail!("console closed the event stream")
+    }
+}
+
+async fn checked(response: reqwest::Response, action: &str) -> anyhow::Result<reqwest::Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let body = response.text().await.unwrap_or_default();
+    let detail = serde_json::from_str::<Value>(&body)
+        .ok()
+        .and_then(|value| value["error"].as_str().map(str::to_string))
+        .unwrap_or(body);
+    match status {
+        StatusCode::UNAUTHORIZED => {
+            anyhow::bail!("{action} failed: console rejected the api token")
+        }
+        _ if detail.trim().is_empty() => anyhow::bail!("{action} failed: {status}"),
+        _ => anyhow::bail!("{action} failed: {status}: {}", detail.trim()),
+    }
+}
+
+async fn action_message(response: reqwest::Response) -> anyhow::Result<String> {
+    let body: Value = response.json().await?;
+    Ok(body["message"].as_str().unwrap_or("ok").to_string())
+}
+
+fn queue_row(target: &str, request: &RequestSnapshot, now_ms: u64) -> Vec<String> {
+    let common = &request.common;
+    let mut flags = Vec::new();
+    if common.requires_confirmation {
+        flags.push("confirm");
+    }
+    if common.unparseable {
+        flags.push("unparseable");
+    }
+    if common.outside_window {
+        flags.push("outside-window");
+    }
+    if !common.temporary_rules.is_empty() {
+        flags.push("temporary-rule");
+    }
+    let age_secs = now_ms.saturating_sub(common.received_at_ms) / 1000;
+    vec![
+        target.to_string(),
+        common.id.clone(),
+        common.client.clone(),
+        format!("{age_secs}s"),
+        if flags.is_empty() {
+            "-".to_string()
+        } else {
+            flags.join(",")
+        },
+        preview(&common.raw_command),
+    ]
+}
+
+fn preview(command: &str) -> String {
+    let line = command.lines().next().unwrap_or_default();
+    if line.chars().count() > COMMAND_PREVIEW_CHARS || line.len() < command.trim_end().len() {
+        let head: String = line.chars().take(COMMAND_PREVIEW_CHARS).collect();
+        format!("{head}...")
+    } else {
+        line.to_string()
+    }
+}
+
+fn write_table(out: &mut impl Write, header: &[&str], rows: &[Vec<String>]) -> std::io::Result<()> {
+    if rows.is_empty() {
+        return writeln!(out, "no pending requests");
+    }
+    let mut widths: Vec<usize> = header.iter().map(|title| title.len()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let header: Vec<String> = header.iter().map(|title| title.to_string()).collect();
+    for row in std::iter::once(&header).chain(rows) {
+        let last = row.len() - 1;
+        let mut line = String::new();
+        for (index, (cell, width)) in row.iter().zip(&widths).enumerate() {
+            if index == last {
+                line.push_str(cell);
+            } else {
+                line.push_str(&format!("{cell:<width$}  "));
+            }
+        }
+        writeln!(out, "{line}")?;
+    }
+    Ok(())
+}
+
+// hello/heartbeat/summary 之类的内部帧不打印。
+fn format_event(event: &Value, target: Option<&str>) -> Option<String> {
+    let text = |value: &Value| value.as_str().unwrap_or("-").to_string();
+    let event_target = match event["type"].as_str()? {
+        "target_updated" => text(&event["target"]["name"]),
+        _ => text(&event["target"]),
+    };
+    if target.is_some_and(|name| name != event_target) {
+        return None;
+    }
+    match event["type"].as_str()? {
+        "request_queued" => {
+            let request = &event["request"];
+            Some(format!(
+                "queued    {event_target} {} client={} {}",
+                text(&request["id"]),
+                text(&request["client"]),
+                preview(request["raw_command"].as_str().unwrap_or_default()),
+            ))
+        }
+        "request_resolved" => Some(format!(
+            "resolved  {event_target} {} {}",
+            text(&event["id"]),
+            text(&event["status"]),
+        )),
+        "result_recorded" => {
+            let result = &event["result"];
+            let exit = result["exit_code"]
+                .as_i64()
+                .map(|code| format!(" exit={code}"))
+                .unwrap_or_default();
+            Some(format!(
+                "finished  {event_target} {} {}{exit}",
+                text(&result["id"]),
+                text(&result["status"]),
+            ))
+        }
+        "target_updated" => Some(format!(
+            "target    {event_target} {} pending={}",
+            text(&event["target"]["status"]),
+            event["target"]["pending_count"].as_u64().unwrap_or(0),
+        )),
+        "quota_exhausted" => Some(format!(
+            "quota     {event_target} exhausted: {}",
+            text(&event["limit"]),
+        )),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::Path;
+    use axum::http::StatusCode as AxumStatus;
+    use axum::routing::{get, post};
+    use axum::{Json, Router};
+    use std::sync::Mutex;
+
+    // 模拟 console：dev 队列里有一条高危请求，approve 未回显 id 时返回 428；记录收到的审批请求。
+    async fn stub_console() -> (String, Arc<Mutex<Vec<Value>>>) {
+        let approvals = Arc::new(Mutex::new(Vec::new()));
+        let received = Arc::clone(&approvals);
+        let queued = json!({
+            "id": "req-1",
+            "client": "ci",
+            "target": "dev",
+            "peer": "127.0.0.1:5000",
+            "intent": "clean build output",
+            "mode": "shell",
+            "raw_command": "rm -rf target",
+            "pipeline": [{ "argv": ["rm", "-rf", "target"] }],
+            "cwd": null,
+            "timeout_ms": null,
+            "max_output_bytes": null,
+            "received_at_ms": now_ms() - 42_000,
+            "requires_confirmation": true,
+        });
+        let app = Router::new()
+            .route(
+                "/targets",
+                get(|| async {
+                    Json(json!([
+                        { "name": "dev", "status": "ready", "pending_count": 1, "maintenance": false },
+                        { "name": "lab", "status": "down", "pending_count": 0, "maintenance": false },
+                    ]))
+                }),
+            )
+            .route(
+                "/targets/:name/snapshot",
+                get(move |Path(name): Path<String>| {
+                    let queued = queued.clone();
+                    async move {
+                        if name != "dev" {
+                            return Err(AxumStatus::NOT_FOUND);
+                        }
+                        Ok(Json(json!({
+                            "queue": [queued],
+                            "running": [],
+                            "history": [],
+                            "last_result": null,
+                        })))
+                    }
+                }),
+            )
+            .route(
+                "/targets/:name/approve",
+                post(move |Json(body): Json<Value>| async move {
+                    received.lock().unwrap().push(body.clone());
+                    if body["id"] != "req-1" {
+                        return Err(AxumStatus::NOT_FOUND);
+                    }
+                    if body["confirm"] != "req-1" {
+                        return Err(AxumStatus::PRECONDITION_REQUIRED);
+                    }
+                    Ok(Json(json!({ "message": "approve queued" })))
+                }),
+            );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        (format!("http://{addr}"), approvals)
+    }
+
+    #[tokio::test]
+    async fn lists_the_queue_and_approves_with_confirmation() {
+        let (url, approvals) = stub_console().await;
+        let client = CtlClient::new(&url, None).expect("client");
+
+        let mut out = Vec::new();
+        client.list(None, &mut out).await.expect("list");
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("target dev: ready, 1 pending"), "{out}");
+        assert!(out.contains("target lab: down, 0 pending"), "{out}");
+        let row = out
+            .lines()
+            .find(|line| line.starts_with("dev "))
+            .expect("queue row");
+        for cell in ["req-1", "ci", "42s", "confirm", "rm -rf target"] {
+            assert!(row.contains(cell), "{row}");
+        }
+        assert!(client
+            .list(Some("prod"), &mut Vec::new())
+            .await
+            .unwrap_err()
+            .to_string()
+            .contains("unknown target prod"));
+
+        let err = client
+            .approve("dev", "req-1", false, "alice")
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("--confirm"), "{err}");
+        let err = client
+            .approve("dev", "req-9", true, "alice")
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("no pending request req-9"),
+            "{err}"
+        );
+        let message = client
+            .approve("dev", "req-1", true, "alice")
+            .await
+            .expect("approve");
+        assert_eq!(message, "approve queued");
+        let approvals = approvals.lock().unwrap();
+        assert_eq!(approvals.len(), 3);
+        assert_eq!(approvals[0]["confirm"], Value::Null);
+        assert_eq!(approvals[2]["confirm"], "req-1");
+        assert_eq!(approvals[2]["actor"], "alice");
+    }
+
+    #[tokio::test]
+    async fn reports_unreachable_console_and_rejected_token() {
+        let client = CtlClient::new("http://127.0.0.1:1", None).expect("client");
+        assert!(client.list(None, &mut Vec::new()).await.is_err());
+
+        let app = Router::new().route("/targets", get(|| async { AxumStatus::UNAUTHORIZED }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        let client =
+            CtlClient::new(&format!("http://{addr}"), Some(Arc::from("wrong"))).expect("client");
+        let err = client.list(None, &mut Vec::new()).await.unwrap_err();
+        assert!(err.to_string().contains("rejected the api token"), "{err}");
+        assert!(CtlClient::new("ftp://127.0.0.1", None).is_err());
+    }
+
+    #[test]
+    fn formats_stream_events_for_the_selected_target() {
+        let queued = json!({
+            "type": "request_queued",
+            "target": "dev",
+            "request": { "id": "req-1", "client": "ci", "raw_command": "make\nmake install" },
+        });
+        assert_eq!(
+            format_event(&queued, None).as_deref(),
+            Some("queued    dev req-1 client=ci make...")
+        );
+        assert_eq!(format_event(&queued, Some("lab")), None);
+        let finished = json!({
+            "type": "result_recorded",
+            "target": "dev",
+            "result": { "id": "req-1", "status": "completed", "exit_code": 2 },
+        });
+        assert_eq!(
+            format_event(&finished, Some("dev")).as_deref(),
+            Some("finished  dev req-1 completed exit=2")
+        );
+        let updated = json!({
+            "type": "target_updated",
+            "target": { "name": "dev", "status": "ready", "pending_count": 0 },
+        });
+        assert_eq!(
+            format_event(&updated, None).as_deref(),
+            Some("target    dev ready pending=0")
+        );
+        assert_eq!(
+            format_event(&json!({ "type": "heartbeat", "seq": 3 }), None),
+            None
+        );
+    }
+}
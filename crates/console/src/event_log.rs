@@ -0,0 +1,263 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde_json::Value;
+use tokio::sync::{broadcast, RwLock};
+use tokio_util::sync::CancellationToken;
+
+use crate::events::ConsoleEvent;
+use crate::state::ConsoleState;
+
+// 为 ConsoleEvent 分配全局递增 seq，并保留最近一段已序列化的事件供 /ws?since= 或 resume 消息断线续传。
+// 超出保留窗口（条数或字节数）或 epoch 不符时，客户端改走 targets_snapshot 全量重同步。
+const REPLAY_MAX_EVENTS: usize = 1024;
+const REPLAY_MAX_BYTES: usize = 8 * 1024 * 1024;
+const LIVE_CHANNEL_CAPACITY: usize = 512;
+
+pub(crate) struct EventFrame {
+    pub(crate) seq: u64,
+    pub(crate) payload: Arc<str>,
+}
+
+pub(crate) enum Replay {
+    Frames(Vec<Arc<EventFrame>>),
+    Resync,
+}
+
+pub(crate) struct EventLog {
+    epoch: String,
+    inner: Mutex<EventLogInner>,
+    live_tx: broadcast::Sender<Arc<EventFrame>>,
+}
+
+struct EventLogInner {
+    last_seq: u64,
+    frames: VecDeque<Arc<EventFrame>>,
+    bytes: usize,
+}
+
+impl EventLog {
+    pub(crate) fn new() -> Arc<Self> {
+        let epoch = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis())
+            .unwrap_or(0)
+            .to_string();
+        let (live_tx, _) = broadcast::channel(LIVE_CHANNEL_CAPACITY);
+        Arc::new(Self {
+            epoch,
+            inner: Mutex::new(EventLogInner {
+                last_seq: 0,
+                frames: VecDeque::new(),
+                bytes: 0,
+            }),
+            live_tx,
+        })
+    }
+
+    pub(crate) fn epoch(&self) -> &str {
+        &self.epoch
+    }
+
+    pub(crate) fn subscriber_count(&self) -> usize {
+        self.live_tx.receiver_count()
+    }
+
+    pub(crate) fn record(&self, event: &ConsoleEvent) -> Option<u64> {
+        let mut inner = self.inner.lock().ok()?;
+        let seq = inner.last_seq + 1;
+        let payload = match sequenced_payload(event, seq) {
+            Ok(payload) => payload,
+            Err(err) => {
+                tracing::warn!(error = %err, "failed to serialize console event");
+                return None;
+            }
+        };
+        inner.last_seq = seq;
+        let frame = Arc::new(EventFrame {
+            seq,
+            payload: Arc::from(payload),
+        });
+        inner.bytes += frame.payload.len();
+        inner.frames.push_back(Arc::clone(&frame));
+        while inner.frames.len() > REPLAY_MAX_EVENTS || inner.bytes > REPLAY_MAX_BYTES {
+            let Some(dropped) = inner.frames.pop_front() else {
+                break;
+            };
+            inner.bytes -= dropped.payload.len();
+        }
+        // 持锁广播：subscribe 同样持锁，replay 只含 <= last_seq 的事件，live 只收之后的事件，不漏不重。
+        let _ = self.live_tx.send(frame);
+        Some(seq)
+    }
+
+    /// 丢弃保留窗口，之后的 since 续传都会退化为全量重同步。
+    pub(crate) fn reset_replay(&self) {
+        if let Ok(mut inner) = self.inner.lock() {
+            inner.frames.clear();
+            inner.bytes = 0;
+        }
+    }
+
+    pub(crate) fn subscribe(
+        &self,
+        resume: Option<(&str, u64)>,
+    ) -> (broadcast::Receiver<Arc<EventFrame>>, Replay, u64) {
+        let inner = match self.inner.lock() {
+            Ok(inner) => inner,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let rx = self.live_tx.subscribe();
+        let replay = match resume {
+            Some((epoch, since)) if epoch == self.epoch => {
+                replay_range(&inner, since, inner.last_seq)
+            }
+            _ => Replay::Resync,
+        };
+        (rx, replay, inner.last_seq)
+    }
+
+    /// 连接建立后客户端发来 resume 时，补发 (from_seq, upto] 区间；upto 是该连接已推送到的 seq，
+    /// 之后的事件仍由 live 订阅送达。未带 epoch 时按当前 epoch 处理。
+    pub(crate) fn replay_between(&self, epoch: Option<&str>, from_seq: u64, upto: u64) -> Replay {
+        if epoch.is_some_and(|epoch| epoch != self.epoch) {
+            return Replay::Resync;
+        }
+        let inner = match self.inner.lock() {
+            Ok(inner) => inner,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        replay_range(&inner, from_seq, upto)
+    }
+}
+
+fn replay_range(inner: &EventLogInner, since: u64, upto: u64) -> Replay {
+    if since > upto {
+        return Replay::Resync;
+    }
+    let oldest = inner
+        .frames
+        .front()
+        .map(|frame| frame.seq)
+        .unwrap_or(inner.last_seq + 1);
+    if since + 1 < oldest && since < upto {
+        return Replay::Resync;
+    }
+    Replay::Frames(
+        inner
+            .frames
+            .iter()
+            .filter(|frame| frame.seq > since && frame.seq <= upto)
+            .cloned()
+            .collect(),
+    )
+}
+
+pub(crate) fn sequenced_payload(event: &ConsoleEvent, seq: u64) -> serde_json::Result<String> {
+    let mut value = serde_json::to_value(event)?;
+    if let Value::Object(map) = &mut value {
+        map.insert("seq".to_string(), Value::from(seq));
+    }
+    serde_json::to_string(&value)
+}
+
+pub(crate) fn spawn_event_log(
+    log: Arc<EventLog>,
+    mut event_rx: broadcast::Receiver<ConsoleEvent>,
+    state: Arc<RwLock<ConsoleState>>,
+    shutdown: CancellationToken,
+) {
+    tokio::spawn(async move {
+        loop {
+            let event = tokio::select! {
+                _ = shutdown.cancelled() => break,
+                event = event_rx.recv() => event,
+            };
+            match event {
+                Ok(event) => {
+                    log.record(&event);
+                }
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    // 源头就丢了事件：清空续传窗口并补一条 targets_snapshot 让在线客户端重同步。
+                    tracing::warn!(skipped, "console event log lagged; forcing resync");
+                    log.reset_replay();
+                    let targets = state.read().await.list_targets();
+                    log.record(&ConsoleEvent::TargetsSnapshot { targets });
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(name: &str) -> ConsoleEvent {
+        ConsoleEvent::RequestResolved {
+            target: name.to_string(),
+            id: "req".to_string(),
+            status: protocol::CommandStatus::Approved,
+        }
+    }
+
+    #[test]
+    fn resume_replays_events_after_since() {
+        let log = EventLog::new();
+        for name in ["a", "b", "c"] {
+            log.record(&event(name));
+        }
+        let epoch = log.epoch().to_string();
+        let (_rx, replay, _) = log.subscribe(Some((&epoch, 1)));
+        let Replay::Frames(frames) = replay else {
+            panic!("expected replay");
+        };
+        let seqs: Vec<u64> = frames.iter().map(|frame| frame.seq).collect();
+        assert_eq!(seqs, [2, 3]);
+        assert!(frames[0].payload.contains("\"seq\":2"));
+
+        let (_rx, replay, _) = log.subscribe(Some((&epoch, 3)));
+        assert!(matches!(replay, Replay::Frames(frames) if frames.is_empty()));
+    }
+
+    #[test]
+    fn resume_falls_back_to_resync() {
+        let log = EventLog::new();
+        log.record(&event("a"));
+        assert!(matches!(log.subscribe(None).1, Replay::Resync));
+        assert!(matches!(
+            log.subscribe(Some(("other-epoch", 0))).1,
+            Replay::Resync
+        ));
+        let epoch = log.epoch().to_string();
+        assert!(matches!(log.subscribe(Some((&epoch, 5))).1, Replay::Resync));
+        log.reset_replay();
+        log.record(&event("b"));
+        assert!(matches!(log.subscribe(Some((&epoch, 0))).1, Replay::Resync));
+    }
+
+    #[test]
+    fn in_band_resume_replays_up_to_sent_seq() {
+        let log = EventLog::new();
+        for name in ["a", "b", "c", "d"] {
+            log.record(&event(name));
+        }
+        let Replay::Frames(frames) = log.replay_between(None, 1, 3) else {
+            panic!("expected replay");
+        };
+        let seqs: Vec<u64> = frames.iter().map(|frame| frame.seq).collect();
+        assert_eq!(seqs, [2, 3]);
+        let epoch = log.epoch().to_string();
+        assert!(matches!(
+            log.replay_between(Some(&epoch), 3, 3),
+            Replay::Frames(frames) if frames.is_empty()
+        ));
+        assert!(matches!(
+            log.replay_between(Some("other-epoch"), 1, 3),
+            Replay::Resync
+        ));
+        assert!(matches!(log.replay_between(None, 9, 4), Replay::Resync));
+    }
+}
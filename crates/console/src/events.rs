@@ -1,9 +1,41 @@
-use crate::state::TargetInfo;
+use crate::state::{ConsoleSummary, TargetInfo};
+use protocol::control::{RequestSnapshot, ResultSnapshot};
+use protocol::CommandStatus;
 use serde::Serialize;
 
+// request 级事件供前端增量更新；断线重连或 lag 后仍以 snapshot 重新对齐。
 #[derive(Clone, Debug, Serialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub(crate) enum ConsoleEvent {
-    TargetsSnapshot { targets: Vec<TargetInfo> },
-    TargetUpdated { target: TargetInfo },
+    TargetsSnapshot {
+        targets: Vec<TargetInfo>,
+    },
+    TargetUpdated {
+        target: TargetInfo,
+    },
+    RequestQueued {
+        target: String,
+        request: RequestSnapshot,
+    },
+    RequestResolved {
+        target: String,
+        id: String,
+        status: CommandStatus,
+    },
+    ResultRecorded {
+        target: String,
+        result: ResultSnapshot,
+    },
+    /// 某次执行计数后目标（`per_client` 时为该 client）当天的额度刚好用完。
+    QuotaExhausted {
+        target: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        client: Option<String>,
+        limit: String,
+        resets_at_ms: u64,
+    },
+    /// 待审批数量变化，最多每秒一次，供托盘/dock 徽标使用。
+    SummaryChanged {
+        summary: ConsoleSummary,
+    },
 }
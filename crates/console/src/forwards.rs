@@ -0,0 +1,488 @@
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, SocketAddr, TcpListener};
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
+use tokio::process::{Child, Command};
+use tokio::sync::{broadcast, oneshot, Mutex, RwLock};
+
+use protocol::config::ExtraForward;
+use protocol::control::{ForwardDiagnostic, ForwardOutcome, SshFailurePhase};
+use system_utils::ssh::apply_askpass_env;
+
+use crate::events::ConsoleEvent;
+use crate::runtime::update_if_changed;
+use crate::shell_utils::{apply_ssh_options, classify_ssh_failure};
+use crate::state::{ConsoleState, ForwardInfo, TargetSpec};
+use crate::time_utils::now_ms;
+
+// 包含 SSH 建连与认证的时间，与 ConnectTimeout=10 留出余量。
+const FORWARD_READY_TIMEOUT: Duration = Duration::from_secs(15);
+const FORWARD_READY_POLL: Duration = Duration::from_millis(100);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum ForwardError {
+    NotFound,
+    Failed {
+        phase: SshFailurePhase,
+        detail: String,
+    },
+}
+
+struct ActiveForward {
+    // 区分同名转发的前后两次建立，旧进程退出时不误删新的记录。
+    generation: u64,
+    info: ForwardInfo,
+    stop: oneshot::Sender<()>,
+}
+
+type ForwardWaiter = oneshot::Sender<Result<ForwardInfo, ForwardError>>;
+
+#[derive(Default)]
+struct Forwards {
+    next_generation: u64,
+    active: HashMap<(String, String), ActiveForward>,
+    // 正在建立的转发及等待其结果的调用方；建立期间不持锁，同名的并发 ensure 只起一个 ssh。
+    starting: HashMap<(String, String), Vec<ForwardWaiter>>,
+    // 每个转发最近一次 ensure 的结果，转发退出或释放后仍保留，便于排查。
+    diagnostics: HashMap<(String, String), ForwardDiagnostic>,
+}
+
+impl Forwards {
+    fn published(&self, target: &str) -> (Vec<ForwardInfo>, Vec<ForwardDiagnostic>) {
+        (self.active_for(target), self.diagnostics_for(target))
+    }
+
+    fn active_for(&self, target: &str) -> Vec<ForwardInfo> {
+        let mut forwards: Vec<ForwardInfo> = self
+            .active
+            .iter()
+            .filter(|((name, _), _)| name == target)
+            .map(|(_, active)| active.info.clone())
+            .collect();
+        forwards.sort_by(|a, b| a.name.cmp(&b.name));
+        forwards
+    }
+
+    fn diagnostics_for(&self, target: &str) -> Vec<ForwardDiagnostic> {
+        let mut diagnostics: Vec<ForwardDiagnostic> = self
+            .diagnostics
+            .iter()
+            .filter(|((name, _), _)| name == target)
+            .map(|(_, diagnostic)| diagnostic.clone())
+            .collect();
+        diagnostics.sort_by(|a, b| a.name.cmp(&b.name));
+        diagnostics
+    }
+
+    fn record(&mut self, key: &(String, String), remote_addr: &str, outcome: ForwardOutcome) {
+        self.diagnostics.insert(
+            key.clone(),
+            ForwardDiagnostic {
+                name: key.1.clone(),
+                remote_addr: remote_addr.to_string(),
+                at_ms: now_ms(),
+                outcome,
+            },
+        );
+    }
+}
+
+/// 目标 `extra_forwards` 的按需 `ssh -N -L` 转发：每个 (目标, 转发名) 一个 ssh 进程，
+/// 进程退出或被释放后从 TargetInfo.forwards 中移除。
+#[derive(Clone)]
+pub(crate) struct ForwardManager {
+    forwards: Arc<Mutex<Forwards>>,
+    state: Arc<RwLock<ConsoleState>>,
+    event_tx: broadcast::Sender<ConsoleEvent>,
+}
+
+impl ForwardManager {
+    pub(crate) fn new(
+        state: Arc<RwLock<ConsoleState>>,
+        event_tx: broadcast::Sender<ConsoleEvent>,
+    ) -> Self {
+        Self {
+            forwards: Arc::new(Mutex::new(Forwards::default())),
+            state,
+            event_tx,
+        }
+    }
+
+    /// 配置中的全部额外转发，已建立的带上实际绑定的本地地址。
+    pub(crate) async fn list(&self, target: &TargetSpec) -> Vec<ForwardInfo> {
+        let forwards = self.forwards.lock().await;
+        target
+            .extra_forwards
+            .iter()
+            .map(|forward| {
+                forwards
+                    .active
+                    .get(&(target.name.clone(), forward.name.clone()))
+                    .map(|active| active.info.clone())
+                    .unwrap_or_else(|| ForwardInfo {
+                        name: forward.name.clone(),
+                        remote_addr: forward.remote_addr.clone(),
+                        local_addr: None,
+                    })
+            })
+            .collect()
+    }
+
+    /// 已建立则直接返回；否则起 ssh 并等到本地端口可连接。结果（含失败阶段）记入诊断信息。
+    pub(crate) async fn ensure(
+        &self,
+        target: &TargetSpec,
+        name: &str,
+    ) -> Result<ForwardInfo, ForwardError> {
+        let forward = target
+            .extra_forwards
+            .iter()
+            .find(|forward| forward.name == name)
+            .ok_or(ForwardError::NotFound)?;
+        let key = (target.name.clone(), forward.name.clone());
+        let mut forwards = self.forwards.lock().await;
+        if let Some(info) = forwards.active.get(&key).map(|active| active.info.clone()) {
+            forwards.record(
+                &key,
+                &forward.remote_addr,
+                ForwardOutcome::Established {
+                    latency_ms: 0,
+                    reused: true,
+                },
+            );
+            let (active, diagnostics) = forwards.published(&target.name);
+            drop(forwards);
+            self.publish(&target.name, active, diagnostics).await;
+            return Ok(info);
+        }
+
+        let (result_tx, result_rx) = oneshot::channel();
+        match forwards.starting.get_mut(&key) {
+            Some(waiters) => waiters.push(result_tx),
+            None => {
+                forwards.starting.insert(key.clone(), vec![result_tx]);
+                // 建立过程放到独立任务里：调用方断开也不会留下永远占着的预留。
+                let manager = self.clone();
+                let target = target.clone();
+                let forward = forward.clone();
+                tokio::spawn(async move { manager.establish(target, forward, key).await });
+            }
+        }
+        drop(forwards);
+        result_rx.await.unwrap_or_else(|_| {
+            Err(ForwardError::Failed {
+                phase: SshFailurePhase::Connect,
+                detail: "forward setup was interrupted".to_string(),
+            })
+        })
+    }
+
+    async fn establish(&self, target: TargetSpec, forward: ExtraForward, key: (String, String)) {
+        let started = Instant::now();
+        let result = start_forward(&target, &forward).await;
+        let mut forwards = self.forwards.lock().await;
+        let waiters = forwards.starting.remove(&key).unwrap_or_default();
+        let (local_addr, mut child) = match result {
+            Ok(started) => started,
+            Err((phase, detail)) => {
+                forwards.record(
+                    &key,
+                    &forward.remote_addr,
+                    ForwardOutcome::Failed {
+                        phase,
+                        detail: detail.clone(),
+                    },
+                );
+                let (active, diagnostics) = forwards.published(&target.name);
+                drop(forwards);
+                tracing::warn!(
+                    event = "forward.failed",
+                    target = %target.name,
+                    forward = %forward.name,
+                    phase = ?phase,
+                    detail = %detail,
+                );
+                self.publish(&target.name, active, diagnostics).await;
+                let error = ForwardError::Failed { phase, detail };
+                for waiter in waiters {
+                    let _ = waiter.send(Err(error.clone()));
+                }
+                return;
+            }
+        };
+        if let Some(stderr) = child.stderr.take() {
+            let label = format!("{}/{}", target.name, forward.name);
+            tokio::spawn(async move {
+                let mut lines = BufReader::new(stderr).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    tracing::debug!(forward = %label, "{line}");
+                }
+            });
+        }
+
+        forwards.next_generation += 1;
+        let generation = forwards.next_generation;
+        let info = ForwardInfo {
+            name: forward.name.clone(),
+            remote_addr: forward.remote_addr.clone(),
+            local_addr: Some(local_addr.to_string()),
+        };
+        let (stop, stopped) = oneshot::channel();
+        forwards.active.insert(
+            key.clone(),
+            ActiveForward {
+                generation,
+                info: info.clone(),
+                stop,
+            },
+        );
+        forwards.record(
+            &key,
+            &forward.remote_addr,
+            ForwardOutcome::Established {
+                latency_ms: started.elapsed().as_millis() as u64,
+                reused: false,
+            },
+        );
+        let (active, diagnostics) = forwards.published(&target.name);
+        drop(forwards);
+        tracing::info!(
+            event = "forward.ensured",
+            target = %target.name,
+            forward = %info.name,
+            local_addr = %local_addr,
+            remote_addr = %info.remote_addr,
+        );
+        self.publish(&target.name, active, diagnostics).await;
+        self.watch(key, generation, child, stopped);
+        for waiter in waiters {
+            let _ = waiter.send(Ok(info.clone()));
+        }
+    }
+
+    /// 未建立时返回 false。
+    pub(crate) async fn release(&self, target: &str, name: &str) -> bool {
+        let mut forwards = self.forwards.lock().await;
+        let Some(active) = forwards
+            .active
+            .remove(&(target.to_string(), name.to_string()))
+        else {
+            return false;
+        };
+        let _ = active.stop.send(());
+        let (remaining, diagnostics) = forwards.published(target);
+        drop(forwards);
+        tracing::info!(event = "forward.released", target = %target, forward = %name);
+        self.publish(target, remaining, diagnostics).await;
+        true
+    }
+
+    fn watch(
+        &self,
+        key: (String, String),
+        generation: u64,
+        mut child: Child,
+        stopped: oneshot::Receiver<()>,
+    ) {
+        let manager = self.clone();
+        tokio::spawn(async move {
+            tokio::select! {
+                status = child.wait() => {
+                    tracing::warn!(
+                        event = "forward.exited",
+                        target = %key.0,
+                        forward = %key.1,
+                        status = ?status.ok(),
+                    );
+                    let mut forwards = manager.forwards.lock().await;
+                    let current = forwards
+                        .active
+                        .get(&key)
+                        .is_some_and(|active| active.generation == generation);
+                    if current {
+                        forwards.active.remove(&key);
+                        let (remaining, diagnostics) = forwards.published(&key.0);
+                        drop(forwards);
+                        manager.publish(&key.0, remaining, diagnostics).await;
+                    }
+                }
+                _ = stopped => {
+                    let _ = child.kill().await;
+                }
+            }
+        });
+    }
+
+    async fn publish(
+        &self,
+        target: &str,
+        forwards: Vec<ForwardInfo>,
+        diagnostics: Vec<ForwardDiagnostic>,
+    ) {
+        update_if_changed(target, &self.state, &self.event_tx, |state| {
+            state.set_forwards(target, forwards);
+            state.set_forward_diagnostics(target, diagnostics);
+        })
+        .await;
+    }
+}
+
+// 优先用配置的端口；被占用时退回系统分配的临时端口。探测用的监听随即释放，由 ssh 重新绑定。
+fn pick_local_addr(preferred: Option<u16>) -> std::io::Result<SocketAddr> {
+    if let Some(port) = preferred.filter(|port| *port != 0) {
+        if let Ok(listener) = TcpListener::bind((Ipv4Addr::LOCALHOST, port)) {
+            return listener.local_addr();
+        }
+    }
+    TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?.local_addr()
+}
+
+async fn start_forward(
+    target: &TargetSpec,
+    forward: &ExtraForward,
+) -> Result<(SocketAddr, Child), (SshFailurePhase, String)> {
+    let local_addr = pick_local_addr(forward.local_port).map_err(|err| {
+        (
+            SshFailurePhase::LocalBind,
+            format!("no free local port: {err}"),
+        )
+    })?;
+    if forward
+        .local_port
+        .is_some_and(|port| port != local_addr.port())
+    {
+        tracing::info!(
+            event = "forward.port_fallback",
+            target = %target.name,
+            forward = %forward.name,
+            preferred = ?forward.local_port,
+            local_addr = %local_addr,
+            "preferred local port is in use"
+        );
+    }
+    let mut child = spawn_forward(target, local_addr, &forward.remote_addr)
+        .map_err(|err| (SshFailurePhase::Connect, err))?;
+    wait_until_listening(&mut child, local_addr)
+        .await
+        .map_err(|err| match err {
+            WaitError::Exited(stderr) => {
+                classify_ssh_failure(&stderr, &target.host_key, Some(&forward.remote_addr))
+            }
+            WaitError::Other(detail) => (SshFailurePhase::Connect, detail),
+        })?;
+    Ok((local_addr, child))
+}
+
+fn spawn_forward(
+    target: &TargetSpec,
+    local_addr: SocketAddr,
+    remote_addr: &str,
+) -> Result<Child, String> {
+    let ssh = target
+        .ssh
+        .as_deref()
+        .ok_or_else(|| "missing ssh target".to_string())?;
+    let mut cmd = Command::new("ssh");
+    if let Some(password) = target.ssh_password.as_deref() {
+        apply_askpass_env(&mut cmd, password).map_err(|err| err.to_string())?;
+    }
+    cmd.arg("-N").arg("-T");
+    cmd.arg("-o").arg("ExitOnForwardFailure=yes");
+    cmd.arg("-L").arg(format!("{local_addr}:{remote_addr}"));
+    apply_ssh_options(&mut cmd, target.ssh_password.is_some(), &target.host_key);
+    cmd.args(&target.ssh_args);
+    cmd.arg(ssh);
+    cmd.stdin(Stdio::null());
+    cmd.stdout(Stdio::null());
+    cmd.stderr(Stdio::piped());
+    cmd.kill_on_drop(true);
+    cmd.spawn()
+        .map_err(|err| format!("failed to spawn ssh: {err}"))
+}
+
+enum WaitError {
+    /// ssh 已退出且留下了 stderr，交给 classify_ssh_failure 归类。
+    Exited(String),
+    Other(String),
+}
+
+async fn wait_until_listening(child: &mut Child, local_addr: SocketAddr) -> Result<(), WaitError> {
+    let deadline = tokio::time::Instant::now() + FORWARD_READY_TIMEOUT;
+    loop {
+        if let Some(status) = child
+            .try_wait()
+            .map_err(|err| WaitError::Other(err.to_string()))?
+        {
+            let mut stderr = String::new();
+            if let Some(mut pipe) = child.stderr.take() {
+                let _ = pipe.read_to_string(&mut stderr).await;
+            }
+            return Err(if stderr.trim().is_empty() {
+                WaitError::Other(format!("ssh exited with {status}"))
+            } else {
+                WaitError::Exited(stderr)
+            });
+        }
+        if tokio::net::TcpStream::connect(local_addr).await.is_ok() {
+            return Ok(());
+        }
+        if tokio::time::Instant::now() >= deadline {
+            return Err(WaitError::Other(format!(
+                "ssh forward did not listen on {local_addr} within {}s",
+                FORWARD_READY_TIMEOUT.as_secs()
+            )));
+        }
+        tokio::time::sleep(FORWARD_READY_POLL).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn falls_back_to_ephemeral_port_when_preferred_is_taken() {
+        let taken = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let port = taken.local_addr().unwrap().port();
+        let addr = pick_local_addr(Some(port)).unwrap();
+        assert_ne!(addr.port(), port);
+        assert!(addr.ip().is_loopback());
+        drop(taken);
+        assert_eq!(pick_local_addr(Some(port)).unwrap().port(), port);
+        assert_ne!(pick_local_addr(None).unwrap().port(), 0);
+    }
+
+    #[test]
+    fn keeps_last_outcome_per_forward() {
+        let mut forwards = Forwards::default();
+        let key = |target: &str, name: &str| (target.to_string(), name.to_string());
+        let failed = ForwardOutcome::Failed {
+            phase: SshFailurePhase::Auth,
+            detail: "ssh auth failed: denied".to_string(),
+        };
+        forwards.record(&key("dev", "pg"), "127.0.0.1:5432", failed.clone());
+        forwards.record(&key("prod", "pg"), "127.0.0.1:5432", failed.clone());
+        forwards.record(&key("dev", "api"), "127.0.0.1:19302", failed);
+        forwards.record(
+            &key("dev", "pg"),
+            "127.0.0.1:5432",
+            ForwardOutcome::Established {
+                latency_ms: 120,
+                reused: false,
+            },
+        );
+        let diagnostics = forwards.diagnostics_for("dev");
+        let names: Vec<&str> = diagnostics.iter().map(|item| item.name.as_str()).collect();
+        assert_eq!(names, ["api", "pg"]);
+        assert!(matches!(
+            diagnostics[1].outcome,
+            ForwardOutcome::Established {
+                latency_ms: 120,
+                ..
+            }
+        ));
+    }
+}
@@ -0,0 +1,176 @@
+//! HTTP/WS API 的 bearer token 鉴权与可选 TLS。
+
+use std::future::Future;
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::Context;
+use axum::body::Body;
+use axum::extract::{Query, State};
+use axum::http::header::{AUTHORIZATION, UPGRADE};
+use axum::http::{Request, StatusCode, Uri};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Router;
+use axum_server::tls_rustls::RustlsConfig;
+use axum_server::Handle;
+use serde::Deserialize;
+use system_utils::secret::constant_time_eq;
+use tokio::net::TcpListener;
+
+pub(crate) const API_TOKEN_ENV: &str = "OCTOVALVE_CONSOLE_API_TOKEN";
+
+// 供 sidecar 启动探活与负载均衡健康检查使用，不要求 token。
+const PUBLIC_PATHS: &[&str] = &["/health"];
+
+#[derive(Deserialize)]
+struct TokenQuery {
+    token: Option<String>,
+}
+
+/// 依次取 --api-token、--api-token-file 与 OCTOVALVE_CONSOLE_API_TOKEN；都没有时不启用鉴权。
+pub(crate) fn resolve_api_token(
+    token: Option<&str>,
+    token_file: Option<&Path>,
+) -> anyhow::Result<Option<Arc<str>>> {
+    if let Some(token) = token {
+        let token = token.trim();
+        if token.is_empty() {
+            anyhow::bail!("--api-token is empty");
+        }
+        return Ok(Some(Arc::from(token)));
+    }
+    if let Some(path) = token_file {
+        let raw = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read api token file {}", path.display()))?;
+        let token = raw.trim();
+        if token.is_empty() {
+            anyhow::bail!("api token file {} is empty", path.display());
+        }
+        return Ok(Some(Arc::from(token)));
+    }
+    match std::env::var(API_TOKEN_ENV) {
+        Ok(value) if !value.trim().is_empty() => Ok(Some(Arc::from(value.trim()))),
+        _ => Ok(None),
+    }
+}
+
+/// 缺少或不匹配 token 时只回 401，不说明原因。
+pub(crate) async fn require_api_token(
+    State(expected): State<Arc<str>>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    let authorized = PUBLIC_PATHS.contains(&req.uri().path())
+        || request_token(&req)
+            .is_some_and(|token| constant_time_eq(token.as_bytes(), expected.as_bytes()));
+    if !authorized {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    next.run(req).await
+}
+
+fn request_token<B>(req: &Request<B>) -> Option<String> {
+    if let Some(value) = req.headers().get(AUTHORIZATION) {
+        let value = value.to_str().ok()?;
+        return value
+            .strip_prefix("Bearer ")
+            .map(|token| token.trim().to_string());
+    }
+    // 浏览器的 WebSocket API 无法设置请求头，升级请求允许用 ?token= 传入。
+    let is_upgrade = req
+        .headers()
+        .get(UPGRADE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.eq_ignore_ascii_case("websocket"));
+    if !is_upgrade {
+        return None;
+    }
+    Query::<TokenQuery>::try_from_uri(req.uri()).ok()?.0.token
+}
+
+/// 访问日志里去掉 `token` 查询参数，避免 token 落盘。
+pub(crate) fn redacted_uri(uri: &Uri) -> String {
+    let Some(query) = uri.query() else {
+        return uri.to_string();
+    };
+    let query = query
+        .split('&')
+        .map(|pair| {
+            if pair == "token" || pair.starts_with("token=") {
+                "token=***"
+            } else {
+                pair
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("&");
+    format!("{}?{query}", uri.path())
+}
+
+/// 以 rustls 提供 HTTPS/WSS；`shutdown` 完成后与 axum::serve 一样等待现有连接结束。
+pub(crate) async fn serve_tls(
+    listener: TcpListener,
+    app: Router,
+    cert: &Path,
+    key: &Path,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> anyhow::Result<()> {
+    let config = RustlsConfig::from_pem_file(cert, key)
+        .await
+        .with_context(|| {
+            format!(
+                "failed to load tls cert {} / key {}",
+                cert.display(),
+                key.display()
+            )
+        })?;
+    let handle = Handle::new();
+    let shutdown_handle = handle.clone();
+    tokio::spawn(async move {
+        shutdown.await;
+        shutdown_handle.graceful_shutdown(None);
+    });
+    axum_server::from_tcp_rustls(listener.into_std()?, config)
+        .handle(handle)
+        .serve(app.into_make_service())
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(uri: &str, headers: &[(&str, &str)]) -> Request<()> {
+        let mut builder = Request::builder().uri(uri);
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        builder.body(()).expect("request")
+    }
+
+    #[test]
+    fn reads_bearer_header_and_websocket_query_token() {
+        let req = request("/targets", &[("authorization", "Bearer secret")]);
+        assert_eq!(request_token(&req).as_deref(), Some("secret"));
+
+        let req = request("/targets", &[("authorization", "Basic c2VjcmV0")]);
+        assert_eq!(request_token(&req), None);
+
+        // 普通请求不接受查询参数里的 token。
+        let req = request("/targets?token=secret", &[]);
+        assert_eq!(request_token(&req), None);
+
+        let req = request("/ws?since=3&token=se%2Bcret", &[("upgrade", "websocket")]);
+        assert_eq!(request_token(&req).as_deref(), Some("se+cret"));
+    }
+
+    #[test]
+    fn redacts_token_query_parameter() {
+        let uri: Uri = "/ws?epoch=a&token=secret&since=3".parse().expect("uri");
+        assert_eq!(redacted_uri(&uri), "/ws?epoch=a&token=***&since=3");
+        let uri: Uri = "/targets".parse().expect("uri");
+        assert_eq!(redacted_uri(&uri), "/targets");
+    }
+}
@@ -0,0 +1,133 @@
+use axum::extract::ws::{close_code, CloseFrame, Message};
+use axum::extract::DefaultBodyLimit;
+use protocol::config::ServerSettings;
+
+const DEFAULT_MAX_CONTROL_BODY_BYTES: usize = 16 * 1024;
+const DEFAULT_MAX_BODY_BYTES: usize = 1024 * 1024;
+const DEFAULT_MAX_WS_MESSAGE_BYTES: usize = 256 * 1024;
+const DEFAULT_WS_SEND_QUEUE: usize = 256;
+
+/// `[server]` 解析后的上限；请求体超限由 axum 返回 413。
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct ServerLimits {
+    pub(crate) max_control_body_bytes: usize,
+    pub(crate) max_body_bytes: usize,
+    pub(crate) max_ws_message_bytes: usize,
+    pub(crate) ws_send_queue: usize,
+}
+
+impl ServerLimits {
+    pub(crate) fn from_config(server: Option<&ServerSettings>) -> Self {
+        let pick = |value: Option<usize>, default: usize| {
+            value.filter(|value| *value > 0).unwrap_or(default)
+        };
+        Self {
+            max_control_body_bytes: pick(
+                server.and_then(|server| server.max_control_body_bytes),
+                DEFAULT_MAX_CONTROL_BODY_BYTES,
+            ),
+            max_body_bytes: pick(
+                server.and_then(|server| server.max_body_bytes),
+                DEFAULT_MAX_BODY_BYTES,
+            ),
+            max_ws_message_bytes: pick(
+                server.and_then(|server| server.max_ws_message_bytes),
+                DEFAULT_MAX_WS_MESSAGE_BYTES,
+            ),
+            ws_send_queue: pick(
+                server.and_then(|server| server.ws_send_queue),
+                DEFAULT_WS_SEND_QUEUE,
+            ),
+        }
+    }
+
+    /// 整个 router 的请求体上限。
+    pub(crate) fn body_limit(&self) -> DefaultBodyLimit {
+        DefaultBodyLimit::max(self.max_body_bytes)
+    }
+
+    /// 审批控制类路由挂在自身上的上限，覆盖外层的 [`Self::body_limit`]。
+    pub(crate) fn control_body_limit(&self) -> DefaultBodyLimit {
+        DefaultBodyLimit::max(self.max_control_body_bytes)
+    }
+}
+
+// tungstenite 超出 max_message_size / max_frame_size 时返回 Capacity 错误，axum 只透传其文本。
+pub(crate) fn is_message_too_big(err: &axum::Error) -> bool {
+    err.to_string().contains("Space limit exceeded")
+}
+
+pub(crate) fn message_too_big_close() -> Message {
+    Message::Close(Some(CloseFrame {
+        code: close_code::SIZE,
+        reason: "message too big".into(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn falls_back_to_defaults_for_missing_or_zero_values() {
+        let defaults = ServerLimits::from_config(None);
+        assert_eq!(
+            defaults.max_control_body_bytes,
+            DEFAULT_MAX_CONTROL_BODY_BYTES
+        );
+        assert_eq!(defaults.ws_send_queue, DEFAULT_WS_SEND_QUEUE);
+
+        let limits = ServerLimits::from_config(Some(&ServerSettings {
+            max_control_body_bytes: Some(0),
+            max_body_bytes: Some(4096),
+            max_ws_message_bytes: None,
+            ws_send_queue: Some(8),
+        }));
+        assert_eq!(
+            limits,
+            ServerLimits {
+                max_control_body_bytes: DEFAULT_MAX_CONTROL_BODY_BYTES,
+                max_body_bytes: 4096,
+                max_ws_message_bytes: DEFAULT_MAX_WS_MESSAGE_BYTES,
+                ws_send_queue: 8,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn control_routes_reject_bodies_over_the_control_limit() {
+        use axum::routing::post;
+        use axum::Json;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let limits = ServerLimits {
+            max_control_body_bytes: 64,
+            max_body_bytes: 4096,
+            ..ServerLimits::from_config(None)
+        };
+        let echo = |Json(body): Json<serde_json::Value>| async move { Json(body) };
+        let app = axum::Router::new()
+            .route("/control", post(echo).layer(limits.control_body_limit()))
+            .route("/other", post(echo))
+            .layer(limits.body_limit());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let body = format!("{{\"command\":\"{}\"}}", "x".repeat(256));
+        for (path, status) in [("/control", "413"), ("/other", "200")] {
+            let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+            let request = format!(
+                "POST {path} HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            stream.write_all(request.as_bytes()).await.unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await.unwrap();
+            assert!(
+                response.starts_with(&format!("HTTP/1.1 {status}")),
+                "{path}: {response}"
+            );
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use std::net::{IpAddr, SocketAddr};
+
+// 非 loopback 监听时的来源过滤；loopback 来源（本机 proxy）始终放行。
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct ClientAccess {
+    cidrs: Vec<Cidr>,
+}
+
+impl ClientAccess {
+    /// loopback 监听不需要过滤，返回 None；非 loopback 监听必须显式开启并提供 CIDR 列表。
+    pub(crate) fn for_listener(
+        listen_addr: SocketAddr,
+        allow_nonlocal_bind: bool,
+        allowed_client_cidrs: &[String],
+    ) -> anyhow::Result<Option<Self>> {
+        if listen_addr.ip().is_loopback() {
+            return Ok(None);
+        }
+        if !allow_nonlocal_bind {
+            anyhow::bail!(
+                "command_listen_addr {listen_addr} is not a loopback address; pass --allow-nonlocal-bind together with --allowed-client-cidr to expose it"
+            );
+        }
+        let cidrs = allowed_client_cidrs
+            .iter()
+            .map(|value| Cidr::parse(value))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        if cidrs.is_empty() {
+            anyhow::bail!(
+                "--allow-nonlocal-bind requires at least one --allowed-client-cidr for {listen_addr}"
+            );
+        }
+        Ok(Some(Self { cidrs }))
+    }
+
+    pub(crate) fn permits(&self, peer: IpAddr) -> bool {
+        let peer = peer.to_canonical();
+        peer.is_loopback() || self.cidrs.iter().any(|cidr| cidr.contains(peer))
+    }
+}
+
+pub(super) fn accept_peer(access: Option<&ClientAccess>, peer: SocketAddr) -> bool {
+    match access {
+        Some(access) => access.permits(peer.ip()),
+        None => true,
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Cidr {
+    network: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    fn parse(value: &str) -> anyhow::Result<Self> {
+        let trimmed = value.trim();
+        let (addr, prefix) = match trimmed.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (trimmed, None),
+        };
+        let network: IpAddr = addr
+            .parse()
+            .map_err(|_| anyhow::anyhow!("invalid client CIDR {trimmed:?}"))?;
+        let max_prefix = if network.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix
+                .parse::<u8>()
+                .ok()
+                .filter(|prefix| *prefix <= max_prefix)
+                .ok_or_else(|| {
+                    anyhow::anyhow!("invalid prefix length in client CIDR {trimmed:?}")
+                })?,
+            None => max_prefix,
+        };
+        Ok(Self { network, prefix })
+    }
+
+    fn contains(&self, ip: IpAddr) -> bool {
+        match (self.network, ip) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                prefix_matches(&network.octets(), &ip.octets(), self.prefix)
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                prefix_matches(&network.octets(), &ip.octets(), self.prefix)
+            }
+            _ => false,
+        }
+    }
+}
+
+fn prefix_matches(network: &[u8], ip: &[u8], prefix: u8) -> bool {
+    let full_bytes = usize::from(prefix / 8);
+    if network[..full_bytes] != ip[..full_bytes] {
+        return false;
+    }
+    let rest_bits = prefix % 8;
+    if rest_bits == 0 {
+        return true;
+    }
+    let mask = 0xffu8 << (8 - rest_bits);
+    network[full_bytes] & mask == ip[full_bytes] & mask
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(value: &str) -> SocketAddr {
+        value.parse().expect("socket addr")
+    }
+
+    #[test]
+    fn cidr_matches_prefixes() {
+        let cidr = Cidr::parse("10.0.0.0/8").unwrap();
+        assert!(cidr.contains("10.200.3.4".parse().unwrap()));
+        assert!(!cidr.contains("11.0.0.1".parse().unwrap()));
+
+        let cidr = Cidr::parse("192.168.1.128/25").unwrap();
+        assert!(cidr.contains("192.168.1.200".parse().unwrap()));
+        assert!(!cidr.contains("192.168.1.127".parse().unwrap()));
+
+        let cidr = Cidr::parse("192.168.2.162").unwrap();
+        assert!(cidr.contains("192.168.2.162".parse().unwrap()));
+        assert!(!cidr.contains("192.168.2.163".parse().unwrap()));
+
+        let cidr = Cidr::parse("fd00::/8").unwrap();
+        assert!(cidr.contains("fd12::1".parse().unwrap()));
+        assert!(!cidr.contains("10.0.0.1".parse().unwrap()));
+
+        assert!(Cidr::parse("0.0.0.0/0")
+            .unwrap()
+            .contains("8.8.8.8".parse().unwrap()));
+        assert!(Cidr::parse("10.0.0.0/33").is_err());
+        assert!(Cidr::parse("not-an-ip/8").is_err());
+    }
+
+    #[test]
+    fn nonlocal_listener_requires_flag_and_cidrs() {
+        let cidrs = vec!["10.0.0.0/8".to_string()];
+        assert_eq!(
+            ClientAccess::for_listener(addr("127.0.0.1:19310"), false, &[]).unwrap(),
+            None
+        );
+        assert!(ClientAccess::for_listener(addr("0.0.0.0:19310"), false, &cidrs).is_err());
+        assert!(ClientAccess::for_listener(addr("0.0.0.0:19310"), true, &[]).is_err());
+        assert!(
+            ClientAccess::for_listener(addr("0.0.0.0:19310"), true, &cidrs)
+                .unwrap()
+                .is_some()
+        );
+    }
+
+    #[test]
+    fn accept_path_drops_peers_outside_allowlist() {
+        let cidrs = vec!["10.0.0.0/8".to_string()];
+        let access = ClientAccess::for_listener(addr("0.0.0.0:19310"), true, &cidrs).unwrap();
+        let access = access.as_ref();
+        assert!(accept_peer(access, addr("10.1.2.3:50000")));
+        assert!(accept_peer(access, addr("127.0.0.1:50000")));
+        assert!(accept_peer(access, addr("[::ffff:10.1.2.3]:50000")));
+        assert!(!accept_peer(access, addr("192.168.1.5:50000")));
+        assert!(accept_peer(None, addr("192.168.1.5:50000")));
+    }
+}
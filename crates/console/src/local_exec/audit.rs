@@ -1,10 +1,14 @@
-use protocol::{CommandRequest, CommandStage};
+use protocol::control::{ExecutionContext, TemporaryRuleUse};
+use protocol::{CommandRequest, CommandStage, RequestPriority};
 use serde::Serialize;
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::SystemTime;
 
 use super::events::PendingRequest;
+use crate::terminal_capture::{CapturedCommand, TERMINAL_SOURCE};
+use crate::time_utils::system_time_ms;
 
 #[derive(Serialize)]
 pub(crate) struct RequestRecord {
@@ -22,6 +26,22 @@ pub(crate) struct RequestRecord {
     timeout_ms: Option<u64>,
     max_output_bytes: Option<u64>,
     pipeline: Vec<CommandStage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    priority: Option<RequestPriority>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    batch_id: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    intent_tags: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metadata: Option<BTreeMap<String, String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    run_as: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    temporary_rules: Vec<TemporaryRuleUse>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    trace_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source: Option<String>,
 }
 
 impl RequestRecord {
@@ -45,12 +65,50 @@ impl RequestRecord {
             timeout_ms: request.timeout_ms,
             max_output_bytes: request.max_output_bytes,
             pipeline: request.pipeline.clone(),
+            priority: request.priority,
+            batch_id: request.batch_id.clone(),
+            intent_tags: request.intent_tags.clone(),
+            metadata: request.metadata.clone(),
+            run_as: request.run_as.clone(),
+            temporary_rules: Vec::new(),
+            trace_id: request.trace_id.clone(),
+            source: None,
+        }
+    }
+
+    /// 交互式终端里记录下来的命令：没有经过审批，`source` 标为 `terminal`。
+    pub(crate) fn from_terminal_command(id: &str, target: &str, command: &CapturedCommand) -> Self {
+        Self {
+            id: id.to_string(),
+            client: TERMINAL_SOURCE.to_string(),
+            target: target.to_string(),
+            peer: TERMINAL_SOURCE.to_string(),
+            received_at_ms: command.started_at_ms,
+            intent: command.intent().to_string(),
+            mode: protocol::CommandMode::Shell,
+            command: command.command.clone(),
+            raw_command: command.command.clone(),
+            cwd: None,
+            env: None,
+            timeout_ms: None,
+            max_output_bytes: None,
+            pipeline: Vec::new(),
+            priority: None,
+            batch_id: None,
+            intent_tags: Vec::new(),
+            metadata: Some(command.metadata()),
+            run_as: None,
+            temporary_rules: Vec::new(),
+            trace_id: None,
+            source: Some(TERMINAL_SOURCE.to_string()),
         }
     }
 }
 
 pub(crate) fn spawn_write_request_record(output_dir: Arc<PathBuf>, pending: &PendingRequest) {
-    let record = RequestRecord::from_request(&pending.request, &pending.peer, pending.received_at);
+    let mut record =
+        RequestRecord::from_request(&pending.request, &pending.peer, pending.received_at);
+    record.temporary_rules = pending.temporary_rules.clone();
     spawn_write_request_record_value(output_dir, record);
 }
 
@@ -72,8 +130,22 @@ pub(crate) async fn write_request_record(
     Ok(())
 }
 
-fn system_time_ms(time: SystemTime) -> u64 {
-    time.duration_since(UNIX_EPOCH)
-        .map(|duration| duration.as_millis() as u64)
-        .unwrap_or(0)
+/// 执行上下文单独落盘为 `<id>.context.json`，历史加载时再合并回结果快照。
+pub(crate) fn spawn_write_execution_context(
+    output_dir: Arc<PathBuf>,
+    id: String,
+    context: ExecutionContext,
+) {
+    tokio::spawn(async move {
+        let path = output_dir.join(format!("{id}.context.json"));
+        let result = match serde_json::to_vec_pretty(&context) {
+            Ok(payload) => tokio::fs::write(path, payload)
+                .await
+                .map_err(anyhow::Error::from),
+            Err(err) => Err(err.into()),
+        };
+        if let Err(err) = result {
+            tracing::warn!(error = %err, "failed to write execution context");
+        }
+    });
 }
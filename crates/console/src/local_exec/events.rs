@@ -1,4 +1,6 @@
+use protocol::control::{FileChange, TemporaryRuleUse};
 use protocol::{CommandRequest, CommandResponse};
+use std::collections::BTreeMap;
 use std::time::{Instant, SystemTime};
 use tokio::sync::oneshot;
 
@@ -14,4 +16,68 @@ pub(crate) struct PendingRequest {
     pub(crate) received_at: SystemTime,
     pub(crate) queued_at: Instant,
     pub(crate) respond_to: oneshot::Sender<CommandResponse>,
+    pub(crate) file_changes: Vec<FileChange>,
+    /// 入队时按脱敏规则生成的环境变量预览，快照只使用这一份。
+    pub(crate) env_preview: Option<BTreeMap<String, String>>,
+    /// 命中 dangerous_patterns，批准时需要回显请求 id。
+    pub(crate) requires_confirmation: bool,
+    /// raw_command 无法解析，pipeline 仍是客户端声明的版本，只能人工审批。
+    pub(crate) unparseable: bool,
+    /// 命中某条时间窗但当前不在窗内，只能人工审批。
+    pub(crate) outside_window: bool,
+    /// 操作员审批时改写了命令，这里保留客户端提交的原始 raw_command。
+    pub(crate) original_command: Option<String>,
+    /// 放行了被拒命令的临时规则，审批时需要提醒操作员。
+    pub(crate) temporary_rules: Vec<TemporaryRuleUse>,
+    /// 定时任务设置了 auto_approve：命令全部通过白名单时以该 actor 自动批准。
+    pub(crate) preapproved_by: Option<String>,
+    /// 批准或拒绝时记录，未决定前为 None。
+    pub(crate) decision: Option<ApprovalDecision>,
+}
+
+/// 谁在排队多久之后批准或拒绝了请求；自动批准的 actor 为 `auto-approve`。
+#[derive(Clone, Debug)]
+pub(crate) struct ApprovalDecision {
+    pub(crate) actor: String,
+    pub(crate) approved: bool,
+    pub(crate) latency_ms: u64,
+    /// 操作员拒绝时填写的原因，会随错误信息返回给 agent。
+    pub(crate) reason: Option<String>,
+}
+
+impl ApprovalDecision {
+    pub(crate) fn approved_by(&self) -> Option<String> {
+        self.approved.then(|| self.actor.clone())
+    }
+
+    pub(crate) fn denied_by(&self) -> Option<String> {
+        (!self.approved).then(|| self.actor.clone())
+    }
+
+    pub(crate) fn deny_reason(&self) -> Option<String> {
+        self.reason.clone().filter(|_| !self.approved)
+    }
+}
+
+impl PendingRequest {
+    pub(crate) fn decide(&mut self, actor: String, approved: bool) {
+        self.decision = Some(ApprovalDecision {
+            actor,
+            approved,
+            latency_ms: self.queued_at.elapsed().as_millis() as u64,
+            reason: None,
+        });
+    }
+
+    pub(crate) fn deny(&mut self, actor: String, reason: Option<String>) {
+        self.decide(actor, false);
+        if let Some(decision) = self.decision.as_mut() {
+            decision.reason = reason;
+        }
+    }
+
+    /// intake 总会补齐追踪 id；直接构造的请求（测试等）没有时为空串。
+    pub(crate) fn trace_id(&self) -> &str {
+        self.request.trace_id.as_deref().unwrap_or_default()
+    }
 }
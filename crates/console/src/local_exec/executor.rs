@@ -7,14 +7,18 @@ use std::sync::Arc;
 use std::time::Duration;
 
 use anyhow::Context;
+use base64::engine::general_purpose::STANDARD as BASE64_ENGINE;
+use base64::Engine;
 use portable_pty::{native_pty_system, CommandBuilder, PtySize};
 use tokio::process::Command;
 use tokio::sync::{mpsc, Mutex};
 use tokio_util::sync::CancellationToken;
 
-use protocol::{CommandRequest, CommandResponse};
+use protocol::control::{ExecutionContext, ExecutionPath, TemporaryRuleUse};
+use protocol::{CommandRequest, CommandResponse, OutputRef, BINARY_OUTPUT_ENCODING};
 use system_utils::path::expand_tilde;
 use system_utils::ssh::apply_askpass_env;
+use system_utils::terminal::normalize_terminal_output;
 use tracing::warn;
 
 use crate::shell_utils::{
@@ -22,9 +26,14 @@ use crate::shell_utils::{
 };
 use crate::state::TargetSpec;
 
-use super::policy::{LimitsConfig, Whitelist};
+use super::policy::{
+    policy_denial, LimitsConfig, ResourceLimits, RunAsConfig, SandboxConfig, Whitelist,
+};
 use super::process::{apply_process_group, terminate_child};
-use super::stream::read_stream_capture;
+use super::redaction::{RedactionConfig, REDACTED};
+use super::stream::{read_stream_capture, spool_output};
+use super::temporary::rule_matches;
+use super::windows::build_windows_command;
 
 const DEFAULT_SSH_CONTROL_DIR: &str = "~/.octovalve/ssh-control";
 const DEFAULT_SSH_CONTROL_PERSIST: &str = "60s";
@@ -34,22 +43,45 @@ const DEFAULT_PTY_TERM: &str = "xterm-256color";
 const PTY_CANCEL_GRACE_SECS: u64 = 2;
 const PTY_MARKER_BEGIN_PREFIX: &str = "__OCTOVALVE_BEGIN__";
 const PTY_MARKER_END_PREFIX: &str = "__OCTOVALVE_END__";
-
+const PTY_MARKER_READY_PREFIX: &str = "__OCTOVALVE_READY__";
+const PTY_WARMUP_TIMEOUT: Duration = Duration::from_secs(15);
+const PTY_HEALTH_TIMEOUT: Duration = Duration::from_secs(2);
+const CWD_CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+const SIGXCPU_EXIT_CODE: i32 = 128 + 24;
+const SIGXFSZ_EXIT_CODE: i32 = 128 + 25;
+const MEMORY_ERROR_PATTERNS: [&str; 4] = [
+    "cannot allocate memory",
+    "out of memory",
+    "memory exhausted",
+    "bad_alloc",
+];
+
+/// 返回响应以及实际执行方式；命令没有真正发出（被拒绝、spawn 失败等）时上下文为 None。
 pub(super) async fn execute_request(
     target: &TargetSpec,
     request: &CommandRequest,
     whitelist: &Whitelist,
+    temporary_rules: &[TemporaryRuleUse],
     limits: &LimitsConfig,
+    sandbox: &SandboxConfig,
+    redaction: &RedactionConfig,
     pty_manager: Option<Arc<PtySessionManager>>,
+    spool_dir: Option<&Path>,
     cancel: CancellationToken,
     force_cancel: CancellationToken,
-) -> CommandResponse {
+) -> (CommandResponse, Option<ExecutionContext>) {
     if cancel.is_cancelled() || force_cancel.is_cancelled() {
-        return CommandResponse::cancelled(request.id.clone(), None, None, None);
+        return (
+            CommandResponse::cancelled(request.id.clone(), None, None, None),
+            None,
+        );
     }
 
     if request.raw_command.trim().is_empty() {
-        return CommandResponse::error(request.id.clone(), "raw_command is empty");
+        return (
+            CommandResponse::error(request.id.clone(), "raw_command is empty"),
+            None,
+        );
     }
 
     if request.pipeline.is_empty() {
@@ -57,10 +89,56 @@ pub(super) async fn execute_request(
             id = %request.id,
             "empty pipeline, skipping whitelist validation"
         );
-    } else {
-        for stage in &request.pipeline {
-            if let Err(message) = whitelist.validate_deny(stage) {
-                return CommandResponse::denied(request.id.clone(), message);
+    } else if let Err(denial) = policy_denial(whitelist, request, |command| {
+        // 只认入队时用过的临时规则，审批期间新加的规则不会放行额外的命令。
+        temporary_rules
+            .iter()
+            .find(|rule_use| rule_matches(&rule_use.rule, command))
+            .cloned()
+    }) {
+        return (
+            CommandResponse::denied(request.id.clone(), denial.message),
+            None,
+        );
+    }
+
+    // intake 已把默认账号写入请求；审批期间策略可能已重载，执行前按当前策略再确认一次。
+    if let Some(user) = request.run_as.as_deref() {
+        if let Err(denial) = limits.run_as.resolve(Some(user)) {
+            return (
+                CommandResponse::denied(
+                    request.id.clone(),
+                    format!("denied by policy: {}", denial.message),
+                ),
+                None,
+            );
+        }
+    }
+
+    // cwd 缺失时 `cd` 会让命令以含糊的退出码失败；执行前先确认，按请求与策略决定是否创建。
+    let mut cwd_created = false;
+    if let Some(cwd) = request_cwd(request).filter(|_| !target.shell.is_windows()) {
+        let allow_create = request.create_cwd && limits.cwd.allows_create(&cwd);
+        match ensure_remote_cwd(target, &cwd, allow_create).await {
+            Ok(CwdState::Present) => {}
+            Ok(CwdState::Created) => cwd_created = true,
+            Ok(state) => {
+                return (
+                    CommandResponse::error(
+                        request.id.clone(),
+                        describe_cwd_state(&cwd, &target.name, state, request.create_cwd),
+                    ),
+                    None,
+                );
+            }
+            Err(err) => {
+                return (
+                    CommandResponse::error(
+                        request.id.clone(),
+                        format!("failed to check cwd {cwd} on {}: {err}", target.name),
+                    ),
+                    None,
+                );
             }
         }
     }
@@ -70,7 +148,8 @@ pub(super) async fn execute_request(
     let timeout_ms = requested_timeout_ms
         .unwrap_or(max_timeout_ms)
         .min(max_timeout_ms);
-    let timeout = Duration::from_millis(timeout_ms);
+    // detach 任务按设计会超过任何合理的超时，只能被显式终止。
+    let timeout = (!request.detach).then(|| Duration::from_millis(timeout_ms));
 
     let max_output_bytes = request
         .max_output_bytes
@@ -82,11 +161,13 @@ pub(super) async fn execute_request(
     let mut timed_out = false;
     let mut exec_fut: std::pin::Pin<
         Box<dyn Future<Output = anyhow::Result<ExecutionOutcome>> + Send>,
-    > = if let Some(manager) = pty_manager {
+    > = if let Some(manager) = pty_manager.filter(|_| !request.detach) {
         Box::pin(execute_pty_command(
             manager,
             request,
+            redaction,
             max_bytes,
+            spool_dir.filter(|_| limits.normalize_output),
             cancel.clone(),
             force_cancel.clone(),
         ))
@@ -94,43 +175,90 @@ pub(super) async fn execute_request(
         Box::pin(execute_ssh_command(
             target,
             request,
+            &limits.resources,
+            &limits.run_as,
+            sandbox,
+            redaction,
             max_bytes,
+            spool_dir,
             cancel.clone(),
             force_cancel.clone(),
-            target.tty,
+            target.tty && !request.detach,
         ))
     };
+    let deadline = async {
+        match timeout {
+            Some(timeout) => tokio::time::sleep(timeout).await,
+            None => std::future::pending().await,
+        }
+    };
     let outcome = tokio::select! {
         result = &mut exec_fut => result,
-        _ = tokio::time::sleep(timeout) => {
+        _ = deadline => {
             timed_out = true;
             cancel.cancel();
             exec_fut.await
         }
     };
+    let outcome = outcome.map(|outcome| {
+        if limits.normalize_output {
+            outcome.normalize_text()
+        } else {
+            outcome
+        }
+    });
 
+    let context = outcome
+        .as_ref()
+        .ok()
+        .and_then(|outcome| outcome.result().context.clone())
+        .map(|mut context| {
+            context.cwd_created = cwd_created;
+            context
+        });
     if timed_out {
-        return CommandResponse::error(request.id.clone(), "command timed out");
+        return (
+            CommandResponse::error(request.id.clone(), "command timed out"),
+            context,
+        );
     }
 
-    match outcome {
-        Ok(ExecutionOutcome::Completed(result)) => CommandResponse::completed(
-            request.id.clone(),
-            result.exit_code.unwrap_or(1),
-            result.stdout,
-            result.stderr,
-        ),
-        Ok(ExecutionOutcome::Cancelled(result)) => CommandResponse::cancelled(
-            request.id.clone(),
-            result.exit_code,
-            result.stdout,
-            result.stderr,
-        ),
+    let response = match outcome {
+        Ok(ExecutionOutcome::Completed(result)) => {
+            let mut response = CommandResponse::completed(
+                request.id.clone(),
+                result.exit_code.unwrap_or(1),
+                result.stdout.clone(),
+                result.stderr.clone(),
+            );
+            result.apply_to(&mut response);
+            response.error = describe_limit_exit(
+                response.exit_code,
+                response.stderr.as_deref().or(response.stdout.as_deref()),
+                &limits.resources,
+            );
+            response
+        }
+        Ok(ExecutionOutcome::Cancelled(result)) => {
+            let mut response = CommandResponse::cancelled(
+                request.id.clone(),
+                result.exit_code,
+                result.stdout.clone(),
+                result.stderr.clone(),
+            );
+            result.apply_to(&mut response);
+            response
+        }
         Err(err) => CommandResponse::error(request.id.clone(), err.to_string()),
-    }
+    };
+    (response, context)
 }
 
 pub(super) async fn force_kill_remote(target: &TargetSpec, request_id: &str) -> anyhow::Result<()> {
+    if target.shell.is_windows() {
+        // Windows 目标没有 pidfile；本地 ssh 被终止后 sshd 会回收会话 Job。
+        return Ok(());
+    }
     let ssh = target
         .ssh
         .as_ref()
@@ -140,7 +268,7 @@ pub(super) async fn force_kill_remote(target: &TargetSpec, request_id: &str) ->
         apply_askpass_env(&mut cmd, password)?;
     }
     cmd.arg("-T");
-    apply_ssh_options(&mut cmd, target.ssh_password.is_some());
+    apply_ssh_options(&mut cmd, target.ssh_password.is_some(), &target.host_key);
     if let Some(control_path) = resolve_control_path(target) {
         apply_control_master(&mut cmd, &control_path);
     }
@@ -157,10 +285,124 @@ pub(super) async fn force_kill_remote(target: &TargetSpec, request_id: &str) ->
     Ok(())
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CwdState {
+    Present,
+    Created,
+    Missing,
+    NotDirectory,
+}
+
+async fn ensure_remote_cwd(
+    target: &TargetSpec,
+    cwd: &str,
+    allow_create: bool,
+) -> anyhow::Result<CwdState> {
+    let ssh = target
+        .ssh
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("missing ssh target"))?;
+    let mut cmd = Command::new("ssh");
+    if let Some(password) = target.ssh_password.as_deref() {
+        apply_askpass_env(&mut cmd, password)?;
+    }
+    cmd.arg("-T");
+    apply_ssh_options(&mut cmd, target.ssh_password.is_some(), &target.host_key);
+    if let Some(control_path) = resolve_control_path(target) {
+        apply_control_master(&mut cmd, &control_path);
+    }
+    cmd.args(&target.ssh_args);
+    cmd.arg(ssh);
+    cmd.arg(build_cwd_check_command(cwd, allow_create));
+    cmd.stdin(Stdio::null());
+    cmd.kill_on_drop(true);
+    apply_process_group(&mut cmd);
+    let output = tokio::time::timeout(CWD_CHECK_TIMEOUT, cmd.output())
+        .await
+        .map_err(|_| anyhow::anyhow!("timed out after {}s", CWD_CHECK_TIMEOUT.as_secs()))?
+        .context("spawn cwd check ssh command")?;
+    parse_cwd_state(&output.stdout).ok_or_else(|| {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::anyhow!(
+            "unexpected output (exit {:?}): {}",
+            output.status.code(),
+            stderr.trim()
+        )
+    })
+}
+
+fn build_cwd_check_command(cwd: &str, allow_create: bool) -> String {
+    let dir = remote_path_word(cwd);
+    let create = if allow_create {
+        format!("elif mkdir -p -- {dir}; then echo created; ")
+    } else {
+        String::new()
+    };
+    let script = format!(
+        "if [ -d {dir} ]; then echo present; elif [ -e {dir} ]; then echo notdir; \
+{create}else echo missing; fi"
+    );
+    format!("bash --noprofile -c {}", shell_escape(&script))
+}
+
+fn parse_cwd_state(stdout: &[u8]) -> Option<CwdState> {
+    match String::from_utf8_lossy(stdout).lines().last()?.trim() {
+        "present" => Some(CwdState::Present),
+        "created" => Some(CwdState::Created),
+        "missing" => Some(CwdState::Missing),
+        "notdir" => Some(CwdState::NotDirectory),
+        _ => None,
+    }
+}
+
+fn describe_cwd_state(cwd: &str, target: &str, state: CwdState, create_requested: bool) -> String {
+    match state {
+        CwdState::NotDirectory => format!("cwd {cwd} on {target} is not a directory"),
+        _ if create_requested => format!(
+            "cwd {cwd} does not exist on {target}; creating it is not allowed by limits.allow_create_cwd / limits.create_cwd_prefixes"
+        ),
+        _ => format!("cwd {cwd} does not exist on {target}; pass create_cwd to create it"),
+    }
+}
+
+/// 远端路径转成 shell 单词：开头的 `~` 展开为 `$HOME`，其余部分仍按字面引用。
+fn remote_path_word(path: &str) -> String {
+    if path == "~" {
+        return "\"$HOME\"".to_string();
+    }
+    match path.strip_prefix("~/") {
+        Some(rest) if rest.is_empty() => "\"$HOME\"/".to_string(),
+        Some(rest) => format!("\"$HOME\"/{}", shell_escape(rest)),
+        None => shell_escape(path),
+    }
+}
+
 struct ExecutionResult {
     exit_code: Option<i32>,
     stdout: Option<String>,
     stderr: Option<String>,
+    stdout_base64: Option<String>,
+    stderr_base64: Option<String>,
+    stdout_ref: Option<OutputRef>,
+    stderr_ref: Option<OutputRef>,
+    context: Option<ExecutionContext>,
+}
+
+impl ExecutionResult {
+    fn apply_to(self, response: &mut CommandResponse) {
+        response.stdout_ref = self.stdout_ref;
+        response.stderr_ref = self.stderr_ref;
+        response.stdout_encoding = self
+            .stdout_base64
+            .as_ref()
+            .map(|_| BINARY_OUTPUT_ENCODING.to_string());
+        response.stderr_encoding = self
+            .stderr_base64
+            .as_ref()
+            .map(|_| BINARY_OUTPUT_ENCODING.to_string());
+        response.stdout_base64 = self.stdout_base64;
+        response.stderr_base64 = self.stderr_base64;
+    }
 }
 
 enum ExecutionOutcome {
@@ -168,16 +410,67 @@ enum ExecutionOutcome {
     Cancelled(ExecutionResult),
 }
 
+impl ExecutionOutcome {
+    fn result(&self) -> &ExecutionResult {
+        match self {
+            ExecutionOutcome::Completed(result) | ExecutionOutcome::Cancelled(result) => result,
+        }
+    }
+
+    fn result_mut(&mut self) -> &mut ExecutionResult {
+        match self {
+            ExecutionOutcome::Completed(result) | ExecutionOutcome::Cancelled(result) => result,
+        }
+    }
+
+    fn with_output_refs(
+        mut self,
+        stdout_ref: Option<OutputRef>,
+        stderr_ref: Option<OutputRef>,
+    ) -> Self {
+        let result = self.result_mut();
+        result.stdout_ref = stdout_ref;
+        result.stderr_ref = stderr_ref;
+        self
+    }
+
+    fn with_context(mut self, context: ExecutionContext) -> Self {
+        self.result_mut().context = Some(context);
+        self
+    }
+
+    // 只改文本输出；base64 原始字节与落盘文件保持原样。
+    fn normalize_text(mut self) -> Self {
+        let result = self.result_mut();
+        for text in [&mut result.stdout, &mut result.stderr] {
+            if let Some(value) = text.as_mut() {
+                *value = normalize_terminal_output(value);
+            }
+        }
+        self
+    }
+}
+
 pub(super) struct PtySessionManager {
     target: TargetSpec,
+    resources: ResourceLimits,
+    run_as: RunAsConfig,
+    sandbox: SandboxConfig,
     state: Mutex<PtySessionState>,
 }
 
 struct PtySessionState {
     session: Option<PtySession>,
+    /// 最近一次预热失败的原因，由执行方取走后标记为目标错误。
+    warmup_failure: Option<String>,
 }
 
 struct PtySession {
+    /// 会话建立时的 ssh 参数与本地环境变量（值未脱敏，记录时再处理）。
+    ssh_argv: Vec<String>,
+    local_env: Vec<(String, String)>,
+    control_path: Option<PathBuf>,
+    locale: Option<String>,
     writer: Box<dyn Write + Send>,
     reader_rx: mpsc::UnboundedReceiver<Vec<u8>>,
     buffer: Vec<u8>,
@@ -186,6 +479,7 @@ struct PtySession {
 }
 
 struct PtyCommandOutcome {
+    context: ExecutionContext,
     exit_code: Option<i32>,
     output: Vec<u8>,
     truncated: bool,
@@ -194,28 +488,67 @@ struct PtyCommandOutcome {
 }
 
 impl PtySessionManager {
-    pub(super) fn new(target: TargetSpec) -> Self {
+    pub(super) fn new(
+        target: TargetSpec,
+        resources: ResourceLimits,
+        run_as: RunAsConfig,
+        sandbox: SandboxConfig,
+    ) -> Self {
         Self {
             target,
-            state: Mutex::new(PtySessionState { session: None }),
+            resources,
+            run_as,
+            sandbox,
+            state: Mutex::new(PtySessionState {
+                session: None,
+                warmup_failure: None,
+            }),
         }
     }
 
+    pub(super) async fn take_warmup_failure(&self) -> Option<String> {
+        self.state.lock().await.warmup_failure.take()
+    }
+
     async fn run_command(
         &self,
         request: &CommandRequest,
+        redaction: &RedactionConfig,
         max_bytes: usize,
         cancel: CancellationToken,
         force_cancel: CancellationToken,
     ) -> anyhow::Result<PtyCommandOutcome> {
         let mut state = self.state.lock().await;
-        if state.session.is_none() {
-            state.session = Some(PtySession::spawn(&self.target)?);
+        let reusable = match state.session.as_mut() {
+            Some(session) => session.is_healthy().await,
+            None => false,
+        };
+        if !reusable {
+            if state.session.take().is_some() {
+                warn!(target = %self.target.name, "pty session failed health check, recreating");
+            }
+            match spawn_warm_session(&self.target).await {
+                Ok(session) => state.session = Some(session),
+                Err(err) => {
+                    let message = format!("pty session warm-up failed: {err:#}");
+                    state.warmup_failure = Some(message.clone());
+                    return Err(anyhow::anyhow!(message));
+                }
+            }
         }
         let result = match state.session.as_mut() {
             Some(session) => {
                 session
-                    .run_command(request, max_bytes, cancel, force_cancel)
+                    .run_command(
+                        request,
+                        &self.resources,
+                        &self.run_as,
+                        &self.sandbox,
+                        redaction,
+                        max_bytes,
+                        cancel,
+                        force_cancel,
+                    )
                     .await
             }
             None => Err(anyhow::anyhow!("pty session not available")),
@@ -235,10 +568,26 @@ impl PtySessionManager {
     }
 }
 
+async fn spawn_warm_session(target: &TargetSpec) -> anyhow::Result<PtySession> {
+    let mut session = PtySession::spawn(target)?;
+    if let Err(err) = session.warm_up(PTY_WARMUP_TIMEOUT).await {
+        if let Err(kill_err) = session.child.kill() {
+            warn!(error = %kill_err, "failed to kill pty child");
+        }
+        return Err(err);
+    }
+    Ok(session)
+}
+
 async fn execute_ssh_command(
     target: &TargetSpec,
     request: &CommandRequest,
+    resources: &ResourceLimits,
+    run_as: &RunAsConfig,
+    sandbox: &SandboxConfig,
+    redaction: &RedactionConfig,
     max_bytes: usize,
+    spool_dir: Option<&Path>,
     cancel: CancellationToken,
     force_cancel: CancellationToken,
     tty: bool,
@@ -248,7 +597,8 @@ async fn execute_ssh_command(
         .as_ref()
         .ok_or_else(|| anyhow::anyhow!("missing ssh target"))?;
     let locale = resolve_exec_locale(target);
-    let remote_cmd = build_remote_command(target, request);
+    let remote_cmd = build_remote_command(target, request, resources, run_as, sandbox);
+    let control_path = resolve_control_path(target);
     let mut cmd = Command::new("ssh");
     if let Some(password) = target.ssh_password.as_deref() {
         apply_askpass_env(&mut cmd, password)?;
@@ -258,13 +608,56 @@ async fn execute_ssh_command(
     } else {
         cmd.arg("-T");
     }
-    apply_ssh_options(&mut cmd, target.ssh_password.is_some());
-    if let Some(control_path) = resolve_control_path(target) {
-        apply_control_master(&mut cmd, &control_path);
+    apply_ssh_options(&mut cmd, target.ssh_password.is_some(), &target.host_key);
+    if let Some(control_path) = control_path.as_deref() {
+        apply_control_master(&mut cmd, control_path);
     }
     apply_locale_env(&mut cmd, locale.as_deref());
     cmd.args(&target.ssh_args);
     cmd.arg(ssh);
+    let context = {
+        let std_cmd = cmd.as_std();
+        let ssh_argv = std::iter::once(std_cmd.get_program())
+            .chain(std_cmd.get_args())
+            .map(|arg| arg.to_string_lossy().to_string())
+            .collect();
+        let local_env: Vec<(String, String)> = std_cmd
+            .get_envs()
+            .filter_map(|(key, value)| {
+                Some((
+                    key.to_string_lossy().to_string(),
+                    value?.to_string_lossy().to_string(),
+                ))
+            })
+            .collect();
+        ExecutionContext {
+            path: ExecutionPath::Ssh,
+            ssh_argv,
+            remote_command: build_remote_command(
+                target,
+                &redacted_request(request, redaction),
+                resources,
+                run_as,
+                sandbox,
+            ),
+            cwd: request_cwd(request),
+            env: redacted_request_env(request, redaction),
+            local_env: redaction.redact_env(
+                local_env
+                    .iter()
+                    .map(|(key, value)| (key.as_str(), value.as_str())),
+            ),
+            locale: locale.clone(),
+            control_path: control_path.as_ref().map(|path| path.display().to_string()),
+            tty,
+            run_as: request.run_as.clone(),
+            sandbox: sandbox
+                .wrapper_for(request)
+                .filter(|_| !target.shell.is_windows())
+                .map(<[String]>::to_vec),
+            cwd_created: false,
+        }
+    };
     cmd.arg(remote_cmd);
     cmd.stdin(Stdio::null());
     cmd.stdout(Stdio::piped());
@@ -275,8 +668,10 @@ async fn execute_ssh_command(
 
     let stdout = child.stdout.take().context("missing stdout")?;
     let stderr = child.stderr.take().context("missing stderr")?;
-    let stdout_task = tokio::spawn(read_stream_capture(stdout, max_bytes));
-    let stderr_task = tokio::spawn(read_stream_capture(stderr, max_bytes));
+    let stdout_spool = spool_dir.map(|dir| dir.join(format!("{}.stdout", request.id)));
+    let stderr_spool = spool_dir.map(|dir| dir.join(format!("{}.stderr", request.id)));
+    let stdout_task = tokio::spawn(read_stream_capture(stdout, max_bytes, stdout_spool));
+    let stderr_task = tokio::spawn(read_stream_capture(stderr, max_bytes, stderr_spool));
 
     let mut cancelled = false;
     let status = tokio::select! {
@@ -292,11 +687,11 @@ async fn execute_ssh_command(
     };
     let exit_code = status.and_then(|status| status.code());
 
-    let (stdout_bytes, stdout_truncated) = stdout_task
+    let (stdout_bytes, stdout_truncated, stdout_ref) = stdout_task
         .await
         .context("stdout task join")?
         .context("stdout read")?;
-    let (stderr_bytes, stderr_truncated) = stderr_task
+    let (stderr_bytes, stderr_truncated, stderr_ref) = stderr_task
         .await
         .context("stderr task join")?
         .context("stderr read")?;
@@ -309,19 +704,30 @@ async fn execute_ssh_command(
         stderr_truncated,
         cancelled,
         tty,
-    ))
+    )
+    .with_output_refs(stdout_ref, stderr_ref)
+    .with_context(context))
 }
 
 async fn execute_pty_command(
     manager: Arc<PtySessionManager>,
     request: &CommandRequest,
+    redaction: &RedactionConfig,
     max_bytes: usize,
+    spool_dir: Option<&Path>,
     cancel: CancellationToken,
     force_cancel: CancellationToken,
 ) -> anyhow::Result<ExecutionOutcome> {
     let outcome = manager
-        .run_command(request, max_bytes, cancel, force_cancel)
+        .run_command(request, redaction, max_bytes, cancel, force_cancel)
         .await?;
+    // PTY 输出没有流式落盘；要整理输出时先把原始字节写盘，保证原文可查。
+    let stdout_ref = match spool_dir.filter(|_| !outcome.output.is_empty()) {
+        Some(dir) => {
+            spool_output(dir.join(format!("{}.stdout", request.id)), &outcome.output).await
+        }
+        None => None,
+    };
     Ok(build_execution_outcome(
         outcome.exit_code,
         outcome.output,
@@ -330,10 +736,72 @@ async fn execute_pty_command(
         false,
         outcome.cancelled,
         true,
-    ))
+    )
+    .with_output_refs(stdout_ref, None)
+    .with_context(outcome.context))
 }
 
-fn build_remote_command(target: &TargetSpec, request: &CommandRequest) -> String {
+fn request_cwd(request: &CommandRequest) -> Option<String> {
+    request
+        .cwd
+        .as_deref()
+        .filter(|value| !value.trim().is_empty())
+        .map(str::to_string)
+}
+
+fn redacted_request_env(
+    request: &CommandRequest,
+    redaction: &RedactionConfig,
+) -> BTreeMap<String, String> {
+    request
+        .env
+        .as_ref()
+        .map(|env| {
+            redaction.redact_env(
+                env.iter()
+                    .map(|(key, value)| (key.as_str(), value.as_str())),
+            )
+        })
+        .unwrap_or_default()
+}
+
+// 远端命令里的环境变量前缀带着明文值，记录前先换成脱敏后的值再重新拼装。
+fn redacted_request(request: &CommandRequest, redaction: &RedactionConfig) -> CommandRequest {
+    let mut request = request.clone();
+    if let Some(env) = request.env.as_mut() {
+        for (key, value) in env.iter_mut() {
+            if redaction.is_secret(key) {
+                *value = REDACTED.to_string();
+            }
+        }
+    }
+    request
+}
+
+fn build_remote_command(
+    target: &TargetSpec,
+    request: &CommandRequest,
+    resources: &ResourceLimits,
+    run_as: &RunAsConfig,
+    sandbox: &SandboxConfig,
+) -> String {
+    if target.shell.is_windows() {
+        if *resources != ResourceLimits::default() {
+            warn!(
+                target = %target.name,
+                id = %request.id,
+                "resource limits are not applied on windows targets"
+            );
+        }
+        if sandbox.wrapper_for(request).is_some() {
+            warn!(
+                target = %target.name,
+                id = %request.id,
+                "sandbox wrapper is not applied on windows targets"
+            );
+        }
+        return build_windows_command(target.shell, request);
+    }
     let mut env_pairs: BTreeMap<String, String> = BTreeMap::new();
     if let Some(env) = request.env.as_ref() {
         for (key, value) in env {
@@ -357,7 +825,7 @@ fn build_remote_command(target: &TargetSpec, request: &CommandRequest) -> String
         .filter(|value| !value.trim().is_empty())
     {
         command.push_str("cd ");
-        command.push_str(&shell_escape(cwd));
+        command.push_str(&remote_path_word(cwd));
         command.push_str(" && ");
     }
     if !env_prefix.is_empty() {
@@ -365,14 +833,19 @@ fn build_remote_command(target: &TargetSpec, request: &CommandRequest) -> String
         command.push(' ');
     }
     command.push_str(request.raw_command.trim());
-    let command = wrap_command_with_pidfile(&command, &request.id);
+    let command = wrap_command_with_pidfile(&command, request, resources, run_as, sandbox);
     format!(
         "{shell_prefix}bash --noprofile -lc {}",
         shell_escape(&command)
     )
 }
 
-fn build_session_command(request: &CommandRequest) -> String {
+fn build_session_command(
+    request: &CommandRequest,
+    resources: &ResourceLimits,
+    run_as: &RunAsConfig,
+    sandbox: &SandboxConfig,
+) -> String {
     let mut env_pairs: BTreeMap<String, String> = BTreeMap::new();
     if let Some(env) = request.env.as_ref() {
         for (key, value) in env {
@@ -386,13 +859,13 @@ fn build_session_command(request: &CommandRequest) -> String {
         command.push(' ');
     }
     command.push_str(request.raw_command.trim());
-    let command = wrap_command_with_pidfile(&command, &request.id);
+    let command = wrap_command_with_pidfile(&command, request, resources, run_as, sandbox);
     if let Some(cwd) = request
         .cwd
         .as_deref()
         .filter(|value| !value.trim().is_empty())
     {
-        return format!("(cd {} && {})", shell_escape(cwd), command);
+        return format!("(cd {} && {})", remote_path_word(cwd), command);
     }
     command
 }
@@ -410,10 +883,31 @@ fn sanitize_request_id(value: &str) -> String {
         .collect()
 }
 
-fn wrap_command_with_pidfile(command: &str, request_id: &str) -> String {
-    let safe_id = sanitize_request_id(request_id);
+fn wrap_command_with_pidfile(
+    command: &str,
+    request: &CommandRequest,
+    resources: &ResourceLimits,
+    run_as: &RunAsConfig,
+    sandbox: &SandboxConfig,
+) -> String {
+    let safe_id = sanitize_request_id(&request.id);
     let pidfile = format!("$HOME/.octovalve/run/{safe_id}.pid");
-    let inner = format!("bash --noprofile -lc {}", shell_escape(command));
+    let command = format!("{}{command}", build_ulimit_prefix(resources));
+    let nice = resources
+        .nice
+        .map(|value| format!("nice -n {value} "))
+        .unwrap_or_default();
+    // pidfile 仍写在登录用户的 $HOME 下，setsid 建立的进程组包含 sudo/doas 及其子进程。
+    let run_as = request
+        .run_as
+        .as_deref()
+        .map(|user| run_as.command_prefix(user))
+        .unwrap_or_default();
+    let sandbox = sandbox.command_prefix(request);
+    let inner = format!(
+        "{nice}{run_as}{sandbox}bash --noprofile -lc {}",
+        shell_escape(&command)
+    );
     format!(
         "mkdir -p \"$HOME/.octovalve/run\"; pidfile=\"{pidfile}\"; rm -f \"$pidfile\"; \
 setsid {inner} & pid=$!; echo $pid > \"$pidfile\"; wait $pid; status=$?; \
@@ -421,15 +915,96 @@ rm -f \"$pidfile\"; exit $status"
     )
 }
 
-fn build_pty_command(id: u64, request: &CommandRequest) -> String {
+// ulimit/umask 只作用于内层 bash 及其子进程，不会影响 PTY 常驻会话本身。
+fn build_ulimit_prefix(resources: &ResourceLimits) -> String {
+    let mut prefix = String::new();
+    if let Some(secs) = resources.max_cpu_secs {
+        prefix.push_str(&format!("ulimit -t {secs} || exit 1; "));
+    }
+    if let Some(bytes) = resources.max_memory_bytes {
+        let kib = bytes.div_ceil(1024).max(1);
+        prefix.push_str(&format!("ulimit -v {kib} || exit 1; "));
+    }
+    if let Some(bytes) = resources.max_file_size_bytes {
+        let blocks = bytes.div_ceil(1024).max(1);
+        prefix.push_str(&format!("ulimit -f {blocks} || exit 1; "));
+    }
+    if let Some(umask) = resources.umask {
+        prefix.push_str(&format!("umask {umask:04o} || exit 1; "));
+    }
+    prefix
+}
+
+fn describe_limit_exit(
+    exit_code: Option<i32>,
+    output: Option<&str>,
+    resources: &ResourceLimits,
+) -> Option<String> {
+    let code = exit_code.filter(|code| *code != 0)?;
+    if let Some(secs) = resources.max_cpu_secs.filter(|_| code == SIGXCPU_EXIT_CODE) {
+        return Some(format!("cpu time limit exceeded (max_cpu_secs={secs})"));
+    }
+    if let Some(bytes) = resources
+        .max_file_size_bytes
+        .filter(|_| code == SIGXFSZ_EXIT_CODE)
+    {
+        return Some(format!(
+            "file size limit exceeded (max_file_size_bytes={bytes})"
+        ));
+    }
+    let bytes = resources.max_memory_bytes?;
+    let output = output?.to_ascii_lowercase();
+    if MEMORY_ERROR_PATTERNS
+        .iter()
+        .any(|pattern| output.contains(pattern))
+    {
+        return Some(format!(
+            "memory limit likely exceeded (max_memory_bytes={bytes})"
+        ));
+    }
+    None
+}
+
+fn build_pty_command(
+    id: u64,
+    request: &CommandRequest,
+    resources: &ResourceLimits,
+    run_as: &RunAsConfig,
+    sandbox: &SandboxConfig,
+) -> String {
     let begin_marker = format!("{PTY_MARKER_BEGIN_PREFIX}{id}__");
     let end_prefix = format!("{PTY_MARKER_END_PREFIX}{id}__");
-    let command = build_session_command(request);
+    let command = build_session_command(request, resources, run_as, sandbox);
     format!(
         "printf '%s\\n' '{begin_marker}'; {command}; status=$?; printf '%s%d__\\n' '{end_prefix}' \"$status\""
     )
 }
 
+// 标记分两段输出，回显出来的命令行本身不会被当成就绪标记。
+fn build_pty_probe(id: u64) -> String {
+    format!("printf '%s%s\\n' '{PTY_MARKER_READY_PREFIX}' '{id}__'")
+}
+
+fn pty_ready_marker(id: u64) -> Vec<u8> {
+    format!("{PTY_MARKER_READY_PREFIX}{id}__").into_bytes()
+}
+
+/// 找到就绪标记时丢弃它及之前的全部输出（连同行尾）并返回 true。
+fn consume_ready_marker(buffer: &mut Vec<u8>, marker: &[u8]) -> bool {
+    let Some(pos) = find_subsequence(buffer, marker) else {
+        return false;
+    };
+    let mut drain_len = pos + marker.len();
+    if buffer.get(drain_len) == Some(&b'\r') {
+        drain_len += 1;
+    }
+    if buffer.get(drain_len) == Some(&b'\n') {
+        drain_len += 1;
+    }
+    buffer.drain(..drain_len);
+    true
+}
+
 fn pty_begin_marker(id: u64) -> Vec<u8> {
     format!("{PTY_MARKER_BEGIN_PREFIX}{id}__\n").into_bytes()
 }
@@ -482,24 +1057,29 @@ fn build_execution_outcome(
     cancelled: bool,
     tty: bool,
 ) -> ExecutionOutcome {
-    let (stdout, stderr) = if tty {
+    let ((stdout, stdout_base64), (stderr, stderr_base64)) = if tty {
         let merged = merge_pty_output(
             stdout_bytes,
             stdout_truncated,
             stderr_bytes,
             stderr_truncated,
         );
-        (merged, None)
+        (merged, (None, None))
     } else {
         (
-            format_output(&stdout_bytes, stdout_truncated),
-            format_output(&stderr_bytes, stderr_truncated),
+            capture_output(&stdout_bytes, stdout_truncated),
+            capture_output(&stderr_bytes, stderr_truncated),
         )
     };
     let result = ExecutionResult {
         exit_code,
         stdout,
         stderr,
+        stdout_base64,
+        stderr_base64,
+        stdout_ref: None,
+        stderr_ref: None,
+        context: None,
     };
     if cancelled {
         ExecutionOutcome::Cancelled(result)
@@ -513,9 +1093,9 @@ fn merge_pty_output(
     stdout_truncated: bool,
     stderr_bytes: Vec<u8>,
     stderr_truncated: bool,
-) -> Option<String> {
+) -> (Option<String>, Option<String>) {
     if stdout_bytes.is_empty() && stderr_bytes.is_empty() {
-        return None;
+        return (None, None);
     }
     let mut merged = stdout_bytes;
     if !stderr_bytes.is_empty() {
@@ -526,7 +1106,24 @@ fn merge_pty_output(
         }
         merged.extend_from_slice(&stderr_bytes);
     }
-    format_output(&merged, stdout_truncated || stderr_truncated)
+    capture_output(&merged, stdout_truncated || stderr_truncated)
+}
+
+/// 返回 (有损文本, 原始字节的 base64)；只有输出不是合法 UTF-8 时才附带 base64。
+fn capture_output(bytes: &[u8], truncated: bool) -> (Option<String>, Option<String>) {
+    (
+        format_output(bytes, truncated),
+        raw_output_base64(bytes, truncated),
+    )
+}
+
+fn raw_output_base64(bytes: &[u8], truncated: bool) -> Option<String> {
+    match std::str::from_utf8(bytes) {
+        Ok(_) => None,
+        // 截断可能切在多字节字符中间，末尾不完整的序列不算二进制输出。
+        Err(err) if truncated && err.error_len().is_none() => None,
+        Err(_) => Some(BASE64_ENGINE.encode(bytes)),
+    }
 }
 
 fn format_output(bytes: &[u8], truncated: bool) -> Option<String> {
@@ -546,24 +1143,27 @@ impl PtySession {
             .ssh
             .as_ref()
             .ok_or_else(|| anyhow::anyhow!("missing ssh target"))?;
-        let pair = native_pty_system().openpty(PtySize {
-            rows: DEFAULT_PTY_ROWS,
-            cols: DEFAULT_PTY_COLS,
-            pixel_width: 0,
-            pixel_height: 0,
-        })?;
         let mut cmd = CommandBuilder::new("ssh");
+        let mut local_env = Vec::new();
         if let Some(password) = target.ssh_password.as_deref() {
             for (key, value) in system_utils::ssh::askpass_env(password)? {
-                cmd.env(key, value);
+                cmd.env(&key, &value);
+                local_env.push((key, value));
             }
         }
         cmd.arg("-tt");
-        apply_ssh_options(&mut cmd, target.ssh_password.is_some());
-        if let Some(control_path) = resolve_control_path(target) {
-            apply_control_master_builder(&mut cmd, &control_path);
+        apply_ssh_options(&mut cmd, target.ssh_password.is_some(), &target.host_key);
+        let control_path = resolve_control_path(target);
+        if let Some(control_path) = control_path.as_deref() {
+            apply_control_master_builder(&mut cmd, control_path);
+        }
+        let locale = resolve_exec_locale(target);
+        apply_locale_env_builder(&mut cmd, locale.as_deref());
+        if let Some(locale) = locale.as_deref() {
+            for key in ["LANG", "LC_CTYPE", "LC_ALL"] {
+                local_env.push((key.to_string(), locale.to_string()));
+            }
         }
-        apply_locale_env_builder(&mut cmd, resolve_exec_locale(target).as_deref());
         for arg in &target.ssh_args {
             cmd.arg(arg);
         }
@@ -571,7 +1171,28 @@ impl PtySession {
         cmd.arg("bash");
         cmd.arg("--noprofile");
         cmd.arg("--norc");
+        Self::start(cmd, local_env, control_path, locale)
+    }
+
+    fn start(
+        mut cmd: CommandBuilder,
+        mut local_env: Vec<(String, String)>,
+        control_path: Option<PathBuf>,
+        locale: Option<String>,
+    ) -> anyhow::Result<Self> {
+        let pair = native_pty_system().openpty(PtySize {
+            rows: DEFAULT_PTY_ROWS,
+            cols: DEFAULT_PTY_COLS,
+            pixel_width: 0,
+            pixel_height: 0,
+        })?;
         cmd.env("TERM", DEFAULT_PTY_TERM);
+        local_env.push(("TERM".to_string(), DEFAULT_PTY_TERM.to_string()));
+        let ssh_argv = cmd
+            .get_argv()
+            .iter()
+            .map(|arg| arg.to_string_lossy().to_string())
+            .collect();
         let child = pair
             .slave
             .spawn_command(cmd)
@@ -580,33 +1201,75 @@ impl PtySession {
         let (reader_tx, reader_rx) = mpsc::unbounded_channel();
         std::thread::spawn(move || read_pty_loop(reader, reader_tx));
         let writer = pair.master.take_writer().context("take pty writer")?;
-        let mut session = Self {
+        Ok(Self {
+            ssh_argv,
+            local_env,
+            control_path,
+            locale,
             writer,
             reader_rx,
             buffer: Vec::new(),
             next_id: 1,
             child,
-        };
-        session.initialize()?;
-        Ok(session)
+        })
     }
 
-    fn initialize(&mut self) -> anyhow::Result<()> {
+    /// 关闭提示符与回显后等待首个就绪标记，登录横幅等早期输出随之丢弃，不会混进第一条命令。
+    async fn warm_up(&mut self, timeout: Duration) -> anyhow::Result<()> {
         self.write_line("export PS1=")?;
         self.write_line("stty -echo")?;
-        Ok(())
+        self.probe(timeout).await
+    }
+
+    /// 复用缓存会话前确认 shell 仍能响应；顺带清掉上一条命令之后的残留输出。
+    async fn is_healthy(&mut self) -> bool {
+        if !matches!(self.child.try_wait(), Ok(None)) {
+            return false;
+        }
+        while let Ok(chunk) = self.reader_rx.try_recv() {
+            self.buffer.extend(chunk);
+        }
+        self.buffer.clear();
+        self.probe(PTY_HEALTH_TIMEOUT).await.is_ok()
+    }
+
+    async fn probe(&mut self, timeout: Duration) -> anyhow::Result<()> {
+        let id = self.next_id;
+        self.next_id = self.next_id.saturating_add(1);
+        let marker = pty_ready_marker(id);
+        self.write_line(&build_pty_probe(id))?;
+        let wait = async {
+            while !consume_ready_marker(&mut self.buffer, &marker) {
+                match self.reader_rx.recv().await {
+                    Some(chunk) => self.buffer.extend(chunk),
+                    None => anyhow::bail!("pty session closed before becoming ready"),
+                }
+            }
+            Ok(())
+        };
+        tokio::time::timeout(timeout, wait).await.map_err(|_| {
+            anyhow::anyhow!(
+                "pty session did not respond within {}s",
+                timeout.as_secs_f32()
+            )
+        })?
     }
 
     async fn run_command(
         &mut self,
         request: &CommandRequest,
+        resources: &ResourceLimits,
+        run_as: &RunAsConfig,
+        sandbox: &SandboxConfig,
+        redaction: &RedactionConfig,
         max_bytes: usize,
         cancel: CancellationToken,
         force_cancel: CancellationToken,
     ) -> anyhow::Result<PtyCommandOutcome> {
         let id = self.next_id;
         self.next_id = self.next_id.saturating_add(1);
-        let command = build_pty_command(id, request);
+        let command = build_pty_command(id, request, resources, run_as, sandbox);
+        let context = self.execution_context(id, request, resources, run_as, sandbox, redaction);
         let begin_marker = pty_begin_marker(id);
         let end_prefix = pty_end_prefix(id);
         self.write_line(&command)?;
@@ -627,6 +1290,7 @@ impl PtySession {
                 &mut seen_begin,
             ) {
                 return Ok(PtyCommandOutcome {
+                    context,
                     exit_code,
                     output,
                     truncated,
@@ -641,6 +1305,7 @@ impl PtySession {
                         tracing::warn!(error = %err, "failed to kill pty child");
                     }
                     return Ok(PtyCommandOutcome {
+                        context,
                         exit_code: None,
                         output,
                         truncated,
@@ -658,6 +1323,7 @@ impl PtySession {
                             Some(chunk) => self.buffer.extend(chunk),
                             None => {
                                 return Ok(PtyCommandOutcome {
+                                    context,
                                     exit_code: None,
                                     output,
                                     truncated,
@@ -672,6 +1338,7 @@ impl PtySession {
                             tracing::warn!(error = %err, "failed to kill pty child");
                         }
                         return Ok(PtyCommandOutcome {
+                            context,
                             exit_code: None,
                             output,
                             truncated,
@@ -681,6 +1348,7 @@ impl PtySession {
                     }
                     _ = tokio::time::sleep(remaining) => {
                         return Ok(PtyCommandOutcome {
+                            context,
                             exit_code: None,
                             output,
                             truncated,
@@ -696,6 +1364,7 @@ impl PtySession {
                             Some(chunk) => self.buffer.extend(chunk),
                             None => {
                                 return Ok(PtyCommandOutcome {
+                                    context,
                                     exit_code: None,
                                     output,
                                     truncated,
@@ -718,6 +1387,7 @@ impl PtySession {
                             tracing::warn!(error = %err, "failed to kill pty child");
                         }
                         return Ok(PtyCommandOutcome {
+                            context,
                             exit_code: None,
                             output,
                             truncated,
@@ -730,6 +1400,44 @@ impl PtySession {
         }
     }
 
+    fn execution_context(
+        &self,
+        id: u64,
+        request: &CommandRequest,
+        resources: &ResourceLimits,
+        run_as: &RunAsConfig,
+        sandbox: &SandboxConfig,
+        redaction: &RedactionConfig,
+    ) -> ExecutionContext {
+        ExecutionContext {
+            path: ExecutionPath::Pty,
+            ssh_argv: self.ssh_argv.clone(),
+            remote_command: build_pty_command(
+                id,
+                &redacted_request(request, redaction),
+                resources,
+                run_as,
+                sandbox,
+            ),
+            cwd: request_cwd(request),
+            env: redacted_request_env(request, redaction),
+            local_env: redaction.redact_env(
+                self.local_env
+                    .iter()
+                    .map(|(key, value)| (key.as_str(), value.as_str())),
+            ),
+            locale: self.locale.clone(),
+            control_path: self
+                .control_path
+                .as_ref()
+                .map(|path| path.display().to_string()),
+            tty: true,
+            run_as: request.run_as.clone(),
+            sandbox: sandbox.wrapper_for(request).map(<[String]>::to_vec),
+            cwd_created: false,
+        }
+    }
+
     fn write_line(&mut self, line: &str) -> anyhow::Result<()> {
         self.writer
             .write_all(line.as_bytes())
@@ -922,6 +1630,8 @@ fn find_subsequence(haystack: &[u8], needle: &[u8]) -> Option<usize> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::shell_utils::HostKeyCheck;
+    use protocol::config::TargetShell;
 
     fn env_lock() -> &'static std::sync::Mutex<()> {
         static LOCK: std::sync::OnceLock<std::sync::Mutex<()>> = std::sync::OnceLock::new();
@@ -937,6 +1647,10 @@ mod tests {
             ssh_password: None,
             terminal_locale: Some("en_US.UTF-8".to_string()),
             tty: false,
+            shell: TargetShell::Bash,
+            extra_forwards: Vec::new(),
+            host_key: HostKeyCheck::default(),
+            banner: None,
         }
     }
 
@@ -946,13 +1660,10 @@ mod tests {
             client: "client".to_string(),
             target: "dev".to_string(),
             intent: "intent".to_string(),
-            mode: protocol::CommandMode::Shell,
             raw_command: "echo hello".to_string(),
             cwd: Some("/tmp/work dir".to_string()),
             env: Some(BTreeMap::from([("FOO".to_string(), "bar baz".to_string())])),
-            timeout_ms: None,
-            max_output_bytes: None,
-            pipeline: Vec::new(),
+            ..Default::default()
         }
     }
 
@@ -967,7 +1678,13 @@ mod tests {
     fn build_remote_command_includes_env_and_cwd() {
         let target = sample_target();
         let request = sample_request();
-        let cmd = build_remote_command(&target, &request);
+        let cmd = build_remote_command(
+            &target,
+            &request,
+            &ResourceLimits::default(),
+            &RunAsConfig::default(),
+            &SandboxConfig::default(),
+        );
         assert!(cmd.contains("bash --noprofile -lc "));
         assert!(cmd.contains("cd "));
         assert!(cmd.contains("/tmp/work dir"));
@@ -978,24 +1695,217 @@ mod tests {
         assert!(cmd.contains("echo hello"));
     }
 
+    #[test]
+    fn recorded_remote_command_redacts_secret_env() {
+        let target = sample_target();
+        let mut request = sample_request();
+        request
+            .env
+            .as_mut()
+            .unwrap()
+            .insert("API_TOKEN".to_string(), "s3cr3t".to_string());
+        let redaction = RedactionConfig::default();
+        let recorded = build_remote_command(
+            &target,
+            &redacted_request(&request, &redaction),
+            &ResourceLimits::default(),
+            &RunAsConfig::default(),
+            &SandboxConfig::default(),
+        );
+        assert!(!recorded.contains("s3cr3t"));
+        assert!(recorded.contains("bar baz"));
+        let env = redacted_request_env(&request, &redaction);
+        assert_eq!(env["API_TOKEN"], REDACTED);
+        assert_eq!(env["FOO"], "bar baz");
+        assert!(build_remote_command(
+            &target,
+            &request,
+            &ResourceLimits::default(),
+            &RunAsConfig::default(),
+            &SandboxConfig::default()
+        )
+        .contains("s3cr3t"));
+    }
+
+    #[test]
+    fn build_remote_command_uses_cmd_for_windows_targets() {
+        let mut target = sample_target();
+        target.shell = TargetShell::Cmd;
+        let cmd = build_remote_command(
+            &target,
+            &sample_request(),
+            &ResourceLimits::default(),
+            &RunAsConfig::default(),
+            &SandboxConfig::default(),
+        );
+        assert!(cmd.starts_with("cmd /S /C "));
+        assert!(!cmd.contains("bash"));
+        assert!(!cmd.contains("setsid"));
+    }
+
     #[test]
     fn build_session_command_wraps_cwd() {
         let request = sample_request();
-        let cmd = build_session_command(&request);
+        let cmd = build_session_command(
+            &request,
+            &ResourceLimits::default(),
+            &RunAsConfig::default(),
+            &SandboxConfig::default(),
+        );
         assert!(cmd.starts_with("(cd "));
         assert!(cmd.contains("&&"));
         assert!(cmd.contains("echo hello"));
     }
 
+    #[test]
+    fn cwd_tilde_expands_to_remote_home() {
+        assert_eq!(remote_path_word("~"), "\"$HOME\"");
+        assert_eq!(remote_path_word("~/my dir"), "\"$HOME\"/'my dir'");
+        assert_eq!(remote_path_word("/srv/~x"), "'/srv/~x'");
+        let mut request = sample_request();
+        request.cwd = Some("~/work".to_string());
+        let cmd = build_session_command(
+            &request,
+            &ResourceLimits::default(),
+            &RunAsConfig::default(),
+            &SandboxConfig::default(),
+        );
+        assert!(cmd.starts_with("(cd \"$HOME\"/'work' && "));
+    }
+
+    #[test]
+    fn cwd_check_creates_only_when_allowed() {
+        let check = build_cwd_check_command("/tmp/work dir", false);
+        assert!(check.contains("[ -d '\"'\"'/tmp/work dir'\"'\"' ]"));
+        assert!(!check.contains("mkdir"));
+        assert!(build_cwd_check_command("/tmp/x", true).contains("mkdir -p -- "));
+
+        assert_eq!(parse_cwd_state(b"motd\ncreated\n"), Some(CwdState::Created));
+        assert_eq!(parse_cwd_state(b"notdir\n"), Some(CwdState::NotDirectory));
+        assert_eq!(parse_cwd_state(b""), None);
+        assert!(describe_cwd_state("/x", "dev", CwdState::Missing, true)
+            .contains("limits.allow_create_cwd"));
+        assert!(describe_cwd_state("/x", "dev", CwdState::Missing, false).contains("create_cwd"));
+    }
+
     #[test]
     fn build_pty_command_adds_markers() {
         let request = sample_request();
-        let cmd = build_pty_command(7, &request);
+        let cmd = build_pty_command(
+            7,
+            &request,
+            &ResourceLimits::default(),
+            &RunAsConfig::default(),
+            &SandboxConfig::default(),
+        );
         assert!(cmd.contains(PTY_MARKER_BEGIN_PREFIX));
         assert!(cmd.contains(PTY_MARKER_END_PREFIX));
         assert!(cmd.contains("status=$?"));
     }
 
+    #[test]
+    fn build_remote_command_applies_resource_limits() {
+        let target = sample_target();
+        let request = sample_request();
+        let resources = ResourceLimits {
+            nice: Some(10),
+            max_cpu_secs: Some(60),
+            max_memory_bytes: Some(512 * 1024 * 1024),
+            max_file_size_bytes: Some(1500),
+            umask: Some(0o027),
+        };
+        let cmd = build_remote_command(
+            &target,
+            &request,
+            &resources,
+            &RunAsConfig::default(),
+            &SandboxConfig::default(),
+        );
+        assert!(cmd.contains("nice -n 10 bash"));
+        assert!(cmd.contains("ulimit -t 60"));
+        assert!(cmd.contains("ulimit -v 524288"));
+        assert!(cmd.contains("ulimit -f 2"));
+        assert!(cmd.contains("umask 0027"));
+    }
+
+    #[test]
+    fn run_as_wraps_inner_shell() {
+        let mut request = sample_request();
+        request.run_as = Some("svc".to_string());
+        let run_as = RunAsConfig {
+            run_as: Some("svc".to_string()),
+            ..RunAsConfig::default()
+        };
+        let resources = ResourceLimits {
+            nice: Some(5),
+            ..ResourceLimits::default()
+        };
+        let sandbox = SandboxConfig::default();
+        let cmd = build_session_command(&request, &resources, &run_as, &sandbox);
+        assert!(cmd.contains("setsid nice -n 5 sudo -n -u 'svc' -- bash --noprofile -lc "));
+        request.run_as = None;
+        assert!(!build_session_command(&request, &resources, &run_as, &sandbox).contains("sudo"));
+    }
+
+    #[test]
+    fn sandbox_wraps_inner_shell_unless_exempt() {
+        let mut request = sample_request();
+        request.run_as = Some("svc".to_string());
+        request.pipeline = vec![protocol::CommandStage {
+            argv: vec!["echo".to_string(), "hello".to_string()],
+        }];
+        let run_as = RunAsConfig {
+            run_as: Some("svc".to_string()),
+            ..RunAsConfig::default()
+        };
+        let sandbox = SandboxConfig {
+            enabled: true,
+            wrapper: vec!["/usr/bin/env".to_string(), "SANDBOXED=1".to_string()],
+            exempt_commands: vec!["systemctl".to_string()],
+        };
+        let cmd = build_session_command(&request, &ResourceLimits::default(), &run_as, &sandbox);
+        assert!(cmd.contains(
+            "setsid sudo -n -u 'svc' -- '/usr/bin/env' 'SANDBOXED=1' bash --noprofile -lc "
+        ));
+
+        request.pipeline = vec![protocol::CommandStage {
+            argv: vec!["/usr/bin/systemctl".to_string(), "status".to_string()],
+        }];
+        let cmd = build_session_command(&request, &ResourceLimits::default(), &run_as, &sandbox);
+        assert!(!cmd.contains("/usr/bin/env"));
+        assert!(sandbox.wrapper_for(&request).is_none());
+
+        let disabled = SandboxConfig {
+            enabled: false,
+            ..sandbox.clone()
+        };
+        request.pipeline.clear();
+        assert!(disabled.wrapper_for(&request).is_none());
+        assert_eq!(
+            sandbox.wrapper_for(&request).map(<[String]>::to_vec),
+            Some(sandbox.wrapper.clone())
+        );
+    }
+
+    #[test]
+    fn describe_limit_exit_reports_hit_limit() {
+        let resources = ResourceLimits {
+            nice: None,
+            max_cpu_secs: Some(5),
+            max_memory_bytes: Some(1024),
+            max_file_size_bytes: None,
+            umask: None,
+        };
+        let cpu = describe_limit_exit(Some(SIGXCPU_EXIT_CODE), None, &resources).unwrap();
+        assert!(cpu.contains("max_cpu_secs=5"));
+        assert!(describe_limit_exit(Some(SIGXFSZ_EXIT_CODE), None, &resources).is_none());
+        let memory =
+            describe_limit_exit(Some(1), Some("tar: Cannot allocate memory"), &resources).unwrap();
+        assert!(memory.contains("max_memory_bytes=1024"));
+        assert!(describe_limit_exit(Some(0), None, &resources).is_none());
+        assert!(describe_limit_exit(Some(2), Some("no such file"), &resources).is_none());
+    }
+
     #[test]
     fn extract_pty_output_collects_output() {
         let begin = pty_begin_marker(1);
@@ -1034,6 +1944,41 @@ mod tests {
         assert!(buffer.is_empty());
     }
 
+    #[test]
+    fn ready_marker_ignores_echoed_probe() {
+        let marker = pty_ready_marker(3);
+        let mut buffer = b"Last login: yesterday\r\n".to_vec();
+        buffer.extend_from_slice(build_pty_probe(3).as_bytes());
+        buffer.extend_from_slice(b"\r\n");
+        assert!(!consume_ready_marker(&mut buffer, &marker));
+        buffer.extend_from_slice(&marker);
+        buffer.extend_from_slice(b"\r\nnext");
+        assert!(consume_ready_marker(&mut buffer, &marker));
+        assert_eq!(buffer, b"next");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn pty_warm_up_discards_banner_and_detects_dead_session() {
+        let mut cmd = CommandBuilder::new("bash");
+        cmd.args([
+            "--noprofile",
+            "--norc",
+            "-c",
+            "echo 'Welcome to dev'; exec bash --noprofile --norc",
+        ]);
+        let mut session = PtySession::start(cmd, Vec::new(), None, None).expect("spawn pty");
+        session
+            .warm_up(Duration::from_secs(10))
+            .await
+            .expect("warm up");
+        assert!(!String::from_utf8_lossy(&session.buffer).contains("Welcome"));
+        assert!(session.is_healthy().await);
+
+        session.child.kill().expect("kill");
+        assert!(!session.is_healthy().await);
+    }
+
     #[test]
     fn control_path_is_stable_per_target() {
         let target = sample_target();
@@ -1059,7 +2004,13 @@ mod tests {
     fn build_remote_command_disables_profiles() {
         let target = sample_target();
         let request = sample_request();
-        let cmd = build_remote_command(&target, &request);
+        let cmd = build_remote_command(
+            &target,
+            &request,
+            &ResourceLimits::default(),
+            &RunAsConfig::default(),
+            &SandboxConfig::default(),
+        );
         assert!(cmd.contains("bash --noprofile -lc "));
     }
 
@@ -1079,6 +2030,22 @@ mod tests {
         assert!(out.contains("[output truncated]"));
     }
 
+    #[test]
+    fn invalid_utf8_output_carries_base64() {
+        let (text, raw) = capture_output(b"ok", false);
+        assert_eq!(text.as_deref(), Some("ok"));
+        assert!(raw.is_none());
+
+        let gbk = [0xc4, 0xe3, 0xba, 0xc3];
+        let (text, raw) = capture_output(&gbk, false);
+        assert!(text.expect("text").contains('\u{fffd}'));
+        assert_eq!(raw.as_deref(), Some(BASE64_ENGINE.encode(gbk).as_str()));
+
+        let cut = "你好".as_bytes();
+        assert!(raw_output_base64(&cut[..4], true).is_none());
+        assert!(raw_output_base64(&cut[..4], false).is_some());
+    }
+
     #[test]
     fn pty_merges_stderr_into_stdout() {
         let outcome = build_execution_outcome(
@@ -1102,6 +2069,20 @@ mod tests {
         }
     }
 
+    #[test]
+    fn normalize_text_keeps_raw_bytes() {
+        let raw = b"\x1b[32m 10%\r100%\x1b[0m\n\xff".to_vec();
+        let outcome =
+            build_execution_outcome(Some(0), raw.clone(), false, Vec::new(), false, false, false)
+                .normalize_text();
+        let result = outcome.result();
+        assert_eq!(result.stdout.as_deref(), Some("100%\n\u{fffd}"));
+        assert_eq!(
+            result.stdout_base64.as_deref(),
+            Some(BASE64_ENGINE.encode(raw).as_str())
+        );
+    }
+
     #[test]
     fn resolve_exec_locale_prefers_target() {
         let _guard = env_lock().lock().unwrap();
@@ -1113,6 +2094,10 @@ mod tests {
             ssh_password: None,
             terminal_locale: Some("en_US.utf8".to_string()),
             tty: false,
+            shell: TargetShell::Bash,
+            extra_forwards: Vec::new(),
+            host_key: HostKeyCheck::default(),
+            banner: None,
         };
         let backup = std::env::var("OCTOVALVE_TERMINAL_LOCALE").ok();
         std::env::set_var("OCTOVALVE_TERMINAL_LOCALE", "zh_CN.utf8");
@@ -1136,6 +2121,10 @@ mod tests {
             ssh_password: None,
             terminal_locale: None,
             tty: false,
+            shell: TargetShell::Bash,
+            extra_forwards: Vec::new(),
+            host_key: HostKeyCheck::default(),
+            banner: None,
         };
         let backup = std::env::var("OCTOVALVE_TERMINAL_LOCALE").ok();
         std::env::set_var("OCTOVALVE_TERMINAL_LOCALE", "zh_CN.utf8");
@@ -1159,6 +2148,10 @@ mod tests {
             ssh_password: None,
             terminal_locale: None,
             tty: false,
+            shell: TargetShell::Bash,
+            extra_forwards: Vec::new(),
+            host_key: HostKeyCheck::default(),
+            banner: None,
         };
         let backup = std::env::var("OCTOVALVE_APP_LANGUAGE").ok();
         std::env::set_var("OCTOVALVE_APP_LANGUAGE", "zh-CN");
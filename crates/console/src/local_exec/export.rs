@@ -0,0 +1,311 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+
+use bytes::Bytes;
+use futures_util::stream::{self, Stream, StreamExt};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use super::history::collect_result_files;
+
+const CSV_HEADER: &str = concat!(
+    "id,client,intent,command,status,exit_code,queued_for_secs,duration_ms,finished_at,",
+    "approved_by,denied_by,decision_latency_ms,source\n"
+);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ExportFormat {
+    Csv,
+    Jsonl,
+}
+
+impl ExportFormat {
+    pub(crate) fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Jsonl => "application/x-ndjson",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct ExportRange {
+    pub(crate) from_ms: Option<u64>,
+    pub(crate) to_ms: Option<u64>,
+}
+
+impl ExportRange {
+    fn contains(&self, finished_at_ms: u64) -> bool {
+        self.from_ms
+            .map(|from| finished_at_ms >= from)
+            .unwrap_or(true)
+            && self.to_ms.map(|to| finished_at_ms <= to).unwrap_or(true)
+    }
+}
+
+// 逐条读取落盘的 request/result 记录并输出，避免把整个审计目录读入内存。
+pub(crate) fn export_history_stream(
+    output_dir: PathBuf,
+    range: ExportRange,
+    format: ExportFormat,
+) -> impl Stream<Item = std::io::Result<Bytes>> + Send + 'static {
+    // 扫描审计目录是同步 IO，放到 blocking 线程里，不占用处理请求的 runtime 线程。
+    let scan_dir = output_dir.clone();
+    let files = stream::once(async move {
+        tokio::task::spawn_blocking(move || {
+            let mut files = collect_result_files(&scan_dir);
+            files.reverse();
+            files
+        })
+        .await
+        .unwrap_or_default()
+    })
+    .flat_map(stream::iter);
+    let header = match format {
+        ExportFormat::Csv => Some(Ok(Bytes::from_static(CSV_HEADER.as_bytes()))),
+        ExportFormat::Jsonl => None,
+    };
+    let rows = files
+        .filter(move |(_, modified_ms)| {
+            // mtime 缺失时留到读取记录后再按 received_at + duration 判断。
+            let keep = modified_ms.map(|ms| range.contains(ms)).unwrap_or(true);
+            async move { keep }
+        })
+        .then(move |(path, modified_ms)| {
+            let output_dir = output_dir.clone();
+            async move { export_row(&output_dir, &path, modified_ms, range, format).await }
+        })
+        .filter_map(|row| async move { row.map(Ok) });
+    stream::iter(header).chain(rows)
+}
+
+async fn export_row(
+    output_dir: &Path,
+    result_path: &Path,
+    modified_ms: Option<u64>,
+    range: ExportRange,
+    format: ExportFormat,
+) -> Option<Bytes> {
+    let result = read_json_value(result_path).await?;
+    let id = result.get("id").and_then(Value::as_str)?.to_string();
+    let request = read_json_value(&output_dir.join(format!("{id}.request.json"))).await;
+    let duration_ms = result
+        .get("duration_ms")
+        .and_then(Value::as_u64)
+        .unwrap_or(0);
+    let received_at_ms = request
+        .as_ref()
+        .and_then(|value| value.get("received_at_ms"))
+        .and_then(Value::as_u64)
+        .unwrap_or(0);
+    let finished_at_ms = modified_ms
+        .or_else(|| received_at_ms.checked_add(duration_ms))
+        .unwrap_or(0);
+    if !range.contains(finished_at_ms) {
+        return None;
+    }
+    let finished_at = format_rfc3339_ms(finished_at_ms);
+    let line = match format {
+        ExportFormat::Jsonl => {
+            let record = json!({
+                "request": request,
+                "result": result,
+                "finished_at": finished_at,
+            });
+            let mut line = serde_json::to_string(&record).ok()?;
+            line.push('\n');
+            line
+        }
+        ExportFormat::Csv => {
+            let queued_for_secs = if received_at_ms > 0 && finished_at_ms >= received_at_ms {
+                (finished_at_ms - received_at_ms) / 1000
+            } else {
+                duration_ms / 1000
+            };
+            let request_field = |key: &str| {
+                request
+                    .as_ref()
+                    .and_then(|value| value.get(key))
+                    .and_then(Value::as_str)
+                    .unwrap_or("")
+                    .to_string()
+            };
+            let mut command = request_field("raw_command");
+            if command.is_empty() {
+                command = request_field("command");
+            }
+            let status = result
+                .get("status")
+                .and_then(Value::as_str)
+                .unwrap_or("")
+                .to_string();
+            let exit_code = result
+                .get("exit_code")
+                .and_then(Value::as_i64)
+                .map(|code| code.to_string())
+                .unwrap_or_default();
+            let result_field = |key: &str| {
+                result
+                    .get(key)
+                    .and_then(Value::as_str)
+                    .unwrap_or("")
+                    .to_string()
+            };
+            let decision_latency_ms = result
+                .get("decision_latency_ms")
+                .and_then(Value::as_u64)
+                .map(|latency| latency.to_string())
+                .unwrap_or_default();
+            let fields = [
+                id,
+                request_field("client"),
+                request_field("intent"),
+                command,
+                status,
+                exit_code,
+                queued_for_secs.to_string(),
+                duration_ms.to_string(),
+                finished_at,
+                result_field("approved_by"),
+                result_field("denied_by"),
+                decision_latency_ms,
+                request_field("source"),
+            ];
+            let mut line = fields
+                .iter()
+                .map(|field| csv_field(field))
+                .collect::<Vec<_>>()
+                .join(",");
+            line.push('\n');
+            line
+        }
+    };
+    Some(Bytes::from(line))
+}
+
+async fn read_json_value(path: &Path) -> Option<Value> {
+    let payload = match tokio::fs::read(path).await {
+        Ok(payload) => payload,
+        Err(err) => {
+            tracing::warn!(error = %err, path = %path.display(), "failed to read audit record");
+            return None;
+        }
+    };
+    match serde_json::from_slice(&payload) {
+        Ok(value) => Some(value),
+        Err(err) => {
+            tracing::warn!(error = %err, path = %path.display(), "failed to parse audit record");
+            None
+        }
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn format_rfc3339_ms(ms: u64) -> String {
+    humantime::format_rfc3339_millis(UNIX_EPOCH + Duration::from_millis(ms)).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_utils::temp_dir;
+    use super::*;
+    use std::fs;
+
+    fn write_records(dir: &Path, id: &str, command: &str) {
+        let request = json!({
+            "id": id,
+            "client": "octovalve-proxy",
+            "intent": "check",
+            "raw_command": command,
+            "received_at_ms": 1000,
+        });
+        let result = json!({
+            "id": id,
+            "status": "completed",
+            "exit_code": 0,
+            "duration_ms": 20,
+            "approved_by": "alice",
+            "decision_latency_ms": 1500,
+        });
+        fs::write(
+            dir.join(format!("{id}.request.json")),
+            serde_json::to_vec(&request).unwrap(),
+        )
+        .unwrap();
+        fs::write(
+            dir.join(format!("{id}.result.json")),
+            serde_json::to_vec(&result).unwrap(),
+        )
+        .unwrap();
+    }
+
+    fn collect(stream: impl Stream<Item = std::io::Result<Bytes>>) -> String {
+        let rt = tokio::runtime::Runtime::new().expect("runtime");
+        let chunks: Vec<_> = rt.block_on(stream.collect());
+        chunks
+            .into_iter()
+            .map(|chunk| String::from_utf8(chunk.unwrap().to_vec()).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn exports_csv_with_escaped_fields() {
+        let dir = temp_dir("octovalve-export-csv");
+        write_records(&dir, "req-1", "echo \"a,b\"");
+        let output = collect(export_history_stream(
+            dir.clone(),
+            ExportRange::default(),
+            ExportFormat::Csv,
+        ));
+        let mut lines = output.lines();
+        assert_eq!(lines.next(), Some(CSV_HEADER.trim_end()));
+        let row = lines.next().expect("row");
+        assert!(row.starts_with("req-1,octovalve-proxy,check,\"echo \"\"a,b\"\"\",completed,0,"));
+        assert!(row.ends_with(",alice,,1500,"));
+        assert!(lines.next().is_none());
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn exports_jsonl_within_range() {
+        let dir = temp_dir("octovalve-export-jsonl");
+        write_records(&dir, "req-1", "ls");
+        let output = collect(export_history_stream(
+            dir.clone(),
+            ExportRange::default(),
+            ExportFormat::Jsonl,
+        ));
+        let record: Value = serde_json::from_str(output.trim()).expect("jsonl");
+        assert_eq!(record["request"]["raw_command"], "ls");
+        assert_eq!(record["result"]["status"], "completed");
+        assert_eq!(record["result"]["approved_by"], "alice");
+
+        let future = ExportRange {
+            from_ms: Some(u64::MAX - 1),
+            to_ms: None,
+        };
+        let output = collect(export_history_stream(
+            dir.clone(),
+            future,
+            ExportFormat::Jsonl,
+        ));
+        assert!(output.is_empty());
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn csv_field_quotes_special_characters() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("line\nbreak"), "\"line\nbreak\"");
+    }
+}
@@ -0,0 +1,432 @@
+use std::path::Path;
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+use protocol::control::{FileChange, FileChangeAction};
+use protocol::CommandRequest;
+
+// 审批辅助：从 raw_command 中识别常见的写文件写法（heredoc、echo/printf 重定向、tee、sed -i），
+// 供 UI 展示“将写入哪些文件”。只是尽力而为的静态分析，识别不了就不标注，绝不影响请求本身。
+const MAX_ANALYZED_BYTES: usize = 256 * 1024;
+const MAX_FILE_CHANGES: usize = 32;
+const PREVIEW_MAX_BYTES: usize = 4 * 1024;
+
+pub(crate) fn analyze_request(request: &CommandRequest) -> Vec<FileChange> {
+    if request.raw_command.len() > MAX_ANALYZED_BYTES {
+        return Vec::new();
+    }
+    let mut changes = Vec::new();
+    for script in scripts(request) {
+        analyze_script(&script, &mut changes);
+    }
+    changes.truncate(MAX_FILE_CHANGES);
+    changes
+}
+
+// `bash -lc '<script>'` 这类包装只分析内层脚本，否则直接分析 raw_command。
+fn scripts(request: &CommandRequest) -> Vec<String> {
+    let inner: Vec<String> = request
+        .pipeline
+        .iter()
+        .filter_map(|stage| shell_script(&stage.argv))
+        .collect();
+    if inner.is_empty() {
+        vec![request.raw_command.clone()]
+    } else {
+        inner
+    }
+}
+
+fn shell_script(argv: &[String]) -> Option<String> {
+    let program = argv.first().map(|value| basename(value))?;
+    if !matches!(program, "bash" | "sh" | "zsh" | "dash") {
+        return None;
+    }
+    let mut args = argv.iter().skip(1);
+    while let Some(arg) = args.next() {
+        if arg.starts_with('-') && !arg.starts_with("--") && arg.contains('c') {
+            return args.next().cloned();
+        }
+        if !arg.starts_with('-') {
+            break;
+        }
+    }
+    None
+}
+
+fn analyze_script(script: &str, changes: &mut Vec<FileChange>) {
+    let lines: Vec<&str> = script.lines().collect();
+    let mut index = 0;
+    while index < lines.len() {
+        let line = lines[index];
+        index += 1;
+        let Some((delimiter, strip_tabs)) = heredoc_delimiter(line) else {
+            for command in split_commands(line) {
+                analyze_command(&command, None, changes);
+            }
+            continue;
+        };
+        let mut body = String::new();
+        while index < lines.len() {
+            let candidate = lines[index];
+            index += 1;
+            if is_terminator(candidate, &delimiter, strip_tabs) {
+                break;
+            }
+            let candidate = if strip_tabs {
+                candidate.trim_start_matches('\t')
+            } else {
+                candidate
+            };
+            body.push_str(candidate);
+            body.push('\n');
+        }
+        for command in split_commands(line) {
+            analyze_command(&command, Some(&body), changes);
+        }
+    }
+}
+
+fn heredoc_regex() -> &'static Regex {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    REGEX.get_or_init(|| {
+        Regex::new(r#"(?:^|[^<])<<(-?)\s*['"]?([A-Za-z_][A-Za-z0-9_]*)['"]?"#)
+            .expect("heredoc regex")
+    })
+}
+
+fn heredoc_delimiter(line: &str) -> Option<(String, bool)> {
+    let captures = heredoc_regex().captures(line)?;
+    Some((captures[2].to_string(), !captures[1].is_empty()))
+}
+
+// 包在 `bash -lc '...'` 里时结束行可能带着外层引号，例如 `EOF'`。
+fn is_terminator(line: &str, delimiter: &str, strip_tabs: bool) -> bool {
+    let line = if strip_tabs {
+        line.trim_start_matches('\t')
+    } else {
+        line
+    };
+    let line = line.trim_end();
+    line == delimiter || line.trim_end_matches(['\'', '"']) == delimiter
+}
+
+// 按 `;`、`&&`、`||`、`|`、`&` 切分简单命令，忽略引号内的分隔符。
+fn split_commands(line: &str) -> Vec<Vec<String>> {
+    let mut segments = Vec::new();
+    let mut current = String::new();
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+    for ch in line.chars() {
+        if escaped {
+            current.push(ch);
+            escaped = false;
+            continue;
+        }
+        match quote {
+            Some(open) => {
+                if ch == open {
+                    quote = None;
+                } else if ch == '\\' && open == '"' {
+                    escaped = true;
+                }
+                current.push(ch);
+            }
+            None => match ch {
+                '\\' => {
+                    escaped = true;
+                    current.push(ch);
+                }
+                '\'' | '"' => {
+                    quote = Some(ch);
+                    current.push(ch);
+                }
+                // `>&2` / `2>&1` 里的 & 不是命令分隔符。
+                '&' if current.ends_with('>') => current.push(ch),
+                ';' | '|' | '&' => segments.push(std::mem::take(&mut current)),
+                _ => current.push(ch),
+            },
+        }
+    }
+    segments.push(current);
+    segments
+        .iter()
+        .filter_map(|segment| shell_words::split(segment).ok())
+        .filter(|tokens| !tokens.is_empty())
+        .collect()
+}
+
+struct Redirect {
+    action: FileChangeAction,
+    path: String,
+}
+
+// 取出 stdout 重定向目标，返回剩余的参数（不含重定向与 heredoc 记号）。
+fn take_redirects(tokens: &[String]) -> (Vec<String>, Vec<Redirect>) {
+    let mut args = Vec::new();
+    let mut redirects = Vec::new();
+    let mut iter = tokens.iter();
+    while let Some(token) = iter.next() {
+        let normalized = match token.strip_prefix('1') {
+            Some(rest) if rest.starts_with('>') => rest,
+            _ => token.as_str(),
+        };
+        if normalized.starts_with("<<") {
+            if normalized.trim_start_matches(['<', '-']).is_empty() {
+                iter.next();
+            }
+            continue;
+        }
+        let (action, rest) = if let Some(rest) = normalized.strip_prefix(">>") {
+            (FileChangeAction::Append, rest)
+        } else if let Some(rest) = normalized.strip_prefix('>') {
+            (
+                FileChangeAction::Write,
+                rest.strip_prefix('|').unwrap_or(rest),
+            )
+        } else {
+            args.push(token.clone());
+            continue;
+        };
+        if rest.starts_with('&') {
+            continue;
+        }
+        let path = if rest.is_empty() {
+            match iter.next() {
+                Some(path) => path.clone(),
+                None => continue,
+            }
+        } else {
+            rest.to_string()
+        };
+        if is_written_path(&path) {
+            redirects.push(Redirect { action, path });
+        }
+    }
+    (args, redirects)
+}
+
+fn is_written_path(path: &str) -> bool {
+    !path.is_empty() && !path.starts_with("/dev/") && !path.starts_with('&')
+}
+
+fn analyze_command(tokens: &[String], heredoc: Option<&str>, changes: &mut Vec<FileChange>) {
+    let (args, redirects) = take_redirects(tokens);
+    let Some(program) = args.first().map(|value| basename(value)) else {
+        return;
+    };
+    match program {
+        "tee" => {
+            let append = args[1..]
+                .iter()
+                .any(|arg| arg == "--append" || (is_short_flags(arg) && arg.contains('a')));
+            let action = if append {
+                FileChangeAction::Append
+            } else {
+                FileChangeAction::Write
+            };
+            for path in args[1..].iter().filter(|arg| !arg.starts_with('-')) {
+                if is_written_path(path) {
+                    push_change(changes, path, action, heredoc);
+                }
+            }
+            for redirect in redirects {
+                push_change(changes, &redirect.path, redirect.action, heredoc);
+            }
+        }
+        "sed" => analyze_sed(&args[1..], changes),
+        _ => {
+            let content = match program {
+                "cat" if args.len() == 1 => heredoc.map(str::to_string),
+                "echo" => Some(echo_output(&args[1..])),
+                "printf" => args.get(1).cloned(),
+                _ => None,
+            };
+            for redirect in redirects {
+                push_change(changes, &redirect.path, redirect.action, content.as_deref());
+            }
+        }
+    }
+}
+
+fn echo_output(args: &[String]) -> String {
+    let mut newline = true;
+    let mut words = args;
+    while let Some(flag) = words.first().filter(|arg| is_echo_flag(arg)) {
+        if flag.contains('n') {
+            newline = false;
+        }
+        words = &words[1..];
+    }
+    let mut output = words.join(" ");
+    if newline {
+        output.push('\n');
+    }
+    output
+}
+
+fn is_echo_flag(arg: &str) -> bool {
+    arg.len() > 1
+        && arg.starts_with('-')
+        && arg[1..].chars().all(|ch| matches!(ch, 'n' | 'e' | 'E'))
+}
+
+fn is_short_flags(arg: &str) -> bool {
+    arg.len() > 1 && arg.starts_with('-') && !arg.starts_with("--")
+}
+
+fn analyze_sed(args: &[String], changes: &mut Vec<FileChange>) {
+    let mut in_place = false;
+    let mut scripts = Vec::new();
+    let mut operands = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg == "-e" || arg == "--expression" {
+            if let Some(script) = iter.next() {
+                scripts.push(script.clone());
+            }
+        } else if let Some(script) = arg.strip_prefix("--expression=") {
+            scripts.push(script.to_string());
+        } else if arg == "-f" || arg == "--file" {
+            iter.next();
+        } else if arg.starts_with("--in-place") {
+            in_place = true;
+        } else if arg.starts_with("--") {
+            continue;
+        } else if is_short_flags(arg) {
+            // `-i` 之后的字符是备份后缀，不再是其它选项。
+            let flags = &arg[1..];
+            if let Some(position) = flags.find('i') {
+                in_place = true;
+                if flags[..position].contains('e') {
+                    if let Some(script) = iter.next() {
+                        scripts.push(script.clone());
+                    }
+                }
+            } else if flags.ends_with('e') {
+                if let Some(script) = iter.next() {
+                    scripts.push(script.clone());
+                }
+            }
+        } else {
+            operands.push(arg.clone());
+        }
+    }
+    if !in_place {
+        return;
+    }
+    if scripts.is_empty() && !operands.is_empty() {
+        scripts.push(operands.remove(0));
+    }
+    let preview = scripts.join("\n");
+    for path in operands.iter().filter(|path| is_written_path(path)) {
+        changes.push(FileChange {
+            path: path.clone(),
+            action: FileChangeAction::Edit,
+            preview: (!preview.is_empty()).then(|| truncate_preview(&preview)),
+            bytes: None,
+        });
+    }
+}
+
+fn push_change(
+    changes: &mut Vec<FileChange>,
+    path: &str,
+    action: FileChangeAction,
+    content: Option<&str>,
+) {
+    changes.push(FileChange {
+        path: path.to_string(),
+        action,
+        preview: content.map(truncate_preview),
+        bytes: content.map(|content| content.len() as u64),
+    });
+}
+
+fn truncate_preview(content: &str) -> String {
+    if content.len() <= PREVIEW_MAX_BYTES {
+        return content.to_string();
+    }
+    let mut end = PREVIEW_MAX_BYTES;
+    while !content.is_char_boundary(end) {
+        end -= 1;
+    }
+    content[..end].to_string()
+}
+
+fn basename(value: &str) -> &str {
+    Path::new(value)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use protocol::CommandStage;
+
+    fn request(raw_command: &str, pipeline: Vec<Vec<&str>>) -> CommandRequest {
+        CommandRequest {
+            id: "req".to_string(),
+            client: "client".to_string(),
+            target: "dev".to_string(),
+            intent: "write config".to_string(),
+            raw_command: raw_command.to_string(),
+            pipeline: pipeline
+                .into_iter()
+                .map(|argv| CommandStage {
+                    argv: argv.into_iter().map(str::to_string).collect(),
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn detects_heredoc_inside_shell_wrapper() {
+        let script = "cat > /etc/nginx/nginx.conf <<'EOF'\nworker_processes 1;\nEOF\ntee -a /tmp/log <<-END\n\tline\n\tEND";
+        let raw = format!("bash -lc '{script}'");
+        let changes = analyze_request(&request(&raw, vec![vec!["bash", "-lc", script]]));
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].path, "/etc/nginx/nginx.conf");
+        assert_eq!(changes[0].action, FileChangeAction::Write);
+        assert_eq!(changes[0].preview.as_deref(), Some("worker_processes 1;\n"));
+        assert_eq!(changes[0].bytes, Some(20));
+        assert_eq!(changes[1].path, "/tmp/log");
+        assert_eq!(changes[1].action, FileChangeAction::Append);
+        assert_eq!(changes[1].preview.as_deref(), Some("line\n"));
+    }
+
+    #[test]
+    fn detects_echo_redirects_and_sed_in_place() {
+        let raw = "echo -n hello >> notes.txt && sed -i.bak -e 's/a/b/' a.conf b.conf; ls 2>/dev/null >&2";
+        let changes = analyze_request(&request(raw, vec![vec!["echo"]]));
+        let summary: Vec<(&str, FileChangeAction, Option<&str>)> = changes
+            .iter()
+            .map(|change| {
+                (
+                    change.path.as_str(),
+                    change.action,
+                    change.preview.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("notes.txt", FileChangeAction::Append, Some("hello")),
+                ("a.conf", FileChangeAction::Edit, Some("s/a/b/")),
+                ("b.conf", FileChangeAction::Edit, Some("s/a/b/")),
+            ]
+        );
+    }
+
+    #[test]
+    fn unparsable_commands_yield_no_changes() {
+        assert!(analyze_request(&request("echo 'unterminated > x", vec![])).is_empty());
+        assert!(analyze_request(&request("sed 's/a/b/' file", vec![])).is_empty());
+        assert!(analyze_request(&request("ls -l | grep foo", vec![])).is_empty());
+    }
+}
@@ -1,11 +1,19 @@
-use protocol::control::ResultSnapshot;
-use protocol::{CommandMode, CommandStage, CommandStatus};
+use protocol::control::{ExecutionContext, HistoryPage, ResultSnapshot};
+use protocol::{CommandMode, CommandStage, CommandStatus, OutputRef};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs::{self, File};
-use std::io::Read;
+use std::io::{BufRead, BufReader, Read};
 use std::path::{Path, PathBuf};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+
+use crate::time_utils::system_time_ms;
+
+const HISTORY_INDEX_FILE: &str = "history.index.jsonl";
+const INDEX_SUMMARY_MAX_CHARS: usize = 200;
+pub(crate) const MAX_HISTORY_PAGE_LIMIT: usize = 200;
 
 #[derive(Debug, Deserialize, Serialize)]
 struct RequestRecord {
@@ -23,6 +31,14 @@ struct RequestRecord {
     received_at_ms: u64,
     #[serde(default)]
     pipeline: Vec<CommandStage>,
+    #[serde(default)]
+    batch_id: Option<String>,
+    #[serde(default)]
+    intent_tags: Vec<String>,
+    #[serde(default)]
+    metadata: Option<BTreeMap<String, String>>,
+    #[serde(default)]
+    source: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -35,6 +51,229 @@ struct ResultRecord {
     error: Option<String>,
     #[serde(default)]
     duration_ms: u128,
+    #[serde(default)]
+    stdout_ref: Option<OutputRef>,
+    #[serde(default)]
+    stderr_ref: Option<OutputRef>,
+    #[serde(default)]
+    denied_by_rule: Option<String>,
+    #[serde(default)]
+    binary_output: bool,
+    #[serde(default)]
+    executed_command: Option<String>,
+    #[serde(default)]
+    approved_by: Option<String>,
+    #[serde(default)]
+    denied_by: Option<String>,
+    #[serde(default)]
+    decision_latency_ms: Option<u64>,
+    #[serde(default)]
+    deny_reason: Option<String>,
+}
+
+// 追加写入的历史索引（每行一条 JSON），按完成时间分页；详细内容仍从各请求的记录文件读取。
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub(crate) struct HistoryIndexEntry {
+    id: String,
+    finished_at_ms: u64,
+    status: CommandStatus,
+    #[serde(default)]
+    summary: String,
+}
+
+impl HistoryIndexEntry {
+    pub(crate) fn new(id: &str, finished_at_ms: u64, status: CommandStatus, command: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            finished_at_ms,
+            status,
+            summary: command.chars().take(INDEX_SUMMARY_MAX_CHARS).collect(),
+        }
+    }
+}
+
+/// 服务启动时载入、由 server 与 service 共享的历史存储。
+pub(crate) type SharedHistoryStore = Arc<Mutex<HistoryStore>>;
+
+// 索引文件在内存中的副本：只在启动时读一次，之后随 append 同步更新，分页与 id 查重都不再读文件。
+pub(crate) struct HistoryStore {
+    output_dir: PathBuf,
+    // 按写入顺序保存，同一 id 只保留最先写入的一条。
+    entries: Vec<HistoryIndexEntry>,
+    ids: HashSet<String>,
+}
+
+/// 从内存索引中切出的一页，记录文件由 [`IndexPage::load`] 在阻塞线程里读取。
+pub(crate) struct IndexPage {
+    entries: Vec<HistoryIndexEntry>,
+    next_before_ms: Option<u64>,
+    next_before_id: Option<String>,
+}
+
+impl HistoryStore {
+    /// 读取目标审计目录的历史索引；索引不存在时从已有记录文件回填并写出一次。
+    /// 会同步读写文件，须在 `spawn_blocking` 中调用。
+    pub(crate) fn load(output_dir: &Path) -> Self {
+        let mut store = Self {
+            output_dir: output_dir.to_path_buf(),
+            entries: Vec::new(),
+            ids: HashSet::new(),
+        };
+        if store.index_path().exists() {
+            store.read_index();
+        } else if let Err(err) = store.backfill() {
+            tracing::warn!(
+                error = %err,
+                path = %store.index_path().display(),
+                "failed to backfill history index"
+            );
+        }
+        store
+    }
+
+    pub(crate) fn into_shared(self) -> SharedHistoryStore {
+        Arc::new(Mutex::new(self))
+    }
+
+    fn index_path(&self) -> PathBuf {
+        self.output_dir.join(HISTORY_INDEX_FILE)
+    }
+
+    fn backfill(&mut self) -> anyhow::Result<()> {
+        let request_records = load_request_records(&self.output_dir);
+        let mut lines = String::new();
+        for (path, finished_at_ms) in collect_result_files(&self.output_dir).into_iter().rev() {
+            let Ok(record) = read_json::<ResultRecord>(&path) else {
+                continue;
+            };
+            let Some(request) = request_records.get(&record.id) else {
+                continue;
+            };
+            let command = match record.executed_command.as_ref() {
+                Some(executed) => executed,
+                None if request.raw_command.is_empty() => &request.command,
+                None => &request.raw_command,
+            };
+            let finished_at_ms = finished_at_ms.unwrap_or(request.received_at_ms);
+            let entry = HistoryIndexEntry::new(&record.id, finished_at_ms, record.status, command);
+            lines.push_str(&serde_json::to_string(&entry)?);
+            lines.push('\n');
+            self.insert(entry);
+        }
+        fs::write(self.index_path(), lines)?;
+        Ok(())
+    }
+
+    fn read_index(&mut self) {
+        let Ok(file) = File::open(self.index_path()) else {
+            return;
+        };
+        for line in BufReader::new(file).lines().map_while(Result::ok) {
+            if let Ok(entry) = serde_json::from_str::<HistoryIndexEntry>(&line) {
+                self.insert(entry);
+            }
+        }
+    }
+
+    fn insert(&mut self, entry: HistoryIndexEntry) {
+        if self.ids.insert(entry.id.clone()) {
+            self.entries.push(entry);
+        }
+    }
+
+    /// 该 id 是否已有完成记录。
+    pub(crate) fn contains(&self, id: &str) -> bool {
+        self.ids.contains(id)
+    }
+
+    pub(crate) async fn append(&mut self, entry: HistoryIndexEntry) {
+        let Ok(mut line) = serde_json::to_string(&entry) else {
+            return;
+        };
+        line.push('\n');
+        let result = async {
+            let mut file = tokio::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(self.index_path())
+                .await?;
+            file.write_all(line.as_bytes()).await?;
+            file.flush().await
+        }
+        .await;
+        if let Err(err) = result {
+            tracing::warn!(error = %err, id = %entry.id, "failed to append history index");
+        }
+        self.insert(entry);
+    }
+
+    /// 返回排在游标 (`before_ms`, `before_id`) 之后的最近 `limit` 条记录（新到旧）。
+    /// 记录按 (finished_at_ms, id) 倒序；不带 `before_id` 时只按毫秒比较，兼容旧客户端。
+    pub(crate) fn page(
+        &self,
+        before_ms: Option<u64>,
+        before_id: Option<&str>,
+        limit: usize,
+    ) -> IndexPage {
+        let limit = limit.clamp(1, MAX_HISTORY_PAGE_LIMIT);
+        let older = |entry: &HistoryIndexEntry, before: u64| {
+            entry.finished_at_ms < before
+                || (entry.finished_at_ms == before
+                    && before_id.is_some_and(|id| entry.id.as_str() < id))
+        };
+        let mut entries: Vec<HistoryIndexEntry> = self
+            .entries
+            .iter()
+            .filter(|&entry| before_ms.is_none_or(|before| older(entry, before)))
+            .cloned()
+            .collect();
+        entries.sort_by(|a, b| (b.finished_at_ms, &b.id).cmp(&(a.finished_at_ms, &a.id)));
+        let has_more = entries.len() > limit;
+        entries.truncate(limit);
+        let (next_before_ms, next_before_id) = match entries.last() {
+            Some(entry) if has_more => (Some(entry.finished_at_ms), Some(entry.id.clone())),
+            _ => (None, None),
+        };
+        IndexPage {
+            entries,
+            next_before_ms,
+            next_before_id,
+        }
+    }
+}
+
+impl IndexPage {
+    /// 读取本页各条目的记录文件；会同步读文件，须在 `spawn_blocking` 中调用。
+    pub(crate) fn load(self, output_dir: &Path, max_output_bytes: u64) -> HistoryPage {
+        let items = self
+            .entries
+            .into_iter()
+            .filter_map(|entry| load_entry(output_dir, entry, max_output_bytes))
+            .collect();
+        HistoryPage {
+            items,
+            next_before_ms: self.next_before_ms,
+            next_before_id: self.next_before_id,
+        }
+    }
+}
+
+fn load_entry(
+    output_dir: &Path,
+    entry: HistoryIndexEntry,
+    max_output_bytes: u64,
+) -> Option<ResultSnapshot> {
+    let result_path = output_dir.join(format!("{}.result.json", entry.id));
+    let request_path = output_dir.join(format!("{}.request.json", entry.id));
+    let record = read_json::<ResultRecord>(&result_path).ok()?;
+    let request = read_json::<RequestRecord>(&request_path).ok()?;
+    Some(build_result_snapshot(
+        output_dir,
+        record,
+        &request,
+        Some(entry.finished_at_ms),
+        max_output_bytes,
+    ))
 }
 
 pub(crate) fn load_history(
@@ -61,48 +300,13 @@ pub(crate) fn load_history(
             );
             continue;
         };
-        let raw_command = if request.raw_command.is_empty() {
-            request.command.clone()
-        } else {
-            request.raw_command.clone()
-        };
-        let finished_at_ms = finished_at_ms
-            .or_else(|| {
-                request
-                    .received_at_ms
-                    .checked_add(record.duration_ms as u64)
-            })
-            .unwrap_or(0);
-        let queued_for_secs =
-            if request.received_at_ms > 0 && finished_at_ms >= request.received_at_ms {
-                (finished_at_ms - request.received_at_ms) / 1000
-            } else {
-                (record.duration_ms / 1000) as u64
-            };
-        let stdout = read_text_limited(
-            output_dir.join(format!("{}.stdout", record.id)),
-            max_output_bytes,
-        );
-        let stderr = read_text_limited(
-            output_dir.join(format!("{}.stderr", record.id)),
-            max_output_bytes,
-        );
-        results.push(ResultSnapshot {
-            id: record.id.clone(),
-            status: record.status,
-            exit_code: record.exit_code,
-            error: record.error,
-            intent: request.intent.clone(),
-            mode: request.mode.clone(),
-            raw_command,
-            pipeline: request.pipeline.clone(),
-            cwd: request.cwd.clone(),
-            peer: request.peer.clone(),
-            queued_for_secs,
+        results.push(build_result_snapshot(
+            output_dir,
+            record,
+            request,
             finished_at_ms,
-            stdout,
-            stderr,
-        });
+            max_output_bytes,
+        ));
     }
     results.sort_by(|a, b| b.finished_at_ms.cmp(&a.finished_at_ms));
     if results.len() > limit {
@@ -111,6 +315,80 @@ pub(crate) fn load_history(
     results
 }
 
+fn build_result_snapshot(
+    output_dir: &Path,
+    record: ResultRecord,
+    request: &RequestRecord,
+    finished_at_ms: Option<u64>,
+    max_output_bytes: u64,
+) -> ResultSnapshot {
+    let submitted = if request.raw_command.is_empty() {
+        request.command.clone()
+    } else {
+        request.raw_command.clone()
+    };
+    // 审批时被改写过的请求，request.json 里是原始命令，result.json 里是实际执行的命令；
+    // 落盘的 pipeline 对应原始命令，不再沿用。
+    let (raw_command, original_command, pipeline) = match record.executed_command.clone() {
+        Some(executed) => (executed, Some(submitted), Vec::new()),
+        None => (submitted, None, request.pipeline.clone()),
+    };
+    let finished_at_ms = finished_at_ms
+        .or_else(|| {
+            request
+                .received_at_ms
+                .checked_add(record.duration_ms as u64)
+        })
+        .unwrap_or(0);
+    let queued_for_secs = if request.received_at_ms > 0 && finished_at_ms >= request.received_at_ms
+    {
+        (finished_at_ms - request.received_at_ms) / 1000
+    } else {
+        (record.duration_ms / 1000) as u64
+    };
+    let stdout = read_text_limited(
+        output_dir.join(format!("{}.stdout", record.id)),
+        max_output_bytes,
+    );
+    let stderr = read_text_limited(
+        output_dir.join(format!("{}.stderr", record.id)),
+        max_output_bytes,
+    );
+    // 旧记录或未真正执行的请求没有 context.json。
+    let execution =
+        read_json::<ExecutionContext>(&output_dir.join(format!("{}.context.json", record.id))).ok();
+    ResultSnapshot {
+        id: record.id.clone(),
+        status: record.status,
+        exit_code: record.exit_code,
+        error: record.error,
+        intent: request.intent.clone(),
+        mode: request.mode.clone(),
+        raw_command,
+        original_command,
+        pipeline,
+        cwd: request.cwd.clone(),
+        peer: request.peer.clone(),
+        queued_for_secs,
+        finished_at_ms,
+        stdout,
+        stderr,
+        stdout_ref: record.stdout_ref,
+        stderr_ref: record.stderr_ref,
+        denied_by_rule: record.denied_by_rule,
+        approved_by: record.approved_by,
+        denied_by: record.denied_by,
+        decision_latency_ms: record.decision_latency_ms,
+        deny_reason: record.deny_reason,
+        batch_id: request.batch_id.clone(),
+        intent_tags: request.intent_tags.clone(),
+        metadata: request.metadata.clone(),
+        binary_output: record.binary_output,
+        execution,
+        source: request.source.clone(),
+    }
+}
+
 fn load_request_records(output_dir: &Path) -> HashMap<String, RequestRecord> {
     let mut records = HashMap::new();
     for entry in fs::read_dir(output_dir).into_iter().flatten() {
@@ -137,7 +415,7 @@ fn load_request_records(output_dir: &Path) -> HashMap<String, RequestRecord> {
     records
 }
 
-fn collect_result_files(output_dir: &Path) -> Vec<(PathBuf, Option<u64>)> {
+pub(super) fn collect_result_files(output_dir: &Path) -> Vec<(PathBuf, Option<u64>)> {
     let mut files = Vec::new();
     for entry in fs::read_dir(output_dir).into_iter().flatten() {
         let entry = match entry {
@@ -155,7 +433,7 @@ fn collect_result_files(output_dir: &Path) -> Vec<(PathBuf, Option<u64>)> {
             .metadata()
             .ok()
             .and_then(|meta| meta.modified().ok())
-            .and_then(system_time_ms);
+            .map(system_time_ms);
         files.push((path, finished_at_ms));
     }
     files.sort_by(|a, b| b.1.cmp(&a.1));
@@ -207,12 +485,6 @@ fn is_result_record(path: &Path) -> bool {
             .unwrap_or(false)
 }
 
-fn system_time_ms(time: SystemTime) -> Option<u64> {
-    time.duration_since(UNIX_EPOCH)
-        .ok()
-        .map(|duration| duration.as_millis() as u64)
-}
-
 #[cfg(test)]
 mod tests {
     use super::super::test_utils::temp_dir;
@@ -232,6 +504,10 @@ mod tests {
             cwd: Some("/tmp".to_string()),
             received_at_ms: 1000,
             pipeline: Vec::new(),
+            batch_id: None,
+            intent_tags: Vec::new(),
+            metadata: None,
+            source: None,
         };
         let result = ResultRecord {
             id: "req-1".to_string(),
@@ -239,6 +515,15 @@ mod tests {
             exit_code: Some(0),
             error: None,
             duration_ms: 500,
+            stdout_ref: None,
+            stderr_ref: None,
+            denied_by_rule: None,
+            binary_output: false,
+            executed_command: None,
+            approved_by: None,
+            denied_by: None,
+            decision_latency_ms: None,
+            deny_reason: None,
         };
         fs::write(
             dir.join("req-1.request.json"),
@@ -259,6 +544,7 @@ mod tests {
         assert_eq!(item.raw_command, "echo ok");
         assert_eq!(item.stdout.as_deref(), Some("ok\n"));
         assert_eq!(item.stderr.as_deref(), Some("warn\n"));
+        assert!(item.execution.is_none());
         fs::remove_dir_all(&dir).ok();
     }
 
@@ -277,6 +563,10 @@ mod tests {
                 cwd: None,
                 received_at_ms: 1000 + idx as u64,
                 pipeline: Vec::new(),
+                batch_id: None,
+                intent_tags: Vec::new(),
+                metadata: None,
+                source: None,
             };
             let result = ResultRecord {
                 id: id.clone(),
@@ -284,6 +574,15 @@ mod tests {
                 exit_code: Some(0),
                 error: None,
                 duration_ms: 10,
+                stdout_ref: None,
+                stderr_ref: None,
+                denied_by_rule: None,
+                binary_output: false,
+                executed_command: None,
+                approved_by: None,
+                denied_by: None,
+                decision_latency_ms: None,
+                deny_reason: None,
             };
             fs::write(
                 dir.join(format!("{id}.request.json")),
@@ -300,4 +599,171 @@ mod tests {
         assert_eq!(history.len(), 2);
         fs::remove_dir_all(&dir).ok();
     }
+
+    fn empty_store(dir: &Path) -> HistoryStore {
+        HistoryStore {
+            output_dir: dir.to_path_buf(),
+            entries: Vec::new(),
+            ids: HashSet::new(),
+        }
+    }
+
+    fn write_history_records(dir: &Path, count: u64) {
+        for idx in 0..count {
+            let id = format!("req-{idx}");
+            let request = RequestRecord {
+                id: id.clone(),
+                peer: "127.0.0.1".to_string(),
+                intent: "intent".to_string(),
+                mode: CommandMode::Shell,
+                command: format!("echo {idx}"),
+                raw_command: format!("echo {idx}"),
+                cwd: None,
+                received_at_ms: 1000 * idx,
+                pipeline: Vec::new(),
+                batch_id: None,
+                intent_tags: Vec::new(),
+                metadata: None,
+                source: None,
+            };
+            let result = ResultRecord {
+                id: id.clone(),
+                status: CommandStatus::Completed,
+                exit_code: Some(0),
+                error: None,
+                duration_ms: 10,
+                stdout_ref: None,
+                stderr_ref: None,
+                denied_by_rule: None,
+                binary_output: false,
+                executed_command: None,
+                approved_by: None,
+                denied_by: None,
+                decision_latency_ms: None,
+                deny_reason: None,
+            };
+            fs::write(
+                dir.join(format!("{id}.request.json")),
+                serde_json::to_vec_pretty(&request).unwrap(),
+            )
+            .unwrap();
+            fs::write(
+                dir.join(format!("{id}.result.json")),
+                serde_json::to_vec_pretty(&result).unwrap(),
+            )
+            .unwrap();
+        }
+    }
+
+    #[test]
+    fn history_store_pages_by_finished_time() {
+        let dir = temp_dir("octovalve-history-store");
+        write_history_records(&dir, 5);
+        let mut store = empty_store(&dir);
+        let rt = tokio::runtime::Runtime::new().expect("runtime");
+        for idx in 0..5u64 {
+            let entry = HistoryIndexEntry::new(
+                &format!("req-{idx}"),
+                1000 * idx + 10,
+                CommandStatus::Completed,
+                "echo",
+            );
+            rt.block_on(store.append(entry));
+        }
+
+        let first = store.page(None, None, 2).load(&dir, 1024);
+        let ids: Vec<&str> = first.items.iter().map(|item| item.id.as_str()).collect();
+        assert_eq!(ids, ["req-4", "req-3"]);
+        assert_eq!(first.next_before_ms, Some(3010));
+
+        let second = store
+            .page(first.next_before_ms, first.next_before_id.as_deref(), 2)
+            .load(&dir, 1024);
+        let ids: Vec<&str> = second.items.iter().map(|item| item.id.as_str()).collect();
+        assert_eq!(ids, ["req-2", "req-1"]);
+
+        let last = store
+            .page(second.next_before_ms, second.next_before_id.as_deref(), 2)
+            .load(&dir, 1024);
+        assert_eq!(last.items.len(), 1);
+        assert_eq!(last.next_before_ms, None);
+
+        fs::remove_file(dir.join(HISTORY_INDEX_FILE)).unwrap();
+        let reopened = HistoryStore::load(&dir);
+        assert_eq!(
+            reopened.page(None, None, 10).load(&dir, 1024).items.len(),
+            5
+        );
+        assert!(dir.join(HISTORY_INDEX_FILE).exists());
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn load_keeps_existing_index_and_tracks_ids() {
+        let dir = temp_dir("octovalve-history-load");
+        write_history_records(&dir, 3);
+        let entry = HistoryIndexEntry::new("req-1", 10, CommandStatus::Completed, "echo 1");
+        let line = format!("{}\n", serde_json::to_string(&entry).unwrap());
+        fs::write(dir.join(HISTORY_INDEX_FILE), &line).unwrap();
+
+        let mut store = HistoryStore::load(&dir);
+        // 已有索引原样保留，不再从记录文件回填。
+        assert_eq!(
+            fs::read_to_string(dir.join(HISTORY_INDEX_FILE)).unwrap(),
+            line
+        );
+        assert!(store.contains("req-1"));
+        assert!(!store.contains("req-2"));
+
+        let rt = tokio::runtime::Runtime::new().expect("runtime");
+        rt.block_on(store.append(HistoryIndexEntry::new(
+            "req-2",
+            20,
+            CommandStatus::Completed,
+            "echo 2",
+        )));
+        assert!(store.contains("req-2"));
+        let page = store.page(None, None, 10).load(&dir, 1024);
+        let ids: Vec<&str> = page.items.iter().map(|item| item.id.as_str()).collect();
+        assert_eq!(ids, ["req-2", "req-1"]);
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn history_pages_keep_entries_finished_in_the_same_ms() {
+        let dir = temp_dir("octovalve-history-same-ms");
+        write_history_records(&dir, 5);
+        let mut store = empty_store(&dir);
+        let rt = tokio::runtime::Runtime::new().expect("runtime");
+        // req-1..req-3 同一毫秒完成，第一页恰好在它们中间截断。
+        for (idx, finished_at_ms) in [(0u64, 10u64), (1, 20), (2, 20), (3, 20), (4, 30)] {
+            let entry = HistoryIndexEntry::new(
+                &format!("req-{idx}"),
+                finished_at_ms,
+                CommandStatus::Completed,
+                "echo",
+            );
+            rt.block_on(store.append(entry));
+        }
+
+        let mut ids = Vec::new();
+        let mut cursor: (Option<u64>, Option<String>) = (None, None);
+        loop {
+            let page = store
+                .page(cursor.0, cursor.1.as_deref(), 2)
+                .load(&dir, 1024);
+            ids.extend(page.items.into_iter().map(|item| item.id));
+            if page.next_before_ms.is_none() {
+                break;
+            }
+            cursor = (page.next_before_ms, page.next_before_id);
+        }
+        assert_eq!(ids, ["req-4", "req-3", "req-2", "req-1", "req-0"]);
+
+        // 只带毫秒游标的旧客户端仍按毫秒翻页。
+        let legacy = store.page(Some(20), None, 10).load(&dir, 1024);
+        let ids: Vec<&str> = legacy.items.iter().map(|item| item.id.as_str()).collect();
+        assert_eq!(ids, ["req-0"]);
+        fs::remove_dir_all(&dir).ok();
+    }
 }
@@ -1,48 +1,104 @@
+mod access;
 mod audit;
 mod events;
 mod executor;
+mod export;
+mod file_changes;
 mod history;
 mod output;
 mod policy;
 mod process;
+mod quota;
+mod redaction;
+mod retention;
+mod schedules;
 mod server;
 mod service;
+mod shell_parse;
 mod snapshots;
 mod stream;
+mod templates;
+mod temporary;
 #[cfg(test)]
 mod test_utils;
+mod time_window;
+mod webhook;
+mod windows;
 
 use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::path::{Path, PathBuf};
 use std::process::Stdio;
 use std::sync::Arc;
+use std::time::SystemTime;
 
 use tokio::process::Command;
 use tokio::sync::broadcast;
 use tokio::sync::RwLock;
 
 use crate::events::ConsoleEvent;
-use crate::runtime::emit_target_update;
-use crate::shell_utils::apply_ssh_options;
+use crate::metrics::Metrics;
+use crate::runtime::{emit_target_update, spawn_probe_worker, ProbeSettings};
+use crate::shell_utils::{apply_ssh_options, classify_ssh_failure};
 use crate::state::{ConsoleState, ControlCommand, TargetSpec, TargetStatus};
+use protocol::control::{HistoryPage, OutputChunk};
+use protocol::OutputStream;
 use system_utils::ssh::apply_askpass_env;
 
+pub(crate) use access::ClientAccess;
+pub(crate) use export::{ExportFormat, ExportRange};
 pub(crate) use policy::PolicyConfig;
-use policy::Whitelist;
+use policy::{join_diagnostics, ActivePolicy, LivePolicy};
+use quota::QuotaTracker;
+pub(crate) use retention::{audit_usage, AuditUsage};
+pub(crate) use schedules::{spawn_scheduler, ScheduleInfo, ScheduleRegistry};
+pub(crate) use server::CommandIntake;
 use service::TargetServiceHandle;
+pub(crate) use templates::{TemplateError, TemplateInfo, TemplateRegistry};
+use webhook::ResultWebhook;
 
 pub(crate) async fn spawn_local_exec(
     listen_addr: SocketAddr,
+    client_access: Option<ClientAccess>,
     policy: PolicyConfig,
+    policy_path: PathBuf,
     audit_root: PathBuf,
+    probe_settings: ProbeSettings,
     state: Arc<RwLock<ConsoleState>>,
     event_tx: broadcast::Sender<ConsoleEvent>,
-) -> anyhow::Result<()> {
-    let whitelist = Arc::new(Whitelist::from_config(&policy.whitelist)?);
-    let limits = Arc::new(policy.limits);
+    metrics: Arc<Metrics>,
+) -> anyhow::Result<Arc<CommandIntake>> {
+    let check = policy.check();
+    for warning in &check.warnings {
+        tracing::warn!(path = %warning.path, "policy: {}", warning.message);
+    }
+    if !check.errors.is_empty() {
+        anyhow::bail!(
+            "invalid policy {}: {}",
+            policy_path.display(),
+            join_diagnostics(&check.errors)
+        );
+    }
+    let live_policy = Arc::new(LivePolicy::new(
+        policy_path,
+        ActivePolicy::from_config(&policy)?,
+    ));
+    let control_token = policy.control_token;
+    let redaction = Arc::new(policy.redaction);
     let audit_root = Arc::new(audit_root);
     std::fs::create_dir_all(&*audit_root)?;
+    let quota = Arc::new(QuotaTracker::load(&audit_root));
+    let result_webhook = policy
+        .result_webhook
+        .as_deref()
+        .map(|url| ResultWebhook::spawn(url, audit_root.join(webhook::SPOOL_FILE)))
+        .transpose()?;
+    retention::spawn_retention_task(
+        Arc::clone(&audit_root),
+        policy.retention,
+        Arc::clone(&state),
+        event_tx.clone(),
+    );
 
     let targets = {
         let guard = state.read().await;
@@ -51,6 +107,10 @@ pub(crate) async fn spawn_local_exec(
 
     let mut services: HashMap<String, TargetServiceHandle> = HashMap::new();
     for target in targets {
+        state.write().await.set_quota_usage(
+            &target.name,
+            quota.usage(&policy.quota, &target.name, SystemTime::now()),
+        );
         if target
             .ssh
             .as_deref()
@@ -67,13 +127,23 @@ pub(crate) async fn spawn_local_exec(
         }
         let output_dir = Arc::new(target_audit_dir(&audit_root, &target.name));
         std::fs::create_dir_all(&*output_dir)?;
+        // 历史索引只在这里载入（必要时回填）一次，之后 server 与 service 共用内存里的这一份。
+        let history_dir = Arc::clone(&output_dir);
+        let history_store =
+            tokio::task::spawn_blocking(move || history::HistoryStore::load(&history_dir))
+                .await?
+                .into_shared();
         let handle = service::spawn_service(
             target.clone(),
-            Arc::clone(&whitelist),
-            Arc::clone(&limits),
+            Arc::clone(&live_policy),
+            Arc::clone(&redaction),
+            Arc::clone(&quota),
             Arc::clone(&output_dir),
+            history_store,
+            result_webhook.clone(),
             Arc::clone(&state),
             event_tx.clone(),
+            Arc::clone(&metrics),
         );
         {
             let mut guard = state.write().await;
@@ -83,26 +153,50 @@ pub(crate) async fn spawn_local_exec(
         emit_target_update(&target.name, &state, &event_tx).await;
         let target_name = target.name.clone();
         services.insert(target_name.clone(), handle);
-        let state = Arc::clone(&state);
-        let event_tx = event_tx.clone();
-        tokio::spawn(async move {
-            let (status, error) = match check_ssh_ready(&target).await {
-                Ok(()) => (TargetStatus::Ready, None),
-                Err(err) => (TargetStatus::Down, Some(err)),
-            };
-            {
-                let mut guard = state.write().await;
-                guard.set_status(&target_name, status, error);
-            }
-            emit_target_update(&target_name, &state, &event_tx).await;
-        });
+        let target = Arc::new(target);
+        let policy = Arc::clone(&live_policy);
+        let retry = spawn_probe_worker(
+            target_name.clone(),
+            probe_settings,
+            move || {
+                let target = Arc::clone(&target);
+                // Windows 目标不包装沙箱，只检查连通性。
+                let probe = if target.shell.is_windows() {
+                    "true".to_string()
+                } else {
+                    policy.current().sandbox.probe_command()
+                };
+                async move { check_ssh_ready(&target, &probe).await }
+            },
+            Arc::clone(&state),
+            event_tx.clone(),
+        );
+        state
+            .write()
+            .await
+            .register_retry_notifier(target_name, retry);
     }
 
-    server::spawn_command_server(listen_addr, services, Arc::clone(&whitelist)).await?;
-    Ok(())
+    let intake = Arc::new(CommandIntake::new(
+        services,
+        live_policy,
+        redaction,
+        quota,
+        state,
+        metrics,
+        result_webhook,
+    ));
+    server::spawn_command_server(
+        listen_addr,
+        client_access,
+        Arc::clone(&intake),
+        control_token,
+    )
+    .await?;
+    Ok(intake)
 }
 
-async fn check_ssh_ready(target: &TargetSpec) -> Result<(), String> {
+async fn check_ssh_ready(target: &TargetSpec, probe: &str) -> Result<(), String> {
     let ssh = target
         .ssh
         .as_ref()
@@ -112,10 +206,10 @@ async fn check_ssh_ready(target: &TargetSpec) -> Result<(), String> {
         apply_askpass_env(&mut cmd, password).map_err(|err| err.to_string())?;
     }
     cmd.arg("-T");
-    apply_ssh_options(&mut cmd, target.ssh_password.is_some());
+    apply_ssh_options(&mut cmd, target.ssh_password.is_some(), &target.host_key);
     cmd.args(&target.ssh_args);
     cmd.arg(ssh);
-    cmd.arg("true");
+    cmd.arg(probe);
     cmd.stdin(Stdio::null());
     cmd.stdout(Stdio::null());
     cmd.stderr(Stdio::piped());
@@ -124,12 +218,11 @@ async fn check_ssh_ready(target: &TargetSpec) -> Result<(), String> {
     if output.status.success() {
         return Ok(());
     }
-    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
-    if stderr.is_empty() {
-        Err(format!("ssh exited with {}", output.status))
-    } else {
-        Err(stderr)
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if stderr.trim().is_empty() {
+        return Err(format!("ssh exited with {}", output.status));
     }
+    Err(classify_ssh_failure(&stderr, &target.host_key, None).1)
 }
 
 fn target_audit_dir(root: &Path, target: &str) -> PathBuf {
@@ -137,6 +230,61 @@ fn target_audit_dir(root: &Path, target: &str) -> PathBuf {
     root.join(sanitized)
 }
 
+pub(crate) async fn fetch_output(
+    audit_root: &Path,
+    target: &str,
+    id: &str,
+    stream: OutputStream,
+    offset: u64,
+    len: u64,
+) -> std::io::Result<OutputChunk> {
+    let output_dir = target_audit_dir(audit_root, target);
+    output::read_output_chunk(&output_dir, id, stream, offset, len).await
+}
+
+// 分页读取审计历史，inline 输出按 `max_output_bytes` 截断，完整输出通过 output 接口获取。
+// 运行中的目标从共享索引分页；没有 service 的目标（如未配置 ssh）临时载入一次磁盘上的索引。
+pub(crate) async fn history_page(
+    intake: &CommandIntake,
+    audit_root: &Path,
+    target: &str,
+    before_ms: Option<u64>,
+    before_id: Option<String>,
+    limit: usize,
+    max_output_bytes: u64,
+) -> HistoryPage {
+    let output_dir = target_audit_dir(audit_root, target);
+    let store = intake.history_store(target);
+    tokio::task::spawn_blocking(move || {
+        let page = match store {
+            Some(store) => store
+                .blocking_lock()
+                .page(before_ms, before_id.as_deref(), limit),
+            None => history::HistoryStore::load(&output_dir).page(
+                before_ms,
+                before_id.as_deref(),
+                limit,
+            ),
+        };
+        page.load(&output_dir, max_output_bytes)
+    })
+    .await
+    .unwrap_or(HistoryPage {
+        items: Vec::new(),
+        next_before_ms: None,
+        next_before_id: None,
+    })
+}
+
+pub(crate) fn export_target_history(
+    audit_root: &Path,
+    target: &str,
+    range: ExportRange,
+    format: ExportFormat,
+) -> impl futures_util::Stream<Item = std::io::Result<bytes::Bytes>> + Send + 'static {
+    export::export_history_stream(target_audit_dir(audit_root, target), range, format)
+}
+
 pub(crate) async fn send_control_command(
     name: &str,
     command: ControlCommand,
@@ -1,8 +1,17 @@
-use protocol::{CommandResponse, CommandStatus};
+use base64::engine::general_purpose::STANDARD as BASE64_ENGINE;
+use base64::Engine;
+use protocol::control::OutputChunk;
+use protocol::{CommandResponse, CommandStatus, OutputRef, OutputStream};
 use serde::Serialize;
+use std::io::SeekFrom;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+use super::events::ApprovalDecision;
+
+pub(crate) const MAX_FETCH_OUTPUT_LEN: u64 = 1024 * 1024;
 
 #[derive(Serialize)]
 struct ResultRecord {
@@ -11,15 +20,46 @@ struct ResultRecord {
     exit_code: Option<i32>,
     error: Option<String>,
     duration_ms: u128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stdout_ref: Option<OutputRef>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stderr_ref: Option<OutputRef>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    denied_by_rule: Option<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    binary_output: bool,
+    /// 操作员改写后实际执行的命令；原始命令仍在 request.json 里。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    executed_command: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    approved_by: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    denied_by: Option<String>,
+    /// 从入队到批准/拒绝的毫秒数。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    decision_latency_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    deny_reason: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    trace_id: Option<String>,
 }
 
 pub(crate) fn spawn_write_result_record(
     output_dir: Arc<PathBuf>,
     response: CommandResponse,
     duration: Duration,
+    denied_by_rule: Option<String>,
+    decision: Option<ApprovalDecision>,
 ) {
     tokio::spawn(async move {
-        write_result_record(&output_dir, &response, duration).await;
+        write_result_record(
+            &output_dir,
+            &response,
+            duration,
+            denied_by_rule,
+            decision.as_ref(),
+        )
+        .await;
         write_output_files(&output_dir, &response).await;
     });
 }
@@ -28,6 +68,8 @@ pub(crate) async fn write_result_record(
     output_dir: &Path,
     response: &CommandResponse,
     duration: Duration,
+    denied_by_rule: Option<String>,
+    decision: Option<&ApprovalDecision>,
 ) {
     let record = ResultRecord {
         id: response.id.clone(),
@@ -35,6 +77,16 @@ pub(crate) async fn write_result_record(
         exit_code: response.exit_code,
         error: response.error.clone(),
         duration_ms: duration.as_millis(),
+        stdout_ref: response.stdout_ref.clone(),
+        stderr_ref: response.stderr_ref.clone(),
+        denied_by_rule,
+        binary_output: response.binary_output(),
+        executed_command: response.executed_command.clone(),
+        approved_by: decision.and_then(ApprovalDecision::approved_by),
+        denied_by: decision.and_then(ApprovalDecision::denied_by),
+        decision_latency_ms: decision.map(|decision| decision.latency_ms),
+        deny_reason: decision.and_then(ApprovalDecision::deny_reason),
+        trace_id: response.trace_id.clone(),
     };
     let path = output_dir.join(format!("{}.result.json", response.id));
     if let Ok(payload) = serde_json::to_vec_pretty(&record) {
@@ -45,20 +97,83 @@ pub(crate) async fn write_result_record(
 }
 
 pub(crate) async fn write_output_files(output_dir: &Path, response: &CommandResponse) {
-    if let Some(stdout) = response.stdout.as_ref() {
-        let path = output_dir.join(format!("{}.stdout", response.id));
-        if let Err(err) = tokio::fs::write(path, stdout).await {
-            tracing::warn!(error = %err, "failed to write stdout output");
+    // 已有完整捕获文件时不再用（可能被截断的）内联输出覆盖。
+    if response.stdout_ref.is_none() {
+        if let Some(bytes) = inline_output_bytes(&response.stdout, &response.stdout_base64) {
+            let path = output_dir.join(format!("{}.stdout", response.id));
+            if let Err(err) = tokio::fs::write(path, bytes).await {
+                tracing::warn!(error = %err, "failed to write stdout output");
+            }
         }
     }
-    if let Some(stderr) = response.stderr.as_ref() {
-        let path = output_dir.join(format!("{}.stderr", response.id));
-        if let Err(err) = tokio::fs::write(path, stderr).await {
-            tracing::warn!(error = %err, "failed to write stderr output");
+    if response.stderr_ref.is_none() {
+        if let Some(bytes) = inline_output_bytes(&response.stderr, &response.stderr_base64) {
+            let path = output_dir.join(format!("{}.stderr", response.id));
+            if let Err(err) = tokio::fs::write(path, bytes).await {
+                tracing::warn!(error = %err, "failed to write stderr output");
+            }
         }
     }
 }
 
+// 非 UTF-8 输出优先落盘原始字节，便于通过 output 接口下载；否则写文本。
+fn inline_output_bytes(text: &Option<String>, base64: &Option<String>) -> Option<Vec<u8>> {
+    if let Some(raw) = base64
+        .as_deref()
+        .and_then(|value| BASE64_ENGINE.decode(value).ok())
+    {
+        return Some(raw);
+    }
+    text.as_ref().map(|text| text.clone().into_bytes())
+}
+
+/// 请求 id 会直接拼进审计文件名（`{id}.request.json` 等），不能为空，也不能含路径分隔符或 `..`。
+pub(crate) fn is_safe_request_id(id: &str) -> bool {
+    !id.is_empty() && !id.contains(['/', '\\']) && !id.contains("..")
+}
+
+pub(crate) fn output_file_path(
+    output_dir: &Path,
+    id: &str,
+    stream: OutputStream,
+) -> Option<PathBuf> {
+    if !is_safe_request_id(id) {
+        return None;
+    }
+    let suffix = match stream {
+        OutputStream::Stdout => "stdout",
+        OutputStream::Stderr => "stderr",
+    };
+    Some(output_dir.join(format!("{id}.{suffix}")))
+}
+
+pub(crate) async fn read_output_chunk(
+    output_dir: &Path,
+    id: &str,
+    stream: OutputStream,
+    offset: u64,
+    len: u64,
+) -> std::io::Result<OutputChunk> {
+    let path = output_file_path(output_dir, id, stream).ok_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::InvalidInput, "invalid request id")
+    })?;
+    let mut file = tokio::fs::File::open(&path).await?;
+    let total_bytes = file.metadata().await?.len();
+    let offset = offset.min(total_bytes);
+    let len = len.min(MAX_FETCH_OUTPUT_LEN).min(total_bytes - offset);
+    file.seek(SeekFrom::Start(offset)).await?;
+    let mut buf = Vec::with_capacity(len as usize);
+    file.take(len).read_to_end(&mut buf).await?;
+    Ok(OutputChunk {
+        id: id.to_string(),
+        stream,
+        offset,
+        total_bytes,
+        eof: offset + buf.len() as u64 >= total_bytes,
+        data: BASE64_ENGINE.encode(&buf),
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::super::test_utils::temp_dir;
@@ -75,10 +190,19 @@ mod tests {
             stdout: Some("ok".to_string()),
             stderr: Some("warn".to_string()),
             error: None,
+            stdout_ref: None,
+            stderr_ref: None,
+            stdout_encoding: None,
+            stderr_encoding: None,
+            stdout_base64: None,
+            stderr_base64: None,
+            modified_by_operator: false,
+            executed_command: None,
+            trace_id: None,
         };
         let rt = tokio::runtime::Runtime::new().expect("runtime");
         rt.block_on(async {
-            write_result_record(&dir, &response, Duration::from_millis(10)).await;
+            write_result_record(&dir, &response, Duration::from_millis(10), None, None).await;
             write_output_files(&dir, &response).await;
         });
         assert!(dir.join("req-1.result.json").exists());
@@ -89,4 +213,42 @@ mod tests {
         );
         fs::remove_dir_all(&dir).ok();
     }
+
+    #[test]
+    fn reads_output_chunks() {
+        let dir = temp_dir("octovalve-output-chunk");
+        fs::write(dir.join("req-1.stdout"), "hello world").unwrap();
+        let rt = tokio::runtime::Runtime::new().expect("runtime");
+        let chunk = rt
+            .block_on(read_output_chunk(
+                &dir,
+                "req-1",
+                OutputStream::Stdout,
+                6,
+                64,
+            ))
+            .expect("chunk");
+        assert_eq!(chunk.total_bytes, 11);
+        assert_eq!(chunk.offset, 6);
+        assert!(chunk.eof);
+        assert_eq!(BASE64_ENGINE.decode(chunk.data).unwrap(), b"world");
+
+        let chunk = rt
+            .block_on(read_output_chunk(&dir, "req-1", OutputStream::Stdout, 0, 5))
+            .expect("chunk");
+        assert!(!chunk.eof);
+        assert_eq!(BASE64_ENGINE.decode(chunk.data).unwrap(), b"hello");
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn rejects_path_like_ids() {
+        let dir = PathBuf::from("/tmp/octovalve");
+        assert!(output_file_path(&dir, "../etc/passwd", OutputStream::Stdout).is_none());
+        assert!(output_file_path(&dir, "a/b", OutputStream::Stderr).is_none());
+        assert_eq!(
+            output_file_path(&dir, "req-1", OutputStream::Stderr),
+            Some(dir.join("req-1.stderr"))
+        );
+    }
 }
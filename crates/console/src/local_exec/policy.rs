@@ -1,10 +1,21 @@
 use regex::Regex;
 use serde::Deserialize;
 use std::collections::{BTreeMap, HashMap, HashSet};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::SystemTime;
 
+use protocol::config::{validate_whitelist_entries, ConfigDiagnostic};
+use protocol::control::TemporaryRuleUse;
 use protocol::{CommandRequest, CommandStage};
 
+use super::quota::QuotaConfig;
+use super::redaction::RedactionConfig;
+use super::retention::RetentionConfig;
+use super::shell_parse::{parse_shell_stages, redirects_output_to_file};
+use super::time_window::{TimeWindow, TimeWindowConfig, TimeZoneSpec};
+use crate::shell_utils::shell_escape;
+
 #[derive(Debug, Clone, Deserialize)]
 pub(crate) struct PolicyConfig {
     #[serde(default)]
@@ -13,6 +24,26 @@ pub(crate) struct PolicyConfig {
     pub(crate) limits: LimitsConfig,
     #[serde(default = "default_auto_approve_allowed")]
     pub(crate) auto_approve_allowed: bool,
+    #[serde(flatten)]
+    pub(crate) intent: IntentPolicy,
+    /// 设置后命令通道每条连接的首帧必须携带该 token（见 `protocol::CommandAuth`）。
+    #[serde(default)]
+    pub(crate) control_token: Option<String>,
+    #[serde(default)]
+    pub(crate) retention: RetentionConfig,
+    #[serde(default)]
+    pub(crate) redaction: RedactionConfig,
+    #[serde(default)]
+    pub(crate) sandbox: SandboxConfig,
+    /// 只读白名单，只看 allowed/denied/arg_rules：用于向已打开的终端会话注入命令，
+    /// 以及校验声明了 `readonly = true` 的请求。
+    #[serde(default)]
+    pub(crate) readonly_whitelist: WhitelistConfig,
+    #[serde(default)]
+    pub(crate) quota: QuotaConfig,
+    /// 每个请求结束后把结果 POST 到这个地址（`--result-webhook` 优先），见 [`super::webhook`]。
+    #[serde(default)]
+    pub(crate) result_webhook: Option<String>,
 }
 
 impl PolicyConfig {
@@ -21,6 +52,319 @@ impl PolicyConfig {
         let config = toml::from_str(&content)?;
         Ok(config)
     }
+
+    /// `--check-config` 用的静态检查：错误会导致启动失败，警告只是提示规则不会按预期生效。
+    pub(crate) fn check(&self) -> PolicyCheck {
+        let whitelist = &self.whitelist;
+        let mut diagnostics = Vec::new();
+        for (section, rules) in [
+            ("whitelist", &whitelist.arg_rules),
+            ("readonly_whitelist", &self.readonly_whitelist.arg_rules),
+        ] {
+            for (command, pattern) in rules {
+                if let Err(err) = Regex::new(pattern) {
+                    diagnostics.push(ConfigDiagnostic::error(
+                        format!("{section}.arg_rules.{command}"),
+                        format!("invalid regex: {err}"),
+                    ));
+                }
+            }
+        }
+        if self
+            .control_token
+            .as_deref()
+            .is_some_and(|token| token.trim().is_empty())
+        {
+            diagnostics.push(ConfigDiagnostic::error(
+                "control_token",
+                "control_token must not be empty when set",
+            ));
+        }
+        if let Some(url) = self.result_webhook.as_deref() {
+            if let Err(err) = super::webhook::parse_webhook_url(url) {
+                diagnostics.push(ConfigDiagnostic::error(
+                    "result_webhook",
+                    format!("invalid url: {err}"),
+                ));
+            }
+        }
+        for (index, pattern) in self.redaction.env_patterns.iter().enumerate() {
+            if pattern.trim().is_empty() {
+                diagnostics.push(ConfigDiagnostic::error(
+                    format!("redaction.env_patterns[{index}]"),
+                    "pattern must not be empty",
+                ));
+            }
+        }
+        let retention = &self.retention;
+        for (key, value) in [
+            ("max_total_bytes", retention.max_total_bytes),
+            ("max_age_days", retention.max_age_days),
+            ("max_files", retention.max_files.map(|value| value as u64)),
+        ] {
+            if value == Some(0) {
+                diagnostics.push(ConfigDiagnostic::error(
+                    format!("retention.{key}"),
+                    "retention limits must be greater than 0 (omit a key to disable it)",
+                ));
+            }
+        }
+        if self.limits.timeout_secs == 0 {
+            diagnostics.push(ConfigDiagnostic::error(
+                "limits.timeout_secs",
+                "limits.timeout_secs must be greater than 0",
+            ));
+        }
+        if self.limits.max_output_bytes == 0 {
+            diagnostics.push(ConfigDiagnostic::error(
+                "limits.max_output_bytes",
+                "limits.max_output_bytes must be greater than 0",
+            ));
+        }
+        if self
+            .limits
+            .resources
+            .umask
+            .is_some_and(|umask| umask > 0o777)
+        {
+            diagnostics.push(ConfigDiagnostic::error(
+                "limits.umask",
+                "limits.umask must be between 0o000 and 0o777",
+            ));
+        }
+        let run_as = &self.limits.run_as;
+        let users = run_as
+            .run_as
+            .iter()
+            .map(|user| ("limits.run_as".to_string(), user))
+            .chain(
+                run_as
+                    .run_as_allowed
+                    .iter()
+                    .enumerate()
+                    .map(|(index, user)| (format!("limits.run_as_allowed[{index}]"), user)),
+            );
+        for (path, user) in users {
+            if user.trim().is_empty() || user.trim() != user {
+                diagnostics.push(ConfigDiagnostic::error(
+                    path,
+                    "user name must not be empty or padded",
+                ));
+            }
+        }
+        for (index, prefix) in self.limits.cwd.create_cwd_prefixes.iter().enumerate() {
+            if !(prefix.starts_with('/') || prefix == "~" || prefix.starts_with("~/"))
+                || prefix.split('/').any(|part| part == "..")
+            {
+                diagnostics.push(ConfigDiagnostic::error(
+                    format!("limits.create_cwd_prefixes[{index}]"),
+                    "prefix must be an absolute path or start with ~/ and must not contain '..'",
+                ));
+            }
+        }
+        let sandbox = &self.sandbox;
+        if sandbox.enabled
+            && sandbox
+                .wrapper
+                .first()
+                .map_or(true, |program| program.trim().is_empty())
+        {
+            diagnostics.push(ConfigDiagnostic::error(
+                "sandbox.wrapper",
+                "sandbox.enabled requires a wrapper argv such as [\"bwrap\", ...]",
+            ));
+        }
+        for (index, command) in sandbox.exempt_commands.iter().enumerate() {
+            if command.trim().is_empty() || command.contains('/') {
+                diagnostics.push(ConfigDiagnostic::error(
+                    format!("sandbox.exempt_commands[{index}]"),
+                    "exempt commands are matched by basename and must not be empty or contain '/'",
+                ));
+            }
+        }
+        if let Err(err) = TimeZoneSpec::parse(whitelist.timezone.as_deref()) {
+            diagnostics.push(ConfigDiagnostic::error("whitelist.timezone", err));
+        }
+        for (index, window) in whitelist.time_windows.iter().enumerate() {
+            if let Err(err) = TimeWindow::from_config(window) {
+                diagnostics.push(ConfigDiagnostic::error(
+                    format!("whitelist.time_windows[{index}]"),
+                    err,
+                ));
+            }
+        }
+        diagnostics.extend(self.quota.check());
+        diagnostics.extend(validate_whitelist_entries(
+            &whitelist.allowed,
+            &whitelist.denied,
+        ));
+
+        // denied 先于 allowed 判定，与之重叠（含 basename 匹配）的 allowed 条目永远不会生效。
+        let denied: HashSet<&str> = whitelist.denied.iter().map(String::as_str).collect();
+        let allowed: HashSet<&str> = whitelist.allowed.iter().map(String::as_str).collect();
+        for (index, entry) in whitelist.allowed.iter().enumerate() {
+            if let Some(rule) = matching_entry(&denied, entry) {
+                diagnostics.push(ConfigDiagnostic::warning(
+                    format!("whitelist.allowed[{index}]"),
+                    format!("allowed rule {entry:?} is shadowed by denied rule {rule:?}"),
+                ));
+            }
+        }
+        for command in whitelist.arg_rules.keys() {
+            if matching_entry(&allowed, command).is_none() {
+                diagnostics.push(ConfigDiagnostic::warning(
+                    format!("whitelist.arg_rules.{command}"),
+                    format!("has no effect: {command:?} is not in whitelist.allowed"),
+                ));
+            }
+        }
+        if !whitelist.auto_approve_tags.is_empty() && !self.auto_approve_allowed {
+            diagnostics.push(ConfigDiagnostic::warning(
+                "whitelist.auto_approve_tags",
+                "auto_approve_tags is set but auto_approve_allowed is false; nothing will be auto-approved",
+            ));
+        }
+        for (index, pattern) in whitelist.dangerous_patterns.iter().enumerate() {
+            if pattern.trim().is_empty() {
+                diagnostics.push(ConfigDiagnostic::warning(
+                    format!("whitelist.dangerous_patterns[{index}]"),
+                    "empty pattern is ignored",
+                ));
+            }
+        }
+        if !self.intent.require_intent
+            && (self.intent.min_intent_length > 0 || self.intent.banned_intents.is_some())
+        {
+            diagnostics.push(ConfigDiagnostic::warning(
+                "require_intent",
+                "min_intent_length/banned_intents have no effect unless require_intent = true",
+            ));
+        }
+        if self.auto_approve_allowed && whitelist.allowed.is_empty() {
+            diagnostics.push(ConfigDiagnostic::warning(
+                "auto_approve_allowed",
+                "auto_approve_allowed is set but whitelist.allowed is empty; nothing will be auto-approved",
+            ));
+        }
+        let (errors, warnings) = diagnostics
+            .into_iter()
+            .partition(|diagnostic| diagnostic.is_error());
+        PolicyCheck {
+            allowed: whitelist.allowed.len(),
+            denied: whitelist.denied.len(),
+            arg_rules: whitelist.arg_rules.len(),
+            errors,
+            warnings,
+        }
+    }
+}
+
+pub(crate) struct PolicyCheck {
+    pub(crate) allowed: usize,
+    pub(crate) denied: usize,
+    pub(crate) arg_rules: usize,
+    pub(crate) errors: Vec<ConfigDiagnostic>,
+    pub(crate) warnings: Vec<ConfigDiagnostic>,
+}
+
+/// 请求处理时生效的白名单、limits、intent 与 quota 规则。
+#[derive(Clone)]
+pub(crate) struct ActivePolicy {
+    pub(crate) whitelist: Arc<Whitelist>,
+    pub(crate) limits: Arc<LimitsConfig>,
+    pub(crate) intent: Arc<IntentPolicy>,
+    pub(crate) sandbox: Arc<SandboxConfig>,
+    pub(crate) readonly_whitelist: Arc<Whitelist>,
+    pub(crate) quota: Arc<QuotaConfig>,
+    /// 为 false 时标签与定时任务都不会触发自动批准。
+    pub(crate) auto_approve_allowed: bool,
+}
+
+impl ActivePolicy {
+    pub(crate) fn from_config(config: &PolicyConfig) -> anyhow::Result<Self> {
+        let mut whitelist = config.whitelist.clone();
+        if !config.auto_approve_allowed {
+            whitelist.auto_approve_tags.clear();
+        }
+        Ok(Self {
+            whitelist: Arc::new(Whitelist::from_config(&whitelist)?),
+            limits: Arc::new(config.limits.clone()),
+            intent: Arc::new(config.intent.clone()),
+            sandbox: Arc::new(config.sandbox.clone()),
+            readonly_whitelist: Arc::new(Whitelist::from_config(&config.readonly_whitelist)?),
+            quota: Arc::new(config.quota.clone()),
+            auto_approve_allowed: config.auto_approve_allowed,
+        })
+    }
+
+    /// 定时任务的 auto_approve 不看 intent 标签，但 pipeline 仍须全部通过 allowed/arg_rules。
+    pub(crate) fn approves_preapproved(&self, request: &CommandRequest) -> bool {
+        self.auto_approve_allowed
+            && self.whitelist.allows_request(request)
+            && !self.whitelist.requires_confirmation(request)
+    }
+
+    /// 声明 `readonly` 且通过 readonly_whitelist 的请求自动批准；dangerous_patterns 仍要人工确认。
+    pub(crate) fn approves_readonly(&self, request: &CommandRequest) -> bool {
+        self.auto_approve_allowed
+            && request.readonly
+            && readonly_request_denial(&self.readonly_whitelist, request).is_none()
+            && !self.whitelist.requires_confirmation(request)
+    }
+}
+
+/// `--broker-config` 中可热重载的部分。每个请求按处理当时的快照校验与执行，重载只影响之后的请求；
+/// `control_token`、`retention`、`redaction`、`result_webhook` 仍在启动时确定。
+pub(crate) struct LivePolicy {
+    path: PathBuf,
+    current: RwLock<ActivePolicy>,
+}
+
+impl LivePolicy {
+    pub(crate) fn new(path: PathBuf, active: ActivePolicy) -> Self {
+        Self {
+            path,
+            current: RwLock::new(active),
+        }
+    }
+
+    pub(crate) fn current(&self) -> ActivePolicy {
+        self.current
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// 重新读取配置文件并整体替换；解析或校验失败时保留旧配置并返回错误，成功时返回检查警告。
+    pub(crate) fn reload(&self) -> Result<Vec<String>, String> {
+        let config = PolicyConfig::load(&self.path)
+            .map_err(|err| format!("failed to load {}: {err}", self.path.display()))?;
+        let check = config.check();
+        if !check.errors.is_empty() {
+            return Err(join_diagnostics(&check.errors));
+        }
+        let active = ActivePolicy::from_config(&config).map_err(|err| err.to_string())?;
+        *self.current.write().unwrap_or_else(PoisonError::into_inner) = active;
+        Ok(check.warnings.iter().map(ToString::to_string).collect())
+    }
+}
+
+pub(crate) fn join_diagnostics(diagnostics: &[ConfigDiagnostic]) -> String {
+    diagnostics
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+fn matching_entry<'a>(entries: &HashSet<&'a str>, command: &str) -> Option<&'a str> {
+    if let Some(entry) = entries.get(command) {
+        return Some(entry);
+    }
+    Path::new(command)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| entries.get(name).copied())
 }
 
 #[derive(Debug, Clone, Deserialize, Default)]
@@ -31,12 +375,137 @@ pub(crate) struct WhitelistConfig {
     pub(crate) denied: Vec<String>,
     #[serde(default)]
     pub(crate) arg_rules: BTreeMap<String, String>,
+    /// 非空时，带任一标签且整条 pipeline 命中 allowed 的请求会被 console 自动批准。
+    #[serde(default)]
+    pub(crate) auto_approve_tags: Vec<String>,
+    /// 命中的请求审批时必须回显请求 id 确认，且不会被自动批准。
+    #[serde(default)]
+    pub(crate) dangerous_patterns: Vec<String>,
+    /// console 无法从 raw_command 解析出各段命令时的处理方式。
+    #[serde(default)]
+    pub(crate) on_unparseable: UnparseablePolicy,
+    #[serde(default)]
+    pub(crate) hard_deny: HardDenyConfig,
+    /// 按命令或 intent 标签限制可执行的时段；窗外的请求不会被自动批准。
+    #[serde(default)]
+    pub(crate) time_windows: Vec<TimeWindowConfig>,
+    /// 时间窗使用的时区，缺省为 console 主机的本地时间。
+    #[serde(default)]
+    pub(crate) timezone: Option<String>,
+}
+
+/// 与 denied 一样直接拒绝；`immutable = true` 时临时放行规则也无法解除。
+#[derive(Debug, Clone, Deserialize, Default)]
+pub(crate) struct HardDenyConfig {
+    #[serde(default)]
+    pub(crate) commands: Vec<String>,
+    #[serde(default)]
+    pub(crate) immutable: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum UnparseablePolicy {
+    /// 直接拒绝。
+    #[default]
+    Deny,
+    /// 进入人工审批并标记为无法解析，不会被自动批准。
+    Manual,
+}
+
+const DEFAULT_BANNED_INTENTS: &[&str] = &[
+    "run command",
+    "run a command",
+    "run",
+    "command",
+    "execute",
+    "execute command",
+    "test",
+    "n/a",
+    "none",
+    "todo",
+];
+
+// intent 质量校验：agent 经常把 intent 填成 "run command"，审计时毫无信息量。
+#[derive(Debug, Clone, Default, Deserialize)]
+pub(crate) struct IntentPolicy {
+    #[serde(default)]
+    pub(crate) require_intent: bool,
+    #[serde(default)]
+    pub(crate) min_intent_length: usize,
+    /// 未配置时使用内置的占位词列表；比较时忽略大小写与多余空白。
+    #[serde(default)]
+    pub(crate) banned_intents: Option<Vec<String>>,
+}
+
+impl IntentPolicy {
+    pub(crate) fn validate(&self, intent: &str) -> Result<(), PolicyDenial> {
+        if !self.require_intent {
+            return Ok(());
+        }
+        let normalized = normalize_intent(intent);
+        if normalized.is_empty() {
+            return Err(intent_denial("intent is empty", "require_intent"));
+        }
+        let length = normalized.chars().count();
+        if length < self.min_intent_length {
+            return Err(intent_denial(
+                &format!(
+                    "intent is too short ({length} < {} characters)",
+                    self.min_intent_length
+                ),
+                "min_intent_length",
+            ));
+        }
+        let banned = match &self.banned_intents {
+            Some(list) => list
+                .iter()
+                .any(|entry| normalize_intent(entry) == normalized),
+            None => DEFAULT_BANNED_INTENTS.contains(&normalized.as_str()),
+        };
+        if banned {
+            return Err(intent_denial(
+                &format!("intent {intent:?} is a placeholder"),
+                "banned_intents",
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn normalize_intent(intent: &str) -> String {
+    intent
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+fn intent_denial(reason: &str, rule: &str) -> PolicyDenial {
+    PolicyDenial {
+        message: format!(
+            "{reason}; provide a meaningful intent that explains why this command is needed"
+        ),
+        rule: Some(format!("intent.{rule}")),
+    }
 }
 
 #[derive(Debug, Clone, Deserialize)]
 pub(crate) struct LimitsConfig {
     pub(crate) timeout_secs: u64,
     pub(crate) max_output_bytes: u64,
+    /// 响应、快照与历史里的输出折叠 `\r` 覆盖并去掉 ANSI 控制序列；落盘的 `<id>.stdout`/`<id>.stderr` 保持原样。
+    /// 截断仍按原始字节计算。
+    #[serde(default = "default_normalize_output")]
+    pub(crate) normalize_output: bool,
+    #[serde(flatten)]
+    pub(crate) resources: ResourceLimits,
+    #[serde(flatten)]
+    pub(crate) run_as: RunAsConfig,
+    #[serde(flatten)]
+    pub(crate) cwd: CwdConfig,
+    #[serde(flatten)]
+    pub(crate) request: RequestLimits,
 }
 
 impl Default for LimitsConfig {
@@ -44,7 +513,225 @@ impl Default for LimitsConfig {
         Self {
             timeout_secs: 30,
             max_output_bytes: 1024 * 1024,
+            normalize_output: true,
+            resources: ResourceLimits::default(),
+            run_as: RunAsConfig::default(),
+            cwd: CwdConfig::default(),
+            request: RequestLimits::default(),
+        }
+    }
+}
+
+// 远端命令的资源限制：命令经 ssh 在目标机执行，因此通过远端 shell 的 nice/ulimit 生效，
+// 而不是作用在本地 ssh 进程上。
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub(crate) struct ResourceLimits {
+    #[serde(default)]
+    pub(crate) nice: Option<i32>,
+    #[serde(default)]
+    pub(crate) max_cpu_secs: Option<u64>,
+    #[serde(default)]
+    pub(crate) max_memory_bytes: Option<u64>,
+    #[serde(default)]
+    pub(crate) max_file_size_bytes: Option<u64>,
+    /// 远端 shell 的 umask，TOML 中写作 `0o027`。
+    #[serde(default)]
+    pub(crate) umask: Option<u32>,
+}
+
+/// 以其他账号执行已批准的命令：远端命令外包一层 `sudo -n -u`（或 `doas -n -u`），
+/// 目标机需为 SSH 登录用户配置免密提权。未配置时沿用 SSH 登录用户。
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub(crate) struct RunAsConfig {
+    /// 请求未指定 run_as 时使用的账号。
+    #[serde(default)]
+    pub(crate) run_as: Option<String>,
+    /// 请求可以自选的账号；为空时只接受 `run_as` 本身。
+    #[serde(default)]
+    pub(crate) run_as_allowed: Vec<String>,
+    #[serde(default)]
+    pub(crate) run_as_command: RunAsCommand,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum RunAsCommand {
+    #[default]
+    Sudo,
+    Doas,
+}
+
+impl RunAsConfig {
+    /// 返回实际执行账号；None 表示沿用 SSH 登录用户。
+    pub(crate) fn resolve(&self, requested: Option<&str>) -> Result<Option<String>, PolicyDenial> {
+        let Some(user) = requested.map(str::trim).filter(|user| !user.is_empty()) else {
+            return Ok(self.run_as.clone());
+        };
+        if self.run_as.as_deref() == Some(user) || self.run_as_allowed.iter().any(|u| u == user) {
+            return Ok(Some(user.to_string()));
+        }
+        Err(PolicyDenial {
+            message: format!("run_as {user:?} is not allowed by limits.run_as_allowed"),
+            rule: Some("limits.run_as_allowed".to_string()),
+        })
+    }
+
+    /// 放在远端 `bash` 前面的提权前缀，末尾带空格。
+    pub(crate) fn command_prefix(&self, user: &str) -> String {
+        let user = shell_escape(user);
+        match self.run_as_command {
+            RunAsCommand::Sudo => format!("sudo -n -u {user} -- "),
+            RunAsCommand::Doas => format!("doas -n -u {user} "),
+        }
+    }
+}
+
+/// 请求带 `create_cwd` 时是否允许 broker 在执行前 `mkdir -p` 工作目录；默认不允许，cwd 不存在即报错。
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub(crate) struct CwdConfig {
+    #[serde(default)]
+    pub(crate) allow_create_cwd: bool,
+    /// 只允许在这些目录（含自身）下创建；为空时不限制。`~` 按字面比较。
+    #[serde(default)]
+    pub(crate) create_cwd_prefixes: Vec<String>,
+}
+
+impl CwdConfig {
+    pub(crate) fn allows_create(&self, cwd: &str) -> bool {
+        if !self.allow_create_cwd || cwd.split('/').any(|part| part == "..") {
+            return false;
+        }
+        if self.create_cwd_prefixes.is_empty() {
+            return true;
         }
+        let cwd = cwd.trim_end_matches('/');
+        self.create_cwd_prefixes.iter().any(|prefix| {
+            let prefix = prefix.trim_end_matches('/');
+            cwd == prefix
+                || cwd
+                    .strip_prefix(prefix)
+                    .is_some_and(|rest| rest.starts_with('/'))
+        })
+    }
+}
+
+/// 请求本身的规模上限，在白名单校验之前检查，避免超大的 pipeline/argv 拖慢校验与界面渲染。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub(crate) struct RequestLimits {
+    #[serde(default = "default_max_pipeline_stages")]
+    pub(crate) max_pipeline_stages: usize,
+    #[serde(default = "default_max_args_per_stage")]
+    pub(crate) max_args_per_stage: usize,
+    #[serde(default = "default_max_raw_command_bytes")]
+    pub(crate) max_raw_command_bytes: usize,
+}
+
+impl Default for RequestLimits {
+    fn default() -> Self {
+        Self {
+            max_pipeline_stages: default_max_pipeline_stages(),
+            max_args_per_stage: default_max_args_per_stage(),
+            max_raw_command_bytes: default_max_raw_command_bytes(),
+        }
+    }
+}
+
+impl RequestLimits {
+    pub(crate) fn raw_command_denial(&self, raw_command: &str) -> Option<PolicyDenial> {
+        (raw_command.len() > self.max_raw_command_bytes).then(|| PolicyDenial {
+            message: format!(
+                "raw_command is {} bytes, limit is {}",
+                raw_command.len(),
+                self.max_raw_command_bytes
+            ),
+            rule: Some("limits.max_raw_command_bytes".to_string()),
+        })
+    }
+
+    pub(crate) fn pipeline_denial(&self, pipeline: &[CommandStage]) -> Option<PolicyDenial> {
+        if pipeline.len() > self.max_pipeline_stages {
+            return Some(PolicyDenial {
+                message: format!(
+                    "pipeline has {} stages, limit is {}",
+                    pipeline.len(),
+                    self.max_pipeline_stages
+                ),
+                rule: Some("limits.max_pipeline_stages".to_string()),
+            });
+        }
+        let (index, stage) = pipeline
+            .iter()
+            .enumerate()
+            .find(|(_, stage)| stage.argv.len() > self.max_args_per_stage)?;
+        Some(PolicyDenial {
+            message: format!(
+                "pipeline stage {} has {} arguments, limit is {}",
+                index + 1,
+                stage.argv.len(),
+                self.max_args_per_stage
+            ),
+            rule: Some("limits.max_args_per_stage".to_string()),
+        })
+    }
+}
+
+/// 已批准命令在远端外包一层沙箱：`wrapper` 是放在 `bash` 前面的 argv 前缀（bwrap/firejail/nsjail 等），
+/// pipeline 各段都属于 `exempt_commands`（按 basename）时不包装。Windows 目标不生效。
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub(crate) struct SandboxConfig {
+    #[serde(default)]
+    pub(crate) enabled: bool,
+    #[serde(default)]
+    pub(crate) wrapper: Vec<String>,
+    #[serde(default)]
+    pub(crate) exempt_commands: Vec<String>,
+}
+
+impl SandboxConfig {
+    /// 启用时需要在目标机上存在的 wrapper 程序。
+    pub(crate) fn program(&self) -> Option<&str> {
+        self.wrapper
+            .first()
+            .map(String::as_str)
+            .filter(|program| self.enabled && !program.trim().is_empty())
+    }
+
+    /// 目标探测时执行的远端命令：启用后 wrapper 不存在即探测失败，目标标记为 down。
+    pub(crate) fn probe_command(&self) -> String {
+        match self.program() {
+            Some(program) => format!(
+                "command -v {} >/dev/null 2>&1 || {{ echo {} >&2; exit 127; }}",
+                shell_escape(program),
+                shell_escape(&format!("sandbox wrapper not found: {program}"))
+            ),
+            None => "true".to_string(),
+        }
+    }
+
+    /// 该请求实际使用的 wrapper argv；未启用或整条 pipeline 豁免时为 None。
+    pub(crate) fn wrapper_for(&self, request: &CommandRequest) -> Option<&[String]> {
+        self.program()?;
+        let exempt = !request.pipeline.is_empty()
+            && request.pipeline.iter().all(|stage| {
+                stage.argv.first().is_some_and(|program| {
+                    let name = Path::new(program)
+                        .file_name()
+                        .and_then(|name| name.to_str())
+                        .unwrap_or(program);
+                    self.exempt_commands.iter().any(|command| command == name)
+                })
+            });
+        (!exempt).then_some(self.wrapper.as_slice())
+    }
+
+    /// 放在远端 `bash` 前面的沙箱前缀，末尾带空格。
+    pub(crate) fn command_prefix(&self, request: &CommandRequest) -> String {
+        self.wrapper_for(request)
+            .map(|wrapper| {
+                let argv: Vec<String> = wrapper.iter().map(|arg| shell_escape(arg)).collect();
+                format!("{} ", argv.join(" "))
+            })
+            .unwrap_or_default()
     }
 }
 
@@ -52,13 +739,92 @@ fn default_auto_approve_allowed() -> bool {
     true
 }
 
+fn default_normalize_output() -> bool {
+    true
+}
+
+fn default_max_pipeline_stages() -> usize {
+    64
+}
+
+fn default_max_args_per_stage() -> usize {
+    1024
+}
+
+fn default_max_raw_command_bytes() -> usize {
+    256 * 1024
+}
+
 #[derive(Debug, Clone)]
 pub(crate) struct Whitelist {
-    #[allow(dead_code)]
     allowed: HashSet<String>,
     denied: HashSet<String>,
-    #[allow(dead_code)]
     arg_rules: HashMap<String, Regex>,
+    auto_approve_tags: Vec<String>,
+    dangerous_patterns: Vec<DangerousPattern>,
+    on_unparseable: UnparseablePolicy,
+    hard_denied: HashSet<String>,
+    hard_deny_immutable: bool,
+    time_windows: Vec<TimeWindow>,
+    timezone: TimeZoneSpec,
+}
+
+/// 请求命中了某条时间窗但当前不在窗内。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct OutsideWindow {
+    pub(crate) rule: String,
+    pub(crate) deny: bool,
+}
+
+#[derive(Debug, Clone)]
+struct DangerousPattern {
+    text: String,
+    tokens: Vec<String>,
+}
+
+impl DangerousPattern {
+    fn parse(value: &str) -> Option<Self> {
+        let text = value.trim().to_lowercase();
+        if text.is_empty() {
+            return None;
+        }
+        let tokens = text.split_whitespace().map(str::to_string).collect();
+        Some(Self { text, tokens })
+    }
+
+    // 优先按 pipeline 各段匹配：argv（首项取 basename）中连续出现全部 token，或某个参数包含整段文本；
+    // 没有 pipeline 时退化为 raw_command 子串匹配。
+    fn matches(&self, request: &CommandRequest) -> bool {
+        if request.pipeline.is_empty() {
+            return request.raw_command.to_lowercase().contains(&self.text);
+        }
+        request
+            .pipeline
+            .iter()
+            .any(|stage| self.matches_stage(stage))
+    }
+
+    fn matches_stage(&self, stage: &CommandStage) -> bool {
+        let argv: Vec<String> = stage
+            .argv
+            .iter()
+            .enumerate()
+            .map(|(index, arg)| {
+                let arg = if index == 0 {
+                    Path::new(arg)
+                        .file_name()
+                        .and_then(|name| name.to_str())
+                        .unwrap_or(arg)
+                } else {
+                    arg
+                };
+                arg.to_lowercase()
+            })
+            .collect();
+        argv.windows(self.tokens.len())
+            .any(|window| window == self.tokens.as_slice())
+            || argv.iter().any(|arg| arg.contains(&self.text))
+    }
 }
 
 impl Whitelist {
@@ -69,14 +835,66 @@ impl Whitelist {
                 .map_err(|err| anyhow::anyhow!("invalid regex for {command}: {err}"))?;
             arg_rules.insert(command.to_string(), regex);
         }
+        let time_windows = config
+            .time_windows
+            .iter()
+            .enumerate()
+            .map(|(index, window)| {
+                TimeWindow::from_config(window)
+                    .map_err(|err| anyhow::anyhow!("whitelist.time_windows[{index}]: {err}"))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        let timezone = TimeZoneSpec::parse(config.timezone.as_deref())
+            .map_err(|err| anyhow::anyhow!("whitelist.timezone: {err}"))?;
         Ok(Self {
             allowed: config.allowed.iter().cloned().collect(),
             denied: config.denied.iter().cloned().collect(),
             arg_rules,
+            auto_approve_tags: protocol::normalize_intent_tags(config.auto_approve_tags.clone()),
+            dangerous_patterns: config
+                .dangerous_patterns
+                .iter()
+                .filter_map(|pattern| DangerousPattern::parse(pattern))
+                .collect(),
+            on_unparseable: config.on_unparseable,
+            hard_denied: config.hard_deny.commands.iter().cloned().collect(),
+            hard_deny_immutable: config.hard_deny.immutable,
+            time_windows,
+            timezone,
         })
     }
 
-    #[allow(dead_code)]
+    /// 找出请求命中、但 `now` 不在其中的时间窗；同时命中多条时优先返回 `deny_outside_window` 的那条。
+    pub(crate) fn outside_window(
+        &self,
+        request: &CommandRequest,
+        now: SystemTime,
+    ) -> Option<OutsideWindow> {
+        if self.time_windows.is_empty() {
+            return None;
+        }
+        let clock = self.timezone.clock(now);
+        self.time_windows
+            .iter()
+            .enumerate()
+            .filter(|(_, window)| window.applies_to(request) && !window.contains(&clock))
+            .map(|(index, window)| OutsideWindow {
+                rule: format!("whitelist.time_windows[{index}]: {}", window.description),
+                deny: window.deny,
+            })
+            .reduce(|first, next| {
+                if !first.deny && next.deny {
+                    next
+                } else {
+                    first
+                }
+            })
+    }
+
+    pub(crate) fn on_unparseable(&self) -> UnparseablePolicy {
+        self.on_unparseable
+    }
+
     pub(crate) fn validate_allow(&self, stage: &CommandStage) -> Result<(), String> {
         let command = stage.command().ok_or_else(|| "empty command".to_string())?;
         if !self.is_allowed(command) {
@@ -101,12 +919,17 @@ impl Whitelist {
 
     pub(crate) fn validate_deny(&self, stage: &CommandStage) -> Result<(), String> {
         let command = stage.command().ok_or_else(|| "empty command".to_string())?;
-        if self.is_denied(command) {
+        if self.denied_rule(command).is_some() || self.hard_denied_rule(command).is_some() {
             return Err(format!("command denied: {command}"));
         }
         Ok(())
     }
 
+    /// 命中 `hard_deny.immutable` 的命令不能添加临时放行规则。
+    pub(crate) fn is_immutable_denied(&self, command: &str) -> bool {
+        self.hard_deny_immutable && self.hard_denied_rule(command).is_some()
+    }
+
     pub(crate) fn allows_request(&self, request: &CommandRequest) -> bool {
         if self.allowed.is_empty() {
             return false;
@@ -120,7 +943,21 @@ impl Whitelist {
             .all(|stage| self.validate_allow(stage).is_ok())
     }
 
-    #[allow(dead_code)]
+    /// 自动批准同时要求：带 auto_approve_tags 中的标签，且 pipeline 全部通过 allowed/arg_rules。
+    pub(crate) fn auto_approves(&self, request: &CommandRequest) -> bool {
+        self.auto_approve_tags
+            .iter()
+            .any(|tag| request.has_intent_tag(tag))
+            && self.allows_request(request)
+            && !self.requires_confirmation(request)
+    }
+
+    pub(crate) fn requires_confirmation(&self, request: &CommandRequest) -> bool {
+        self.dangerous_patterns
+            .iter()
+            .any(|pattern| pattern.matches(request))
+    }
+
     fn is_allowed(&self, command: &str) -> bool {
         if self.allowed.contains(command) {
             return true;
@@ -131,14 +968,23 @@ impl Whitelist {
         false
     }
 
-    fn is_denied(&self, command: &str) -> bool {
-        if self.denied.contains(command) {
-            return true;
+    /// 返回命中的 denied 条目（原样取自配置），用于在历史记录中标注由哪条规则拒绝。
+    fn denied_rule(&self, command: &str) -> Option<&str> {
+        if let Some(rule) = self.denied.get(command) {
+            return Some(rule);
         }
-        if let Some(name) = self.basename(command) {
-            return self.denied.contains(name);
+        self.basename(command)
+            .and_then(|name| self.denied.get(name))
+            .map(String::as_str)
+    }
+
+    fn hard_denied_rule(&self, command: &str) -> Option<&str> {
+        if let Some(rule) = self.hard_denied.get(command) {
+            return Some(rule);
         }
-        false
+        self.basename(command)
+            .and_then(|name| self.hard_denied.get(name))
+            .map(String::as_str)
     }
 
     fn basename<'a>(&self, command: &'a str) -> Option<&'a str> {
@@ -148,43 +994,183 @@ impl Whitelist {
     }
 }
 
-pub(crate) fn deny_message(whitelist: &Whitelist, request: &CommandRequest) -> Option<String> {
+#[derive(Debug)]
+pub(crate) struct PolicyDenial {
+    pub(crate) message: String,
+    pub(crate) rule: Option<String>,
+}
+
+/// 终端注入前的只读校验：必须是单行、不含输出重定向，且按 raw 命令解析出的每一段都命中
+/// readonly_whitelist 的 allowed（及 arg_rules）、不命中 denied。返回拒绝原因。
+pub(crate) fn readonly_denial(whitelist: &Whitelist, command: &str) -> Option<String> {
+    if command.contains(['\n', '\r']) {
+        return Some("command must be a single line".to_string());
+    }
+    if command.contains('>') {
+        return Some("output redirection is not allowed".to_string());
+    }
+    let stages = match parse_shell_stages(command) {
+        Ok(stages) => stages,
+        Err(err) => return Some(format!("command not parseable: {err}")),
+    };
+    stages.iter().find_map(|stage| {
+        whitelist
+            .validate_deny(stage)
+            .and_then(|()| whitelist.validate_allow(stage))
+            .err()
+    })
+}
+
+/// 声明了 `readonly = true` 的请求：必须是单行、不含 heredoc/here-string，pipeline 每段都要命中
+/// readonly_whitelist 的 allowed（及 arg_rules）、不命中 denied，且不能把输出重定向到文件。返回不符的原因。
+pub(crate) fn readonly_request_denial(
+    whitelist: &Whitelist,
+    request: &CommandRequest,
+) -> Option<String> {
+    if request.pipeline.is_empty() {
+        return Some("command not parseable".to_string());
+    }
+    if request.raw_command.contains(['\n', '\r']) {
+        return Some("command must be a single line".to_string());
+    }
+    if request.raw_command.contains("<<") {
+        return Some("heredoc and here-string input are not allowed".to_string());
+    }
+    if redirects_output_to_file(&request.raw_command) {
+        return Some("output redirection to a file is not read-only".to_string());
+    }
+    request.pipeline.iter().find_map(|stage| {
+        whitelist
+            .validate_deny(stage)
+            .and_then(|()| whitelist.validate_allow(stage))
+            .err()
+    })
+}
+
+/// 按 denied / hard_deny 检查 pipeline 各段。命中的段若有临时放行规则（`temporary` 按命令查找）则放行，
+/// 通过时返回用到的规则；immutable 的 hard_deny 不看临时规则。
+pub(crate) fn policy_denial(
+    whitelist: &Whitelist,
+    request: &CommandRequest,
+    temporary: impl Fn(&str) -> Option<TemporaryRuleUse>,
+) -> Result<Vec<TemporaryRuleUse>, PolicyDenial> {
+    let mut used: Vec<TemporaryRuleUse> = Vec::new();
     for stage in &request.pipeline {
-        if let Err(message) = whitelist.validate_deny(stage) {
-            return Some(message);
+        let Some(command) = stage.command() else {
+            return Err(PolicyDenial {
+                message: "empty command".to_string(),
+                rule: None,
+            });
+        };
+        let (rule, unlockable) = match (
+            whitelist.hard_denied_rule(command),
+            whitelist.denied_rule(command),
+        ) {
+            (Some(rule), _) => (
+                format!("whitelist.hard_deny: {rule}"),
+                !whitelist.hard_deny_immutable,
+            ),
+            (None, Some(rule)) => (format!("whitelist.denied: {rule}"), true),
+            (None, None) => continue,
+        };
+        match unlockable.then(|| temporary(command)).flatten() {
+            Some(rule_use) => {
+                if !used.iter().any(|existing| existing.id == rule_use.id) {
+                    used.push(rule_use);
+                }
+            }
+            None => {
+                return Err(PolicyDenial {
+                    message: format!("command denied: {command}"),
+                    rule: Some(rule),
+                });
+            }
         }
     }
-    None
+    Ok(used)
 }
 
+// 日志摘要只展示有限的段数与参数，超大请求不会刷屏。
+const SUMMARY_MAX_STAGES: usize = 10;
+const SUMMARY_MAX_ARGS: usize = 32;
+const SUMMARY_MAX_RAW_BYTES: usize = 1024;
+
 pub(crate) fn request_summary(request: &CommandRequest) -> String {
     let pipeline = format_pipeline(&request.pipeline);
     if pipeline.is_empty() {
-        request.raw_command.clone()
+        truncate_summary(&request.raw_command)
     } else {
         pipeline
     }
 }
 
 fn format_pipeline(pipeline: &[CommandStage]) -> String {
-    pipeline
+    let mut summary = pipeline
         .iter()
-        .map(|stage| stage.argv.join(" "))
+        .take(SUMMARY_MAX_STAGES)
+        .map(|stage| {
+            let mut argv = stage.argv[..stage.argv.len().min(SUMMARY_MAX_ARGS)].join(" ");
+            if stage.argv.len() > SUMMARY_MAX_ARGS {
+                argv.push_str(&format!(
+                    " ... (+{} args)",
+                    stage.argv.len() - SUMMARY_MAX_ARGS
+                ));
+            }
+            argv
+        })
         .collect::<Vec<_>>()
-        .join(" | ")
+        .join(" | ");
+    if pipeline.len() > SUMMARY_MAX_STAGES {
+        summary.push_str(&format!(
+            " | ... (pipeline: {} stages, showing first {SUMMARY_MAX_STAGES})",
+            pipeline.len()
+        ));
+    }
+    summary
+}
+
+fn truncate_summary(raw_command: &str) -> String {
+    if raw_command.len() <= SUMMARY_MAX_RAW_BYTES {
+        return raw_command.to_string();
+    }
+    let mut end = SUMMARY_MAX_RAW_BYTES;
+    while !raw_command.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{} ... ({} bytes)", &raw_command[..end], raw_command.len())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn test_request(raw_command: &str) -> CommandRequest {
+        CommandRequest {
+            id: "req".to_string(),
+            client: "client".to_string(),
+            target: "dev".to_string(),
+            intent: "inspect".to_string(),
+            raw_command: raw_command.to_string(),
+            pipeline: raw_command
+                .split('|')
+                .map(|stage| CommandStage {
+                    argv: stage.split_whitespace().map(str::to_string).collect(),
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn whitelist_config(allowed: &[&str]) -> WhitelistConfig {
+        WhitelistConfig {
+            allowed: allowed.iter().map(|command| command.to_string()).collect(),
+            ..WhitelistConfig::default()
+        }
+    }
+
     #[test]
     fn allows_exact_command() {
-        let config = WhitelistConfig {
-            allowed: vec!["ls".to_string()],
-            denied: Vec::new(),
-            arg_rules: BTreeMap::new(),
-        };
+        let config = whitelist_config(&["ls"]);
         let whitelist = Whitelist::from_config(&config).expect("whitelist");
         let stage = CommandStage {
             argv: vec!["ls".to_string(), "-l".to_string()],
@@ -194,11 +1180,7 @@ mod tests {
 
     #[test]
     fn allows_basename_match() {
-        let config = WhitelistConfig {
-            allowed: vec!["grep".to_string()],
-            denied: Vec::new(),
-            arg_rules: BTreeMap::new(),
-        };
+        let config = whitelist_config(&["grep"]);
         let whitelist = Whitelist::from_config(&config).expect("whitelist");
         let stage = CommandStage {
             argv: vec!["/usr/bin/grep".to_string(), "foo".to_string()],
@@ -208,11 +1190,7 @@ mod tests {
 
     #[test]
     fn rejects_disallowed_command() {
-        let config = WhitelistConfig {
-            allowed: vec!["ls".to_string()],
-            denied: Vec::new(),
-            arg_rules: BTreeMap::new(),
-        };
+        let config = whitelist_config(&["ls"]);
         let whitelist = Whitelist::from_config(&config).expect("whitelist");
         let stage = CommandStage {
             argv: vec!["rm".to_string(), "-rf".to_string(), "/".to_string()],
@@ -225,9 +1203,8 @@ mod tests {
         let mut arg_rules = BTreeMap::new();
         arg_rules.insert("grep".to_string(), "^[A-Za-z0-9_\\.-]+$".to_string());
         let config = WhitelistConfig {
-            allowed: vec!["grep".to_string()],
-            denied: Vec::new(),
             arg_rules,
+            ..whitelist_config(&["grep"])
         };
         let whitelist = Whitelist::from_config(&config).expect("whitelist");
         let ok_stage = CommandStage {
@@ -243,9 +1220,8 @@ mod tests {
     #[test]
     fn rejects_denied_command() {
         let config = WhitelistConfig {
-            allowed: vec!["ls".to_string()],
             denied: vec!["rm".to_string()],
-            arg_rules: BTreeMap::new(),
+            ..whitelist_config(&["ls"])
         };
         let whitelist = Whitelist::from_config(&config).expect("whitelist");
         let stage = CommandStage {
@@ -257,14 +1233,492 @@ mod tests {
     #[test]
     fn rejects_denied_basename() {
         let config = WhitelistConfig {
-            allowed: vec!["/bin/ls".to_string()],
             denied: vec!["rm".to_string()],
-            arg_rules: BTreeMap::new(),
+            ..whitelist_config(&["/bin/ls"])
         };
         let whitelist = Whitelist::from_config(&config).expect("whitelist");
         let stage = CommandStage {
             argv: vec!["/bin/rm".to_string(), "-rf".to_string(), "/".to_string()],
         };
         assert!(whitelist.validate_deny(&stage).is_err());
+        assert_eq!(whitelist.denied_rule("/bin/rm"), Some("rm"));
+    }
+
+    #[test]
+    fn auto_approves_only_tagged_allowed_requests() {
+        let config = WhitelistConfig {
+            auto_approve_tags: vec!["Read-Only".to_string()],
+            ..whitelist_config(&["ls", "cat"])
+        };
+        let whitelist = Whitelist::from_config(&config).expect("whitelist");
+        let mut request = test_request("ls -l | cat");
+        assert!(!whitelist.auto_approves(&request));
+        request.intent_tags = vec!["read-only".to_string()];
+        assert!(whitelist.auto_approves(&request));
+        request.pipeline.push(CommandStage {
+            argv: vec!["rm".to_string()],
+        });
+        assert!(!whitelist.auto_approves(&request));
+    }
+
+    #[test]
+    fn dangerous_patterns_match_stages_then_raw_command() {
+        let config = WhitelistConfig {
+            auto_approve_tags: vec!["read-only".to_string()],
+            dangerous_patterns: vec!["rm -rf".to_string(), "DROP DATABASE".to_string()],
+            ..whitelist_config(&["rm"])
+        };
+        let whitelist = Whitelist::from_config(&config).expect("whitelist");
+        assert!(whitelist.requires_confirmation(&test_request("/bin/rm -rf /tmp/x")));
+        assert!(!whitelist.requires_confirmation(&test_request("rm -r -f /tmp/x")));
+        let mut request = test_request("psql -c");
+        request.pipeline[0]
+            .argv
+            .push("drop database prod".to_string());
+        assert!(whitelist.requires_confirmation(&request));
+
+        let mut raw_only = test_request("");
+        raw_only.pipeline.clear();
+        raw_only.raw_command = "echo ok; rm -rf /".to_string();
+        assert!(whitelist.requires_confirmation(&raw_only));
+
+        let mut tagged = test_request("rm -rf /tmp/x");
+        tagged.intent_tags = vec!["read-only".to_string()];
+        assert!(!whitelist.auto_approves(&tagged));
+    }
+
+    #[test]
+    fn intent_policy_rejects_placeholders() {
+        let config: PolicyConfig = toml::from_str(
+            r#"
+require_intent = true
+min_intent_length = 8
+"#,
+        )
+        .expect("policy");
+        let intent = &config.intent;
+        assert!(intent.validate("check disk usage on /var").is_ok());
+        let denial = intent.validate("  ").unwrap_err();
+        assert_eq!(denial.rule.as_deref(), Some("intent.require_intent"));
+        let denial = intent.validate("ls").unwrap_err();
+        assert_eq!(denial.rule.as_deref(), Some("intent.min_intent_length"));
+        let denial = intent.validate("Run   Command").unwrap_err();
+        assert_eq!(denial.rule.as_deref(), Some("intent.banned_intents"));
+        assert!(denial.message.contains("meaningful intent"));
+
+        assert!(IntentPolicy::default().validate("").is_ok());
+    }
+
+    #[test]
+    fn check_reports_shadowed_and_invalid_rules() {
+        let config: PolicyConfig = toml::from_str(
+            r#"
+result_webhook = "ftp://ci.example.com/hook"
+
+[whitelist]
+allowed = ["ls", "/bin/rm", "grep"]
+denied = ["rm"]
+
+[whitelist.arg_rules]
+grep = "["
+cat = "^a$"
+
+[limits]
+timeout_secs = 0
+max_output_bytes = 1024
+"#,
+        )
+        .expect("policy");
+        let check = config.check();
+        assert_eq!((check.allowed, check.denied, check.arg_rules), (3, 1, 2));
+        let errors: Vec<&str> = check
+            .errors
+            .iter()
+            .map(|error| error.path.as_str())
+            .collect();
+        assert_eq!(
+            errors,
+            [
+                "whitelist.arg_rules.grep",
+                "result_webhook",
+                "limits.timeout_secs"
+            ]
+        );
+        let warnings: Vec<String> = check.warnings.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            warnings,
+            [
+                r#"whitelist.allowed[1]: allowed rule "/bin/rm" is shadowed by denied rule "rm""#,
+                r#"whitelist.arg_rules.cat: has no effect: "cat" is not in whitelist.allowed"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn readonly_whitelist_gates_terminal_injection() {
+        let config: PolicyConfig = toml::from_str(
+            r#"
+[readonly_whitelist]
+allowed = ["ls", "cat", "grep"]
+denied = ["cat"]
+arg_rules = { grep = "^[A-Za-z0-9_./-]+$" }
+"#,
+        )
+        .expect("policy");
+        let active = ActivePolicy::from_config(&config).expect("active policy");
+        let whitelist = &active.readonly_whitelist;
+        assert_eq!(readonly_denial(whitelist, "ls -la '/srv/app dir'"), None);
+        assert_eq!(readonly_denial(whitelist, "ls /var/log | grep nginx"), None);
+        for (command, reason) in [
+            ("cat /etc/hosts", "command denied: cat"),
+            ("ls && rm -rf /tmp/x", "command not allowed: rm"),
+            ("grep 'a b' /tmp/x", "argument rejected: a b"),
+            ("ls > /tmp/out", "output redirection is not allowed"),
+            ("ls\nrm -rf /", "command must be a single line"),
+        ] {
+            assert_eq!(
+                readonly_denial(whitelist, command).as_deref(),
+                Some(reason),
+                "{command}"
+            );
+        }
+        assert!(readonly_denial(whitelist, "ls $(whoami)")
+            .is_some_and(|reason| reason.starts_with("command not parseable")));
+        let empty = Whitelist::from_config(&WhitelistConfig::default()).unwrap();
+        assert!(readonly_denial(&empty, "ls").is_some());
+    }
+
+    #[test]
+    fn readonly_requests_must_match_the_readonly_whitelist() {
+        let config: PolicyConfig = toml::from_str(
+            r#"
+[whitelist]
+dangerous_patterns = ["cat /etc/shadow"]
+
+[readonly_whitelist]
+allowed = ["ls", "cat", "grep"]
+"#,
+        )
+        .expect("policy");
+        let active = ActivePolicy::from_config(&config).expect("active policy");
+        let whitelist = &active.readonly_whitelist;
+        let mut request = test_request("ls -la /srv | grep app");
+        assert!(!active.approves_readonly(&request));
+        request.readonly = true;
+        assert_eq!(readonly_request_denial(whitelist, &request), None);
+        assert!(active.approves_readonly(&request));
+
+        for (command, reason) in [
+            ("rm -rf /tmp/x", "command not allowed: rm"),
+            ("ls | tee /tmp/x", "command not allowed: tee"),
+            (
+                "ls > /tmp/out",
+                "output redirection to a file is not read-only",
+            ),
+            ("ls\nrm -rf /tmp/x", "command must be a single line"),
+            ("cat <<EOF", "heredoc and here-string input are not allowed"),
+            (
+                "grep app <<< $(id)",
+                "heredoc and here-string input are not allowed",
+            ),
+        ] {
+            let mut request = test_request(command);
+            request.readonly = true;
+            assert_eq!(
+                readonly_request_denial(whitelist, &request).as_deref(),
+                Some(reason),
+                "{command}"
+            );
+            assert!(!active.approves_readonly(&request), "{command}");
+        }
+
+        let mut request = test_request("cat /etc/shadow");
+        request.readonly = true;
+        assert_eq!(readonly_request_denial(whitelist, &request), None);
+        assert!(!active.approves_readonly(&request));
+    }
+
+    #[test]
+    fn parses_optional_resource_limits() {
+        let config: PolicyConfig = toml::from_str(
+            r#"
+[limits]
+timeout_secs = 10
+max_output_bytes = 1024
+nice = 5
+max_cpu_secs = 60
+umask = 0o027
+run_as = "svc"
+run_as_allowed = ["deploy"]
+run_as_command = "doas"
+"#,
+        )
+        .expect("policy");
+        assert_eq!(config.limits.timeout_secs, 10);
+        assert_eq!(config.limits.resources.nice, Some(5));
+        assert_eq!(config.limits.resources.max_cpu_secs, Some(60));
+        assert!(config.limits.resources.max_memory_bytes.is_none());
+        assert_eq!(config.limits.resources.umask, Some(0o027));
+        assert!(config.check().errors.is_empty());
+
+        let run_as = &config.limits.run_as;
+        assert_eq!(run_as.resolve(None).ok(), Some(Some("svc".to_string())));
+        assert_eq!(
+            run_as.resolve(Some("deploy")).ok(),
+            Some(Some("deploy".to_string()))
+        );
+        let denial = run_as.resolve(Some("root")).unwrap_err();
+        assert_eq!(denial.rule.as_deref(), Some("limits.run_as_allowed"));
+        assert_eq!(run_as.command_prefix("svc"), "doas -n -u 'svc' ");
+        assert!(RunAsConfig::default().resolve(Some("svc")).is_err());
+        assert_eq!(RunAsConfig::default().resolve(None).ok(), Some(None));
+    }
+
+    #[test]
+    fn rejects_requests_over_size_limits() {
+        assert_eq!(LimitsConfig::default().request.max_pipeline_stages, 64);
+        let config: PolicyConfig = toml::from_str(
+            r#"
+[limits]
+timeout_secs = 10
+max_output_bytes = 1024
+max_pipeline_stages = 2
+max_args_per_stage = 3
+max_raw_command_bytes = 16
+"#,
+        )
+        .expect("policy");
+        let limits = config.limits.request;
+        let request = test_request("ls | grep a | wc -l");
+        let denial = limits.pipeline_denial(&request.pipeline).expect("denied");
+        assert_eq!(denial.message, "pipeline has 3 stages, limit is 2");
+        assert_eq!(denial.rule.as_deref(), Some("limits.max_pipeline_stages"));
+        let denial = limits
+            .pipeline_denial(&test_request("ls -l -a /tmp").pipeline)
+            .expect("denied");
+        assert_eq!(
+            denial.message,
+            "pipeline stage 1 has 4 arguments, limit is 3"
+        );
+        assert!(limits
+            .pipeline_denial(&test_request("ls -l | wc").pipeline)
+            .is_none());
+        assert!(limits.raw_command_denial("ls -l | wc").is_none());
+        let denial = limits
+            .raw_command_denial("echo 0123456789abcdef")
+            .expect("denied");
+        assert_eq!(denial.rule.as_deref(), Some("limits.max_raw_command_bytes"));
+    }
+
+    #[test]
+    fn clamps_large_request_summaries() {
+        let raw_command = vec!["cat a"; 12].join(" | ");
+        let summary = request_summary(&test_request(&raw_command));
+        assert!(summary.ends_with("cat a | ... (pipeline: 12 stages, showing first 10)"));
+        assert_eq!(summary.matches("cat a").count(), 10);
+
+        let raw_command = format!("echo {}", vec!["x"; 40].join(" "));
+        let summary = request_summary(&test_request(&raw_command));
+        assert!(summary.ends_with("x ... (+9 args)"), "{summary}");
+    }
+
+    #[test]
+    fn limits_cwd_creation_to_prefixes() {
+        let config: PolicyConfig = toml::from_str(
+            r#"
+[limits]
+timeout_secs = 10
+max_output_bytes = 1024
+allow_create_cwd = true
+create_cwd_prefixes = ["/srv/work/", "~/tmp", "relative"]
+"#,
+        )
+        .expect("policy");
+        let cwd = &config.limits.cwd;
+        assert!(cwd.allows_create("/srv/work"));
+        assert!(cwd.allows_create("/srv/work/a/b/"));
+        assert!(cwd.allows_create("~/tmp/build"));
+        assert!(!cwd.allows_create("/srv/workspace"));
+        assert!(!cwd.allows_create("/srv/work/../etc"));
+        assert!(!cwd.allows_create("/home/user/tmp"));
+        let errors = config.check().errors;
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].path, "limits.create_cwd_prefixes[2]");
+
+        assert!(!CwdConfig::default().allows_create("/tmp/x"));
+        let any = CwdConfig {
+            allow_create_cwd: true,
+            create_cwd_prefixes: Vec::new(),
+        };
+        assert!(any.allows_create("/tmp/x"));
+    }
+
+    #[test]
+    fn parses_sandbox_and_requires_wrapper_when_enabled() {
+        let config: PolicyConfig = toml::from_str(
+            r#"
+[limits]
+timeout_secs = 10
+max_output_bytes = 1024
+
+[sandbox]
+enabled = true
+wrapper = ["/usr/bin/env", "SANDBOXED=1"]
+exempt_commands = ["systemctl"]
+"#,
+        )
+        .expect("policy");
+        assert!(config.check().errors.is_empty());
+        assert_eq!(
+            config.sandbox.probe_command(),
+            "command -v '/usr/bin/env' >/dev/null 2>&1 || \
+{ echo 'sandbox wrapper not found: /usr/bin/env' >&2; exit 127; }"
+        );
+        assert_eq!(
+            config.sandbox.command_prefix(&test_request("ls /tmp")),
+            "'/usr/bin/env' 'SANDBOXED=1' "
+        );
+        assert_eq!(SandboxConfig::default().probe_command(), "true");
+
+        let config: PolicyConfig = toml::from_str(
+            "[sandbox]\nenabled = true\nwrapper = []\nexempt_commands = [\"/bin/ls\"]\n",
+        )
+        .expect("policy");
+        let paths: Vec<String> = config
+            .check()
+            .errors
+            .into_iter()
+            .map(|diagnostic| diagnostic.path)
+            .collect();
+        assert_eq!(paths, ["sandbox.wrapper", "sandbox.exempt_commands[0]"]);
+    }
+
+    #[test]
+    fn temporary_rules_lift_denied_but_not_immutable_hard_deny() {
+        let whitelist = Whitelist::from_config(&WhitelistConfig {
+            denied: vec!["iptables".to_string()],
+            hard_deny: HardDenyConfig {
+                commands: vec!["mkfs".to_string()],
+                immutable: true,
+            },
+            ..WhitelistConfig::default()
+        })
+        .expect("whitelist");
+        let allow = |command: &str| {
+            Some(TemporaryRuleUse {
+                id: format!("tmp-{command}"),
+                rule: command.to_string(),
+                actor: "ops".to_string(),
+            })
+        };
+
+        let denial = policy_denial(&whitelist, &test_request("iptables -L"), |_| None).unwrap_err();
+        assert_eq!(denial.rule.as_deref(), Some("whitelist.denied: iptables"));
+        let used = policy_denial(
+            &whitelist,
+            &test_request("iptables -L | iptables -S"),
+            allow,
+        )
+        .expect("lifted");
+        assert_eq!(used.len(), 1);
+        assert_eq!(used[0].actor, "ops");
+
+        let denial = policy_denial(&whitelist, &test_request("mkfs /dev/sdb"), allow).unwrap_err();
+        assert_eq!(denial.rule.as_deref(), Some("whitelist.hard_deny: mkfs"));
+        assert!(whitelist.is_immutable_denied("/sbin/mkfs"));
+        assert!(!whitelist.is_immutable_denied("iptables"));
+    }
+
+    #[test]
+    fn flags_requests_outside_time_windows() {
+        let config: PolicyConfig = toml::from_str(
+            r#"
+            [whitelist]
+            allowed = ["ls", "kubectl"]
+            timezone = "Europe/Berlin"
+
+            [[whitelist.time_windows]]
+            commands = ["kubectl"]
+            intent_tags = ["deploy"]
+            allowed_hours = "09:00-18:00"
+            allowed_days = ["mon-fri"]
+
+            [[whitelist.time_windows]]
+            commands = ["helm"]
+            allowed_days = ["sat"]
+            deny_outside_window = true
+            "#,
+        )
+        .expect("policy");
+        assert!(config.check().errors.is_empty());
+        let whitelist = Whitelist::from_config(&config.whitelist).expect("whitelist");
+        let at = |secs| std::time::UNIX_EPOCH + std::time::Duration::from_secs(secs);
+        // 周三 09:30 CEST / 周三 08:30 CET。
+        let (inside, outside) = (at(1_791_963_000), at(1_768_375_800));
+
+        let kubectl = test_request("kubectl apply -f app.yaml");
+        assert_eq!(whitelist.outside_window(&kubectl, inside), None);
+        assert_eq!(
+            whitelist.outside_window(&kubectl, outside),
+            Some(OutsideWindow {
+                rule: "whitelist.time_windows[0]: 09:00-18:00 mon-fri".to_string(),
+                deny: false,
+            })
+        );
+        assert_eq!(whitelist.outside_window(&test_request("ls"), outside), None);
+        let mut tagged = test_request("ls");
+        tagged.intent_tags = vec!["deploy".to_string()];
+        assert!(whitelist.outside_window(&tagged, outside).is_some());
+
+        // 同时命中时优先报告硬拒绝的时间窗。
+        let both = test_request("kubectl get pods | helm list");
+        let window = whitelist.outside_window(&both, outside).expect("outside");
+        assert!(window.deny);
+        assert_eq!(window.rule, "whitelist.time_windows[1]: sat");
+
+        let invalid: PolicyConfig = toml::from_str(
+            "[whitelist]\ntimezone = \"Mars/Olympus\"\n[[whitelist.time_windows]]\nallowed_hours = \"9-5\"\n",
+        )
+        .expect("policy");
+        let paths: Vec<String> = invalid
+            .check()
+            .errors
+            .iter()
+            .map(|diagnostic| diagnostic.path.clone())
+            .collect();
+        assert_eq!(paths, ["whitelist.timezone", "whitelist.time_windows[0]"]);
+        assert!(Whitelist::from_config(&invalid.whitelist).is_err());
+    }
+
+    #[test]
+    fn reload_swaps_policy_and_keeps_old_on_error() {
+        let dir = super::super::test_utils::temp_dir("octovalve-policy-reload");
+        let path = dir.join("local-bin.toml");
+        let write = |content: &str| std::fs::write(&path, content).expect("write policy");
+        write("[whitelist]\ndenied = [\"rm\"]\n[limits]\ntimeout_secs = 10\nmax_output_bytes = 1024\n");
+        let config = PolicyConfig::load(&path).expect("policy");
+        let live = LivePolicy::new(path.clone(), ActivePolicy::from_config(&config).unwrap());
+        let before = live.current();
+        assert!(
+            policy_denial(&before.whitelist, &test_request("rm -rf /tmp/x"), |_| None).is_err()
+        );
+
+        write("[whitelist]\ndenied = [\"dd\"]\n[limits]\ntimeout_secs = 20\nmax_output_bytes = 1024\n");
+        assert!(live.reload().is_ok());
+        let after = live.current();
+        assert_eq!(after.limits.timeout_secs, 20);
+        assert!(
+            policy_denial(&after.whitelist, &test_request("dd if=/dev/zero"), |_| None).is_err()
+        );
+        assert!(policy_denial(&after.whitelist, &test_request("rm -rf /tmp/x"), |_| None).is_ok());
+        // 已取走的快照不受重载影响。
+        assert_eq!(before.limits.timeout_secs, 10);
+
+        write("[limits]\ntimeout_secs = \"soon\"\n");
+        assert!(live.reload().unwrap_err().contains("failed to load"));
+        write("[limits]\ntimeout_secs = 0\nmax_output_bytes = 1024\n");
+        assert!(live.reload().unwrap_err().contains("timeout_secs"));
+        assert_eq!(live.current().limits.timeout_secs, 20);
+        let _ = std::fs::remove_dir_all(dir);
     }
 }
@@ -1,3 +1,4 @@
+#[cfg(unix)]
 use std::io;
 use std::time::Duration;
 
@@ -17,7 +18,13 @@ pub(super) fn apply_process_group(cmd: &mut Command) {
     }
 }
 
-#[cfg(not(unix))]
+#[cfg(windows)]
+pub(super) fn apply_process_group(cmd: &mut Command) {
+    const CREATE_NEW_PROCESS_GROUP: u32 = 0x0000_0200;
+    cmd.creation_flags(CREATE_NEW_PROCESS_GROUP);
+}
+
+#[cfg(not(any(unix, windows)))]
 pub(super) fn apply_process_group(_cmd: &mut Command) {}
 
 #[cfg(unix)]
@@ -29,17 +36,43 @@ fn signal_child(child: &mut tokio::process::Child, signal: i32) {
     }
 }
 
+#[cfg(unix)]
+fn interrupt_child(child: &mut tokio::process::Child) {
+    signal_child(child, libc::SIGINT);
+}
+
+#[cfg(unix)]
+fn kill_child_tree(child: &mut tokio::process::Child) {
+    signal_child(child, libc::SIGKILL);
+}
+
+// Windows 没有面向进程组的 SIGINT，宽限期后直接用 taskkill 结束整棵进程树。
 #[cfg(not(unix))]
-fn signal_child(_child: &mut tokio::process::Child, _signal: i32) {}
+fn interrupt_child(_child: &mut tokio::process::Child) {}
+
+#[cfg(windows)]
+fn kill_child_tree(child: &mut tokio::process::Child) {
+    if let Some(pid) = child.id() {
+        let _ = std::process::Command::new("taskkill")
+            .args(["/T", "/F", "/PID", &pid.to_string()])
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .status();
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn kill_child_tree(_child: &mut tokio::process::Child) {}
 
 pub(super) async fn terminate_child(
     child: &mut tokio::process::Child,
 ) -> Option<std::process::ExitStatus> {
-    signal_child(child, libc::SIGINT);
+    interrupt_child(child);
     match tokio::time::timeout(CANCEL_GRACE, child.wait()).await {
         Ok(status) => return status.ok(),
         Err(_) => {
-            signal_child(child, libc::SIGKILL);
+            kill_child_tree(child);
             let _ = child.kill().await;
             match tokio::time::timeout(CANCEL_GRACE, child.wait()).await {
                 Ok(status) => status.ok(),
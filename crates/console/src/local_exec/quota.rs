@@ -0,0 +1,511 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use protocol::config::ConfigDiagnostic;
+use serde::{Deserialize, Serialize};
+
+use super::templates::TEMPLATE_CLIENT;
+use crate::state::{QuotaCounter, QuotaUsage};
+
+const QUOTA_STATE_FILE: &str = "quota.json";
+const DAY_SECS: u64 = 86_400;
+
+/// 每个目标每天（UTC）的命令数与累计执行时长上限；`targets.<name>` 按字段覆盖全局值。
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct QuotaConfig {
+    #[serde(flatten)]
+    pub(crate) limits: QuotaLimits,
+    /// true 时同一目标上每个 client 各自计数，否则所有 client 共用额度。
+    #[serde(default)]
+    pub(crate) per_client: bool,
+    /// 操作员通过命令模板下发的请求不计数也不受限；终端会话本来就不经过队列。
+    #[serde(default = "default_exempt_templates")]
+    pub(crate) exempt_templates: bool,
+    #[serde(default)]
+    pub(crate) targets: BTreeMap<String, QuotaLimits>,
+}
+
+impl Default for QuotaConfig {
+    fn default() -> Self {
+        Self {
+            limits: QuotaLimits::default(),
+            per_client: false,
+            exempt_templates: default_exempt_templates(),
+            targets: BTreeMap::new(),
+        }
+    }
+}
+
+fn default_exempt_templates() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub(crate) struct QuotaLimits {
+    #[serde(default)]
+    pub(crate) max_daily_commands: Option<u64>,
+    #[serde(default)]
+    pub(crate) max_daily_execution_secs: Option<u64>,
+}
+
+impl QuotaLimits {
+    fn is_empty(&self) -> bool {
+        self.max_daily_commands.is_none() && self.max_daily_execution_secs.is_none()
+    }
+}
+
+impl QuotaConfig {
+    pub(crate) fn limits_for(&self, target: &str) -> QuotaLimits {
+        let overrides = self.targets.get(target).copied().unwrap_or_default();
+        QuotaLimits {
+            max_daily_commands: overrides
+                .max_daily_commands
+                .or(self.limits.max_daily_commands),
+            max_daily_execution_secs: overrides
+                .max_daily_execution_secs
+                .or(self.limits.max_daily_execution_secs),
+        }
+    }
+
+    fn is_exempt(&self, client: &str) -> bool {
+        self.exempt_templates && client == TEMPLATE_CLIENT
+    }
+
+    pub(crate) fn check(&self) -> Vec<ConfigDiagnostic> {
+        let sections = std::iter::once(("quota".to_string(), &self.limits)).chain(
+            self.targets
+                .iter()
+                .map(|(name, limits)| (format!("quota.targets.{name}"), limits)),
+        );
+        let mut diagnostics = Vec::new();
+        for (section, limits) in sections {
+            for (key, value) in [
+                ("max_daily_commands", limits.max_daily_commands),
+                ("max_daily_execution_secs", limits.max_daily_execution_secs),
+            ] {
+                if value == Some(0) {
+                    diagnostics.push(ConfigDiagnostic::error(
+                        format!("{section}.{key}"),
+                        "quota limits must be greater than 0 (omit a key to disable it)",
+                    ));
+                }
+            }
+        }
+        diagnostics
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct Consumption {
+    commands: u64,
+    execution_ms: u64,
+}
+
+impl Consumption {
+    fn add(&mut self, other: Consumption) {
+        self.commands += other.commands;
+        self.execution_ms += other.execution_ms;
+    }
+
+    fn counter(&self) -> QuotaCounter {
+        QuotaCounter {
+            commands: self.commands,
+            execution_secs: self.execution_ms / 1000,
+        }
+    }
+
+    fn exhausted_by(&self, limits: &QuotaLimits) -> Option<&'static str> {
+        if limits
+            .max_daily_commands
+            .is_some_and(|max| self.commands >= max)
+        {
+            return Some("max_daily_commands");
+        }
+        if limits
+            .max_daily_execution_secs
+            .is_some_and(|max| self.execution_ms >= max.saturating_mul(1000))
+        {
+            return Some("max_daily_execution_secs");
+        }
+        None
+    }
+}
+
+// 始终按 client 分开记录，切换 per_client 时不需要迁移。
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct QuotaFile {
+    day: u64,
+    usage: BTreeMap<String, BTreeMap<String, Consumption>>,
+}
+
+/// 一次执行计数后额度刚好用完时返回，用于发出 `quota_exhausted` 事件。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct QuotaExhausted {
+    pub(crate) client: Option<String>,
+    pub(crate) limit: &'static str,
+    pub(crate) resets_at_ms: u64,
+}
+
+/// 各目标当天的消耗，每次变化后写回审计根目录下的 `quota.json`，console 重启不会清零。
+pub(crate) struct QuotaTracker {
+    path: PathBuf,
+    file: Mutex<QuotaFile>,
+    // 快照的版本号，在 `file` 锁内递增；后台写入乱序完成时不会用旧快照覆盖新的。
+    version: AtomicU64,
+    persisted: Arc<Mutex<u64>>,
+}
+
+impl QuotaTracker {
+    pub(crate) fn load(audit_root: &Path) -> Self {
+        let path = audit_root.join(QUOTA_STATE_FILE);
+        let file = match std::fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|err| {
+                tracing::warn!(
+                    path = %path.display(),
+                    error = %err,
+                    "ignoring unreadable quota state"
+                );
+                QuotaFile::default()
+            }),
+            Err(_) => QuotaFile::default(),
+        };
+        Self {
+            path,
+            file: Mutex::new(file),
+            version: AtomicU64::new(0),
+            persisted: Arc::new(Mutex::new(0)),
+        }
+    }
+
+    /// 额度已用完时返回拒绝原因与命中的规则。`in_flight` 为已批准、尚未结束的命令数，按已用的命令数计入。
+    pub(crate) fn check(
+        &self,
+        config: &QuotaConfig,
+        target: &str,
+        client: &str,
+        in_flight: u64,
+        now: SystemTime,
+    ) -> Result<(), (String, String)> {
+        let limits = config.limits_for(target);
+        if limits.is_empty() || config.is_exempt(client) {
+            return Ok(());
+        }
+        let day = day_index(now);
+        let mut used = {
+            let mut file = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+            roll_over(&mut file, day);
+            consumed(&file, target, config.per_client.then_some(client))
+        };
+        used.commands += in_flight;
+        let Some(limit) = used.exhausted_by(&limits) else {
+            return Ok(());
+        };
+        let scope = if config.per_client {
+            format!("client {client} on target {target}")
+        } else {
+            format!("target {target}")
+        };
+        let mut remaining = Vec::new();
+        if let Some(max) = limits.max_daily_commands {
+            remaining.push(format!(
+                "{} of {max} commands left",
+                max.saturating_sub(used.commands)
+            ));
+        }
+        if let Some(max) = limits.max_daily_execution_secs {
+            remaining.push(format!(
+                "{}s of {max}s execution left",
+                max.saturating_sub(used.execution_ms / 1000)
+            ));
+        }
+        let resets_at = UNIX_EPOCH + Duration::from_secs((day + 1) * DAY_SECS);
+        Err((
+            format!(
+                "daily quota exhausted for {scope}: {}; resets at {}",
+                remaining.join(", "),
+                humantime::format_rfc3339(resets_at)
+            ),
+            format!("quota.{limit}"),
+        ))
+    }
+
+    /// 记录一次已执行的命令；返回记录后的用量，以及额度是否因此用完。未配置额度的目标不记录。
+    pub(crate) fn record(
+        &self,
+        config: &QuotaConfig,
+        target: &str,
+        client: &str,
+        execution: Duration,
+        now: SystemTime,
+    ) -> Option<(QuotaUsage, Option<QuotaExhausted>)> {
+        let limits = config.limits_for(target);
+        if limits.is_empty() || config.is_exempt(client) {
+            return None;
+        }
+        let day = day_index(now);
+        let (usage, exhausted, payload) = {
+            let mut file = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+            roll_over(&mut file, day);
+            let scope = config.per_client.then_some(client);
+            let before = consumed(&file, target, scope).exhausted_by(&limits);
+            file.usage
+                .entry(target.to_string())
+                .or_default()
+                .entry(client.to_string())
+                .or_default()
+                .add(Consumption {
+                    commands: 1,
+                    execution_ms: execution.as_millis() as u64,
+                });
+            let after = consumed(&file, target, scope).exhausted_by(&limits);
+            let exhausted = match (before, after) {
+                (None, Some(limit)) => Some(QuotaExhausted {
+                    client: scope.map(str::to_string),
+                    limit,
+                    resets_at_ms: (day + 1) * DAY_SECS * 1000,
+                }),
+                _ => None,
+            };
+            let payload = serde_json::to_vec(&*file)
+                .map(|payload| (self.version.fetch_add(1, Ordering::Relaxed) + 1, payload));
+            (build_usage(&file, config, target, day), exhausted, payload)
+        };
+        match payload {
+            Ok((version, payload)) => self.persist(version, payload),
+            Err(err) => {
+                tracing::warn!(error = %err, "failed to serialize quota state");
+            }
+        }
+        usage.map(|usage| (usage, exhausted))
+    }
+
+    // 在 runtime 里交给 blocking 线程写，不阻塞调用方所在的 service 循环；先写临时文件再 rename，
+    // 崩溃时不会留下写了一半的 quota.json。
+    fn persist(&self, version: u64, payload: Vec<u8>) {
+        let path = self.path.clone();
+        let persisted = Arc::clone(&self.persisted);
+        let write = move || {
+            let mut last = persisted.lock().unwrap_or_else(PoisonError::into_inner);
+            if *last >= version {
+                return;
+            }
+            match write_atomically(&path, &payload) {
+                Ok(()) => *last = version,
+                Err(err) => {
+                    tracing::warn!(path = %path.display(), error = %err, "failed to write quota state");
+                }
+            }
+        };
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) => {
+                handle.spawn_blocking(write);
+            }
+            Err(_) => write(),
+        }
+    }
+
+    /// 目标当前的额度与用量；未配置额度时为 None。
+    pub(crate) fn usage(
+        &self,
+        config: &QuotaConfig,
+        target: &str,
+        now: SystemTime,
+    ) -> Option<QuotaUsage> {
+        let day = day_index(now);
+        let mut file = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+        roll_over(&mut file, day);
+        build_usage(&file, config, target, day)
+    }
+}
+
+fn write_atomically(path: &Path, payload: &[u8]) -> std::io::Result<()> {
+    let tmp = path.with_extension("json.tmp");
+    let mut file = std::fs::File::create(&tmp)?;
+    std::io::Write::write_all(&mut file, payload)?;
+    file.sync_all()?;
+    std::fs::rename(&tmp, path)
+}
+
+fn day_index(now: SystemTime) -> u64 {
+    now.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() / DAY_SECS)
+        .unwrap_or(0)
+}
+
+fn roll_over(file: &mut QuotaFile, day: u64) {
+    if file.day != day {
+        file.day = day;
+        file.usage.clear();
+    }
+}
+
+fn consumed(file: &QuotaFile, target: &str, client: Option<&str>) -> Consumption {
+    let Some(clients) = file.usage.get(target) else {
+        return Consumption::default();
+    };
+    match client {
+        Some(client) => clients.get(client).copied().unwrap_or_default(),
+        None => clients
+            .values()
+            .fold(Consumption::default(), |mut total, usage| {
+                total.add(*usage);
+                total
+            }),
+    }
+}
+
+fn build_usage(
+    file: &QuotaFile,
+    config: &QuotaConfig,
+    target: &str,
+    day: u64,
+) -> Option<QuotaUsage> {
+    let limits = config.limits_for(target);
+    if limits.is_empty() {
+        return None;
+    }
+    let clients = if config.per_client {
+        file.usage
+            .get(target)
+            .map(|clients| {
+                clients
+                    .iter()
+                    .map(|(client, usage)| (client.clone(), usage.counter()))
+                    .collect()
+            })
+            .unwrap_or_default()
+    } else {
+        BTreeMap::new()
+    };
+    Some(QuotaUsage {
+        used: consumed(file, target, None).counter(),
+        max_daily_commands: limits.max_daily_commands,
+        max_daily_execution_secs: limits.max_daily_execution_secs,
+        per_client: config.per_client,
+        clients,
+        resets_at_ms: (day + 1) * DAY_SECS * 1000,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_utils::temp_dir;
+    use super::*;
+
+    fn config(toml: &str) -> QuotaConfig {
+        toml::from_str(toml).expect("quota config")
+    }
+
+    #[test]
+    fn denies_after_limits_and_survives_restart() {
+        let root = temp_dir("quota");
+        let config = config(
+            r#"
+max_daily_commands = 2
+[targets.prod]
+max_daily_execution_secs = 60
+"#,
+        );
+        let now = UNIX_EPOCH + Duration::from_secs(20_000 * DAY_SECS + 3600);
+        let tracker = QuotaTracker::load(&root);
+        assert!(tracker.check(&config, "dev", "agent", 0, now).is_ok());
+        let (_, exhausted) = tracker
+            .record(&config, "dev", "agent", Duration::from_secs(5), now)
+            .expect("usage");
+        assert!(exhausted.is_none());
+        let (usage, exhausted) = tracker
+            .record(&config, "dev", "other", Duration::from_secs(5), now)
+            .expect("usage");
+        assert_eq!(usage.used.commands, 2);
+        assert_eq!(usage.used.execution_secs, 10);
+        assert_eq!(
+            exhausted.map(|exhausted| exhausted.limit),
+            Some("max_daily_commands")
+        );
+
+        let tracker = QuotaTracker::load(&root);
+        let (message, rule) = tracker
+            .check(&config, "dev", "agent", 0, now)
+            .expect_err("quota exhausted");
+        assert_eq!(rule, "quota.max_daily_commands");
+        assert!(message.contains("0 of 2 commands left"), "{message}");
+        assert!(
+            message.contains("resets at 2024-10-05T00:00:00Z"),
+            "{message}"
+        );
+        assert!(tracker
+            .check(&config, "dev", TEMPLATE_CLIENT, 0, now)
+            .is_ok());
+
+        // 按字段覆盖：prod 同时受全局命令数与自身执行时长限制。
+        tracker.record(&config, "prod", "agent", Duration::from_secs(61), now);
+        let (message, rule) = tracker
+            .check(&config, "prod", "agent", 0, now)
+            .expect_err("execution budget exhausted");
+        assert_eq!(rule, "quota.max_daily_execution_secs");
+        assert!(
+            message.contains("1 of 2 commands left, 0s of 60s execution left"),
+            "{message}"
+        );
+
+        let tomorrow = now + Duration::from_secs(DAY_SECS);
+        assert!(tracker.check(&config, "dev", "agent", 0, tomorrow).is_ok());
+        assert_eq!(
+            tracker
+                .usage(&config, "dev", tomorrow)
+                .map(|usage| usage.used.commands),
+            Some(0)
+        );
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn per_client_quota_counts_each_client_and_ignores_corrupt_state() {
+        let root = temp_dir("quota-clients");
+        std::fs::write(root.join(QUOTA_STATE_FILE), b"{not json").unwrap();
+        let config = config("max_daily_commands = 1\nper_client = true");
+        let now = SystemTime::now();
+        let tracker = QuotaTracker::load(&root);
+        let (usage, exhausted) = tracker
+            .record(&config, "dev", "agent", Duration::ZERO, now)
+            .expect("usage");
+        assert_eq!(
+            exhausted.and_then(|exhausted| exhausted.client),
+            Some("agent".to_string())
+        );
+        assert_eq!(usage.clients["agent"].commands, 1);
+        assert!(tracker.check(&config, "dev", "agent", 0, now).is_err());
+        assert!(tracker.check(&config, "dev", "other", 0, now).is_ok());
+        assert!(config.check().is_empty());
+        assert_eq!(self::config("max_daily_commands = 0").check().len(), 1);
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn counts_in_flight_commands_and_writes_state_atomically() {
+        let root = temp_dir("quota-in-flight");
+        let config = config("max_daily_commands = 2");
+        let now = SystemTime::now();
+        let tracker = QuotaTracker::load(&root);
+        tracker.record(&config, "dev", "agent", Duration::ZERO, now);
+        // 已完成 1 条、执行中 1 条时额度已占满，批准时的复查要拒绝第三条。
+        assert!(tracker.check(&config, "dev", "agent", 0, now).is_ok());
+        let (_, rule) = tracker
+            .check(&config, "dev", "agent", 1, now)
+            .expect_err("in-flight command counts");
+        assert_eq!(rule, "quota.max_daily_commands");
+
+        assert!(!root.join("quota.json.tmp").exists());
+        let reloaded = QuotaTracker::load(&root);
+        assert_eq!(
+            reloaded
+                .usage(&config, "dev", now)
+                .map(|usage| usage.used.commands),
+            Some(1)
+        );
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
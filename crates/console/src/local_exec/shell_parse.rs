@@ -0,0 +1,483 @@
+use std::path::Path;
+
+use protocol::CommandStage;
+
+// 出现在段首时说明是控制结构/分组，实际执行什么要看运行时，无法按段校验。
+const CONTROL_WORDS: &[&str] = &[
+    "if", "then", "elif", "else", "fi", "for", "while", "until", "do", "done", "case", "esac",
+    "select", "function", "{", "}", "!", "[[", "]]",
+];
+
+/// 从 raw_command 推导出需要校验的各段命令：按 `|`、`||`、`&&`、`;`、`&` 和换行切分，
+/// 去掉段首的 `NAME=value` 赋值，跳过 heredoc 正文和注释。
+/// 命令替换、子 shell、控制结构、以变量或通配/花括号展开作为命令名等无法静态确定实际命令的写法返回错误。
+pub(crate) fn parse_shell_stages(command: &str) -> Result<Vec<CommandStage>, String> {
+    let mut stages = Vec::new();
+    for segment in split_segments(command)? {
+        // shell_words 会去掉引号，是否展开要看原文里命令名的写法。
+        if let Some(word) = raw_words(&segment)
+            .into_iter()
+            .find(|word| !is_assignment(word))
+            .filter(|word| !matches!(word.as_str(), "[" | "{") && expands_unquoted(word))
+        {
+            return Err(format!("command name is not static: {word}"));
+        }
+        let tokens = shell_words::split(&segment).map_err(|err| err.to_string())?;
+        let argv: Vec<String> = tokens
+            .into_iter()
+            .skip_while(|token| is_assignment(token))
+            .collect();
+        let Some(first) = argv.first() else {
+            continue;
+        };
+        if CONTROL_WORDS.contains(&first.as_str()) {
+            return Err(format!("unsupported shell syntax: {first}"));
+        }
+        if first.contains('$') {
+            return Err(format!("command name is not static: {first}"));
+        }
+        stages.push(CommandStage { argv });
+    }
+    if stages.is_empty() {
+        return Err("command is empty".to_string());
+    }
+    Ok(stages)
+}
+
+/// 客户端声明的 pipeline 与服务端解析结果对不上时返回第一个没出现在声明里的命令。
+/// proxy 只按 `|` 切分，`a && b` 会被声明成一段，所以只要求每个解析出的命令名在声明的参数里出现过。
+pub(crate) fn undeclared_command<'a>(
+    declared: &[CommandStage],
+    parsed: &'a [CommandStage],
+) -> Option<&'a str> {
+    if declared.is_empty() {
+        return None;
+    }
+    let declared: Vec<&str> = declared
+        .iter()
+        .flat_map(|stage| stage.argv.iter())
+        .flat_map(|arg| arg.split([';', '|', '&', '\n']))
+        .collect();
+    parsed
+        .iter()
+        .filter_map(CommandStage::command)
+        .find(|command| {
+            let name = basename(command);
+            !declared
+                .iter()
+                .any(|token| *token == *command || basename(token) == name)
+        })
+}
+
+/// raw_command 是否把输出写进文件：`>`、`>>`、`>|`、`&>`、`<>` 以及 `>&file`。
+/// 复制描述符（`2>&1`、`>&-`）和写到 `/dev/null` 的不算；引号内的字符按字面量跳过。
+/// 只做词法扫描，heredoc 正文里的 `>` 也会被当成重定向，宁可误判为写文件。
+pub(crate) fn redirects_output_to_file(command: &str) -> bool {
+    let mut chars = command.chars().peekable();
+    let mut quote: Option<char> = None;
+    while let Some(ch) = chars.next() {
+        match (quote, ch) {
+            (Some('\''), '\'') => quote = None,
+            (Some('\''), _) => {}
+            (_, '\\') => {
+                chars.next();
+            }
+            (Some(_), '"') => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"') => quote = Some(ch),
+            (None, '<') if chars.peek() == Some(&'>') => return true,
+            (None, '>') => {
+                if matches!(chars.peek(), Some('>' | '|')) {
+                    chars.next();
+                }
+                let duplicate = chars.peek() == Some(&'&');
+                if duplicate {
+                    chars.next();
+                }
+                let target = redirect_target(&mut chars);
+                let is_fd = !target.is_empty() && target.chars().all(|ch| ch.is_ascii_digit());
+                if duplicate && (is_fd || target == "-") {
+                    continue;
+                }
+                if target != "/dev/null" {
+                    return true;
+                }
+            }
+            _ => {}
+        }
+    }
+    false
+}
+
+// 读出重定向后的目标词（去掉引号），遇到空白或 shell 元字符结束。
+fn redirect_target(chars: &mut std::iter::Peekable<std::str::Chars<'_>>) -> String {
+    while chars.peek().is_some_and(|ch| *ch == ' ' || *ch == '\t') {
+        chars.next();
+    }
+    let mut target = String::new();
+    let mut quote: Option<char> = None;
+    while let Some(ch) = chars.peek().copied() {
+        match quote {
+            Some(open) if ch == open => quote = None,
+            Some(_) => target.push(ch),
+            None if ch == '\'' || ch == '"' => quote = Some(ch),
+            None if ch.is_whitespace() || matches!(ch, ';' | '|' | '&' | '<' | '>' | '(' | ')') => {
+                break;
+            }
+            None => target.push(ch),
+        }
+        chars.next();
+    }
+    target
+}
+
+fn basename(command: &str) -> &str {
+    Path::new(command)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(command)
+}
+
+// 按空白切出未去引号的词，只用于检查命令名的原始写法。
+fn raw_words(segment: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut quote: Option<char> = None;
+    let mut chars = segment.chars();
+    while let Some(ch) = chars.next() {
+        match (quote, ch) {
+            (None, ch) if ch.is_whitespace() => {
+                if !current.is_empty() {
+                    words.push(std::mem::take(&mut current));
+                }
+                continue;
+            }
+            (Some('\''), '\'') => quote = None,
+            (Some('\''), _) => {}
+            (_, '\\') => {
+                current.push(ch);
+                if let Some(next) = chars.next() {
+                    current.push(next);
+                }
+                continue;
+            }
+            (Some(_), '"') => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"') => quote = Some(ch),
+            _ => {}
+        }
+        current.push(ch);
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    words
+}
+
+// 未加引号的 `*`、`?`、`[`、`{` 会在运行时做路径名或花括号展开（`/bin/r?`、`{rm,-rf,/}`）。
+fn expands_unquoted(word: &str) -> bool {
+    let mut chars = word.chars();
+    let mut quote: Option<char> = None;
+    while let Some(ch) = chars.next() {
+        match (quote, ch) {
+            (Some('\''), '\'') => quote = None,
+            (Some('\''), _) => {}
+            (_, '\\') => {
+                chars.next();
+            }
+            (Some(_), '"') => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"') => quote = Some(ch),
+            (None, '*' | '?' | '[' | '{') => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+fn is_assignment(token: &str) -> bool {
+    let Some((name, _)) = token.split_once('=') else {
+        return false;
+    };
+    !name.is_empty()
+        && !name.starts_with(|ch: char| ch.is_ascii_digit())
+        && name
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || ch == '_')
+}
+
+fn split_segments(command: &str) -> Result<Vec<String>, String> {
+    let mut segments = Vec::new();
+    let mut current = String::new();
+    let mut quote: Option<char> = None;
+    let mut heredocs: Vec<(String, bool)> = Vec::new();
+    let mut chars = command.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match quote {
+            Some('\'') => {
+                current.push(ch);
+                if ch == '\'' {
+                    quote = None;
+                }
+            }
+            Some(_) => match ch {
+                '\\' => {
+                    current.push(ch);
+                    if let Some(next) = chars.next() {
+                        current.push(next);
+                    }
+                }
+                '`' => return Err("command substitution is not supported".to_string()),
+                '$' if chars.peek() == Some(&'(') => {
+                    return Err("command substitution is not supported".to_string());
+                }
+                '"' => {
+                    quote = None;
+                    current.push(ch);
+                }
+                _ => current.push(ch),
+            },
+            None => match ch {
+                '\\' => {
+                    current.push(ch);
+                    if let Some(next) = chars.next() {
+                        current.push(next);
+                    }
+                }
+                '\'' | '"' => {
+                    quote = Some(ch);
+                    current.push(ch);
+                }
+                // `$(...)`、`<(...)`、`(...)` 都会在这里被拒绝。
+                '`' | '(' | ')' => {
+                    return Err(format!("unsupported shell syntax: {ch}"));
+                }
+                '#' if current.is_empty() || current.ends_with(char::is_whitespace) => {
+                    while chars.peek().is_some_and(|next| *next != '\n') {
+                        chars.next();
+                    }
+                }
+                '<' if chars.peek() == Some(&'<') => {
+                    chars.next();
+                    if chars.peek() == Some(&'<') {
+                        chars.next();
+                        current.push_str("<<<");
+                        continue;
+                    }
+                    current.push_str("<<");
+                    if chars.peek() == Some(&'-') {
+                        chars.next();
+                        current.push('-');
+                    }
+                    while chars
+                        .peek()
+                        .is_some_and(|next| *next == ' ' || *next == '\t')
+                    {
+                        chars.next();
+                    }
+                    let mut word = String::new();
+                    while let Some(next) = chars.peek().copied() {
+                        if next.is_whitespace() || matches!(next, ';' | '|' | '&' | '<' | '>') {
+                            break;
+                        }
+                        word.push(next);
+                        chars.next();
+                    }
+                    let delimiter: String = word
+                        .chars()
+                        .filter(|c| !matches!(c, '\'' | '"' | '\\'))
+                        .collect();
+                    if delimiter.is_empty() {
+                        return Err("heredoc without delimiter".to_string());
+                    }
+                    let expands = delimiter.len() == word.len();
+                    current.push_str(&word);
+                    heredocs.push((delimiter, expands));
+                }
+                // `2>&1`、`<&0`、`>|` 里的 & 和 | 不是命令分隔符。
+                '&' | '|' if current.ends_with(['>', '<']) => current.push(ch),
+                '|' | '&' | ';' | '\n' => {
+                    if matches!(ch, '|' | '&') && chars.peek() == Some(&ch) {
+                        chars.next();
+                    } else if ch == '|' && chars.peek() == Some(&'&') {
+                        chars.next();
+                    }
+                    segments.push(std::mem::take(&mut current));
+                    if ch == '\n' {
+                        skip_heredoc_bodies(&mut chars, &mut heredocs)?;
+                    }
+                }
+                _ => current.push(ch),
+            },
+        }
+    }
+    if quote.is_some() {
+        return Err("unterminated quote".to_string());
+    }
+    if !heredocs.is_empty() {
+        return Err("unterminated heredoc".to_string());
+    }
+    segments.push(current);
+    Ok(segments
+        .into_iter()
+        .filter(|segment| !segment.trim().is_empty())
+        .collect())
+}
+
+// heredoc 正文是数据不是命令；按行读到结束符为止（`<<-` 允许前导 tab）。
+// 结束符没加引号时正文会做展开，和双引号一样拒绝 `$(...)`、反引号和 `${...}`。
+fn skip_heredoc_bodies(
+    chars: &mut std::iter::Peekable<std::str::Chars<'_>>,
+    heredocs: &mut Vec<(String, bool)>,
+) -> Result<(), String> {
+    for (delimiter, expands) in heredocs.drain(..) {
+        loop {
+            if chars.peek().is_none() {
+                return Err(format!("unterminated heredoc {delimiter}"));
+            }
+            let line: String = chars.by_ref().take_while(|ch| *ch != '\n').collect();
+            if line.trim_start_matches('\t') == delimiter {
+                break;
+            }
+            if expands && has_substitution(&line) {
+                return Err(format!(
+                    "substitution in unquoted heredoc {delimiter} is not supported"
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn has_substitution(line: &str) -> bool {
+    let mut chars = line.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => {
+                chars.next();
+            }
+            '`' => return true,
+            '$' if matches!(chars.peek(), Some('(' | '{')) => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commands(command: &str) -> Vec<Vec<String>> {
+        parse_shell_stages(command)
+            .expect("parse")
+            .into_iter()
+            .map(|stage| stage.argv)
+            .collect()
+    }
+
+    fn stage(argv: &[&str]) -> CommandStage {
+        CommandStage {
+            argv: argv.iter().map(|arg| arg.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn splits_on_shell_operators() {
+        assert_eq!(
+            commands("LANG=C ls -l /tmp | grep 'a|b' && rm -rf x; echo \"done;\" 2>&1 &"),
+            vec![
+                vec!["ls", "-l", "/tmp"],
+                vec!["grep", "a|b"],
+                vec!["rm", "-rf", "x"],
+                vec!["echo", "done;", "2>&1"],
+            ]
+        );
+        assert_eq!(
+            commands("cat <<'EOF' > /tmp/f\nrm -rf /\nEOF\nwc -l /tmp/f # count"),
+            vec![
+                vec!["cat", "<<EOF", ">", "/tmp/f"],
+                vec!["wc", "-l", "/tmp/f"]
+            ]
+        );
+        // 不带引号的 heredoc 正文会展开，里面的命令替换同样要拒绝。
+        assert_eq!(
+            commands("cat <<EOF > /tmp/f\nhome=$HOME \\$(literal)\nEOF"),
+            vec![vec!["cat", "<<EOF", ">", "/tmp/f"]]
+        );
+        for command in [
+            "cat <<EOF\n$(rm -rf /)\nEOF",
+            "cat <<-EOF\n\t`id`\n\tEOF",
+            "cat <<EOF\n${HOME:=$(id)}\nEOF",
+        ] {
+            assert!(parse_shell_stages(command).is_err(), "{command}");
+        }
+    }
+
+    #[test]
+    fn rejects_syntax_it_cannot_see_through() {
+        for command in [
+            "echo $(rm -rf /)",
+            "echo \"`id`\"",
+            "(cd /tmp; rm -rf x)",
+            "diff <(ls a) <(ls b)",
+            "for f in *; do rm $f; done",
+            "$CMD --flag",
+            "/bin/r? -rf /",
+            "/usr/bin/r[m] -rf /",
+            "{rm,-rf,/}",
+            "ls; LANG=C /bin/*sh -c id",
+            "echo 'unterminated",
+            "cat <<EOF\nno end",
+            "   ",
+        ] {
+            assert!(parse_shell_stages(command).is_err(), "{command}");
+        }
+        // 参数里的通配、引号中的字面量以及 `[` 测试命令本身不受影响。
+        assert_eq!(
+            commands("[ -f /tmp/x ] && ls *.log '{a,b}' && '/opt/r?' x"),
+            vec![
+                vec!["[", "-f", "/tmp/x", "]"],
+                vec!["ls", "*.log", "{a,b}"],
+                vec!["/opt/r?", "x"],
+            ]
+        );
+    }
+
+    #[test]
+    fn detects_output_redirection_to_files() {
+        for command in [
+            "ls > /tmp/out",
+            "ls >>log",
+            "echo x >| f",
+            "ls &> all.log",
+            "ls >&out.txt",
+            "cat <> f",
+            "ls 2>err.log",
+        ] {
+            assert!(redirects_output_to_file(command), "{command}");
+        }
+        for command in [
+            "ls 2>&1 | grep x",
+            "ls >&2",
+            "ls 2>/dev/null",
+            "ls > '/dev/null'",
+            "grep '>' file",
+            "echo \"a > b\"",
+            "cat < input",
+        ] {
+            assert!(!redirects_output_to_file(command), "{command}");
+        }
+    }
+
+    #[test]
+    fn detects_spoofed_pipeline() {
+        let parsed = parse_shell_stages("rm -rf /").unwrap();
+        assert_eq!(undeclared_command(&[stage(&["ls"])], &parsed), Some("rm"));
+        // proxy 只按 `|` 切分，`&&` 留在同一段里也算声明过。
+        let parsed = parse_shell_stages("ls -l && /bin/rm x;echo ok").unwrap();
+        let declared = [stage(&["ls", "-l", "&&", "rm", "x;echo", "ok"])];
+        assert_eq!(undeclared_command(&declared, &parsed), None);
+        assert_eq!(undeclared_command(&[], &parsed), None);
+    }
+}
@@ -1,15 +1,20 @@
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::SystemTime;
 
-use protocol::control::{RequestSnapshot, ResultSnapshot, RunningSnapshot, SnapshotCommonFields};
-use protocol::CommandResponse;
+use protocol::control::{
+    DetachedSnapshot, DetachedState, RequestSnapshot, ResultSnapshot, RunningSnapshot,
+    SnapshotCommonFields,
+};
+use protocol::{CommandMode, CommandResponse, CommandStatus};
 
-use super::events::PendingRequest;
+use super::events::{ApprovalDecision, PendingRequest};
+use crate::terminal_capture::{CapturedCommand, TERMINAL_SOURCE};
+use crate::time_utils::system_time_ms;
 
 pub(super) fn build_queue_snapshots(pending: &[PendingRequest]) -> Vec<RequestSnapshot> {
     pending.iter().map(to_request_snapshot).collect()
 }
 
-fn to_request_snapshot(pending: &PendingRequest) -> RequestSnapshot {
+pub(super) fn to_request_snapshot(pending: &PendingRequest) -> RequestSnapshot {
     RequestSnapshot {
         common: build_common_fields(pending),
     }
@@ -30,11 +35,27 @@ pub(super) fn running_snapshot_from_pending(
     }
 }
 
+pub(super) fn detached_snapshot_from_pending(
+    pending: &PendingRequest,
+    started_at: SystemTime,
+) -> DetachedSnapshot {
+    DetachedSnapshot {
+        common: build_common_fields(pending),
+        started_at_ms: system_time_ms(started_at),
+        state: DetachedState::Running,
+        status: None,
+        exit_code: None,
+        error: None,
+        finished_at_ms: None,
+    }
+}
+
 pub(super) fn result_snapshot_from_response(
     pending: &PendingRequest,
     response: &CommandResponse,
     finished_at: SystemTime,
 ) -> ResultSnapshot {
+    let decision = pending.decision.as_ref();
     ResultSnapshot {
         id: pending.request.id.clone(),
         status: response.status.clone(),
@@ -43,6 +64,7 @@ pub(super) fn result_snapshot_from_response(
         intent: pending.request.intent.clone(),
         mode: pending.request.mode.clone(),
         raw_command: pending.request.raw_command.clone(),
+        original_command: pending.original_command.clone(),
         pipeline: pending.request.pipeline.clone(),
         cwd: pending.request.cwd.clone(),
         peer: pending.peer.clone(),
@@ -50,13 +72,54 @@ pub(super) fn result_snapshot_from_response(
         finished_at_ms: system_time_ms(finished_at),
         stdout: response.stdout.clone(),
         stderr: response.stderr.clone(),
+        stdout_ref: response.stdout_ref.clone(),
+        stderr_ref: response.stderr_ref.clone(),
+        denied_by_rule: None,
+        approved_by: decision.and_then(ApprovalDecision::approved_by),
+        denied_by: decision.and_then(ApprovalDecision::denied_by),
+        decision_latency_ms: decision.map(|decision| decision.latency_ms),
+        deny_reason: decision.and_then(ApprovalDecision::deny_reason),
+        batch_id: pending.request.batch_id.clone(),
+        intent_tags: pending.request.intent_tags.clone(),
+        metadata: pending.request.metadata.clone(),
+        binary_output: response.binary_output(),
+        execution: None,
+        source: None,
     }
 }
 
-fn system_time_ms(time: SystemTime) -> u64 {
-    time.duration_since(UNIX_EPOCH)
-        .map(|duration| duration.as_millis() as u64)
-        .unwrap_or(0)
+pub(super) fn terminal_result_snapshot(id: &str, command: &CapturedCommand) -> ResultSnapshot {
+    let duration_ms = command.finished_at_ms.saturating_sub(command.started_at_ms);
+    ResultSnapshot {
+        id: id.to_string(),
+        status: CommandStatus::Completed,
+        exit_code: command.exit_code,
+        error: None,
+        intent: command.intent().to_string(),
+        mode: CommandMode::Shell,
+        raw_command: command.command.clone(),
+        original_command: None,
+        pipeline: Vec::new(),
+        cwd: None,
+        peer: TERMINAL_SOURCE.to_string(),
+        queued_for_secs: duration_ms / 1000,
+        finished_at_ms: command.finished_at_ms,
+        stdout: None,
+        stderr: None,
+        stdout_ref: None,
+        stderr_ref: None,
+        denied_by_rule: None,
+        approved_by: None,
+        denied_by: None,
+        decision_latency_ms: None,
+        deny_reason: None,
+        batch_id: None,
+        intent_tags: Vec::new(),
+        metadata: Some(command.metadata()),
+        binary_output: false,
+        execution: None,
+        source: Some(TERMINAL_SOURCE.to_string()),
+    }
 }
 
 fn build_common_fields(pending: &PendingRequest) -> SnapshotCommonFields {
@@ -69,10 +132,22 @@ fn build_common_fields(pending: &PendingRequest) -> SnapshotCommonFields {
         intent: request.intent.clone(),
         mode: request.mode.clone(),
         raw_command: request.raw_command.clone(),
+        original_command: pending.original_command.clone(),
         pipeline: request.pipeline.clone(),
         cwd: request.cwd.clone(),
         timeout_ms: request.timeout_ms,
         max_output_bytes: request.max_output_bytes,
         received_at_ms: system_time_ms(pending.received_at),
+        priority: request.effective_priority(),
+        batch_id: request.batch_id.clone(),
+        intent_tags: request.intent_tags.clone(),
+        metadata: request.metadata.clone(),
+        env_preview: pending.env_preview.clone(),
+        file_changes: pending.file_changes.clone(),
+        requires_confirmation: pending.requires_confirmation,
+        unparseable: pending.unparseable,
+        outside_window: pending.outside_window,
+        run_as: request.run_as.clone(),
+        temporary_rules: pending.temporary_rules.clone(),
     }
 }
@@ -1,17 +1,36 @@
-use tokio::io::{AsyncRead, AsyncReadExt};
+use std::path::PathBuf;
+
+use protocol::OutputRef;
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
 
 pub(super) async fn read_stream_capture<R: AsyncRead + Unpin>(
     mut reader: R,
     max_bytes: usize,
-) -> std::io::Result<(Vec<u8>, bool)> {
+    spool_path: Option<PathBuf>,
+) -> std::io::Result<(Vec<u8>, bool, Option<OutputRef>)> {
     let mut buffer = Vec::new();
     let mut truncated = false;
     let mut chunk = [0u8; 4096];
+    let mut spool = match spool_path {
+        Some(path) => OutputSpool::create(path).await,
+        None => None,
+    };
     loop {
         let n = reader.read(&mut chunk).await?;
         if n == 0 {
             break;
         }
+        if let Some(active) = spool.as_mut() {
+            if let Err(err) = active.write(&chunk[..n]).await {
+                tracing::warn!(
+                    error = %err,
+                    path = %active.path.display(),
+                    "failed to spool output"
+                );
+                spool = None;
+            }
+        }
         if buffer.len() < max_bytes {
             let remaining = max_bytes - buffer.len();
             let to_copy = remaining.min(n);
@@ -23,5 +42,94 @@ pub(super) async fn read_stream_capture<R: AsyncRead + Unpin>(
             truncated = true;
         }
     }
-    Ok((buffer, truncated))
+    let output_ref = match spool {
+        Some(active) => active.finish().await,
+        None => None,
+    };
+    Ok((buffer, truncated, output_ref))
+}
+
+/// 把已在内存中的输出一次性落盘，返回与流式落盘相同的引用。
+pub(super) async fn spool_output(path: PathBuf, data: &[u8]) -> Option<OutputRef> {
+    let mut spool = OutputSpool::create(path).await?;
+    if let Err(err) = spool.write(data).await {
+        tracing::warn!(error = %err, path = %spool.path.display(), "failed to spool output");
+        return None;
+    }
+    spool.finish().await
+}
+
+// 完整输出落盘（不受 max_bytes 限制），同时计算 sha256 供分页读取时校验。
+struct OutputSpool {
+    path: PathBuf,
+    file: tokio::fs::File,
+    hasher: Sha256,
+    total_bytes: u64,
+}
+
+impl OutputSpool {
+    async fn create(path: PathBuf) -> Option<Self> {
+        match tokio::fs::File::create(&path).await {
+            Ok(file) => Some(Self {
+                path,
+                file,
+                hasher: Sha256::new(),
+                total_bytes: 0,
+            }),
+            Err(err) => {
+                tracing::warn!(error = %err, path = %path.display(), "failed to create output spool");
+                None
+            }
+        }
+    }
+
+    async fn write(&mut self, data: &[u8]) -> std::io::Result<()> {
+        self.file.write_all(data).await?;
+        self.hasher.update(data);
+        self.total_bytes += data.len() as u64;
+        Ok(())
+    }
+
+    async fn finish(mut self) -> Option<OutputRef> {
+        if let Err(err) = self.file.flush().await {
+            tracing::warn!(error = %err, path = %self.path.display(), "failed to flush output spool");
+            return None;
+        }
+        Some(OutputRef {
+            sha256: format!("{:x}", self.hasher.finalize()),
+            total_bytes: self.total_bytes,
+            path: self.path.display().to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_utils::temp_dir;
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn spools_full_output_beyond_limit() {
+        let dir = temp_dir("octovalve-stream");
+        let path = dir.join("req-1.stdout");
+        let rt = tokio::runtime::Runtime::new().expect("runtime");
+        let (buffer, truncated, output_ref) = rt
+            .block_on(read_stream_capture(
+                &b"hello world"[..],
+                5,
+                Some(path.clone()),
+            ))
+            .expect("capture");
+        assert_eq!(buffer, b"hello");
+        assert!(truncated);
+        let output_ref = output_ref.expect("output ref");
+        assert_eq!(output_ref.total_bytes, 11);
+        assert_eq!(
+            output_ref.sha256,
+            "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9"
+        );
+        assert_eq!(fs::read(&path).unwrap(), b"hello world");
+        fs::remove_dir_all(&dir).ok();
+    }
 }
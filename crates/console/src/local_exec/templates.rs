@@ -0,0 +1,356 @@
+use std::collections::{BTreeMap, HashSet};
+
+use anyhow::Context;
+use protocol::config::CommandTemplate;
+use protocol::{CommandMode, CommandRequest, CommandStage};
+use regex::Regex;
+use serde::Serialize;
+use uuid::Uuid;
+
+pub(crate) const TEMPLATE_CLIENT: &str = "console-template";
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct TemplateInfo {
+    pub(crate) name: String,
+    pub(crate) description: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) target: Option<String>,
+    pub(crate) raw_command: String,
+    pub(crate) params: BTreeMap<String, String>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum TemplateError {
+    NotFound,
+    /// 参数或目标不合法，每条一个原因，直接返回给调用方。
+    Invalid(Vec<String>),
+}
+
+struct CompiledTemplate {
+    info: TemplateInfo,
+    params: BTreeMap<String, Regex>,
+}
+
+pub(crate) struct TemplateRegistry {
+    templates: Vec<CompiledTemplate>,
+}
+
+impl TemplateRegistry {
+    pub(crate) fn from_config(templates: &[CommandTemplate]) -> anyhow::Result<Self> {
+        let mut names = HashSet::new();
+        let mut compiled = Vec::with_capacity(templates.len());
+        for template in templates {
+            let name = template.name.trim();
+            if name.is_empty() {
+                anyhow::bail!("template name cannot be empty");
+            }
+            if !names.insert(name.to_string()) {
+                anyhow::bail!("duplicate template name: {name}");
+            }
+            if template.raw_command.trim().is_empty() {
+                anyhow::bail!("template {name} raw_command cannot be empty");
+            }
+            let mut params = BTreeMap::new();
+            for (param, pattern) in &template.params {
+                // 整体匹配，避免 `[a-z]+` 这类规则只校验了值的一部分。
+                let regex = Regex::new(&format!("^(?:{pattern})$")).with_context(|| {
+                    format!("template {name} param {param} has invalid regex {pattern:?}")
+                })?;
+                params.insert(param.clone(), regex);
+            }
+            for placeholder in placeholders(&template.raw_command) {
+                if !params.contains_key(placeholder) {
+                    anyhow::bail!(
+                        "template {name} uses {{{placeholder}}} but does not declare it in params"
+                    );
+                }
+            }
+            compiled.push(CompiledTemplate {
+                info: TemplateInfo {
+                    name: name.to_string(),
+                    description: template.description.trim().to_string(),
+                    target: template
+                        .target
+                        .as_deref()
+                        .map(str::trim)
+                        .filter(|glob| !glob.is_empty())
+                        .map(str::to_string),
+                    raw_command: template.raw_command.clone(),
+                    params: template.params.clone(),
+                },
+                params,
+            });
+        }
+        Ok(Self {
+            templates: compiled,
+        })
+    }
+
+    pub(crate) fn list(&self) -> Vec<TemplateInfo> {
+        self.templates
+            .iter()
+            .map(|template| template.info.clone())
+            .collect()
+    }
+
+    pub(crate) fn contains(&self, name: &str) -> bool {
+        self.templates
+            .iter()
+            .any(|template| template.info.name == name)
+    }
+
+    /// 校验目标与参数并渲染出待审批的请求；所有问题一次性返回。
+    pub(crate) fn render(
+        &self,
+        name: &str,
+        target: &str,
+        values: &BTreeMap<String, String>,
+    ) -> Result<CommandRequest, TemplateError> {
+        let template = self
+            .templates
+            .iter()
+            .find(|template| template.info.name == name)
+            .ok_or(TemplateError::NotFound)?;
+        let mut errors = Vec::new();
+        if let Some(glob) = template.info.target.as_deref() {
+            if !glob_matches(glob, target) {
+                errors.push(format!(
+                    "template {name} cannot run on target {target} (allowed: {glob})"
+                ));
+            }
+        }
+        for key in values.keys() {
+            if !template.params.contains_key(key) {
+                errors.push(format!("unknown param {key}"));
+            }
+        }
+        for (param, regex) in &template.params {
+            match values.get(param) {
+                None => errors.push(format!("missing param {param}")),
+                Some(value) if !regex.is_match(value) => errors.push(format!(
+                    "param {param} value {value:?} does not match {:?}",
+                    template.info.params[param]
+                )),
+                Some(_) => {}
+            }
+        }
+        if !errors.is_empty() {
+            return Err(TemplateError::Invalid(errors));
+        }
+
+        let raw_command = render_command(&template.info.raw_command, values);
+        let pipeline = parse_pipeline(&raw_command)
+            .map_err(|err| TemplateError::Invalid(vec![format!("rendered command: {err}")]))?;
+        let intent = if template.info.description.is_empty() {
+            format!("template {name}")
+        } else {
+            format!("template {name}: {}", template.info.description)
+        };
+        Ok(CommandRequest {
+            id: Uuid::new_v4().to_string(),
+            client: TEMPLATE_CLIENT.to_string(),
+            target: target.to_string(),
+            intent,
+            mode: CommandMode::Shell,
+            raw_command,
+            cwd: None,
+            env: None,
+            timeout_ms: None,
+            max_output_bytes: None,
+            pipeline,
+            priority: None,
+            batch_id: None,
+            intent_tags: Vec::new(),
+            metadata: Some(BTreeMap::from([("template".to_string(), name.to_string())])),
+            detach: false,
+            run_as: None,
+            compression: Vec::new(),
+            progress: false,
+            create_cwd: false,
+            readonly: false,
+            trace_id: None,
+        })
+    }
+}
+
+// `{name}` 形式的占位符；`{` 后不是合法参数名或前面是 `$` 时按普通文本处理（例如 `${VAR}`、awk 脚本）。
+fn placeholder_spans(command: &str) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    let mut offset = 0;
+    while let Some(start) = command[offset..].find('{').map(|index| offset + index) {
+        let after = start + 1;
+        match command[after..].find('}') {
+            Some(len)
+                if is_param_name(&command[after..after + len])
+                    && !command[..start].ends_with('$') =>
+            {
+                spans.push((start, after + len + 1));
+                offset = after + len + 1;
+            }
+            _ => offset = after,
+        }
+    }
+    spans
+}
+
+fn placeholders(command: &str) -> Vec<&str> {
+    placeholder_spans(command)
+        .into_iter()
+        .map(|(start, end)| &command[start + 1..end - 1])
+        .collect()
+}
+
+fn render_command(command: &str, values: &BTreeMap<String, String>) -> String {
+    let mut rendered = String::with_capacity(command.len());
+    let mut last = 0;
+    for (start, end) in placeholder_spans(command) {
+        rendered.push_str(&command[last..start]);
+        match values.get(&command[start + 1..end - 1]) {
+            Some(value) => rendered.push_str(value),
+            None => rendered.push_str(&command[start..end]),
+        }
+        last = end;
+    }
+    rendered.push_str(&command[last..]);
+    rendered
+}
+
+fn is_param_name(value: &str) -> bool {
+    !value.is_empty()
+        && value
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || ch == '_' || ch == '-')
+}
+
+// 与 octovalve-proxy 的拆分规则一致：按 shell 词法切分，`|` 分段，仅用于白名单校验。
+pub(super) fn parse_pipeline(command: &str) -> Result<Vec<CommandStage>, String> {
+    let tokens = shell_words::split(command).map_err(|err| err.to_string())?;
+    let mut pipeline = Vec::new();
+    let mut current = Vec::new();
+    for token in tokens {
+        if token == "|" {
+            if current.is_empty() {
+                return Err("empty pipeline segment".to_string());
+            }
+            pipeline.push(CommandStage {
+                argv: std::mem::take(&mut current),
+            });
+        } else {
+            current.push(token);
+        }
+    }
+    if current.is_empty() {
+        return Err("command is empty or ends with a pipe".to_string());
+    }
+    pipeline.push(CommandStage { argv: current });
+    Ok(pipeline)
+}
+
+pub(super) fn glob_matches(glob: &str, value: &str) -> bool {
+    let pattern: Vec<char> = glob.chars().collect();
+    let text: Vec<char> = value.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|ch| *ch == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry() -> TemplateRegistry {
+        TemplateRegistry::from_config(&[CommandTemplate {
+            name: "restart".to_string(),
+            description: "restart a service".to_string(),
+            target: Some("web-*".to_string()),
+            raw_command: "systemctl restart {service} && echo ${HOME}".to_string(),
+            params: BTreeMap::from([("service".to_string(), "[a-z0-9-]+".to_string())]),
+        }])
+        .expect("registry")
+    }
+
+    fn params(value: &str) -> BTreeMap<String, String> {
+        BTreeMap::from([("service".to_string(), value.to_string())])
+    }
+
+    #[test]
+    fn renders_valid_params_into_request() {
+        let request = registry()
+            .render("restart", "web-1", &params("nginx"))
+            .expect("render");
+        assert_eq!(
+            request.raw_command,
+            "systemctl restart nginx && echo ${HOME}"
+        );
+        assert_eq!(request.client, TEMPLATE_CLIENT);
+        assert_eq!(request.intent, "template restart: restart a service");
+        assert_eq!(request.pipeline[0].argv[0], "systemctl");
+    }
+
+    #[test]
+    fn rejects_bad_params_and_targets() {
+        let registry = registry();
+        assert_eq!(
+            registry.render("missing", "web-1", &params("nginx")),
+            Err(TemplateError::NotFound)
+        );
+        let Err(TemplateError::Invalid(errors)) =
+            registry.render("restart", "db-1", &params("nginx; rm -rf /"))
+        else {
+            panic!("expected invalid");
+        };
+        assert_eq!(errors.len(), 2, "{errors:?}");
+        let mut extra = params("nginx");
+        extra.insert("user".to_string(), "root".to_string());
+        assert!(registry.render("restart", "web-1", &extra).is_err());
+        assert!(registry
+            .render("restart", "web-1", &BTreeMap::new())
+            .is_err());
+    }
+
+    #[test]
+    fn validates_template_config() {
+        let template = |raw_command: &str, params: &[(&str, &str)]| CommandTemplate {
+            name: "t".to_string(),
+            description: String::new(),
+            target: None,
+            raw_command: raw_command.to_string(),
+            params: params
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+        };
+        assert!(TemplateRegistry::from_config(&[template("df -h {path}", &[])]).is_err());
+        assert!(TemplateRegistry::from_config(&[template("df -h", &[("p", "(")])]).is_err());
+        assert!(
+            TemplateRegistry::from_config(&[template("df -h", &[]), template("uptime", &[])])
+                .is_err()
+        );
+        assert!(TemplateRegistry::from_config(&[template("awk '{print $1}' f", &[])]).is_ok());
+    }
+
+    #[test]
+    fn matches_target_globs() {
+        assert!(glob_matches("*", "anything"));
+        assert!(glob_matches("web-*", "web-1"));
+        assert!(glob_matches("web-?", "web-1"));
+        assert!(!glob_matches("web-?", "web-12"));
+        assert!(glob_matches("*-prod", "api-prod"));
+        assert!(!glob_matches("web-*", "db-1"));
+    }
+}
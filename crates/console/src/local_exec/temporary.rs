@@ -0,0 +1,149 @@
+use std::path::Path;
+use std::sync::{PoisonError, RwLock};
+use std::time::{Duration, SystemTime};
+
+use protocol::control::TemporaryRuleUse;
+
+use crate::state::TemporaryRule;
+use crate::time_utils::system_time_ms;
+
+pub(crate) const MAX_TEMPORARY_RULE_TTL_SECS: u64 = 24 * 60 * 60;
+
+/// 单个目标上运维临时放行的命令。只解除 whitelist.denied（及非 immutable 的 hard_deny）的拒绝，
+/// 不会让命令跳过人工审批；只保存在内存里，console 重启后全部失效。
+#[derive(Default)]
+pub(crate) struct TemporaryRules {
+    inner: RwLock<RuleSet>,
+}
+
+#[derive(Default)]
+struct RuleSet {
+    rules: Vec<TemporaryRule>,
+    next_id: u64,
+}
+
+impl TemporaryRules {
+    pub(crate) fn add(
+        &self,
+        rule: &str,
+        ttl: Duration,
+        actor: &str,
+        now: SystemTime,
+    ) -> Result<TemporaryRule, String> {
+        let rule = rule.trim();
+        if rule.is_empty() || rule.contains(char::is_whitespace) {
+            return Err("rule must be a single command name or path".to_string());
+        }
+        if ttl.is_zero() || ttl.as_secs() > MAX_TEMPORARY_RULE_TTL_SECS {
+            return Err(format!(
+                "ttl_secs must be between 1 and {MAX_TEMPORARY_RULE_TTL_SECS}"
+            ));
+        }
+        let mut inner = self.inner.write().unwrap_or_else(PoisonError::into_inner);
+        inner.next_id += 1;
+        let created_at_ms = system_time_ms(now);
+        let entry = TemporaryRule {
+            id: format!("tmp-{}", inner.next_id),
+            rule: rule.to_string(),
+            actor: actor.to_string(),
+            created_at_ms,
+            expires_at_ms: created_at_ms.saturating_add(ttl.as_millis() as u64),
+        };
+        inner.rules.push(entry.clone());
+        Ok(entry)
+    }
+
+    /// 尚未过期的规则，顺带清掉已过期的。
+    pub(crate) fn list(&self, now: SystemTime) -> Vec<TemporaryRule> {
+        let now_ms = system_time_ms(now);
+        let mut inner = self.inner.write().unwrap_or_else(PoisonError::into_inner);
+        inner.rules.retain(|rule| rule.expires_at_ms > now_ms);
+        inner.rules.clone()
+    }
+
+    pub(crate) fn revoke(&self, id: &str, now: SystemTime) -> Option<TemporaryRule> {
+        let now_ms = system_time_ms(now);
+        let mut inner = self.inner.write().unwrap_or_else(PoisonError::into_inner);
+        let index = inner
+            .rules
+            .iter()
+            .position(|rule| rule.id == id && rule.expires_at_ms > now_ms)?;
+        Some(inner.rules.remove(index))
+    }
+
+    pub(crate) fn matching(&self, command: &str, now: SystemTime) -> Option<TemporaryRuleUse> {
+        let now_ms = system_time_ms(now);
+        let inner = self.inner.read().unwrap_or_else(PoisonError::into_inner);
+        inner
+            .rules
+            .iter()
+            .find(|rule| rule.expires_at_ms > now_ms && rule_matches(&rule.rule, command))
+            .map(|rule| TemporaryRuleUse {
+                id: rule.id.clone(),
+                rule: rule.rule.clone(),
+                actor: rule.actor.clone(),
+            })
+    }
+}
+
+/// 与 whitelist.denied 相同：规则等于命令本身或其 basename 即命中。
+pub(crate) fn rule_matches(rule: &str, command: &str) -> bool {
+    rule == command
+        || Path::new(command)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name == rule)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::UNIX_EPOCH;
+
+    #[test]
+    fn rules_match_by_basename_and_expire() {
+        let rules = TemporaryRules::default();
+        let now = UNIX_EPOCH + Duration::from_secs(1_000);
+        let added = rules
+            .add("iptables", Duration::from_secs(60), "ops", now)
+            .expect("add");
+        assert_eq!(added.id, "tmp-1");
+        assert_eq!(added.expires_at_ms, 1_060_000);
+
+        let used = rules.matching("/usr/sbin/iptables", now).expect("match");
+        assert_eq!(used.id, "tmp-1");
+        assert_eq!(used.actor, "ops");
+        assert!(rules.matching("ip6tables", now).is_none());
+
+        let later = now + Duration::from_secs(60);
+        assert!(rules.matching("iptables", later).is_none());
+        assert!(rules.list(later).is_empty());
+        assert!(rules.revoke("tmp-1", later).is_none());
+    }
+
+    #[test]
+    fn rejects_invalid_rules_and_revokes() {
+        let rules = TemporaryRules::default();
+        let now = UNIX_EPOCH + Duration::from_secs(1_000);
+        assert!(rules.add(" ", Duration::from_secs(60), "ops", now).is_err());
+        assert!(rules
+            .add("rm -rf", Duration::from_secs(60), "ops", now)
+            .is_err());
+        assert!(rules
+            .add(
+                "rm",
+                Duration::from_secs(MAX_TEMPORARY_RULE_TTL_SECS + 1),
+                "ops",
+                now
+            )
+            .is_err());
+        assert!(rules.add("rm", Duration::ZERO, "ops", now).is_err());
+
+        rules
+            .add("reboot", Duration::from_secs(60), "ops", now)
+            .expect("add");
+        assert_eq!(rules.list(now).len(), 1);
+        assert!(rules.revoke("tmp-1", now).is_some());
+        assert!(rules.matching("reboot", now).is_none());
+    }
+}
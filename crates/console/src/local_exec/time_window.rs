@@ -0,0 +1,355 @@
+//! 白名单时间窗：按 console 所在主机的本地时间（或 `whitelist.timezone`）判断请求是否落在允许的时段内。
+//!
+//! 时区支持 `local`、`UTC`、固定偏移（`+08:00`）与 IANA 名称（`Europe/Berlin`，由 chrono-tz 内置的时区库处理夏令时）；
+//! `local` 由系统 `localtime_r` 按 `TZ` / `/etc/localtime` 处理夏令时。
+
+use std::collections::HashSet;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use chrono::{DateTime, Offset, TimeZone};
+use chrono_tz::Tz;
+use protocol::CommandRequest;
+use serde::Deserialize;
+
+const MINUTE_SECS: i64 = 60;
+const HOUR_SECS: i64 = 60 * MINUTE_SECS;
+const DAY_SECS: i64 = 24 * HOUR_SECS;
+const DAY_NAMES: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+#[derive(Debug, Clone, Deserialize, Default)]
+pub(crate) struct TimeWindowConfig {
+    /// 受限的命令，按完整路径或 basename 匹配（同 allowed）；与 intent_tags 任一命中即受限，两者都为空时限制所有请求。
+    #[serde(default)]
+    pub(crate) commands: Vec<String>,
+    #[serde(default)]
+    pub(crate) intent_tags: Vec<String>,
+    /// `HH:MM-HH:MM`，含起点不含终点；终点早于起点表示跨午夜，如 `22:00-06:00`。
+    #[serde(default)]
+    pub(crate) allowed_hours: Option<String>,
+    /// `mon`..`sun` 或区间 `mon-fri`，为空表示每天。
+    #[serde(default)]
+    pub(crate) allowed_days: Vec<String>,
+    /// 时间窗外直接拒绝；默认只是禁止自动批准，交给人工审批。
+    #[serde(default)]
+    pub(crate) deny_outside_window: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct LocalClock {
+    /// 0 为周日。
+    pub(crate) weekday: u32,
+    pub(crate) minute_of_day: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum TimeZoneSpec {
+    Local,
+    /// 相对 UTC 的秒数，东正西负。
+    Fixed(i64),
+    Named(Tz),
+}
+
+impl TimeZoneSpec {
+    pub(crate) fn parse(value: Option<&str>) -> Result<Self, String> {
+        let value = value.map(str::trim).unwrap_or("local");
+        if value.is_empty() || value.eq_ignore_ascii_case("local") {
+            return Ok(Self::Local);
+        }
+        if value.eq_ignore_ascii_case("utc") {
+            return Ok(Self::Fixed(0));
+        }
+        if value.starts_with(['+', '-']) {
+            // 固定偏移按 ISO 习惯东正西负。
+            return parse_offset(value)
+                .map(Self::Fixed)
+                .ok_or_else(|| format!("invalid utc offset {value:?} (expected +HH:MM)"));
+        }
+        value.parse::<Tz>().map(Self::Named).map_err(|_| {
+            format!("unknown timezone {value:?} (expected local, UTC, +HH:MM or an IANA name like Europe/Berlin)")
+        })
+    }
+
+    fn utc_offset(&self, utc_secs: i64) -> i64 {
+        match self {
+            Self::Local => local_utc_offset(utc_secs),
+            Self::Fixed(offset) => *offset,
+            Self::Named(tz) => DateTime::from_timestamp(utc_secs, 0)
+                .map(|utc| {
+                    i64::from(
+                        tz.offset_from_utc_datetime(&utc.naive_utc())
+                            .fix()
+                            .local_minus_utc(),
+                    )
+                })
+                .unwrap_or(0),
+        }
+    }
+
+    pub(crate) fn clock(&self, now: SystemTime) -> LocalClock {
+        let utc_secs = now
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs() as i64)
+            .unwrap_or(0);
+        let local = utc_secs + self.utc_offset(utc_secs);
+        let days = local.div_euclid(DAY_SECS);
+        LocalClock {
+            // 1970-01-01 是周四。
+            weekday: (days + 4).rem_euclid(7) as u32,
+            minute_of_day: (local.rem_euclid(DAY_SECS) / MINUTE_SECS) as u32,
+        }
+    }
+}
+
+#[cfg(unix)]
+fn local_utc_offset(utc_secs: i64) -> i64 {
+    let time = utc_secs as libc::time_t;
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    // localtime_r 可重入，夏令时由系统时区库处理。
+    if unsafe { libc::localtime_r(&time, &mut tm) }.is_null() {
+        return 0;
+    }
+    tm.tm_gmtoff as i64
+}
+
+// 非 unix 主机没有 tm_gmtoff，`local` 退化为 UTC；需要本地时间时请写明偏移或 IANA 时区名。
+#[cfg(not(unix))]
+fn local_utc_offset(_utc_secs: i64) -> i64 {
+    0
+}
+
+/// `[+-]hh[:mm[:ss]]`，返回带符号的秒数。
+fn parse_offset(value: &str) -> Option<i64> {
+    let (sign, digits) = match value.as_bytes().first()? {
+        b'-' => (-1, &value[1..]),
+        b'+' => (1, &value[1..]),
+        _ => (1, value),
+    };
+    let mut total = 0;
+    let mut parts = 0;
+    for (part, unit) in digits.split(':').zip([HOUR_SECS, MINUTE_SECS, 1]) {
+        let limit = if unit == HOUR_SECS { 24 } else { 59 };
+        let part = part
+            .parse::<i64>()
+            .ok()
+            .filter(|value| (0..=limit).contains(value))?;
+        total += part * unit;
+        parts += 1;
+    }
+    (parts > 0 && digits.split(':').count() == parts).then_some(sign * total)
+}
+
+/// 编译后的一条时间窗。
+#[derive(Debug, Clone)]
+pub(crate) struct TimeWindow {
+    commands: HashSet<String>,
+    intent_tags: Vec<String>,
+    /// 分钟数区间 `[start, end)`；end <= start 时跨午夜。
+    hours: Option<(u32, u32)>,
+    /// bit0 为周日。
+    days: u8,
+    pub(crate) deny: bool,
+    pub(crate) description: String,
+}
+
+impl TimeWindow {
+    pub(crate) fn from_config(config: &TimeWindowConfig) -> Result<Self, String> {
+        let hours = config
+            .allowed_hours
+            .as_deref()
+            .map(|hours| {
+                parse_hours(hours).ok_or_else(|| {
+                    format!("invalid allowed_hours {hours:?} (expected HH:MM-HH:MM)")
+                })
+            })
+            .transpose()?;
+        let mut days = 0u8;
+        for day in &config.allowed_days {
+            days |= parse_days(day).ok_or_else(|| {
+                format!("invalid allowed_days entry {day:?} (expected mon..sun or mon-fri)")
+            })?;
+        }
+        if hours.is_none() && days == 0 {
+            return Err("time window needs allowed_hours or allowed_days".to_string());
+        }
+        let mut description = Vec::new();
+        if let Some(hours) = &config.allowed_hours {
+            description.push(hours.trim().to_string());
+        }
+        if !config.allowed_days.is_empty() {
+            description.push(config.allowed_days.join(","));
+        }
+        Ok(Self {
+            commands: config.commands.iter().cloned().collect(),
+            intent_tags: protocol::normalize_intent_tags(config.intent_tags.clone()),
+            hours,
+            days: if days == 0 { 0x7f } else { days },
+            deny: config.deny_outside_window,
+            description: description.join(" "),
+        })
+    }
+
+    pub(crate) fn applies_to(&self, request: &CommandRequest) -> bool {
+        if self.commands.is_empty() && self.intent_tags.is_empty() {
+            return true;
+        }
+        self.intent_tags
+            .iter()
+            .any(|tag| request.has_intent_tag(tag))
+            || request.pipeline.iter().any(|stage| {
+                stage.command().is_some_and(|command| {
+                    self.commands.contains(command)
+                        || Path::new(command)
+                            .file_name()
+                            .and_then(|name| name.to_str())
+                            .is_some_and(|name| self.commands.contains(name))
+                })
+            })
+    }
+
+    pub(crate) fn contains(&self, clock: &LocalClock) -> bool {
+        if self.days & (1 << clock.weekday) == 0 {
+            return false;
+        }
+        match self.hours {
+            None => true,
+            Some((start, end)) if start < end => (start..end).contains(&clock.minute_of_day),
+            Some((start, end)) => clock.minute_of_day >= start || clock.minute_of_day < end,
+        }
+    }
+}
+
+fn parse_hours(value: &str) -> Option<(u32, u32)> {
+    let (start, end) = value.trim().split_once('-')?;
+    let minute = |value: &str| {
+        let (hour, minute) = value.trim().split_once(':')?;
+        let hour = hour.parse::<u32>().ok()?;
+        let minute = minute.parse::<u32>().ok().filter(|minute| *minute < 60)?;
+        let total = hour * 60 + minute;
+        (total <= 24 * 60).then_some(total)
+    };
+    let (start, end) = (minute(start)?, minute(end)?);
+    (start != end && start < 24 * 60).then_some((start, end))
+}
+
+fn parse_days(value: &str) -> Option<u8> {
+    let index = |name: &str| {
+        let name = name.trim().to_ascii_lowercase();
+        DAY_NAMES.iter().position(|day| *day == name)
+    };
+    match value.split_once('-') {
+        None => index(value).map(|day| 1 << day),
+        Some((start, end)) => {
+            let (start, end) = (index(start)?, index(end)?);
+            // `fri-mon` 这样的区间跨过周末。
+            let mut mask = 0u8;
+            let mut day = start;
+            loop {
+                mask |= 1 << day;
+                if day == end {
+                    break;
+                }
+                day = (day + 1) % 7;
+            }
+            Some(mask)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn at(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    fn offset(tz: &TimeZoneSpec, secs: i64) -> i64 {
+        tz.utc_offset(secs) / HOUR_SECS
+    }
+
+    #[test]
+    fn named_zones_follow_dst_transitions() {
+        let berlin = TimeZoneSpec::parse(Some("Europe/Berlin")).expect("tz");
+        // 2026-03-29 01:00 UTC 开始夏令时，2026-10-25 01:00 UTC 结束。
+        assert_eq!(offset(&berlin, 1_774_745_940), 1);
+        assert_eq!(offset(&berlin, 1_774_746_000), 2);
+        assert_eq!(offset(&berlin, 1_792_889_940), 2);
+        assert_eq!(offset(&berlin, 1_792_890_000), 1);
+
+        // 南半球夏令时跨年：2026-04-05 03:00 AEDT 结束，2026-10-04 02:00 AEST 开始。
+        let sydney = TimeZoneSpec::parse(Some("Australia/Sydney")).expect("tz");
+        assert_eq!(offset(&sydney, 1_775_318_340), 11);
+        assert_eq!(offset(&sydney, 1_775_318_400), 10);
+        assert_eq!(offset(&sydney, 1_791_043_140), 10);
+        assert_eq!(offset(&sydney, 1_791_043_200), 11);
+
+        assert_eq!(
+            TimeZoneSpec::parse(Some("+05:30")),
+            Ok(TimeZoneSpec::Fixed(5 * HOUR_SECS + 30 * MINUTE_SECS))
+        );
+        assert_eq!(TimeZoneSpec::parse(Some("UTC")), Ok(TimeZoneSpec::Fixed(0)));
+        assert_eq!(
+            offset(&TimeZoneSpec::parse(Some("Asia/Shanghai")).expect("tz"), 0),
+            8
+        );
+        assert_eq!(TimeZoneSpec::parse(None), Ok(TimeZoneSpec::Local));
+        for invalid in ["Mars/Olympus_Mons", "CET-1CEST,M3.5.0,M10.5.0/3", "+25:00"] {
+            assert!(TimeZoneSpec::parse(Some(invalid)).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn business_hours_window_uses_local_clock() {
+        let berlin = TimeZoneSpec::parse(Some("Europe/Berlin")).expect("tz");
+        let window = TimeWindow::from_config(&TimeWindowConfig {
+            allowed_hours: Some("09:00-18:00".to_string()),
+            allowed_days: vec!["mon-fri".to_string()],
+            ..TimeWindowConfig::default()
+        })
+        .expect("window");
+        let inside = |secs| window.contains(&berlin.clock(at(secs)));
+        // 周三 07:30 UTC：夏令时 09:30 在窗内，冬令时 08:30 不在。
+        assert!(inside(1_791_963_000));
+        assert!(!inside(1_768_375_800));
+        assert!(inside(1_768_379_400));
+        // 周三 18:30 CEST 与周六上午。
+        assert!(!inside(1_791_995_400));
+        assert!(!inside(1_792_222_200));
+    }
+
+    #[test]
+    fn parses_overnight_hours_and_day_lists() {
+        let window = TimeWindow::from_config(&TimeWindowConfig {
+            allowed_hours: Some("22:00-06:00".to_string()),
+            allowed_days: vec!["fri-mon".to_string(), "Wed".to_string()],
+            ..TimeWindowConfig::default()
+        })
+        .expect("window");
+        let clock = |weekday, hour: u32| LocalClock {
+            weekday,
+            minute_of_day: hour * 60,
+        };
+        assert!(window.contains(&clock(0, 23)));
+        assert!(window.contains(&clock(1, 5)));
+        assert!(!window.contains(&clock(1, 6)));
+        assert!(window.contains(&clock(3, 2)));
+        assert!(!window.contains(&clock(2, 23)));
+
+        for (hours, days) in [
+            (Some("9-18"), vec![]),
+            (Some("09:00-09:00"), vec![]),
+            (Some("09:00-25:00"), vec![]),
+            (None, vec!["weekday"]),
+            (None, vec![]),
+        ] {
+            let config = TimeWindowConfig {
+                allowed_hours: hours.map(str::to_string),
+                allowed_days: days.into_iter().map(str::to_string).collect(),
+                ..TimeWindowConfig::default()
+            };
+            assert!(TimeWindow::from_config(&config).is_err(), "{hours:?}");
+        }
+    }
+}
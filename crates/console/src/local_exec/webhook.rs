@@ -0,0 +1,301 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use protocol::{CommandRequest, CommandResponse};
+use serde::Serialize;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc::{self, error::TrySendError};
+
+use crate::time_utils::system_time_ms;
+
+/// 投递失败的结果追加到审计根目录下的这个文件（每行一条 JSON），不会静默丢弃。
+pub(crate) const SPOOL_FILE: &str = "result-webhook.spool.jsonl";
+const QUEUE_CAPACITY: usize = 256;
+const MAX_ATTEMPTS: u32 = 3;
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 每个请求结束后 POST 给 `result_webhook` 的请求体。
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct ResultDelivery {
+    id: String,
+    target: String,
+    client: String,
+    intent: String,
+    received_at_ms: u64,
+    finished_at_ms: u64,
+    duration_ms: u64,
+    response: CommandResponse,
+}
+
+impl ResultDelivery {
+    /// `duration` 是实际执行耗时，未执行就结束的请求为 0。
+    pub(crate) fn new(
+        request: &CommandRequest,
+        response: &CommandResponse,
+        received_at: SystemTime,
+        finished_at: SystemTime,
+        duration: Duration,
+    ) -> Self {
+        Self {
+            id: request.id.clone(),
+            target: request.target.clone(),
+            client: request.client.clone(),
+            intent: request.intent.clone(),
+            received_at_ms: system_time_ms(received_at),
+            finished_at_ms: system_time_ms(finished_at),
+            duration_ms: duration.as_millis() as u64,
+            response: response.clone(),
+        }
+    }
+}
+
+/// 结果 webhook：投递在独立任务里按顺序进行，不拖慢请求处理；队列有上限，
+/// 队列满或重试耗尽的结果写入 spool 文件。
+#[derive(Clone)]
+pub(crate) struct ResultWebhook {
+    tx: mpsc::Sender<ResultDelivery>,
+    spool_path: Arc<PathBuf>,
+}
+
+impl ResultWebhook {
+    pub(crate) fn spawn(url: &str, spool_path: PathBuf) -> anyhow::Result<Self> {
+        Self::spawn_with_backoff(url, spool_path, INITIAL_BACKOFF)
+    }
+
+    fn spawn_with_backoff(
+        url: &str,
+        spool_path: PathBuf,
+        initial_backoff: Duration,
+    ) -> anyhow::Result<Self> {
+        let url = parse_webhook_url(url)
+            .map_err(|err| anyhow::anyhow!("invalid result_webhook {url}: {err}"))?;
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()?;
+        let (tx, rx) = mpsc::channel(QUEUE_CAPACITY);
+        let spool_path = Arc::new(spool_path);
+        tokio::spawn(deliver_loop(
+            client,
+            url,
+            rx,
+            Arc::clone(&spool_path),
+            initial_backoff,
+        ));
+        Ok(Self { tx, spool_path })
+    }
+
+    /// 只入队，不等待投递。
+    pub(crate) fn notify(&self, delivery: ResultDelivery) {
+        let delivery = match self.tx.try_send(delivery) {
+            Ok(()) => return,
+            Err(TrySendError::Full(delivery)) | Err(TrySendError::Closed(delivery)) => delivery,
+        };
+        tracing::warn!(
+            event = "webhook.queue_full",
+            id = %delivery.id,
+            "result webhook queue unavailable; spooling result"
+        );
+        let spool_path = Arc::clone(&self.spool_path);
+        tokio::spawn(async move { spool(&spool_path, &delivery).await });
+    }
+}
+
+/// 只接受 http/https 地址；`PolicyConfig::check` 也用它在启动前报错。
+pub(crate) fn parse_webhook_url(url: &str) -> Result<reqwest::Url, String> {
+    let url = reqwest::Url::parse(url).map_err(|err| err.to_string())?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err("scheme must be http or https".to_string());
+    }
+    Ok(url)
+}
+
+async fn deliver_loop(
+    client: reqwest::Client,
+    url: reqwest::Url,
+    mut rx: mpsc::Receiver<ResultDelivery>,
+    spool_path: Arc<PathBuf>,
+    initial_backoff: Duration,
+) {
+    while let Some(delivery) = rx.recv().await {
+        if let Err(err) = deliver(&client, &url, &delivery, initial_backoff).await {
+            tracing::warn!(
+                event = "webhook.delivery_failed",
+                id = %delivery.id,
+                target = %delivery.target,
+                attempts = MAX_ATTEMPTS,
+                error = %err,
+            );
+            spool(&spool_path, &delivery).await;
+        }
+    }
+}
+
+// 最多尝试 MAX_ATTEMPTS 次，间隔按 initial_backoff 翻倍；非 2xx 也算失败。
+async fn deliver(
+    client: &reqwest::Client,
+    url: &reqwest::Url,
+    delivery: &ResultDelivery,
+    initial_backoff: Duration,
+) -> Result<(), String> {
+    let mut backoff = initial_backoff;
+    let mut last_error = String::new();
+    for attempt in 1..=MAX_ATTEMPTS {
+        match client.post(url.clone()).json(delivery).send().await {
+            Ok(response) if response.status().is_success() => return Ok(()),
+            Ok(response) => last_error = format!("status {}", response.status()),
+            Err(err) => last_error = err.to_string(),
+        }
+        if attempt < MAX_ATTEMPTS {
+            tokio::time::sleep(backoff).await;
+            backoff *= 2;
+        }
+    }
+    Err(last_error)
+}
+
+async fn spool(path: &Path, delivery: &ResultDelivery) {
+    let Ok(mut line) = serde_json::to_string(delivery) else {
+        return;
+    };
+    line.push('\n');
+    let result = async {
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await?;
+        file.write_all(line.as_bytes()).await?;
+        file.flush().await
+    }
+    .await;
+    if let Err(err) = result {
+        tracing::error!(
+            event = "webhook.spool_failed",
+            id = %delivery.id,
+            path = %path.display(),
+            error = %err,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_utils::temp_dir;
+    use super::*;
+    use axum::http::StatusCode;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn delivery(id: &str) -> ResultDelivery {
+        let request = CommandRequest {
+            id: id.to_string(),
+            client: "ci".to_string(),
+            target: "dev".to_string(),
+            intent: "run tests".to_string(),
+            raw_command: "cargo test".to_string(),
+            ..Default::default()
+        };
+        let now = SystemTime::now();
+        ResultDelivery::new(
+            &request,
+            &CommandResponse::completed(id, 0, None, None),
+            now,
+            now,
+            Duration::from_millis(1500),
+        )
+    }
+
+    // 前 `failures` 次返回 503，之后返回 200；记录收到的请求体。
+    async fn stub_server(
+        failures: usize,
+    ) -> (String, Arc<AtomicUsize>, Arc<std::sync::Mutex<Vec<String>>>) {
+        let hits = Arc::new(AtomicUsize::new(0));
+        let bodies = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let (counter, received) = (Arc::clone(&hits), Arc::clone(&bodies));
+        let app = axum::Router::new().route(
+            "/hook",
+            axum::routing::post(move |body: String| async move {
+                received.lock().unwrap().push(body);
+                if counter.fetch_add(1, Ordering::SeqCst) < failures {
+                    StatusCode::SERVICE_UNAVAILABLE
+                } else {
+                    StatusCode::OK
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        (format!("http://{addr}/hook"), hits, bodies)
+    }
+
+    async fn wait_for(condition: impl Fn() -> bool) {
+        for _ in 0..300 {
+            if condition() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("condition not reached");
+    }
+
+    #[tokio::test]
+    async fn retries_until_the_webhook_accepts() {
+        let dir = temp_dir("octovalve-webhook-retry");
+        let (url, hits, bodies) = stub_server(2).await;
+        let spool_path = dir.join(SPOOL_FILE);
+        let webhook =
+            ResultWebhook::spawn_with_backoff(&url, spool_path.clone(), Duration::from_millis(5))
+                .expect("webhook");
+        webhook.notify(delivery("req-1"));
+        wait_for(|| hits.load(Ordering::SeqCst) == 3).await;
+
+        let body: serde_json::Value =
+            serde_json::from_str(&bodies.lock().unwrap()[2]).expect("json body");
+        assert_eq!(body["id"], "req-1");
+        assert_eq!(body["client"], "ci");
+        assert_eq!(body["intent"], "run tests");
+        assert_eq!(body["duration_ms"], 1500);
+        assert_eq!(body["response"]["status"], "completed");
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!spool_path.exists());
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn spools_results_after_the_last_attempt_fails() {
+        let dir = temp_dir("octovalve-webhook-spool");
+        let (url, hits, _) = stub_server(usize::MAX).await;
+        let spool_path = dir.join(SPOOL_FILE);
+        let webhook =
+            ResultWebhook::spawn_with_backoff(&url, spool_path.clone(), Duration::from_millis(5))
+                .expect("webhook");
+        webhook.notify(delivery("req-1"));
+        webhook.notify(delivery("req-2"));
+        wait_for(|| {
+            std::fs::read_to_string(&spool_path)
+                .map(|spooled| spooled.lines().count() == 2)
+                .unwrap_or(false)
+        })
+        .await;
+        assert_eq!(hits.load(Ordering::SeqCst), 2 * MAX_ATTEMPTS as usize);
+        let spooled = std::fs::read_to_string(&spool_path).unwrap();
+        let spooled: Vec<serde_json::Value> = spooled
+            .lines()
+            .map(|line| serde_json::from_str(line).expect("spooled json"))
+            .collect();
+        assert_eq!(spooled[0]["id"], "req-1");
+        assert_eq!(spooled[1]["id"], "req-2");
+        assert_eq!(spooled[1]["response"]["exit_code"], 0);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn rejects_non_http_urls() {
+        let rt = tokio::runtime::Runtime::new().expect("runtime");
+        let _guard = rt.enter();
+        assert!(ResultWebhook::spawn("ftp://example.invalid/hook", PathBuf::from("x")).is_err());
+        assert!(ResultWebhook::spawn("not a url", PathBuf::from("x")).is_err());
+    }
+}
@@ -0,0 +1,141 @@
+use base64::engine::general_purpose::STANDARD as BASE64_ENGINE;
+use base64::Engine;
+use protocol::config::TargetShell;
+use protocol::CommandRequest;
+
+// Windows 目标通过 OpenSSH 执行，远端默认 shell 为 cmd.exe。
+// 这里不做 setsid/pidfile 包装：sshd 会把会话进程放进 Job Object，
+// 断开 ssh 连接即可结束整棵进程树。
+pub(super) fn build_windows_command(shell: TargetShell, request: &CommandRequest) -> String {
+    match shell {
+        TargetShell::Powershell => build_powershell_command(request),
+        _ => build_cmd_command(request),
+    }
+}
+
+fn build_cmd_command(request: &CommandRequest) -> String {
+    let mut command = String::new();
+    if let Some(cwd) = non_empty_cwd(request) {
+        command.push_str("cd /d ");
+        command.push_str(&cmd_quote(cwd));
+        command.push_str(" && ");
+    }
+    if let Some(env) = request.env.as_ref() {
+        for (key, value) in env {
+            if key.trim().is_empty() {
+                continue;
+            }
+            command.push_str(&format!("set {} && ", cmd_quote(&format!("{key}={value}"))));
+        }
+    }
+    command.push_str(request.raw_command.trim());
+    format!("cmd /S /C \"{command}\"")
+}
+
+fn build_powershell_command(request: &CommandRequest) -> String {
+    let mut script = String::from("$ErrorActionPreference = 'Continue'\n");
+    if let Some(cwd) = non_empty_cwd(request) {
+        script.push_str(&format!("Set-Location -LiteralPath {}\n", ps_quote(cwd)));
+    }
+    if let Some(env) = request.env.as_ref() {
+        for (key, value) in env {
+            if key.trim().is_empty() {
+                continue;
+            }
+            script.push_str(&format!(
+                "Set-Item -LiteralPath {} -Value {}\n",
+                ps_quote(&format!("env:{key}")),
+                ps_quote(value)
+            ));
+        }
+    }
+    script.push_str(request.raw_command.trim());
+    script.push_str(
+        "\n$octovalveOk = $?\nif ($LASTEXITCODE) { exit $LASTEXITCODE }\nif (-not $octovalveOk) { exit 1 }\n",
+    );
+    format!(
+        "powershell -NoLogo -NoProfile -NonInteractive -ExecutionPolicy Bypass -EncodedCommand {}",
+        encode_powershell_script(&script)
+    )
+}
+
+fn non_empty_cwd(request: &CommandRequest) -> Option<&str> {
+    request
+        .cwd
+        .as_deref()
+        .filter(|value| !value.trim().is_empty())
+}
+
+// -EncodedCommand 需要 UTF-16LE 的 base64，避免多层引号转义。
+fn encode_powershell_script(script: &str) -> String {
+    let bytes: Vec<u8> = script
+        .encode_utf16()
+        .flat_map(|unit| unit.to_le_bytes())
+        .collect();
+    BASE64_ENGINE.encode(bytes)
+}
+
+fn cmd_quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('"', "\"\""))
+}
+
+fn ps_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn sample_request() -> CommandRequest {
+        let mut env = BTreeMap::new();
+        env.insert("RUST_LOG".to_string(), "debug".to_string());
+        CommandRequest {
+            id: "req-1".to_string(),
+            client: "client".to_string(),
+            target: "win".to_string(),
+            intent: "build".to_string(),
+            raw_command: "cargo build".to_string(),
+            cwd: Some("C:\\work\\repo".to_string()),
+            env: Some(env),
+            ..Default::default()
+        }
+    }
+
+    fn decode_powershell_script(command: &str) -> String {
+        let encoded = command.rsplit(' ').next().expect("encoded");
+        let bytes = BASE64_ENGINE.decode(encoded).expect("base64");
+        let units: Vec<u16> = bytes
+            .chunks(2)
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+            .collect();
+        String::from_utf16(&units).expect("utf16")
+    }
+
+    #[test]
+    fn cmd_command_sets_cwd_and_env() {
+        let cmd = build_windows_command(TargetShell::Cmd, &sample_request());
+        assert_eq!(
+            cmd,
+            "cmd /S /C \"cd /d \"C:\\work\\repo\" && set \"RUST_LOG=debug\" && cargo build\""
+        );
+    }
+
+    #[test]
+    fn powershell_command_is_encoded() {
+        let cmd = build_windows_command(TargetShell::Powershell, &sample_request());
+        assert!(cmd.starts_with("powershell -NoLogo -NoProfile -NonInteractive"));
+        let script = decode_powershell_script(&cmd);
+        assert!(script.contains("Set-Location -LiteralPath 'C:\\work\\repo'\n"));
+        assert!(script.contains("Set-Item -LiteralPath 'env:RUST_LOG' -Value 'debug'\n"));
+        assert!(script.contains("cargo build\n"));
+        assert!(script.contains("exit $LASTEXITCODE"));
+    }
+
+    #[test]
+    fn quotes_are_escaped() {
+        assert_eq!(cmd_quote("a\"b"), "\"a\"\"b\"");
+        assert_eq!(ps_quote("it's"), "'it''s'");
+    }
+}
@@ -1,124 +1,389 @@
+mod check;
 mod cli;
 mod config;
 mod control;
+mod ctl;
+mod event_log;
 mod events;
+mod forwards;
+mod http_security;
+mod limits;
 mod local_exec;
+mod metrics;
 mod runtime;
 mod shell_utils;
 mod state;
+mod summary;
 mod terminal;
+mod terminal_capture;
+mod time_utils;
 mod uploads;
 
-use crate::cli::Args;
+use crate::cli::{Args, Command};
 use crate::config::load_console_config;
-use crate::control::ServiceSnapshot;
+use crate::control::{DetachedSnapshot, HistoryPage, OutputChunk, ResultSnapshot, ServiceSnapshot};
+use crate::event_log::{sequenced_payload, spawn_event_log, EventLog, Replay};
 use crate::events::ConsoleEvent;
-use crate::local_exec::{spawn_local_exec, PolicyConfig};
-use crate::state::{build_console_state, ConsoleState, ControlCommand, TargetInfo};
-use crate::terminal::terminal_ws_handler;
+use crate::forwards::{ForwardError, ForwardManager};
+use crate::http_security::{redacted_uri, require_api_token, resolve_api_token, serve_tls};
+use crate::limits::{is_message_too_big, message_too_big_close, ServerLimits};
+use crate::local_exec::{
+    audit_usage, export_target_history, fetch_output, history_page, spawn_local_exec,
+    spawn_scheduler, AuditUsage, ClientAccess, CommandIntake, ExportFormat, ExportRange,
+    PolicyConfig, ScheduleInfo, ScheduleRegistry, TemplateError, TemplateInfo, TemplateRegistry,
+};
+use crate::metrics::{Metrics, TargetGauges};
+use crate::state::{
+    build_console_state, ConsoleState, ConsoleSummary, ControlCommand, EditRejection, ForwardInfo,
+    TargetInfo, TargetStatus, TemporaryRule,
+};
+use crate::summary::spawn_summary_notifier;
+use crate::terminal::{
+    inject_terminal_command, list_terminal_sessions, terminal_ws_handler, TerminalRegistry,
+};
 use crate::uploads::{DirectoryEntry, UploadRegistry, UploadRequest, UploadStatus};
 use anyhow::Context;
 use axum::body::Body;
 use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
 use axum::extract::State;
 use axum::extract::{Path, Query};
-use axum::http::Request;
+use axum::http::header::{CONTENT_DISPOSITION, CONTENT_TYPE};
 use axum::http::StatusCode;
+use axum::http::{HeaderValue, Request};
 use axum::middleware::{self, Next};
-use axum::response::IntoResponse;
 use axum::response::Response;
+use axum::response::{Html, IntoResponse};
 use axum::routing::get;
-use axum::routing::post;
-use axum::{Json, Router};
+use axum::routing::{delete, post};
+use axum::{Extension, Json, Router};
 use clap::Parser;
+use futures_util::stream::SplitSink;
+use futures_util::{SinkExt, StreamExt};
+use protocol::{OutputStream, TRACE_ID_HEADER};
 use serde::Deserialize;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::{Instant, SystemTime};
 use system_utils::path::expand_tilde;
+use system_utils::secret::ConfigKey;
 use tokio::net::TcpListener;
 use tokio::sync::broadcast;
+use tokio::sync::mpsc;
+use tokio::sync::oneshot;
 use tokio::sync::RwLock;
 use tokio::time::{interval, Duration};
 use tokio_util::sync::CancellationToken;
-use tracing::info;
+use tracing::{info, Instrument};
+use uuid::Uuid;
+
+const DEFAULT_OUTPUT_CHUNK_LEN: u64 = 64 * 1024;
+const DEFAULT_HISTORY_PAGE_LIMIT: usize = 50;
+const HISTORY_PAGE_OUTPUT_BYTES: u64 = 64 * 1024;
+const WS_HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+const WS_CLOSE_GRACE: Duration = Duration::from_secs(5);
+const MAX_ACTOR_LEN: usize = 64;
+const MAX_DENY_REASON_LEN: usize = 500;
 
 #[derive(Clone)]
 struct AppState {
     state: Arc<RwLock<crate::state::ConsoleState>>,
-    event_tx: broadcast::Sender<ConsoleEvent>,
+    event_log: Arc<EventLog>,
     uploads: UploadRegistry,
+    terminals: TerminalRegistry,
+    audit_root: Arc<PathBuf>,
+    retention_max_bytes: Option<u64>,
+    started_at: Instant,
+    command_listen_addr: Arc<str>,
+    event_tx: broadcast::Sender<ConsoleEvent>,
+    templates: Arc<TemplateRegistry>,
+    schedules: Arc<ScheduleRegistry>,
+    intake: Arc<CommandIntake>,
+    metrics: Arc<Metrics>,
+    forwards: ForwardManager,
+    limits: ServerLimits,
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let args = Args::parse();
+    if let Some(Command::Ctl(ctl)) = &args.command {
+        return ctl::run(ctl).await;
+    }
+    if args.check_config {
+        std::process::exit(if check::run_check_config(&args) { 0 } else { 1 });
+    }
     init_tracing(args.log_to_stderr)?;
 
     info!(
         listen_addr = %args.listen_addr,
         config = %args.config.display(),
         command_listen_addr = %args.command_listen_addr,
+        serve_status_page = args.serve_status_page,
+        metrics_enabled = !args.metrics_disabled,
+        tls = args.tls_cert.is_some(),
         "console starting"
     );
+    let api_token = resolve_api_token(args.api_token.as_deref(), args.api_token_file.as_deref())?;
     let config = load_console_config(&args.config)
         .with_context(|| format!("failed to load config {}", args.config.display()))?;
-    let state = build_console_state(config)?;
+    let config_key = ConfigKey::resolve(args.config_key_file.as_deref())?;
+    let terminal_max_idle = config
+        .terminal
+        .as_ref()
+        .and_then(|terminal| terminal.max_idle_secs)
+        .filter(|secs| *secs > 0)
+        .map(Duration::from_secs);
+    let terminal_scrollback = config
+        .terminal
+        .as_ref()
+        .and_then(|terminal| terminal.scrollback_bytes)
+        .unwrap_or(terminal::DEFAULT_SCROLLBACK_BYTES);
+    let terminal_capture = config
+        .terminal
+        .as_ref()
+        .and_then(|terminal| terminal.capture_commands)
+        .unwrap_or(false);
+    let probe_settings = runtime::ProbeSettings::from_config(config.health.as_ref());
+    let limits = ServerLimits::from_config(config.server.as_ref());
+    let templates =
+        Arc::new(TemplateRegistry::from_config(&config.templates).context("invalid templates")?);
+    let schedules = Arc::new(
+        ScheduleRegistry::from_config(&config.schedules, &templates)
+            .context("invalid schedules")?,
+    );
+    let state = build_console_state(config, config_key.as_ref())?;
     let local_audit_dir = expand_tilde(&args.local_audit_dir);
+    let mut policy = PolicyConfig::load(&args.broker_config)
+        .with_context(|| format!("failed to load policy {}", args.broker_config.display()))?;
+    if let Some(url) = args.result_webhook.clone() {
+        policy.result_webhook = Some(url);
+    }
     let shutdown = CancellationToken::new();
     let shared_state = Arc::new(RwLock::new(state));
     let (event_tx, _) = broadcast::channel(512);
+    let event_log = EventLog::new();
+    spawn_event_log(
+        Arc::clone(&event_log),
+        event_tx.subscribe(),
+        Arc::clone(&shared_state),
+        shutdown.clone(),
+    );
+    spawn_summary_notifier(
+        event_tx.clone(),
+        Arc::clone(&shared_state),
+        shutdown.clone(),
+    );
+    let listen_addr = args
+        .command_listen_addr
+        .parse()
+        .with_context(|| format!("invalid command_listen_addr {}", args.command_listen_addr))?;
+    let client_access = ClientAccess::for_listener(
+        listen_addr,
+        args.allow_nonlocal_bind,
+        &args.allowed_client_cidrs,
+    )?;
+    let audit_root = Arc::new(local_audit_dir.clone());
+    let retention_max_bytes = policy.retention.max_total_bytes;
+    let metrics = Arc::new(Metrics::default());
+    let intake = spawn_local_exec(
+        listen_addr,
+        client_access,
+        policy,
+        args.broker_config.clone(),
+        local_audit_dir,
+        probe_settings,
+        Arc::clone(&shared_state),
+        event_tx.clone(),
+        Arc::clone(&metrics),
+    )
+    .await
+    .context("failed to start local exec server")?;
+    spawn_policy_reload_on_sighup(Arc::clone(&intake));
+    spawn_scheduler(
+        Arc::clone(&schedules),
+        Arc::clone(&templates),
+        Arc::clone(&intake),
+        Arc::clone(&shared_state),
+        shutdown.clone(),
+    );
     let app_state = AppState {
         state: Arc::clone(&shared_state),
-        event_tx: event_tx.clone(),
+        event_log,
         uploads: UploadRegistry::new(),
+        terminals: TerminalRegistry::new(
+            args.terminal_allow_rw_attach,
+            terminal_max_idle,
+            terminal_scrollback,
+            terminal_capture,
+        ),
+        audit_root,
+        retention_max_bytes,
+        started_at: Instant::now(),
+        command_listen_addr: Arc::from(args.command_listen_addr.as_str()),
+        event_tx: event_tx.clone(),
+        templates,
+        schedules,
+        intake,
+        metrics,
+        forwards: ForwardManager::new(Arc::clone(&shared_state), event_tx.clone()),
+        limits,
     };
 
     if let Some(parent_pid) = resolve_parent_pid() {
         spawn_parent_watchdog(parent_pid, shutdown.clone());
     }
 
-    let app = Router::new()
+    // 审批控制接口只接受很小的 JSON，其余接口共用 max_body_bytes。
+    let control_limit = limits.control_body_limit();
+    let mut router = Router::new();
+    if args.serve_status_page {
+        router = router.route("/", get(status_page));
+    }
+    if !args.metrics_disabled {
+        router = router.route("/metrics", get(get_metrics));
+    }
+    let mut app = router
         .route("/health", get(health))
+        .route("/health/detail", get(health_detail))
+        .route("/summary", get(get_summary))
         .route("/targets", get(list_targets))
         .route("/targets/:name/snapshot", get(get_snapshot))
-        .route("/targets/:name/approve", post(approve_command))
-        .route("/targets/:name/deny", post(deny_command))
-        .route("/targets/:name/cancel", post(cancel_command))
-        .route("/targets/:name/force-cancel", post(force_cancel_command))
+        .route("/targets/:name/results/:id/output", get(get_result_output))
+        .route("/targets/:name/history", get(get_history_page))
+        .route("/targets/:name/history/export", get(export_history))
+        .route("/audit/usage", get(get_audit_usage))
+        .route("/policy/reload", post(reload_policy))
+        .route(
+            "/targets/:name/approve",
+            post(approve_command).layer(control_limit),
+        )
+        .route(
+            "/targets/:name/approve-edited",
+            post(approve_edited_command).layer(control_limit),
+        )
+        .route(
+            "/targets/:name/deny",
+            post(deny_command).layer(control_limit),
+        )
+        .route("/targets/:name/denied", get(list_denied_requests))
+        .route(
+            "/targets/:name/cancel",
+            post(cancel_command).layer(control_limit),
+        )
+        .route(
+            "/targets/:name/force-cancel",
+            post(force_cancel_command).layer(control_limit),
+        )
+        .route("/targets/:name/detached/:id", get(get_detached_job))
+        .route(
+            "/targets/:name/kill-detached",
+            post(kill_detached_job).layer(control_limit),
+        )
+        .route(
+            "/targets/:name/maintenance",
+            post(set_target_maintenance).layer(control_limit),
+        )
+        .route(
+            "/targets/:name/policy/temporary",
+            get(list_temporary_rules)
+                .post(add_temporary_rule)
+                .layer(control_limit),
+        )
+        .route(
+            "/targets/:name/policy/temporary/:id",
+            delete(revoke_temporary_rule),
+        )
+        .route("/templates", get(list_templates))
+        .route("/schedules", get(list_schedules))
+        .route(
+            "/targets/:name/templates/:template/run",
+            post(run_template).layer(control_limit),
+        )
+        .route(
+            "/targets/:name/retry",
+            post(retry_target).layer(control_limit),
+        )
         .route("/targets/:name/dirs", get(list_target_dirs))
+        .route("/targets/:name/forwards", get(list_forwards))
+        .route(
+            "/targets/:name/forwards/:forward/ensure",
+            post(ensure_forward).layer(control_limit),
+        )
+        .route("/targets/:name/forwards/:forward", delete(release_forward))
         .route("/targets/:name/upload", post(start_upload))
         .route("/uploads/:id", get(get_upload_status))
         .route("/targets/:name/terminal", get(terminal_ws_handler))
+        .route("/targets/:name/terminals", get(list_terminal_sessions))
+        .route(
+            "/targets/:name/terminal/:session_id/inject",
+            post(inject_terminal_command).layer(control_limit),
+        )
         .route("/ws", get(ws_handler))
-        .with_state(app_state)
-        .layer(middleware::from_fn(log_http_request));
-    let policy = PolicyConfig::load(&args.broker_config)
-        .with_context(|| format!("failed to load policy {}", args.broker_config.display()))?;
-    let listen_addr = args
-        .command_listen_addr
-        .parse()
-        .with_context(|| format!("invalid command_listen_addr {}", args.command_listen_addr))?;
-    spawn_local_exec(
-        listen_addr,
-        policy,
-        local_audit_dir,
-        Arc::clone(&shared_state),
-        event_tx.clone(),
-    )
-    .await
-    .context("failed to start local exec server")?;
+        .layer(limits.body_limit())
+        .with_state(app_state);
+    if let Some(token) = api_token {
+        app = app.layer(middleware::from_fn_with_state(token, require_api_token));
+    }
+    let app = app.layer(middleware::from_fn(log_http_request));
 
     let listener = TcpListener::bind(&args.listen_addr)
         .await
         .with_context(|| format!("failed to bind {}", args.listen_addr))?;
     info!(addr = %args.listen_addr, "console listening");
-    axum::serve(listener, app)
-        .with_graceful_shutdown(wait_for_shutdown(shutdown.clone()))
-        .await?;
+    match (args.tls_cert.as_deref(), args.tls_key.as_deref()) {
+        (Some(cert), Some(key)) => {
+            serve_tls(
+                listener,
+                app,
+                cert,
+                key,
+                wait_for_shutdown(shutdown.clone()),
+            )
+            .await?
+        }
+        _ => {
+            axum::serve(listener, app)
+                .with_graceful_shutdown(wait_for_shutdown(shutdown.clone()))
+                .await?
+        }
+    }
     info!("console shutting down");
     shutdown.cancel();
     Ok(())
 }
 
+// SIGHUP 与 POST /policy/reload 等效：重新读取 --broker-config，失败时保留旧策略。
+#[cfg(unix)]
+fn spawn_policy_reload_on_sighup(intake: Arc<CommandIntake>) {
+    use tokio::signal::unix::{signal, SignalKind};
+    let mut hangup = match signal(SignalKind::hangup()) {
+        Ok(hangup) => hangup,
+        Err(err) => {
+            tracing::warn!(error = %err, "failed to install SIGHUP handler");
+            return;
+        }
+    };
+    tokio::spawn(async move {
+        while hangup.recv().await.is_some() {
+            info!("SIGHUP received; reloading policy");
+            let _ = log_policy_reload(intake.reload_policy());
+        }
+    });
+}
+
+#[cfg(windows)]
+fn spawn_policy_reload_on_sighup(_intake: Arc<CommandIntake>) {}
+
+fn log_policy_reload(result: Result<Vec<String>, String>) -> Result<Vec<String>, String> {
+    match &result {
+        Ok(warnings) => info!(event = "policy.reloaded", warnings = ?warnings),
+        Err(err) => tracing::warn!(event = "policy.reload_failed", error = %err),
+    }
+    result
+}
+
 fn resolve_parent_pid() -> Option<u32> {
     let value = std::env::var("OCTOVALVE_PARENT_PID").ok()?;
     value.parse::<u32>().ok()
@@ -174,24 +439,129 @@ async fn health() -> &'static str {
     "ok"
 }
 
-async fn log_http_request(req: Request<Body>, next: Next) -> Response {
+async fn status_page() -> Html<&'static str> {
+    Html(include_str!("status_page.html"))
+}
+
+#[derive(serde::Serialize)]
+struct HealthDetail {
+    status: &'static str,
+    uptime_secs: u64,
+    targets: TargetHealth,
+    command_server: CommandServerHealth,
+    event_subscribers: usize,
+}
+
+#[derive(Default, serde::Serialize)]
+struct TargetHealth {
+    total: usize,
+    ready: usize,
+    down: usize,
+}
+
+#[derive(serde::Serialize)]
+struct CommandServerHealth {
+    addr: String,
+    bound: bool,
+}
+
+// 配置了目标但没有任何一个 Ready 时返回 503，便于启动页区分“进程活着”和“可用”。
+async fn health_detail(State(state): State<AppState>) -> (StatusCode, Json<HealthDetail>) {
+    let targets = {
+        let console = state.state.read().await;
+        summarize_targets(&console.list_targets())
+    };
+    let bound = state.intake.command_server_listening();
+    let healthy = bound && (targets.total == 0 || targets.ready > 0);
+    let detail = HealthDetail {
+        status: if healthy { "ok" } else { "degraded" },
+        uptime_secs: state.started_at.elapsed().as_secs(),
+        targets,
+        command_server: CommandServerHealth {
+            addr: state.command_listen_addr.to_string(),
+            bound,
+        },
+        event_subscribers: state.event_log.subscriber_count(),
+    };
+    let status = if healthy {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(detail))
+}
+
+async fn get_metrics(State(state): State<AppState>) -> impl IntoResponse {
+    let now = SystemTime::now();
+    let targets: Vec<TargetGauges> = {
+        let console = state.state.read().await;
+        console
+            .list_targets()
+            .into_iter()
+            .map(|target| TargetGauges {
+                heartbeat_age: console
+                    .last_seen_at(&target.name)
+                    .and_then(|seen| now.duration_since(seen).ok()),
+                up: target.status == TargetStatus::Ready,
+                pending: target.pending_count,
+                consecutive_failures: target.consecutive_failures,
+                name: target.name,
+            })
+            .collect()
+    };
+    let body = state
+        .metrics
+        .render(&targets, state.event_log.subscriber_count());
+    metrics::text_response(body)
+}
+
+fn summarize_targets(targets: &[TargetInfo]) -> TargetHealth {
+    let mut health = TargetHealth::default();
+    for target in targets {
+        health.total += 1;
+        match target.status {
+            TargetStatus::Ready => health.ready += 1,
+            TargetStatus::Down => health.down += 1,
+        }
+    }
+    health
+}
+
+/// 本次 HTTP 请求的追踪 id，由 [`log_http_request`] 放进请求扩展。
+#[derive(Clone)]
+struct TraceId(String);
+
+// 调用方带了合法的 X-Octovalve-Trace-Id 就沿用，否则生成一个；处理过程中的日志都在同一个 span 下，响应回写该头。
+async fn log_http_request(mut req: Request<Body>, next: Next) -> Response {
+    let trace_id = req
+        .headers()
+        .get(TRACE_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(protocol::normalize_trace_id)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+    req.extensions_mut().insert(TraceId(trace_id.clone()));
     let method = req.method().clone();
-    let uri = req.uri().clone();
+    let uri = redacted_uri(req.uri());
     let host = req
         .headers()
         .get("host")
         .and_then(|value| value.to_str().ok())
         .unwrap_or("-")
         .to_string();
-    let response = next.run(req).await;
+    let span = tracing::info_span!("http", trace_id = %trace_id);
+    let mut response = next.run(req).instrument(span).await;
     let status = response.status();
     tracing::info!(
         method = %method,
         uri = %uri,
         host = %host,
         status = %status,
+        trace_id = %trace_id,
         "http request"
     );
+    if let Ok(value) = HeaderValue::from_str(&trace_id) {
+        response.headers_mut().insert(TRACE_ID_HEADER, value);
+    }
     response
 }
 
@@ -200,6 +570,10 @@ async fn list_targets(State(state): State<AppState>) -> Json<Vec<TargetInfo>> {
     Json(state.list_targets())
 }
 
+async fn get_summary(State(state): State<AppState>) -> Json<ConsoleSummary> {
+    Json(state.state.read().await.summary())
+}
+
 async fn get_snapshot(
     Path(name): Path<String>,
     State(state): State<AppState>,
@@ -245,9 +619,83 @@ async fn get_snapshot(
     }
 }
 
+#[derive(serde::Serialize)]
+struct PolicyReloadResponse {
+    message: String,
+    warnings: Vec<String>,
+}
+
+/// 热重载白名单/limits/intent 规则，只影响之后的请求；配置有误时返回 422，旧策略继续生效。
+async fn reload_policy(
+    State(state): State<AppState>,
+) -> Result<Json<PolicyReloadResponse>, (StatusCode, Json<serde_json::Value>)> {
+    match log_policy_reload(state.intake.reload_policy()) {
+        Ok(warnings) => Ok(Json(PolicyReloadResponse {
+            message: "policy reloaded".to_string(),
+            warnings,
+        })),
+        Err(error) => Err((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(json!({ "error": error })),
+        )),
+    }
+}
+
 #[derive(Deserialize)]
 struct CommandPayload {
     id: String,
+    /// 高危请求批准时需回显请求 id。
+    #[serde(default)]
+    confirm: Option<String>,
+    /// 批准/拒绝人，写入结果快照与审计记录；缺省记为 `unknown`。
+    #[serde(default)]
+    actor: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct DenyPayload {
+    id: String,
+    #[serde(default)]
+    actor: Option<String>,
+    /// 拒绝原因，只保留第一行。
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct MaintenancePayload {
+    enabled: bool,
+    #[serde(default)]
+    message: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TemporaryRulePayload {
+    /// 命令名或完整路径，与 whitelist.denied 的匹配方式相同。
+    rule: String,
+    ttl_secs: u64,
+    #[serde(default)]
+    actor: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ActorQuery {
+    #[serde(default)]
+    actor: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct OutputQuery {
+    stream: Option<OutputStream>,
+    offset: Option<u64>,
+    len: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct ExportQuery {
+    from: Option<String>,
+    to: Option<String>,
+    format: Option<ExportFormat>,
 }
 
 #[derive(Deserialize)]
@@ -271,35 +719,134 @@ struct ActionResponse {
     message: String,
 }
 
+#[derive(Deserialize)]
+struct ApproveEditedPayload {
+    id: String,
+    raw_command: String,
+    /// 改写后的命令命中高危规则时需回显请求 id。
+    #[serde(default)]
+    confirm: Option<String>,
+    #[serde(default)]
+    actor: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TemplateRunPayload {
+    #[serde(default)]
+    params: BTreeMap<String, String>,
+}
+
+fn decision_actor(actor: Option<String>) -> String {
+    actor
+        .map(|actor| actor.trim().chars().take(MAX_ACTOR_LEN).collect::<String>())
+        .filter(|actor| !actor.is_empty())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+fn deny_reason(reason: Option<String>) -> Option<String> {
+    reason.and_then(|reason| {
+        let line = reason.lines().next().unwrap_or_default().trim();
+        (!line.is_empty()).then(|| line.chars().take(MAX_DENY_REASON_LEN).collect())
+    })
+}
+
 async fn approve_command(
     Path(name): Path<String>,
     State(state): State<AppState>,
     Json(payload): Json<CommandPayload>,
 ) -> Result<Json<ActionResponse>, StatusCode> {
-    let sender = state.state.read().await.command_sender(&name);
+    let sender = {
+        let guard = state.state.read().await;
+        // 维护模式下排队请求保留，但不允许放行。
+        if guard.maintenance_message(&name).is_some() {
+            return Err(StatusCode::CONFLICT);
+        }
+        guard.command_sender(&name)
+    };
     let Some(sender) = sender else {
         return Err(StatusCode::NOT_FOUND);
     };
+    // 是否需要回显 id 由 service 按自己的待审批队列判断。
+    let confirmed = payload.confirm.as_deref() == Some(payload.id.as_str());
+    let (respond_to, rx) = oneshot::channel();
     sender
-        .send(ControlCommand::Approve(payload.id))
+        .send(ControlCommand::Approve {
+            actor: decision_actor(payload.actor),
+            id: payload.id,
+            confirmed,
+            respond_to: Some(respond_to),
+        })
         .await
         .map_err(|_| StatusCode::SERVICE_UNAVAILABLE)?;
-    Ok(Json(ActionResponse {
-        message: "approve queued".to_string(),
-    }))
+    match rx.await.map_err(|_| StatusCode::SERVICE_UNAVAILABLE)? {
+        Ok(()) => Ok(Json(ActionResponse {
+            message: "approve queued".to_string(),
+        })),
+        Err(EditRejection::ConfirmationRequired) => Err(StatusCode::PRECONDITION_REQUIRED),
+        Err(EditRejection::NotFound) => Err(StatusCode::NOT_FOUND),
+        Err(EditRejection::Invalid(_)) => Err(StatusCode::FORBIDDEN),
+    }
+}
+
+/// 用改写后的命令批准：service 重新做白名单校验，不通过时请求留在队列里并返回原因。
+async fn approve_edited_command(
+    Path(name): Path<String>,
+    State(state): State<AppState>,
+    Json(payload): Json<ApproveEditedPayload>,
+) -> Result<Json<ActionResponse>, (StatusCode, Json<serde_json::Value>)> {
+    let error = |status: StatusCode, error: &str| (status, Json(json!({ "error": error })));
+    let sender = {
+        let guard = state.state.read().await;
+        if guard.maintenance_message(&name).is_some() {
+            return Err(error(StatusCode::CONFLICT, "target in maintenance"));
+        }
+        guard.command_sender(&name)
+    };
+    let Some(sender) = sender else {
+        return Err(error(StatusCode::NOT_FOUND, "unknown target"));
+    };
+    let confirmed = payload.confirm.as_deref() == Some(payload.id.as_str());
+    let (respond_to, rx) = oneshot::channel();
+    let unavailable = || error(StatusCode::SERVICE_UNAVAILABLE, "target service stopped");
+    sender
+        .send(ControlCommand::ApproveEdited {
+            id: payload.id,
+            raw_command: payload.raw_command,
+            actor: decision_actor(payload.actor),
+            confirmed,
+            respond_to,
+        })
+        .await
+        .map_err(|_| unavailable())?;
+    match rx.await.map_err(|_| unavailable())? {
+        Ok(()) => Ok(Json(ActionResponse {
+            message: "approve queued".to_string(),
+        })),
+        Err(EditRejection::NotFound) => Err(error(StatusCode::NOT_FOUND, "unknown request")),
+        Err(EditRejection::ConfirmationRequired) => Err(error(
+            StatusCode::PRECONDITION_REQUIRED,
+            "edited command requires confirmation",
+        )),
+        Err(EditRejection::Invalid(reason)) => Err(error(StatusCode::FORBIDDEN, &reason)),
+    }
 }
 
+/// `reason` 可选，返回给 agent 的错误为 `denied by operator: <reason>`。
 async fn deny_command(
     Path(name): Path<String>,
     State(state): State<AppState>,
-    Json(payload): Json<CommandPayload>,
+    Json(payload): Json<DenyPayload>,
 ) -> Result<Json<ActionResponse>, StatusCode> {
     let sender = state.state.read().await.command_sender(&name);
     let Some(sender) = sender else {
         return Err(StatusCode::NOT_FOUND);
     };
     sender
-        .send(ControlCommand::Deny(payload.id))
+        .send(ControlCommand::Deny {
+            actor: decision_actor(payload.actor),
+            id: payload.id,
+            reason: deny_reason(payload.reason),
+        })
         .await
         .map_err(|_| StatusCode::SERVICE_UNAVAILABLE)?;
     Ok(Json(ActionResponse {
@@ -307,6 +854,23 @@ async fn deny_command(
     }))
 }
 
+async fn list_denied_requests(
+    Path(name): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Json<Vec<ResultSnapshot>>, StatusCode> {
+    let sender = state.state.read().await.command_sender(&name);
+    let Some(sender) = sender else {
+        return Err(StatusCode::NOT_FOUND);
+    };
+    let (respond_to, rx) = oneshot::channel();
+    sender
+        .send(ControlCommand::QueryDenied { respond_to })
+        .await
+        .map_err(|_| StatusCode::SERVICE_UNAVAILABLE)?;
+    let denied = rx.await.map_err(|_| StatusCode::SERVICE_UNAVAILABLE)?;
+    Ok(Json(denied))
+}
+
 async fn cancel_command(
     Path(name): Path<String>,
     State(state): State<AppState>,
@@ -343,6 +907,356 @@ async fn force_cancel_command(
     }))
 }
 
+async fn get_detached_job(
+    Path((name, id)): Path<(String, String)>,
+    State(state): State<AppState>,
+) -> Result<Json<DetachedSnapshot>, StatusCode> {
+    let sender = state.state.read().await.command_sender(&name);
+    let Some(sender) = sender else {
+        return Err(StatusCode::NOT_FOUND);
+    };
+    let (respond_to, rx) = oneshot::channel();
+    sender
+        .send(ControlCommand::QueryDetached { id, respond_to })
+        .await
+        .map_err(|_| StatusCode::SERVICE_UNAVAILABLE)?;
+    let job = rx.await.map_err(|_| StatusCode::SERVICE_UNAVAILABLE)?;
+    job.map(Json).ok_or(StatusCode::NOT_FOUND)
+}
+
+async fn kill_detached_job(
+    Path(name): Path<String>,
+    State(state): State<AppState>,
+    Json(payload): Json<CommandPayload>,
+) -> Result<Json<ActionResponse>, StatusCode> {
+    let sender = state.state.read().await.command_sender(&name);
+    let Some(sender) = sender else {
+        return Err(StatusCode::NOT_FOUND);
+    };
+    sender
+        .send(ControlCommand::KillDetached(payload.id))
+        .await
+        .map_err(|_| StatusCode::SERVICE_UNAVAILABLE)?;
+    Ok(Json(ActionResponse {
+        message: "kill queued".to_string(),
+    }))
+}
+
+async fn list_temporary_rules(
+    Path(name): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Json<Vec<TemporaryRule>>, StatusCode> {
+    let sender = state.state.read().await.command_sender(&name);
+    let Some(sender) = sender else {
+        return Err(StatusCode::NOT_FOUND);
+    };
+    let (respond_to, rx) = oneshot::channel();
+    sender
+        .send(ControlCommand::ListTemporaryRules { respond_to })
+        .await
+        .map_err(|_| StatusCode::SERVICE_UNAVAILABLE)?;
+    let rules = rx.await.map_err(|_| StatusCode::SERVICE_UNAVAILABLE)?;
+    Ok(Json(rules))
+}
+
+/// 临时放开一条 denied 规则，到期自动失效；hard_deny.immutable 中的命令返回 400。
+async fn add_temporary_rule(
+    Path(name): Path<String>,
+    State(state): State<AppState>,
+    Json(payload): Json<TemporaryRulePayload>,
+) -> Result<(StatusCode, Json<TemporaryRule>), (StatusCode, Json<serde_json::Value>)> {
+    let error = |status: StatusCode, error: &str| (status, Json(json!({ "error": error })));
+    let sender = state.state.read().await.command_sender(&name);
+    let Some(sender) = sender else {
+        return Err(error(StatusCode::NOT_FOUND, "unknown target"));
+    };
+    let (respond_to, rx) = oneshot::channel();
+    let unavailable = || error(StatusCode::SERVICE_UNAVAILABLE, "target service stopped");
+    sender
+        .send(ControlCommand::AddTemporaryRule {
+            rule: payload.rule,
+            ttl_secs: payload.ttl_secs,
+            actor: decision_actor(payload.actor),
+            respond_to,
+        })
+        .await
+        .map_err(|_| unavailable())?;
+    match rx.await.map_err(|_| unavailable())? {
+        Ok(rule) => Ok((StatusCode::CREATED, Json(rule))),
+        Err(reason) => Err(error(StatusCode::BAD_REQUEST, &reason)),
+    }
+}
+
+async fn revoke_temporary_rule(
+    Path((name, id)): Path<(String, String)>,
+    Query(query): Query<ActorQuery>,
+    State(state): State<AppState>,
+) -> Result<Json<ActionResponse>, StatusCode> {
+    let sender = state.state.read().await.command_sender(&name);
+    let Some(sender) = sender else {
+        return Err(StatusCode::NOT_FOUND);
+    };
+    let (respond_to, rx) = oneshot::channel();
+    sender
+        .send(ControlCommand::RevokeTemporaryRule {
+            id,
+            actor: decision_actor(query.actor),
+            respond_to,
+        })
+        .await
+        .map_err(|_| StatusCode::SERVICE_UNAVAILABLE)?;
+    if !rx.await.map_err(|_| StatusCode::SERVICE_UNAVAILABLE)? {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    Ok(Json(ActionResponse {
+        message: "temporary rule revoked".to_string(),
+    }))
+}
+
+async fn set_target_maintenance(
+    Path(name): Path<String>,
+    State(state): State<AppState>,
+    Json(payload): Json<MaintenancePayload>,
+) -> Result<Json<TargetInfo>, StatusCode> {
+    let message = payload.enabled.then(|| payload.message.unwrap_or_default());
+    if !state.state.write().await.set_maintenance(&name, message) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    info!(target = %name, enabled = payload.enabled, "target maintenance updated");
+    runtime::emit_target_update(&name, &state.state, &state.event_tx).await;
+    let info = state.state.read().await.target_info(&name);
+    info.map(Json).ok_or(StatusCode::NOT_FOUND)
+}
+
+async fn list_templates(State(state): State<AppState>) -> Json<Vec<TemplateInfo>> {
+    Json(state.templates.list())
+}
+
+async fn list_schedules(State(state): State<AppState>) -> Json<Vec<ScheduleInfo>> {
+    Json(state.schedules.list())
+}
+
+/// 按模板渲染命令并送入与命令连接相同的审批队列；返回 202 后通过快照 / 事件流跟踪结果。
+async fn run_template(
+    Path((name, template)): Path<(String, String)>,
+    State(state): State<AppState>,
+    trace_id: Option<Extension<TraceId>>,
+    Json(payload): Json<TemplateRunPayload>,
+) -> Result<(StatusCode, Json<serde_json::Value>), (StatusCode, Json<serde_json::Value>)> {
+    let not_found = |error: &str| (StatusCode::NOT_FOUND, Json(json!({ "error": error })));
+    if state.state.read().await.target_spec(&name).is_none() {
+        return Err(not_found("unknown target"));
+    }
+    let mut request = match state.templates.render(&template, &name, &payload.params) {
+        Ok(request) => request,
+        Err(TemplateError::NotFound) => return Err(not_found("unknown template")),
+        Err(TemplateError::Invalid(details)) => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(json!({ "error": "invalid template params", "details": details })),
+            ));
+        }
+    };
+    request.trace_id = trace_id.map(|Extension(TraceId(trace_id))| trace_id);
+    let id = request.id.clone();
+    let raw_command = request.raw_command.clone();
+    match state.intake.submit(request, "console-api").await {
+        Ok(response_rx) => {
+            info!(target = %name, template = %template, id = %id, "template command queued");
+            tokio::spawn(async move {
+                let _ = response_rx.await;
+            });
+            Ok((
+                StatusCode::ACCEPTED,
+                Json(json!({ "id": id, "raw_command": raw_command, "status": "queued" })),
+            ))
+        }
+        Err(response) => Err((
+            StatusCode::FORBIDDEN,
+            Json(serde_json::to_value(response).unwrap_or_default()),
+        )),
+    }
+}
+
+/// 立即重新探测目标并清零熔断计数；没有 SSH 配置（无探测 worker）的目标返回 409。
+async fn retry_target(
+    Path(name): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Json<TargetInfo>, StatusCode> {
+    let notifier = {
+        let mut guard = state.state.write().await;
+        if guard.target_spec(&name).is_none() {
+            return Err(StatusCode::NOT_FOUND);
+        }
+        let notifier = guard.retry_notifier(&name).ok_or(StatusCode::CONFLICT)?;
+        guard.set_probe_state(&name, 0, None);
+        notifier
+    };
+    notifier.notify_one();
+    info!(target = %name, "target probe retry requested");
+    runtime::emit_target_update(&name, &state.state, &state.event_tx).await;
+    let info = state.state.read().await.target_info(&name);
+    info.map(Json).ok_or(StatusCode::NOT_FOUND)
+}
+
+async fn get_result_output(
+    Path((name, id)): Path<(String, String)>,
+    Query(query): Query<OutputQuery>,
+    State(state): State<AppState>,
+) -> Result<Json<OutputChunk>, StatusCode> {
+    if state.state.read().await.target_spec(&name).is_none() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let chunk = fetch_output(
+        &state.audit_root,
+        &name,
+        &id,
+        query.stream.unwrap_or(OutputStream::Stdout),
+        query.offset.unwrap_or(0),
+        query.len.unwrap_or(DEFAULT_OUTPUT_CHUNK_LEN),
+    )
+    .await
+    .map_err(|err| match err.kind() {
+        std::io::ErrorKind::NotFound => StatusCode::NOT_FOUND,
+        std::io::ErrorKind::InvalidInput => StatusCode::BAD_REQUEST,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    })?;
+    Ok(Json(chunk))
+}
+
+#[derive(Deserialize)]
+struct HistoryQuery {
+    before_ms: Option<u64>,
+    before_id: Option<String>,
+    limit: Option<usize>,
+}
+
+async fn get_history_page(
+    Path(name): Path<String>,
+    Query(query): Query<HistoryQuery>,
+    State(state): State<AppState>,
+) -> Result<Json<HistoryPage>, StatusCode> {
+    if state.state.read().await.target_spec(&name).is_none() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let page = history_page(
+        &state.intake,
+        &state.audit_root,
+        &name,
+        query.before_ms,
+        query.before_id,
+        query.limit.unwrap_or(DEFAULT_HISTORY_PAGE_LIMIT),
+        HISTORY_PAGE_OUTPUT_BYTES,
+    )
+    .await;
+    Ok(Json(page))
+}
+
+async fn get_audit_usage(State(state): State<AppState>) -> Result<Json<AuditUsage>, StatusCode> {
+    let targets: Vec<String> = state
+        .state
+        .read()
+        .await
+        .target_specs()
+        .into_iter()
+        .map(|target| target.name)
+        .collect();
+    let audit_root = Arc::clone(&state.audit_root);
+    let max_total_bytes = state.retention_max_bytes;
+    let usage =
+        tokio::task::spawn_blocking(move || audit_usage(&audit_root, &targets, max_total_bytes))
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(Json(usage))
+}
+
+async fn export_history(
+    Path(name): Path<String>,
+    Query(query): Query<ExportQuery>,
+    State(state): State<AppState>,
+) -> Result<Response, StatusCode> {
+    if state.state.read().await.target_spec(&name).is_none() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let range = ExportRange {
+        from_ms: parse_rfc3339_ms(query.from.as_deref())?,
+        to_ms: parse_rfc3339_ms(query.to.as_deref())?,
+    };
+    let format = query.format.unwrap_or(ExportFormat::Csv);
+    let extension = match format {
+        ExportFormat::Csv => "csv",
+        ExportFormat::Jsonl => "jsonl",
+    };
+    let stream = export_target_history(&state.audit_root, &name, range, format);
+    Response::builder()
+        .header(CONTENT_TYPE, format.content_type())
+        .header(
+            CONTENT_DISPOSITION,
+            format!("attachment; filename=\"octovalve-history.{extension}\""),
+        )
+        .body(Body::from_stream(stream))
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+fn parse_rfc3339_ms(value: Option<&str>) -> Result<Option<u64>, StatusCode> {
+    let Some(value) = value.map(str::trim).filter(|value| !value.is_empty()) else {
+        return Ok(None);
+    };
+    // 带偏移（+08:00）的时间交给 chrono；不带时区的写法仍按 UTC 解析。
+    if let Ok(time) = chrono::DateTime::parse_from_rfc3339(value) {
+        return Ok(Some(u64::try_from(time.timestamp_millis()).unwrap_or(0)));
+    }
+    let time = humantime::parse_rfc3339_weak(value).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let ms = time
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0);
+    Ok(Some(ms))
+}
+
+async fn list_forwards(
+    Path(name): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Json<Vec<ForwardInfo>>, StatusCode> {
+    let target = state.state.read().await.target_spec(&name);
+    let Some(target) = target else {
+        return Err(StatusCode::NOT_FOUND);
+    };
+    Ok(Json(state.forwards.list(&target).await))
+}
+
+/// 按需建立 `extra_forwards` 中的转发；ssh 失败返回 502 与失败阶段、说明。
+async fn ensure_forward(
+    Path((name, forward)): Path<(String, String)>,
+    State(state): State<AppState>,
+) -> Result<Json<ForwardInfo>, (StatusCode, Json<serde_json::Value>)> {
+    let error = |status: StatusCode, error: &str| (status, Json(json!({ "error": error })));
+    let target = state.state.read().await.target_spec(&name);
+    let Some(target) = target else {
+        return Err(error(StatusCode::NOT_FOUND, "unknown target"));
+    };
+    match state.forwards.ensure(&target, &forward).await {
+        Ok(info) => Ok(Json(info)),
+        Err(ForwardError::NotFound) => Err(error(StatusCode::NOT_FOUND, "unknown forward")),
+        Err(ForwardError::Failed { phase, detail }) => Err((
+            StatusCode::BAD_GATEWAY,
+            Json(json!({ "error": detail, "phase": phase })),
+        )),
+    }
+}
+
+async fn release_forward(
+    Path((name, forward)): Path<(String, String)>,
+    State(state): State<AppState>,
+) -> Result<Json<ActionResponse>, StatusCode> {
+    if !state.forwards.release(&name, &forward).await {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    Ok(Json(ActionResponse {
+        message: "forward released".to_string(),
+    }))
+}
+
 async fn list_target_dirs(
     Path(name): Path<String>,
     Query(query): Query<DirQuery>,
@@ -402,59 +1316,206 @@ async fn get_upload_status(
     Ok(Json(status))
 }
 
-async fn ws_handler(ws: WebSocketUpgrade, State(state): State<AppState>) -> impl IntoResponse {
-    ws.on_upgrade(|socket| handle_ws(socket, state))
+#[derive(Deserialize)]
+struct WsQuery {
+    since: Option<u64>,
+    epoch: Option<String>,
+}
+
+async fn ws_handler(
+    ws: WebSocketUpgrade,
+    Query(query): Query<WsQuery>,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    let max_message = state.limits.max_ws_message_bytes;
+    ws.max_message_size(max_message)
+        .max_frame_size(max_message)
+        .on_upgrade(|socket| handle_ws(socket, state, query))
+}
+
+enum WsExit {
+    Closed,
+    SlowClient,
 }
 
-async fn handle_ws(mut socket: WebSocket, state: AppState) {
-    let snapshot = {
-        let state = state.state.read().await;
-        state.list_targets()
+// 连接建立后先发 hello（epoch + 当前 seq），再按 since 补发缺失事件，无法补发时下发 targets_snapshot 全量重同步；
+// 连接中收到 resume 消息时同样处理。
+// 出站消息经每连接的有界队列交给写任务：队列写满或订阅端积压超过 broadcast 容量时直接断开，
+// 客户端带 since 重连即可，慢客户端不会拖住 service loop。
+async fn handle_ws(socket: WebSocket, state: AppState, query: WsQuery) {
+    let resume = match (query.epoch.as_deref(), query.since) {
+        (Some(epoch), Some(since)) => Some((epoch, since)),
+        _ => None,
     };
-    if send_ws_event(
-        &mut socket,
-        ConsoleEvent::TargetsSnapshot { targets: snapshot },
-    )
-    .await
-    .is_err()
+    let (sink, mut stream) = socket.split();
+    let (out_tx, out_rx) = mpsc::channel(state.limits.ws_send_queue);
+    let mut writer = tokio::spawn(write_ws_messages(out_rx, sink));
+    let (mut rx, replay, mut last_sent) = state.event_log.subscribe(resume);
+    let hello = json!({
+        "type": "hello",
+        "epoch": state.event_log.epoch(),
+        "seq": last_sent,
+    });
+    if send_ws_text(&out_tx, hello.to_string()).await.is_err() {
+        return;
+    }
+    if send_replay(&out_tx, &state, replay, last_sent)
+        .await
+        .is_err()
     {
         return;
     }
 
-    let mut rx = state.event_tx.subscribe();
-    loop {
+    let mut heartbeat = interval(WS_HEARTBEAT_INTERVAL);
+    heartbeat.tick().await;
+    let exit = loop {
         tokio::select! {
-            event = rx.recv() => {
-                match event {
-                    Ok(event) => {
-                        if send_ws_event(&mut socket, event).await.is_err() {
-                            break;
+            frame = rx.recv() => {
+                match frame {
+                    Ok(frame) => {
+                        last_sent = frame.seq;
+                        if let Err(exit) = queue_ws_text(&out_tx, frame.payload.to_string()) {
+                            break exit;
                         }
                     }
-                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
-                    Err(_) => break,
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        tracing::warn!(skipped, "websocket subscriber lagged; closing connection");
+                        let _ = out_tx.try_send(Message::Close(None));
+                        break WsExit::Closed;
+                    }
+                    Err(_) => break WsExit::Closed,
+                }
+            }
+            _ = heartbeat.tick() => {
+                let payload = json!({ "type": "heartbeat", "seq": last_sent });
+                if let Err(exit) = queue_ws_text(&out_tx, payload.to_string()) {
+                    break exit;
                 }
             }
-            msg = socket.recv() => {
+            msg = stream.next() => {
                 match msg {
-                    Some(Ok(Message::Close(_))) | None => break,
+                    Some(Ok(Message::Close(_))) | None => break WsExit::Closed,
+                    Some(Ok(Message::Text(text))) => {
+                        let Some(resume) = parse_ws_resume(&text) else {
+                            continue;
+                        };
+                        let replay = state.event_log.replay_between(
+                            resume.epoch.as_deref(),
+                            resume.from_seq,
+                            last_sent,
+                        );
+                        if send_replay(&out_tx, &state, replay, last_sent).await.is_err() {
+                            break WsExit::Closed;
+                        }
+                    }
                     Some(Ok(_)) => {}
-                    Some(Err(_)) => break,
+                    Some(Err(err)) if is_message_too_big(&err) => {
+                        tracing::warn!(
+                            limit = state.limits.max_ws_message_bytes,
+                            "websocket message too big; closing connection"
+                        );
+                        let _ = out_tx.try_send(message_too_big_close());
+                        break WsExit::Closed;
+                    }
+                    Some(Err(_)) => break WsExit::Closed,
                 }
             }
         }
+    };
+    drop(out_tx);
+    match exit {
+        WsExit::SlowClient => {
+            tracing::warn!(
+                queue = state.limits.ws_send_queue,
+                "websocket client too slow; closing connection"
+            );
+            writer.abort();
+        }
+        // 给写任务一点时间把已排队的消息和 close 帧发完。
+        WsExit::Closed => {
+            if tokio::time::timeout(WS_CLOSE_GRACE, &mut writer)
+                .await
+                .is_err()
+            {
+                writer.abort();
+            }
+        }
     }
 }
 
-async fn send_ws_event(socket: &mut WebSocket, event: ConsoleEvent) -> Result<(), axum::Error> {
-    let payload = match serde_json::to_string(&event) {
-        Ok(payload) => payload,
-        Err(err) => {
-            tracing::warn!(error = %err, "failed to serialize websocket event");
-            return Ok(());
+async fn write_ws_messages(
+    mut out_rx: mpsc::Receiver<Message>,
+    mut sink: SplitSink<WebSocket, Message>,
+) {
+    while let Some(message) = out_rx.recv().await {
+        let closing = matches!(message, Message::Close(_));
+        if sink.send(message).await.is_err() || closing {
+            break;
         }
-    };
-    socket.send(Message::Text(payload)).await
+    }
+}
+
+#[derive(Deserialize)]
+struct WsResume {
+    from_seq: u64,
+    epoch: Option<String>,
+}
+
+// 连接后客户端可发 {"type":"resume","from_seq":N}，与 ?since= 等价；其他消息忽略。
+fn parse_ws_resume(text: &str) -> Option<WsResume> {
+    let value: serde_json::Value = serde_json::from_str(text).ok()?;
+    if value.get("type").and_then(serde_json::Value::as_str) != Some("resume") {
+        return None;
+    }
+    serde_json::from_value(value).ok()
+}
+
+async fn send_replay(
+    out_tx: &mpsc::Sender<Message>,
+    state: &AppState,
+    replay: Replay,
+    seq: u64,
+) -> Result<(), WsClosed> {
+    match replay {
+        Replay::Frames(frames) => {
+            for frame in frames {
+                send_ws_text(out_tx, frame.payload.to_string()).await?;
+            }
+        }
+        Replay::Resync => {
+            let targets = {
+                let state = state.state.read().await;
+                state.list_targets()
+            };
+            let event = ConsoleEvent::TargetsSnapshot { targets };
+            match sequenced_payload(&event, seq) {
+                Ok(payload) => send_ws_text(out_tx, payload).await?,
+                Err(err) => {
+                    tracing::warn!(error = %err, "failed to serialize websocket event");
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+struct WsClosed;
+
+// hello 与补发可以等待写任务腾出队列；实时事件和心跳走 queue_ws_text，不等待。
+async fn send_ws_text(out_tx: &mpsc::Sender<Message>, payload: String) -> Result<(), WsClosed> {
+    out_tx
+        .send(Message::Text(payload))
+        .await
+        .map_err(|_| WsClosed)
+}
+
+fn queue_ws_text(out_tx: &mpsc::Sender<Message>, payload: String) -> Result<(), WsExit> {
+    out_tx
+        .try_send(Message::Text(payload))
+        .map_err(|err| match err {
+            mpsc::error::TrySendError::Full(_) => WsExit::SlowClient,
+            mpsc::error::TrySendError::Closed(_) => WsExit::Closed,
+        })
 }
 
 fn init_tracing(log_to_stderr: bool) -> anyhow::Result<()> {
@@ -0,0 +1,317 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use axum::http::header::CONTENT_TYPE;
+use axum::response::IntoResponse;
+use protocol::{CommandResponse, CommandStatus};
+
+const TEXT_FORMAT: &str = "text/plain; version=0.0.4; charset=utf-8";
+// 命令耗时分桶（秒），覆盖秒级交互命令到半小时的长任务。
+const DURATION_BUCKETS: [f64; 10] = [0.1, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 300.0, 1800.0];
+// 与 executor 超时返回的错误文本一致。
+const TIMEOUT_ERROR: &str = "command timed out";
+
+/// 请求生命周期中计数的节点；标签只有 target 与 status，不带请求 id。
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum RequestOutcome {
+    Received,
+    Approved,
+    Denied,
+    Completed,
+    Error,
+    Cancelled,
+    TimedOut,
+    Detached,
+}
+
+impl RequestOutcome {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Received => "received",
+            Self::Approved => "approved",
+            Self::Denied => "denied",
+            Self::Completed => "completed",
+            Self::Error => "error",
+            Self::Cancelled => "cancelled",
+            Self::TimedOut => "timed_out",
+            Self::Detached => "detached",
+        }
+    }
+
+    /// 执行结束后的最终状态；超时在协议里是带固定错误文本的 Error。
+    pub(crate) fn finished(response: &CommandResponse) -> Self {
+        match response.status {
+            CommandStatus::Completed => Self::Completed,
+            CommandStatus::Cancelled => Self::Cancelled,
+            CommandStatus::Denied => Self::Denied,
+            CommandStatus::Approved => Self::Approved,
+            CommandStatus::Detached => Self::Detached,
+            CommandStatus::Error if response.error.as_deref() == Some(TIMEOUT_ERROR) => {
+                Self::TimedOut
+            }
+            CommandStatus::Error => Self::Error,
+        }
+    }
+}
+
+/// 抓取时从 ConsoleState 读出的目标级 gauge。
+pub(crate) struct TargetGauges {
+    pub(crate) name: String,
+    pub(crate) up: bool,
+    pub(crate) pending: usize,
+    pub(crate) consecutive_failures: u32,
+    /// 距离最近一次收到该目标服务快照 / 事件的时间。
+    pub(crate) heartbeat_age: Option<Duration>,
+}
+
+#[derive(Default)]
+struct Histogram {
+    buckets: [u64; DURATION_BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+#[derive(Default)]
+struct Series {
+    requests: BTreeMap<(String, RequestOutcome), u64>,
+    durations: BTreeMap<String, Histogram>,
+}
+
+/// Prometheus 文本格式的计数器，由 intake 与 service loop 更新，`/metrics` 渲染。
+#[derive(Default)]
+pub(crate) struct Metrics {
+    series: Mutex<Series>,
+}
+
+impl Metrics {
+    pub(crate) fn record(&self, target: &str, outcome: RequestOutcome) {
+        let Ok(mut series) = self.series.lock() else {
+            return;
+        };
+        *series
+            .requests
+            .entry((target.to_string(), outcome))
+            .or_default() += 1;
+    }
+
+    pub(crate) fn record_finished(
+        &self,
+        target: &str,
+        response: &CommandResponse,
+        duration: Duration,
+    ) {
+        self.record(target, RequestOutcome::finished(response));
+        let Ok(mut series) = self.series.lock() else {
+            return;
+        };
+        let histogram = series.durations.entry(target.to_string()).or_default();
+        let secs = duration.as_secs_f64();
+        for (bucket, le) in histogram.buckets.iter_mut().zip(DURATION_BUCKETS) {
+            if secs <= le {
+                *bucket += 1;
+            }
+        }
+        histogram.count += 1;
+        histogram.sum += secs;
+    }
+
+    pub(crate) fn render(&self, targets: &[TargetGauges], ws_clients: usize) -> String {
+        let mut out = String::new();
+        header(
+            &mut out,
+            "octovalve_requests_total",
+            "counter",
+            "Command requests by target and lifecycle status.",
+        );
+        if let Ok(series) = self.series.lock() {
+            for ((target, outcome), value) in &series.requests {
+                let _ = writeln!(
+                    out,
+                    "octovalve_requests_total{{target=\"{}\",status=\"{}\"}} {value}",
+                    escape(target),
+                    outcome.as_str()
+                );
+            }
+            header(
+                &mut out,
+                "octovalve_command_duration_seconds",
+                "histogram",
+                "Execution time of approved commands.",
+            );
+            for (target, histogram) in &series.durations {
+                let target = escape(target);
+                for (le, value) in DURATION_BUCKETS.iter().zip(histogram.buckets) {
+                    let _ = writeln!(
+                        out,
+                        "octovalve_command_duration_seconds_bucket{{target=\"{target}\",le=\"{le}\"}} {value}"
+                    );
+                }
+                let _ = writeln!(
+                    out,
+                    "octovalve_command_duration_seconds_bucket{{target=\"{target}\",le=\"+Inf\"}} {}",
+                    histogram.count
+                );
+                let _ = writeln!(
+                    out,
+                    "octovalve_command_duration_seconds_sum{{target=\"{target}\"}} {}",
+                    histogram.sum
+                );
+                let _ = writeln!(
+                    out,
+                    "octovalve_command_duration_seconds_count{{target=\"{target}\"}} {}",
+                    histogram.count
+                );
+            }
+        }
+        gauge(
+            &mut out,
+            "octovalve_pending_requests",
+            "Requests waiting for approval.",
+            targets,
+            |target| Some(target.pending as f64),
+        );
+        gauge(
+            &mut out,
+            "octovalve_target_up",
+            "Whether the last connectivity probe succeeded.",
+            targets,
+            |target| Some(if target.up { 1.0 } else { 0.0 }),
+        );
+        gauge(
+            &mut out,
+            "octovalve_target_consecutive_failures",
+            "Consecutive failed connectivity probes.",
+            targets,
+            |target| Some(f64::from(target.consecutive_failures)),
+        );
+        gauge(
+            &mut out,
+            "octovalve_target_heartbeat_age_seconds",
+            "Seconds since the target service last reported.",
+            targets,
+            |target| target.heartbeat_age.map(|age| age.as_secs_f64()),
+        );
+        header(
+            &mut out,
+            "octovalve_ws_clients",
+            "gauge",
+            "Connected /ws event stream clients.",
+        );
+        let _ = writeln!(out, "octovalve_ws_clients {ws_clients}");
+        out
+    }
+}
+
+pub(crate) fn text_response(body: String) -> impl IntoResponse {
+    ([(CONTENT_TYPE, TEXT_FORMAT)], body)
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+fn gauge(
+    out: &mut String,
+    name: &str,
+    help: &str,
+    targets: &[TargetGauges],
+    value: impl Fn(&TargetGauges) -> Option<f64>,
+) {
+    header(out, name, "gauge", help);
+    for target in targets {
+        if let Some(value) = value(target) {
+            let _ = writeln!(out, "{name}{{target=\"{}\"}} {value}", escape(&target.name));
+        }
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_finished_responses() {
+        let id = "1".to_string();
+        assert_eq!(
+            RequestOutcome::finished(&CommandResponse::error(id.clone(), TIMEOUT_ERROR)),
+            RequestOutcome::TimedOut
+        );
+        assert_eq!(
+            RequestOutcome::finished(&CommandResponse::error(id.clone(), "ssh failed")),
+            RequestOutcome::Error
+        );
+        assert_eq!(
+            RequestOutcome::finished(&CommandResponse::denied(id, "no")),
+            RequestOutcome::Denied
+        );
+    }
+
+    #[tokio::test]
+    async fn scrape_exposes_expected_series() {
+        use std::sync::Arc;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let metrics = Arc::new(Metrics::default());
+        metrics.record("dev", RequestOutcome::Received);
+        metrics.record("dev", RequestOutcome::Received);
+        metrics.record("dev", RequestOutcome::Approved);
+        metrics.record("dev", RequestOutcome::Denied);
+        metrics.record_finished(
+            "dev",
+            &CommandResponse::error("req-1", TIMEOUT_ERROR),
+            Duration::from_millis(700),
+        );
+        let app = axum::Router::new().route(
+            "/metrics",
+            axum::routing::get(move || async move {
+                let targets = [TargetGauges {
+                    name: "dev".to_string(),
+                    up: true,
+                    pending: 1,
+                    consecutive_failures: 0,
+                    heartbeat_age: Some(Duration::from_secs(3)),
+                }];
+                text_response(metrics.render(&targets, 2))
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+        assert!(response.contains(TEXT_FORMAT));
+        for series in [
+            "octovalve_requests_total{target=\"dev\",status=\"received\"} 2",
+            "octovalve_requests_total{target=\"dev\",status=\"approved\"} 1",
+            "octovalve_requests_total{target=\"dev\",status=\"denied\"} 1",
+            "octovalve_requests_total{target=\"dev\",status=\"timed_out\"} 1",
+            "octovalve_command_duration_seconds_bucket{target=\"dev\",le=\"0.5\"} 0",
+            "octovalve_command_duration_seconds_bucket{target=\"dev\",le=\"1\"} 1",
+            "octovalve_command_duration_seconds_count{target=\"dev\"} 1",
+            "octovalve_pending_requests{target=\"dev\"} 1",
+            "octovalve_target_heartbeat_age_seconds{target=\"dev\"} 3",
+            "octovalve_ws_clients 2",
+        ] {
+            assert!(response.contains(series), "missing {series}\n{response}");
+        }
+        assert!(!response.contains("req-1"));
+    }
+}
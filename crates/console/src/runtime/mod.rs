@@ -1,3 +1,5 @@
 mod status;
+mod worker;
 
 pub(crate) use status::emit_target_update;
+pub(crate) use worker::{spawn_probe_worker, update_if_changed, ProbeSettings};
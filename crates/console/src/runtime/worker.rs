@@ -0,0 +1,161 @@
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use protocol::config::HealthSettings;
+use tokio::sync::{broadcast, Notify, RwLock};
+use uuid::Uuid;
+
+use crate::events::ConsoleEvent;
+use crate::state::{ConsoleState, TargetStatus};
+
+const BACKOFF_BASE: Duration = Duration::from_secs(5);
+const BACKOFF_CAP: Duration = Duration::from_secs(300);
+const DEFAULT_FAILURE_THRESHOLD: u32 = 5;
+const DEFAULT_COOL_DOWN_SECS: u64 = 600;
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct ProbeSettings {
+    failure_threshold: u32,
+    cool_down: Duration,
+}
+
+impl ProbeSettings {
+    pub(crate) fn from_config(health: Option<&HealthSettings>) -> Self {
+        let failure_threshold = health
+            .and_then(|health| health.failure_threshold)
+            .filter(|value| *value > 0)
+            .unwrap_or(DEFAULT_FAILURE_THRESHOLD);
+        let cool_down_secs = health
+            .and_then(|health| health.cool_down_secs)
+            .filter(|value| *value > 0)
+            .unwrap_or(DEFAULT_COOL_DOWN_SECS);
+        Self {
+            failure_threshold,
+            cool_down: Duration::from_secs(cool_down_secs),
+        }
+    }
+
+    // 未熔断时指数退避（封顶 BACKOFF_CAP），熔断后固定按 cool_down 探测；抖动在调用处叠加。
+    fn delay_after(&self, failures: u32) -> Duration {
+        if failures >= self.failure_threshold {
+            return self.cool_down;
+        }
+        let exponent = failures.saturating_sub(1).min(16);
+        BACKOFF_BASE.saturating_mul(1 << exponent).min(BACKOFF_CAP)
+    }
+}
+
+fn with_jitter(delay: Duration) -> Duration {
+    let fraction = (Uuid::new_v4().as_u128() % 1000) as f64 / 1000.0;
+    delay.mul_f64(0.8 + 0.4 * fraction)
+}
+
+/// 目标连通性探测循环：失败后退避重试，成功后等待手动 retry 再探测。
+/// 返回的 Notify 供 `POST /targets/:name/retry` 立即唤醒并重置熔断。
+pub(crate) fn spawn_probe_worker<F, Fut>(
+    name: String,
+    settings: ProbeSettings,
+    probe: F,
+    state: Arc<RwLock<ConsoleState>>,
+    event_tx: broadcast::Sender<ConsoleEvent>,
+) -> Arc<Notify>
+where
+    F: Fn() -> Fut + Send + 'static,
+    Fut: Future<Output = Result<(), String>> + Send,
+{
+    let retry = Arc::new(Notify::new());
+    let notify = Arc::clone(&retry);
+    tokio::spawn(async move {
+        let mut failures = 0u32;
+        loop {
+            match probe().await {
+                Ok(()) => {
+                    failures = 0;
+                    update_if_changed(&name, &state, &event_tx, |state| {
+                        state.set_status(&name, TargetStatus::Ready, None);
+                        state.set_probe_state(&name, 0, None);
+                    })
+                    .await;
+                    notify.notified().await;
+                }
+                Err(err) => {
+                    failures = failures.saturating_add(1);
+                    let mut delay = with_jitter(settings.delay_after(failures));
+                    if failures < settings.failure_threshold {
+                        delay = delay.min(BACKOFF_CAP);
+                    } else if failures == settings.failure_threshold {
+                        tracing::warn!(
+                            target = %name,
+                            failures,
+                            cool_down_secs = settings.cool_down.as_secs(),
+                            "target probe circuit open"
+                        );
+                    }
+                    let next_retry_at = SystemTime::now() + delay;
+                    update_if_changed(&name, &state, &event_tx, |state| {
+                        state.set_status(&name, TargetStatus::Down, Some(err));
+                        state.set_probe_state(&name, failures, Some(next_retry_at));
+                    })
+                    .await;
+                    tokio::select! {
+                        _ = tokio::time::sleep(delay) => {}
+                        _ = notify.notified() => {
+                            failures = 0;
+                        }
+                    }
+                }
+            }
+        }
+    });
+    retry
+}
+
+/// 只有 TargetInfo 实际变化时才广播 TargetUpdated，避免周期任务刷屏。
+pub(crate) async fn update_if_changed(
+    name: &str,
+    state: &Arc<RwLock<ConsoleState>>,
+    event_tx: &broadcast::Sender<ConsoleEvent>,
+    apply: impl FnOnce(&mut ConsoleState),
+) {
+    let target = {
+        let mut state = state.write().await;
+        let before = state.target_info(name);
+        apply(&mut state);
+        let after = state.target_info(name);
+        if before == after {
+            None
+        } else {
+            after
+        }
+    };
+    if let Some(target) = target {
+        let _ = event_tx.send(ConsoleEvent::TargetUpdated { target });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backs_off_exponentially_then_cools_down() {
+        let settings = ProbeSettings::from_config(Some(&HealthSettings {
+            failure_threshold: Some(8),
+            cool_down_secs: Some(900),
+        }));
+        let delays: Vec<u64> = (1..=9)
+            .map(|failures| settings.delay_after(failures).as_secs())
+            .collect();
+        assert_eq!(delays, [5, 10, 20, 40, 80, 160, 300, 900, 900]);
+
+        let defaults = ProbeSettings::from_config(None);
+        assert_eq!(defaults.failure_threshold, DEFAULT_FAILURE_THRESHOLD);
+        assert_eq!(
+            defaults.cool_down,
+            Duration::from_secs(DEFAULT_COOL_DOWN_SECS)
+        );
+        let jittered = with_jitter(Duration::from_secs(100));
+        assert!(jittered >= Duration::from_secs(80) && jittered <= Duration::from_secs(120));
+    }
+}
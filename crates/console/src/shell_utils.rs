@@ -2,6 +2,7 @@ use std::collections::BTreeMap;
 use std::ffi::OsStr;
 
 use portable_pty::CommandBuilder;
+use protocol::control::SshFailurePhase;
 
 pub(crate) trait CommandArgs {
     fn arg<S: AsRef<OsStr>>(&mut self, arg: S) -> &mut Self;
@@ -26,13 +27,165 @@ impl CommandArgs for CommandBuilder {
     }
 }
 
-pub(crate) fn apply_ssh_base_options<C: CommandArgs>(cmd: &mut C) {
-    cmd.arg("-o").arg("StrictHostKeyChecking=accept-new");
+/// 目标的 SSH 主机密钥校验方式（配置项 `ssh_host_key_policy`），所有 ssh 调用都经由
+/// [`apply_ssh_base_options`] 使用同一策略。
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) enum HostKeyCheck {
+    #[default]
+    AcceptNew,
+    Strict,
+    Pinned(PinnedHostKey),
+    Insecure,
+}
+
+/// 固定的主机公钥：写入单独的 known_hosts 文件，并用 HostKeyAlias 让 ssh 按别名查找，
+/// 与目标实际的主机名、端口无关。
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct PinnedHostKey {
+    pub(crate) alias: String,
+    pub(crate) known_hosts: std::path::PathBuf,
+    pub(crate) entry: String,
+    pub(crate) fingerprint: String,
+}
+
+impl PinnedHostKey {
+    // 临时目录可能被系统清理，每次使用前确认文件仍在。
+    pub(crate) fn ensure_known_hosts(&self) -> std::io::Result<()> {
+        if self.known_hosts.is_file() {
+            return Ok(());
+        }
+        if let Some(parent) = self.known_hosts.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&self.known_hosts, &self.entry)
+    }
+}
+
+pub(crate) fn apply_ssh_base_options<C: CommandArgs>(cmd: &mut C, host_key: &HostKeyCheck) {
+    match host_key {
+        HostKeyCheck::AcceptNew => {
+            cmd.arg("-o").arg("StrictHostKeyChecking=accept-new");
+        }
+        HostKeyCheck::Strict => {
+            cmd.arg("-o").arg("StrictHostKeyChecking=yes");
+        }
+        HostKeyCheck::Pinned(pinned) => {
+            if let Err(err) = pinned.ensure_known_hosts() {
+                tracing::warn!(
+                    path = %pinned.known_hosts.display(),
+                    error = %err,
+                    "failed to write pinned known_hosts"
+                );
+            }
+            cmd.arg("-o").arg("StrictHostKeyChecking=yes");
+            cmd.arg("-o").arg(format!(
+                "UserKnownHostsFile=\"{}\"",
+                pinned.known_hosts.display()
+            ));
+            cmd.arg("-o").arg("GlobalKnownHostsFile=/dev/null");
+            cmd.arg("-o").arg(format!("HostKeyAlias={}", pinned.alias));
+        }
+        HostKeyCheck::Insecure => {
+            cmd.arg("-o").arg("StrictHostKeyChecking=no");
+            cmd.arg("-o").arg("UserKnownHostsFile=/dev/null");
+        }
+    }
     cmd.arg("-o").arg("ConnectTimeout=10");
 }
 
-pub(crate) fn apply_ssh_options<C: CommandArgs>(cmd: &mut C, has_password: bool) {
-    apply_ssh_base_options(cmd);
+/// ssh 因主机密钥校验失败退出时，返回指明远端密钥指纹的错误信息；其他失败返回 None。
+pub(crate) fn host_key_failure(stderr: &str, host_key: &HostKeyCheck) -> Option<String> {
+    if !stderr.contains("Host key verification failed")
+        && !stderr.contains("REMOTE HOST IDENTIFICATION HAS CHANGED")
+    {
+        return None;
+    }
+    let remote = stderr
+        .split_whitespace()
+        .find(|word| word.starts_with("SHA256:"))
+        .map(|word| word.trim_end_matches('.'));
+    let mut message = match remote {
+        Some(fingerprint) => {
+            format!("ssh host key verification failed: remote host presented {fingerprint}")
+        }
+        None => "ssh host key verification failed: remote host key is not trusted".to_string(),
+    };
+    if let HostKeyCheck::Pinned(pinned) = host_key {
+        message.push_str(&format!(", pinned key is {}", pinned.fingerprint));
+    }
+    Some(message)
+}
+
+const AUTH_PATTERNS: &[&str] = &[
+    "permission denied",
+    "too many authentication failures",
+    "authentication failed",
+    "no more authentication methods",
+];
+const LOCAL_BIND_PATTERNS: &[&str] = &[
+    "bind",
+    "cannot listen to port",
+    "could not request local forwarding",
+    "address already in use",
+];
+const REMOTE_BIND_PATTERNS: &[&str] = &[
+    "open failed",
+    "connect failed",
+    "remote port forwarding failed",
+    "administratively prohibited",
+];
+
+/// 按 ssh stderr 归类失败阶段，并给出以阶段开头的说明（取匹配到的那一行，否则取最后一行）。
+/// 无法识别的输出按 Connect 处理。
+pub(crate) fn classify_ssh_failure(
+    stderr: &str,
+    host_key: &HostKeyCheck,
+    remote_addr: Option<&str>,
+) -> (SshFailurePhase, String) {
+    if let Some(message) = host_key_failure(stderr, host_key) {
+        return (SshFailurePhase::Auth, message);
+    }
+    let matching = |patterns: &[&str]| {
+        stderr.lines().map(str::trim).find(|line| {
+            let line = line.to_ascii_lowercase();
+            patterns.iter().any(|pattern| line.contains(pattern))
+        })
+    };
+    if let Some(line) = matching(AUTH_PATTERNS) {
+        return (SshFailurePhase::Auth, format!("ssh auth failed: {line}"));
+    }
+    if let Some(line) = matching(LOCAL_BIND_PATTERNS) {
+        return (
+            SshFailurePhase::LocalBind,
+            format!("local port unavailable: {line}"),
+        );
+    }
+    if let Some(line) = matching(REMOTE_BIND_PATTERNS) {
+        let port = remote_addr.and_then(|addr| addr.rsplit(':').next());
+        let detail = match port {
+            Some(port) => format!("remote port {port} refused: {line}"),
+            None => format!("remote forward failed: {line}"),
+        };
+        return (SshFailurePhase::RemoteBind, detail);
+    }
+    let line = stderr
+        .lines()
+        .rev()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .unwrap_or("no output");
+    (
+        SshFailurePhase::Connect,
+        format!("ssh connect failed: {line}"),
+    )
+}
+
+pub(crate) fn apply_ssh_options<C: CommandArgs>(
+    cmd: &mut C,
+    has_password: bool,
+    host_key: &HostKeyCheck,
+) {
+    apply_ssh_base_options(cmd, host_key);
     if !has_password {
         cmd.arg("-o").arg("BatchMode=yes");
     }
@@ -87,3 +240,42 @@ pub(crate) fn shell_escape(value: &str) -> String {
     escaped.push('\'');
     escaped
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_ssh_failures_by_phase() {
+        let classify = |stderr: &str| {
+            classify_ssh_failure(stderr, &HostKeyCheck::AcceptNew, Some("127.0.0.1:19302"))
+        };
+        assert_eq!(
+            classify("Warning: Permanently added 'h'.\ndev@h: Permission denied (publickey).\n"),
+            (
+                SshFailurePhase::Auth,
+                "ssh auth failed: dev@h: Permission denied (publickey).".to_string()
+            )
+        );
+        assert_eq!(
+            classify("Host key verification failed.").0,
+            SshFailurePhase::Auth
+        );
+        assert_eq!(
+            classify("bind [127.0.0.1]:8080: Address already in use").0,
+            SshFailurePhase::LocalBind
+        );
+        assert_eq!(
+            classify("channel 2: open failed: connect failed: Connection refused").1,
+            "remote port 19302 refused: channel 2: open failed: connect failed: Connection refused"
+        );
+        assert_eq!(
+            classify("ssh: connect to host h port 22: Connection timed out\n"),
+            (
+                SshFailurePhase::Connect,
+                "ssh connect failed: ssh: connect to host h port 22: Connection timed out"
+                    .to_string()
+            )
+        );
+    }
+}
@@ -1,11 +1,21 @@
 use crate::config::{ConsoleConfig, ConsoleDefaults, TargetConfig};
+use crate::shell_utils::{HostKeyCheck, PinnedHostKey};
+use base64::engine::general_purpose::{STANDARD, STANDARD_NO_PAD};
+use base64::Engine;
+use sha2::{Digest, Sha256};
 use std::collections::{HashMap, HashSet};
 
 use super::{ConsoleState, TargetSpec};
 
-use protocol::config::{parse_ssh_destination, resolve_terminal_locale};
+use protocol::config::{
+    parse_ssh_destination, resolve_ssh_password, resolve_terminal_locale, HostKeyPolicy,
+};
+use system_utils::secret::ConfigKey;
 
-pub(crate) fn build_console_state(config: ConsoleConfig) -> anyhow::Result<ConsoleState> {
+pub(crate) fn build_console_state(
+    config: ConsoleConfig,
+    config_key: Option<&ConfigKey>,
+) -> anyhow::Result<ConsoleState> {
     let defaults = config.defaults.unwrap_or_default();
     let mut targets = HashMap::new();
     let mut order = Vec::new();
@@ -19,7 +29,7 @@ pub(crate) fn build_console_state(config: ConsoleConfig) -> anyhow::Result<Conso
         }
         seen.insert(target.name.clone());
 
-        let resolved = resolve_target(&defaults, target)?;
+        let resolved = resolve_target(&defaults, target, config_key)?;
         if let Some(ssh) = resolved.ssh.as_ref() {
             if parse_ssh_destination(ssh).is_none() {
                 anyhow::bail!("target {} ssh must be in the form user@host", resolved.name);
@@ -40,15 +50,16 @@ pub(crate) fn build_console_state(config: ConsoleConfig) -> anyhow::Result<Conso
     Ok(ConsoleState::new(targets, order, config.default_target))
 }
 
-fn resolve_target(defaults: &ConsoleDefaults, target: TargetConfig) -> anyhow::Result<TargetSpec> {
+fn resolve_target(
+    defaults: &ConsoleDefaults,
+    target: TargetConfig,
+    config_key: Option<&ConfigKey>,
+) -> anyhow::Result<TargetSpec> {
     let mut ssh_args = defaults.ssh_args.clone().unwrap_or_default();
     if let Some(extra) = target.ssh_args.clone() {
         ssh_args.extend(extra);
     }
-    let ssh_password = target
-        .ssh_password
-        .clone()
-        .or_else(|| defaults.ssh_password.clone());
+    let ssh_password = resolve_ssh_password(defaults, &target, config_key)?;
     let terminal_locale = resolve_terminal_locale(Some(defaults), &target);
     if ssh_password.is_some() {
         tracing::warn!(
@@ -57,6 +68,16 @@ fn resolve_target(defaults: &ConsoleDefaults, target: TargetConfig) -> anyhow::R
         );
     }
 
+    if target.tty && target.shell.is_windows() {
+        anyhow::bail!(
+            "target {} tty sessions are only supported with shell = \"bash\"",
+            target.name
+        );
+    }
+    validate_extra_forwards(&target)?;
+    let host_key = resolve_host_key(&target)?;
+    let banner = target.banner();
+
     Ok(TargetSpec {
         name: target.name,
         desc: target.desc,
@@ -65,17 +86,130 @@ fn resolve_target(defaults: &ConsoleDefaults, target: TargetConfig) -> anyhow::R
         ssh_password,
         terminal_locale,
         tty: target.tty,
+        shell: target.shell,
+        extra_forwards: target.extra_forwards,
+        host_key,
+        banner,
+    })
+}
+
+fn resolve_host_key(target: &TargetConfig) -> anyhow::Result<HostKeyCheck> {
+    Ok(match target.ssh_host_key_policy {
+        HostKeyPolicy::AcceptNew => HostKeyCheck::AcceptNew,
+        HostKeyPolicy::Strict => HostKeyCheck::Strict,
+        HostKeyPolicy::Insecure => {
+            tracing::warn!(
+                target = %target.name,
+                "ssh_host_key_policy = \"insecure\": host keys are not verified"
+            );
+            HostKeyCheck::Insecure
+        }
+        HostKeyPolicy::Pinned => {
+            let key = target.ssh_host_key.as_deref().ok_or_else(|| {
+                anyhow::anyhow!(
+                    "target {} ssh_host_key_policy = \"pinned\" requires ssh_host_key",
+                    target.name
+                )
+            })?;
+            let pinned = pinned_host_key(&target.name, key)
+                .map_err(|err| anyhow::anyhow!("target {} ssh_host_key {err}", target.name))?;
+            pinned.ensure_known_hosts().map_err(|err| {
+                anyhow::anyhow!(
+                    "target {} failed to write {}: {err}",
+                    target.name,
+                    pinned.known_hosts.display()
+                )
+            })?;
+            HostKeyCheck::Pinned(pinned)
+        }
     })
 }
 
+// 接受 `ssh-keyscan` / known_hosts 的格式：可带主机名前缀与注释，只取 `<type> <base64>`。
+fn pinned_host_key(target: &str, key: &str) -> Result<PinnedHostKey, String> {
+    let fields: Vec<&str> = key.split_whitespace().collect();
+    let start = fields
+        .iter()
+        .position(|field| {
+            ["ssh-", "ecdsa-", "sk-"]
+                .iter()
+                .any(|prefix| field.starts_with(prefix))
+        })
+        .ok_or_else(|| "must look like \"ssh-ed25519 AAAA...\"".to_string())?;
+    let (Some(key_type), Some(encoded)) = (fields.get(start), fields.get(start + 1)) else {
+        return Err("is missing the base64 key data".to_string());
+    };
+    let blob = STANDARD
+        .decode(encoded)
+        .map_err(|err| format!("has invalid base64 key data: {err}"))?;
+    let alias: String = target
+        .chars()
+        .map(|ch| {
+            if ch.is_ascii_alphanumeric() || matches!(ch, '.' | '_' | '-') {
+                ch
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let alias = format!("octovalve-{alias}");
+    Ok(PinnedHostKey {
+        known_hosts: std::env::temp_dir()
+            .join("octovalve-known-hosts")
+            .join(&alias),
+        entry: format!("{alias} {key_type} {encoded}\n"),
+        fingerprint: format!("SHA256:{}", STANDARD_NO_PAD.encode(Sha256::digest(&blob))),
+        alias,
+    })
+}
+
+fn validate_extra_forwards(target: &TargetConfig) -> anyhow::Result<()> {
+    let mut names = HashSet::new();
+    for forward in &target.extra_forwards {
+        let name = forward.name.trim();
+        if name.is_empty() || name != forward.name || name.contains(['/', '\\']) {
+            anyhow::bail!(
+                "target {} extra_forwards name {:?} is invalid",
+                target.name,
+                forward.name
+            );
+        }
+        if !names.insert(name) {
+            anyhow::bail!(
+                "target {} has duplicate extra_forwards name {name}",
+                target.name
+            );
+        }
+        let port = forward
+            .remote_addr
+            .rsplit_once(':')
+            .and_then(|(host, port)| (!host.is_empty()).then_some(port))
+            .and_then(|port| port.parse::<u16>().ok());
+        if port.is_none() {
+            anyhow::bail!(
+                "target {} extra_forwards {name} remote_addr must be host:port",
+                target.name
+            );
+        }
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use protocol::config::ExtraForward;
 
     #[test]
     fn merges_ssh_args_from_defaults_and_target() {
         let config = ConsoleConfig {
             default_target: None,
+            aliases: Default::default(),
+            terminal: None,
+            health: None,
+            server: None,
+            templates: Vec::new(),
+            schedules: Vec::new(),
             defaults: Some(ConsoleDefaults {
                 ssh_args: Some(vec![
                     "-o".to_string(),
@@ -89,11 +223,19 @@ mod tests {
                 ssh: Some("devops@127.0.0.1".to_string()),
                 ssh_args: Some(vec!["-p".to_string(), "2222".to_string()]),
                 ssh_password: None,
+                ssh_password_encrypted: None,
                 terminal_locale: None,
                 tty: false,
+                shell: Default::default(),
+                default_cwd: None,
+                queue_when_unreachable: false,
+                extra_forwards: Vec::new(),
+                ssh_host_key_policy: Default::default(),
+                ssh_host_key: None,
+                banner: None,
             }],
         };
-        let state = build_console_state(config).expect("state");
+        let state = build_console_state(config, None).expect("state");
         let target = state.target_spec("dev").expect("target");
         assert_eq!(
             target.ssh_args,
@@ -106,10 +248,54 @@ mod tests {
         );
     }
 
+    #[test]
+    fn pinned_host_key_writes_alias_entry_and_fingerprint() {
+        let key =
+            "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIOMqqnkVzrm0SdG6UOoqKLsabgH5C9okWi0dh2l9GKJl";
+        let pinned = pinned_host_key("web 1", &format!("[web-1]:2222 {key} root@web-1")).unwrap();
+        assert_eq!(pinned.alias, "octovalve-web_1");
+        assert_eq!(pinned.entry, format!("octovalve-web_1 {key}\n"));
+        assert!(pinned.fingerprint.starts_with("SHA256:"));
+        assert!(!pinned.fingerprint.ends_with('='));
+        assert!(pinned_host_key("web", "SHA256:abc").is_err());
+        assert!(pinned_host_key("web", "ssh-ed25519 not*base64").is_err());
+
+        let mut cmd = std::process::Command::new("ssh");
+        let _ = std::fs::remove_file(&pinned.known_hosts);
+        crate::shell_utils::apply_ssh_base_options(&mut cmd, &HostKeyCheck::Pinned(pinned.clone()));
+        let args: Vec<String> = cmd
+            .get_args()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect();
+        assert!(args.contains(&"HostKeyAlias=octovalve-web_1".to_string()));
+        assert_eq!(
+            std::fs::read_to_string(&pinned.known_hosts).unwrap(),
+            pinned.entry
+        );
+        let _ = std::fs::remove_file(&pinned.known_hosts);
+
+        let mismatch = "@@@@@@@@@@@\n@    WARNING: REMOTE HOST IDENTIFICATION HAS CHANGED!     @\nThe fingerprint for the ED25519 key sent by the remote host is\nSHA256:abcDEF123.\nHost key verification failed.";
+        let message =
+            crate::shell_utils::host_key_failure(mismatch, &HostKeyCheck::Pinned(pinned.clone()))
+                .unwrap();
+        assert!(message.contains("SHA256:abcDEF123,"), "{message}");
+        assert!(message.contains(&pinned.fingerprint), "{message}");
+        assert!(
+            crate::shell_utils::host_key_failure("Connection refused", &HostKeyCheck::Strict)
+                .is_none()
+        );
+    }
+
     #[test]
     fn requires_user_in_ssh_destination() {
         let config = ConsoleConfig {
             default_target: None,
+            aliases: Default::default(),
+            terminal: None,
+            health: None,
+            server: None,
+            templates: Vec::new(),
+            schedules: Vec::new(),
             defaults: None,
             targets: vec![TargetConfig {
                 name: "dev".to_string(),
@@ -117,14 +303,123 @@ mod tests {
                 ssh: Some("127.0.0.1".to_string()),
                 ssh_args: None,
                 ssh_password: None,
+                ssh_password_encrypted: None,
                 terminal_locale: None,
                 tty: false,
+                shell: Default::default(),
+                default_cwd: None,
+                queue_when_unreachable: false,
+                extra_forwards: Vec::new(),
+                ssh_host_key_policy: Default::default(),
+                ssh_host_key: None,
+                banner: None,
             }],
         };
-        let err = build_console_state(config)
+        let err = build_console_state(config, None)
             .err()
             .expect("expected error")
             .to_string();
         assert!(err.contains("user@host"));
     }
+
+    #[test]
+    fn validates_extra_forwards() {
+        let config = |remote_addr: &str| ConsoleConfig {
+            default_target: None,
+            aliases: Default::default(),
+            terminal: None,
+            health: None,
+            server: None,
+            templates: Vec::new(),
+            schedules: Vec::new(),
+            defaults: None,
+            targets: vec![TargetConfig {
+                name: "dev".to_string(),
+                desc: "dev".to_string(),
+                ssh: Some("devops@127.0.0.1".to_string()),
+                ssh_args: None,
+                ssh_password: None,
+                ssh_password_encrypted: None,
+                terminal_locale: None,
+                tty: false,
+                shell: Default::default(),
+                default_cwd: None,
+                queue_when_unreachable: false,
+                extra_forwards: vec![ExtraForward {
+                    name: "grafana".to_string(),
+                    remote_addr: remote_addr.to_string(),
+                    local_port: Some(3000),
+                }],
+                ssh_host_key_policy: Default::default(),
+                ssh_host_key: None,
+                banner: None,
+            }],
+        };
+        let state = build_console_state(config("127.0.0.1:3000"), None).expect("state");
+        assert_eq!(state.target_spec("dev").unwrap().extra_forwards.len(), 1);
+        for remote_addr in ["127.0.0.1", ":3000", "localhost:http"] {
+            let err = build_console_state(config(remote_addr), None)
+                .err()
+                .expect("expected error")
+                .to_string();
+            assert!(err.contains("remote_addr"), "{remote_addr}: {err}");
+        }
+    }
+
+    #[test]
+    fn decrypts_encrypted_ssh_password() {
+        let key = ConfigKey::new("correct horse").expect("key");
+        let encrypted = key.encrypt("s3cret").expect("encrypt");
+        let config = || ConsoleConfig {
+            default_target: None,
+            aliases: Default::default(),
+            terminal: None,
+            health: None,
+            server: None,
+            templates: Vec::new(),
+            schedules: Vec::new(),
+            defaults: None,
+            targets: vec![TargetConfig {
+                name: "dev".to_string(),
+                desc: "dev".to_string(),
+                ssh: Some("devops@127.0.0.1".to_string()),
+                ssh_args: None,
+                ssh_password: None,
+                ssh_password_encrypted: Some(encrypted.clone()),
+                terminal_locale: None,
+                tty: false,
+                shell: Default::default(),
+                default_cwd: None,
+                queue_when_unreachable: false,
+                extra_forwards: Vec::new(),
+                ssh_host_key_policy: Default::default(),
+                ssh_host_key: None,
+                banner: None,
+            }],
+        };
+        let state = build_console_state(config(), Some(&key)).expect("state");
+        assert_eq!(
+            state
+                .target_spec("dev")
+                .expect("target")
+                .ssh_password
+                .as_deref(),
+            Some("s3cret")
+        );
+
+        let wrong = ConfigKey::new("wrong").expect("key");
+        let err = format!(
+            "{:#}",
+            build_console_state(config(), Some(&wrong))
+                .err()
+                .expect("error")
+        );
+        assert!(err.contains("target dev ssh_password_encrypted"));
+
+        let err = format!(
+            "{:#}",
+            build_console_state(config(), None).err().expect("error")
+        );
+        assert!(err.contains("OCTOVALVE_CONFIG_KEY"));
+    }
 }
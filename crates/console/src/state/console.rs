@@ -1,11 +1,17 @@
 use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::SystemTime;
 
-use tokio::sync::mpsc;
+use protocol::ProgressStage;
+use tokio::sync::{mpsc, Notify};
 
-use crate::control::{ServiceEvent, ServiceSnapshot};
+use crate::control::{ForwardDiagnostic, ServiceEvent, ServiceSnapshot};
+use crate::time_utils::now_ms;
 
-use super::model::{ControlCommand, TargetInfo, TargetSpec, TargetStatus};
+use super::model::{
+    ConsoleSummary, ControlCommand, ForwardInfo, QuotaUsage, TargetInfo, TargetSpec, TargetStatus,
+    TargetSummary, TemporaryRule,
+};
 
 const HISTORY_LIMIT: usize = 50;
 
@@ -20,11 +26,30 @@ struct ConnectionState {
     last_seen: HashMap<String, SystemTime>,
     last_error: HashMap<String, String>,
     command_txs: HashMap<String, mpsc::Sender<ControlCommand>>,
+    probes: HashMap<String, ProbeState>,
+    retry_notifiers: HashMap<String, Arc<Notify>>,
+}
+
+struct ProbeState {
+    consecutive_failures: u32,
+    next_retry_at: Option<SystemTime>,
+}
+
+struct AuditState {
+    bytes: u64,
+    warning: Option<String>,
 }
 
 struct SessionState {
     pending_count: HashMap<String, usize>,
     snapshots: HashMap<String, ServiceSnapshot>,
+    // 维护模式：存在即开启，值为提示信息（可为空字符串）。
+    maintenance: HashMap<String, String>,
+    audit: HashMap<String, AuditState>,
+    forwards: HashMap<String, Vec<ForwardInfo>>,
+    forward_diagnostics: HashMap<String, Vec<ForwardDiagnostic>>,
+    quota: HashMap<String, QuotaUsage>,
+    temporary_rules: HashMap<String, Vec<TemporaryRule>>,
 }
 
 pub(crate) struct ConsoleState {
@@ -55,10 +80,18 @@ impl ConsoleState {
                 last_seen: HashMap::new(),
                 last_error: HashMap::new(),
                 command_txs: HashMap::new(),
+                probes: HashMap::new(),
+                retry_notifiers: HashMap::new(),
             },
             session: SessionState {
                 pending_count,
                 snapshots: HashMap::new(),
+                maintenance: HashMap::new(),
+                audit: HashMap::new(),
+                forwards: HashMap::new(),
+                forward_diagnostics: HashMap::new(),
+                quota: HashMap::new(),
+                temporary_rules: HashMap::new(),
             },
         }
     }
@@ -72,6 +105,42 @@ impl ConsoleState {
             .collect()
     }
 
+    pub(crate) fn summary(&self) -> ConsoleSummary {
+        let now_ms = now_ms();
+        let targets: Vec<TargetSummary> = self
+            .cache
+            .order
+            .iter()
+            .filter(|name| self.cache.targets.contains_key(*name))
+            .map(|name| TargetSummary {
+                name: name.clone(),
+                status: *self
+                    .connection
+                    .status
+                    .get(name)
+                    .unwrap_or(&TargetStatus::Down),
+                pending_count: *self.session.pending_count.get(name).unwrap_or(&0),
+                oldest_pending_secs: self
+                    .session
+                    .snapshots
+                    .get(name)
+                    .and_then(|snapshot| {
+                        snapshot
+                            .queue
+                            .iter()
+                            .map(|request| request.common.received_at_ms)
+                            .min()
+                    })
+                    .map(|received_at_ms| now_ms.saturating_sub(received_at_ms) / 1000),
+                quota: self.quota_usage(name, now_ms),
+            })
+            .collect();
+        ConsoleSummary {
+            total_pending: targets.iter().map(|target| target.pending_count).sum(),
+            targets,
+        }
+    }
+
     pub(crate) fn target_specs(&self) -> Vec<TargetSpec> {
         self.cache
             .order
@@ -81,7 +150,43 @@ impl ConsoleState {
     }
 
     pub(crate) fn snapshot(&self, name: &str) -> Option<ServiceSnapshot> {
-        self.session.snapshots.get(name).cloned()
+        let mut snapshot = self.session.snapshots.get(name).cloned()?;
+        snapshot.banner = self
+            .cache
+            .targets
+            .get(name)
+            .and_then(|target| target.banner.clone());
+        snapshot.forward_diagnostics = self.forward_diagnostics(name);
+        Some(snapshot)
+    }
+
+    /// 该 id 是否仍在待审批、执行中或后台运行，用于拒绝重复提交的请求 id。
+    pub(crate) fn request_in_flight(&self, name: &str, id: &str) -> bool {
+        let Some(snapshot) = self.session.snapshots.get(name) else {
+            return false;
+        };
+        snapshot.queue.iter().any(|request| request.common.id == id)
+            || snapshot.running.iter().any(|job| job.common.id == id)
+            || snapshot.detached.iter().any(|job| job.common.id == id)
+    }
+
+    /// 请求当前所处阶段与在待审批队列中的位置（从 1 开始），供命令通道的进度帧使用。
+    pub(crate) fn request_progress(&self, name: &str, id: &str) -> (ProgressStage, Option<usize>) {
+        let Some(snapshot) = self.session.snapshots.get(name) else {
+            return (ProgressStage::Queued, None);
+        };
+        if let Some(index) = snapshot
+            .queue
+            .iter()
+            .position(|request| request.common.id == id)
+        {
+            return (ProgressStage::Queued, Some(index + 1));
+        }
+        if snapshot.running.iter().any(|job| job.common.id == id) {
+            (ProgressStage::Executing, None)
+        } else {
+            (ProgressStage::Queued, None)
+        }
     }
 
     pub(crate) fn target_spec(&self, name: &str) -> Option<TargetSpec> {
@@ -93,6 +198,7 @@ impl ConsoleState {
         Some(TargetInfo {
             name: target.name.clone(),
             desc: target.desc.clone(),
+            banner: target.banner.clone(),
             ssh: target.ssh.clone(),
             status: *self
                 .connection
@@ -113,9 +219,96 @@ impl ConsoleState {
                 .as_ref()
                 .map(|default| default == &target.name)
                 .unwrap_or(false),
+            maintenance: self.session.maintenance.contains_key(&target.name),
+            maintenance_message: self
+                .session
+                .maintenance
+                .get(&target.name)
+                .filter(|message| !message.is_empty())
+                .cloned(),
+            consecutive_failures: self
+                .connection
+                .probes
+                .get(&target.name)
+                .map(|probe| probe.consecutive_failures)
+                .unwrap_or(0),
+            next_retry_at: self
+                .connection
+                .probes
+                .get(&target.name)
+                .and_then(|probe| probe.next_retry_at.as_ref())
+                .map(format_time),
+            audit_bytes: self
+                .session
+                .audit
+                .get(&target.name)
+                .map(|audit| audit.bytes),
+            warning: self
+                .session
+                .audit
+                .get(&target.name)
+                .and_then(|audit| audit.warning.clone()),
+            forwards: self
+                .session
+                .forwards
+                .get(&target.name)
+                .cloned()
+                .unwrap_or_default(),
+            forward_diagnostics: self.forward_diagnostics(&target.name),
+            quota: self.quota_usage(&target.name, now_ms()),
+            temporary_rules: self.active_temporary_rules(&target.name, now_ms()),
         })
     }
 
+    // 规则到期不会主动推送更新，读取时过滤掉已过期的。
+    fn active_temporary_rules(&self, name: &str, now_ms: u64) -> Vec<TemporaryRule> {
+        self.session
+            .temporary_rules
+            .get(name)
+            .map(|rules| {
+                rules
+                    .iter()
+                    .filter(|rule| rule.expires_at_ms > now_ms)
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    // 跨过 UTC 零点后还没有新的计数时，按已重置展示。
+    fn quota_usage(&self, name: &str, now_ms: u64) -> Option<QuotaUsage> {
+        let mut usage = self.session.quota.get(name)?.clone();
+        if now_ms >= usage.resets_at_ms {
+            const DAY_MS: u64 = 86_400_000;
+            usage.used = Default::default();
+            usage.clients.clear();
+            usage.resets_at_ms = (now_ms / DAY_MS + 1) * DAY_MS;
+        }
+        Some(usage)
+    }
+
+    /// 开启（`Some(message)`）或关闭（`None`）目标的维护模式；未知目标返回 false。
+    pub(crate) fn set_maintenance(&mut self, name: &str, message: Option<String>) -> bool {
+        if !self.cache.targets.contains_key(name) {
+            return false;
+        }
+        match message {
+            Some(message) => {
+                self.session
+                    .maintenance
+                    .insert(name.to_string(), message.trim().to_string());
+            }
+            None => {
+                self.session.maintenance.remove(name);
+            }
+        }
+        true
+    }
+
+    pub(crate) fn maintenance_message(&self, name: &str) -> Option<&str> {
+        self.session.maintenance.get(name).map(String::as_str)
+    }
+
     pub(crate) fn register_command_sender(
         &mut self,
         name: String,
@@ -137,6 +330,92 @@ impl ConsoleState {
         }
     }
 
+    pub(crate) fn set_probe_state(
+        &mut self,
+        name: &str,
+        consecutive_failures: u32,
+        next_retry_at: Option<SystemTime>,
+    ) {
+        self.connection.probes.insert(
+            name.to_string(),
+            ProbeState {
+                consecutive_failures,
+                next_retry_at,
+            },
+        );
+    }
+
+    pub(crate) fn set_audit_usage(&mut self, name: &str, bytes: u64, warning: Option<String>) {
+        self.session
+            .audit
+            .insert(name.to_string(), AuditState { bytes, warning });
+    }
+
+    /// 由 quota 计数在启动、策略重载与每次执行结束后写入；None 表示该目标未配置额度。
+    pub(crate) fn set_quota_usage(&mut self, name: &str, usage: Option<QuotaUsage>) {
+        match usage {
+            Some(usage) => {
+                self.session.quota.insert(name.to_string(), usage);
+            }
+            None => {
+                self.session.quota.remove(name);
+            }
+        }
+    }
+
+    /// 由 service 在添加 / 撤销临时放行规则后写入。
+    pub(crate) fn set_temporary_rules(&mut self, name: &str, rules: Vec<TemporaryRule>) {
+        if rules.is_empty() {
+            self.session.temporary_rules.remove(name);
+        } else {
+            self.session.temporary_rules.insert(name.to_string(), rules);
+        }
+    }
+
+    /// 由 ForwardManager 在建立 / 释放 / 转发进程退出后写入当前活跃的额外转发。
+    pub(crate) fn set_forwards(&mut self, name: &str, forwards: Vec<ForwardInfo>) {
+        if forwards.is_empty() {
+            self.session.forwards.remove(name);
+        } else {
+            self.session.forwards.insert(name.to_string(), forwards);
+        }
+    }
+
+    /// 由 ForwardManager 在每次 ensure 之后写入各转发最近一次的结果。
+    pub(crate) fn set_forward_diagnostics(
+        &mut self,
+        name: &str,
+        diagnostics: Vec<ForwardDiagnostic>,
+    ) {
+        if diagnostics.is_empty() {
+            self.session.forward_diagnostics.remove(name);
+        } else {
+            self.session
+                .forward_diagnostics
+                .insert(name.to_string(), diagnostics);
+        }
+    }
+
+    fn forward_diagnostics(&self, name: &str) -> Vec<ForwardDiagnostic> {
+        self.session
+            .forward_diagnostics
+            .get(name)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn register_retry_notifier(&mut self, name: String, notifier: Arc<Notify>) {
+        self.connection.retry_notifiers.insert(name, notifier);
+    }
+
+    pub(crate) fn retry_notifier(&self, name: &str) -> Option<Arc<Notify>> {
+        self.connection.retry_notifiers.get(name).cloned()
+    }
+
+    pub(crate) fn last_seen_at(&self, name: &str) -> Option<SystemTime> {
+        self.connection.last_seen.get(name).copied()
+    }
+
     pub(crate) fn note_seen(&mut self, name: &str) {
         self.connection
             .last_seen
@@ -163,6 +442,9 @@ impl ConsoleState {
                         running: Vec::new(),
                         history: Vec::new(),
                         last_result: None,
+                        detached: Vec::new(),
+                        banner: None,
+                        forward_diagnostics: Vec::new(),
                     });
                 entry.queue = queue;
                 self.session
@@ -179,9 +461,28 @@ impl ConsoleState {
                         running: Vec::new(),
                         history: Vec::new(),
                         last_result: None,
+                        detached: Vec::new(),
+                        banner: None,
+                        forward_diagnostics: Vec::new(),
                     });
                 entry.running = running;
             }
+            ServiceEvent::DetachedUpdated(detached) => {
+                let entry = self
+                    .session
+                    .snapshots
+                    .entry(name.to_string())
+                    .or_insert_with(|| ServiceSnapshot {
+                        queue: Vec::new(),
+                        running: Vec::new(),
+                        history: Vec::new(),
+                        last_result: None,
+                        detached: Vec::new(),
+                        banner: None,
+                        forward_diagnostics: Vec::new(),
+                    });
+                entry.detached = detached;
+            }
             ServiceEvent::ResultUpdated(result) => {
                 let entry = self
                     .session
@@ -192,6 +493,9 @@ impl ConsoleState {
                         running: Vec::new(),
                         history: Vec::new(),
                         last_result: None,
+                        detached: Vec::new(),
+                        banner: None,
+                        forward_diagnostics: Vec::new(),
                     });
                 entry.last_result = Some(result.clone());
                 entry.history.insert(0, result);
@@ -4,4 +4,7 @@ mod model;
 
 pub(crate) use config::build_console_state;
 pub(crate) use console::ConsoleState;
-pub(crate) use model::{ControlCommand, TargetInfo, TargetSpec, TargetStatus};
+pub(crate) use model::{
+    ConsoleSummary, ControlCommand, EditRejection, ForwardInfo, QuotaCounter, QuotaUsage,
+    TargetInfo, TargetSpec, TargetStatus, TemporaryRule,
+};
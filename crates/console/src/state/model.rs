@@ -1,10 +1,80 @@
+use crate::shell_utils::HostKeyCheck;
+use crate::terminal_capture::CapturedCommand;
+use protocol::config::{ExtraForward, TargetShell};
+use protocol::control::{DetachedSnapshot, ForwardDiagnostic, ResultSnapshot};
+use protocol::CancelOutcome;
 use serde::Serialize;
+use std::collections::BTreeMap;
+use tokio::sync::oneshot;
 
 pub(crate) enum ControlCommand {
-    Approve(String),
-    Deny(String),
+    /// actor 是批准/拒绝人，写入结果快照与审计记录。
+    Approve {
+        id: String,
+        actor: String,
+        /// 请求带了 `confirm` 且与 id 一致；命中 dangerous_patterns 的请求未确认时留在队列里。
+        confirmed: bool,
+        /// 自动批准不关心结果时为 None。
+        respond_to: Option<oneshot::Sender<Result<(), EditRejection>>>,
+    },
+    Deny {
+        id: String,
+        actor: String,
+        reason: Option<String>,
+    },
     Cancel(String),
     ForceCancel(String),
+    /// 未知 id 回 None。
+    QueryDetached {
+        id: String,
+        respond_to: oneshot::Sender<Option<DetachedSnapshot>>,
+    },
+    /// 最近被拒绝的请求，新的在前。
+    QueryDenied {
+        respond_to: oneshot::Sender<Vec<ResultSnapshot>>,
+    },
+    KillDetached(String),
+    /// 用操作员改写后的命令批准；校验不通过时请求留在队列里。
+    ApproveEdited {
+        id: String,
+        raw_command: String,
+        actor: String,
+        /// 请求带了 `confirm` 且与 id 一致。
+        confirmed: bool,
+        respond_to: oneshot::Sender<Result<(), EditRejection>>,
+    },
+    /// 临时放行一条命令；命中 `whitelist.hard_deny`（immutable）的命令返回错误。
+    AddTemporaryRule {
+        rule: String,
+        ttl_secs: u64,
+        actor: String,
+        respond_to: oneshot::Sender<Result<TemporaryRule, String>>,
+    },
+    ListTemporaryRules {
+        respond_to: oneshot::Sender<Vec<TemporaryRule>>,
+    },
+    /// 未知或已过期的 id 回 false。
+    RevokeTemporaryRule {
+        id: String,
+        actor: String,
+        respond_to: oneshot::Sender<bool>,
+    },
+    /// 提交方（proxy 的 cancel_command）取消自己的请求：排队中的直接移出队列，执行中的与 Cancel 相同。
+    ClientCancel {
+        id: String,
+        client: String,
+        respond_to: oneshot::Sender<CancelOutcome>,
+    },
+    /// `[terminal] capture_commands` 开启时，交互式终端里执行完的一条命令，直接记入目标历史。
+    RecordTerminalCommand(CapturedCommand),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum EditRejection {
+    NotFound,
+    /// 改写后的命令命中 dangerous_patterns，需要回显请求 id。
+    ConfirmationRequired,
+    Invalid(String),
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
@@ -23,12 +93,19 @@ pub(crate) struct TargetSpec {
     pub(crate) ssh_password: Option<String>,
     pub(crate) terminal_locale: Option<String>,
     pub(crate) tty: bool,
+    pub(crate) shell: TargetShell,
+    pub(crate) extra_forwards: Vec<ExtraForward>,
+    pub(crate) host_key: HostKeyCheck,
+    pub(crate) banner: Option<String>,
 }
 
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, PartialEq, Serialize)]
 pub(crate) struct TargetInfo {
     pub(crate) name: String,
     pub(crate) desc: String,
+    /// 配置中的 `banner`，已截断到 512 个字符。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) banner: Option<String>,
     pub(crate) ssh: Option<String>,
     pub(crate) status: TargetStatus,
     pub(crate) pending_count: usize,
@@ -36,4 +113,84 @@ pub(crate) struct TargetInfo {
     pub(crate) last_error: Option<String>,
     pub(crate) terminal_available: bool,
     pub(crate) is_default: bool,
+    pub(crate) maintenance: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) maintenance_message: Option<String>,
+    pub(crate) consecutive_failures: u32,
+    /// 连通性探测失败后下一次自动重试的时间（RFC3339）。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) next_retry_at: Option<String>,
+    /// 该目标审计目录中请求记录占用的字节数，由 retention 任务周期刷新。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) audit_bytes: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) warning: Option<String>,
+    /// 当前已建立的 `extra_forwards`。
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub(crate) forwards: Vec<ForwardInfo>,
+    /// 各额外转发最近一次建立的结果（成功耗时或失败阶段）。
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub(crate) forward_diagnostics: Vec<ForwardDiagnostic>,
+    /// 配置了 `[quota]` 时当天（UTC）的用量。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) quota: Option<QuotaUsage>,
+    /// 尚未过期的临时放行规则。
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub(crate) temporary_rules: Vec<TemporaryRule>,
+}
+
+/// `GET /summary` 与 `summary_changed` 事件的内容：只含待审批数量，不读取历史与快照详情。
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct ConsoleSummary {
+    pub(crate) total_pending: usize,
+    pub(crate) targets: Vec<TargetSummary>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct TargetSummary {
+    pub(crate) name: String,
+    pub(crate) status: TargetStatus,
+    pub(crate) pending_count: usize,
+    pub(crate) oldest_pending_secs: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) quota: Option<QuotaUsage>,
+}
+
+/// `used` 为目标上所有 client 的合计；`per_client` 时额度按 client 计算，各自用量见 `clients`。
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct QuotaUsage {
+    pub(crate) used: QuotaCounter,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) max_daily_commands: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) max_daily_execution_secs: Option<u64>,
+    pub(crate) per_client: bool,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) clients: BTreeMap<String, QuotaCounter>,
+    pub(crate) resets_at_ms: u64,
+}
+
+/// 运维临时放行的命令（按完整路径或 basename 匹配），到 `expires_at_ms` 自动失效。
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct TemporaryRule {
+    pub(crate) id: String,
+    pub(crate) rule: String,
+    pub(crate) actor: String,
+    pub(crate) created_at_ms: u64,
+    pub(crate) expires_at_ms: u64,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub(crate) struct QuotaCounter {
+    pub(crate) commands: u64,
+    pub(crate) execution_secs: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct ForwardInfo {
+    pub(crate) name: String,
+    pub(crate) remote_addr: String,
+    /// 已建立时实际绑定的本地地址，可能与配置的 local_port 不同。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) local_addr: Option<String>,
 }
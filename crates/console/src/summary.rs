@@ -0,0 +1,195 @@
+use std::sync::Arc;
+
+use tokio::sync::{broadcast, RwLock};
+use tokio::time::{sleep_until, Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+use crate::events::ConsoleEvent;
+use crate::state::{ConsoleState, ConsoleSummary};
+
+const SUMMARY_MIN_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 监听会改变待审批数量的事件，数量确有变化时发出 `summary_changed`；
+/// 距上次发送不足 1 秒时推迟到窗口结束再按最新状态发送一次。
+pub(crate) fn spawn_summary_notifier(
+    event_tx: broadcast::Sender<ConsoleEvent>,
+    state: Arc<RwLock<ConsoleState>>,
+    shutdown: CancellationToken,
+) {
+    let mut event_rx = event_tx.subscribe();
+    tokio::spawn(async move {
+        // 首个相关事件总会发送一次，之后只在数量变化时发送。
+        let mut last_counts: Option<Vec<(String, usize)>> = None;
+        let mut last_sent: Option<Instant> = None;
+        let mut deadline: Option<Instant> = None;
+        loop {
+            tokio::select! {
+                _ = shutdown.cancelled() => break,
+                _ = sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => {
+                    deadline = None;
+                    let summary = state.read().await.summary();
+                    let counts = pending_counts(&summary);
+                    if last_counts.as_ref() != Some(&counts) {
+                        last_counts = Some(counts);
+                        last_sent = Some(Instant::now());
+                        let _ = event_tx.send(ConsoleEvent::SummaryChanged { summary });
+                    }
+                }
+                event = event_rx.recv() => {
+                    let relevant = match event {
+                        Ok(event) => affects_pending(&event),
+                        Err(broadcast::error::RecvError::Lagged(_)) => true,
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    };
+                    if relevant && deadline.is_none() {
+                        let now = Instant::now();
+                        deadline = Some(
+                            last_sent
+                                .map(|sent| sent + SUMMARY_MIN_INTERVAL)
+                                .filter(|next| *next > now)
+                                .unwrap_or(now),
+                        );
+                    }
+                }
+            }
+        }
+    });
+}
+
+fn affects_pending(event: &ConsoleEvent) -> bool {
+    matches!(
+        event,
+        ConsoleEvent::TargetsSnapshot { .. }
+            | ConsoleEvent::TargetUpdated { .. }
+            | ConsoleEvent::RequestQueued { .. }
+            | ConsoleEvent::RequestResolved { .. }
+    )
+}
+
+fn pending_counts(summary: &ConsoleSummary) -> Vec<(String, usize)> {
+    summary
+        .targets
+        .iter()
+        .map(|target| (target.name.clone(), target.pending_count))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control::ServiceEvent;
+    use crate::state::build_console_state;
+    use protocol::control::RequestSnapshot;
+    use serde_json::json;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn console_state() -> ConsoleState {
+        let config = toml::from_str(
+            r#"
+[[targets]]
+name = "dev"
+desc = "dev"
+ssh = "devops@127.0.0.1"
+
+[[targets]]
+name = "prod"
+desc = "prod"
+ssh = "devops@127.0.0.2"
+"#,
+        )
+        .expect("config");
+        build_console_state(config, None).expect("state")
+    }
+
+    fn queued(id: &str, received_at_ms: u64) -> RequestSnapshot {
+        serde_json::from_value(json!({
+            "id": id,
+            "client": "proxy",
+            "target": "dev",
+            "peer": "127.0.0.1:1",
+            "intent": "check",
+            "mode": "shell",
+            "raw_command": "ls",
+            "pipeline": [],
+            "cwd": null,
+            "timeout_ms": null,
+            "max_output_bytes": null,
+            "received_at_ms": received_at_ms,
+        }))
+        .expect("request snapshot")
+    }
+
+    #[test]
+    fn summary_counts_pending_and_oldest_request() {
+        let mut state = console_state();
+        let ten_secs_ago = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64
+            - 10_000;
+        state.apply_event(
+            "dev",
+            ServiceEvent::QueueUpdated(vec![
+                queued("a", ten_secs_ago + 5_000),
+                queued("b", ten_secs_ago),
+            ]),
+        );
+        let summary = state.summary();
+        assert_eq!(summary.total_pending, 2);
+        let names: Vec<&str> = summary
+            .targets
+            .iter()
+            .map(|target| target.name.as_str())
+            .collect();
+        assert_eq!(names, ["dev", "prod"]);
+        assert_eq!(summary.targets[0].pending_count, 2);
+        assert!(summary.targets[0]
+            .oldest_pending_secs
+            .is_some_and(|secs| (10..12).contains(&secs)));
+        assert_eq!(summary.targets[1].pending_count, 0);
+        assert!(summary.targets[1].oldest_pending_secs.is_none());
+    }
+
+    #[tokio::test]
+    async fn notifier_coalesces_changes_within_interval() {
+        let state = Arc::new(RwLock::new(console_state()));
+        let (event_tx, _) = broadcast::channel(16);
+        let shutdown = CancellationToken::new();
+        spawn_summary_notifier(event_tx.clone(), Arc::clone(&state), shutdown.clone());
+
+        let queue_len = |len: usize| {
+            let state = Arc::clone(&state);
+            let event_tx = event_tx.clone();
+            async move {
+                let queue = (0..len).map(|i| queued(&format!("r{i}"), 0)).collect();
+                state
+                    .write()
+                    .await
+                    .apply_event("dev", ServiceEvent::QueueUpdated(queue));
+                let _ = event_tx.send(ConsoleEvent::RequestQueued {
+                    target: "dev".to_string(),
+                    request: queued("r0", 0),
+                });
+            }
+        };
+        let next_summary = |mut rx: broadcast::Receiver<ConsoleEvent>| async move {
+            loop {
+                if let ConsoleEvent::SummaryChanged { summary } = rx.recv().await.unwrap() {
+                    return summary;
+                }
+            }
+        };
+
+        let first = next_summary(event_tx.subscribe());
+        queue_len(1).await;
+        assert_eq!(first.await.total_pending, 1);
+
+        let started = Instant::now();
+        let second = next_summary(event_tx.subscribe());
+        queue_len(2).await;
+        queue_len(3).await;
+        assert_eq!(second.await.total_pending, 3);
+        assert!(started.elapsed() >= Duration::from_millis(500));
+        shutdown.cancel();
+    }
+}
@@ -1,31 +1,58 @@
-use crate::shell_utils::apply_ssh_base_options;
-use crate::state::TargetSpec;
+use crate::limits::{is_message_too_big, message_too_big_close};
+use crate::shell_utils::{apply_ssh_base_options, HostKeyCheck};
+use crate::state::{ControlCommand, TargetSpec};
+use crate::terminal_capture::{setup_line, CapturedCommand, CommandCapture, SETUP_TIMEOUT};
+use crate::time_utils::now_ms;
 use crate::AppState;
 use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
 use axum::extract::{Path, Query, State};
 use axum::http::StatusCode;
 use axum::response::IntoResponse;
+use axum::Json;
 use base64::engine::general_purpose::STANDARD as BASE64_ENGINE;
 use base64::Engine;
 use portable_pty::{native_pty_system, CommandBuilder, PtySize};
 use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{HashMap, VecDeque};
 use std::io::{Read, Write};
 use std::sync::mpsc as std_mpsc;
+use std::sync::{Arc, Mutex, PoisonError};
 use std::thread;
+use std::time::Duration;
 use system_utils::ssh::askpass_env;
-use tokio::sync::mpsc;
+use tokio::sync::{broadcast, mpsc, RwLock};
 use tokio::task::spawn_blocking;
+use tokio::time::{interval_at, sleep_until, Instant};
 use tracing::{info, warn};
+use uuid::Uuid;
 
 const DEFAULT_COLS: u16 = 80;
 const DEFAULT_ROWS: u16 = 24;
 const DEFAULT_TERM: &str = "xterm-256color";
+const ATTACH_CHANNEL_CAPACITY: usize = 256;
+pub(crate) const DEFAULT_SCROLLBACK_BYTES: usize = 256 * 1024;
+// 客户端崩溃时 WS 不一定会收到 Close：定期 Ping，超过 PONG_TIMEOUT 没有任何来信即视为断开。
+const PING_INTERVAL: Duration = Duration::from_secs(20);
+const PONG_TIMEOUT: Duration = Duration::from_secs(60);
+// Ctrl-U：注入前清空当前输入行，避免和会话里没敲完的内容拼成另一条命令。
+const LINE_KILL: &str = "\x15";
 
 #[derive(Debug, Deserialize)]
 pub(crate) struct TerminalQuery {
     cols: Option<u16>,
     rows: Option<u16>,
     term: Option<String>,
+    attach: Option<String>,
+    mode: Option<SessionMode>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum SessionMode {
+    Primary,
+    Readonly,
+    Rw,
 }
 
 #[derive(Debug, Deserialize)]
@@ -39,10 +66,24 @@ enum TerminalRequest {
 #[derive(Debug, Serialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 enum TerminalResponse {
-    Ready { cols: u16, rows: u16, term: String },
-    Output { data: String },
-    Exit { code: Option<i32> },
-    Error { message: String },
+    Ready {
+        cols: u16,
+        rows: u16,
+        term: String,
+        session_id: String,
+        mode: SessionMode,
+    },
+    Output {
+        data: String,
+    },
+    Exit {
+        code: Option<i32>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        reason: Option<String>,
+    },
+    Error {
+        message: String,
+    },
 }
 
 struct TerminalTarget {
@@ -51,6 +92,7 @@ struct TerminalTarget {
     ssh_args: Vec<String>,
     ssh_password: Option<String>,
     terminal_locale: Option<String>,
+    host_key: HostKeyCheck,
 }
 
 impl TerminalTarget {
@@ -65,6 +107,7 @@ impl TerminalTarget {
             ssh_args: spec.ssh_args,
             ssh_password: spec.ssh_password,
             terminal_locale: spec.terminal_locale,
+            host_key: spec.host_key,
         })
     }
 }
@@ -83,23 +126,334 @@ enum TerminalOutput {
 }
 
 enum TerminalAction {
-    Continue,
+    Input,
+    Resized { cols: u16, rows: u16 },
     Close,
 }
 
+#[derive(Clone, Serialize)]
+pub(crate) struct TerminalSessionInfo {
+    pub(crate) session_id: String,
+    pub(crate) target: String,
+    pub(crate) cols: u16,
+    pub(crate) rows: u16,
+    pub(crate) term: String,
+    pub(crate) started_at_ms: u64,
+    pub(crate) attachments: usize,
+    /// 主连接已断开、会话仍在运行，可用 `mode=primary` 重新接管。
+    pub(crate) detached: bool,
+}
+
+#[derive(Clone)]
+enum SessionBroadcast {
+    Output(Arc<[u8]>),
+    Exit {
+        code: Option<i32>,
+        reason: Option<String>,
+    },
+}
+
+enum SessionControl {
+    Resize { cols: u16, rows: u16 },
+}
+
+// 会话最近的输出，附加或重新接管时先回放；超出容量时丢弃最早的字节，容量为 0 时不保留。
+struct Scrollback {
+    data: VecDeque<u8>,
+    capacity: usize,
+}
+
+impl Scrollback {
+    fn new(capacity: usize) -> Self {
+        Self {
+            data: VecDeque::new(),
+            capacity,
+        }
+    }
+
+    fn push(&mut self, bytes: &[u8]) {
+        let bytes = &bytes[bytes.len().saturating_sub(self.capacity)..];
+        let overflow = (self.data.len() + bytes.len()).saturating_sub(self.capacity);
+        self.data.drain(..overflow);
+        self.data.extend(bytes);
+    }
+
+    fn snapshot(&self) -> Vec<u8> {
+        self.data.iter().copied().collect()
+    }
+}
+
+struct SharedTerminal {
+    info: TerminalSessionInfo,
+    broadcast_tx: broadcast::Sender<SessionBroadcast>,
+    input_tx: std_mpsc::Sender<Vec<u8>>,
+    control_tx: mpsc::UnboundedSender<SessionControl>,
+    scrollback: Arc<Mutex<Scrollback>>,
+    reattach_tx: mpsc::UnboundedSender<WebSocket>,
+}
+
+struct AttachHandle {
+    info: TerminalSessionInfo,
+    broadcast_rx: broadcast::Receiver<SessionBroadcast>,
+    input_tx: std_mpsc::Sender<Vec<u8>>,
+    control_tx: mpsc::UnboundedSender<SessionControl>,
+    scrollback: Vec<u8>,
+}
+
+// 每个主会话登记在这里，供第二位操作者以只读（或配置允许时读写）方式附加观看。
+// 配置了 max_idle 时主连接断开后会话保留到空闲超时，期间原操作者可重新接管。
+#[derive(Clone)]
+pub(crate) struct TerminalRegistry {
+    inner: Arc<RwLock<HashMap<String, SharedTerminal>>>,
+    allow_rw_attach: bool,
+    max_idle: Option<Duration>,
+    scrollback_bytes: usize,
+    capture_commands: bool,
+}
+
+impl TerminalRegistry {
+    pub(crate) fn new(
+        allow_rw_attach: bool,
+        max_idle: Option<Duration>,
+        scrollback_bytes: usize,
+        capture_commands: bool,
+    ) -> Self {
+        Self {
+            inner: Arc::new(RwLock::new(HashMap::new())),
+            allow_rw_attach,
+            max_idle,
+            scrollback_bytes,
+            capture_commands,
+        }
+    }
+
+    pub(crate) async fn list(&self, target: &str) -> Vec<TerminalSessionInfo> {
+        let guard = self.inner.read().await;
+        let mut sessions: Vec<TerminalSessionInfo> = guard
+            .values()
+            .filter(|session| session.info.target == target)
+            .map(|session| session.info.clone())
+            .collect();
+        sessions.sort_by(|a, b| a.started_at_ms.cmp(&b.started_at_ms));
+        sessions
+    }
+
+    async fn register(&self, session: SharedTerminal) {
+        let mut guard = self.inner.write().await;
+        guard.insert(session.info.session_id.clone(), session);
+    }
+
+    async fn contains(&self, session_id: &str, target: &str) -> bool {
+        let guard = self.inner.read().await;
+        guard
+            .get(session_id)
+            .map(|session| session.info.target == target)
+            .unwrap_or(false)
+    }
+
+    async fn attach(&self, session_id: &str, target: &str) -> Option<AttachHandle> {
+        let mut guard = self.inner.write().await;
+        let session = guard.get_mut(session_id)?;
+        if session.info.target != target {
+            return None;
+        }
+        session.info.attachments += 1;
+        // 会话循环在同一把锁内追加 scrollback 并广播，快照与订阅之间不会漏掉或重复输出。
+        let scrollback = session
+            .scrollback
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        Some(AttachHandle {
+            info: session.info.clone(),
+            broadcast_rx: session.broadcast_tx.subscribe(),
+            input_tx: session.input_tx.clone(),
+            control_tx: session.control_tx.clone(),
+            scrollback: scrollback.snapshot(),
+        })
+    }
+
+    async fn is_detached(&self, session_id: &str, target: &str) -> Option<bool> {
+        let guard = self.inner.read().await;
+        guard
+            .get(session_id)
+            .filter(|session| session.info.target == target)
+            .map(|session| session.info.detached)
+    }
+
+    async fn set_detached(&self, session_id: &str, detached: bool) {
+        let mut guard = self.inner.write().await;
+        if let Some(session) = guard.get_mut(session_id) {
+            session.info.detached = detached;
+        }
+    }
+
+    // 只有已脱离主连接的会话能被接管；先清掉 detached，并发的两次接管只有一次成功。
+    async fn reclaim(
+        &self,
+        session_id: &str,
+        target: &str,
+    ) -> Option<mpsc::UnboundedSender<WebSocket>> {
+        let mut guard = self.inner.write().await;
+        let session = guard
+            .get_mut(session_id)
+            .filter(|session| session.info.target == target && session.info.detached)?;
+        session.info.detached = false;
+        Some(session.reattach_tx.clone())
+    }
+
+    async fn input_sender(
+        &self,
+        session_id: &str,
+        target: &str,
+    ) -> Option<std_mpsc::Sender<Vec<u8>>> {
+        let guard = self.inner.read().await;
+        guard
+            .get(session_id)
+            .filter(|session| session.info.target == target)
+            .map(|session| session.input_tx.clone())
+    }
+
+    async fn detach(&self, session_id: &str) {
+        let mut guard = self.inner.write().await;
+        if let Some(session) = guard.get_mut(session_id) {
+            session.info.attachments = session.info.attachments.saturating_sub(1);
+        }
+    }
+
+    async fn update_size(&self, session_id: &str, cols: u16, rows: u16) {
+        let mut guard = self.inner.write().await;
+        if let Some(session) = guard.get_mut(session_id) {
+            session.info.cols = cols;
+            session.info.rows = rows;
+        }
+    }
+
+    async fn remove(&self, session_id: &str) {
+        let mut guard = self.inner.write().await;
+        guard.remove(session_id);
+    }
+}
+
+pub(crate) async fn list_terminal_sessions(
+    Path(name): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Json<Vec<TerminalSessionInfo>>, StatusCode> {
+    if state.state.read().await.target_spec(&name).is_none() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    Ok(Json(state.terminals.list(&name).await))
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct InjectPayload {
+    command: String,
+    #[serde(default)]
+    actor: Option<String>,
+}
+
+/// 把一条通过 readonly_whitelist 校验的命令写入已有终端会话；不通过时返回 403 与原因，目标维护中返回 409。
+/// 先发 Ctrl-U 清掉提示符后可能残留的半行输入，再写命令与换行；每次注入都以终端命令记入目标历史。
+pub(crate) async fn inject_terminal_command(
+    Path((name, session_id)): Path<(String, String)>,
+    State(state): State<AppState>,
+    Json(payload): Json<InjectPayload>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    let error = |status: StatusCode, error: &str| (status, Json(json!({ "error": error })));
+    let Some(input_tx) = state.terminals.input_sender(&session_id, &name).await else {
+        return Err(error(StatusCode::NOT_FOUND, "terminal session not found"));
+    };
+    let command_tx = {
+        let guard = state.state.read().await;
+        if guard.maintenance_message(&name).is_some() {
+            return Err(error(StatusCode::CONFLICT, "target in maintenance"));
+        }
+        guard.command_sender(&name)
+    };
+    let command = payload.command.trim();
+    let actor = crate::decision_actor(payload.actor);
+    if let Some(reason) = state.intake.readonly_denial(command) {
+        warn!(
+            event = "terminal.inject_denied",
+            target = %name,
+            session_id = %session_id,
+            actor = %actor,
+            command = %command,
+            reason = %reason,
+        );
+        return Err(error(StatusCode::FORBIDDEN, &reason));
+    }
+    if input_tx
+        .send(format!("{LINE_KILL}{command}\n").into_bytes())
+        .is_err()
+    {
+        return Err(error(StatusCode::GONE, "terminal session closed"));
+    }
+    info!(
+        event = "terminal.inject",
+        target = %name,
+        session_id = %session_id,
+        actor = %actor,
+        command = %command,
+    );
+    let injected_at_ms = now_ms();
+    record_terminal_command(
+        command_tx.as_ref(),
+        &name,
+        CapturedCommand {
+            session_id,
+            command: command.to_string(),
+            exit_code: None,
+            started_at_ms: injected_at_ms,
+            finished_at_ms: injected_at_ms,
+            injected_by: Some(actor),
+        },
+    );
+    Ok(Json(json!({ "message": "command injected" })))
+}
+
 pub(crate) async fn terminal_ws_handler(
     ws: WebSocketUpgrade,
     Path(name): Path<String>,
     Query(query): Query<TerminalQuery>,
     State(state): State<AppState>,
 ) -> impl IntoResponse {
-    let spec = {
+    let (spec, command_tx) = {
         let guard = state.state.read().await;
-        guard.target_spec(&name)
+        (guard.target_spec(&name), guard.command_sender(&name))
     };
     let Some(spec) = spec else {
         return StatusCode::NOT_FOUND.into_response();
     };
+    let registry = state.terminals.clone();
+    // 命令边界靠 bash/zsh 钩子识别，Windows 目标不尝试。
+    let capture_tx = command_tx.filter(|_| registry.capture_commands && !spec.shell.is_windows());
+    let max_message = state.limits.max_ws_message_bytes;
+    let ws = ws.max_message_size(max_message).max_frame_size(max_message);
+
+    if let Some(session_id) = query.attach {
+        let mode = query.mode.unwrap_or(SessionMode::Readonly);
+        match mode {
+            SessionMode::Primary => {
+                return match registry.is_detached(&session_id, &name).await {
+                    None => StatusCode::NOT_FOUND.into_response(),
+                    Some(false) => StatusCode::CONFLICT.into_response(),
+                    Some(true) => ws.on_upgrade(move |socket| {
+                        handle_reattach(socket, registry, name, session_id)
+                    }),
+                };
+            }
+            SessionMode::Rw if !registry.allow_rw_attach => {
+                return StatusCode::FORBIDDEN.into_response();
+            }
+            _ => {}
+        }
+        if !registry.contains(&session_id, &name).await {
+            return StatusCode::NOT_FOUND.into_response();
+        }
+        return ws
+            .on_upgrade(move |socket| handle_attachment(socket, registry, name, session_id, mode));
+    }
+
     let Some(target) = TerminalTarget::from_spec(spec) else {
         return StatusCode::BAD_REQUEST.into_response();
     };
@@ -112,10 +466,16 @@ pub(crate) async fn terminal_ws_handler(
         .unwrap_or_else(|| DEFAULT_TERM.to_string());
     let config = TerminalConfig { cols, rows, term };
 
-    ws.on_upgrade(move |socket| handle_terminal(socket, target, config))
+    ws.on_upgrade(move |socket| handle_terminal(socket, target, config, registry, capture_tx))
 }
 
-async fn handle_terminal(mut socket: WebSocket, target: TerminalTarget, config: TerminalConfig) {
+async fn handle_terminal(
+    mut socket: WebSocket,
+    target: TerminalTarget,
+    config: TerminalConfig,
+    registry: TerminalRegistry,
+    capture_tx: Option<mpsc::Sender<ControlCommand>>,
+) {
     let pair = match native_pty_system().openpty(PtySize {
         rows: config.rows,
         cols: config.cols,
@@ -137,7 +497,7 @@ async fn handle_terminal(mut socket: WebSocket, target: TerminalTarget, config:
 
     let mut cmd = CommandBuilder::new("ssh");
     apply_locale_env(&mut cmd, target.terminal_locale.as_deref());
-    apply_ssh_base_options(&mut cmd);
+    apply_ssh_base_options(&mut cmd, &target.host_key);
     for arg in &target.ssh_args {
         cmd.arg(arg);
     }
@@ -174,6 +534,11 @@ async fn handle_terminal(mut socket: WebSocket, target: TerminalTarget, config:
     let mut master = pair.master;
     let (output_tx, mut output_rx) = mpsc::unbounded_channel::<TerminalOutput>();
     let (input_tx, input_rx) = std_mpsc::channel::<Vec<u8>>();
+    let (control_tx, mut control_rx) = mpsc::unbounded_channel::<SessionControl>();
+    let (reattach_tx, mut reattach_rx) = mpsc::unbounded_channel::<WebSocket>();
+    let (broadcast_tx, _) = broadcast::channel::<SessionBroadcast>(ATTACH_CHANNEL_CAPACITY);
+    let scrollback = Arc::new(Mutex::new(Scrollback::new(registry.scrollback_bytes)));
+    let session_id = Uuid::new_v4().to_string();
 
     if let Ok(reader) = master.try_clone_reader() {
         let output_tx = output_tx.clone();
@@ -196,29 +561,85 @@ async fn handle_terminal(mut socket: WebSocket, target: TerminalTarget, config:
         }
     }
 
+    // 钩子注入行写在 shell 启动前，shell 就绪后读到；就绪标记出现前的输出暂不转发。
+    let mut capture = capture_tx.as_ref().map(|_| {
+        let (line, nonce) = setup_line();
+        let _ = input_tx.send(format!("{line}\n").into_bytes());
+        CommandCapture::new(session_id.clone(), &nonce)
+    });
+    let capture_deadline = Instant::now() + SETUP_TIMEOUT;
+
+    let mut killer = child.clone_killer();
     let (exit_tx, mut exit_rx) = tokio::sync::oneshot::channel();
     spawn_blocking(move || {
         let _ = child.wait();
         let _ = exit_tx.send(None);
     });
 
+    registry
+        .register(SharedTerminal {
+            info: TerminalSessionInfo {
+                session_id: session_id.clone(),
+                target: target.name.clone(),
+                cols: config.cols,
+                rows: config.rows,
+                term: config.term.clone(),
+                started_at_ms: now_ms(),
+                attachments: 0,
+                detached: false,
+            },
+            broadcast_tx: broadcast_tx.clone(),
+            input_tx: input_tx.clone(),
+            control_tx,
+            scrollback: scrollback.clone(),
+            reattach_tx,
+        })
+        .await;
+
     let _ = send_response(
         &mut socket,
         TerminalResponse::Ready {
             cols: config.cols,
             rows: config.rows,
             term: config.term.clone(),
+            session_id: session_id.clone(),
+            mode: SessionMode::Primary,
         },
     )
     .await;
-    info!(target = %target.name, "terminal session started");
+    info!(target = %target.name, session_id = %session_id, "terminal session started");
 
+    // 主连接断开后置为 None；没有配置 max_idle 时不保留会话，直接结束。
+    let mut primary = Some(socket);
+    let (mut cols, mut rows) = (config.cols, config.rows);
+    let mut exit_code = None;
+    let mut exit_reason = None;
+    let mut child_exited = false;
+    let mut ping = interval_at(Instant::now() + PING_INTERVAL, PING_INTERVAL);
+    let mut last_heard = Instant::now();
+    // 空闲：既无输入也无输出；脱离主连接后无论是否还有输出，最多再保留 max_idle。
+    let mut last_activity = Instant::now();
+    let mut detached_at: Option<Instant> = None;
     loop {
+        let idle_deadline = registry.max_idle.map(|max_idle| {
+            let deadline = last_activity + max_idle;
+            detached_at.map_or(deadline, |at| deadline.min(at + max_idle))
+        });
+        let mut primary_lost = false;
+        let setup_deadline = capture
+            .as_ref()
+            .filter(|capture| capture.is_pending_setup())
+            .map(|_| capture_deadline);
         tokio::select! {
-            msg = socket.recv() => {
+            msg = recv_primary(&mut primary) => {
+                last_heard = Instant::now();
                 match msg {
                     Some(Ok(Message::Text(text))) => match handle_request(&text, &input_tx, &mut master) {
-                        Ok(TerminalAction::Continue) => {}
+                        Ok(TerminalAction::Input) => last_activity = Instant::now(),
+                        Ok(TerminalAction::Resized { cols: new_cols, rows: new_rows }) => {
+                            (cols, rows) = (new_cols, new_rows);
+                            registry.update_size(&session_id, cols, rows).await;
+                        }
                         Ok(TerminalAction::Close) => break,
                         Err(err) => {
                             warn!(target = %target.name, error = %err, "terminal request error");
@@ -226,40 +647,285 @@ async fn handle_terminal(mut socket: WebSocket, target: TerminalTarget, config:
                     },
                     Some(Ok(Message::Binary(_))) => {}
                     Some(Ok(Message::Ping(_))) | Some(Ok(Message::Pong(_))) => {}
-                    Some(Ok(Message::Close(_))) | None => break,
+                    Some(Ok(Message::Close(_))) | None => primary_lost = true,
                     Some(Err(err)) => {
                         warn!(target = %target.name, error = %err, "terminal websocket error");
-                        break;
+                        if is_message_too_big(&err) {
+                            if let Some(socket) = primary.as_mut() {
+                                let _ = socket.send(message_too_big_close()).await;
+                            }
+                        }
+                        primary_lost = true;
                     }
                 }
             }
+            Some(mut socket) = reattach_rx.recv() => {
+                let replay = scrollback.lock().unwrap_or_else(PoisonError::into_inner).snapshot();
+                let ready = TerminalResponse::Ready {
+                    cols,
+                    rows,
+                    term: config.term.clone(),
+                    session_id: session_id.clone(),
+                    mode: SessionMode::Primary,
+                };
+                if send_response(&mut socket, ready).await.is_ok()
+                    && (replay.is_empty()
+                        || send_response(&mut socket, TerminalResponse::Output { data: BASE64_ENGINE.encode(&replay) }).await.is_ok())
+                {
+                    primary = Some(socket);
+                    detached_at = None;
+                    last_heard = Instant::now();
+                    info!(target = %target.name, session_id = %session_id, replay_bytes = replay.len(), "terminal session reattached");
+                } else {
+                    registry.set_detached(&session_id, true).await;
+                }
+            }
             Some(output) = output_rx.recv() => {
                 match output {
                     TerminalOutput::Data(bytes) => {
-                        let response = TerminalResponse::Output { data: BASE64_ENGINE.encode(bytes) };
-                        if send_response(&mut socket, response).await.is_err() {
-                            break;
+                        last_activity = Instant::now();
+                        let bytes = match capture.as_mut() {
+                            Some(capture) => {
+                                let (visible, commands) = capture.feed(&bytes, now_ms());
+                                for command in commands {
+                                    record_terminal_command(capture_tx.as_ref(), &target.name, command);
+                                }
+                                visible
+                            }
+                            None => bytes,
+                        };
+                        if !bytes.is_empty() && forward_output(bytes, &scrollback, &broadcast_tx, &mut primary).await.is_err() {
+                            primary_lost = true;
                         }
                     }
                     TerminalOutput::Exit(code) => {
-                        let _ = send_response(&mut socket, TerminalResponse::Exit { code }).await;
+                        exit_code = code;
+                        let _ = send_primary(&mut primary, TerminalResponse::Exit { code, reason: None }).await;
                         break;
                     }
                     TerminalOutput::Error(message) => {
-                        let _ = send_response(&mut socket, TerminalResponse::Error { message }).await;
+                        let _ = send_primary(&mut primary, TerminalResponse::Error { message }).await;
                         break;
                     }
                 }
             }
+            Some(control) = control_rx.recv() => {
+                match control {
+                    SessionControl::Resize { cols: new_cols, rows: new_rows } => {
+                        if let Err(err) = resize_pty(&mut master, new_cols, new_rows) {
+                            warn!(target = %target.name, error = %err, "terminal resize failed");
+                        } else {
+                            (cols, rows) = (new_cols, new_rows);
+                            registry.update_size(&session_id, cols, rows).await;
+                        }
+                    }
+                }
+            }
             code = &mut exit_rx => {
-                let response = TerminalResponse::Exit { code: code.ok().flatten() };
-                let _ = send_response(&mut socket, response).await;
+                child_exited = true;
+                exit_code = code.ok().flatten();
+                let response = TerminalResponse::Exit { code: exit_code, reason: None };
+                let _ = send_primary(&mut primary, response).await;
                 break;
             }
+            _ = ping.tick() => {
+                if let Some(socket) = primary.as_mut() {
+                    if last_heard.elapsed() >= PONG_TIMEOUT {
+                        warn!(target = %target.name, session_id = %session_id, "terminal client stopped answering pings");
+                        primary_lost = true;
+                    } else if socket.send(Message::Ping(Vec::new())).await.is_err() {
+                        primary_lost = true;
+                    }
+                }
+            }
+            _ = sleep_until_deadline(setup_deadline) => {
+                if let Some(capture) = capture.as_mut() {
+                    warn!(target = %target.name, session_id = %session_id, "terminal command capture hooks did not report ready");
+                    let held = capture.abandon();
+                    if !held.is_empty() && forward_output(held, &scrollback, &broadcast_tx, &mut primary).await.is_err() {
+                        primary_lost = true;
+                    }
+                }
+            }
+            _ = sleep_until_deadline(idle_deadline) => {
+                let idle_secs = registry.max_idle.map(|max_idle| max_idle.as_secs()).unwrap_or(0);
+                let reason = format!("closed after {idle_secs}s without input or output");
+                info!(target = %target.name, session_id = %session_id, idle_secs, "terminal session idle timeout");
+                let response = TerminalResponse::Exit { code: None, reason: Some(reason.clone()) };
+                let _ = send_primary(&mut primary, response).await;
+                exit_reason = Some(reason);
+                break;
+            }
+        }
+        if primary_lost {
+            if registry.max_idle.is_none() {
+                break;
+            }
+            primary = None;
+            detached_at = Some(Instant::now());
+            registry.set_detached(&session_id, true).await;
+            info!(target = %target.name, session_id = %session_id, "terminal primary disconnected, session kept for reattach");
+        }
+    }
+
+    // 断开（Close/超时/空闲）时远端 ssh 仍在运行，主动结束，避免遗留 PTY。
+    if !child_exited {
+        if let Err(err) = killer.kill() {
+            warn!(target = %target.name, session_id = %session_id, error = %err, "failed to kill terminal process");
+        }
+    }
+    registry.remove(&session_id).await;
+    let _ = broadcast_tx.send(SessionBroadcast::Exit {
+        code: exit_code,
+        reason: exit_reason,
+    });
+    info!(target = %target.name, session_id = %session_id, "terminal session closed");
+}
+
+// 把新连接交给仍在运行的会话循环，由它回放 scrollback 并继续作为主连接。
+async fn handle_reattach(
+    mut socket: WebSocket,
+    registry: TerminalRegistry,
+    target: String,
+    session_id: String,
+) {
+    let Some(reattach_tx) = registry.reclaim(&session_id, &target).await else {
+        let _ = send_response(
+            &mut socket,
+            TerminalResponse::Error {
+                message: format!("terminal session not detached: {session_id}"),
+            },
+        )
+        .await;
+        return;
+    };
+    if let Err(mpsc::error::SendError(mut socket)) = reattach_tx.send(socket) {
+        let _ = send_response(
+            &mut socket,
+            TerminalResponse::Error {
+                message: format!("terminal session closed: {session_id}"),
+            },
+        )
+        .await;
+    }
+}
+
+async fn handle_attachment(
+    mut socket: WebSocket,
+    registry: TerminalRegistry,
+    target: String,
+    session_id: String,
+    mode: SessionMode,
+) {
+    let Some(handle) = registry.attach(&session_id, &target).await else {
+        let _ = send_response(
+            &mut socket,
+            TerminalResponse::Error {
+                message: format!("terminal session not found: {session_id}"),
+            },
+        )
+        .await;
+        return;
+    };
+    let AttachHandle {
+        info,
+        mut broadcast_rx,
+        input_tx,
+        control_tx,
+        scrollback,
+    } = handle;
+    let writable = mode == SessionMode::Rw;
+
+    let _ = send_response(
+        &mut socket,
+        TerminalResponse::Ready {
+            cols: info.cols,
+            rows: info.rows,
+            term: info.term.clone(),
+            session_id: session_id.clone(),
+            mode,
+        },
+    )
+    .await;
+    if !scrollback.is_empty() {
+        let replay = TerminalResponse::Output {
+            data: BASE64_ENGINE.encode(&scrollback),
+        };
+        let _ = send_response(&mut socket, replay).await;
+    }
+    info!(target = %target, session_id = %session_id, mode = ?mode, "terminal attached");
+
+    loop {
+        tokio::select! {
+            msg = socket.recv() => {
+                match msg {
+                    Some(Ok(Message::Text(text))) => {
+                        let request = match serde_json::from_str::<TerminalRequest>(&text) {
+                            Ok(request) => request,
+                            Err(err) => {
+                                warn!(target = %target, error = %err, "terminal request error");
+                                continue;
+                            }
+                        };
+                        match request {
+                            TerminalRequest::Close => break,
+                            _ if !writable => {
+                                warn!(target = %target, session_id = %session_id, "ignored input from read-only attachment");
+                            }
+                            TerminalRequest::Input { data } => match BASE64_ENGINE.decode(data) {
+                                Ok(bytes) => {
+                                    let _ = input_tx.send(bytes);
+                                }
+                                Err(err) => {
+                                    warn!(target = %target, error = %err, "terminal request error");
+                                }
+                            },
+                            TerminalRequest::Resize { cols, rows } => {
+                                let _ = control_tx.send(SessionControl::Resize {
+                                    cols: cols.max(1),
+                                    rows: rows.max(1),
+                                });
+                            }
+                        }
+                    }
+                    Some(Ok(Message::Binary(_))) => {}
+                    Some(Ok(Message::Ping(_))) | Some(Ok(Message::Pong(_))) => {}
+                    Some(Ok(Message::Close(_))) | None => break,
+                    Some(Err(err)) => {
+                        warn!(target = %target, error = %err, "terminal websocket error");
+                        if is_message_too_big(&err) {
+                            let _ = socket.send(message_too_big_close()).await;
+                        }
+                        break;
+                    }
+                }
+            }
+            event = broadcast_rx.recv() => {
+                match event {
+                    Ok(SessionBroadcast::Output(bytes)) => {
+                        let response = TerminalResponse::Output { data: BASE64_ENGINE.encode(&bytes) };
+                        if send_response(&mut socket, response).await.is_err() {
+                            break;
+                        }
+                    }
+                    Ok(SessionBroadcast::Exit { code, reason }) => {
+                        let _ = send_response(&mut socket, TerminalResponse::Exit { code, reason }).await;
+                        break;
+                    }
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!(target = %target, session_id = %session_id, skipped, "terminal attachment lagged");
+                    }
+                    Err(broadcast::error::RecvError::Closed) => {
+                        let _ = send_response(&mut socket, TerminalResponse::Exit { code: None, reason: None }).await;
+                        break;
+                    }
+                }
+            }
         }
     }
 
-    info!(target = %target.name, "terminal session closed");
+    registry.detach(&session_id).await;
+    info!(target = %target, session_id = %session_id, "terminal detached");
 }
 
 fn handle_request(
@@ -272,22 +938,74 @@ fn handle_request(
         TerminalRequest::Input { data } => {
             let bytes = BASE64_ENGINE.decode(data)?;
             let _ = input_tx.send(bytes);
+            Ok(TerminalAction::Input)
         }
         TerminalRequest::Resize { cols, rows } => {
             let cols = cols.max(1);
             let rows = rows.max(1);
-            master.resize(PtySize {
-                rows,
-                cols,
-                pixel_width: 0,
-                pixel_height: 0,
-            })?;
-        }
-        TerminalRequest::Close => {
-            return Ok(TerminalAction::Close);
+            resize_pty(master, cols, rows)?;
+            Ok(TerminalAction::Resized { cols, rows })
         }
+        TerminalRequest::Close => Ok(TerminalAction::Close),
+    }
+}
+
+async fn sleep_until_deadline(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => sleep_until(deadline).await,
+        None => std::future::pending().await,
     }
-    Ok(TerminalAction::Continue)
+}
+
+// 输出先进 scrollback 再广播，与 attach 时的快照在同一把锁内，接着发给主连接。
+async fn forward_output(
+    bytes: Vec<u8>,
+    scrollback: &Mutex<Scrollback>,
+    broadcast_tx: &broadcast::Sender<SessionBroadcast>,
+    primary: &mut Option<WebSocket>,
+) -> Result<(), axum::Error> {
+    let response = TerminalResponse::Output {
+        data: BASE64_ENGINE.encode(&bytes),
+    };
+    {
+        let mut scrollback = scrollback.lock().unwrap_or_else(PoisonError::into_inner);
+        scrollback.push(&bytes);
+        let _ = broadcast_tx.send(SessionBroadcast::Output(Arc::from(bytes)));
+    }
+    send_primary(primary, response).await
+}
+
+fn record_terminal_command(
+    capture_tx: Option<&mpsc::Sender<ControlCommand>>,
+    target: &str,
+    command: CapturedCommand,
+) {
+    let Some(capture_tx) = capture_tx else {
+        return;
+    };
+    if let Err(err) = capture_tx.try_send(ControlCommand::RecordTerminalCommand(command)) {
+        warn!(target = %target, error = %err, "failed to record terminal command");
+    }
+}
+
+async fn recv_primary(primary: &mut Option<WebSocket>) -> Option<Result<Message, axum::Error>> {
+    match primary {
+        Some(socket) => socket.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+fn resize_pty(
+    master: &mut Box<dyn portable_pty::MasterPty + Send>,
+    cols: u16,
+    rows: u16,
+) -> anyhow::Result<()> {
+    master.resize(PtySize {
+        rows,
+        cols,
+        pixel_width: 0,
+        pixel_height: 0,
+    })
 }
 
 fn read_pty_loop(
@@ -340,6 +1058,16 @@ async fn send_response(
     socket.send(Message::Text(payload)).await
 }
 
+async fn send_primary(
+    primary: &mut Option<WebSocket>,
+    response: TerminalResponse,
+) -> Result<(), axum::Error> {
+    match primary {
+        Some(socket) => send_response(socket, response).await,
+        None => Ok(()),
+    }
+}
+
 fn configure_askpass(cmd: &mut CommandBuilder, password: &str) -> anyhow::Result<()> {
     for (key, value) in askpass_env(password)? {
         cmd.env(key, value);
@@ -406,3 +1134,22 @@ fn is_utf8_locale(value: &str) -> bool {
     let lower = value.to_ascii_lowercase();
     lower.contains("utf-8") || lower.contains("utf8")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scrollback_keeps_only_the_latest_bytes() {
+        let mut scrollback = Scrollback::new(8);
+        scrollback.push(b"hello");
+        scrollback.push(b" world");
+        assert_eq!(scrollback.snapshot(), b"lo world");
+        scrollback.push(b"0123456789");
+        assert_eq!(scrollback.snapshot(), b"23456789");
+
+        let mut disabled = Scrollback::new(0);
+        disabled.push(b"ignored");
+        assert!(disabled.snapshot().is_empty());
+    }
+}
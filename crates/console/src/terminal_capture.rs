@@ -0,0 +1,349 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+use uuid::Uuid;
+
+pub(crate) const TERMINAL_SOURCE: &str = "terminal";
+const TERMINAL_INTENT: &str = "interactive terminal command";
+const TERMINAL_INJECT_INTENT: &str = "command injected into terminal by operator";
+/// 钩子在该时间内没有就绪（shell 不是 bash/zsh 或注入失败）即放弃记录，暂存的输出原样交还。
+pub(crate) const SETUP_TIMEOUT: Duration = Duration::from_secs(5);
+
+// 与执行器的 PTY 标记相同：分段输出，回显出来的注入行不会被当成就绪标记。
+const READY_PREFIX: &str = "__OCTOVALVE_TERM_READY_";
+// 命令边界用私有 OSC 序列上报，转发给客户端前去掉。序列里带每个会话随机的 nonce，
+// 远端输出（cat 一个文件、motd 等）伪造的标记没有 nonce，按普通输出转发且不记录。
+const OSC_PREFIX: &str = "\x1b]6973;";
+const OSC_END: u8 = 0x07;
+const MAX_OSC_BYTES: usize = 8 * 1024;
+const MAX_HELD_BYTES: usize = 1024 * 1024;
+
+// bash 用 PS0/PROMPT_COMMAND，zsh 用 preexec/precmd；序号不变（空行、未进历史的命令）时不记录。
+// nonce 放在未导出的 __ov_k 里，子进程看不到。其他 shell 里 eval 失败被静默，仍会打印就绪标记。
+const SETUP_SCRIPT: &str = concat!(
+    "if [ -n \"$BASH_VERSION\" ]; then ",
+    "__ov_prompt() { local s=$? n c; read -r n c <<< \"$(HISTTIMEFORMAT= history 1)\"; ",
+    "printf \"\\033]6973;%s;E;%s;%s;%s\\007\" \"$__ov_k\" \"$s\" \"$n\" \"${c//[[:cntrl:]]/ }\"; return $s; }; ",
+    "PS0=\"${PS0}\\e]6973;${__ov_k};B\\a\"; ",
+    "PROMPT_COMMAND=\"__ov_prompt${PROMPT_COMMAND:+;$PROMPT_COMMAND}\"; ",
+    "elif [ -n \"$ZSH_VERSION\" ]; then ",
+    "__ov_n=0; __ov_c=; ",
+    "__ov_preexec() { __ov_n=$((__ov_n+1)); __ov_c=$1; printf \"\\033]6973;%s;B\\007\" \"$__ov_k\"; }; ",
+    "__ov_precmd() { local s=$?; ",
+    "printf \"\\033]6973;%s;E;%s;%s;%s\\007\" \"$__ov_k\" \"$s\" \"$__ov_n\" \"${__ov_c//[[:cntrl:]]/ }\"; return $s; }; ",
+    "preexec_functions=(__ov_preexec $preexec_functions); ",
+    "precmd_functions=(__ov_precmd $precmd_functions); ",
+    "fi",
+);
+
+// shell 在执行前就把读到的行写进历史，所以关闭历史要单独占第一行：bash 用 `set +o history`（记下原先是否开启），
+// zsh 用 `fc -p` 换上不落盘的临时历史。带 nonce 的后两行都在关闭期间读入，不会进入历史文件；
+// 最后一行恢复历史后才打印就绪标记，之前的回显与钩子输出都被暂存丢弃。
+const HISTORY_OFF: &str = concat!(
+    " if [ -n \"$ZSH_VERSION\" ]; then fc -p; ",
+    "else __ov_h=; [[ -o history ]] && __ov_h=1; set +o history; fi 2>/dev/null",
+);
+const HISTORY_RESTORE: &str = concat!(
+    "if [ -n \"$ZSH_VERSION\" ]; then fc -P; ",
+    "else [ -n \"$__ov_h\" ] && set -o history; unset __ov_h; fi 2>/dev/null",
+);
+
+/// 生成本会话的 nonce，返回写入 PTY 的输入（三行，不含末尾换行）与该 nonce。
+pub(crate) fn setup_line() -> (String, String) {
+    let nonce = Uuid::new_v4().simple().to_string();
+    let line = format!(
+        "{HISTORY_OFF}\n __ov_k={nonce}; eval '{SETUP_SCRIPT}' 2>/dev/null\n {HISTORY_RESTORE}; printf '%s%s\\n' '__OCTOVALVE_TERM' '_READY_{nonce}__'"
+    );
+    (line, nonce)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct CapturedCommand {
+    pub(crate) session_id: String,
+    pub(crate) command: String,
+    pub(crate) exit_code: Option<i32>,
+    pub(crate) started_at_ms: u64,
+    pub(crate) finished_at_ms: u64,
+    /// 通过 inject 接口写入会话的命令记下操作员；此时没有退出码，命令本身仍可能另被钩子记录一次。
+    pub(crate) injected_by: Option<String>,
+}
+
+impl CapturedCommand {
+    pub(crate) fn metadata(&self) -> BTreeMap<String, String> {
+        let mut metadata =
+            BTreeMap::from([("terminal_session".to_string(), self.session_id.clone())]);
+        if let Some(actor) = &self.injected_by {
+            metadata.insert("injected_by".to_string(), actor.clone());
+        }
+        metadata
+    }
+
+    pub(crate) fn intent(&self) -> &'static str {
+        if self.injected_by.is_some() {
+            TERMINAL_INJECT_INTENT
+        } else {
+            TERMINAL_INTENT
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    /// 等待就绪标记，期间的输出（含回显的注入行）暂存。
+    Setup,
+    Active,
+    Disabled,
+}
+
+/// 从终端输出里识别命令边界，并把标记从转发给客户端的字节中去掉。
+pub(crate) struct CommandCapture {
+    session_id: String,
+    ready_marker: Vec<u8>,
+    osc_prefix: Vec<u8>,
+    phase: Phase,
+    held: Vec<u8>,
+    pending: Vec<u8>,
+    last_seq: Option<String>,
+    started_at_ms: Option<u64>,
+    prompt_at_ms: u64,
+}
+
+impl CommandCapture {
+    pub(crate) fn new(session_id: String, nonce: &str) -> Self {
+        Self {
+            session_id,
+            ready_marker: format!("{READY_PREFIX}{nonce}__").into_bytes(),
+            osc_prefix: format!("{OSC_PREFIX}{nonce};").into_bytes(),
+            phase: Phase::Setup,
+            held: Vec::new(),
+            pending: Vec::new(),
+            last_seq: None,
+            started_at_ms: None,
+            prompt_at_ms: 0,
+        }
+    }
+
+    pub(crate) fn is_pending_setup(&self) -> bool {
+        self.phase == Phase::Setup
+    }
+
+    /// 放弃记录：之后的输出原样透传，返回暂存的输出。
+    pub(crate) fn abandon(&mut self) -> Vec<u8> {
+        self.phase = Phase::Disabled;
+        let mut held = std::mem::take(&mut self.held);
+        held.append(&mut self.pending);
+        held
+    }
+
+    /// 返回应转发给客户端的字节，以及这段输出里结束的命令。
+    pub(crate) fn feed(&mut self, bytes: &[u8], now_ms: u64) -> (Vec<u8>, Vec<CapturedCommand>) {
+        match self.phase {
+            Phase::Disabled => (bytes.to_vec(), Vec::new()),
+            Phase::Setup => {
+                self.held.extend_from_slice(bytes);
+                let Some(pos) = find_subsequence(&self.held, &self.ready_marker) else {
+                    if self.held.len() > MAX_HELD_BYTES {
+                        return (self.abandon(), Vec::new());
+                    }
+                    return (Vec::new(), Vec::new());
+                };
+                let mut start = pos + self.ready_marker.len();
+                if self.held.get(start) == Some(&b'\r') {
+                    start += 1;
+                }
+                if self.held.get(start) == Some(&b'\n') {
+                    start += 1;
+                }
+                let rest = self.held.split_off(start);
+                self.held.clear();
+                self.phase = Phase::Active;
+                self.prompt_at_ms = now_ms;
+                self.scan(&rest, now_ms)
+            }
+            Phase::Active => self.scan(bytes, now_ms),
+        }
+    }
+
+    fn scan(&mut self, bytes: &[u8], now_ms: u64) -> (Vec<u8>, Vec<CapturedCommand>) {
+        let mut data = std::mem::take(&mut self.pending);
+        data.extend_from_slice(bytes);
+        let mut visible = Vec::with_capacity(data.len());
+        let mut commands = Vec::new();
+        let mut rest = data.as_slice();
+        loop {
+            let Some(pos) = find_subsequence(rest, &self.osc_prefix) else {
+                // 末尾可能是被切开的序列开头，留到下一段再判断。
+                let keep = partial_prefix_len(rest, &self.osc_prefix);
+                visible.extend_from_slice(&rest[..rest.len() - keep]);
+                self.pending = rest[rest.len() - keep..].to_vec();
+                break;
+            };
+            visible.extend_from_slice(&rest[..pos]);
+            let body = &rest[pos + self.osc_prefix.len()..];
+            match body.iter().position(|byte| *byte == OSC_END) {
+                Some(end) => {
+                    if let Some(command) = self.handle_marker(&body[..end], now_ms) {
+                        commands.push(command);
+                    }
+                    rest = &body[end + 1..];
+                }
+                None if body.len() > MAX_OSC_BYTES => {
+                    visible.extend_from_slice(&rest[pos..]);
+                    break;
+                }
+                None => {
+                    self.pending = rest[pos..].to_vec();
+                    break;
+                }
+            }
+        }
+        (visible, commands)
+    }
+
+    fn handle_marker(&mut self, payload: &[u8], now_ms: u64) -> Option<CapturedCommand> {
+        let payload = String::from_utf8_lossy(payload);
+        if payload == "B" {
+            self.started_at_ms = Some(now_ms);
+            return None;
+        }
+        let mut parts = payload.strip_prefix("E;")?.splitn(3, ';');
+        let status = parts.next()?;
+        let seq = parts.next()?.to_string();
+        let command = parts.next().unwrap_or_default().trim().to_string();
+        let started_at_ms = self.started_at_ms.take().unwrap_or(self.prompt_at_ms);
+        self.prompt_at_ms = now_ms;
+        // 第一次提示符只记下当前序号。
+        let previous = self.last_seq.replace(seq.clone())?;
+        if previous == seq || command.is_empty() {
+            return None;
+        }
+        Some(CapturedCommand {
+            session_id: self.session_id.clone(),
+            command,
+            exit_code: status.parse().ok(),
+            started_at_ms,
+            finished_at_ms: now_ms,
+            injected_by: None,
+        })
+    }
+}
+
+fn find_subsequence(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+fn partial_prefix_len(data: &[u8], prefix: &[u8]) -> usize {
+    (1..prefix.len().min(data.len() + 1))
+        .rev()
+        .find(|len| data.ends_with(&prefix[..*len]))
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NONCE: &str = "k1";
+
+    fn ready(capture: &mut CommandCapture) {
+        let (visible, _) = capture.feed(b"motd\r\n eval ... '_READY_k1__'\r\n", 0);
+        assert!(visible.is_empty());
+        let (visible, _) = capture.feed(b"__OCTOVALVE_TERM_READY_k1__\r\n", 0);
+        assert!(visible.is_empty());
+    }
+
+    #[test]
+    fn records_commands_between_prompts_and_strips_markers() {
+        let mut capture = CommandCapture::new("s1".to_string(), NONCE);
+        ready(&mut capture);
+        let (visible, commands) = capture.feed(b"\x1b]6973;k1;E;0;41;\x07$ ", 10);
+        assert_eq!(visible, b"$ ");
+        assert!(commands.is_empty());
+
+        let (visible, commands) = capture.feed(b"ls\r\n\x1b]6973;k1;B\x07a b\r\n\x1b]69", 20);
+        assert_eq!(visible, b"ls\r\na b\r\n");
+        assert!(commands.is_empty());
+        let (visible, commands) = capture.feed(b"73;k1;E;2;42;ls; false\x07$ ", 50);
+        assert_eq!(visible, b"$ ");
+        assert_eq!(
+            commands,
+            vec![CapturedCommand {
+                session_id: "s1".to_string(),
+                command: "ls; false".to_string(),
+                exit_code: Some(2),
+                started_at_ms: 20,
+                finished_at_ms: 50,
+                injected_by: None,
+            }]
+        );
+
+        // 空行不会改变序号。
+        let (_, commands) = capture.feed(b"\r\n\x1b]6973;k1;E;0;42;ls; false\x07$ ", 60);
+        assert!(commands.is_empty());
+    }
+
+    #[test]
+    fn injected_commands_carry_the_operator() {
+        let mut command = CapturedCommand {
+            session_id: "s1".to_string(),
+            command: "ls -la /srv".to_string(),
+            exit_code: None,
+            started_at_ms: 10,
+            finished_at_ms: 10,
+            injected_by: None,
+        };
+        assert_eq!(command.intent(), TERMINAL_INTENT);
+        assert!(!command.metadata().contains_key("injected_by"));
+
+        command.injected_by = Some("alice".to_string());
+        assert_eq!(command.intent(), TERMINAL_INJECT_INTENT);
+        let metadata = command.metadata();
+        assert_eq!(metadata["terminal_session"], "s1");
+        assert_eq!(metadata["injected_by"], "alice");
+    }
+
+    #[test]
+    fn ignores_markers_without_the_session_nonce() {
+        let mut capture = CommandCapture::new("s1".to_string(), NONCE);
+        ready(&mut capture);
+        capture.feed(b"\x1b]6973;k1;E;0;41;\x07$ ", 10);
+        // 远端输出里伪造的标记原样转发，不产生记录，也不影响真实命令的记录。
+        let forged = b"\x1b]6973;B\x07\x1b]6973;E;0;99;rm -rf /\x07\x1b]6973;k2;E;0;98;x\x07";
+        let (visible, commands) = capture.feed(forged, 20);
+        assert_eq!(visible, forged);
+        assert!(commands.is_empty());
+        let (_, commands) = capture.feed(b"\x1b]6973;k1;E;0;42;cat motd\x07", 30);
+        assert_eq!(commands.len(), 1);
+        assert_eq!(commands[0].command, "cat motd");
+    }
+
+    #[test]
+    fn abandons_when_hooks_never_report_ready() {
+        let mut capture = CommandCapture::new("s1".to_string(), NONCE);
+        let (visible, _) = capture.feed(b"fish> __OCTOVALVE_TERM_READY_k2__\r\n", 0);
+        assert!(visible.is_empty());
+        assert!(capture.is_pending_setup());
+        assert_eq!(capture.abandon(), b"fish> __OCTOVALVE_TERM_READY_k2__\r\n");
+        let (visible, commands) = capture.feed(b"\x1b]6973;k1;E;0;1;x\x07", 0);
+        assert_eq!(visible, b"\x1b]6973;k1;E;0;1;x\x07");
+        assert!(commands.is_empty());
+    }
+
+    #[test]
+    fn setup_line_does_not_contain_the_ready_marker() {
+        let (line, nonce) = setup_line();
+        let capture = CommandCapture::new("s1".to_string(), &nonce);
+        assert!(find_subsequence(line.as_bytes(), &capture.ready_marker).is_none());
+        assert!(line.contains(&format!("__ov_k={nonce};")));
+        assert_ne!(setup_line().1, nonce);
+        assert!(!SETUP_SCRIPT.contains('\''));
+
+        // 关闭历史的第一行不带 nonce，带 nonce 的行都在它之后，恢复历史与就绪标记在最后一行。
+        let lines: Vec<&str> = line.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(!lines[0].contains(&nonce));
+        assert!(lines[0].contains("set +o history") && lines[0].contains("fc -p"));
+        assert!(lines[2].contains("fc -P") && lines[2].contains(&format!("_READY_{nonce}__")));
+    }
+}
@@ -0,0 +1,12 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Unix 毫秒时间戳；早于 1970 的时间记为 0。
+pub(crate) fn system_time_ms(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+pub(crate) fn now_ms() -> u64 {
+    system_time_ms(SystemTime::now())
+}
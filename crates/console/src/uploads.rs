@@ -141,7 +141,7 @@ pub(crate) async fn list_remote_directories(
     if let Some(password) = target.ssh_password.as_deref() {
         apply_askpass_env(&mut cmd, password).map_err(|err| err.to_string())?;
     }
-    apply_ssh_options(&mut cmd, target.ssh_password.is_some());
+    apply_ssh_options(&mut cmd, target.ssh_password.is_some(), &target.host_key);
     for arg in &target.ssh_args {
         cmd.arg(arg);
     }
@@ -195,7 +195,7 @@ async fn fetch_remote_home(target: &TargetSpec) -> Result<String, String> {
     if let Some(password) = target.ssh_password.as_deref() {
         apply_askpass_env(&mut cmd, password).map_err(|err| err.to_string())?;
     }
-    apply_ssh_options(&mut cmd, target.ssh_password.is_some());
+    apply_ssh_options(&mut cmd, target.ssh_password.is_some(), &target.host_key);
     for arg in &target.ssh_args {
         cmd.arg(arg);
     }
@@ -322,7 +322,7 @@ async fn run_upload(
     if let Some(password) = target.ssh_password.as_deref() {
         apply_askpass_env(&mut cmd, password).map_err(|err| err.to_string())?;
     }
-    apply_ssh_options(&mut cmd, target.ssh_password.is_some());
+    apply_ssh_options(&mut cmd, target.ssh_password.is_some(), &target.host_key);
     for arg in &target.ssh_args {
         cmd.arg(arg);
     }
@@ -1,4 +1,5 @@
 use clap::Parser;
+use std::net::SocketAddr;
 use std::path::PathBuf;
 
 #[derive(Parser, Debug)]
@@ -18,4 +19,24 @@ pub(crate) struct Args {
     pub(crate) timeout_ms: u64,
     #[arg(long, default_value_t = 1024 * 1024)]
     pub(crate) max_output_bytes: u64,
+    /// 解密 ssh_password_encrypted 的口令文件；未设置时读取 OCTOVALVE_CONFIG_KEY。
+    #[arg(long)]
+    pub(crate) config_key_file: Option<PathBuf>,
+    /// 命令通道认证 token 文件（对应 console broker 配置的 control_token）；未设置时读取 OCTOVALVE_CONTROL_TOKEN。
+    #[arg(long)]
+    pub(crate) control_token_file: Option<PathBuf>,
+    /// 本地命令历史目录，按 client_id 写入 <client_id>.jsonl。
+    #[arg(long, default_value = "~/.octovalve/proxy-history")]
+    pub(crate) history_dir: String,
+    /// 只读 HTTP 状态接口（/health、/forwards、/clients），例如 127.0.0.1:19311；未设置时不监听。
+    #[arg(long)]
+    pub(crate) status_addr: Option<SocketAddr>,
+    /// 离线队列目录（仅对 queue_when_unreachable = true 的目标生效），按 client_id 写入 <client_id>.json。
+    #[arg(long, default_value = "~/.octovalve/proxy-queue")]
+    pub(crate) offline_queue_dir: String,
+    #[arg(long, default_value_t = 100)]
+    pub(crate) offline_queue_max: usize,
+    /// 排队超过该时长仍未送达的请求作废（expired）。
+    #[arg(long, default_value_t = 3600)]
+    pub(crate) offline_queue_ttl_secs: u64,
 }
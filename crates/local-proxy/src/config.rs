@@ -1,5 +1,4 @@
 use anyhow::Context;
-use protocol::config::parse_ssh_destination;
 pub(crate) use protocol::config::ProxyConfig;
 use std::path::PathBuf;
 
@@ -7,19 +6,15 @@ fn validate_proxy_config(config: &ProxyConfig) -> anyhow::Result<()> {
     if config.targets.is_empty() {
         anyhow::bail!("config must include at least one target");
     }
-    for (index, target) in config.targets.iter().enumerate() {
-        let label = if target.name.trim().is_empty() {
-            format!("target[{}]", index)
-        } else {
-            format!("target {}", target.name.trim())
-        };
-        let ssh = target.ssh.as_deref().unwrap_or("").trim();
-        if ssh.is_empty() {
-            anyhow::bail!("{} must set ssh (user@host)", label);
-        }
-        if parse_ssh_destination(ssh).is_none() {
-            anyhow::bail!("{} ssh must be user@host", label);
-        }
+    let (errors, warnings): (Vec<_>, Vec<_>) = protocol::config::validate_proxy_config(config)
+        .into_iter()
+        .partition(|diagnostic| diagnostic.is_error());
+    for warning in &warnings {
+        tracing::warn!(path = %warning.path, "{}", warning.message);
+    }
+    if !errors.is_empty() {
+        let errors: Vec<String> = errors.iter().map(ToString::to_string).collect();
+        anyhow::bail!("{}", errors.join("; "));
     }
     Ok(())
 }
@@ -57,7 +52,8 @@ desc = "dev"
 ssh = "127.0.0.1"
 "#;
         let parsed: ProxyConfig = toml::from_str(input).unwrap();
-        assert!(validate_proxy_config(&parsed).is_err());
+        let err = validate_proxy_config(&parsed).unwrap_err().to_string();
+        assert!(err.contains("targets[0].ssh"), "{err}");
     }
 
     #[test]
@@ -0,0 +1,143 @@
+// default_cwd 模板：支持 {client} / {intent_slug}，`{{`/`}}` 转义为字面量花括号。
+const INTENT_SLUG_MAX_LEN: usize = 48;
+const INTENT_SLUG_FALLBACK: &str = "default";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    Client,
+    IntentSlug,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct CwdTemplate {
+    raw: String,
+    segments: Vec<Segment>,
+}
+
+impl CwdTemplate {
+    pub(crate) fn parse(raw: &str) -> anyhow::Result<Self> {
+        let trimmed = raw.trim();
+        if trimmed.is_empty() {
+            anyhow::bail!("default_cwd cannot be empty");
+        }
+        let mut segments = Vec::new();
+        let mut literal = String::new();
+        let mut chars = trimmed.chars().peekable();
+        while let Some(ch) = chars.next() {
+            match ch {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    let mut closed = false;
+                    for next in chars.by_ref() {
+                        if next == '}' {
+                            closed = true;
+                            break;
+                        }
+                        name.push(next);
+                    }
+                    if !closed {
+                        anyhow::bail!("default_cwd has an unclosed '{{' in {trimmed:?}");
+                    }
+                    let segment = match name.as_str() {
+                        "client" => Segment::Client,
+                        "intent_slug" => Segment::IntentSlug,
+                        other => anyhow::bail!(
+                            "default_cwd uses unknown variable {{{other}}}; supported: {{client}}, {{intent_slug}}"
+                        ),
+                    };
+                    if !literal.is_empty() {
+                        segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                    }
+                    segments.push(segment);
+                }
+                '}' => anyhow::bail!("default_cwd has an unmatched '}}' in {trimmed:?}"),
+                other => literal.push(other),
+            }
+        }
+        if !literal.is_empty() {
+            segments.push(Segment::Literal(literal));
+        }
+        Ok(Self {
+            raw: trimmed.to_string(),
+            segments,
+        })
+    }
+
+    pub(crate) fn raw(&self) -> &str {
+        &self.raw
+    }
+
+    pub(crate) fn render(&self, client: &str, intent: &str) -> String {
+        let mut out = String::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(value) => out.push_str(value),
+                Segment::Client => out.push_str(client),
+                Segment::IntentSlug => out.push_str(&intent_slug(intent)),
+            }
+        }
+        out
+    }
+}
+
+// 只保留 ASCII 字母数字，其余折叠为单个 '-'，避免 intent 中的空格/斜杠/中文进入路径。
+fn intent_slug(intent: &str) -> String {
+    let mut slug = String::new();
+    for ch in intent.chars() {
+        if ch.is_ascii_alphanumeric() {
+            slug.push(ch.to_ascii_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+        if slug.len() >= INTENT_SLUG_MAX_LEN {
+            break;
+        }
+    }
+    let slug = slug.trim_end_matches('-');
+    if slug.is_empty() {
+        INTENT_SLUG_FALLBACK.to_string()
+    } else {
+        slug.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_client_and_intent_slug() {
+        let template = CwdTemplate::parse("/home/dev/work/{client}/{intent_slug}").unwrap();
+        assert_eq!(
+            template.render("codex-1", "Build the Release / v2!"),
+            "/home/dev/work/codex-1/build-the-release-v2"
+        );
+        assert_eq!(
+            template.render("codex-1", "排查问题"),
+            "/home/dev/work/codex-1/default"
+        );
+    }
+
+    #[test]
+    fn escaped_braces_are_literal() {
+        let template = CwdTemplate::parse("/tmp/{{x}}/{client}").unwrap();
+        assert_eq!(template.render("a", ""), "/tmp/{x}/a");
+    }
+
+    #[test]
+    fn rejects_invalid_templates() {
+        assert!(CwdTemplate::parse("/tmp/{user}").is_err());
+        assert!(CwdTemplate::parse("/tmp/{client").is_err());
+        assert!(CwdTemplate::parse("/tmp/client}").is_err());
+        assert!(CwdTemplate::parse("  ").is_err());
+    }
+}
@@ -0,0 +1,266 @@
+use std::borrow::Cow;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use protocol::{CommandResponse, CommandStage, CommandStatus};
+use serde::Serialize;
+
+// sshd 把整条远端命令作为单个参数交给 shell（Linux 单参数上限 128 KiB），base64 膨胀后仍需留出余量。
+pub(crate) const DEFAULT_WRITE_FILE_MAX_BYTES: u64 = 64 * 1024;
+pub(crate) const DEFAULT_READ_FILE_MAX_BYTES: u64 = 1024 * 1024;
+
+/// read_file / write_file 翻译出的命令，与 run_command 一样走白名单与审批。
+#[derive(Debug)]
+pub(crate) struct FileCommand {
+    pub(crate) intent: String,
+    pub(crate) raw_command: String,
+    pub(crate) pipeline: Vec<CommandStage>,
+    pub(crate) max_output_bytes: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct ReadFileResult {
+    pub(crate) id: String,
+    pub(crate) path: String,
+    pub(crate) content_base64: String,
+    pub(crate) bytes: usize,
+    pub(crate) truncated: bool,
+}
+
+impl ReadFileResult {
+    /// 内容是合法 UTF-8 时返回文本。
+    pub(crate) fn text(&self) -> Option<String> {
+        let bytes = STANDARD.decode(&self.content_base64).ok()?;
+        String::from_utf8(bytes).ok()
+    }
+}
+
+/// `dd if=<path> bs=<max_bytes+1> count=1 | base64`：多读一个字节用来判断是否截断，
+/// 输出始终是 ASCII，不受 tty 与编码影响。
+pub(crate) fn read_command(
+    path: &str,
+    max_bytes: u64,
+    intent: Option<&str>,
+) -> Result<FileCommand, String> {
+    let path = validate_path(path)?;
+    if max_bytes == 0 {
+        return Err("max_bytes must be greater than 0".to_string());
+    }
+    let pipeline = vec![
+        stage(&[
+            "dd",
+            &format!("if={}", operand_path(path)),
+            &format!("bs={}", max_bytes + 1),
+            "count=1",
+            "status=none",
+        ]),
+        stage(&["base64"]),
+    ];
+    // base64 每 76 个字符换一行。
+    let encoded = (max_bytes + 1).div_ceil(3) * 4;
+    Ok(FileCommand {
+        intent: file_intent(format!("read {path}"), intent),
+        raw_command: join_pipeline(&pipeline),
+        pipeline,
+        max_output_bytes: Some(encoded + encoded / 76 + 1),
+    })
+}
+
+/// `echo <base64> | base64 -d | dd of=<path>`；指定 mode 时最后一段换成 `install -m <mode> --`。
+pub(crate) fn write_command(
+    path: &str,
+    content_base64: &str,
+    mode: Option<&str>,
+    max_bytes: u64,
+    intent: Option<&str>,
+) -> Result<FileCommand, String> {
+    let path = validate_path(path)?;
+    let operand = operand_path(path);
+    let compact: String = content_base64
+        .chars()
+        .filter(|ch| !ch.is_ascii_whitespace())
+        .collect();
+    let content = STANDARD
+        .decode(compact.as_bytes())
+        .map_err(|err| format!("content_base64 is not valid base64: {err}"))?;
+    if content.len() as u64 > max_bytes {
+        return Err(format!(
+            "content is {} bytes, exceeds write_file limit of {max_bytes} bytes",
+            content.len()
+        ));
+    }
+    let sink = match mode {
+        Some(mode) => {
+            if !(3..=4).contains(&mode.len()) || !mode.chars().all(|ch| ('0'..='7').contains(&ch)) {
+                return Err(format!("mode must be octal like 0644, got {mode:?}"));
+            }
+            stage(&["install", "-m", mode, "--", "/dev/stdin", &operand])
+        }
+        None => stage(&["dd", &format!("of={operand}"), "status=none"]),
+    };
+    let pipeline = vec![
+        stage(&["echo", &STANDARD.encode(&content)]),
+        stage(&["base64", "-d"]),
+        sink,
+    ];
+    Ok(FileCommand {
+        intent: file_intent(format!("write {path} ({} bytes)", content.len()), intent),
+        raw_command: join_pipeline(&pipeline),
+        pipeline,
+        max_output_bytes: None,
+    })
+}
+
+/// 命令未成功完成时返回 None，由调用方按普通命令结果返回。
+pub(crate) fn read_result(
+    path: &str,
+    max_bytes: u64,
+    response: &CommandResponse,
+) -> Option<Result<ReadFileResult, String>> {
+    if response.status != CommandStatus::Completed || response.exit_code != Some(0) {
+        return None;
+    }
+    let stdout: String = response
+        .stdout
+        .as_deref()
+        .unwrap_or_default()
+        .chars()
+        .filter(|ch| !ch.is_ascii_whitespace())
+        .collect();
+    let mut content = match STANDARD.decode(stdout.as_bytes()) {
+        Ok(content) => content,
+        Err(err) => return Some(Err(format!("failed to decode file content: {err}"))),
+    };
+    let truncated = content.len() as u64 > max_bytes;
+    content.truncate(max_bytes as usize);
+    Some(Ok(ReadFileResult {
+        id: response.id.clone(),
+        path: path.trim().to_string(),
+        bytes: content.len(),
+        content_base64: STANDARD.encode(&content),
+        truncated,
+    }))
+}
+
+fn validate_path(path: &str) -> Result<&str, String> {
+    let path = path.trim();
+    if path.is_empty() {
+        return Err("path is empty".to_string());
+    }
+    if path.contains(['\0', '\n', '\r']) {
+        return Err("path contains control characters".to_string());
+    }
+    Ok(path)
+}
+
+// 以 `-` 开头的相对路径加上 `./`，不会被当成选项。
+fn operand_path(path: &str) -> Cow<'_, str> {
+    if path.starts_with('-') {
+        Cow::Owned(format!("./{path}"))
+    } else {
+        Cow::Borrowed(path)
+    }
+}
+
+fn file_intent(action: String, intent: Option<&str>) -> String {
+    match intent.map(str::trim).filter(|intent| !intent.is_empty()) {
+        Some(intent) => format!("{action}: {intent}"),
+        None => action,
+    }
+}
+
+fn stage(argv: &[&str]) -> CommandStage {
+    CommandStage {
+        argv: argv.iter().map(|arg| arg.to_string()).collect(),
+    }
+}
+
+fn join_pipeline(pipeline: &[CommandStage]) -> String {
+    pipeline
+        .iter()
+        .map(|stage| shell_words::join(&stage.argv))
+        .collect::<Vec<_>>()
+        .join(" | ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn argv(command: &FileCommand) -> Vec<Vec<String>> {
+        let stages: Vec<Vec<String>> = command
+            .raw_command
+            .split(" | ")
+            .map(|stage| shell_words::split(stage).unwrap())
+            .collect();
+        let declared: Vec<Vec<String>> = command
+            .pipeline
+            .iter()
+            .map(|stage| stage.argv.clone())
+            .collect();
+        assert_eq!(stages, declared);
+        stages
+    }
+
+    #[test]
+    fn builds_read_pipeline_and_decodes_result() {
+        let command = read_command(" /etc/my hosts ", 4, None).unwrap();
+        assert_eq!(command.intent, "read /etc/my hosts");
+        assert_eq!(
+            argv(&command),
+            vec![
+                vec!["dd", "if=/etc/my hosts", "bs=5", "count=1", "status=none"],
+                vec!["base64"],
+            ]
+        );
+
+        let stdout = format!("{}\n", STANDARD.encode(b"hello"));
+        let response = CommandResponse::completed("req", 0, Some(stdout), None);
+        let result = read_result("/etc/my hosts", 4, &response).unwrap().unwrap();
+        assert_eq!(result.content_base64, STANDARD.encode(b"hell"));
+        assert_eq!(result.bytes, 4);
+        assert!(result.truncated);
+
+        let failed = CommandResponse::completed("req", 1, None, Some("No such file".into()));
+        assert!(read_result("/etc/my hosts", 4, &failed).is_none());
+    }
+
+    #[test]
+    fn write_validates_content_before_dispatch() {
+        let content = STANDARD.encode(b"a=1\n");
+        let command =
+            write_command("/tmp/app.env", &content, Some("0600"), 16, Some("config")).unwrap();
+        assert_eq!(command.intent, "write /tmp/app.env (4 bytes): config");
+        assert_eq!(
+            argv(&command),
+            vec![
+                vec!["echo", content.as_str()],
+                vec!["base64", "-d"],
+                vec!["install", "-m", "0600", "--", "/dev/stdin", "/tmp/app.env"],
+            ]
+        );
+        let command = write_command("/tmp/app.env", &content, None, 16, None).unwrap();
+        assert_eq!(
+            argv(&command)[2],
+            vec!["dd", "of=/tmp/app.env", "status=none"]
+        );
+
+        let command = write_command("-rf", &content, Some("0644"), 16, None).unwrap();
+        assert_eq!(command.intent, "write -rf (4 bytes)");
+        assert_eq!(
+            argv(&command)[2],
+            vec!["install", "-m", "0644", "--", "/dev/stdin", "./-rf"]
+        );
+        let command = write_command("--help", &content, None, 16, None).unwrap();
+        assert_eq!(argv(&command)[2], vec!["dd", "of=./--help", "status=none"]);
+
+        assert!(write_command("/tmp/x", "not base64!", None, 16, None)
+            .unwrap_err()
+            .contains("base64"));
+        assert!(write_command("/tmp/x", &content, None, 3, None)
+            .unwrap_err()
+            .contains("exceeds"));
+        assert!(write_command("/tmp/x", &content, Some("rw"), 16, None).is_err());
+        assert!(write_command("  ", &content, None, 16, None).is_err());
+    }
+}
@@ -0,0 +1,224 @@
+use protocol::{CommandRequest, CommandResponse, CommandStatus};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+
+pub(crate) const DEFAULT_HISTORY_MAX_BYTES: u64 = 4 * 1024 * 1024;
+pub(crate) const DEFAULT_HISTORY_LIMIT: usize = 20;
+pub(crate) const MAX_HISTORY_LIMIT: usize = 200;
+
+// 本地记录当前 client 发起的命令，便于 agent 回看本次会话请求过什么；只记状态与退出码，不含输出。
+// 写入失败只打日志，不影响命令本身。
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) struct HistoryEntry {
+    pub(crate) timestamp_ms: u64,
+    pub(crate) id: String,
+    pub(crate) target: String,
+    pub(crate) intent: String,
+    pub(crate) command: String,
+    pub(crate) status: CommandStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) exit_code: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) batch_id: Option<String>,
+}
+
+impl HistoryEntry {
+    pub(crate) fn new(request: &CommandRequest, response: &CommandResponse) -> Self {
+        Self {
+            timestamp_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_millis() as u64)
+                .unwrap_or(0),
+            id: request.id.clone(),
+            target: request.target.clone(),
+            intent: request.intent.clone(),
+            command: request.raw_command.clone(),
+            status: response.status.clone(),
+            exit_code: response.exit_code,
+            error: response.error.clone(),
+            batch_id: request.batch_id.clone(),
+        }
+    }
+}
+
+pub(crate) struct CommandHistory {
+    path: PathBuf,
+    max_bytes: u64,
+    write_lock: Mutex<()>,
+}
+
+impl CommandHistory {
+    /// `max_bytes` 为 0 时不记录历史，返回 None。
+    pub(crate) fn new(dir: &Path, client_id: &str, max_bytes: u64) -> Option<Self> {
+        if max_bytes == 0 {
+            return None;
+        }
+        Some(Self {
+            path: dir.join(format!("{}.jsonl", file_stem(client_id))),
+            max_bytes,
+            write_lock: Mutex::new(()),
+        })
+    }
+
+    pub(crate) async fn record(&self, entry: &HistoryEntry) {
+        if let Err(err) = self.append(entry).await {
+            tracing::warn!(
+                path = %self.path.display(),
+                error = %err,
+                "failed to write command history"
+            );
+        }
+    }
+
+    async fn append(&self, entry: &HistoryEntry) -> anyhow::Result<()> {
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+        let _guard = self.write_lock.lock().await;
+        if let Some(parent) = self.path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        // 超过上限时把当前文件轮转为 .1（只保留一份），再写新文件。
+        let current = match tokio::fs::metadata(&self.path).await {
+            Ok(metadata) => metadata.len(),
+            Err(_) => 0,
+        };
+        if current > 0 && current + line.len() as u64 > self.max_bytes {
+            tokio::fs::rename(&self.path, self.rotated_path()).await?;
+        }
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await?;
+        file.write_all(&line).await?;
+        file.flush().await?;
+        Ok(())
+    }
+
+    /// 最近的记录，新的在前；当前文件不够时再读轮转文件。`status` 为 Some 时只返回该状态的记录。
+    pub(crate) async fn recent(
+        &self,
+        target: Option<&str>,
+        status: Option<&CommandStatus>,
+        limit: usize,
+    ) -> Vec<HistoryEntry> {
+        let mut entries = Vec::new();
+        for path in [self.path.clone(), self.rotated_path()] {
+            let Ok(content) = tokio::fs::read_to_string(&path).await else {
+                continue;
+            };
+            let mut lines = collect_recent(&content, target, status, limit - entries.len());
+            entries.append(&mut lines);
+            if entries.len() >= limit {
+                break;
+            }
+        }
+        entries
+    }
+
+    fn rotated_path(&self) -> PathBuf {
+        let mut name = self.path.as_os_str().to_owned();
+        name.push(".1");
+        PathBuf::from(name)
+    }
+}
+
+fn collect_recent(
+    content: &str,
+    target: Option<&str>,
+    status: Option<&CommandStatus>,
+    limit: usize,
+) -> Vec<HistoryEntry> {
+    content
+        .lines()
+        .rev()
+        .filter_map(|line| serde_json::from_str::<HistoryEntry>(line).ok())
+        .filter(|entry| target.map_or(true, |target| entry.target == target))
+        .filter(|entry| status.map_or(true, |status| &entry.status == status))
+        .take(limit)
+        .collect()
+}
+
+// client_id 来自命令行，落盘前只保留安全字符。
+pub(crate) fn file_stem(client_id: &str) -> String {
+    let stem: String = client_id
+        .trim()
+        .chars()
+        .map(|ch| {
+            if ch.is_ascii_alphanumeric() || matches!(ch, '-' | '_' | '.') {
+                ch
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if stem.is_empty() || stem.chars().all(|ch| ch == '.') {
+        "default".to_string()
+    } else {
+        stem
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn entry(target: &str, id: &str) -> HistoryEntry {
+        HistoryEntry {
+            timestamp_ms: 1,
+            id: id.to_string(),
+            target: target.to_string(),
+            intent: "test".to_string(),
+            command: "ls".to_string(),
+            status: CommandStatus::Completed,
+            exit_code: Some(0),
+            error: None,
+            batch_id: None,
+        }
+    }
+
+    #[test]
+    fn sanitizes_client_id_for_file_name() {
+        assert_eq!(file_stem("codex-1"), "codex-1");
+        assert_eq!(file_stem("../../etc/passwd"), ".._.._etc_passwd");
+        assert_eq!(file_stem(".."), "default");
+        assert_eq!(file_stem(""), "default");
+    }
+
+    #[test]
+    fn rotates_and_reads_recent_entries() {
+        let dir = std::env::temp_dir().join(format!("octovalve-proxy-history-{}", Uuid::new_v4()));
+        let line_len = serde_json::to_vec(&entry("dev", "a")).unwrap().len() as u64 + 1;
+        let history = CommandHistory::new(&dir, "client", line_len * 2).expect("history");
+        let rt = tokio::runtime::Runtime::new().expect("runtime");
+        let (all, dev, denied) = rt.block_on(async {
+            for (target, id) in [("dev", "a"), ("prod", "b"), ("dev", "c")] {
+                let mut entry = entry(target, id);
+                if id == "b" {
+                    entry.status = CommandStatus::Denied;
+                }
+                history.record(&entry).await;
+            }
+            (
+                history.recent(None, None, 10).await,
+                history.recent(Some("dev"), None, 1).await,
+                history.recent(None, Some(&CommandStatus::Denied), 10).await,
+            )
+        });
+        assert!(history.rotated_path().exists());
+        let ids: Vec<&str> = all.iter().map(|entry| entry.id.as_str()).collect();
+        assert_eq!(ids, ["c", "b", "a"]);
+        let ids: Vec<&str> = dev.iter().map(|entry| entry.id.as_str()).collect();
+        assert_eq!(ids, ["c"]);
+        let ids: Vec<&str> = denied.iter().map(|entry| entry.id.as_str()).collect();
+        assert_eq!(ids, ["b"]);
+        assert!(CommandHistory::new(&dir, "client", 0).is_none());
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -1,11 +1,18 @@
 mod cli;
 mod config;
+mod cwd;
+mod files;
+mod history;
 mod mcp;
+mod offline;
 mod state;
+mod status;
 
 use clap::Parser;
 use cli::Args;
+use history::CommandHistory;
 use mcp::ProxyHandler;
+use offline::OfflineQueue;
 use rmcp::model::{
     Implementation, InitializeResult, ProtocolVersion, ServerCapabilities, ToolsCapability,
 };
@@ -14,6 +21,8 @@ use rmcp::transport::stdio;
 use state::build_proxy_state;
 use std::io;
 use std::sync::Arc;
+use std::time::Duration;
+use system_utils::path::expand_tilde;
 use tokio::sync::RwLock;
 use tokio_util::sync::CancellationToken;
 use tracing_subscriber::prelude::*;
@@ -25,6 +34,16 @@ async fn main() -> anyhow::Result<()> {
     let (state, defaults) = build_proxy_state(&args)?;
     let state = Arc::new(RwLock::new(state));
     let shutdown = CancellationToken::new();
+    if let Some(addr) = args.status_addr {
+        let addr = status::spawn_status_server(
+            addr,
+            Arc::clone(&state),
+            args.client_id.clone(),
+            shutdown.clone(),
+        )
+        .await?;
+        tracing::info!(addr = %addr, "status endpoint listening");
+    }
 
     let server_details = InitializeResult {
         server_info: Implementation {
@@ -45,7 +64,38 @@ async fn main() -> anyhow::Result<()> {
         protocol_version: ProtocolVersion::V_2025_06_18,
     };
 
-    let handler = ProxyHandler::new(Arc::clone(&state), args.client_id, defaults, server_details);
+    let history = CommandHistory::new(
+        &expand_tilde(&args.history_dir),
+        &args.client_id,
+        defaults.history_max_bytes,
+    )
+    .map(Arc::new);
+    // 只有显式开启 queue_when_unreachable 的目标才会用到离线队列，默认仍是连不上即失败。
+    let offline = if state.read().await.any_queue_when_unreachable() {
+        let queue = Arc::new(OfflineQueue::load(
+            &expand_tilde(&args.offline_queue_dir),
+            &args.client_id,
+            args.offline_queue_max,
+            Duration::from_secs(args.offline_queue_ttl_secs),
+        ));
+        offline::spawn_flusher(
+            Arc::clone(&queue),
+            Arc::clone(&state),
+            history.clone(),
+            shutdown.clone(),
+        );
+        Some(queue)
+    } else {
+        None
+    };
+    let handler = ProxyHandler::new(
+        Arc::clone(&state),
+        args.client_id,
+        defaults,
+        history,
+        offline,
+        server_details,
+    );
     let server = handler
         .serve_with_ct(stdio(), shutdown.clone())
         .await
@@ -1,23 +1,37 @@
+use crate::files::{
+    read_command, read_result, write_command, FileCommand, ReadFileResult,
+    DEFAULT_READ_FILE_MAX_BYTES,
+};
+use crate::history::{CommandHistory, HistoryEntry, DEFAULT_HISTORY_LIMIT, MAX_HISTORY_LIMIT};
+use crate::offline::{OfflineQueue, QueuedOutcome};
 use crate::state::{ProxyRuntimeDefaults, ProxyState, TargetListEntry};
 use anyhow::Context;
 use bytes::Bytes;
 use futures_util::{SinkExt, StreamExt};
-use protocol::{CommandMode, CommandRequest, CommandResponse, CommandStage, CommandStatus};
+use protocol::{
+    compression, normalize_intent_tags, validate_request_metadata, CancelOutcome, CommandAuth,
+    CommandCancel, CommandMode, CommandProgress, CommandRequest, CommandResponse, CommandStage,
+    CommandStatus, ProgressStage, RequestPriority,
+};
 use rmcp::{
     model::{
         CallToolRequestParam, CallToolResult, Content, JsonObject, ListToolsResult,
-        PaginatedRequestParam, ServerInfo, Tool, ToolAnnotations,
+        PaginatedRequestParam, ProgressNotificationParam, ProgressToken, ServerInfo, Tool,
+        ToolAnnotations,
     },
-    ErrorData as McpError, RoleServer, ServerHandler,
+    service::RequestContext,
+    ErrorData as McpError, Peer, RoleServer, ServerHandler,
 };
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_json::{json, Map, Value};
 use std::collections::BTreeMap;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use tokio::net::TcpStream;
-use tokio::sync::RwLock;
+use tokio::sync::{mpsc, RwLock};
+use tokio::task::JoinHandle;
 use tokio::time::{sleep, Duration};
 use tokio_util::codec::{Framed, LengthDelimitedCodec};
+use tracing::Instrument;
 use uuid::Uuid;
 
 pub(crate) struct ProxyHandler {
@@ -25,7 +39,13 @@ pub(crate) struct ProxyHandler {
     client_id: String,
     default_timeout_ms: u64,
     default_max_output_bytes: u64,
+    default_metadata: BTreeMap<String, String>,
+    write_file_max_bytes: u64,
+    history: Option<Arc<CommandHistory>>,
+    offline: Option<Arc<OfflineQueue>>,
     server_info: ServerInfo,
+    // set_default_target 固定的目标（已解析为目标名），只在本进程（即本次 MCP 会话）内有效。
+    pinned_target: Mutex<Option<String>>,
 }
 
 impl ProxyHandler {
@@ -33,6 +53,8 @@ impl ProxyHandler {
         state: Arc<RwLock<ProxyState>>,
         client_id: String,
         defaults: ProxyRuntimeDefaults,
+        history: Option<Arc<CommandHistory>>,
+        offline: Option<Arc<OfflineQueue>>,
         server_info: ServerInfo,
     ) -> Self {
         Self {
@@ -40,7 +62,43 @@ impl ProxyHandler {
             client_id,
             default_timeout_ms: defaults.timeout_ms,
             default_max_output_bytes: defaults.max_output_bytes,
+            default_metadata: defaults.metadata,
+            write_file_max_bytes: defaults.write_file_max_bytes,
+            history,
+            offline,
             server_info,
+            pinned_target: Mutex::new(None),
+        }
+    }
+
+    fn pinned_target(&self) -> Option<String> {
+        self.pinned_target
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone()
+    }
+
+    // 未传 target 时回落到会话固定的目标，其次是配置的 default_target。
+    fn effective_default(&self, state: &ProxyState) -> Option<String> {
+        self.pinned_target().or_else(|| state.default_target())
+    }
+
+    // 显式传入的 target 可以是别名，统一解析为目标名后再路由。
+    fn select_target(
+        &self,
+        state: &ProxyState,
+        requested: Option<String>,
+    ) -> Result<String, McpError> {
+        match requested.filter(|name| !name.trim().is_empty()) {
+            Some(name) => state
+                .resolve_target(&name)
+                .map_err(|err| McpError::invalid_params(err.to_string(), None)),
+            None => self.effective_default(state).ok_or_else(|| {
+                McpError::invalid_params(
+                    format!("target is required; {}", state.describe_targets()),
+                    None,
+                )
+            }),
         }
     }
 
@@ -56,7 +114,7 @@ impl ProxyHandler {
         let mut target_schema = json!({
             "type": "string",
             "enum": targets,
-            "description": "Target name defined in octovalve-proxy config."
+            "description": "Target name or alias defined in octovalve-proxy config."
         });
         if let Some(default) = default_target {
             target_schema["default"] = json!(default);
@@ -69,6 +127,15 @@ impl ProxyHandler {
                 "description": "Why this command is needed (required for audit)."
             }),
         );
+        properties.insert(
+            "intent_tags".to_string(),
+            json!({
+                "type": "array",
+                "items": { "type": "string" },
+                "description": "Optional machine-readable categories for the intent, e.g. [\"read-only\"] or [\"deploy\"]. The console policy may auto-approve tagged requests."
+            }),
+        );
+        properties.insert("metadata".to_string(), metadata_schema());
         properties.insert(
             "mode".to_string(),
             json!({
@@ -82,7 +149,23 @@ impl ProxyHandler {
             "cwd".to_string(),
             json!({
                 "type": "string",
-                "description": "Working directory on the target machine. If set, command runs as `cd <cwd> && ...`. Must already exist. Prefer absolute paths. `~` is not expanded. If omitted, uses /tmp ."
+                "description": "Working directory on the target machine. If set, command runs as `cd <cwd> && ...`. Must already exist unless create_cwd is set; a missing directory fails before the command runs. Prefer absolute paths; a leading `~` expands to the remote home. If omitted, uses the target's default_cwd (see list_targets), otherwise /tmp ."
+            }),
+        );
+        properties.insert(
+            "create_cwd".to_string(),
+            json!({
+                "type": "boolean",
+                "default": false,
+                "description": "Create cwd (mkdir -p) if it does not exist. Only honored when the console policy allows it (limits.allow_create_cwd / create_cwd_prefixes)."
+            }),
+        );
+        properties.insert(
+            "readonly".to_string(),
+            json!({
+                "type": "boolean",
+                "default": false,
+                "description": "Declare the command read-only (e.g. cat, ls, grep, ps, df). The console auto-approves it when every pipeline stage is on its read-only whitelist and nothing is redirected to a file; otherwise the request is denied with the reason. Leave unset for anything that changes state so it queues for approval."
             }),
         );
         properties.insert(
@@ -101,6 +184,15 @@ impl ProxyHandler {
                 "description": "Override output size limit in bytes."
             }),
         );
+        properties.insert(
+            "priority".to_string(),
+            json!({
+                "type": "string",
+                "enum": ["low", "normal", "high"],
+                "default": "normal",
+                "description": "Queue ordering hint for pending approval. Does not bypass whitelist or approval."
+            }),
+        );
         properties.insert(
             "env".to_string(),
             json!({
@@ -109,6 +201,28 @@ impl ProxyHandler {
                 "description": "Extra environment variables."
             }),
         );
+        properties.insert(
+            "detach".to_string(),
+            json!({
+                "type": "boolean",
+                "default": false,
+                "description": "For long jobs (e.g. backups): return status `detached` right after approval instead of waiting. The command keeps running on the console without a timeout; the operator can query or kill it by id in the console."
+            }),
+        );
+        properties.insert(
+            "run_as".to_string(),
+            json!({
+                "type": "string",
+                "description": "Optional account to run the command as on the target. Only honored when the console policy allows it; otherwise the request is denied."
+            }),
+        );
+        properties.insert(
+            "request_id".to_string(),
+            json!({
+                "type": "string",
+                "description": "Optional caller-chosen request id (1-64 characters of A-Z, a-z, 0-9, `.`, `_`, `-`). Use a fresh value per call; pass it to cancel_command from another tool call to abort this command while it waits for approval or runs. Defaults to a random UUID, which is also returned in the result."
+            }),
+        );
 
         let mut input_schema = Map::new();
         input_schema.insert("type".to_string(), Value::String("object".to_string()));
@@ -140,13 +254,215 @@ impl ProxyHandler {
         }
     }
 
+    fn fan_out_definition(&self, targets: &[String]) -> Tool {
+        let mut properties = Map::new();
+        properties.insert(
+            "command".to_string(),
+            json!({
+                "type": "string",
+                "description": "Shell-like command line, sent unchanged to every selected target."
+            }),
+        );
+        properties.insert(
+            "targets".to_string(),
+            json!({
+                "oneOf": [
+                    { "type": "array", "items": { "type": "string", "enum": targets }, "minItems": 1 },
+                    { "type": "string", "enum": ["*"] }
+                ],
+                "description": "Target names or aliases, or \"*\" for every configured target."
+            }),
+        );
+        properties.insert(
+            "intent".to_string(),
+            json!({
+                "type": "string",
+                "description": "Why this command is needed (required for audit). Shared by all per-target requests."
+            }),
+        );
+        properties.insert(
+            "intent_tags".to_string(),
+            json!({
+                "type": "array",
+                "items": { "type": "string" },
+                "description": "Optional machine-readable categories for the intent, e.g. [\"read-only\"] or [\"deploy\"]. The console policy may auto-approve tagged requests."
+            }),
+        );
+        properties.insert("metadata".to_string(), metadata_schema());
+        properties.insert(
+            "max_parallel".to_string(),
+            json!({
+                "type": "integer",
+                "minimum": 1,
+                "default": DEFAULT_FAN_OUT_PARALLEL,
+                "description": "Maximum number of targets whose requests are in flight at once."
+            }),
+        );
+        properties.insert(
+            "mode".to_string(),
+            json!({
+                "type": "string",
+                "enum": ["shell"],
+                "default": "shell",
+                "description": "Execution mode: shell uses /bin/bash -lc."
+            }),
+        );
+        properties.insert(
+            "cwd".to_string(),
+            json!({
+                "type": "string",
+                "description": "Working directory on every target. If omitted, each target uses its own default_cwd, otherwise /tmp ."
+            }),
+        );
+        properties.insert(
+            "timeout_ms".to_string(),
+            json!({
+                "type": "integer",
+                "minimum": 0,
+                "description": "Override command timeout in milliseconds."
+            }),
+        );
+        properties.insert(
+            "max_output_bytes".to_string(),
+            json!({
+                "type": "integer",
+                "minimum": 0,
+                "description": "Override output size limit in bytes."
+            }),
+        );
+        properties.insert(
+            "priority".to_string(),
+            json!({
+                "type": "string",
+                "enum": ["low", "normal", "high"],
+                "default": "normal",
+                "description": "Queue ordering hint for pending approval. Does not bypass whitelist or approval."
+            }),
+        );
+        properties.insert(
+            "env".to_string(),
+            json!({
+                "type": "object",
+                "additionalProperties": { "type": "string" },
+                "description": "Extra environment variables."
+            }),
+        );
+
+        let mut input_schema = Map::new();
+        input_schema.insert("type".to_string(), Value::String("object".to_string()));
+        input_schema.insert(
+            "required".to_string(),
+            json!(["command", "intent", "targets"]),
+        );
+        input_schema.insert("properties".to_string(), Value::Object(properties));
+
+        Tool {
+            name: "run_command_on_targets".into(),
+            description: Some(
+                "Run the same command on several targets concurrently. Each target gets its own request and manual approval; requests share the intent and a batch_id. Returns a per-target map of status, exit_code and a stdout preview; one failing target does not abort the others.".into(),
+            ),
+            input_schema: Arc::new(input_schema),
+            output_schema: None,
+            title: Some("Run Command On Targets".to_string()),
+            annotations: Some(ToolAnnotations {
+                read_only_hint: Some(false),
+                destructive_hint: Some(true),
+                open_world_hint: Some(false),
+                idempotent_hint: Some(false),
+                title: Some("Run Command On Targets".to_string()),
+            }),
+            icons: None,
+        }
+    }
+
+    fn read_file_definition(&self, targets: &[String], default_target: Option<&String>) -> Tool {
+        let mut properties = file_target_properties(targets, default_target);
+        properties.insert(
+            "max_bytes".to_string(),
+            json!({
+                "type": "integer",
+                "minimum": 1,
+                "default": DEFAULT_READ_FILE_MAX_BYTES,
+                "description": "Read at most this many bytes from the start of the file; `truncated` is set when the file is longer."
+            }),
+        );
+        let mut input_schema = Map::new();
+        input_schema.insert("type".to_string(), Value::String("object".to_string()));
+        input_schema.insert("required".to_string(), file_required(default_target, &[]));
+        input_schema.insert("properties".to_string(), Value::Object(properties));
+        Tool {
+            name: "read_file".into(),
+            description: Some("Read a file on the target through the same whitelist and manual approval as run_command (runs `dd | base64`). The content is returned base64-encoded as content_base64 in the structured result, with its byte count and a truncated flag.".into()),
+            input_schema: Arc::new(input_schema),
+            output_schema: None,
+            title: Some("Read File".to_string()),
+            annotations: Some(ToolAnnotations {
+                read_only_hint: Some(true),
+                destructive_hint: Some(false),
+                open_world_hint: Some(false),
+                idempotent_hint: Some(true),
+                title: Some("Read File".to_string()),
+            }),
+            icons: None,
+        }
+    }
+
+    fn write_file_definition(&self, targets: &[String], default_target: Option<&String>) -> Tool {
+        let mut properties = file_target_properties(targets, default_target);
+        properties.insert(
+            "content_base64".to_string(),
+            json!({
+                "type": "string",
+                "description": format!("Full file content, base64-encoded. At most {} bytes after decoding.", self.write_file_max_bytes)
+            }),
+        );
+        properties.insert(
+            "mode".to_string(),
+            json!({
+                "type": "string",
+                "pattern": "^[0-7]{3,4}$",
+                "description": "Octal permission bits, e.g. \"0644\". If set, the file is written with `install -m`; otherwise an existing file keeps its mode."
+            }),
+        );
+        let mut input_schema = Map::new();
+        input_schema.insert("type".to_string(), Value::String("object".to_string()));
+        input_schema.insert(
+            "required".to_string(),
+            file_required(default_target, &["content_base64"]),
+        );
+        input_schema.insert("properties".to_string(), Value::Object(properties));
+        Tool {
+            name: "write_file".into(),
+            description: Some("Create or overwrite a file on the target through the same whitelist and manual approval as run_command (runs `echo | base64 -d | dd` or `install`). Content is sent base64-encoded, so quoting and binary data are safe.".into()),
+            input_schema: Arc::new(input_schema),
+            output_schema: None,
+            title: Some("Write File".to_string()),
+            annotations: Some(ToolAnnotations {
+                read_only_hint: Some(false),
+                destructive_hint: Some(true),
+                open_world_hint: Some(false),
+                idempotent_hint: Some(true),
+                title: Some("Write File".to_string()),
+            }),
+            icons: None,
+        }
+    }
+
     fn list_targets_definition(&self) -> Tool {
+        let mut properties = Map::new();
+        properties.insert(
+            "intent".to_string(),
+            json!({
+                "type": "string",
+                "description": "Optional intent used to preview default_cwd when it contains {intent_slug}."
+            }),
+        );
         let mut input_schema = Map::new();
         input_schema.insert("type".to_string(), Value::String("object".to_string()));
-        input_schema.insert("properties".to_string(), Value::Object(Map::new()));
+        input_schema.insert("properties".to_string(), Value::Object(properties));
         Tool {
             name: "list_targets".into(),
-            description: Some("List available targets configured in octovalve-proxy.".into()),
+            description: Some("List available targets configured in octovalve-proxy with their aliases, the effective default_target, the default_cwd used when run_command omits cwd, and any operator banner (e.g. change-window notices) to read before choosing a target.".into()),
             input_schema: Arc::new(input_schema),
             output_schema: None,
             title: Some("List Targets".to_string()),
@@ -160,64 +476,470 @@ impl ProxyHandler {
             icons: None,
         }
     }
-}
 
-impl ServerHandler for ProxyHandler {
-    fn get_info(&self) -> ServerInfo {
-        self.server_info.clone()
+    fn set_default_target_definition(&self, targets: &[String]) -> Tool {
+        let mut choices: Vec<Value> = targets.iter().map(|name| json!(name)).collect();
+        choices.push(Value::Null);
+        let mut properties = Map::new();
+        properties.insert(
+            "target".to_string(),
+            json!({
+                "type": ["string", "null"],
+                "enum": choices,
+                "description": "Target name or alias to use when a tool call omits target. Omit or pass null to fall back to the configured default_target."
+            }),
+        );
+        let mut input_schema = Map::new();
+        input_schema.insert("type".to_string(), Value::String("object".to_string()));
+        input_schema.insert("properties".to_string(), Value::Object(properties));
+        Tool {
+            name: "set_default_target".into(),
+            description: Some("Pin the target used by run_command, read_file, write_file and cancel_command when they omit target. Lasts for this MCP session only.".into()),
+            input_schema: Arc::new(input_schema),
+            output_schema: None,
+            title: Some("Set Default Target".to_string()),
+            annotations: Some(ToolAnnotations {
+                read_only_hint: Some(false),
+                destructive_hint: Some(false),
+                open_world_hint: Some(false),
+                idempotent_hint: Some(true),
+                title: Some("Set Default Target".to_string()),
+            }),
+            icons: None,
+        }
     }
 
-    fn list_tools(
-        &self,
-        _: Option<PaginatedRequestParam>,
-        _: rmcp::service::RequestContext<RoleServer>,
-    ) -> impl std::future::Future<Output = Result<ListToolsResult, McpError>> + Send + '_ {
-        async move {
-            let (targets, default_target) = {
-                let state = self.state.read().await;
-                (state.target_names(), state.default_target())
-            };
-            Ok(ListToolsResult::with_all_items(vec![
-                self.tool_definition(&targets, default_target.as_ref()),
-                self.list_targets_definition(),
-            ]))
+    fn history_definition(&self, targets: &[String]) -> Tool {
+        let mut properties = Map::new();
+        let mut target_schema = json!({
+            "type": "string",
+            "description": "Only return commands sent to this target."
+        });
+        if !targets.is_empty() {
+            target_schema["enum"] = json!(targets);
+        }
+        properties.insert("target".to_string(), target_schema);
+        properties.insert(
+            "limit".to_string(),
+            json!({
+                "type": "integer",
+                "minimum": 1,
+                "maximum": MAX_HISTORY_LIMIT,
+                "description": format!("Maximum number of entries, newest first (default {DEFAULT_HISTORY_LIMIT}).")
+            }),
+        );
+        let mut input_schema = Map::new();
+        input_schema.insert("type".to_string(), Value::String("object".to_string()));
+        input_schema.insert("properties".to_string(), Value::Object(properties));
+        Tool {
+            name: "get_command_history".into(),
+            description: Some("List commands this client previously sent through run_command/run_command_on_targets, newest first, with target, intent, final status and exit code. Output is not included; rerun or ask the operator if you need it.".into()),
+            input_schema: Arc::new(input_schema),
+            output_schema: None,
+            title: Some("Get Command History".to_string()),
+            annotations: Some(ToolAnnotations {
+                read_only_hint: Some(true),
+                destructive_hint: Some(false),
+                open_world_hint: Some(false),
+                idempotent_hint: Some(true),
+                title: Some("Get Command History".to_string()),
+            }),
+            icons: None,
         }
     }
 
-    fn call_tool(
-        &self,
-        request: CallToolRequestParam,
-        _: rmcp::service::RequestContext<RoleServer>,
-    ) -> impl std::future::Future<Output = Result<CallToolResult, McpError>> + Send + '_ {
-        async move {
-            match request.name.as_ref() {
-                "run_command" => {
-                    let args = parse_arguments(request.arguments)
-                        .map_err(|err| McpError::invalid_params(err, None))?;
-                    let pipeline = parse_pipeline(&args.command)
+    fn denied_definition(&self, targets: &[String]) -> Tool {
+        let mut properties = Map::new();
+        let mut target_schema = json!({
+            "type": "string",
+            "description": "Only return requests sent to this target."
+        });
+        if !targets.is_empty() {
+            target_schema["enum"] = json!(targets);
+        }
+        properties.insert("target".to_string(), target_schema);
+        properties.insert(
+            "limit".to_string(),
+            json!({
+                "type": "integer",
+                "minimum": 1,
+                "maximum": MAX_HISTORY_LIMIT,
+                "description": format!("Maximum number of entries, newest first (default {DEFAULT_HISTORY_LIMIT}).")
+            }),
+        );
+        let mut input_schema = Map::new();
+        input_schema.insert("type".to_string(), Value::String("object".to_string()));
+        input_schema.insert("properties".to_string(), Value::Object(properties));
+        Tool {
+            name: "get_denied_requests".into(),
+            description: Some("List commands this client sent that were denied, newest first. `error` holds the policy rule or the operator's reason (e.g. \"denied by operator: resubmit with --dry-run\"); read it before retrying and adjust the command instead of resubmitting it unchanged.".into()),
+            input_schema: Arc::new(input_schema),
+            output_schema: None,
+            title: Some("Get Denied Requests".to_string()),
+            annotations: Some(ToolAnnotations {
+                read_only_hint: Some(true),
+                destructive_hint: Some(false),
+                open_world_hint: Some(false),
+                idempotent_hint: Some(true),
+                title: Some("Get Denied Requests".to_string()),
+            }),
+            icons: None,
+        }
+    }
+
+    fn queued_result_definition(&self) -> Tool {
+        let mut properties = Map::new();
+        properties.insert(
+            "id".to_string(),
+            json!({
+                "type": "string",
+                "description": "Request id returned by run_command with status queued_offline."
+            }),
+        );
+        let mut input_schema = Map::new();
+        input_schema.insert("type".to_string(), Value::String("object".to_string()));
+        input_schema.insert("properties".to_string(), Value::Object(properties));
+        input_schema.insert("required".to_string(), json!(["id"]));
+        Tool {
+            name: "get_queued_result".into(),
+            description: Some("Check a run_command request that was queued offline because the console was unreachable. Returns its queue position while waiting, the command result once it has been delivered, or status expired if it was dropped after the queue TTL.".into()),
+            input_schema: Arc::new(input_schema),
+            output_schema: None,
+            title: Some("Get Queued Result".to_string()),
+            annotations: Some(ToolAnnotations {
+                read_only_hint: Some(true),
+                destructive_hint: Some(false),
+                open_world_hint: Some(false),
+                idempotent_hint: Some(true),
+                title: Some("Get Queued Result".to_string()),
+            }),
+            icons: None,
+        }
+    }
+
+    fn cancel_definition(&self, targets: &[String], default_target: Option<&String>) -> Tool {
+        let mut properties = Map::new();
+        let mut target_schema = json!({
+            "type": "string",
+            "enum": targets,
+            "description": "Target the request was sent to."
+        });
+        if let Some(default) = default_target {
+            target_schema["default"] = json!(default);
+        }
+        properties.insert("target".to_string(), target_schema);
+        properties.insert(
+            "id".to_string(),
+            json!({
+                "type": "string",
+                "description": "Request id from run_command (its result, or the request_id you supplied)."
+            }),
+        );
+        let mut input_schema = Map::new();
+        input_schema.insert("type".to_string(), Value::String("object".to_string()));
+        input_schema.insert("properties".to_string(), Value::Object(properties));
+        let required = if default_target.is_some() {
+            json!(["id"])
+        } else {
+            json!(["id", "target"])
+        };
+        input_schema.insert("required".to_string(), required);
+        Tool {
+            name: "cancel_command".into(),
+            description: Some("Cancel a request this proxy submitted: a pending request is removed from the approval queue, a running command is stopped. Returns outcome cancelled, not_found or already_finished.".into()),
+            input_schema: Arc::new(input_schema),
+            output_schema: None,
+            title: Some("Cancel Command".to_string()),
+            annotations: Some(ToolAnnotations {
+                read_only_hint: Some(false),
+                destructive_hint: Some(true),
+                open_world_hint: Some(false),
+                idempotent_hint: Some(true),
+                title: Some("Cancel Command".to_string()),
+            }),
+            icons: None,
+        }
+    }
+
+    async fn dispatch(
+        &self,
+        addr: &str,
+        request: &CommandRequest,
+        progress: Option<&ProgressSender>,
+    ) -> CommandResponse {
+        match deliver(
+            &self.state,
+            self.history.as_deref(),
+            addr,
+            request,
+            progress,
+        )
+        .await
+        {
+            Ok(response) => response,
+            Err(err) => record_error(self.history.as_deref(), request, err.to_string()).await,
+        }
+    }
+
+    // 目标开启 queue_when_unreachable 时，连不上 console 的请求转入离线队列而不是直接失败。
+    async fn dispatch_or_queue(
+        &self,
+        offline: &OfflineQueue,
+        addr: &str,
+        request: &CommandRequest,
+        progress: Option<&ProgressSender>,
+    ) -> CallToolResult {
+        let err = match deliver(
+            &self.state,
+            self.history.as_deref(),
+            addr,
+            request,
+            progress,
+        )
+        .await
+        {
+            Ok(response) => return response_to_tool_result(response),
+            Err(err) => err,
+        };
+        match offline.enqueue(request.clone()).await {
+            Ok(position) => {
+                tracing::info!(id = %request.id, target = %request.target, trace_id = request.trace_id.as_deref().unwrap_or_default(), position, "console unreachable; request queued offline");
+                queued_to_tool_result(&request.id, position, Some(&err.to_string()))
+            }
+            Err(full) => {
+                let message = format!("{err}; {full}");
+                response_to_tool_result(
+                    record_error(self.history.as_deref(), request, message).await,
+                )
+            }
+        }
+    }
+
+    // read_file / write_file 共用：解析目标与 cwd，组装与 run_command 相同形状的请求。
+    async fn file_request(
+        &self,
+        target: Option<String>,
+        command: FileCommand,
+        metadata: Option<BTreeMap<String, String>>,
+        trace_id: &str,
+    ) -> Result<(String, CommandRequest), McpError> {
+        let state = self.state.read().await;
+        let target = self.select_target(&state, target)?;
+        let addr = state
+            .target_addr(&target)
+            .map_err(|err| McpError::invalid_params(err.to_string(), None))?;
+        let cwd = state.default_cwd(&target, &self.client_id, &command.intent);
+        let request = CommandRequest {
+            id: Uuid::new_v4().to_string(),
+            client: self.client_id.clone(),
+            target,
+            intent: command.intent,
+            mode: CommandMode::Shell,
+            raw_command: command.raw_command,
+            cwd,
+            env: None,
+            timeout_ms: Some(self.default_timeout_ms),
+            max_output_bytes: Some(
+                command
+                    .max_output_bytes
+                    .unwrap_or(self.default_max_output_bytes),
+            ),
+            pipeline: command.pipeline,
+            priority: None,
+            batch_id: None,
+            intent_tags: Vec::new(),
+            metadata,
+            detach: false,
+            run_as: None,
+            compression: compression::supported_encodings(),
+            progress: false,
+            create_cwd: false,
+            readonly: false,
+            trace_id: Some(trace_id.to_string()),
+        };
+        Ok((addr, request))
+    }
+
+    fn request_metadata(
+        &self,
+        context: &RequestContext<RoleServer>,
+        supplied: Option<BTreeMap<String, String>>,
+    ) -> Result<Option<BTreeMap<String, String>>, McpError> {
+        let client_name = context
+            .peer
+            .peer_info()
+            .map(|info| info.client_info.name.as_str());
+        merge_metadata(
+            &self.default_metadata,
+            client_name,
+            &context.meta.0,
+            supplied,
+        )
+        .map_err(|err| McpError::invalid_params(err, None))
+    }
+
+    async fn run_on_targets(
+        &self,
+        args: FanOutArgs,
+        pipeline: Vec<CommandStage>,
+        metadata: Option<BTreeMap<String, String>>,
+        trace_id: &str,
+    ) -> Result<CallToolResult, McpError> {
+        // 先整体校验目标名，未知目标直接报参数错误，避免只下发了一部分。
+        let plans = {
+            let state = self.state.read().await;
+            let names = args
+                .targets
+                .resolve(&state.target_names(), |name| state.resolve_target(name))?;
+            let mut plans = Vec::with_capacity(names.len());
+            for name in names {
+                let addr = state
+                    .target_addr(&name)
+                    .map_err(|err| McpError::invalid_params(err.to_string(), None))?;
+                let cwd = args
+                    .cwd
+                    .clone()
+                    .filter(|cwd| !cwd.trim().is_empty())
+                    .or_else(|| state.default_cwd(&name, &self.client_id, &args.intent));
+                plans.push((name, addr, cwd));
+            }
+            plans
+        };
+
+        let batch_id = Uuid::new_v4().to_string();
+        let max_parallel = args
+            .max_parallel
+            .unwrap_or(DEFAULT_FAN_OUT_PARALLEL)
+            .clamp(1, plans.len().max(1));
+        let mode = args.mode.unwrap_or(CommandMode::Shell);
+        let intent_tags = normalize_intent_tags(args.intent_tags.clone());
+        let results: BTreeMap<String, FanOutEntry> = futures_util::stream::iter(plans)
+            .map(|(target, addr, cwd)| {
+                let request = CommandRequest {
+                    id: Uuid::new_v4().to_string(),
+                    client: self.client_id.clone(),
+                    target,
+                    intent: args.intent.clone(),
+                    mode: mode.clone(),
+                    raw_command: args.command.clone(),
+                    cwd,
+                    env: args.env.clone(),
+                    timeout_ms: Some(args.timeout_ms.unwrap_or(self.default_timeout_ms)),
+                    max_output_bytes: Some(
+                        args.max_output_bytes
+                            .unwrap_or(self.default_max_output_bytes),
+                    ),
+                    pipeline: pipeline.clone(),
+                    priority: args.priority,
+                    batch_id: Some(batch_id.clone()),
+                    intent_tags: intent_tags.clone(),
+                    metadata: metadata.clone(),
+                    detach: false,
+                    run_as: None,
+                    compression: compression::supported_encodings(),
+                    progress: false,
+                    create_cwd: false,
+                    readonly: false,
+                    trace_id: Some(trace_id.to_string()),
+                };
+                async move {
+                    let response = self.dispatch(&addr, &request, None).await;
+                    (request.target, FanOutEntry::from_response(response))
+                }
+            })
+            .buffer_unordered(max_parallel)
+            .collect()
+            .await;
+
+        Ok(fan_out_to_tool_result(&batch_id, trace_id, results))
+    }
+}
+
+impl ServerHandler for ProxyHandler {
+    fn get_info(&self) -> ServerInfo {
+        self.server_info.clone()
+    }
+
+    fn list_tools(
+        &self,
+        _: Option<PaginatedRequestParam>,
+        _: RequestContext<RoleServer>,
+    ) -> impl std::future::Future<Output = Result<ListToolsResult, McpError>> + Send + '_ {
+        async move {
+            let (targets, choices, default_target) = {
+                let state = self.state.read().await;
+                (
+                    state.target_names(),
+                    state.target_choices(),
+                    self.effective_default(&state),
+                )
+            };
+            let mut tools = vec![
+                self.tool_definition(&choices, default_target.as_ref()),
+                self.fan_out_definition(&choices),
+                self.list_targets_definition(),
+                self.set_default_target_definition(&choices),
+                self.history_definition(&targets),
+                self.denied_definition(&targets),
+                self.read_file_definition(&choices, default_target.as_ref()),
+                self.write_file_definition(&choices, default_target.as_ref()),
+                self.cancel_definition(&choices, default_target.as_ref()),
+            ];
+            if self.offline.is_some() {
+                tools.push(self.queued_result_definition());
+            }
+            Ok(ListToolsResult::with_all_items(tools))
+        }
+    }
+
+    fn call_tool(
+        &self,
+        request: CallToolRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> impl std::future::Future<Output = Result<CallToolResult, McpError>> + Send + '_ {
+        // 每次工具调用一个追踪 id，随请求带到 console，日志、审计记录与响应里都能按它串起来。
+        let trace_id = Uuid::new_v4().to_string();
+        let span = tracing::info_span!("tool_call", tool = %request.name, trace_id = %trace_id);
+        async move {
+            match request.name.as_ref() {
+                "run_command" => {
+                    let args = parse_arguments(request.arguments)
                         .map_err(|err| McpError::invalid_params(err, None))?;
+                    let pipeline = parse_pipeline(&args.command)
+                        .map_err(|err| McpError::invalid_params(err, None))?;
+                    let metadata = self.request_metadata(&context, args.metadata)?;
 
-                    let (target, addr) = {
+                    let (target, addr, cwd, queue_offline) = {
                         let state = self.state.read().await;
-                        let target = args
-                            .target
-                            .or_else(|| state.default_target())
-                            .ok_or_else(|| McpError::invalid_params("target is required", None))?;
+                        let target = self.select_target(&state, args.target)?;
                         let addr = state
                             .target_addr(&target)
                             .map_err(|err| McpError::invalid_params(err.to_string(), None))?;
-                        (target, addr)
+                        let cwd = args
+                            .cwd
+                            .filter(|cwd| !cwd.trim().is_empty())
+                            .or_else(|| state.default_cwd(&target, &self.client_id, &args.intent));
+                        let queue_offline = state.queue_when_unreachable(&target);
+                        (target, addr, cwd, queue_offline)
                     };
 
+                    let id = match args.request_id {
+                        Some(id) => validate_request_id(&id)
+                            .map(|_| id)
+                            .map_err(|err| McpError::invalid_params(err, None))?,
+                        None => Uuid::new_v4().to_string(),
+                    };
                     let mode = args.mode.unwrap_or(CommandMode::Shell);
+                    // 只有带 progress token 的调用才向 console 要进度帧，其余仍阻塞到出结果。
+                    let progress_token = context.meta.get_progress_token();
                     let request = CommandRequest {
-                        id: Uuid::new_v4().to_string(),
+                        id,
                         client: self.client_id.clone(),
                         target: target.clone(),
                         intent: args.intent,
                         mode,
                         raw_command: args.command.clone(),
-                        cwd: args.cwd,
+                        cwd,
                         env: args.env,
                         timeout_ms: Some(args.timeout_ms.unwrap_or(self.default_timeout_ms)),
                         max_output_bytes: Some(
@@ -225,38 +947,186 @@ impl ServerHandler for ProxyHandler {
                                 .unwrap_or(self.default_max_output_bytes),
                         ),
                         pipeline,
+                        priority: args.priority,
+                        batch_id: None,
+                        intent_tags: normalize_intent_tags(args.intent_tags),
+                        metadata,
+                        detach: args.detach,
+                        run_as: args.run_as,
+                        compression: compression::supported_encodings(),
+                        progress: progress_token.is_some(),
+                        create_cwd: args.create_cwd,
+                        readonly: args.readonly,
+                        trace_id: Some(trace_id.clone()),
                     };
 
-                    let response = match send_request(&addr, &request).await {
-                        Ok(response) => response,
-                        Err(err) => CommandResponse::error(request.id.clone(), err.to_string()),
+                    let forwarder = progress_token
+                        .map(|token| spawn_progress_forwarder(context.peer.clone(), token));
+                    let progress = forwarder.as_ref().map(|(sender, _)| sender);
+                    let result = match self.offline.as_ref().filter(|_| queue_offline) {
+                        Some(offline) => {
+                            self.dispatch_or_queue(offline, &addr, &request, progress)
+                                .await
+                        }
+                        None => {
+                            response_to_tool_result(self.dispatch(&addr, &request, progress).await)
+                        }
                     };
-
-                    {
-                        let mut state = self.state.write().await;
-                        match response.status {
-                            CommandStatus::Completed
-                            | CommandStatus::Denied
-                            | CommandStatus::Approved
-                            | CommandStatus::Cancelled => {
-                                state.note_success(&request.target);
-                            }
-                            CommandStatus::Error => {
-                                if let Some(error) = response.error.as_ref() {
-                                    state.note_failure(&request.target, error);
-                                }
-                            }
+                    // 等已收到的进度通知发完再返回结果，避免通知晚于结果到达客户端。
+                    if let Some((sender, handle)) = forwarder {
+                        drop(sender);
+                        let _ = handle.await;
+                    }
+                    Ok(result)
+                }
+                "get_queued_result" => {
+                    let Some(offline) = self.offline.as_ref() else {
+                        return Err(McpError::invalid_params(
+                            "offline queue is not enabled for any target",
+                            None,
+                        ));
+                    };
+                    let args = parse_queued_result_arguments(request.arguments)
+                        .map_err(|err| McpError::invalid_params(err, None))?;
+                    match offline.outcome(&args.id).await {
+                        Some(QueuedOutcome::Pending { position }) => {
+                            Ok(queued_to_tool_result(&args.id, position, None))
+                        }
+                        Some(QueuedOutcome::Finished(response)) => {
+                            Ok(response_to_tool_result(response))
                         }
+                        Some(QueuedOutcome::Expired) => Ok(expired_to_tool_result(&args.id)),
+                        None => Err(McpError::invalid_params(
+                            format!("unknown queued request id: {}", args.id),
+                            None,
+                        )),
                     }
-
+                }
+                "cancel_command" => {
+                    let args = parse_cancel_arguments(request.arguments)
+                        .map_err(|err| McpError::invalid_params(err, None))?;
+                    let (target, addr, control_token) = {
+                        let state = self.state.read().await;
+                        let target = self.select_target(&state, args.target)?;
+                        let addr = state
+                            .target_addr(&target)
+                            .map_err(|err| McpError::invalid_params(err.to_string(), None))?;
+                        (target, addr, state.control_token())
+                    };
+                    let cancel = CommandCancel::Cancel {
+                        target: target.clone(),
+                        id: args.id.clone(),
+                        client: self.client_id.clone(),
+                    };
+                    let outcome = send_cancel(&addr, control_token.as_deref(), &cancel)
+                        .await
+                        .map_err(|err| McpError::internal_error(format!("{err:#}"), None))?;
+                    Ok(cancel_to_tool_result(&target, &args.id, outcome))
+                }
+                "run_command_on_targets" => {
+                    let mut args = parse_fan_out_arguments(request.arguments)
+                        .map_err(|err| McpError::invalid_params(err, None))?;
+                    let pipeline = parse_pipeline(&args.command)
+                        .map_err(|err| McpError::invalid_params(err, None))?;
+                    let metadata = self.request_metadata(&context, args.metadata.take())?;
+                    self.run_on_targets(args, pipeline, metadata, &trace_id)
+                        .await
+                }
+                "read_file" => {
+                    let args = parse_read_file_arguments(request.arguments)
+                        .map_err(|err| McpError::invalid_params(err, None))?;
+                    let max_bytes = args.max_bytes.unwrap_or(DEFAULT_READ_FILE_MAX_BYTES);
+                    let command = read_command(&args.path, max_bytes, args.intent.as_deref())
+                        .map_err(|err| McpError::invalid_params(err, None))?;
+                    let metadata = self.request_metadata(&context, args.metadata)?;
+                    let (addr, request) = self
+                        .file_request(args.target, command, metadata, &trace_id)
+                        .await?;
+                    let response = self.dispatch(&addr, &request, None).await;
+                    Ok(match read_result(&args.path, max_bytes, &response) {
+                        Some(Ok(result)) => read_file_to_tool_result(result),
+                        Some(Err(err)) => response_to_tool_result(
+                            CommandResponse::error(response.id, err)
+                                .with_trace_id(response.trace_id),
+                        ),
+                        None => response_to_tool_result(response),
+                    })
+                }
+                "write_file" => {
+                    let args = parse_write_file_arguments(request.arguments)
+                        .map_err(|err| McpError::invalid_params(err, None))?;
+                    // 解码失败或超过大小限制时直接报参数错误，不下发命令。
+                    let command = write_command(
+                        &args.path,
+                        &args.content_base64,
+                        args.mode.as_deref(),
+                        self.write_file_max_bytes,
+                        args.intent.as_deref(),
+                    )
+                    .map_err(|err| McpError::invalid_params(err, None))?;
+                    let metadata = self.request_metadata(&context, args.metadata)?;
+                    let (addr, request) = self
+                        .file_request(args.target, command, metadata, &trace_id)
+                        .await?;
+                    let response = self.dispatch(&addr, &request, None).await;
                     Ok(response_to_tool_result(response))
                 }
                 "list_targets" => {
-                    let targets = {
+                    let args = parse_list_targets_arguments(request.arguments)
+                        .map_err(|err| McpError::invalid_params(err, None))?;
+                    let (targets, default_target) = {
                         let mut state = self.state.write().await;
-                        state.list_targets()
+                        let targets = state.list_targets(&self.client_id, args.intent.as_deref());
+                        (targets, self.effective_default(&state))
+                    };
+                    Ok(targets_to_tool_result(targets, default_target))
+                }
+                "set_default_target" => {
+                    let args = parse_set_default_target_arguments(request.arguments)
+                        .map_err(|err| McpError::invalid_params(err, None))?;
+                    let state = self.state.read().await;
+                    let pinned = args
+                        .target
+                        .filter(|name| !name.trim().is_empty())
+                        .map(|name| state.resolve_target(&name))
+                        .transpose()
+                        .map_err(|err| McpError::invalid_params(err.to_string(), None))?;
+                    *self
+                        .pinned_target
+                        .lock()
+                        .unwrap_or_else(|err| err.into_inner()) = pinned.clone();
+                    let default_target = self.effective_default(&state);
+                    Ok(default_target_to_tool_result(pinned, default_target))
+                }
+                "get_command_history" => {
+                    let args = parse_history_arguments(request.arguments)
+                        .map_err(|err| McpError::invalid_params(err, None))?;
+                    let limit = args
+                        .limit
+                        .unwrap_or(DEFAULT_HISTORY_LIMIT)
+                        .clamp(1, MAX_HISTORY_LIMIT);
+                    let entries = match self.history.as_ref() {
+                        Some(history) => history.recent(args.target.as_deref(), None, limit).await,
+                        None => Vec::new(),
                     };
-                    Ok(targets_to_tool_result(targets))
+                    Ok(history_to_tool_result(entries, self.history.is_some()))
+                }
+                "get_denied_requests" => {
+                    let args = parse_history_arguments(request.arguments)
+                        .map_err(|err| McpError::invalid_params(err, None))?;
+                    let limit = args
+                        .limit
+                        .unwrap_or(DEFAULT_HISTORY_LIMIT)
+                        .clamp(1, MAX_HISTORY_LIMIT);
+                    let entries = match self.history.as_ref() {
+                        Some(history) => {
+                            history
+                                .recent(args.target.as_deref(), Some(&CommandStatus::Denied), limit)
+                                .await
+                        }
+                        None => Vec::new(),
+                    };
+                    Ok(history_to_tool_result(entries, self.history.is_some()))
                 }
                 _ => Err(McpError::invalid_params(
                     format!("unknown tool: {}", request.name),
@@ -264,6 +1134,7 @@ impl ServerHandler for ProxyHandler {
                 )),
             }
         }
+        .instrument(span)
     }
 }
 
@@ -274,9 +1145,86 @@ struct RunCommandArgs {
     target: Option<String>,
     mode: Option<CommandMode>,
     cwd: Option<String>,
+    #[serde(default)]
+    create_cwd: bool,
+    #[serde(default)]
+    readonly: bool,
     timeout_ms: Option<u64>,
     max_output_bytes: Option<u64>,
     env: Option<BTreeMap<String, String>>,
+    priority: Option<RequestPriority>,
+    #[serde(default)]
+    intent_tags: Vec<String>,
+    metadata: Option<BTreeMap<String, String>>,
+    #[serde(default)]
+    detach: bool,
+    run_as: Option<String>,
+    request_id: Option<String>,
+}
+
+const MAX_REQUEST_ID_LEN: usize = 64;
+
+// 调用方自带的 id 会出现在审计路径与 console URL 中，只允许安全字符。
+fn validate_request_id(id: &str) -> Result<(), String> {
+    if id.is_empty() || id.len() > MAX_REQUEST_ID_LEN {
+        return Err(format!(
+            "request_id must be 1-{MAX_REQUEST_ID_LEN} characters"
+        ));
+    }
+    if !id
+        .chars()
+        .all(|ch| ch.is_ascii_alphanumeric() || matches!(ch, '.' | '_' | '-'))
+        || id.starts_with('.')
+    {
+        return Err("request_id may only contain A-Z, a-z, 0-9, '.', '_' and '-'".to_string());
+    }
+    Ok(())
+}
+
+// 从 MCP 请求 `_meta` 中识别的键；同时接受 camelCase 写法。
+const META_KEYS: [(&str, &str); 3] = [
+    ("agent", "agent"),
+    ("session_id", "sessionId"),
+    ("tool_call_id", "toolCallId"),
+];
+
+fn metadata_schema() -> Value {
+    json!({
+        "type": "object",
+        "additionalProperties": { "type": "string" },
+        "description": "Optional caller context shown to the operator and kept in the audit log, e.g. {\"agent\": \"codex\", \"session_id\": \"...\", \"tool_call_id\": \"...\"}. At most 16 keys, 256 bytes per value."
+    })
+}
+
+/// 优先级从低到高：proxy 配置 `[defaults.metadata]`、MCP clientInfo.name（作为 agent）、
+/// 请求 `_meta` 中的 agent/session_id/tool_call_id、工具参数 `metadata`。空值丢弃。
+fn merge_metadata(
+    defaults: &BTreeMap<String, String>,
+    client_name: Option<&str>,
+    meta: &JsonObject,
+    supplied: Option<BTreeMap<String, String>>,
+) -> Result<Option<BTreeMap<String, String>>, String> {
+    let mut metadata = defaults.clone();
+    if let Some(name) = client_name {
+        metadata.insert("agent".to_string(), name.to_string());
+    }
+    for (key, alias) in META_KEYS {
+        let value = meta
+            .get(key)
+            .or_else(|| meta.get(alias))
+            .and_then(Value::as_str);
+        if let Some(value) = value {
+            metadata.insert(key.to_string(), value.to_string());
+        }
+    }
+    metadata.extend(supplied.unwrap_or_default());
+    let metadata: BTreeMap<String, String> = metadata
+        .into_iter()
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .filter(|(_, value)| !value.is_empty())
+        .collect();
+    validate_request_metadata(&metadata)?;
+    Ok((!metadata.is_empty()).then_some(metadata))
 }
 
 fn parse_arguments(args: Option<JsonObject>) -> Result<RunCommandArgs, String> {
@@ -284,6 +1232,274 @@ fn parse_arguments(args: Option<JsonObject>) -> Result<RunCommandArgs, String> {
     serde_json::from_value(Value::Object(map)).map_err(|err| err.to_string())
 }
 
+const DEFAULT_FAN_OUT_PARALLEL: usize = 4;
+const STDOUT_PREVIEW_MAX_CHARS: usize = 2000;
+
+#[derive(Debug, Deserialize)]
+struct FanOutArgs {
+    command: String,
+    intent: String,
+    targets: TargetSelection,
+    max_parallel: Option<usize>,
+    mode: Option<CommandMode>,
+    cwd: Option<String>,
+    timeout_ms: Option<u64>,
+    max_output_bytes: Option<u64>,
+    env: Option<BTreeMap<String, String>>,
+    priority: Option<RequestPriority>,
+    #[serde(default)]
+    intent_tags: Vec<String>,
+    metadata: Option<BTreeMap<String, String>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum TargetSelection {
+    All(String),
+    List(Vec<String>),
+}
+
+impl TargetSelection {
+    fn resolve(
+        &self,
+        configured: &[String],
+        resolve_name: impl Fn(&str) -> anyhow::Result<String>,
+    ) -> Result<Vec<String>, McpError> {
+        let names = match self {
+            TargetSelection::All(value) if value == "*" => configured.to_vec(),
+            TargetSelection::All(value) => {
+                return Err(McpError::invalid_params(
+                    format!("targets must be a list of names or \"*\", got {value:?}"),
+                    None,
+                ))
+            }
+            TargetSelection::List(names) => {
+                let mut unique = Vec::with_capacity(names.len());
+                for name in names {
+                    let name = resolve_name(name)
+                        .map_err(|err| McpError::invalid_params(err.to_string(), None))?;
+                    if !unique.contains(&name) {
+                        unique.push(name);
+                    }
+                }
+                unique
+            }
+        };
+        if names.is_empty() {
+            return Err(McpError::invalid_params("targets is empty", None));
+        }
+        Ok(names)
+    }
+}
+
+fn parse_fan_out_arguments(args: Option<JsonObject>) -> Result<FanOutArgs, String> {
+    let map = args.ok_or_else(|| "missing arguments".to_string())?;
+    serde_json::from_value(Value::Object(map)).map_err(|err| err.to_string())
+}
+
+#[derive(Debug, Serialize)]
+struct FanOutEntry {
+    id: String,
+    status: CommandStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exit_code: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stdout_preview: Option<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    stdout_truncated: bool,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    binary_output: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    modified_by_operator: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    executed_command: Option<String>,
+}
+
+impl FanOutEntry {
+    fn from_response(response: CommandResponse) -> Self {
+        let binary_output = response.binary_output();
+        let (stdout_preview, stdout_truncated) = match response.stdout {
+            Some(stdout) => {
+                let truncated = stdout.chars().count() > STDOUT_PREVIEW_MAX_CHARS;
+                let preview = if truncated {
+                    stdout.chars().take(STDOUT_PREVIEW_MAX_CHARS).collect()
+                } else {
+                    stdout
+                };
+                (Some(preview), truncated)
+            }
+            None => (None, false),
+        };
+        Self {
+            id: response.id,
+            status: response.status,
+            exit_code: response.exit_code,
+            stdout_preview,
+            stdout_truncated,
+            binary_output,
+            error: response.error,
+            modified_by_operator: response.modified_by_operator,
+            executed_command: response.executed_command,
+        }
+    }
+
+    fn is_failure(&self) -> bool {
+        matches!(
+            self.status,
+            CommandStatus::Error | CommandStatus::Denied | CommandStatus::Cancelled
+        )
+    }
+}
+
+fn fan_out_to_tool_result(
+    batch_id: &str,
+    trace_id: &str,
+    results: BTreeMap<String, FanOutEntry>,
+) -> CallToolResult {
+    // 只有全部目标都失败时才标记 is_error；部分失败在各自条目中体现。
+    let all_failed = results.values().all(FanOutEntry::is_failure);
+    let payload = json!({ "batch_id": batch_id, "trace_id": trace_id, "results": results });
+    let text = serde_json::to_string_pretty(&payload).unwrap_or_else(|_| "{}".to_string());
+    CallToolResult {
+        content: vec![Content::text(text)],
+        is_error: Some(all_failed),
+        meta: None,
+        structured_content: Some(payload),
+    }
+}
+
+fn file_target_properties(
+    targets: &[String],
+    default_target: Option<&String>,
+) -> Map<String, Value> {
+    let mut properties = Map::new();
+    let mut target_schema = json!({
+        "type": "string",
+        "enum": targets,
+        "description": "Target name or alias defined in octovalve-proxy config."
+    });
+    if let Some(default) = default_target {
+        target_schema["default"] = json!(default);
+    }
+    properties.insert("target".to_string(), target_schema);
+    properties.insert(
+        "path".to_string(),
+        json!({
+            "type": "string",
+            "description": "File path on the target. Prefer absolute paths; relative paths resolve against the target's default_cwd, otherwise /tmp ."
+        }),
+    );
+    properties.insert(
+        "intent".to_string(),
+        json!({
+            "type": "string",
+            "description": "Optional reason, appended to the generated intent (e.g. \"read /etc/hosts\")."
+        }),
+    );
+    properties.insert("metadata".to_string(), metadata_schema());
+    properties
+}
+
+fn file_required(default_target: Option<&String>, extra: &[&str]) -> Value {
+    let mut required = vec!["path"];
+    required.extend_from_slice(extra);
+    if default_target.is_none() {
+        required.push("target");
+    }
+    json!(required)
+}
+
+#[derive(Debug, Deserialize)]
+struct ReadFileArgs {
+    path: String,
+    target: Option<String>,
+    max_bytes: Option<u64>,
+    intent: Option<String>,
+    metadata: Option<BTreeMap<String, String>>,
+}
+
+fn parse_read_file_arguments(args: Option<JsonObject>) -> Result<ReadFileArgs, String> {
+    let map = args.ok_or_else(|| "missing arguments".to_string())?;
+    serde_json::from_value(Value::Object(map)).map_err(|err| err.to_string())
+}
+
+#[derive(Debug, Deserialize)]
+struct WriteFileArgs {
+    path: String,
+    content_base64: String,
+    target: Option<String>,
+    mode: Option<String>,
+    intent: Option<String>,
+    metadata: Option<BTreeMap<String, String>>,
+}
+
+fn parse_write_file_arguments(args: Option<JsonObject>) -> Result<WriteFileArgs, String> {
+    let map = args.ok_or_else(|| "missing arguments".to_string())?;
+    serde_json::from_value(Value::Object(map)).map_err(|err| err.to_string())
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ListTargetsArgs {
+    intent: Option<String>,
+}
+
+fn parse_list_targets_arguments(args: Option<JsonObject>) -> Result<ListTargetsArgs, String> {
+    match args {
+        Some(map) => serde_json::from_value(Value::Object(map)).map_err(|err| err.to_string()),
+        None => Ok(ListTargetsArgs::default()),
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct SetDefaultTargetArgs {
+    target: Option<String>,
+}
+
+fn parse_set_default_target_arguments(
+    args: Option<JsonObject>,
+) -> Result<SetDefaultTargetArgs, String> {
+    match args {
+        Some(map) => serde_json::from_value(Value::Object(map)).map_err(|err| err.to_string()),
+        None => Ok(SetDefaultTargetArgs::default()),
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct HistoryArgs {
+    target: Option<String>,
+    limit: Option<usize>,
+}
+
+fn parse_history_arguments(args: Option<JsonObject>) -> Result<HistoryArgs, String> {
+    match args {
+        Some(map) => serde_json::from_value(Value::Object(map)).map_err(|err| err.to_string()),
+        None => Ok(HistoryArgs::default()),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct QueuedResultArgs {
+    id: String,
+}
+
+fn parse_queued_result_arguments(args: Option<JsonObject>) -> Result<QueuedResultArgs, String> {
+    let map = args.ok_or_else(|| "missing arguments".to_string())?;
+    serde_json::from_value(Value::Object(map)).map_err(|err| err.to_string())
+}
+
+#[derive(Debug, Deserialize)]
+struct CancelArgs {
+    id: String,
+    target: Option<String>,
+}
+
+fn parse_cancel_arguments(args: Option<JsonObject>) -> Result<CancelArgs, String> {
+    let map = args.ok_or_else(|| "missing arguments".to_string())?;
+    serde_json::from_value(Value::Object(map)).map_err(|err| err.to_string())
+}
+
 fn parse_pipeline(command: &str) -> Result<Vec<CommandStage>, String> {
     let tokens = shell_words::split(command).map_err(|err| err.to_string())?;
     if tokens.is_empty() {
@@ -309,7 +1525,88 @@ fn parse_pipeline(command: &str) -> Result<Vec<CommandStage>, String> {
     Ok(pipeline)
 }
 
-async fn send_request(addr: &str, request: &CommandRequest) -> anyhow::Result<CommandResponse> {
+// 连接阶段就失败（请求一定没有发出），离线队列据此判断可以安全地稍后补发。
+#[derive(Debug)]
+struct Unreachable(std::io::Error);
+
+impl std::fmt::Display for Unreachable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl std::error::Error for Unreachable {}
+
+/// 发送请求并更新转发统计与本地历史。连不上 console 时返回 Err（请求未送达、不记历史），
+/// 由调用方决定报错还是离线排队；其余错误都转成 Error 响应。
+pub(crate) async fn deliver(
+    state: &RwLock<ProxyState>,
+    history: Option<&CommandHistory>,
+    addr: &str,
+    request: &CommandRequest,
+    progress: Option<&ProgressSender>,
+) -> anyhow::Result<CommandResponse> {
+    let control_token = {
+        let mut state = state.write().await;
+        state.begin_command(&request.target);
+        state.control_token()
+    };
+    let response = match send_request(addr, control_token.as_deref(), request, progress).await {
+        Ok(response) => response,
+        Err(err) if err.downcast_ref::<Unreachable>().is_some() => {
+            let mut state = state.write().await;
+            state.finish_command(&request.target);
+            state.note_failure(&request.target, &err.to_string());
+            return Err(err);
+        }
+        Err(err) => CommandResponse::error(request.id.clone(), err.to_string())
+            .with_trace_id(request.trace_id.clone()),
+    };
+
+    let mut state = state.write().await;
+    state.finish_command(&request.target);
+    match response.status {
+        CommandStatus::Completed
+        | CommandStatus::Denied
+        | CommandStatus::Approved
+        | CommandStatus::Cancelled
+        | CommandStatus::Detached => {
+            state.note_success(&request.target);
+        }
+        CommandStatus::Error => {
+            if let Some(error) = response.error.as_ref() {
+                state.note_failure(&request.target, error);
+            }
+        }
+    }
+    drop(state);
+    if let Some(history) = history {
+        history.record(&HistoryEntry::new(request, &response)).await;
+    }
+    Ok(response)
+}
+
+pub(crate) async fn record_error(
+    history: Option<&CommandHistory>,
+    request: &CommandRequest,
+    message: String,
+) -> CommandResponse {
+    let response =
+        CommandResponse::error(request.id.clone(), message).with_trace_id(request.trace_id.clone());
+    if let Some(history) = history {
+        history.record(&HistoryEntry::new(request, &response)).await;
+    }
+    response
+}
+
+// console 拒绝认证时的统一错误文本，会作为目标的 last_error 展示，便于与网络错误区分。
+const CONTROL_TOKEN_REJECTED: &str =
+    "console rejected the control token (check control_token / --control-token-file)";
+
+async fn connect(
+    addr: &str,
+    control_token: Option<&str>,
+) -> anyhow::Result<Framed<TcpStream, LengthDelimitedCodec>> {
     let mut last_err = None;
     for attempt in 0..3 {
         match TcpStream::connect(addr).await {
@@ -318,16 +1615,13 @@ async fn send_request(addr: &str, request: &CommandRequest) -> anyhow::Result<Co
                     .max_frame_length(protocol::framing::MAX_FRAME_LENGTH)
                     .new_codec();
                 let mut framed = Framed::new(stream, codec);
-                let payload = serde_json::to_vec(request)?;
-                framed.send(Bytes::from(payload)).await?;
-
-                let response = framed
-                    .next()
-                    .await
-                    .context("connection closed")?
-                    .context("read response")?;
-                let response: CommandResponse = serde_json::from_slice(&response)?;
-                return Ok(response);
+                if let Some(token) = control_token {
+                    let auth = CommandAuth::Auth {
+                        token: token.to_string(),
+                    };
+                    framed.send(Bytes::from(serde_json::to_vec(&auth)?)).await?;
+                }
+                return Ok(framed);
             }
             Err(err) => {
                 last_err = Some(err);
@@ -339,15 +1633,129 @@ async fn send_request(addr: &str, request: &CommandRequest) -> anyhow::Result<Co
     }
 
     let err = last_err
-        .map(|err| anyhow::anyhow!(err))
+        .map(|err| anyhow::Error::new(Unreachable(err)))
         .unwrap_or_else(|| anyhow::anyhow!("failed to connect to {addr}"));
     Err(err).with_context(|| format!("failed to connect to {addr}"))
 }
 
+// 读取一帧回复；console 拒绝认证时统一报 CONTROL_TOKEN_REJECTED。
+async fn read_reply(framed: &mut Framed<TcpStream, LengthDelimitedCodec>) -> anyhow::Result<Bytes> {
+    let reply = framed
+        .next()
+        .await
+        .context("connection closed")?
+        .context("read response")?;
+    if let Ok(CommandAuth::AuthRejected) = serde_json::from_slice(&reply) {
+        anyhow::bail!(CONTROL_TOKEN_REJECTED);
+    }
+    let reply = reply.freeze();
+    let decoded = compression::decode_frame(&reply).context("decompress response")?;
+    if decoded.len() != reply.len() {
+        tracing::debug!(
+            compressed_bytes = reply.len(),
+            bytes = decoded.len(),
+            "decompressed command response"
+        );
+    }
+    Ok(Bytes::from(decoded.into_owned()))
+}
+
+// 请求带 progress 时，最终响应之前的进度帧逐个转给 `progress`。
+async fn send_request(
+    addr: &str,
+    control_token: Option<&str>,
+    request: &CommandRequest,
+    progress: Option<&ProgressSender>,
+) -> anyhow::Result<CommandResponse> {
+    let mut framed = connect(addr, control_token).await?;
+    framed
+        .send(Bytes::from(serde_json::to_vec(request)?))
+        .await?;
+    loop {
+        let reply = read_reply(&mut framed).await?;
+        if let Some(frame) = CommandProgress::from_frame(&reply) {
+            if let Some(progress) = progress {
+                let _ = progress.send(frame);
+            }
+            continue;
+        }
+        let response: CommandResponse = serde_json::from_slice(&reply)?;
+        return Ok(response);
+    }
+}
+
+type ProgressSender = mpsc::UnboundedSender<CommandProgress>;
+
+/// 把收到的进度帧转成 MCP progress 通知，直到发送端被丢弃或客户端断开。
+fn spawn_progress_forwarder(
+    peer: Peer<RoleServer>,
+    token: ProgressToken,
+) -> (ProgressSender, JoinHandle<()>) {
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let handle = tokio::spawn(async move {
+        let mut sequence = 0;
+        while let Some(frame) = receiver.recv().await {
+            sequence += 1;
+            let notification = progress_notification(&token, sequence, &frame);
+            if let Err(err) = peer.notify_progress(notification).await {
+                tracing::debug!(error = %err, "failed to send progress notification");
+                break;
+            }
+        }
+    });
+    (sender, handle)
+}
+
+// 审批等待时长未知，不给 total；progress 按帧序号递增，满足协议要求的单调性。
+fn progress_notification(
+    token: &ProgressToken,
+    sequence: u32,
+    frame: &CommandProgress,
+) -> ProgressNotificationParam {
+    let CommandProgress::Progress {
+        stage,
+        elapsed_secs,
+        position,
+        ..
+    } = frame;
+    let message = match (stage, position) {
+        (ProgressStage::Queued, Some(position)) => {
+            format!("waiting for approval, queued {elapsed_secs}s, position {position}")
+        }
+        (ProgressStage::Queued, None) => format!("waiting for approval, queued {elapsed_secs}s"),
+        (ProgressStage::Executing, _) => format!("executing, {elapsed_secs}s since submitted"),
+    };
+    ProgressNotificationParam {
+        progress_token: token.clone(),
+        progress: f64::from(sequence),
+        total: None,
+        message: Some(message),
+    }
+}
+
+async fn send_cancel(
+    addr: &str,
+    control_token: Option<&str>,
+    cancel: &CommandCancel,
+) -> anyhow::Result<CancelOutcome> {
+    let mut framed = connect(addr, control_token).await?;
+    framed
+        .send(Bytes::from(serde_json::to_vec(cancel)?))
+        .await?;
+    match serde_json::from_slice(&read_reply(&mut framed).await?) {
+        Ok(CommandCancel::CancelResult { outcome, .. }) => Ok(outcome),
+        // 旧版 console 把取消帧当作无效请求，回的是普通错误响应。
+        _ => anyhow::bail!("console does not support cancel_command; upgrade the console"),
+    }
+}
+
 fn response_to_tool_result(response: CommandResponse) -> CallToolResult {
     let id = format!("id: {}", response.id);
     let status = format!("status: {:?}", response.status);
     let mut message = vec![id, status];
+    if let Some(trace_id) = response.trace_id.as_ref() {
+        message.push(format!("trace_id: {trace_id}"));
+    }
     if let Some(code) = response.exit_code {
         message.push(format!("exit_code: {code}"));
     }
@@ -360,9 +1768,29 @@ fn response_to_tool_result(response: CommandResponse) -> CallToolResult {
     if let Some(error) = response.error.as_ref() {
         message.push(format!("error: {error}"));
     }
+    if response.status == CommandStatus::Detached {
+        message.push(
+            "The command is running detached on the console; its output and exit code are recorded under this id.".to_string(),
+        );
+    }
+    if response.modified_by_operator {
+        message.push("modified_by_operator: true".to_string());
+        if let Some(command) = response.executed_command.as_ref() {
+            message.push(format!("executed_command: {command}"));
+        }
+    }
+    let binary_output = response.binary_output();
+    if binary_output {
+        message.push(
+            "binary_output: true (output is not valid UTF-8; raw bytes are in stdout_base64/stderr_base64)".to_string(),
+        );
+    }
 
     let text = message.join("\n");
     let mut structured = serde_json::to_value(&response).ok();
+    if let Some(Value::Object(map)) = structured.as_mut() {
+        map.insert("binary_output".to_string(), Value::Bool(binary_output));
+    }
 
     if matches!(
         response.status,
@@ -384,8 +1812,109 @@ fn response_to_tool_result(response: CommandResponse) -> CallToolResult {
     }
 }
 
-fn targets_to_tool_result(targets: Vec<TargetListEntry>) -> CallToolResult {
-    let payload = json!({ "targets": targets });
+fn read_file_to_tool_result(result: ReadFileResult) -> CallToolResult {
+    let mut message = vec![
+        format!("id: {}", result.id),
+        "status: Completed".to_string(),
+        format!("path: {}", result.path),
+        format!("bytes: {}", result.bytes),
+    ];
+    if result.truncated {
+        message.push("truncated: true (raise max_bytes to read more)".to_string());
+    }
+    // 文本内容直接附上方便阅读；二进制内容只在 structured content 的 content_base64 中。
+    match result.text() {
+        Some(text) => message.push(format!("content:\n{text}")),
+        None => message.push("binary content; see content_base64".to_string()),
+    }
+    let payload = serde_json::to_value(&result).ok();
+    CallToolResult {
+        content: vec![Content::text(message.join("\n"))],
+        is_error: Some(false),
+        meta: None,
+        structured_content: payload,
+    }
+}
+
+fn queued_to_tool_result(id: &str, position: usize, reason: Option<&str>) -> CallToolResult {
+    let mut payload = json!({ "id": id, "status": "queued_offline", "position": position });
+    if let Some(reason) = reason {
+        payload["error"] = json!(reason);
+    }
+    let text = format!(
+        "id: {id}\nstatus: queued_offline\nposition: {position}\nThe console is unreachable; the request will be sent when it comes back. Poll get_queued_result with this id."
+    );
+    CallToolResult {
+        content: vec![Content::text(text)],
+        is_error: Some(false),
+        meta: None,
+        structured_content: Some(payload),
+    }
+}
+
+fn cancel_to_tool_result(target: &str, id: &str, outcome: CancelOutcome) -> CallToolResult {
+    let payload = json!({ "target": target, "id": id, "outcome": outcome });
+    let note = match outcome {
+        CancelOutcome::Cancelled => "The request was removed from the approval queue or its command was told to stop; the original run_command call returns status Cancelled.",
+        CancelOutcome::NotFound => "No pending or running request with this id was submitted by this proxy on the target.",
+        CancelOutcome::AlreadyFinished => "The request already finished; nothing was cancelled.",
+    };
+    let outcome = serde_json::to_value(outcome)
+        .ok()
+        .and_then(|value| value.as_str().map(str::to_string))
+        .unwrap_or_default();
+    CallToolResult {
+        content: vec![Content::text(format!(
+            "id: {id}\ntarget: {target}\noutcome: {outcome}\n{note}"
+        ))],
+        is_error: Some(false),
+        meta: None,
+        structured_content: Some(payload),
+    }
+}
+
+fn expired_to_tool_result(id: &str) -> CallToolResult {
+    let payload = json!({ "id": id, "status": "expired", "is_error": true });
+    CallToolResult {
+        content: vec![Content::text(format!(
+            "id: {id}\nstatus: expired\nThe request was dropped from the offline queue before the console became reachable."
+        ))],
+        is_error: Some(true),
+        meta: None,
+        structured_content: Some(payload),
+    }
+}
+
+fn history_to_tool_result(entries: Vec<HistoryEntry>, enabled: bool) -> CallToolResult {
+    let payload = json!({ "enabled": enabled, "entries": entries });
+    let text = serde_json::to_string_pretty(&payload).unwrap_or_else(|_| "{}".to_string());
+    CallToolResult {
+        content: vec![Content::text(text)],
+        is_error: Some(false),
+        meta: None,
+        structured_content: Some(payload),
+    }
+}
+
+fn targets_to_tool_result(
+    targets: Vec<TargetListEntry>,
+    default_target: Option<String>,
+) -> CallToolResult {
+    let payload = json!({ "targets": targets, "default_target": default_target });
+    let text = serde_json::to_string_pretty(&payload).unwrap_or_else(|_| "{}".to_string());
+    CallToolResult {
+        content: vec![Content::text(text)],
+        is_error: Some(false),
+        meta: None,
+        structured_content: Some(payload),
+    }
+}
+
+fn default_target_to_tool_result(
+    pinned: Option<String>,
+    default_target: Option<String>,
+) -> CallToolResult {
+    let payload = json!({ "pinned_target": pinned, "default_target": default_target });
     let text = serde_json::to_string_pretty(&payload).unwrap_or_else(|_| "{}".to_string());
     CallToolResult {
         content: vec![Content::text(text)],
@@ -417,9 +1946,218 @@ mod tests {
         );
     }
 
+    #[test]
+    fn merges_metadata_by_precedence() {
+        let defaults = BTreeMap::from([
+            ("agent".to_string(), "from-config".to_string()),
+            ("team".to_string(), "infra".to_string()),
+        ]);
+        let mut meta = JsonObject::new();
+        meta.insert("sessionId".to_string(), json!("s-1"));
+        meta.insert("tool_call_id".to_string(), json!("call-1"));
+        let supplied = BTreeMap::from([
+            ("tool_call_id".to_string(), "call-2".to_string()),
+            ("team".to_string(), " ".to_string()),
+        ]);
+        let merged = merge_metadata(&defaults, Some("claude-desktop"), &meta, Some(supplied))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            merged,
+            BTreeMap::from([
+                ("agent".to_string(), "claude-desktop".to_string()),
+                ("session_id".to_string(), "s-1".to_string()),
+                ("tool_call_id".to_string(), "call-2".to_string()),
+            ])
+        );
+
+        let empty = merge_metadata(&BTreeMap::new(), None, &JsonObject::new(), None).unwrap();
+        assert!(empty.is_none());
+        let oversized = BTreeMap::from([("note".to_string(), "x".repeat(300))]);
+        assert!(
+            merge_metadata(&BTreeMap::new(), None, &JsonObject::new(), Some(oversized))
+                .unwrap_err()
+                .contains("note")
+        );
+    }
+
+    #[test]
+    fn fan_out_resolves_target_selection() {
+        let configured = vec!["a".to_string(), "b".to_string()];
+        let resolve_name = |name: &str| match name {
+            "a" | "b" => Ok(name.to_string()),
+            "alpha" => Ok("a".to_string()),
+            _ => Err(anyhow::anyhow!("unknown target {name:?}")),
+        };
+        let all: TargetSelection = serde_json::from_value(json!("*")).unwrap();
+        assert_eq!(all.resolve(&configured, resolve_name).unwrap(), configured);
+
+        let list: TargetSelection =
+            serde_json::from_value(json!(["b", "alpha", "b", "a"])).unwrap();
+        assert_eq!(
+            list.resolve(&configured, resolve_name).unwrap(),
+            vec!["b", "a"]
+        );
+
+        let unknown: TargetSelection = serde_json::from_value(json!(["c"])).unwrap();
+        assert!(unknown.resolve(&configured, resolve_name).is_err());
+        let bad: TargetSelection = serde_json::from_value(json!("a")).unwrap();
+        assert!(bad.resolve(&configured, resolve_name).is_err());
+        let empty: TargetSelection = serde_json::from_value(json!([])).unwrap();
+        assert!(empty.resolve(&configured, resolve_name).is_err());
+    }
+
+    #[test]
+    fn fan_out_entry_truncates_stdout_preview() {
+        let stdout = "x".repeat(STDOUT_PREVIEW_MAX_CHARS + 10);
+        let entry =
+            FanOutEntry::from_response(CommandResponse::completed("req", 0, Some(stdout), None));
+        assert_eq!(
+            entry.stdout_preview.as_deref().map(str::len),
+            Some(STDOUT_PREVIEW_MAX_CHARS)
+        );
+        assert!(entry.stdout_truncated);
+        assert!(!entry.is_failure());
+    }
+
     #[test]
     fn parse_rejects_empty_segment() {
         let err = parse_pipeline("ls | | grep foo").unwrap_err();
         assert!(err.contains("empty pipeline segment"));
     }
+
+    #[test]
+    fn validates_caller_supplied_request_id() {
+        assert!(validate_request_id("deploy-42.retry_1").is_ok());
+        assert!(validate_request_id("").is_err());
+        assert!(validate_request_id(".hidden").is_err());
+        assert!(validate_request_id("../etc").is_err());
+        assert!(validate_request_id("a b").is_err());
+        assert!(validate_request_id(&"x".repeat(MAX_REQUEST_ID_LEN + 1)).is_err());
+    }
+
+    #[tokio::test]
+    async fn cancel_round_trips_through_control_channel() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let server = tokio::spawn(async move {
+            // 第二条连接模拟不认识取消帧的旧版 console。
+            for legacy in [false, true] {
+                let (stream, _) = listener.accept().await.unwrap();
+                let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+                let auth = framed.next().await.unwrap().unwrap();
+                assert_eq!(
+                    serde_json::from_slice::<CommandAuth>(&auth).unwrap(),
+                    CommandAuth::Auth {
+                        token: "s3cret".to_string()
+                    }
+                );
+                let frame = framed.next().await.unwrap().unwrap();
+                let cancel: CommandCancel = serde_json::from_slice(&frame).unwrap();
+                let CommandCancel::Cancel { target, id, client } = cancel else {
+                    panic!("expected a cancel frame");
+                };
+                assert_eq!((target.as_str(), client.as_str()), ("dev", "proxy"));
+                let reply = if legacy {
+                    serde_json::to_vec(&CommandResponse::error("invalid", "invalid request"))
+                } else {
+                    serde_json::to_vec(&CommandCancel::CancelResult {
+                        id,
+                        outcome: CancelOutcome::AlreadyFinished,
+                    })
+                };
+                framed.send(Bytes::from(reply.unwrap())).await.unwrap();
+            }
+        });
+
+        let cancel = CommandCancel::Cancel {
+            target: "dev".to_string(),
+            id: "req-1".to_string(),
+            client: "proxy".to_string(),
+        };
+        assert_eq!(
+            send_cancel(&addr, Some("s3cret"), &cancel).await.unwrap(),
+            CancelOutcome::AlreadyFinished
+        );
+        let err = send_cancel(&addr, Some("s3cret"), &cancel)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("does not support"), "{err}");
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn progress_frames_become_notifications_until_response() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+            let frame = framed.next().await.unwrap().unwrap();
+            let request: CommandRequest = serde_json::from_slice(&frame).unwrap();
+            assert!(request.progress);
+            let frames = [
+                (ProgressStage::Queued, 0, None),
+                (ProgressStage::Queued, 10, Some(2)),
+                (ProgressStage::Queued, 20, Some(1)),
+                (ProgressStage::Executing, 30, None),
+            ];
+            for (stage, elapsed_secs, position) in frames {
+                let progress = CommandProgress::Progress {
+                    id: request.id.clone(),
+                    stage,
+                    elapsed_secs,
+                    position,
+                };
+                let payload = serde_json::to_vec(&progress).unwrap();
+                framed.send(Bytes::from(payload)).await.unwrap();
+            }
+            let response = CommandResponse::completed(request.id, 0, Some("ok".to_string()), None);
+            let payload = serde_json::to_vec(&response).unwrap();
+            framed.send(Bytes::from(payload)).await.unwrap();
+        });
+
+        let request = CommandRequest {
+            id: "req-1".to_string(),
+            client: "proxy".to_string(),
+            target: "dev".to_string(),
+            intent: "inspect".to_string(),
+            raw_command: "ls".to_string(),
+            pipeline: parse_pipeline("ls").unwrap(),
+            progress: true,
+            ..Default::default()
+        };
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let response = send_request(&addr, None, &request, Some(&sender))
+            .await
+            .unwrap();
+        assert_eq!(response.status, CommandStatus::Completed);
+        server.await.unwrap();
+        drop(sender);
+
+        let token = ProgressToken(rmcp::model::NumberOrString::String("tok".into()));
+        let mut notifications = Vec::new();
+        while let Some(frame) = receiver.recv().await {
+            let sequence = notifications.len() as u32 + 1;
+            notifications.push(progress_notification(&token, sequence, &frame));
+        }
+        let progress: Vec<f64> = notifications.iter().map(|item| item.progress).collect();
+        assert_eq!(progress, [1.0, 2.0, 3.0, 4.0]);
+        assert!(notifications
+            .iter()
+            .all(|item| item.progress_token == token));
+        let messages: Vec<&str> = notifications
+            .iter()
+            .map(|item| item.message.as_deref().unwrap())
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "waiting for approval, queued 0s",
+                "waiting for approval, queued 10s, position 2",
+                "waiting for approval, queued 20s, position 1",
+                "executing, 30s since submitted",
+            ]
+        );
+    }
 }
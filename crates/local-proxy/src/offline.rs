@@ -0,0 +1,388 @@
+use crate::history::{file_stem, CommandHistory};
+use crate::mcp::{deliver, record_error};
+use crate::state::ProxyState;
+use protocol::{CommandRequest, CommandResponse};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::{Mutex, RwLock};
+use tokio_util::sync::CancellationToken;
+
+// 已送达/作废的结果只在内存中保留最近这么多条，供 get_queued_result 查询。
+const MAX_FINISHED: usize = 200;
+const FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+
+// 连不上 console 时暂存的请求：按入队顺序落盘，恢复连通后由后台任务按目标补发。
+// submitted 在发出前落盘，避免 console 已收到的请求在重启后被再发一次。
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct QueuedRequest {
+    queued_at_ms: u64,
+    request: CommandRequest,
+    #[serde(default)]
+    submitted: bool,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) enum QueuedOutcome {
+    Pending { position: usize },
+    Finished(CommandResponse),
+    Expired,
+}
+
+struct Inner {
+    queue: VecDeque<QueuedRequest>,
+    finished: HashMap<String, Option<CommandResponse>>,
+    finished_order: VecDeque<String>,
+    draining: HashSet<String>,
+}
+
+pub(crate) struct OfflineQueue {
+    path: PathBuf,
+    max_entries: usize,
+    ttl: Duration,
+    inner: Mutex<Inner>,
+}
+
+impl OfflineQueue {
+    /// 读取上次退出时未送达的请求；文件损坏时只打日志并从空队列开始。
+    /// 已发出但没等到结果的请求可能已在 console 执行，不再补发，只记为失败。
+    pub(crate) fn load(dir: &Path, client_id: &str, max_entries: usize, ttl: Duration) -> Self {
+        let path = dir.join(format!("{}.json", file_stem(client_id)));
+        let mut queue: VecDeque<QueuedRequest> = match std::fs::read(&path) {
+            Ok(data) => serde_json::from_slice(&data).unwrap_or_else(|err| {
+                tracing::warn!(path = %path.display(), error = %err, "ignoring unreadable offline queue");
+                VecDeque::new()
+            }),
+            Err(_) => VecDeque::new(),
+        };
+        let mut inner = Inner {
+            queue: VecDeque::new(),
+            finished: HashMap::new(),
+            finished_order: VecDeque::new(),
+            draining: HashSet::new(),
+        };
+        for entry in queue.drain(..) {
+            if !entry.submitted {
+                inner.queue.push_back(entry);
+                continue;
+            }
+            tracing::warn!(id = %entry.request.id, target = %entry.request.target, "offline request was submitted before restart; not resending");
+            let response = CommandResponse::error(
+                entry.request.id.clone(),
+                "proxy restarted after submitting this request; check the console history before retrying",
+            );
+            remember(&mut inner, entry.request.id, Some(response));
+        }
+        Self {
+            path,
+            max_entries: max_entries.max(1),
+            ttl,
+            inner: Mutex::new(inner),
+        }
+    }
+
+    /// 入队成功返回从 1 开始的队列位置。
+    pub(crate) async fn enqueue(&self, request: CommandRequest) -> Result<usize, String> {
+        let mut inner = self.inner.lock().await;
+        if inner.queue.len() >= self.max_entries {
+            return Err(format!(
+                "offline queue is full ({} requests)",
+                self.max_entries
+            ));
+        }
+        inner.queue.push_back(QueuedRequest {
+            queued_at_ms: now_ms(),
+            request,
+            submitted: false,
+        });
+        self.persist(&inner).await;
+        Ok(inner.queue.len())
+    }
+
+    pub(crate) async fn outcome(&self, id: &str) -> Option<QueuedOutcome> {
+        let inner = self.inner.lock().await;
+        if let Some(index) = inner.queue.iter().position(|entry| entry.request.id == id) {
+            return Some(QueuedOutcome::Pending {
+                position: index + 1,
+            });
+        }
+        inner.finished.get(id).map(|response| match response {
+            Some(response) => QueuedOutcome::Finished(response.clone()),
+            None => QueuedOutcome::Expired,
+        })
+    }
+
+    /// 移除超过 TTL 且尚未发出的请求并返回它们，调用方负责记录历史。
+    pub(crate) async fn take_expired(&self) -> Vec<CommandRequest> {
+        let mut inner = self.inner.lock().await;
+        let cutoff = now_ms().saturating_sub(self.ttl.as_millis() as u64);
+        let mut expired = Vec::new();
+        inner.queue.retain(|entry| {
+            if entry.submitted || entry.queued_at_ms >= cutoff {
+                return true;
+            }
+            expired.push(entry.request.clone());
+            false
+        });
+        if expired.is_empty() {
+            return expired;
+        }
+        for request in &expired {
+            remember(&mut inner, request.id.clone(), None);
+        }
+        self.persist(&inner).await;
+        expired
+    }
+
+    /// 返回有待发请求、且还没有补发任务的目标，并把它们标记为补发中。
+    pub(crate) async fn start_draining(&self) -> Vec<String> {
+        let mut inner = self.inner.lock().await;
+        let mut targets = Vec::new();
+        for entry in &inner.queue {
+            if !entry.submitted
+                && !inner.draining.contains(&entry.request.target)
+                && !targets.contains(&entry.request.target)
+            {
+                targets.push(entry.request.target.clone());
+            }
+        }
+        inner.draining.extend(targets.iter().cloned());
+        targets
+    }
+
+    /// 取出该目标最早的待发请求并先落盘标记为已发出；没有时结束该目标的补发。
+    pub(crate) async fn claim(&self, target: &str) -> Option<CommandRequest> {
+        let mut inner = self.inner.lock().await;
+        let Some(index) = inner
+            .queue
+            .iter()
+            .position(|entry| !entry.submitted && entry.request.target == target)
+        else {
+            inner.draining.remove(target);
+            return None;
+        };
+        inner.queue[index].submitted = true;
+        let request = inner.queue[index].request.clone();
+        self.persist(&inner).await;
+        Some(request)
+    }
+
+    /// console 不可达、请求没有发出去时撤销标记，等下个周期重试。
+    pub(crate) async fn release(&self, target: &str, id: &str) {
+        let mut inner = self.inner.lock().await;
+        if let Some(entry) = inner.queue.iter_mut().find(|entry| entry.request.id == id) {
+            entry.submitted = false;
+        }
+        inner.draining.remove(target);
+        self.persist(&inner).await;
+    }
+
+    pub(crate) async fn complete(&self, response: CommandResponse) {
+        let mut inner = self.inner.lock().await;
+        inner.queue.retain(|entry| entry.request.id != response.id);
+        remember(&mut inner, response.id.clone(), Some(response));
+        self.persist(&inner).await;
+    }
+
+    pub(crate) fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    async fn persist(&self, inner: &Inner) {
+        let result = async {
+            if let Some(parent) = self.path.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            let data = serde_json::to_vec(&inner.queue)?;
+            tokio::fs::write(&self.path, data).await?;
+            anyhow::Ok(())
+        }
+        .await;
+        if let Err(err) = result {
+            tracing::warn!(path = %self.path.display(), error = %err, "failed to persist offline queue");
+        }
+    }
+}
+
+/// 周期性清理过期请求，并为每个目标起独立任务按入队顺序补发；
+/// 补发要等审批和执行，所以不在这个循环里等待。
+pub(crate) fn spawn_flusher(
+    queue: Arc<OfflineQueue>,
+    state: Arc<RwLock<ProxyState>>,
+    history: Option<Arc<CommandHistory>>,
+    shutdown: CancellationToken,
+) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(FLUSH_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = shutdown.cancelled() => break,
+                _ = interval.tick() => {}
+            }
+            for request in queue.take_expired().await {
+                tracing::warn!(id = %request.id, target = %request.target, trace_id = request.trace_id.as_deref().unwrap_or_default(), "offline request expired");
+                let message = format!("expired in offline queue after {}s", queue.ttl().as_secs());
+                record_error(history.as_deref(), &request, message).await;
+            }
+            for target in queue.start_draining().await {
+                let queue = Arc::clone(&queue);
+                let state = Arc::clone(&state);
+                let history = history.clone();
+                tokio::spawn(async move {
+                    drain_target(&queue, &state, history.as_deref(), &target).await;
+                });
+            }
+        }
+    });
+}
+
+async fn drain_target(
+    queue: &OfflineQueue,
+    state: &Arc<RwLock<ProxyState>>,
+    history: Option<&CommandHistory>,
+    target: &str,
+) {
+    while let Some(request) = queue.claim(target).await {
+        let addr = state.read().await.target_addr(&request.target);
+        let response = match addr {
+            Ok(addr) => match deliver(state, history, &addr, &request, None).await {
+                Ok(response) => response,
+                Err(_) => {
+                    queue.release(target, &request.id).await;
+                    return;
+                }
+            },
+            Err(err) => record_error(history, &request, err.to_string()).await,
+        };
+        tracing::info!(id = %request.id, target = %request.target, trace_id = request.trace_id.as_deref().unwrap_or_default(), status = ?response.status, "offline request delivered");
+        queue.complete(response).await;
+    }
+}
+
+fn remember(inner: &mut Inner, id: String, response: Option<CommandResponse>) {
+    if inner.finished.insert(id.clone(), response).is_none() {
+        inner.finished_order.push_back(id);
+    }
+    while inner.finished_order.len() > MAX_FINISHED {
+        if let Some(oldest) = inner.finished_order.pop_front() {
+            inner.finished.remove(&oldest);
+        }
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn request(id: &str) -> CommandRequest {
+        CommandRequest {
+            id: id.to_string(),
+            client: "client".to_string(),
+            target: "dev".to_string(),
+            intent: "test".to_string(),
+            raw_command: "ls".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn persists_bounded_queue_and_tracks_outcomes() {
+        let dir = std::env::temp_dir().join(format!("octovalve-proxy-queue-{}", Uuid::new_v4()));
+        let rt = tokio::runtime::Runtime::new().expect("runtime");
+        rt.block_on(async {
+            let queue = OfflineQueue::load(&dir, "client", 2, Duration::from_secs(60));
+            assert_eq!(queue.enqueue(request("a")).await, Ok(1));
+            assert_eq!(queue.enqueue(request("b")).await, Ok(2));
+            assert!(queue.enqueue(request("c")).await.is_err());
+
+            let reloaded = OfflineQueue::load(&dir, "client", 2, Duration::from_secs(60));
+            assert_eq!(reloaded.start_draining().await, vec!["dev".to_string()]);
+            assert!(reloaded.start_draining().await.is_empty());
+            assert_eq!(
+                reloaded.claim("dev").await.map(|request| request.id),
+                Some("a".to_string())
+            );
+            reloaded
+                .complete(CommandResponse::error("a".to_string(), "boom"))
+                .await;
+            assert!(matches!(
+                reloaded.outcome("a").await,
+                Some(QueuedOutcome::Finished(_))
+            ));
+            assert!(matches!(
+                reloaded.outcome("b").await,
+                Some(QueuedOutcome::Pending { position: 1 })
+            ));
+            assert!(reloaded.outcome("missing").await.is_none());
+
+            let expiring = OfflineQueue::load(&dir, "client", 2, Duration::ZERO);
+            std::thread::sleep(Duration::from_millis(5));
+            let expired = expiring.take_expired().await;
+            assert_eq!(expired.len(), 1);
+            assert!(matches!(
+                expiring.outcome("b").await,
+                Some(QueuedOutcome::Expired)
+            ));
+            assert!(expiring.start_draining().await.is_empty());
+        });
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn submitted_requests_are_not_resent_after_restart() {
+        let dir = std::env::temp_dir().join(format!("octovalve-proxy-queue-{}", Uuid::new_v4()));
+        let rt = tokio::runtime::Runtime::new().expect("runtime");
+        rt.block_on(async {
+            let queue = OfflineQueue::load(&dir, "client", 3, Duration::ZERO);
+            queue.enqueue(request("a")).await.expect("enqueue a");
+            queue.enqueue(request("b")).await.expect("enqueue b");
+            assert_eq!(queue.start_draining().await, vec!["dev".to_string()]);
+            assert_eq!(
+                queue.claim("dev").await.map(|request| request.id),
+                Some("a".to_string())
+            );
+            std::thread::sleep(Duration::from_millis(5));
+            // 已发出的请求在等审批，不随 TTL 作废。
+            let expired = queue.take_expired().await;
+            assert_eq!(
+                expired
+                    .into_iter()
+                    .map(|request| request.id)
+                    .collect::<Vec<_>>(),
+                vec!["b".to_string()]
+            );
+            assert!(matches!(
+                queue.outcome("a").await,
+                Some(QueuedOutcome::Pending { position: 1 })
+            ));
+
+            queue.enqueue(request("c")).await.expect("enqueue c");
+            assert_eq!(
+                queue.claim("dev").await.map(|request| request.id),
+                Some("c".to_string())
+            );
+            queue.release("dev", "c").await;
+
+            let reloaded = OfflineQueue::load(&dir, "client", 3, Duration::from_secs(60));
+            assert!(matches!(
+                reloaded.outcome("a").await,
+                Some(QueuedOutcome::Finished(_))
+            ));
+            assert!(matches!(
+                reloaded.outcome("c").await,
+                Some(QueuedOutcome::Pending { position: 1 })
+            ));
+        });
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -1,12 +1,25 @@
 use crate::cli::Args;
 use crate::config::{load_proxy_config, ProxyConfig};
+use crate::cwd::CwdTemplate;
+use crate::files::DEFAULT_WRITE_FILE_MAX_BYTES;
+use crate::history::DEFAULT_HISTORY_MAX_BYTES;
+use anyhow::Context;
+use protocol::config::resolve_ssh_password;
 use serde::Serialize;
-use std::collections::HashMap;
-use std::time::SystemTime;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Instant, SystemTime};
+use system_utils::secret::ConfigKey;
+
+const CONTROL_TOKEN_ENV: &str = "OCTOVALVE_CONTROL_TOKEN";
 
 pub(crate) struct ProxyRuntimeDefaults {
     pub(crate) timeout_ms: u64,
     pub(crate) max_output_bytes: u64,
+    pub(crate) history_max_bytes: u64,
+    pub(crate) metadata: BTreeMap<String, String>,
+    pub(crate) write_file_max_bytes: u64,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
@@ -25,13 +38,24 @@ pub(crate) struct TargetRuntime {
     pub(crate) status: TargetStatus,
     pub(crate) last_seen: Option<SystemTime>,
     pub(crate) last_error: Option<String>,
+    pub(crate) last_activity: Option<Instant>,
+    pub(crate) pending: usize,
+    pub(crate) requests: u64,
+    pub(crate) failures: u64,
+    pub(crate) default_cwd: Option<CwdTemplate>,
+    pub(crate) queue_when_unreachable: bool,
+    pub(crate) banner: Option<String>,
 }
 
 pub(crate) struct ProxyState {
     targets: HashMap<String, TargetRuntime>,
     target_order: Vec<String>,
+    aliases: BTreeMap<String, String>,
     default_target: Option<String>,
     command_addr: String,
+    started_at: Instant,
+    last_command: Option<Instant>,
+    control_token: Option<Arc<str>>,
 }
 
 #[derive(Serialize)]
@@ -42,6 +66,42 @@ pub(crate) struct TargetListEntry {
     pub(crate) ssh: Option<String>,
     pub(crate) status: TargetStatus,
     pub(crate) last_error: Option<String>,
+    pub(crate) default_cwd: Option<String>,
+    pub(crate) default_cwd_template: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub(crate) aliases: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) banner: Option<String>,
+}
+
+// --status-addr 的只读视图：持读锁拷贝出来，序列化在锁外进行。
+#[derive(Debug, Serialize)]
+pub(crate) struct ForwardStatus {
+    pub(crate) name: String,
+    pub(crate) ssh: Option<String>,
+    pub(crate) status: TargetStatus,
+    pub(crate) pending: usize,
+    pub(crate) idle_secs: Option<u64>,
+    pub(crate) requests: u64,
+    pub(crate) failures: u64,
+    pub(crate) last_seen: Option<String>,
+    pub(crate) last_error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct ClientStatus {
+    pub(crate) client_id: String,
+    pub(crate) in_flight: usize,
+    pub(crate) last_request_age_secs: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct HealthStatus {
+    pub(crate) status: &'static str,
+    pub(crate) uptime_secs: u64,
+    pub(crate) targets: usize,
+    pub(crate) targets_down: usize,
+    pub(crate) command_addr: String,
 }
 
 impl ProxyState {
@@ -53,6 +113,64 @@ impl ProxyState {
         self.default_target.clone()
     }
 
+    /// 工具 schema 中 target 可取的值：目标名在前，别名在后。
+    pub(crate) fn target_choices(&self) -> Vec<String> {
+        let mut choices = self.target_order.clone();
+        choices.extend(self.aliases.keys().cloned());
+        choices
+    }
+
+    /// 把目标名或别名解析为目标名。精确匹配优先，其次忽略大小写匹配；
+    /// 忽略大小写后指向多个目标时报歧义。
+    pub(crate) fn resolve_target(&self, name: &str) -> anyhow::Result<String> {
+        let name = name.trim();
+        if self.targets.contains_key(name) {
+            return Ok(name.to_string());
+        }
+        if let Some(target) = self.aliases.get(name) {
+            return Ok(target.clone());
+        }
+        let mut matches: Vec<&String> = self
+            .target_order
+            .iter()
+            .filter(|target| target.eq_ignore_ascii_case(name))
+            .chain(
+                self.aliases
+                    .iter()
+                    .filter(|(alias, _)| alias.eq_ignore_ascii_case(name))
+                    .map(|(_, target)| target),
+            )
+            .collect();
+        matches.sort();
+        matches.dedup();
+        match matches.as_slice() {
+            [target] => Ok((*target).clone()),
+            [] => anyhow::bail!("unknown target {name:?}; {}", self.describe_targets()),
+            _ => {
+                let candidates: Vec<&str> = matches.iter().map(|target| target.as_str()).collect();
+                anyhow::bail!(
+                    "ambiguous target {name:?} matches {}; {}",
+                    candidates.join(", "),
+                    self.describe_targets()
+                )
+            }
+        }
+    }
+
+    // 出错时附上可用的目标与别名，方便 agent 直接改正。
+    pub(crate) fn describe_targets(&self) -> String {
+        let mut text = format!("available targets: {}", self.target_order.join(", "));
+        if !self.aliases.is_empty() {
+            let aliases: Vec<String> = self
+                .aliases
+                .iter()
+                .map(|(alias, target)| format!("{alias} -> {target}"))
+                .collect();
+            text.push_str(&format!(" (aliases: {})", aliases.join(", ")));
+        }
+        text
+    }
+
     pub(crate) fn target_addr(&self, name: &str) -> anyhow::Result<String> {
         if !self.targets.contains_key(name) {
             return Err(anyhow::anyhow!("unknown target: {name}"));
@@ -60,7 +178,37 @@ impl ProxyState {
         Ok(self.command_addr.clone())
     }
 
-    pub(crate) fn list_targets(&mut self) -> Vec<TargetListEntry> {
+    pub(crate) fn queue_when_unreachable(&self, name: &str) -> bool {
+        self.targets
+            .get(name)
+            .map(|target| target.queue_when_unreachable)
+            .unwrap_or(false)
+    }
+
+    pub(crate) fn any_queue_when_unreachable(&self) -> bool {
+        self.targets
+            .values()
+            .any(|target| target.queue_when_unreachable)
+    }
+
+    pub(crate) fn control_token(&self) -> Option<Arc<str>> {
+        self.control_token.clone()
+    }
+
+    // run_command 未传 cwd 时使用；~ 等展开仍由执行端负责，这里只填字段。
+    pub(crate) fn default_cwd(&self, name: &str, client: &str, intent: &str) -> Option<String> {
+        self.targets
+            .get(name)?
+            .default_cwd
+            .as_ref()
+            .map(|template| template.render(client, intent))
+    }
+
+    pub(crate) fn list_targets(
+        &mut self,
+        client: &str,
+        intent: Option<&str>,
+    ) -> Vec<TargetListEntry> {
         self.target_order
             .iter()
             .filter_map(|name| self.targets.get(name))
@@ -71,10 +219,91 @@ impl ProxyState {
                 ssh: target.ssh.clone(),
                 status: target.status,
                 last_error: target.last_error.clone(),
+                default_cwd: target
+                    .default_cwd
+                    .as_ref()
+                    .map(|template| template.render(client, intent.unwrap_or_default())),
+                default_cwd_template: target
+                    .default_cwd
+                    .as_ref()
+                    .map(|template| template.raw().to_string()),
+                aliases: self
+                    .aliases
+                    .iter()
+                    .filter(|(_, canonical)| **canonical == target.name)
+                    .map(|(alias, _)| alias.clone())
+                    .collect(),
+                banner: target.banner.clone(),
+            })
+            .collect()
+    }
+
+    pub(crate) fn forward_statuses(&self, now: Instant) -> Vec<ForwardStatus> {
+        self.target_order
+            .iter()
+            .filter_map(|name| self.targets.get(name))
+            .map(|target| ForwardStatus {
+                name: target.name.clone(),
+                ssh: target.ssh.clone(),
+                status: target.status,
+                pending: target.pending,
+                idle_secs: if target.pending > 0 {
+                    Some(0)
+                } else {
+                    target
+                        .last_activity
+                        .map(|last| now.saturating_duration_since(last).as_secs())
+                },
+                requests: target.requests,
+                failures: target.failures,
+                last_seen: target.last_seen.map(format_time),
+                last_error: target.last_error.clone(),
             })
             .collect()
     }
 
+    pub(crate) fn client_status(&self, client_id: &str, now: Instant) -> ClientStatus {
+        ClientStatus {
+            client_id: client_id.to_string(),
+            in_flight: self.targets.values().map(|target| target.pending).sum(),
+            last_request_age_secs: self
+                .last_command
+                .map(|last| now.saturating_duration_since(last).as_secs()),
+        }
+    }
+
+    pub(crate) fn health(&self, now: Instant) -> HealthStatus {
+        let targets_down = self
+            .targets
+            .values()
+            .filter(|target| target.status == TargetStatus::Down)
+            .count();
+        HealthStatus {
+            status: if targets_down == 0 { "ok" } else { "degraded" },
+            uptime_secs: now.saturating_duration_since(self.started_at).as_secs(),
+            targets: self.targets.len(),
+            targets_down,
+            command_addr: self.command_addr.clone(),
+        }
+    }
+
+    // 命令发出前调用；排队/等待审批期间计入 pending，供 --status-addr 展示。
+    pub(crate) fn begin_command(&mut self, name: &str) {
+        if let Some(target) = self.targets.get_mut(name) {
+            target.pending += 1;
+            target.requests += 1;
+            target.last_activity = Some(Instant::now());
+        }
+        self.last_command = Some(Instant::now());
+    }
+
+    pub(crate) fn finish_command(&mut self, name: &str) {
+        if let Some(target) = self.targets.get_mut(name) {
+            target.pending = target.pending.saturating_sub(1);
+            target.last_activity = Some(Instant::now());
+        }
+    }
+
     pub(crate) fn note_success(&mut self, name: &str) {
         if let Some(target) = self.targets.get_mut(name) {
             target.last_seen = Some(SystemTime::now());
@@ -86,6 +315,7 @@ impl ProxyState {
     pub(crate) fn note_failure(&mut self, name: &str, err: &str) {
         if let Some(target) = self.targets.get_mut(name) {
             target.status = TargetStatus::Down;
+            target.failures += 1;
             target.last_error = Some(err.to_string());
         }
     }
@@ -97,18 +327,48 @@ fn format_time(time: SystemTime) -> String {
 
 pub(crate) fn build_proxy_state(args: &Args) -> anyhow::Result<(ProxyState, ProxyRuntimeDefaults)> {
     let config = load_proxy_config(&args.config)?;
-    build_state_from_config(args, config)
+    let config_key = ConfigKey::resolve(args.config_key_file.as_deref())?;
+    let (mut state, defaults) = build_state_from_config(args, config, config_key.as_ref())?;
+    state.control_token = resolve_control_token(args.control_token_file.as_deref())?.map(Arc::from);
+    Ok((state, defaults))
+}
+
+// `--control-token-file` 优先，其次 OCTOVALVE_CONTROL_TOKEN；都没有时不发送认证帧。
+fn resolve_control_token(token_file: Option<&Path>) -> anyhow::Result<Option<String>> {
+    if let Some(path) = token_file {
+        let raw = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read control token file {}", path.display()))?;
+        let token = raw.trim();
+        if token.is_empty() {
+            anyhow::bail!("control token file {} is empty", path.display());
+        }
+        return Ok(Some(token.to_string()));
+    }
+    match std::env::var(CONTROL_TOKEN_ENV) {
+        Ok(value) if !value.trim().is_empty() => Ok(Some(value.trim().to_string())),
+        _ => Ok(None),
+    }
 }
 
-fn build_state_from_config(
+pub(crate) fn build_state_from_config(
     args: &Args,
     config: ProxyConfig,
+    config_key: Option<&ConfigKey>,
 ) -> anyhow::Result<(ProxyState, ProxyRuntimeDefaults)> {
     let defaults = config.defaults.unwrap_or_default();
     let command_addr = args.command_addr.clone();
 
     let timeout_ms = defaults.timeout_ms.unwrap_or(args.timeout_ms);
     let max_output_bytes = defaults.max_output_bytes.unwrap_or(args.max_output_bytes);
+    let history_max_bytes = defaults
+        .command_history_max_bytes
+        .unwrap_or(DEFAULT_HISTORY_MAX_BYTES);
+    let metadata = defaults.metadata.clone().unwrap_or_default();
+    let write_file_max_bytes = defaults
+        .write_file_max_bytes
+        .unwrap_or(DEFAULT_WRITE_FILE_MAX_BYTES);
+    protocol::validate_request_metadata(&metadata)
+        .map_err(|err| anyhow::anyhow!("defaults.metadata: {err}"))?;
 
     let mut targets = HashMap::new();
     let mut order = Vec::new();
@@ -131,13 +391,12 @@ fn build_state_from_config(
                 );
             }
         }
+        let ssh_password = resolve_ssh_password(&defaults, &target, config_key)?;
+        let banner = target.banner();
         let mut ssh_args = defaults.ssh_args.clone().unwrap_or_default();
         if let Some(extra) = target.ssh_args {
             ssh_args.extend(extra);
         }
-        let ssh_password = target
-            .ssh_password
-            .or_else(|| defaults.ssh_password.clone());
         if ssh_password.is_some() {
             tracing::warn!(
                 target = %target.name,
@@ -145,6 +404,13 @@ fn build_state_from_config(
             );
         }
 
+        let default_cwd = target
+            .default_cwd
+            .as_deref()
+            .map(CwdTemplate::parse)
+            .transpose()
+            .with_context(|| format!("target {} has invalid default_cwd", target.name))?;
+
         let status = TargetStatus::Ready;
         let runtime = TargetRuntime {
             name: target.name.clone(),
@@ -155,20 +421,42 @@ fn build_state_from_config(
             status,
             last_seen: None,
             last_error: None,
+            last_activity: None,
+            pending: 0,
+            requests: 0,
+            failures: 0,
+            default_cwd,
+            queue_when_unreachable: target.queue_when_unreachable,
+            banner,
         };
 
         order.push(runtime.name.clone());
         targets.insert(runtime.name.clone(), runtime);
     }
 
-    if let Some(default_target) = config.default_target.as_ref() {
+    let mut aliases = BTreeMap::new();
+    for (alias, target) in config.aliases {
+        let target = target.trim().to_string();
+        if targets.contains_key(&alias) {
+            anyhow::bail!("alias {alias} shadows a target with the same name");
+        }
+        if !targets.contains_key(&target) {
+            anyhow::bail!("alias {alias} points to unknown target {target}");
+        }
+        aliases.insert(alias, target);
+    }
+
+    let default_target = config
+        .default_target
+        .map(|name| aliases.get(name.trim()).cloned().unwrap_or(name));
+    if let Some(default_target) = default_target.as_ref() {
         if !targets.contains_key(default_target) {
             anyhow::bail!("default_target {} not found in targets", default_target);
         }
     }
 
     // Auto-select default_target when there's exactly one target
-    let default_target = config.default_target.or_else(|| {
+    let default_target = default_target.or_else(|| {
         if order.len() == 1 {
             Some(order[0].clone())
         } else {
@@ -179,13 +467,20 @@ fn build_state_from_config(
     let state = ProxyState {
         targets,
         target_order: order,
+        aliases,
         default_target,
         command_addr,
+        started_at: Instant::now(),
+        last_command: None,
+        control_token: None,
     };
 
     let defaults = ProxyRuntimeDefaults {
         timeout_ms,
         max_output_bytes,
+        history_max_bytes,
+        metadata,
+        write_file_max_bytes,
     };
     Ok((state, defaults))
 }
@@ -203,6 +498,13 @@ mod tests {
             command_addr: "127.0.0.1:19310".to_string(),
             timeout_ms: 30_000,
             max_output_bytes: 1024 * 1024,
+            config_key_file: None,
+            control_token_file: None,
+            history_dir: "~/.octovalve/proxy-history".to_string(),
+            status_addr: None,
+            offline_queue_dir: "~/.octovalve/proxy-queue".to_string(),
+            offline_queue_max: 100,
+            offline_queue_ttl_secs: 3600,
         }
     }
 
@@ -211,6 +513,12 @@ mod tests {
         let args = base_args();
         let config = ProxyConfig {
             default_target: None,
+            aliases: BTreeMap::new(),
+            terminal: None,
+            health: None,
+            server: None,
+            templates: Vec::new(),
+            schedules: Vec::new(),
             defaults: None,
             targets: vec![TargetConfig {
                 name: "dev".to_string(),
@@ -218,21 +526,95 @@ mod tests {
                 ssh: Some("devops@127.0.0.1".to_string()),
                 ssh_args: None,
                 ssh_password: None,
+                ssh_password_encrypted: None,
                 terminal_locale: None,
                 tty: false,
+                shell: Default::default(),
+                default_cwd: None,
+                queue_when_unreachable: false,
+                extra_forwards: Vec::new(),
+                ssh_host_key_policy: Default::default(),
+                ssh_host_key: None,
+                banner: None,
             }],
         };
-        let (mut state, _) = build_state_from_config(&args, config).expect("state");
+        let (mut state, _) = build_state_from_config(&args, config, None).expect("state");
         assert_eq!(state.target_addr("dev").expect("addr"), "127.0.0.1:19310");
-        let targets = state.list_targets();
+        let targets = state.list_targets("proxy", None);
         assert_eq!(targets[0].status, TargetStatus::Ready);
     }
 
+    #[test]
+    fn default_cwd_template_is_rendered_and_validated() {
+        let args = base_args();
+        let target = TargetConfig {
+            name: "dev".to_string(),
+            desc: "dev".to_string(),
+            ssh: Some("devops@127.0.0.1".to_string()),
+            ssh_args: None,
+            ssh_password: None,
+            ssh_password_encrypted: None,
+            terminal_locale: None,
+            tty: false,
+            shell: Default::default(),
+            default_cwd: Some("/home/devops/work/{client}/{intent_slug}".to_string()),
+            queue_when_unreachable: false,
+            extra_forwards: Vec::new(),
+            ssh_host_key_policy: Default::default(),
+            ssh_host_key: None,
+            banner: None,
+        };
+        let config = ProxyConfig {
+            default_target: None,
+            aliases: BTreeMap::new(),
+            terminal: None,
+            health: None,
+            server: None,
+            templates: Vec::new(),
+            schedules: Vec::new(),
+            defaults: None,
+            targets: vec![target.clone()],
+        };
+        let (mut state, _) = build_state_from_config(&args, config, None).expect("state");
+        assert_eq!(
+            state.default_cwd("dev", "codex-1", "Run tests"),
+            Some("/home/devops/work/codex-1/run-tests".to_string())
+        );
+        let targets = state.list_targets("codex-1", Some("Run tests"));
+        assert_eq!(
+            targets[0].default_cwd.as_deref(),
+            Some("/home/devops/work/codex-1/run-tests")
+        );
+
+        let config = ProxyConfig {
+            default_target: None,
+            aliases: BTreeMap::new(),
+            terminal: None,
+            health: None,
+            server: None,
+            templates: Vec::new(),
+            schedules: Vec::new(),
+            defaults: None,
+            targets: vec![TargetConfig {
+                default_cwd: Some("/home/{user}".to_string()),
+                queue_when_unreachable: false,
+                ..target
+            }],
+        };
+        assert!(build_state_from_config(&args, config, None).is_err());
+    }
+
     #[test]
     fn single_target_auto_selects_default() {
         let args = base_args();
         let config = ProxyConfig {
             default_target: None,
+            aliases: BTreeMap::new(),
+            terminal: None,
+            health: None,
+            server: None,
+            templates: Vec::new(),
+            schedules: Vec::new(),
             defaults: None,
             targets: vec![TargetConfig {
                 name: "only".to_string(),
@@ -240,19 +622,86 @@ mod tests {
                 ssh: Some("user@host".to_string()),
                 ssh_args: None,
                 ssh_password: None,
+                ssh_password_encrypted: None,
                 terminal_locale: None,
                 tty: false,
+                shell: Default::default(),
+                default_cwd: None,
+                queue_when_unreachable: false,
+                extra_forwards: Vec::new(),
+                ssh_host_key_policy: Default::default(),
+                ssh_host_key: None,
+                banner: None,
             }],
         };
-        let (state, _) = build_state_from_config(&args, config).expect("state");
+        let (state, _) = build_state_from_config(&args, config, None).expect("state");
         assert_eq!(state.default_target(), Some("only".to_string()));
     }
 
+    #[test]
+    fn resolves_aliases_and_reports_candidates() {
+        let args = base_args();
+        let target = |name: &str| TargetConfig {
+            name: name.to_string(),
+            desc: name.to_string(),
+            ssh: Some(format!("user@{name}")),
+            ssh_args: None,
+            ssh_password: None,
+            ssh_password_encrypted: None,
+            terminal_locale: None,
+            tty: false,
+            shell: Default::default(),
+            default_cwd: None,
+            queue_when_unreachable: false,
+            extra_forwards: Vec::new(),
+            ssh_host_key_policy: Default::default(),
+            ssh_host_key: None,
+            banner: None,
+        };
+        let config = ProxyConfig {
+            default_target: Some("staging".to_string()),
+            aliases: BTreeMap::from([
+                ("staging".to_string(), "stg-api-1".to_string()),
+                ("Web".to_string(), "web-a".to_string()),
+            ]),
+            terminal: None,
+            health: None,
+            server: None,
+            templates: Vec::new(),
+            schedules: Vec::new(),
+            defaults: None,
+            targets: vec![target("stg-api-1"), target("web-a"), target("WEB")],
+        };
+        let (mut state, _) = build_state_from_config(&args, config, None).expect("state");
+        assert_eq!(state.default_target(), Some("stg-api-1".to_string()));
+        assert_eq!(state.resolve_target(" staging ").unwrap(), "stg-api-1");
+        assert_eq!(state.resolve_target("STG-API-1").unwrap(), "stg-api-1");
+        assert_eq!(state.resolve_target("Web").unwrap(), "web-a");
+        let err = state.resolve_target("web").unwrap_err().to_string();
+        assert!(
+            err.contains("ambiguous") && err.contains("WEB, web-a"),
+            "{err}"
+        );
+        let err = state.resolve_target("prod").unwrap_err().to_string();
+        assert!(
+            err.contains("available targets: stg-api-1, web-a, WEB")
+                && err.contains("staging -> stg-api-1"),
+            "{err}"
+        );
+        assert_eq!(state.list_targets("proxy", None)[0].aliases, ["staging"]);
+    }
+
     #[test]
     fn multiple_targets_no_auto_default() {
         let args = base_args();
         let config = ProxyConfig {
             default_target: None,
+            aliases: BTreeMap::new(),
+            terminal: None,
+            health: None,
+            server: None,
+            templates: Vec::new(),
+            schedules: Vec::new(),
             defaults: None,
             targets: vec![
                 TargetConfig {
@@ -261,8 +710,16 @@ mod tests {
                     ssh: Some("user@a".to_string()),
                     ssh_args: None,
                     ssh_password: None,
+                    ssh_password_encrypted: None,
                     terminal_locale: None,
                     tty: false,
+                    shell: Default::default(),
+                    default_cwd: None,
+                    queue_when_unreachable: false,
+                    extra_forwards: Vec::new(),
+                    ssh_host_key_policy: Default::default(),
+                    ssh_host_key: None,
+                    banner: None,
                 },
                 TargetConfig {
                     name: "b".to_string(),
@@ -270,12 +727,20 @@ mod tests {
                     ssh: Some("user@b".to_string()),
                     ssh_args: None,
                     ssh_password: None,
+                    ssh_password_encrypted: None,
                     terminal_locale: None,
                     tty: false,
+                    shell: Default::default(),
+                    default_cwd: None,
+                    queue_when_unreachable: false,
+                    extra_forwards: Vec::new(),
+                    ssh_host_key_policy: Default::default(),
+                    ssh_host_key: None,
+                    banner: None,
                 },
             ],
         };
-        let (state, _) = build_state_from_config(&args, config).expect("state");
+        let (state, _) = build_state_from_config(&args, config, None).expect("state");
         assert_eq!(state.default_target(), None);
     }
 }
@@ -0,0 +1,136 @@
+use crate::state::{ClientStatus, ForwardStatus, HealthStatus, ProxyState};
+use anyhow::Context;
+use axum::extract::State;
+use axum::routing::get;
+use axum::{Json, Router};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::net::TcpListener;
+use tokio::sync::RwLock;
+use tokio_util::sync::CancellationToken;
+
+// 调试用的只读 HTTP 接口，与 MCP stdio 通道互不影响；只取读锁拷贝数据，锁外序列化。
+#[derive(Clone)]
+struct StatusState {
+    state: Arc<RwLock<ProxyState>>,
+    client_id: Arc<str>,
+}
+
+/// 先完成 bind 再后台 serve，端口被占用时启动直接失败；返回实际监听地址（便于使用端口 0）。
+pub(crate) async fn spawn_status_server(
+    addr: SocketAddr,
+    state: Arc<RwLock<ProxyState>>,
+    client_id: String,
+    shutdown: CancellationToken,
+) -> anyhow::Result<SocketAddr> {
+    let listener = TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("failed to bind status endpoint {addr}"))?;
+    let local_addr = listener.local_addr()?;
+    let app = Router::new()
+        .route("/health", get(health))
+        .route("/forwards", get(forwards))
+        .route("/clients", get(clients))
+        .with_state(StatusState {
+            state,
+            client_id: Arc::from(client_id),
+        });
+    tokio::spawn(async move {
+        let result = axum::serve(listener, app)
+            .with_graceful_shutdown(async move { shutdown.cancelled().await })
+            .await;
+        if let Err(err) = result {
+            tracing::warn!(error = %err, "status endpoint stopped");
+        }
+    });
+    Ok(local_addr)
+}
+
+async fn health(State(status): State<StatusState>) -> Json<HealthStatus> {
+    let health = status.state.read().await.health(Instant::now());
+    Json(health)
+}
+
+async fn forwards(State(status): State<StatusState>) -> Json<Vec<ForwardStatus>> {
+    let forwards = status.state.read().await.forward_statuses(Instant::now());
+    Json(forwards)
+}
+
+async fn clients(State(status): State<StatusState>) -> Json<Vec<ClientStatus>> {
+    let client = status
+        .state
+        .read()
+        .await
+        .client_status(&status.client_id, Instant::now());
+    Json(vec![client])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::Args;
+    use crate::state::build_state_from_config;
+    use clap::Parser;
+    use serde_json::Value;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    async fn get_json(addr: SocketAddr, path: &str) -> Value {
+        let mut stream = TcpStream::connect(addr).await.expect("connect");
+        let request = format!("GET {path} HTTP/1.1\r\nHost: {addr}\r\nConnection: close\r\n\r\n");
+        stream.write_all(request.as_bytes()).await.expect("write");
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.expect("read");
+        assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+        let (_, body) = response.split_once("\r\n\r\n").expect("body");
+        serde_json::from_str(body).expect("json")
+    }
+
+    #[test]
+    fn serves_status_json_on_ephemeral_port() {
+        let args = Args::parse_from(["octovalve-proxy", "--client-id", "codex-1"]);
+        let config = toml::from_str(
+            r#"
+[[targets]]
+name = "dev"
+desc = "dev"
+ssh = "devops@127.0.0.1"
+"#,
+        )
+        .expect("config");
+        let (mut state, _) = build_state_from_config(&args, config, None).expect("state");
+        state.begin_command("dev");
+        let state = Arc::new(RwLock::new(state));
+        let rt = tokio::runtime::Runtime::new().expect("runtime");
+        rt.block_on(async {
+            let shutdown = CancellationToken::new();
+            let addr = spawn_status_server(
+                "127.0.0.1:0".parse().unwrap(),
+                Arc::clone(&state),
+                args.client_id.clone(),
+                shutdown.clone(),
+            )
+            .await
+            .expect("status server");
+
+            let health = get_json(addr, "/health").await;
+            assert_eq!(health["status"], "ok");
+            assert_eq!(health["targets"], 1);
+
+            let forwards = get_json(addr, "/forwards").await;
+            let forward = &forwards.as_array().expect("forwards")[0];
+            assert_eq!(forward["name"], "dev");
+            assert_eq!(forward["pending"], 1);
+            assert_eq!(forward["requests"], 1);
+            assert_eq!(forward["failures"], 0);
+
+            let clients = get_json(addr, "/clients").await;
+            let client = &clients.as_array().expect("clients")[0];
+            assert_eq!(client["client_id"], "codex-1");
+            assert_eq!(client["in_flight"], 1);
+            assert!(client["last_request_age_secs"].is_u64());
+            shutdown.cancel();
+        });
+    }
+}
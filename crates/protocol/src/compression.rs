@@ -0,0 +1,152 @@
+//! 命令通道上大响应的可选压缩。
+//!
+//! proxy 在 [`CommandRequest::compression`](crate::CommandRequest::compression) 里声明支持的编码，
+//! console 只在对端声明了 gzip 且序列化后的响应超过 [`COMPRESSION_THRESHOLD_BYTES`] 时才压缩，
+//! 否则照旧发送原始 JSON；旧版 proxy 不带该字段，始终收到未压缩的响应。
+
+use std::borrow::Cow;
+use std::io::{self, Read, Write};
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+
+use crate::framing::MAX_FRAME_LENGTH;
+
+pub const GZIP_ENCODING: &str = "gzip";
+pub const COMPRESSION_THRESHOLD_BYTES: usize = 32 * 1024;
+
+const ENVELOPE_PREFIX: &[u8] = br#"{"type":"compressed""#;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CompressedFrame {
+    Compressed {
+        encoding: String,
+        data_base64: String,
+    },
+}
+
+/// 本端能解压的编码，填入请求的 `compression` 字段。
+pub fn supported_encodings() -> Vec<String> {
+    vec![GZIP_ENCODING.to_string()]
+}
+
+/// 对端接受 gzip 且 payload 超过阈值时返回压缩信封，否则原样返回。
+pub fn encode_frame(payload: Vec<u8>, accepted: &[String]) -> io::Result<Vec<u8>> {
+    if payload.len() <= COMPRESSION_THRESHOLD_BYTES
+        || !accepted.iter().any(|encoding| encoding == GZIP_ENCODING)
+    {
+        return Ok(payload);
+    }
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::fast());
+    encoder.write_all(&payload)?;
+    let frame = CompressedFrame::Compressed {
+        encoding: GZIP_ENCODING.to_string(),
+        data_base64: STANDARD.encode(encoder.finish()?),
+    };
+    let encoded = serde_json::to_vec(&frame)?;
+    // 高熵输出压缩后加上 base64 膨胀可能反而更大，此时直接发原文。
+    if encoded.len() >= payload.len() {
+        return Ok(payload);
+    }
+    Ok(encoded)
+}
+
+/// 还原压缩信封；普通帧原样返回。
+pub fn decode_frame(frame: &[u8]) -> io::Result<Cow<'_, [u8]>> {
+    decode_frame_with_limit(frame, MAX_FRAME_LENGTH)
+}
+
+// 解压后的大小同样受帧上限约束，避免很小的压缩包被展开成任意大的内存占用。
+fn decode_frame_with_limit(frame: &[u8], limit: usize) -> io::Result<Cow<'_, [u8]>> {
+    // 先按前缀判断，避免为了识别信封把每个大响应都完整解析一遍。
+    if !frame.starts_with(ENVELOPE_PREFIX) {
+        return Ok(Cow::Borrowed(frame));
+    }
+    let CompressedFrame::Compressed {
+        encoding,
+        data_base64,
+    } = serde_json::from_slice(frame)?;
+    if encoding != GZIP_ENCODING {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unsupported frame encoding: {encoding}"),
+        ));
+    }
+    let compressed = STANDARD
+        .decode(data_base64.as_bytes())
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    let mut payload = Vec::new();
+    GzDecoder::new(compressed.as_slice())
+        .take(limit as u64 + 1)
+        .read_to_end(&mut payload)?;
+    if payload.len() > limit {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("decompressed frame exceeds {limit} bytes"),
+        ));
+    }
+    Ok(Cow::Owned(payload))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CommandResponse;
+
+    #[test]
+    fn compresses_large_payloads_only_when_accepted() {
+        let stdout = "line of repetitive output\n".repeat(4096);
+        let response = CommandResponse::completed("req-1", 0, Some(stdout), None);
+        let payload = serde_json::to_vec(&response).expect("serialize");
+        assert!(payload.len() > COMPRESSION_THRESHOLD_BYTES);
+
+        let plain = encode_frame(payload.clone(), &[]).expect("encode");
+        assert_eq!(plain, payload);
+        let compressed = encode_frame(payload.clone(), &supported_encodings()).expect("encode");
+        assert!(compressed.starts_with(ENVELOPE_PREFIX));
+        assert!(compressed.len() < payload.len() / 10);
+
+        let decoded: CommandResponse =
+            serde_json::from_slice(&decode_frame(&compressed).expect("decode")).expect("json");
+        assert_eq!(decoded, response);
+        assert!(matches!(
+            decode_frame(&plain).expect("decode"),
+            Cow::Borrowed(_)
+        ));
+    }
+
+    #[test]
+    fn leaves_small_payloads_and_rejects_unknown_encodings() {
+        let response = CommandResponse::completed("req-1", 0, Some("ok".to_string()), None);
+        let payload = serde_json::to_vec(&response).expect("serialize");
+        assert_eq!(
+            encode_frame(payload.clone(), &supported_encodings()).expect("encode"),
+            payload
+        );
+
+        let frame = serde_json::to_vec(&CompressedFrame::Compressed {
+            encoding: "zstd".to_string(),
+            data_base64: String::new(),
+        })
+        .expect("serialize");
+        assert!(decode_frame(&frame).is_err());
+    }
+
+    #[test]
+    fn rejects_frames_that_decompress_past_the_frame_limit() {
+        let payload = vec![b' '; 64 * 1024];
+        let frame = encode_frame(payload.clone(), &supported_encodings()).expect("encode");
+        assert!(frame.starts_with(ENVELOPE_PREFIX));
+        assert_eq!(
+            decode_frame_with_limit(&frame, payload.len()).expect("decode"),
+            payload
+        );
+        let err = decode_frame_with_limit(&frame, payload.len() - 1).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -1,10 +1,95 @@
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use system_utils::secret::{decrypt_config_secret, ConfigKey};
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct ProxyConfig {
     pub default_target: Option<String>,
+    /// 目标别名 -> 目标名，允许 agent 在不同环境下沿用同一个名字；`default_target` 也可以写别名。
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub aliases: BTreeMap<String, String>,
     pub defaults: Option<ProxyDefaults>,
     pub targets: Vec<TargetConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub terminal: Option<TerminalSettings>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub health: Option<HealthSettings>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server: Option<ServerSettings>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub templates: Vec<CommandTemplate>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub schedules: Vec<CommandSchedule>,
+}
+
+// console 交互式终端的设置；octovalve-proxy 忽略。
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct TerminalSettings {
+    /// 配置后主连接断开的会话会保留到空闲超时，期间可重新接管。
+    pub max_idle_secs: Option<u64>,
+    /// 每个会话保留的最近输出字节数，附加或重新接管时回放；未设置时 256 KiB，0 表示不保留。
+    pub scrollback_bytes: Option<usize>,
+    /// 通过 shell 钩子识别交互式命令边界（bash/zsh），把命令行与耗时记入目标历史。
+    pub capture_commands: Option<bool>,
+}
+
+// console 探测目标 SSH 连通性的熔断设置；octovalve-proxy 忽略。
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct HealthSettings {
+    /// 连续失败达到该次数后进入熔断，只按 cool_down_secs 间隔探测。
+    pub failure_threshold: Option<u32>,
+    pub cool_down_secs: Option<u64>,
+}
+
+// console HTTP/WebSocket 服务的资源上限；未设置或为 0 时使用默认值，octovalve-proxy 忽略。
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct ServerSettings {
+    /// approve/deny/cancel 等审批控制接口的请求体上限。
+    pub max_control_body_bytes: Option<usize>,
+    /// 其他接口的请求体上限。
+    pub max_body_bytes: Option<usize>,
+    /// WebSocket 单条入站消息上限，超出时以 1009 关闭连接。
+    pub max_ws_message_bytes: Option<usize>,
+    /// `/ws` 每个连接待发送事件的队列长度，写满说明客户端读得太慢，直接断开。
+    pub ws_send_queue: Option<usize>,
+}
+
+// console 的命令模板：操作员通过 HTTP 接口填参数下发，仍走正常审批；octovalve-proxy 忽略。
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CommandTemplate {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// 可运行的目标名 glob（支持 `*`、`?`），缺省匹配所有目标。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    /// 命令文本，`{param}` 占位符按参数值替换。
+    pub raw_command: String,
+    /// 参数名到校验正则，值必须整体匹配；未声明的参数会被拒绝。
+    #[serde(default)]
+    pub params: BTreeMap<String, String>,
+}
+
+// console 的定时任务：到点为每个匹配的目标生成请求，照常进入审批队列；octovalve-proxy 忽略。
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CommandSchedule {
+    pub name: String,
+    /// 五段 cron 表达式（分 时 日 月 周），按 UTC 计算。
+    pub cron: String,
+    /// 目标名 glob（支持 `*`、`?`），`*` 表示所有目标。
+    pub target: String,
+    /// 要执行的命令，与 `template` 二选一。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    /// 引用 `[[templates]]` 中的模板名，参数由 `params` 提供。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub params: BTreeMap<String, String>,
+    /// 命令全部通过白名单时自动批准；否则仍等待人工审批。
+    #[serde(default)]
+    pub auto_approve: bool,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -13,7 +98,15 @@ pub struct ProxyDefaults {
     pub max_output_bytes: Option<u64>,
     pub ssh_args: Option<Vec<String>>,
     pub ssh_password: Option<String>,
+    pub ssh_password_encrypted: Option<String>,
     pub terminal_locale: Option<String>,
+    pub command_history_max_bytes: Option<u64>,
+    /// octovalve-proxy：附加到每个请求的默认 metadata（如 `agent`），MCP 客户端提供的同名键优先；console 忽略。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<BTreeMap<String, String>>,
+    /// octovalve-proxy：`write_file` 工具允许写入的最大字节数（解码后）；console 忽略。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub write_file_max_bytes: Option<u64>,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -23,9 +116,89 @@ pub struct TargetConfig {
     pub ssh: Option<String>,
     pub ssh_args: Option<Vec<String>>,
     pub ssh_password: Option<String>,
+    pub ssh_password_encrypted: Option<String>,
     pub terminal_locale: Option<String>,
     #[serde(default)]
     pub tty: bool,
+    #[serde(default)]
+    pub shell: TargetShell,
+    pub default_cwd: Option<String>,
+    /// octovalve-proxy：连不上 console 时把请求存入本地离线队列，恢复后按顺序补发；console 忽略。
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub queue_when_unreachable: bool,
+    /// console：按需建立的额外 SSH 本地端口转发；octovalve-proxy 忽略。
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extra_forwards: Vec<ExtraForward>,
+    /// console：SSH 主机密钥校验方式，探测、执行、终端与端口转发统一使用；octovalve-proxy 忽略。
+    #[serde(default, skip_serializing_if = "HostKeyPolicy::is_default")]
+    pub ssh_host_key_policy: HostKeyPolicy,
+    /// `ssh_host_key_policy = "pinned"` 时固定的主机公钥，即 `ssh-keyscan` 输出中的 `<type> <base64>`。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ssh_host_key: Option<String>,
+    /// 目标的运维提示（如“生产库，需在变更窗口内操作”），随目标信息展示给 agent 与审批人。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub banner: Option<String>,
+}
+
+/// `banner` 保留的最大字符数，超出部分在加载时截断。
+pub const MAX_BANNER_CHARS: usize = 512;
+
+impl TargetConfig {
+    /// 去掉首尾空白并截断到 [`MAX_BANNER_CHARS`] 个字符；未配置或为空时返回 `None`。
+    pub fn banner(&self) -> Option<String> {
+        let banner = self.banner.as_deref().map(str::trim)?;
+        if banner.is_empty() {
+            return None;
+        }
+        Some(banner.chars().take(MAX_BANNER_CHARS).collect())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HostKeyPolicy {
+    /// 首次连接时记入用户 known_hosts，之后不一致即失败。
+    #[default]
+    AcceptNew,
+    /// 只信任用户 known_hosts 中已有的密钥。
+    Strict,
+    /// 只信任 `ssh_host_key`，不读写用户 known_hosts。
+    Pinned,
+    /// 不校验主机密钥。
+    Insecure,
+}
+
+impl HostKeyPolicy {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+// 例如目标上的 Grafana 或调试端口，通过 console 的 forwards 接口按需建立和释放。
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ExtraForward {
+    pub name: String,
+    /// 从目标一侧访问的地址，`host:port`，例如 `127.0.0.1:3000`。
+    pub remote_addr: String,
+    /// 优先绑定的本地端口；缺省或被占用时改用随机端口。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub local_port: Option<u16>,
+}
+
+// 远端执行命令所用的 shell；cmd/powershell 用于 Windows（OpenSSH）目标。
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TargetShell {
+    #[default]
+    Bash,
+    Cmd,
+    Powershell,
+}
+
+impl TargetShell {
+    pub fn is_windows(self) -> bool {
+        matches!(self, TargetShell::Cmd | TargetShell::Powershell)
+    }
 }
 
 impl Default for ProxyDefaults {
@@ -35,7 +208,11 @@ impl Default for ProxyDefaults {
             max_output_bytes: None,
             ssh_args: None,
             ssh_password: None,
+            ssh_password_encrypted: None,
             terminal_locale: None,
+            command_history_max_bytes: None,
+            metadata: None,
+            write_file_max_bytes: None,
         }
     }
 }
@@ -75,6 +252,266 @@ pub fn resolve_terminal_locale(
     target_locale.or(default_locale)
 }
 
+/// console 与 octovalve-proxy 共用，优先级：目标密文 > 目标明文 > defaults 密文 > defaults 明文。
+pub fn resolve_ssh_password(
+    defaults: &ProxyDefaults,
+    target: &TargetConfig,
+    config_key: Option<&ConfigKey>,
+) -> anyhow::Result<Option<String>> {
+    if let Some(encrypted) = target.ssh_password_encrypted.as_deref() {
+        let label = format!("target {} ssh_password_encrypted", target.name);
+        return decrypt_config_secret(config_key, encrypted, &label).map(Some);
+    }
+    if let Some(password) = target.ssh_password.clone() {
+        tracing::warn!(
+            target = %target.name,
+            "ssh_password is stored in plaintext; use ssh_password_encrypted instead"
+        );
+        return Ok(Some(password));
+    }
+    if let Some(encrypted) = defaults.ssh_password_encrypted.as_deref() {
+        let label = format!(
+            "defaults.ssh_password_encrypted (used by target {})",
+            target.name
+        );
+        return decrypt_config_secret(config_key, encrypted, &label).map(Some);
+    }
+    if let Some(password) = defaults.ssh_password.clone() {
+        tracing::warn!(
+            target = %target.name,
+            "defaults.ssh_password is stored in plaintext; use ssh_password_encrypted instead"
+        );
+        return Ok(Some(password));
+    }
+    Ok(None)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfigSeverity {
+    Error,
+    Warning,
+}
+
+/// 反序列化之后的结构检查结果；`path` 指向具体字段（如 `targets[2].ssh`），供 UI 定位。
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ConfigDiagnostic {
+    pub path: String,
+    pub message: String,
+    pub severity: ConfigSeverity,
+}
+
+impl ConfigDiagnostic {
+    pub fn error(path: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            message: message.into(),
+            severity: ConfigSeverity::Error,
+        }
+    }
+
+    pub fn warning(path: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            message: message.into(),
+            severity: ConfigSeverity::Warning,
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == ConfigSeverity::Error
+    }
+}
+
+impl fmt::Display for ConfigDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+/// 目标、额外转发与模板的结构检查，console 与 octovalve-proxy 共用。
+pub fn validate_proxy_config(config: &ProxyConfig) -> Vec<ConfigDiagnostic> {
+    let mut diagnostics = Vec::new();
+    let mut names: HashMap<&str, usize> = HashMap::new();
+    // 本地端口 -> 第一次出现的字段路径。
+    let mut local_ports: HashMap<u16, String> = HashMap::new();
+    for (index, target) in config.targets.iter().enumerate() {
+        let path = format!("targets[{index}]");
+        let name = target.name.trim();
+        if name.is_empty() {
+            diagnostics.push(ConfigDiagnostic::error(
+                format!("{path}.name"),
+                "target name cannot be empty",
+            ));
+        } else if let Some(first) = names.get(name) {
+            diagnostics.push(ConfigDiagnostic::error(
+                format!("{path}.name"),
+                format!("duplicate target name {name:?} (first defined in targets[{first}])"),
+            ));
+        } else {
+            names.insert(name, index);
+        }
+        match target.ssh.as_deref().map(str::trim) {
+            None | Some("") => diagnostics.push(ConfigDiagnostic::error(
+                format!("{path}.ssh"),
+                "missing ssh destination (user@host)",
+            )),
+            Some(ssh) if parse_ssh_destination(ssh).is_none() => diagnostics.push(
+                ConfigDiagnostic::error(format!("{path}.ssh"), "ssh must be in the form user@host"),
+            ),
+            Some(_) => {}
+        }
+
+        match (target.ssh_host_key_policy, target.ssh_host_key.as_deref()) {
+            (HostKeyPolicy::Pinned, None) => diagnostics.push(ConfigDiagnostic::error(
+                format!("{path}.ssh_host_key"),
+                "ssh_host_key_policy = \"pinned\" requires ssh_host_key",
+            )),
+            (HostKeyPolicy::Pinned, Some(_)) | (_, None) => {}
+            (_, Some(_)) => diagnostics.push(ConfigDiagnostic::warning(
+                format!("{path}.ssh_host_key"),
+                "ssh_host_key is only used with ssh_host_key_policy = \"pinned\"",
+            )),
+        }
+
+        if let Some(banner) = target.banner.as_deref() {
+            let chars = banner.trim().chars().count();
+            if chars > MAX_BANNER_CHARS {
+                diagnostics.push(ConfigDiagnostic::warning(
+                    format!("{path}.banner"),
+                    format!("banner is {chars} chars; only the first {MAX_BANNER_CHARS} are kept"),
+                ));
+            }
+        }
+
+        let mut forward_names = HashSet::new();
+        for (forward_index, forward) in target.extra_forwards.iter().enumerate() {
+            let forward_path = format!("{path}.extra_forwards[{forward_index}]");
+            if !forward_names.insert(forward.name.trim()) {
+                diagnostics.push(ConfigDiagnostic::error(
+                    format!("{forward_path}.name"),
+                    format!("duplicate extra_forwards name {:?}", forward.name),
+                ));
+            }
+            let remote_port = forward
+                .remote_addr
+                .rsplit_once(':')
+                .and_then(|(host, port)| (!host.is_empty()).then_some(port))
+                .and_then(|port| port.parse::<u16>().ok());
+            if remote_port.is_none() {
+                diagnostics.push(ConfigDiagnostic::error(
+                    format!("{forward_path}.remote_addr"),
+                    "remote_addr must be host:port",
+                ));
+            }
+            // 端口被占用时转发会退回随机端口，重叠只是提示。
+            if let Some(port) = forward.local_port.filter(|port| *port != 0) {
+                let port_path = format!("{forward_path}.local_port");
+                match local_ports.get(&port) {
+                    Some(first) => diagnostics.push(ConfigDiagnostic::warning(
+                        port_path,
+                        format!("local_port {port} is also used by {first}"),
+                    )),
+                    None => {
+                        local_ports.insert(port, port_path);
+                    }
+                }
+            }
+        }
+    }
+
+    for (alias, target) in &config.aliases {
+        let path = format!("aliases.{alias}");
+        if alias.trim().is_empty() || alias.trim() != alias {
+            diagnostics.push(ConfigDiagnostic::error(
+                path,
+                format!("invalid alias {alias:?}: must be non-empty without surrounding spaces"),
+            ));
+        } else if names.contains_key(alias.as_str()) {
+            diagnostics.push(ConfigDiagnostic::error(
+                path,
+                format!("alias {alias:?} shadows a target with the same name"),
+            ));
+        } else if !names.contains_key(target.trim()) {
+            diagnostics.push(ConfigDiagnostic::error(
+                path,
+                format!("alias {alias:?} points to unknown target {target:?}"),
+            ));
+        }
+    }
+
+    if let Some(default_target) = config.default_target.as_deref() {
+        let default_target = default_target.trim();
+        let resolved = config
+            .aliases
+            .get(default_target)
+            .map(|target| target.trim())
+            .unwrap_or(default_target);
+        if !names.contains_key(resolved) {
+            diagnostics.push(ConfigDiagnostic::error(
+                "default_target",
+                format!("default_target {default_target:?} not found in targets"),
+            ));
+        }
+    }
+
+    for (index, template) in config.templates.iter().enumerate() {
+        let Some(glob) = template.target.as_deref() else {
+            continue;
+        };
+        let path = format!("templates[{index}].target");
+        if glob.trim().is_empty() {
+            diagnostics.push(ConfigDiagnostic::error(
+                path,
+                "target glob must not be empty (omit it to match all targets)",
+            ));
+        } else if let Some(diagnostic) = invalid_target_glob(path, glob) {
+            diagnostics.push(diagnostic);
+        }
+    }
+    for (index, schedule) in config.schedules.iter().enumerate() {
+        let path = format!("schedules[{index}].target");
+        if schedule.target.trim().is_empty() {
+            diagnostics.push(ConfigDiagnostic::error(
+                path,
+                "target glob must not be empty (use \"*\" for all targets)",
+            ));
+        } else if let Some(diagnostic) = invalid_target_glob(path, &schedule.target) {
+            diagnostics.push(diagnostic);
+        }
+    }
+    diagnostics
+}
+
+fn invalid_target_glob(path: String, glob: &str) -> Option<ConfigDiagnostic> {
+    (glob.contains(['[', ']', '{', '}']) || glob.trim() != glob).then(|| {
+        ConfigDiagnostic::error(
+            path,
+            format!("invalid target glob {glob:?}: only `*` and `?` are supported"),
+        )
+    })
+}
+
+/// broker 白名单条目的检查：空命令是错误，同一条目重复出现只是提示。
+pub fn validate_whitelist_entries(allowed: &[String], denied: &[String]) -> Vec<ConfigDiagnostic> {
+    let mut diagnostics = Vec::new();
+    for (section, entries) in [("allowed", allowed), ("denied", denied)] {
+        let mut seen = HashSet::new();
+        for (index, entry) in entries.iter().enumerate() {
+            let path = format!("whitelist.{section}[{index}]");
+            if entry.trim().is_empty() {
+                diagnostics.push(ConfigDiagnostic::error(path, "command must not be empty"));
+            } else if !seen.insert(entry.as_str()) {
+                diagnostics.push(ConfigDiagnostic::warning(
+                    path,
+                    format!("{section} rule {entry:?} is listed more than once"),
+                ));
+            }
+        }
+    }
+    diagnostics
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -91,8 +528,16 @@ mod tests {
             ssh: None,
             ssh_args: None,
             ssh_password: None,
+            ssh_password_encrypted: None,
             terminal_locale: Some("  ".to_string()),
             tty: false,
+            shell: TargetShell::Bash,
+            default_cwd: None,
+            queue_when_unreachable: false,
+            extra_forwards: Vec::new(),
+            ssh_host_key_policy: Default::default(),
+            ssh_host_key: None,
+            banner: None,
         };
         assert_eq!(
             resolve_terminal_locale(Some(&defaults), &target),
@@ -100,6 +545,137 @@ mod tests {
         );
     }
 
+    fn target(name: &str, ssh: Option<&str>) -> TargetConfig {
+        TargetConfig {
+            name: name.to_string(),
+            desc: name.to_string(),
+            ssh: ssh.map(str::to_string),
+            ssh_args: None,
+            ssh_password: None,
+            ssh_password_encrypted: None,
+            terminal_locale: None,
+            tty: false,
+            shell: TargetShell::Bash,
+            default_cwd: None,
+            queue_when_unreachable: false,
+            extra_forwards: Vec::new(),
+            ssh_host_key_policy: Default::default(),
+            ssh_host_key: None,
+            banner: None,
+        }
+    }
+
+    #[test]
+    fn truncates_long_banners_with_warning() {
+        let mut prod = target("prod", Some("devops@10.0.0.1"));
+        prod.banner = Some(format!("  {}  ", "界".repeat(MAX_BANNER_CHARS + 10)));
+        let mut dev = target("dev", Some("devops@10.0.0.2"));
+        dev.banner = Some("   ".to_string());
+
+        assert_eq!(
+            prod.banner().map(|banner| banner.chars().count()),
+            Some(MAX_BANNER_CHARS)
+        );
+        assert_eq!(dev.banner(), None);
+
+        let config = ProxyConfig {
+            default_target: None,
+            aliases: BTreeMap::new(),
+            defaults: None,
+            targets: vec![prod, dev],
+            terminal: None,
+            health: None,
+            server: None,
+            templates: Vec::new(),
+            schedules: Vec::new(),
+        };
+        let diagnostics = validate_proxy_config(&config);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].path, "targets[0].banner");
+        assert!(!diagnostics[0].is_error());
+    }
+
+    #[test]
+    fn reports_structured_config_diagnostics() {
+        let forward = |name: &str, remote_addr: &str, local_port| ExtraForward {
+            name: name.to_string(),
+            remote_addr: remote_addr.to_string(),
+            local_port,
+        };
+        let mut dev = target("dev", Some("devops@10.0.0.1"));
+        dev.extra_forwards = vec![forward("grafana", "127.0.0.1:3000", Some(3000))];
+        let mut duplicate = target("dev", Some("10.0.0.2"));
+        duplicate.extra_forwards = vec![
+            forward("grafana", "127.0.0.1:3000", Some(3000)),
+            forward("grafana", "127.0.0.1", None),
+        ];
+        let config = ProxyConfig {
+            default_target: Some("prod".to_string()),
+            aliases: BTreeMap::from([
+                ("database".to_string(), "db".to_string()),
+                ("dev".to_string(), "db".to_string()),
+                ("staging".to_string(), "stg".to_string()),
+            ]),
+            defaults: None,
+            targets: vec![dev, duplicate, target("db", None)],
+            terminal: None,
+            health: None,
+            server: None,
+            templates: vec![CommandTemplate {
+                name: "restart".to_string(),
+                description: String::new(),
+                target: Some("web-[12]".to_string()),
+                raw_command: "systemctl restart app".to_string(),
+                params: BTreeMap::new(),
+            }],
+            schedules: Vec::new(),
+        };
+        let diagnostics = validate_proxy_config(&config);
+        let paths: Vec<(&str, ConfigSeverity)> = diagnostics
+            .iter()
+            .map(|diagnostic| (diagnostic.path.as_str(), diagnostic.severity))
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                ("targets[1].name", ConfigSeverity::Error),
+                ("targets[1].ssh", ConfigSeverity::Error),
+                (
+                    "targets[1].extra_forwards[0].local_port",
+                    ConfigSeverity::Warning
+                ),
+                ("targets[1].extra_forwards[1].name", ConfigSeverity::Error),
+                (
+                    "targets[1].extra_forwards[1].remote_addr",
+                    ConfigSeverity::Error
+                ),
+                ("targets[2].ssh", ConfigSeverity::Error),
+                ("aliases.dev", ConfigSeverity::Error),
+                ("aliases.staging", ConfigSeverity::Error),
+                ("default_target", ConfigSeverity::Error),
+                ("templates[0].target", ConfigSeverity::Error),
+            ]
+        );
+        assert!(diagnostics[0].message.contains("targets[0]"));
+        assert!(diagnostics[2]
+            .message
+            .contains("targets[0].extra_forwards[0].local_port"));
+
+        let whitelist =
+            validate_whitelist_entries(&["ls".to_string(), " ".to_string(), "ls".to_string()], &[]);
+        assert_eq!(
+            whitelist
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            vec![
+                "whitelist.allowed[1]: command must not be empty",
+                "whitelist.allowed[2]: allowed rule \"ls\" is listed more than once",
+            ]
+        );
+        assert!(whitelist[0].is_error() && !whitelist[1].is_error());
+    }
+
     #[test]
     fn parse_ssh_destination_requires_user_and_host() {
         assert!(parse_ssh_destination("devops@127.0.0.1").is_some());
@@ -1,5 +1,6 @@
-use crate::{CommandMode, CommandStage, CommandStatus};
+use crate::{CommandMode, CommandStage, CommandStatus, OutputRef, OutputStream, RequestPriority};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SnapshotCommonFields {
@@ -10,11 +11,70 @@ pub struct SnapshotCommonFields {
     pub intent: String,
     pub mode: CommandMode,
     pub raw_command: String,
+    /// 操作员审批时改写了命令：raw_command 是实际执行的版本，这里保留客户端提交的原文。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original_command: Option<String>,
     pub pipeline: Vec<CommandStage>,
     pub cwd: Option<String>,
     pub timeout_ms: Option<u64>,
     pub max_output_bytes: Option<u64>,
     pub received_at_ms: u64,
+    #[serde(default)]
+    pub priority: RequestPriority,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub batch_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub intent_tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<BTreeMap<String, String>>,
+    /// 请求携带的环境变量，命中 `redaction.env_patterns` 的值替换为 `*** (N chars)`；明文只留在执行端。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub env_preview: Option<BTreeMap<String, String>>,
+    /// console 从命令文本中静态识别出的文件写入，仅用于审批展示。
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub file_changes: Vec<FileChange>,
+    /// 命中高危规则，批准时必须回显请求 id（审批接口的 `confirm` 字段）。
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub requires_confirmation: bool,
+    /// console 无法解析 raw_command（`on_unparseable = "manual"`），审批时只能看原始命令文本。
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub unparseable: bool,
+    /// 命中白名单时间窗但不在允许时段内，不会被自动批准。
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub outside_window: bool,
+    /// 策略解析后的执行账号；None 表示 SSH 登录用户。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run_as: Option<String>,
+    /// 命中 whitelist.denied、靠运维临时放行规则才进入队列的命令段。
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub temporary_rules: Vec<TemporaryRuleUse>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TemporaryRuleUse {
+    pub id: String,
+    pub rule: String,
+    /// 添加这条规则的操作员。
+    pub actor: String,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FileChangeAction {
+    Write,
+    Append,
+    Edit,
+}
+
+/// `preview` 最多保留前 4 KiB；`bytes` 为识别出的完整写入内容大小（已知时）。
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct FileChange {
+    pub path: String,
+    pub action: FileChangeAction,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preview: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bytes: Option<u64>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -40,6 +100,9 @@ pub struct ResultSnapshot {
     pub intent: String,
     pub mode: CommandMode,
     pub raw_command: String,
+    /// 操作员审批时改写了命令：raw_command 是实际执行的版本，这里保留客户端提交的原文。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original_command: Option<String>,
     pub pipeline: Vec<CommandStage>,
     pub cwd: Option<String>,
     pub peer: String,
@@ -47,6 +110,104 @@ pub struct ResultSnapshot {
     pub finished_at_ms: u64,
     pub stdout: Option<String>,
     pub stderr: Option<String>,
+    #[serde(default)]
+    pub stdout_ref: Option<OutputRef>,
+    #[serde(default)]
+    pub stderr_ref: Option<OutputRef>,
+    /// 被策略拒绝时命中的规则，例如 `whitelist.denied: rm`。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub denied_by_rule: Option<String>,
+    /// 人工批准/拒绝人；未填写的记为 `unknown`，自动批准为 `auto-approve`。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub approved_by: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub denied_by: Option<String>,
+    /// 从入队到批准/拒绝的毫秒数。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decision_latency_ms: Option<u64>,
+    /// 操作员拒绝时填写的原因。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deny_reason: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub batch_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub intent_tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<BTreeMap<String, String>>,
+    /// 输出不是合法 UTF-8，stdout/stderr 只是有损文本，完整字节需通过 output 接口下载。
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub binary_output: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub execution: Option<ExecutionContext>,
+    /// 不是经审批执行的请求时标明来源，例如交互式终端里记录下来的命令为 `terminal`。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecutionPath {
+    /// 每条命令单独起一个 ssh 进程。
+    Ssh,
+    /// 写入目标常驻的 PTY ssh 会话（`tty = true`）。
+    Pty,
+}
+
+/// 命令实际的执行方式，供审计追溯；环境变量中命中脱敏规则的值已替换为 `***`。
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ExecutionContext {
+    pub path: ExecutionPath,
+    /// 本地 ssh 的完整参数（不含远端命令）；PTY 模式下是会话建立时的参数。
+    pub ssh_argv: Vec<String>,
+    /// 交给远端 shell 的最终命令，包含 cd、环境变量前缀、locale 与 pidfile 包装。
+    pub remote_command: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+    /// 请求携带、以前缀形式传给远端的环境变量。
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    /// 设置在本地 ssh 进程上的环境变量（askpass、locale 等）。
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub local_env: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locale: Option<String>,
+    /// 使用 ControlMaster 复用连接时的 ControlPath。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub control_path: Option<String>,
+    pub tty: bool,
+    /// 经 sudo/doas 切换后的实际执行账号；None 表示 SSH 登录用户。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run_as: Option<String>,
+    /// 包在内层 bash 外面的沙箱 wrapper argv；None 表示未启用或命令被豁免。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sandbox: Option<Vec<String>>,
+    /// 本次执行前由 broker 创建了 cwd。
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub cwd_created: bool,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DetachedState {
+    Running,
+    Finished,
+}
+
+/// 以 detach 方式运行的任务；只保存在 console 内存中，结束后仍保留一段时间供查询。
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DetachedSnapshot {
+    #[serde(flatten)]
+    pub common: SnapshotCommonFields,
+    pub started_at_ms: u64,
+    pub state: DetachedState,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<CommandStatus>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finished_at_ms: Option<u64>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -55,6 +216,50 @@ pub struct ServiceSnapshot {
     pub running: Vec<RunningSnapshot>,
     pub history: Vec<ResultSnapshot>,
     pub last_result: Option<ResultSnapshot>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub detached: Vec<DetachedSnapshot>,
+    /// 目标配置中的 `banner`，由 console 在返回快照时填入。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub banner: Option<String>,
+    /// 各 `extra_forwards` 最近一次建立的结果，由 console 在返回快照时填入。
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub forward_diagnostics: Vec<ForwardDiagnostic>,
+}
+
+/// 某个额外转发最近一次 ensure 的结果；`at_ms` 为该次结果产生的时间。
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ForwardDiagnostic {
+    pub name: String,
+    pub remote_addr: String,
+    pub at_ms: u64,
+    #[serde(flatten)]
+    pub outcome: ForwardOutcome,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum ForwardOutcome {
+    /// `reused` 表示转发已存在，未重新起 ssh。
+    Established { latency_ms: u64, reused: bool },
+    /// `detail` 是按阶段整理过的说明（如 `ssh auth failed: ...`），不是原始 stderr。
+    Failed {
+        phase: SshFailurePhase,
+        detail: String,
+    },
+}
+
+/// ssh 失败所处的阶段，按 stderr 中的典型报错归类。
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SshFailurePhase {
+    /// 解析主机、建立 TCP 连接或等待就绪超时。
+    Connect,
+    /// 用户认证或主机指纹校验失败。
+    Auth,
+    /// 远端拒绝打开转发目标（端口未监听、被禁止转发等）。
+    RemoteBind,
+    /// 本地端口无法监听。
+    LocalBind,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -62,6 +267,7 @@ pub struct ServiceSnapshot {
 pub enum ServiceEvent {
     QueueUpdated(Vec<RequestSnapshot>),
     RunningUpdated(Vec<RunningSnapshot>),
+    DetachedUpdated(Vec<DetachedSnapshot>),
     ResultUpdated(ResultSnapshot),
     ConnectionsChanged,
 }
@@ -70,10 +276,50 @@ pub enum ServiceEvent {
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum ControlRequest {
     Snapshot,
-    Approve { id: String },
-    Deny { id: String },
-    Cancel { id: String },
+    Approve {
+        id: String,
+    },
+    Deny {
+        id: String,
+    },
+    Cancel {
+        id: String,
+    },
     Subscribe,
+    FetchOutput {
+        id: String,
+        stream: OutputStream,
+        offset: u64,
+        len: u64,
+    },
+    GetHistory {
+        #[serde(default)]
+        before_ms: Option<u64>,
+        #[serde(default)]
+        before_id: Option<String>,
+        limit: usize,
+    },
+}
+
+/// 历史分页结果；`next_before_ms` 为 None 表示没有更早的记录。
+/// 同一毫秒完成的记录按 id 排序，翻页时需把 `next_before_id` 一并传回。
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HistoryPage {
+    pub items: Vec<ResultSnapshot>,
+    pub next_before_ms: Option<u64>,
+    #[serde(default)]
+    pub next_before_id: Option<String>,
+}
+
+/// 完整输出文件中的一段，`data` 为 base64 编码的原始字节。
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OutputChunk {
+    pub id: String,
+    pub stream: OutputStream,
+    pub offset: u64,
+    pub total_bytes: u64,
+    pub data: String,
+    pub eof: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -83,4 +329,6 @@ pub enum ControlResponse {
     Ack { message: String },
     Error { message: String },
     Event { event: ServiceEvent },
+    Output { chunk: OutputChunk },
+    History { page: HistoryPage },
 }
@@ -1,6 +1,7 @@
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 
+pub mod compression;
 pub mod config;
 pub mod control;
 pub mod framing;
@@ -16,13 +17,24 @@ impl CommandStage {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub enum CommandMode {
+    #[default]
     Shell,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+// 仅影响待审批队列的排序，不影响白名单校验与审批流程。
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum RequestPriority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
 pub struct CommandRequest {
     pub id: String,
     pub client: String,
@@ -38,7 +50,113 @@ pub struct CommandRequest {
     pub timeout_ms: Option<u64>,
     #[serde(default)]
     pub max_output_bytes: Option<u64>,
+    /// 客户端声明的各段。console 对非 Windows 目标会重新解析 raw_command：解析成功时以解析结果替换并要求声明覆盖其中每个命令，
+    /// 解析失败时声明只参与拒绝检查，入队前被清空。pipeline 只用于白名单校验与展示，执行端始终把 raw_command 整体交给远端 shell，
+    /// 因此响应里只有整条命令的退出码，没有分段结果。
     pub pipeline: Vec<CommandStage>,
+    #[serde(default)]
+    pub priority: Option<RequestPriority>,
+    /// 同一次多目标下发（run_command_on_targets）的请求共享同一个 batch_id。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub batch_id: Option<String>,
+    /// 机器可读的意图分类（如 `read-only`、`deploy`），可被白名单 `auto_approve_tags` 引用。
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub intent_tags: Vec<String>,
+    /// 发起方上下文（如 `agent`、`session_id`、`tool_call_id`），只用于审计与展示，不参与策略判断。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<BTreeMap<String, String>>,
+    /// 批准后不等命令结束，立即返回 [`CommandStatus::Detached`]；命令继续在 console 上运行（不受超时限制），
+    /// 结果通过 console 的 detached 接口查询。console 重启后任务随之结束。
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub detach: bool,
+    /// 希望以哪个账号执行；只有命中 broker 策略 `run_as` / `run_as_allowed` 时才会生效，否则直接拒绝。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run_as: Option<String>,
+    /// 本端能解压的响应编码（见 [`compression`]）；为空时 console 始终回未压缩的 JSON。
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub compression: Vec<String>,
+    /// 等待期间希望收到 [`CommandProgress`] 帧；旧版 console 忽略该字段，只回最终响应。
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub progress: bool,
+    /// cwd 不存在时由 broker 先 `mkdir -p`；还需策略 `allow_create_cwd` 放行，否则按不存在报错。
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub create_cwd: bool,
+    /// 调用方声明命令只读：console 按 readonly_whitelist 校验，通过即自动批准，不符时直接拒绝并说明原因。
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub readonly: bool,
+    /// proxy 为每次工具调用生成的追踪 id；console 的日志、审计记录与响应都会带上它。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trace_id: Option<String>,
+}
+
+impl CommandRequest {
+    pub fn effective_priority(&self) -> RequestPriority {
+        self.priority.unwrap_or_default()
+    }
+
+    pub fn has_intent_tag(&self, tag: &str) -> bool {
+        self.intent_tags
+            .iter()
+            .any(|value| value.eq_ignore_ascii_case(tag))
+    }
+}
+
+/// 去掉首尾空白、转小写并去重，空标签丢弃。
+pub fn normalize_intent_tags(tags: Vec<String>) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::with_capacity(tags.len());
+    for tag in tags {
+        let tag = tag.trim().to_ascii_lowercase();
+        if !tag.is_empty() && !normalized.contains(&tag) {
+            normalized.push(tag);
+        }
+    }
+    normalized
+}
+
+/// console HTTP API 接受的追踪 id 请求头；响应会回写同名头。
+pub const TRACE_ID_HEADER: &str = "x-octovalve-trace-id";
+pub const MAX_TRACE_ID_BYTES: usize = 64;
+
+/// 只接受 `[A-Za-z0-9_-]` 组成的非空 id，避免把任意内容写进日志；不合法时返回 `None`，由调用方另行生成。
+pub fn normalize_trace_id(value: &str) -> Option<String> {
+    let value = value.trim();
+    let valid = !value.is_empty()
+        && value.len() <= MAX_TRACE_ID_BYTES
+        && value
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_');
+    valid.then(|| value.to_string())
+}
+
+pub const MAX_METADATA_KEYS: usize = 16;
+pub const MAX_METADATA_KEY_BYTES: usize = 64;
+pub const MAX_METADATA_VALUE_BYTES: usize = 256;
+
+/// proxy 构造请求和 console 接收请求时都会校验，超限直接拒绝而不是截断。
+pub fn validate_request_metadata(metadata: &BTreeMap<String, String>) -> Result<(), String> {
+    if metadata.len() > MAX_METADATA_KEYS {
+        return Err(format!(
+            "metadata has {} keys (max {MAX_METADATA_KEYS})",
+            metadata.len()
+        ));
+    }
+    for (key, value) in metadata {
+        if key.trim().is_empty() {
+            return Err("metadata keys cannot be empty".to_string());
+        }
+        if key.len() > MAX_METADATA_KEY_BYTES {
+            return Err(format!(
+                "metadata key {key:?} is longer than {MAX_METADATA_KEY_BYTES} bytes"
+            ));
+        }
+        if value.len() > MAX_METADATA_VALUE_BYTES {
+            return Err(format!(
+                "metadata value for {key:?} is {} bytes (max {MAX_METADATA_VALUE_BYTES})",
+                value.len()
+            ));
+        }
+    }
+    Ok(())
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -49,6 +167,23 @@ pub enum CommandStatus {
     Error,
     Cancelled,
     Completed,
+    /// 已批准并以 detach 方式启动；响应 id 即后续查询 / 终止用的句柄。
+    Detached,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+/// 指向完整输出捕获文件的引用；内联输出被截断时可按需分页拉取，并用 sha256 校验完整性。
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct OutputRef {
+    pub sha256: String,
+    pub total_bytes: u64,
+    pub path: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -63,9 +198,115 @@ pub struct CommandResponse {
     pub stderr: Option<String>,
     #[serde(default)]
     pub error: Option<String>,
+    #[serde(default)]
+    pub stdout_ref: Option<OutputRef>,
+    #[serde(default)]
+    pub stderr_ref: Option<OutputRef>,
+    /// 输出不是合法 UTF-8 时为 [`BINARY_OUTPUT_ENCODING`]，此时 stdout/stderr 为有损文本，原始字节在 *_base64 中。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stdout_encoding: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stderr_encoding: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stdout_base64: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stderr_base64: Option<String>,
+    /// 操作员在审批时改写了命令；实际执行的命令在 `executed_command`。
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub modified_by_operator: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub executed_command: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trace_id: Option<String>,
+}
+
+pub const BINARY_OUTPUT_ENCODING: &str = "binary";
+
+const PROGRESS_FRAME_PREFIX: &[u8] = br#"{"type":"progress""#;
+
+/// 请求带 `progress = true` 时，console 在最终响应之前按固定间隔回的进度帧，同一连接上可能有多帧。
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CommandProgress {
+    Progress {
+        id: String,
+        stage: ProgressStage,
+        /// 从 console 收到请求起经过的秒数。
+        elapsed_secs: u64,
+        /// 在待审批队列中的位置（从 1 开始）；尚未入队或已批准时为 None。
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        position: Option<usize>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ProgressStage {
+    Queued,
+    Executing,
+}
+
+impl CommandProgress {
+    /// 只按前缀识别，避免为了区分帧类型把每个大响应都解析两遍。
+    pub fn from_frame(frame: &[u8]) -> Option<Self> {
+        if !frame.starts_with(PROGRESS_FRAME_PREFIX) {
+            return None;
+        }
+        serde_json::from_slice(frame).ok()
+    }
+}
+
+/// 命令通道的认证帧。console 配置了 `control_token` 时，每条连接的首帧必须是 `Auth`；
+/// 校验失败时 console 回一帧 `AuthRejected` 后断开连接。
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CommandAuth {
+    Auth { token: String },
+    AuthRejected,
+}
+
+/// 命令通道上的取消帧，供 proxy 的 `cancel_command` 使用：单独建一条连接发送 `Cancel`，
+/// console 回一帧 `CancelResult`。只能取消同一 `client` 提交的请求。
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CommandCancel {
+    Cancel {
+        target: String,
+        id: String,
+        client: String,
+    },
+    CancelResult {
+        id: String,
+        outcome: CancelOutcome,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CancelOutcome {
+    /// 排队中的请求已移出队列，或已向执行中的命令发出取消。
+    Cancelled,
+    NotFound,
+    AlreadyFinished,
 }
 
 impl CommandResponse {
+    pub fn binary_output(&self) -> bool {
+        self.stdout_base64.is_some() || self.stderr_base64.is_some()
+    }
+
+    /// 标记为按操作员改写后的命令执行。
+    pub fn with_operator_edit(mut self, executed_command: impl Into<String>) -> Self {
+        self.modified_by_operator = true;
+        self.executed_command = Some(executed_command.into());
+        self
+    }
+
+    pub fn with_trace_id(mut self, trace_id: Option<String>) -> Self {
+        self.trace_id = trace_id;
+        self
+    }
+
     pub fn denied(id: impl Into<String>, message: impl Into<String>) -> Self {
         Self {
             id: id.into(),
@@ -74,6 +315,15 @@ impl CommandResponse {
             stdout: None,
             stderr: None,
             error: Some(message.into()),
+            stdout_ref: None,
+            stderr_ref: None,
+            stdout_encoding: None,
+            stderr_encoding: None,
+            stdout_base64: None,
+            stderr_base64: None,
+            modified_by_operator: false,
+            executed_command: None,
+            trace_id: None,
         }
     }
 
@@ -85,6 +335,35 @@ impl CommandResponse {
             stdout: None,
             stderr: None,
             error: Some(message.into()),
+            stdout_ref: None,
+            stderr_ref: None,
+            stdout_encoding: None,
+            stderr_encoding: None,
+            stdout_base64: None,
+            stderr_base64: None,
+            modified_by_operator: false,
+            executed_command: None,
+            trace_id: None,
+        }
+    }
+
+    pub fn detached(id: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            status: CommandStatus::Detached,
+            exit_code: None,
+            stdout: None,
+            stderr: None,
+            error: None,
+            stdout_ref: None,
+            stderr_ref: None,
+            stdout_encoding: None,
+            stderr_encoding: None,
+            stdout_base64: None,
+            stderr_base64: None,
+            modified_by_operator: false,
+            executed_command: None,
+            trace_id: None,
         }
     }
 
@@ -101,6 +380,15 @@ impl CommandResponse {
             stdout,
             stderr,
             error: Some("cancelled by operator".to_string()),
+            stdout_ref: None,
+            stderr_ref: None,
+            stdout_encoding: None,
+            stderr_encoding: None,
+            stdout_base64: None,
+            stderr_base64: None,
+            modified_by_operator: false,
+            executed_command: None,
+            trace_id: None,
         }
     }
 
@@ -117,6 +405,15 @@ impl CommandResponse {
             stdout,
             stderr,
             error: None,
+            stdout_ref: None,
+            stderr_ref: None,
+            stdout_encoding: None,
+            stderr_encoding: None,
+            stdout_base64: None,
+            stderr_base64: None,
+            modified_by_operator: false,
+            executed_command: None,
+            trace_id: None,
         }
     }
 }
@@ -132,7 +429,6 @@ mod tests {
             client: "octovalve-proxy".to_string(),
             target: "default".to_string(),
             intent: "list files".to_string(),
-            mode: CommandMode::Shell,
             raw_command: "echo hello".to_string(),
             cwd: Some("/tmp".to_string()),
             env: Some(BTreeMap::from([("LANG".to_string(), "C".to_string())])),
@@ -141,6 +437,15 @@ mod tests {
             pipeline: vec![CommandStage {
                 argv: vec!["echo".to_string(), "hello".to_string()],
             }],
+            priority: Some(RequestPriority::High),
+            batch_id: Some("batch-1".to_string()),
+            intent_tags: vec!["read-only".to_string()],
+            metadata: Some(BTreeMap::from([("agent".to_string(), "codex".to_string())])),
+            detach: true,
+            progress: true,
+            readonly: true,
+            trace_id: Some("0b6c9f5e-2c1d-4f7a-9a51-3f2e8d7c6b5a".to_string()),
+            ..Default::default()
         };
 
         let json = serde_json::to_string(&request).expect("serialize");
@@ -148,6 +453,105 @@ mod tests {
         assert_eq!(request, decoded);
     }
 
+    #[test]
+    fn normalizes_intent_tags() {
+        let tags = normalize_intent_tags(vec![
+            " Read-Only ".to_string(),
+            "read-only".to_string(),
+            String::new(),
+            "deploy".to_string(),
+        ]);
+        assert_eq!(tags, ["read-only", "deploy"]);
+    }
+
+    #[test]
+    fn normalizes_trace_ids() {
+        assert_eq!(
+            normalize_trace_id(" 0b6c9f5e-2c1d_4f7a ").as_deref(),
+            Some("0b6c9f5e-2c1d_4f7a")
+        );
+        assert_eq!(normalize_trace_id(""), None);
+        assert_eq!(normalize_trace_id("a b"), None);
+        assert_eq!(normalize_trace_id("id\nforged=1"), None);
+        assert!(normalize_trace_id(&"a".repeat(MAX_TRACE_ID_BYTES)).is_some());
+        assert_eq!(
+            normalize_trace_id(&"a".repeat(MAX_TRACE_ID_BYTES + 1)),
+            None
+        );
+    }
+
+    #[test]
+    fn validates_metadata_limits() {
+        let ok = BTreeMap::from([
+            ("agent".to_string(), "codex".to_string()),
+            (
+                "session_id".to_string(),
+                "s".repeat(MAX_METADATA_VALUE_BYTES),
+            ),
+        ]);
+        assert!(validate_request_metadata(&ok).is_ok());
+
+        let long_value = BTreeMap::from([(
+            "tool_call_id".to_string(),
+            "x".repeat(MAX_METADATA_VALUE_BYTES + 1),
+        )]);
+        assert!(validate_request_metadata(&long_value)
+            .unwrap_err()
+            .contains("tool_call_id"));
+
+        let too_many: BTreeMap<String, String> = (0..=MAX_METADATA_KEYS)
+            .map(|index| (format!("k{index}"), String::new()))
+            .collect();
+        assert!(validate_request_metadata(&too_many).is_err());
+        let empty_key = BTreeMap::from([(" ".to_string(), "v".to_string())]);
+        assert!(validate_request_metadata(&empty_key).is_err());
+    }
+
+    #[test]
+    fn command_auth_wire_format() {
+        let auth = CommandAuth::Auth {
+            token: "secret".to_string(),
+        };
+        assert_eq!(
+            serde_json::to_string(&auth).expect("serialize"),
+            r#"{"type":"auth","token":"secret"}"#
+        );
+        let rejected: CommandAuth =
+            serde_json::from_str(r#"{"type":"auth_rejected"}"#).expect("deserialize");
+        assert_eq!(rejected, CommandAuth::AuthRejected);
+        assert!(serde_json::from_str::<CommandResponse>(r#"{"type":"auth_rejected"}"#).is_err());
+    }
+
+    #[test]
+    fn command_cancel_wire_format() {
+        let cancel = CommandCancel::Cancel {
+            target: "dev".to_string(),
+            id: "req-1".to_string(),
+            client: "agent".to_string(),
+        };
+        let json = serde_json::to_string(&cancel).expect("serialize");
+        assert_eq!(
+            json,
+            r#"{"type":"cancel","target":"dev","id":"req-1","client":"agent"}"#
+        );
+        assert!(serde_json::from_str::<CommandAuth>(&json).is_err());
+        assert!(serde_json::from_str::<CommandRequest>(&json).is_err());
+        let result: CommandCancel = serde_json::from_str(
+            r#"{"type":"cancel_result","id":"req-1","outcome":"already_finished"}"#,
+        )
+        .expect("deserialize");
+        assert_eq!(
+            result,
+            CommandCancel::CancelResult {
+                id: "req-1".to_string(),
+                outcome: CancelOutcome::AlreadyFinished,
+            }
+        );
+        let response = serde_json::to_string(&CommandResponse::error("invalid", "invalid request"))
+            .expect("serialize");
+        assert!(serde_json::from_str::<CommandCancel>(&response).is_err());
+    }
+
     #[test]
     fn command_response_roundtrip() {
         let response =
@@ -156,4 +560,41 @@ mod tests {
         let decoded: CommandResponse = serde_json::from_str(&json).expect("deserialize");
         assert_eq!(response, decoded);
     }
+
+    #[test]
+    fn command_response_output_refs_roundtrip() {
+        let mut response = CommandResponse::completed("req-3", 0, Some("ok".to_string()), None);
+        response.stdout_ref = Some(OutputRef {
+            sha256: "ab".repeat(32),
+            total_bytes: 4096,
+            path: "/tmp/req-3.stdout".to_string(),
+        });
+        let json = serde_json::to_string(&response).expect("serialize");
+        let decoded: CommandResponse = serde_json::from_str(&json).expect("deserialize");
+        assert_eq!(response, decoded);
+    }
+
+    #[test]
+    fn command_response_without_refs_decodes() {
+        let json = r#"{"id":"req-4","status":"completed","exit_code":0,"stdout":"ok"}"#;
+        let decoded: CommandResponse = serde_json::from_str(json).expect("deserialize");
+        assert!(decoded.stdout_ref.is_none());
+        assert!(decoded.stderr_ref.is_none());
+    }
+
+    #[test]
+    fn progress_frames_are_told_apart_from_responses() {
+        let progress = CommandProgress::Progress {
+            id: "req-5".to_string(),
+            stage: ProgressStage::Queued,
+            elapsed_secs: 45,
+            position: Some(2),
+        };
+        let frame = serde_json::to_vec(&progress).expect("serialize");
+        assert_eq!(CommandProgress::from_frame(&frame), Some(progress));
+
+        let response = CommandResponse::completed("req-5", 0, None, None);
+        let frame = serde_json::to_vec(&response).expect("serialize");
+        assert_eq!(CommandProgress::from_frame(&frame), None);
+    }
 }
@@ -1,3 +1,6 @@
+pub mod lines;
 pub mod path;
 pub mod process;
+pub mod secret;
 pub mod ssh;
+pub mod terminal;
@@ -0,0 +1,255 @@
+//! 换行分隔 JSON 通道（ACP stdio、codex app-server）的行分帧。
+//!
+//! `read_line` 没有长度上限，对端写出超长或不以换行结尾的数据时会一直缓冲；这里限制单行长度，
+//! 超限的行只计数丢弃、不再保存，读到换行后以 [`LineError::TooLong`] 报告一次并继续读下一行。
+
+use std::fmt;
+use std::io;
+
+use tokio::io::{AsyncBufRead, AsyncBufReadExt};
+
+/// 单行默认上限；ACP 消息可能内嵌 base64 图片，留足余量。
+pub const MAX_JSON_LINE_BYTES: usize = 32 * 1024 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LineError {
+    TooLong { limit: usize },
+    InvalidUtf8,
+}
+
+impl fmt::Display for LineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LineError::TooLong { limit } => write!(f, "line exceeds {limit} bytes"),
+            LineError::InvalidUtf8 => f.write_str("line is not valid UTF-8"),
+        }
+    }
+}
+
+impl std::error::Error for LineError {}
+
+/// 与 IO 无关的分帧状态，按任意边界切分的输入都能重新拼成完整的行。
+///
+/// 上限不含行尾的 `\n`/`\r\n`；缓冲区最多保存 `max_line_bytes + 1` 字节（多出的是可能的 `\r`）。
+#[derive(Debug)]
+pub struct LineDecoder {
+    max_line_bytes: usize,
+    line: Vec<u8>,
+    discarding: bool,
+}
+
+impl LineDecoder {
+    pub fn new(max_line_bytes: usize) -> Self {
+        Self {
+            max_line_bytes,
+            line: Vec::new(),
+            discarding: false,
+        }
+    }
+
+    /// 消费 `chunk` 直到（含）第一个换行，返回消费的字节数以及完成的一行。
+    pub fn decode(&mut self, chunk: &[u8]) -> (usize, Option<Result<String, LineError>>) {
+        let newline = chunk.iter().position(|byte| *byte == b'\n');
+        let piece = &chunk[..newline.unwrap_or(chunk.len())];
+        if !self.discarding {
+            if self.line.len() + piece.len() > self.max_line_bytes + 1 {
+                self.discarding = true;
+                self.line = Vec::new();
+            } else {
+                self.line.extend_from_slice(piece);
+            }
+        }
+        match newline {
+            Some(index) => (index + 1, Some(self.take_line())),
+            None => (chunk.len(), None),
+        }
+    }
+
+    /// 输入结束时返回未以换行结尾的最后一行。
+    pub fn finish(&mut self) -> Option<Result<String, LineError>> {
+        if self.line.is_empty() && !self.discarding {
+            return None;
+        }
+        Some(self.take_line())
+    }
+
+    fn take_line(&mut self) -> Result<String, LineError> {
+        let mut line = std::mem::take(&mut self.line);
+        if std::mem::take(&mut self.discarding) {
+            return Err(LineError::TooLong {
+                limit: self.max_line_bytes,
+            });
+        }
+        if line.last() == Some(&b'\r') {
+            line.pop();
+        }
+        if line.len() > self.max_line_bytes {
+            return Err(LineError::TooLong {
+                limit: self.max_line_bytes,
+            });
+        }
+        String::from_utf8(line).map_err(|_| LineError::InvalidUtf8)
+    }
+
+    #[cfg(test)]
+    fn buffered_len(&self) -> usize {
+        self.line.len()
+    }
+}
+
+/// 在 [`AsyncBufRead`] 上按行读取；IO 错误走外层 `Err`，单行的格式问题走内层 `Err`，调用方可跳过该行继续。
+pub struct LineReader<R> {
+    reader: R,
+    decoder: LineDecoder,
+}
+
+impl<R: AsyncBufRead + Unpin> LineReader<R> {
+    pub fn new(reader: R, max_line_bytes: usize) -> Self {
+        Self {
+            reader,
+            decoder: LineDecoder::new(max_line_bytes),
+        }
+    }
+
+    /// 读到 EOF 且没有剩余数据时返回 `Ok(None)`。
+    pub async fn next_line(&mut self) -> io::Result<Option<Result<String, LineError>>> {
+        loop {
+            let chunk = self.reader.fill_buf().await?;
+            if chunk.is_empty() {
+                return Ok(self.decoder.finish());
+            }
+            let (consumed, line) = self.decoder.decode(chunk);
+            self.reader.consume(consumed);
+            if line.is_some() {
+                return Ok(line);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn decode_all(decoder: &mut LineDecoder, chunks: &[&[u8]]) -> Vec<Result<String, LineError>> {
+        let mut lines = Vec::new();
+        for chunk in chunks {
+            let mut rest = *chunk;
+            while !rest.is_empty() {
+                let (consumed, line) = decoder.decode(rest);
+                rest = &rest[consumed..];
+                lines.extend(line);
+            }
+        }
+        lines.extend(decoder.finish());
+        lines
+    }
+
+    #[test]
+    fn enforces_limit_at_boundary() {
+        let mut decoder = LineDecoder::new(4);
+        assert_eq!(
+            decode_all(&mut decoder, &[b"abcd\nabcde\nok\n"]),
+            vec![
+                Ok("abcd".to_string()),
+                Err(LineError::TooLong { limit: 4 }),
+                Ok("ok".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn strips_crlf_without_counting_it() {
+        let mut decoder = LineDecoder::new(4);
+        assert_eq!(
+            decode_all(&mut decoder, &[b"abcd\r\n", b"ab\r", b"\n", b"abcde\r\n"]),
+            vec![
+                Ok("abcd".to_string()),
+                Ok("ab".to_string()),
+                Err(LineError::TooLong { limit: 4 }),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_bad_lines_and_keeps_reading() {
+        let mut decoder = LineDecoder::new(8);
+        assert_eq!(
+            decode_all(
+                &mut decoder,
+                &[b"{\"a\":1}\n\xff\xfe\nxxxxxxxxxxxx", b"xxxx\n{\"b\":2}"]
+            ),
+            vec![
+                Ok("{\"a\":1}".to_string()),
+                Err(LineError::InvalidUtf8),
+                Err(LineError::TooLong { limit: 8 }),
+                Ok("{\"b\":2}".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn reassembles_split_reads() {
+        let mut decoder = LineDecoder::new(64);
+        let mut lines = decode_all(&mut decoder, &[b"{\"jsonrpc\":", b"\"2.0\"", b"}\n"]);
+        assert_eq!(lines.pop(), Some(Ok("{\"jsonrpc\":\"2.0\"}".to_string())));
+        assert!(lines.is_empty());
+    }
+
+    #[tokio::test]
+    async fn reader_reports_too_long_lines_then_continues() {
+        let input: &[u8] = b"first\r\n0123456789\nlast";
+        let mut reader = LineReader::new(tokio::io::BufReader::with_capacity(3, input), 6);
+        assert_eq!(
+            reader.next_line().await.unwrap(),
+            Some(Ok("first".to_string()))
+        );
+        assert_eq!(
+            reader.next_line().await.unwrap(),
+            Some(Err(LineError::TooLong { limit: 6 }))
+        );
+        assert_eq!(
+            reader.next_line().await.unwrap(),
+            Some(Ok("last".to_string()))
+        );
+        assert_eq!(reader.next_line().await.unwrap(), None);
+    }
+
+    proptest! {
+        // 任意字节流、任意切分：不 panic，缓冲不超过上限，结果与一次性输入一致。
+        #[test]
+        fn arbitrary_streams_stay_bounded(
+            data in proptest::collection::vec(any::<u8>(), 0..2048),
+            splits in proptest::collection::vec(1usize..64, 1..32),
+            limit in 0usize..48,
+        ) {
+            let whole = decode_all(&mut LineDecoder::new(limit), &[data.as_slice()]);
+
+            let mut decoder = LineDecoder::new(limit);
+            let mut lines = Vec::new();
+            let mut rest = data.as_slice();
+            for size in splits.iter().cycle() {
+                if rest.is_empty() {
+                    break;
+                }
+                let (chunk, tail) = rest.split_at((*size).min(rest.len()));
+                rest = tail;
+                let mut chunk = chunk;
+                while !chunk.is_empty() {
+                    let (consumed, line) = decoder.decode(chunk);
+                    prop_assert!(consumed > 0);
+                    prop_assert!(decoder.buffered_len() <= limit + 1);
+                    chunk = &chunk[consumed..];
+                    lines.extend(line);
+                }
+            }
+            lines.extend(decoder.finish());
+
+            for line in lines.iter().flatten() {
+                prop_assert!(line.len() <= limit);
+            }
+            prop_assert_eq!(lines, whole);
+        }
+    }
+}
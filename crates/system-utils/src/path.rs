@@ -1,15 +1,143 @@
 use std::path::PathBuf;
 
+/// 展开 `~`、`~/rest`，unix 上还支持 `~user`、`~user/rest`；HOME 取自环境变量。
+/// 无法确定 home（HOME 未设置或为空、用户不存在）时原样返回。
 pub fn expand_tilde(path: &str) -> PathBuf {
-    if path == "~" {
-        if let Ok(home) = std::env::var("HOME") {
-            return PathBuf::from(home);
+    expand_tilde_with(path, env_home_dir)
+}
+
+/// 与 [`expand_tilde`] 相同，但当前用户的 home 由调用方提供（例如 Tauri 的 app handle）。
+pub fn expand_tilde_with(path: &str, home: impl FnOnce() -> Option<PathBuf>) -> PathBuf {
+    expand(path, home, user_home_dir)
+}
+
+fn expand(
+    path: &str,
+    home: impl FnOnce() -> Option<PathBuf>,
+    user_home: impl FnOnce(&str) -> Option<PathBuf>,
+) -> PathBuf {
+    let Some(rest) = path.strip_prefix('~') else {
+        return PathBuf::from(path);
+    };
+    let (user, tail) = match rest.find('/') {
+        Some(index) => (&rest[..index], &rest[index..]),
+        None => (rest, ""),
+    };
+    let base = if user.is_empty() {
+        home()
+    } else {
+        user_home(user)
+    };
+    let Some(base) = base.filter(|base| !base.as_os_str().is_empty()) else {
+        return PathBuf::from(path);
+    };
+    // `~/`、`~//a` 这类多余的分隔符不能让 join 变成绝对路径覆盖 home。
+    let tail = tail.trim_start_matches('/');
+    if tail.is_empty() {
+        base
+    } else {
+        base.join(tail)
+    }
+}
+
+fn env_home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from)
+}
+
+#[cfg(unix)]
+fn user_home_dir(user: &str) -> Option<PathBuf> {
+    use std::ffi::{CStr, CString, OsStr};
+    use std::os::unix::ffi::OsStrExt;
+
+    let name = CString::new(user).ok()?;
+    let mut buf = vec![0 as libc::c_char; 4096];
+    loop {
+        let mut passwd: libc::passwd = unsafe { std::mem::zeroed() };
+        let mut result: *mut libc::passwd = std::ptr::null_mut();
+        let code = unsafe {
+            libc::getpwnam_r(
+                name.as_ptr(),
+                &mut passwd,
+                buf.as_mut_ptr(),
+                buf.len(),
+                &mut result,
+            )
+        };
+        if code == libc::ERANGE && buf.len() < 1 << 20 {
+            buf.resize(buf.len() * 2, 0);
+            continue;
+        }
+        if code != 0 || result.is_null() || passwd.pw_dir.is_null() {
+            return None;
         }
+        let dir = unsafe { CStr::from_ptr(passwd.pw_dir) };
+        return Some(PathBuf::from(OsStr::from_bytes(dir.to_bytes())));
+    }
+}
+
+#[cfg(not(unix))]
+fn user_home_dir(_user: &str) -> Option<PathBuf> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fake_user(user: &str) -> Option<PathBuf> {
+        (user == "alice").then(|| PathBuf::from("/users/alice"))
+    }
+
+    fn run(path: &str, home: Option<&str>) -> PathBuf {
+        expand(path, || home.map(PathBuf::from), fake_user)
     }
-    if let Some(rest) = path.strip_prefix("~/") {
-        if let Ok(home) = std::env::var("HOME") {
-            return PathBuf::from(home).join(rest);
+
+    #[test]
+    fn expands_tilde_matrix() {
+        let home = Some("/home/me");
+        let cases = [
+            ("~", home, "/home/me"),
+            ("~/", home, "/home/me"),
+            ("~/a/b", home, "/home/me/a/b"),
+            ("~/a/b/", home, "/home/me/a/b/"),
+            ("~//a", home, "/home/me/a"),
+            ("~alice", home, "/users/alice"),
+            ("~alice/", home, "/users/alice"),
+            ("~alice/x", home, "/users/alice/x"),
+            ("~bob/x", home, "~bob/x"),
+            ("~", None, "~"),
+            ("~/a", None, "~/a"),
+            ("~/a", Some(""), "~/a"),
+            ("~alice/x", None, "/users/alice/x"),
+            ("/abs/~/x", home, "/abs/~/x"),
+            ("rel/path", home, "rel/path"),
+            ("", home, ""),
+        ];
+        for (input, home, expected) in cases {
+            assert_eq!(run(input, home), PathBuf::from(expected), "input {input:?}");
         }
     }
-    PathBuf::from(path)
+
+    #[test]
+    fn injected_home_is_used_and_not_called_for_plain_paths() {
+        let expanded = expand_tilde_with("~/x", || Some(PathBuf::from("/app/home")));
+        assert_eq!(expanded, PathBuf::from("/app/home/x"));
+        let plain = expand_tilde_with("/etc", || panic!("home should not be resolved"));
+        assert_eq!(plain, PathBuf::from("/etc"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn resolves_real_and_unknown_users() {
+        let root = user_home_dir("root").expect("root home");
+        assert!(root.is_absolute());
+        assert_eq!(expand_tilde("~root/x"), root.join("x"));
+        assert!(user_home_dir("octovalve-no-such-user").is_none());
+        assert_eq!(
+            expand_tilde("~octovalve-no-such-user/x"),
+            PathBuf::from("~octovalve-no-such-user/x")
+        );
+    }
 }
@@ -0,0 +1,121 @@
+use anyhow::Context;
+use argon2::Argon2;
+use base64::engine::general_purpose::STANDARD as BASE64_ENGINE;
+use base64::Engine;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use std::path::Path;
+
+pub const CONFIG_KEY_ENV: &str = "OCTOVALVE_CONFIG_KEY";
+
+// 密文格式：v1:<base64(salt || nonce || ciphertext)>，key 由 Argon2id(passphrase, salt) 派生。
+const SECRET_PREFIX: &str = "v1:";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+/// 比较 token 时不按前缀提前返回，避免泄露 token 长度以外的信息。
+pub fn constant_time_eq(left: &[u8], right: &[u8]) -> bool {
+    if left.len() != right.len() {
+        return false;
+    }
+    left.iter()
+        .zip(right)
+        .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+        == 0
+}
+
+#[derive(Clone)]
+pub struct ConfigKey {
+    passphrase: String,
+}
+
+impl std::fmt::Debug for ConfigKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ConfigKey(..)")
+    }
+}
+
+/// 解密配置中的密文字段；`label` 用于错误信息，例如 `target dev ssh_password_encrypted`。
+pub fn decrypt_config_secret(
+    key: Option<&ConfigKey>,
+    value: &str,
+    label: &str,
+) -> anyhow::Result<String> {
+    let Some(key) = key else {
+        anyhow::bail!(
+            "{label} is set but no config key is available; set {CONFIG_KEY_ENV} or pass --config-key-file"
+        );
+    };
+    key.decrypt(value).with_context(|| {
+        format!("failed to decrypt {label}; check {CONFIG_KEY_ENV}/--config-key-file or re-encrypt with octovalve-encrypt-secret")
+    })
+}
+
+impl ConfigKey {
+    pub fn new(passphrase: impl Into<String>) -> anyhow::Result<Self> {
+        let passphrase = passphrase.into();
+        if passphrase.trim().is_empty() {
+            anyhow::bail!("config key passphrase cannot be empty");
+        }
+        Ok(Self { passphrase })
+    }
+
+    /// `--config-key-file` 优先，其次 `OCTOVALVE_CONFIG_KEY`；都没有时返回 None。
+    pub fn resolve(key_file: Option<&Path>) -> anyhow::Result<Option<Self>> {
+        if let Some(path) = key_file {
+            let raw = std::fs::read_to_string(path)
+                .with_context(|| format!("failed to read config key file {}", path.display()))?;
+            let passphrase = raw.trim_end_matches(['\r', '\n']);
+            return Self::new(passphrase)
+                .with_context(|| format!("config key file {} is empty", path.display()))
+                .map(Some);
+        }
+        match std::env::var(CONFIG_KEY_ENV) {
+            Ok(value) if !value.trim().is_empty() => Self::new(value).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    pub fn encrypt(&self, plaintext: &str) -> anyhow::Result<String> {
+        let mut salt = [0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        let cipher = self.cipher(&salt)?;
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = cipher
+            .encrypt(&nonce, plaintext.as_bytes())
+            .map_err(|_| anyhow::anyhow!("failed to encrypt secret"))?;
+        let mut payload = Vec::with_capacity(SALT_LEN + NONCE_LEN + ciphertext.len());
+        payload.extend_from_slice(&salt);
+        payload.extend_from_slice(&nonce);
+        payload.extend_from_slice(&ciphertext);
+        Ok(format!("{SECRET_PREFIX}{}", BASE64_ENGINE.encode(payload)))
+    }
+
+    pub fn decrypt(&self, value: &str) -> anyhow::Result<String> {
+        let encoded = value.trim().strip_prefix(SECRET_PREFIX).ok_or_else(|| {
+            anyhow::anyhow!("unsupported secret format (expected {SECRET_PREFIX}...)")
+        })?;
+        let payload = BASE64_ENGINE
+            .decode(encoded)
+            .context("secret is not valid base64")?;
+        if payload.len() <= SALT_LEN + NONCE_LEN {
+            anyhow::bail!("secret is truncated");
+        }
+        let (salt, rest) = payload.split_at(SALT_LEN);
+        let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+        let cipher = self.cipher(salt)?;
+        let plaintext = cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| anyhow::anyhow!("wrong config key or corrupted secret"))?;
+        String::from_utf8(plaintext).context("decrypted secret is not valid UTF-8")
+    }
+
+    fn cipher(&self, salt: &[u8]) -> anyhow::Result<ChaCha20Poly1305> {
+        let mut key = [0u8; 32];
+        Argon2::default()
+            .hash_password_into(self.passphrase.as_bytes(), salt, &mut key)
+            .map_err(|err| anyhow::anyhow!("failed to derive config key: {err}"))?;
+        Ok(ChaCha20Poly1305::new(Key::from_slice(&key)))
+    }
+}
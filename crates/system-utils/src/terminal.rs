@@ -0,0 +1,164 @@
+//! 终端输出整理：把进度条类输出（`\r` 覆盖、ANSI 光标移动与擦除）还原成每行的最终状态。
+//!
+//! 只模拟单行内的光标，`\n` 之间视为一行；上下移动光标、清屏等跨行序列直接丢弃，颜色等 SGR 序列同样去掉。
+
+use std::iter::Peekable;
+use std::str::Chars;
+
+const ESC: char = '\u{1b}';
+const BEL: char = '\u{7}';
+const BACKSPACE: char = '\u{8}';
+// 光标右移/定位的上限，避免异常参数撑大行缓冲。
+const MAX_CURSOR_COLUMN: usize = 4096;
+
+/// 按行折叠 `\r` 覆盖并去掉控制序列，保留 `\n` 与 `\t`。
+pub fn normalize_terminal_output(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    let mut line = LineBuffer::default();
+    let mut chars = input.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\n' => {
+                line.flush_into(&mut out);
+                out.push('\n');
+            }
+            '\r' => line.cursor = 0,
+            BACKSPACE => line.cursor = line.cursor.saturating_sub(1),
+            ESC => {
+                if let Some((params, action)) = read_escape(&mut chars) {
+                    line.apply_csi(&params, action);
+                }
+            }
+            '\t' => line.put('\t'),
+            ch if ch.is_control() => {}
+            ch => line.put(ch),
+        }
+    }
+    line.flush_into(&mut out);
+    out
+}
+
+#[derive(Default)]
+struct LineBuffer {
+    cells: Vec<char>,
+    cursor: usize,
+}
+
+impl LineBuffer {
+    fn put(&mut self, ch: char) {
+        if self.cursor < self.cells.len() {
+            self.cells[self.cursor] = ch;
+        } else {
+            self.cells.resize(self.cursor, ' ');
+            self.cells.push(ch);
+        }
+        self.cursor += 1;
+    }
+
+    fn apply_csi(&mut self, params: &str, action: char) {
+        // `?` 开头的私有序列（如隐藏光标）与行内位置无关。
+        if params.starts_with('?') {
+            return;
+        }
+        let first = params
+            .split(';')
+            .next()
+            .and_then(|value| value.parse::<usize>().ok());
+        match action {
+            'C' => {
+                self.cursor = self
+                    .cursor
+                    .saturating_add(first.unwrap_or(1).max(1))
+                    .min(MAX_CURSOR_COLUMN)
+            }
+            'D' => self.cursor = self.cursor.saturating_sub(first.unwrap_or(1).max(1)),
+            'G' => self.cursor = first.unwrap_or(1).saturating_sub(1).min(MAX_CURSOR_COLUMN),
+            'K' => match first.unwrap_or(0) {
+                0 => self.cells.truncate(self.cursor),
+                1 => {
+                    let end = (self.cursor + 1).min(self.cells.len());
+                    self.cells[..end].fill(' ');
+                }
+                _ => self.cells.clear(),
+            },
+            _ => {}
+        }
+    }
+
+    fn flush_into(&mut self, out: &mut String) {
+        out.extend(self.cells.drain(..));
+        self.cursor = 0;
+    }
+}
+
+/// 消费 ESC 之后的序列；只有 CSI 返回 (参数, 结束字符)，其余（OSC、字符集切换等）直接丢弃。
+fn read_escape(chars: &mut Peekable<Chars<'_>>) -> Option<(String, char)> {
+    match chars.next()? {
+        '[' => {
+            let mut params = String::new();
+            for ch in chars.by_ref() {
+                if ('\u{40}'..='\u{7e}').contains(&ch) {
+                    return Some((params, ch));
+                }
+                params.push(ch);
+            }
+            None
+        }
+        ']' => {
+            // OSC 以 BEL 或 ST（ESC \）结束。
+            while let Some(ch) = chars.next() {
+                if ch == BEL {
+                    break;
+                }
+                if ch == ESC && chars.peek() == Some(&'\\') {
+                    chars.next();
+                    break;
+                }
+            }
+            None
+        }
+        ch if ('\u{20}'..='\u{2f}').contains(&ch) => {
+            while chars
+                .peek()
+                .is_some_and(|next| ('\u{20}'..='\u{2f}').contains(next))
+            {
+                chars.next();
+            }
+            chars.next();
+            None
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collapses_carriage_return_progress() {
+        let input = "downloading\n  0%\r 42%\r100%\ndone\r\n";
+        assert_eq!(
+            normalize_terminal_output(input),
+            "downloading\n100%\ndone\n"
+        );
+        // 较短的内容覆盖较长的内容时，未被覆盖的尾部保留，与终端显示一致。
+        assert_eq!(normalize_terminal_output("abcdef\rxy"), "xycdef");
+    }
+
+    #[test]
+    fn applies_cursor_and_erase_sequences() {
+        let input = "\u{1b}[?25l[===>   ] 40%\r\u{1b}[2K[=======] 100%\u{1b}[?25h\n";
+        assert_eq!(normalize_terminal_output(input), "[=======] 100%\n");
+        assert_eq!(normalize_terminal_output("abc\u{1b}[2Dz\u{1b}[K"), "az");
+        assert_eq!(normalize_terminal_output("abc\u{1b}[1Gx"), "xbc");
+        assert_eq!(normalize_terminal_output("ab\u{8}\u{8}cd"), "cd");
+    }
+
+    #[test]
+    fn strips_colors_and_osc_sequences() {
+        let input = "\u{1b}]0;title\u{7}\u{1b}[1;32mok\u{1b}[0m \u{1b}(Bdone\u{1b}]8;;x\u{1b}\\";
+        assert_eq!(normalize_terminal_output(input), "ok done");
+        assert_eq!(normalize_terminal_output("a\tb\u{0}c"), "a\tbc");
+    }
+}